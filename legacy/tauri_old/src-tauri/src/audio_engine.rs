@@ -0,0 +1,674 @@
+//! Playing pre-service playlists and sound cues
+//!
+//! The webview's audio stops when its page is busy, can't pick the device it
+//! plays on, and leaves a gap between tracks. The engine plays through the
+//! system's audio (cpal, by way of rodio) on a thread of its own instead,
+//! since an output stream can't be moved between threads; commands reach it
+//! over a channel.
+//!
+//! A playlist plays its tracks in order, each at its own gain, and loops
+//! when asked. Without a crossfade the next track is queued behind the one
+//! playing, so there's no gap between them; with one, it starts that long
+//! before the end of the track playing, fading in as that fades out. Tracks
+//! whose length can't be read start when the one before ends.
+//!
+//! Sound cues play on top of the playlist, each once, at the same volume.
+//! Everything plays on the chosen device, or the system's default, which is
+//! opened when something is first played. `audio:position` follows playback
+//! a few times a second, `audio:track` each change of track, and
+//! `audio:ended` the end of a playlist that doesn't loop.
+
+use crate::cpres::{self, CpresError};
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::source::Amplify;
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use thiserror::Error;
+
+pub const AUDIO_POSITION_EVENT: &str = "audio:position";
+pub const AUDIO_TRACK_EVENT: &str = "audio:track";
+pub const AUDIO_ENDED_EVENT: &str = "audio:ended";
+
+/// How often the engine checks on playback between commands
+const TICK: Duration = Duration::from_millis(20);
+/// How often `audio:position` is sent while playing
+const POSITION_INTERVAL: Duration = Duration::from_millis(250);
+/// Longest crossfade, in seconds
+const MAX_CROSSFADE: f64 = 30.0;
+
+#[derive(Error, Debug)]
+pub enum AudioError {
+    #[error("No audio device named {0}")]
+    NoDevice(String),
+
+    #[error("Could not play {path}: {detail}")]
+    Decode { path: String, detail: String },
+
+    #[error("Audio playback failed: {0}")]
+    Failed(String),
+
+    #[error(transparent)]
+    Bundle(#[from] CpresError),
+}
+
+impl From<std::io::Error> for AudioError {
+    fn from(error: std::io::Error) -> Self {
+        CpresError::from(error).into()
+    }
+}
+
+/// An audio file, or audio stored in a bundle
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioTrack {
+    /// The file, or the entry path inside `bundle`
+    pub path: String,
+    pub bundle: Option<String>,
+    /// Playback gain in dB, as recorded when the audio was imported
+    #[serde(default)]
+    pub gain: f64,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PlaylistOptions {
+    /// Seconds each track fades into the next; none plays them gaplessly
+    pub crossfade: f64,
+    #[serde(rename = "loop")]
+    pub looping: bool,
+    pub autoplay: bool,
+}
+
+impl Default for PlaylistOptions {
+    fn default() -> Self {
+        Self {
+            crossfade: 0.0,
+            looping: false,
+            autoplay: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioStatus {
+    pub playing: bool,
+    /// The track playing in the playlist, the incoming one during a crossfade
+    pub index: Option<usize>,
+    /// Seconds into it
+    pub position: f64,
+    pub duration: Option<f64>,
+    pub tracks: usize,
+    pub volume: f32,
+    /// The device chosen; None for the system's default
+    pub device: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioDevice {
+    pub name: String,
+    pub default: bool,
+}
+
+/// List the devices audio can be played on
+pub fn list_devices() -> Result<Vec<AudioDevice>, AudioError> {
+    let host = rodio::cpal::default_host();
+    let default = host
+        .default_output_device()
+        .and_then(|device| device.name().ok());
+    let devices = host
+        .output_devices()
+        .map_err(|e| AudioError::Failed(e.to_string()))?;
+    Ok(devices
+        .filter_map(|device| device.name().ok())
+        .map(|name| AudioDevice {
+            default: default.as_deref() == Some(name.as_str()),
+            name,
+        })
+        .collect())
+}
+
+enum Command {
+    Load(Vec<AudioTrack>, PlaylistOptions),
+    Play,
+    Pause,
+    Stop,
+    Skip(usize),
+    Seek(f64),
+    Volume(f32),
+    Device(Option<String>),
+    Cue(AudioTrack),
+    StopCues,
+}
+
+/// A command, and where to say it was carried out
+type Request = (Command, Sender<Result<(), AudioError>>);
+
+/// Managed state: the way to the engine's thread, and what it last reported
+pub struct AudioEngine {
+    requests: Sender<Request>,
+    status: Arc<Mutex<AudioStatus>>,
+}
+
+impl AudioEngine {
+    fn new(app: AppHandle) -> Self {
+        let (requests, received) = mpsc::channel();
+        let status = Arc::new(Mutex::new(AudioStatus {
+            volume: 1.0,
+            ..AudioStatus::default()
+        }));
+        let engine = Engine::new(app, status.clone());
+        std::thread::spawn(move || engine.run(received));
+        Self { requests, status }
+    }
+
+    /// Replace the playlist, starting it from the first track
+    pub fn load(
+        &self,
+        tracks: Vec<AudioTrack>,
+        options: PlaylistOptions,
+    ) -> Result<AudioStatus, AudioError> {
+        self.request(Command::Load(tracks, options))
+    }
+
+    pub fn play(&self) -> Result<AudioStatus, AudioError> {
+        self.request(Command::Play)
+    }
+
+    pub fn pause(&self) -> Result<AudioStatus, AudioError> {
+        self.request(Command::Pause)
+    }
+
+    /// Stop the playlist, going back to its first track
+    pub fn stop(&self) -> Result<AudioStatus, AudioError> {
+        self.request(Command::Stop)
+    }
+
+    /// Play the track at `index` in the playlist
+    pub fn skip(&self, index: usize) -> Result<AudioStatus, AudioError> {
+        self.request(Command::Skip(index))
+    }
+
+    /// Go to `seconds` into the track playing
+    pub fn seek(&self, seconds: f64) -> Result<AudioStatus, AudioError> {
+        self.request(Command::Seek(seconds))
+    }
+
+    /// Volume of everything played, from 0 to 2
+    pub fn set_volume(&self, volume: f32) -> Result<AudioStatus, AudioError> {
+        self.request(Command::Volume(volume))
+    }
+
+    /// Play on the device named `device`, or the system's default; what's
+    /// playing carries on there
+    pub fn set_device(&self, device: Option<String>) -> Result<AudioStatus, AudioError> {
+        self.request(Command::Device(device))
+    }
+
+    /// Play `track` once, over the playlist
+    pub fn cue(&self, track: AudioTrack) -> Result<AudioStatus, AudioError> {
+        self.request(Command::Cue(track))
+    }
+
+    pub fn stop_cues(&self) -> Result<AudioStatus, AudioError> {
+        self.request(Command::StopCues)
+    }
+
+    pub fn status(&self) -> AudioStatus {
+        self.status
+            .lock()
+            .map(|status| status.clone())
+            .unwrap_or_default()
+    }
+
+    /// Send a command, and wait for it to be carried out
+    fn request(&self, command: Command) -> Result<AudioStatus, AudioError> {
+        let stopped = || AudioError::Failed("the audio engine stopped".to_string());
+        let (reply, replied) = mpsc::channel();
+        self.requests
+            .send((command, reply))
+            .map_err(|_| stopped())?;
+        replied.recv().map_err(|_| stopped())??;
+        Ok(self.status())
+    }
+}
+
+/// A sink playing a track, with the tracks queued behind it
+struct Deck {
+    sink: Sink,
+    /// Index and length of each track in the sink, the one playing first
+    queue: VecDeque<(usize, Option<Duration>)>,
+}
+
+impl Deck {
+    fn index(&self) -> Option<usize> {
+        self.queue.front().map(|(index, _)| *index)
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.queue.front().and_then(|(_, duration)| *duration)
+    }
+}
+
+/// The track fading out during a crossfade
+struct Fade {
+    deck: Deck,
+    started: Instant,
+}
+
+/// The engine's thread: owns the output stream and everything playing on it
+struct Engine {
+    app: AppHandle,
+    status: Arc<Mutex<AudioStatus>>,
+    device: Option<String>,
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    tracks: Vec<AudioTrack>,
+    options: PlaylistOptions,
+    volume: f32,
+    playing: bool,
+    deck: Option<Deck>,
+    fade: Option<Fade>,
+    cues: Vec<Sink>,
+    last_position: Instant,
+}
+
+impl Engine {
+    fn new(app: AppHandle, status: Arc<Mutex<AudioStatus>>) -> Self {
+        Self {
+            app,
+            status,
+            device: None,
+            output: None,
+            tracks: Vec::new(),
+            options: PlaylistOptions::default(),
+            volume: 1.0,
+            playing: false,
+            deck: None,
+            fade: None,
+            cues: Vec::new(),
+            last_position: Instant::now(),
+        }
+    }
+
+    fn run(mut self, requests: Receiver<Request>) {
+        loop {
+            match requests.recv_timeout(TICK) {
+                Ok((command, reply)) => {
+                    let result = self.handle(command);
+                    self.report();
+                    let _ = reply.send(result);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            self.tick();
+        }
+    }
+
+    fn handle(&mut self, command: Command) -> Result<(), AudioError> {
+        match command {
+            Command::Load(tracks, options) => {
+                self.stop();
+                self.tracks = tracks;
+                self.options = PlaylistOptions {
+                    crossfade: options.crossfade.clamp(0.0, MAX_CROSSFADE),
+                    ..options
+                };
+                if self.options.autoplay && !self.tracks.is_empty() {
+                    self.start(0, Duration::ZERO, self.volume)?;
+                }
+            }
+            Command::Play => match &self.deck {
+                Some(deck) => {
+                    deck.sink.play();
+                    self.playing = true;
+                }
+                None if !self.tracks.is_empty() => self.start(0, Duration::ZERO, self.volume)?,
+                None => {}
+            },
+            Command::Pause => {
+                self.finish_fade();
+                if let Some(deck) = &self.deck {
+                    deck.sink.pause();
+                }
+                self.playing = false;
+            }
+            Command::Stop => self.stop(),
+            Command::Skip(index) => {
+                if index >= self.tracks.len() {
+                    return Err(AudioError::Failed(format!(
+                        "the playlist has no track {index}"
+                    )));
+                }
+                self.stop();
+                self.start(index, Duration::ZERO, self.volume)?;
+            }
+            Command::Seek(seconds) => {
+                self.seek(Duration::try_from_secs_f64(seconds.max(0.0)).unwrap_or_default())?
+            }
+            Command::Volume(volume) => {
+                self.volume = volume.clamp(0.0, 2.0);
+                if let Some(deck) = &self.deck {
+                    deck.sink.set_volume(self.volume);
+                }
+                for cue in &self.cues {
+                    cue.set_volume(self.volume);
+                }
+            }
+            Command::Device(device) => self.set_device(device)?,
+            Command::Cue(track) => self.cue(&track)?,
+            Command::StopCues => {
+                for cue in self.cues.drain(..) {
+                    cue.stop();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Start the track at `index` from `position` at `volume`, replacing
+    /// what's playing
+    fn start(&mut self, index: usize, position: Duration, volume: f32) -> Result<(), AudioError> {
+        let sink = Sink::try_new(self.stream()?).map_err(|e| AudioError::Failed(e.to_string()))?;
+        sink.set_volume(volume);
+        let mut deck = Deck {
+            sink,
+            queue: VecDeque::new(),
+        };
+        self.append(&mut deck, index)?;
+        if !position.is_zero() {
+            if let Err(e) = deck.sink.try_seek(position) {
+                log::warn!("Could not seek in {}: {e}", self.tracks[index].path);
+            }
+        }
+        self.deck = Some(deck);
+        self.playing = true;
+        self.queue_next();
+        self.changed_track();
+        Ok(())
+    }
+
+    /// Add the track at `index` to the end of `deck`
+    fn append(&self, deck: &mut Deck, index: usize) -> Result<(), AudioError> {
+        let (source, duration) = decode(&self.tracks[index])?;
+        deck.sink.append(source);
+        deck.queue.push_back((index, duration));
+        Ok(())
+    }
+
+    /// Without a crossfade, queue the next track behind the one playing so
+    /// it follows without a gap
+    fn queue_next(&mut self) {
+        if self.options.crossfade > 0.0 {
+            return;
+        }
+        let Some(mut deck) = self.deck.take() else {
+            return;
+        };
+        if deck.queue.len() == 1 {
+            if let Some(next) = deck.index().and_then(|index| self.next(index)) {
+                if let Err(e) = self.append(&mut deck, next) {
+                    log::warn!("Could not queue the next track: {e}");
+                }
+            }
+        }
+        self.deck = Some(deck);
+    }
+
+    /// The track after the one at `index`
+    fn next(&self, index: usize) -> Option<usize> {
+        if index + 1 < self.tracks.len() {
+            Some(index + 1)
+        } else if self.options.looping && !self.tracks.is_empty() {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    fn tick(&mut self) {
+        self.cues.retain(|cue| !cue.empty());
+        if !self.playing {
+            return;
+        }
+
+        if let Some(fade) = &self.fade {
+            let progress = (fade.started.elapsed().as_secs_f64() / self.options.crossfade)
+                .clamp(0.0, 1.0) as f32;
+            fade.deck.sink.set_volume(self.volume * (1.0 - progress));
+            if let Some(deck) = &self.deck {
+                deck.sink.set_volume(self.volume * progress);
+            }
+            if progress >= 1.0 {
+                self.finish_fade();
+            }
+        }
+
+        let Some(deck) = &mut self.deck else {
+            return;
+        };
+        if deck.sink.empty() {
+            // The last track ended, or one whose length wasn't known
+            let last = deck.queue.back().map(|(index, _)| *index);
+            match last.and_then(|index| self.next(index)) {
+                Some(next) => {
+                    if let Err(e) = self.start(next, Duration::ZERO, self.volume) {
+                        log::warn!("Could not play the next track: {e}");
+                        self.ended();
+                    }
+                }
+                None => self.ended(),
+            }
+            return;
+        }
+        // Gapless: the sink moved on to the queued track
+        if deck.sink.len() < deck.queue.len() {
+            while deck.queue.len() > deck.sink.len() {
+                deck.queue.pop_front();
+            }
+            self.queue_next();
+            self.changed_track();
+            return;
+        }
+        if self.options.crossfade > 0.0 && self.fade.is_none() {
+            let crossfade = Duration::from_secs_f64(self.options.crossfade);
+            let due = deck
+                .duration()
+                .is_some_and(|duration| deck.sink.get_pos() + crossfade >= duration);
+            if let Some(next) = deck.index().and_then(|index| self.next(index)) {
+                if due {
+                    self.crossfade(next);
+                }
+            }
+        }
+
+        if self.last_position.elapsed() >= POSITION_INTERVAL {
+            let status = self.report();
+            let _ = self.app.emit(AUDIO_POSITION_EVENT, status);
+        }
+    }
+
+    /// Start fading the track at `index` in over the one playing
+    fn crossfade(&mut self, index: usize) {
+        let outgoing = self.deck.take();
+        if let Err(e) = self.start(index, Duration::ZERO, 0.0) {
+            log::warn!("Could not play the next track: {e}");
+            self.deck = outgoing;
+            return;
+        }
+        self.fade = outgoing.map(|deck| Fade {
+            deck,
+            started: Instant::now(),
+        });
+    }
+
+    /// End a crossfade, leaving only the incoming track
+    fn finish_fade(&mut self) {
+        if let Some(fade) = self.fade.take() {
+            fade.deck.sink.stop();
+        }
+        if let Some(deck) = &self.deck {
+            deck.sink.set_volume(self.volume);
+        }
+    }
+
+    fn seek(&mut self, position: Duration) -> Result<(), AudioError> {
+        self.finish_fade();
+        let Some(deck) = &self.deck else {
+            return Ok(());
+        };
+        deck.sink
+            .try_seek(position)
+            .map_err(|e| AudioError::Failed(e.to_string()))
+    }
+
+    fn stop(&mut self) {
+        self.finish_fade();
+        if let Some(deck) = self.deck.take() {
+            deck.sink.stop();
+        }
+        self.playing = false;
+    }
+
+    /// The playlist played to its end
+    fn ended(&mut self) {
+        self.stop();
+        log::info!("The playlist ended");
+        let status = self.report();
+        let _ = self.app.emit(AUDIO_ENDED_EVENT, status);
+    }
+
+    fn set_device(&mut self, device: Option<String>) -> Result<(), AudioError> {
+        let output = open_output(device.as_deref())?;
+        let resume = self.deck.as_ref().and_then(|deck| {
+            deck.index()
+                .map(|index| (index, deck.sink.get_pos(), deck.sink.is_paused()))
+        });
+        self.finish_fade();
+        self.deck = None;
+        self.cues.clear();
+        self.output = Some(output);
+        self.device = device;
+        log::info!(
+            "Playing audio on {}",
+            self.device.as_deref().unwrap_or("the default device")
+        );
+
+        if let Some((index, position, paused)) = resume {
+            self.start(index, position, self.volume)?;
+            if paused {
+                if let Some(deck) = &self.deck {
+                    deck.sink.pause();
+                }
+                self.playing = false;
+            }
+        }
+        Ok(())
+    }
+
+    fn cue(&mut self, track: &AudioTrack) -> Result<(), AudioError> {
+        let (source, _) = decode(track)?;
+        let sink = Sink::try_new(self.stream()?).map_err(|e| AudioError::Failed(e.to_string()))?;
+        sink.set_volume(self.volume);
+        sink.append(source);
+        self.cues.push(sink);
+        Ok(())
+    }
+
+    /// The output stream, opened on first use
+    fn stream(&mut self) -> Result<&OutputStreamHandle, AudioError> {
+        if self.output.is_none() {
+            self.output = Some(open_output(self.device.as_deref())?);
+        }
+        match &self.output {
+            Some((_, handle)) => Ok(handle),
+            None => Err(AudioError::Failed("no audio output".to_string())),
+        }
+    }
+
+    fn changed_track(&mut self) {
+        let status = self.report();
+        if let Some(index) = status.index {
+            log::info!("Playing track {} of the playlist", index + 1);
+        }
+        let _ = self.app.emit(AUDIO_TRACK_EVENT, status);
+    }
+
+    /// Update the status the commands read, and return it
+    fn report(&mut self) -> AudioStatus {
+        self.last_position = Instant::now();
+        let deck = self.deck.as_ref();
+        let status = AudioStatus {
+            playing: self.playing,
+            index: deck.and_then(Deck::index),
+            position: deck.map_or(0.0, |deck| deck.sink.get_pos().as_secs_f64()),
+            duration: deck
+                .and_then(Deck::duration)
+                .map(|duration| duration.as_secs_f64()),
+            tracks: self.tracks.len(),
+            volume: self.volume,
+            device: self.device.clone(),
+        };
+        if let Ok(mut shared) = self.status.lock() {
+            *shared = status.clone();
+        }
+        status
+    }
+}
+
+/// A track being decoded, at its gain
+type TrackSource = Amplify<Decoder<Cursor<Vec<u8>>>>;
+
+/// Read and start decoding `track`; the length is None when it can't be
+/// read
+fn decode(track: &AudioTrack) -> Result<(TrackSource, Option<Duration>), AudioError> {
+    let data = match &track.bundle {
+        Some(bundle) => cpres::read_bundle_media(Path::new(bundle), &track.path)?,
+        None => std::fs::read(&track.path)?,
+    };
+    let decoder = Decoder::new(Cursor::new(data)).map_err(|e| AudioError::Decode {
+        path: track.path.clone(),
+        detail: e.to_string(),
+    })?;
+    let duration = decoder.total_duration();
+    Ok((
+        decoder.amplify(10f32.powf(track.gain as f32 / 20.0)),
+        duration,
+    ))
+}
+
+/// Open the device named `device`, or the system's default
+fn open_output(device: Option<&str>) -> Result<(OutputStream, OutputStreamHandle), AudioError> {
+    let opened = match device {
+        Some(name) => {
+            let host = rodio::cpal::default_host();
+            let device = host
+                .output_devices()
+                .map_err(|e| AudioError::Failed(e.to_string()))?
+                .find(|device| device.name().is_ok_and(|found| found == name))
+                .ok_or_else(|| AudioError::NoDevice(name.to_string()))?;
+            OutputStream::try_from_device(&device)
+        }
+        None => OutputStream::try_default(),
+    };
+    opened.map_err(|e| AudioError::Failed(e.to_string()))
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(AudioEngine::new(app.clone()));
+}
+
+pub fn shutdown(app: &AppHandle) {
+    if let Some(engine) = app.try_state::<AudioEngine>() {
+        let _ = engine.stop();
+        let _ = engine.stop_cues();
+    }
+}
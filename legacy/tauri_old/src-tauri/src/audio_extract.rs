@@ -0,0 +1,142 @@
+//! Pulling the audio track out of a library video
+//!
+//! A performance video often carries the click or backing track the band
+//! wants on its own. Extracting writes the video's first audio track to an
+//! audio file next to it, named `<video> (audio).<ext>`, and the library
+//! indexes it like any other file. The video's tags (title, artist, album)
+//! are copied over, with the video's name as the title when it has none.
+//! AAC audio going into an M4A is copied as it is; every other combination
+//! is encoded.
+
+use crate::cpres::{self, CpresError};
+use crate::ffmpeg;
+use crate::media_probe;
+use crate::tasks::CancelToken;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const EXTRACT_PROGRESS_EVENT: &str = "media:extract-audio-progress";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// AAC, 256 kb/s
+    #[default]
+    M4a,
+    /// 320 kb/s
+    Mp3,
+    /// 16-bit PCM
+    Wav,
+    Flac,
+}
+
+impl AudioFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::M4a => "m4a",
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Wav => "wav",
+            AudioFormat::Flac => "flac",
+        }
+    }
+
+    fn codec_args(self) -> &'static [&'static str] {
+        match self {
+            AudioFormat::M4a => &["-c:a", "aac", "-b:a", "256k", "-f", "ipod"],
+            AudioFormat::Mp3 => &["-c:a", "libmp3lame", "-b:a", "320k", "-f", "mp3"],
+            AudioFormat::Wav => &["-c:a", "pcm_s16le", "-f", "wav"],
+            AudioFormat::Flac => &["-c:a", "flac", "-f", "flac"],
+        }
+    }
+}
+
+/// Payload of `media:extract-audio-progress`
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractProgress {
+    /// The video's path
+    pub source: String,
+    /// Share done, 0 to 1
+    pub progress: f64,
+}
+
+/// Write the audio of the video at `source` to a new file beside it and
+/// return its path. `on_progress` gets the share done.
+pub fn extract_audio(
+    source: &Path,
+    format: AudioFormat,
+    cancel: &CancelToken,
+    on_progress: impl Fn(f64),
+) -> Result<PathBuf, CpresError> {
+    let dir = source
+        .parent()
+        .ok_or_else(|| CpresError::MissingFile(source.to_string_lossy().to_string()))?;
+    let metadata = media_probe::probe(source, "video");
+    // Without ffprobe the codec is unknown; ffmpeg fails below when there's
+    // no audio after all
+    if metadata.audio_codec.is_none() && metadata.video_codec.is_some() {
+        return Err(CpresError::InvalidBundle(
+            "The video has no audio track".to_string(),
+        ));
+    }
+    let stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    cancel.check()?;
+    // A dot name keeps the library scan away from the unfinished file
+    let temp_file = tempfile::Builder::new()
+        .prefix(".extract-")
+        .suffix(&format!(".{}", format.extension()))
+        .tempfile_in(dir)?;
+    let mut command = ffmpeg::command()?;
+    command
+        .arg("-i")
+        .arg(source)
+        .args(["-map", "0:a:0", "-vn", "-sn", "-dn"])
+        .args(["-map_metadata", "0"]);
+    if !has_title(source) {
+        command.args(["-metadata", &format!("title={stem}")]);
+    }
+    if format == AudioFormat::M4a && metadata.audio_codec.as_deref() == Some("aac") {
+        command.args(["-c:a", "copy", "-f", "ipod"]);
+    } else {
+        command.args(format.codec_args());
+    }
+    command.args(ffmpeg::PROGRESS_ARGS).arg(temp_file.path());
+    ffmpeg::run_with_progress(
+        command,
+        metadata.duration,
+        "extracting audio",
+        || cancel.is_cancelled(),
+        on_progress,
+    )?;
+
+    let output = output_path(dir, &stem, format.extension());
+    cpres::persist_file(temp_file, &output)?;
+    Ok(output)
+}
+
+/// Whether the file at `path` has a title tag
+fn has_title(path: &Path) -> bool {
+    let Ok(mut command) = ffmpeg::probe_command() else {
+        return false;
+    };
+    command
+        .args(["-show_entries", "format_tags=title", "-of", "csv=p=0"])
+        .arg(path);
+    ffmpeg::capture(command, "reading tags")
+        .is_ok_and(|output| !String::from_utf8_lossy(&output).trim().is_empty())
+}
+
+/// `<stem> (audio).<extension>` in `dir`, numbered when that's taken
+fn output_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    let mut output = dir.join(format!("{stem} (audio).{extension}"));
+    for attempt in 2.. {
+        if !output.exists() {
+            break;
+        }
+        output = dir.join(format!("{stem} (audio) ({attempt}).{extension}"));
+    }
+    output
+}
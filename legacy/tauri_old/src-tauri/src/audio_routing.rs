@@ -0,0 +1,81 @@
+//! Which device sound from the webviews is played on
+//!
+//! Videos with sound play in the webviews, which can send each media element
+//! to a device of their own but don't keep the choice. It's kept here, for
+//! each context: `output` for the output windows, which feed the sanctuary's
+//! sound desk, and `preview` for the operator's preview, which belongs in
+//! their headphones with the click track rather than on the PA. Windows
+//! fetch the routes when they load and get `audio:routes` when they change,
+//! and find the devices among their own by name. The audio engine has a
+//! device of its own (see `audio_engine`).
+
+use crate::audio_engine::{self, AudioError};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+pub const AUDIO_ROUTES_EVENT: &str = "audio:routes";
+
+/// Where in the app the sound comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioContext {
+    /// The output windows
+    Output,
+    /// The operator's preview in the main window
+    Preview,
+}
+
+/// The device each context plays on, by name; None for the system's default
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioRoutes {
+    pub output: Option<String>,
+    pub preview: Option<String>,
+}
+
+/// Managed state: the routes
+#[derive(Default)]
+pub struct AudioRouting {
+    routes: Mutex<AudioRoutes>,
+}
+
+impl AudioRouting {
+    /// Play `context` on the device named `device`, or the system's default;
+    /// true when that changed the routes
+    pub fn set(&self, context: AudioContext, device: Option<String>) -> Result<bool, AudioError> {
+        if let Some(name) = &device {
+            let devices = audio_engine::list_devices()?;
+            if !devices.iter().any(|found| &found.name == name) {
+                return Err(AudioError::NoDevice(name.clone()));
+            }
+        }
+        let Ok(mut routes) = self.routes.lock() else {
+            return Ok(false);
+        };
+        let route = match context {
+            AudioContext::Output => &mut routes.output,
+            AudioContext::Preview => &mut routes.preview,
+        };
+        if *route == device {
+            return Ok(false);
+        }
+        log::info!(
+            "Playing {context:?} sound on {}",
+            device.as_deref().unwrap_or("the default device")
+        );
+        *route = device;
+        Ok(true)
+    }
+
+    pub fn routes(&self) -> AudioRoutes {
+        self.routes
+            .lock()
+            .map(|routes| routes.clone())
+            .unwrap_or_default()
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(AudioRouting::default());
+}
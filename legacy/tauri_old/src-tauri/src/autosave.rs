@@ -0,0 +1,340 @@
+//! Autosave scheduler and crash recovery files
+//!
+//! The frontend pushes its latest `BundleState` whenever the document changes;
+//! a background task periodically writes any dirty snapshots to
+//! `<app data>/recovery/<id>.json`. A session marker file is created at startup
+//! and removed on clean exit, so a marker left behind means the previous run
+//! crashed and its recovery files are worth offering to the operator.
+
+use crate::cpres::BundleState;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+const RECOVERY_DIR_NAME: &str = "recovery";
+const SESSION_MARKER_FILENAME: &str = "session.lock";
+const DEFAULT_INTERVAL_SECS: u64 = 30;
+const MIN_INTERVAL_SECS: u64 = 5;
+
+/// Contents of a recovery file on disk
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecoveryFile {
+    pub id: String,
+    pub bundle_path: Option<String>,
+    pub saved_at_ms: u64,
+    pub state: BundleState,
+}
+
+/// Summary of a recovery file for listing without loading the full state
+#[derive(Debug, Serialize)]
+pub struct RecoveryEntry {
+    pub id: String,
+    pub bundle_path: Option<String>,
+    pub saved_at_ms: u64,
+    pub byte_size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryListing {
+    /// True when the previous session did not shut down cleanly
+    pub unclean_shutdown: bool,
+    pub entries: Vec<RecoveryEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct AutosaveConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+}
+
+impl Default for AutosaveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: DEFAULT_INTERVAL_SECS,
+        }
+    }
+}
+
+struct PendingSnapshot {
+    bundle_path: Option<String>,
+    state: BundleState,
+    dirty: bool,
+    /// Tells a snapshot apart from one pushed while it was being written
+    revision: u64,
+}
+
+/// Managed autosave state
+pub struct AutosaveState {
+    recovery_dir: PathBuf,
+    unclean_shutdown: bool,
+    config: Mutex<AutosaveConfig>,
+    pending: Mutex<HashMap<String, PendingSnapshot>>,
+    revisions: AtomicU64,
+}
+
+impl AutosaveState {
+    /// Record the latest document state; it is written on the next autosave tick
+    pub fn update(
+        &self,
+        bundle_path: Option<String>,
+        state: BundleState,
+    ) -> Result<String, String> {
+        let id = recovery_id(bundle_path.as_deref(), &state)?;
+        let mut pending = self.pending.lock().map_err(|e| e.to_string())?;
+        pending.insert(
+            id.clone(),
+            PendingSnapshot {
+                bundle_path,
+                state,
+                dirty: true,
+                revision: self.revisions.fetch_add(1, Ordering::Relaxed),
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn config(&self) -> AutosaveConfig {
+        self.config.lock().map(|c| *c).unwrap_or_default()
+    }
+
+    pub fn configure(&self, config: AutosaveConfig) -> Result<AutosaveConfig, String> {
+        let mut current = self.config.lock().map_err(|e| e.to_string())?;
+        *current = AutosaveConfig {
+            enabled: config.enabled,
+            interval_secs: config.interval_secs.max(MIN_INTERVAL_SECS),
+        };
+        Ok(*current)
+    }
+
+    /// Write every dirty snapshot to its recovery file. Snapshots stay dirty
+    /// until they're written, so one that fails is tried again next tick.
+    pub fn flush(&self) -> Result<(), String> {
+        let dirty: Vec<(u64, RecoveryFile)> = {
+            let pending = self.pending.lock().map_err(|e| e.to_string())?;
+            pending
+                .iter()
+                .filter(|(_, snapshot)| snapshot.dirty)
+                .map(|(id, snapshot)| {
+                    let file = RecoveryFile {
+                        id: id.clone(),
+                        bundle_path: snapshot.bundle_path.clone(),
+                        saved_at_ms: unix_millis(),
+                        state: snapshot.state.clone(),
+                    };
+                    (snapshot.revision, file)
+                })
+                .collect()
+        };
+
+        if dirty.is_empty() {
+            return Ok(());
+        }
+
+        std::fs::create_dir_all(&self.recovery_dir).map_err(|e| e.to_string())?;
+        let mut failure = None;
+        for (revision, file) in dirty {
+            if let Err(e) = self.write_recovery_file(&file) {
+                failure.get_or_insert(e);
+                continue;
+            }
+            let mut pending = self.pending.lock().map_err(|e| e.to_string())?;
+            match pending.get_mut(&file.id) {
+                Some(snapshot) => {
+                    if snapshot.revision == revision {
+                        snapshot.dirty = false;
+                    }
+                }
+                // Saved or discarded while it was being written; the file is
+                // no longer wanted and would be offered for recovery next launch
+                None => {
+                    let _ = std::fs::remove_file(self.recovery_path(&file.id));
+                }
+            }
+        }
+
+        failure.map_or(Ok(()), Err)
+    }
+
+    fn write_recovery_file(&self, file: &RecoveryFile) -> Result<(), String> {
+        let content = serde_json::to_vec(file).map_err(|e| e.to_string())?;
+        let target = self.recovery_path(&file.id);
+        let temp = target.with_extension("json.tmp");
+        std::fs::write(&temp, content).map_err(|e| e.to_string())?;
+        std::fs::rename(&temp, &target).map_err(|e| e.to_string())
+    }
+
+    /// Forget the snapshots and recovery files for a bundle that was just
+    /// saved: those kept by its path, and the one kept by its presentationId
+    /// from before it was first saved
+    pub fn mark_saved(&self, bundle_path: &Path, state: &BundleState) -> Result<(), String> {
+        let path = bundle_path.to_string_lossy().to_string();
+        let unsaved_id = recovery_id(None, state).ok();
+        let mut pending = self.pending.lock().map_err(|e| e.to_string())?;
+        let mut ids: Vec<String> = pending
+            .iter()
+            .filter(|(_, snapshot)| snapshot.bundle_path.as_deref() == Some(path.as_str()))
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.extend(unsaved_id);
+        for id in ids {
+            pending.remove(&id);
+            let _ = std::fs::remove_file(self.recovery_path(&id));
+        }
+        Ok(())
+    }
+
+    pub fn list(&self) -> Result<RecoveryListing, String> {
+        let mut entries = Vec::new();
+        if self.recovery_dir.exists() {
+            for entry in std::fs::read_dir(&self.recovery_dir).map_err(|e| e.to_string())? {
+                let entry = entry.map_err(|e| e.to_string())?;
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+
+                let byte_size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                let Ok(file) = read_recovery_file(&path) else {
+                    continue;
+                };
+                entries.push(RecoveryEntry {
+                    id: file.id,
+                    bundle_path: file.bundle_path,
+                    saved_at_ms: file.saved_at_ms,
+                    byte_size,
+                });
+            }
+        }
+
+        entries.sort_by_key(|e| std::cmp::Reverse(e.saved_at_ms));
+
+        Ok(RecoveryListing {
+            unclean_shutdown: self.unclean_shutdown,
+            entries,
+        })
+    }
+
+    pub fn restore(&self, id: &str) -> Result<RecoveryFile, String> {
+        let path = self.recovery_path(id);
+        if !path.exists() {
+            return Err("Recovery file not found".to_string());
+        }
+        read_recovery_file(&path)
+    }
+
+    pub fn discard(&self, id: &str) -> Result<(), String> {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(id);
+        }
+        let path = self.recovery_path(id);
+        if path.exists() {
+            std::fs::remove_file(path).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn recovery_path(&self, id: &str) -> PathBuf {
+        // Ids are hex digests; strip anything else so a caller can't escape the directory
+        let safe: String = id.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        self.recovery_dir.join(format!("{safe}.json"))
+    }
+
+    fn session_marker_path(&self) -> PathBuf {
+        self.recovery_dir.join(SESSION_MARKER_FILENAME)
+    }
+}
+
+/// Create the managed autosave state, detect an unclean previous shutdown,
+/// and start the background autosave task
+pub fn init(app: &AppHandle) -> Result<(), String> {
+    let recovery_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join(RECOVERY_DIR_NAME);
+    std::fs::create_dir_all(&recovery_dir).map_err(|e| e.to_string())?;
+
+    let marker = recovery_dir.join(SESSION_MARKER_FILENAME);
+    let unclean_shutdown = marker.exists();
+    std::fs::write(&marker, unix_millis().to_string()).map_err(|e| e.to_string())?;
+
+    app.manage(AutosaveState {
+        recovery_dir,
+        unclean_shutdown,
+        config: Mutex::new(AutosaveConfig::default()),
+        pending: Mutex::new(HashMap::new()),
+        revisions: AtomicU64::new(0),
+    });
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let config = handle.state::<AutosaveState>().config();
+            tokio::time::sleep(Duration::from_secs(config.interval_secs)).await;
+            if !config.enabled {
+                continue;
+            }
+
+            let app = handle.clone();
+            let result =
+                tauri::async_runtime::spawn_blocking(move || app.state::<AutosaveState>().flush())
+                    .await;
+            match result {
+                Ok(Err(e)) => log::warn!("Autosave failed: {e}"),
+                Err(e) => log::warn!("Autosave task failed: {e}"),
+                Ok(Ok(())) => {}
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Flush pending snapshots and remove the session marker on a clean exit
+pub fn shutdown(app: &AppHandle) {
+    let Some(state) = app.try_state::<AutosaveState>() else {
+        return;
+    };
+    if let Err(e) = state.flush() {
+        log::warn!("Autosave flush on exit failed: {e}");
+    }
+    let _ = std::fs::remove_file(state.session_marker_path());
+}
+
+/// Stable id for a document: keyed by its bundle path, or by presentation id
+/// for documents that have never been saved
+fn recovery_id(bundle_path: Option<&str>, state: &BundleState) -> Result<String, String> {
+    let key = match bundle_path {
+        Some(path) if !path.trim().is_empty() => format!("path:{path}"),
+        _ => {
+            let manifest: serde_json::Value =
+                serde_json::from_str(&state.manifest).map_err(|e| e.to_string())?;
+            let presentation_id = manifest
+                .get("presentationId")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| "Missing presentationId in manifest".to_string())?;
+            format!("presentation:{presentation_id}")
+        }
+    };
+
+    let digest = Sha256::digest(key.as_bytes());
+    Ok(hex::encode(&digest[..8]))
+}
+
+fn read_recovery_file(path: &Path) -> Result<RecoveryFile, String> {
+    let content = std::fs::read(path).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&content).map_err(|e| e.to_string())
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,392 @@
+//! Advisory locks for bundles on shared drives
+//!
+//! Opening a bundle for editing writes `.<name>.lock` next to it describing
+//! which machine holds it. The holder refreshes the lock's heartbeat while the
+//! app is running, so a lock whose heartbeat is older than `STALE_AFTER_MS`
+//! (crashed app, machine switched off) is reported as stale and may be taken
+//! over. Lockfiles are replaced whole through a temporary file; one that still
+//! can't be read (an older version cut short mid-write) counts as held until
+//! it's been left alone for as long. The locks only coordinate instances of
+//! this app; other programs can still write the file.
+//!
+//! An instance that missed heartbeats long enough (sleep, dropped network)
+//! may find its lock taken over. The heartbeat reads each lockfile back first,
+//! and a lock that changed hands is dropped and reported with
+//! `LOCK_LOST_EVENT`.
+
+use crate::cpres::CpresError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Carries the bundle path of a lock another instance took over
+pub const LOCK_LOST_EVENT: &str = "bundle-lock:lost";
+
+const LOCK_SUFFIX: &str = ".lock";
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+/// Ten missed heartbeats
+const STALE_AFTER_MS: u64 = 5 * 60 * 1000;
+
+/// Contents of a lockfile
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LockOwner {
+    /// Random per-launch id, so two windows on one machine are told apart
+    pub instance_id: String,
+    pub host: String,
+    pub user: String,
+    pub pid: u32,
+    pub acquired_at_ms: u64,
+    pub heartbeat_ms: u64,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LockState {
+    Unlocked,
+    HeldByUs,
+    HeldByOther,
+    /// Held by another instance that stopped refreshing the lock
+    Stale,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LockStatus {
+    pub state: LockState,
+    pub owner: Option<LockOwner>,
+    pub lock_path: String,
+}
+
+/// Managed lock state: the locks this instance holds
+pub struct LockRegistry {
+    instance_id: String,
+    held: Mutex<HashMap<PathBuf, LockOwner>>,
+}
+
+impl LockRegistry {
+    /// Inspect the lock on a bundle without changing it
+    pub fn status(&self, bundle_path: &Path) -> Result<LockStatus, CpresError> {
+        let lock_path = lock_path(bundle_path);
+        let (state, owner) = match read_lock(&lock_path)? {
+            Lockfile::Missing => (LockState::Unlocked, None),
+            Lockfile::Unreadable { modified_ms } => (heartbeat_state(modified_ms), None),
+            Lockfile::Owner(owner) if owner.instance_id == self.instance_id => {
+                (LockState::HeldByUs, Some(owner))
+            }
+            Lockfile::Owner(owner) => (heartbeat_state(owner.heartbeat_ms), Some(owner)),
+        };
+
+        Ok(LockStatus {
+            state,
+            owner,
+            lock_path: lock_path.to_string_lossy().to_string(),
+        })
+    }
+
+    /// Take (or refresh) the lock on a bundle. Fails with `CpresError::BundleLocked`
+    /// when another live instance holds it, unless `force` is set.
+    pub fn acquire(&self, bundle_path: &Path, force: bool) -> Result<LockStatus, CpresError> {
+        let status = self.status(bundle_path)?;
+        if status.state == LockState::HeldByOther && !force {
+            return Err(CpresError::BundleLocked(describe_owner(
+                status.owner.as_ref(),
+            )));
+        }
+
+        let now = unix_millis();
+        let owner = LockOwner {
+            instance_id: self.instance_id.clone(),
+            host: host_name(),
+            user: user_name(),
+            pid: std::process::id(),
+            acquired_at_ms: match (&status.state, &status.owner) {
+                (LockState::HeldByUs, Some(owner)) => owner.acquired_at_ms,
+                _ => now,
+            },
+            heartbeat_ms: now,
+        };
+
+        let lock_path = lock_path(bundle_path);
+        match status.state {
+            LockState::HeldByUs => write_lock(&lock_path, &owner)?,
+            LockState::Unlocked => create_lock(&lock_path, &owner)?,
+            // Remove the old lock and create ours from scratch, so of two
+            // machines taking over the same stale lock only one wins
+            LockState::Stale | LockState::HeldByOther => {
+                match fs::remove_file(&lock_path) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+                create_lock(&lock_path, &owner)?;
+            }
+        }
+
+        self.held
+            .lock()
+            .map_err(|e| CpresError::InvalidBundle(e.to_string()))?
+            .insert(bundle_path.to_path_buf(), owner.clone());
+
+        Ok(LockStatus {
+            state: LockState::HeldByUs,
+            owner: Some(owner),
+            lock_path: lock_path.to_string_lossy().to_string(),
+        })
+    }
+
+    /// Lock a bundle for one rewrite (prune, restore, ...). The returned guard
+    /// releases the lock when dropped, unless it was already held, like by an
+    /// open editor.
+    pub fn hold(&self, bundle_path: &Path) -> Result<HeldLock<'_>, CpresError> {
+        let already_held = self
+            .held
+            .lock()
+            .map_err(|e| CpresError::InvalidBundle(e.to_string()))?
+            .contains_key(bundle_path);
+        self.acquire(bundle_path, false)?;
+        Ok(HeldLock {
+            registry: self,
+            bundle_path: bundle_path.to_path_buf(),
+            release: !already_held,
+        })
+    }
+
+    /// Give up the lock on a bundle; a lockfile owned by someone else is left alone
+    pub fn release(&self, bundle_path: &Path) -> Result<(), CpresError> {
+        self.held
+            .lock()
+            .map_err(|e| CpresError::InvalidBundle(e.to_string()))?
+            .remove(bundle_path);
+
+        let lock_path = lock_path(bundle_path);
+        if let Lockfile::Owner(owner) = read_lock(&lock_path)? {
+            if owner.instance_id == self.instance_id {
+                fs::remove_file(&lock_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrite the heartbeat of every held lock that is still ours. Returns the
+    /// bundles whose lock another instance has taken over; those are no longer held.
+    fn refresh_all(&self) -> Vec<PathBuf> {
+        let Ok(mut held) = self.held.lock() else {
+            return Vec::new();
+        };
+        let now = unix_millis();
+        let mut lost = Vec::new();
+        held.retain(|bundle_path, owner| {
+            let lock_path = lock_path(bundle_path);
+            owner.heartbeat_ms = now;
+            let refreshed = match read_lock(&lock_path) {
+                Ok(Lockfile::Owner(current)) if current.instance_id == self.instance_id => {
+                    write_lock(&lock_path, owner)
+                }
+                Ok(Lockfile::Owner(current)) => {
+                    Err(CpresError::BundleLocked(describe_owner(Some(&current))))
+                }
+                // Removed behind our back: take it again, unless someone else just did
+                Ok(Lockfile::Missing) => create_lock(&lock_path, owner),
+                // Another instance is writing it; look again on the next beat
+                Ok(Lockfile::Unreadable { .. }) => Ok(()),
+                Err(e) => Err(e),
+            };
+            match refreshed {
+                Ok(()) => true,
+                Err(CpresError::BundleLocked(by)) => {
+                    log::warn!("Lock for {} was taken over by {by}", bundle_path.display());
+                    lost.push(bundle_path.clone());
+                    false
+                }
+                Err(e) => {
+                    log::warn!("Could not refresh lock for {}: {e}", bundle_path.display());
+                    true
+                }
+            }
+        });
+        lost
+    }
+
+    fn release_all(&self) {
+        let paths: Vec<PathBuf> = match self.held.lock() {
+            Ok(held) => held.keys().cloned().collect(),
+            Err(_) => return,
+        };
+        for path in paths {
+            if let Err(e) = self.release(&path) {
+                log::warn!("Could not release lock for {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+/// A lock taken by `LockRegistry::hold`
+pub struct HeldLock<'a> {
+    registry: &'a LockRegistry,
+    bundle_path: PathBuf,
+    release: bool,
+}
+
+impl Drop for HeldLock<'_> {
+    fn drop(&mut self) {
+        if !self.release {
+            return;
+        }
+        if let Err(e) = self.registry.release(&self.bundle_path) {
+            log::warn!(
+                "Could not release lock for {}: {e}",
+                self.bundle_path.display()
+            );
+        }
+    }
+}
+
+/// Lockfile path for a bundle: `.<name>.lock` in the same folder
+pub fn lock_path(bundle_path: &Path) -> PathBuf {
+    let name = bundle_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    bundle_path.with_file_name(format!(".{name}{LOCK_SUFFIX}"))
+}
+
+/// Create the managed lock registry and start the heartbeat task
+pub fn init(app: &AppHandle) {
+    app.manage(LockRegistry {
+        instance_id: uuid::Uuid::new_v4().to_string(),
+        held: Mutex::new(HashMap::new()),
+    });
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+            let app = handle.clone();
+            // Network drives can block for a while; keep that off the async runtime
+            let lost = tauri::async_runtime::spawn_blocking(move || {
+                app.state::<LockRegistry>().refresh_all()
+            })
+            .await
+            .unwrap_or_default();
+            for bundle_path in lost {
+                let _ = handle.emit(LOCK_LOST_EVENT, bundle_path.to_string_lossy());
+            }
+        }
+    });
+}
+
+/// Release every lock held by this instance on a clean exit
+pub fn shutdown(app: &AppHandle) {
+    if let Some(registry) = app.try_state::<LockRegistry>() {
+        registry.release_all();
+    }
+}
+
+/// What a lockfile turned out to hold
+enum Lockfile {
+    Missing,
+    Owner(LockOwner),
+    /// Empty or cut short; only its age says whether anyone still holds it
+    Unreadable {
+        modified_ms: u64,
+    },
+}
+
+fn read_lock(lock_path: &Path) -> Result<Lockfile, CpresError> {
+    let content = match fs::read(lock_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Lockfile::Missing),
+        Err(e) => return Err(e.into()),
+    };
+    if let Ok(owner) = serde_json::from_slice(&content) {
+        return Ok(Lockfile::Owner(owner));
+    }
+    let modified_ms = fs::metadata(lock_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|age| age.as_millis() as u64)
+        .unwrap_or(0);
+    Ok(Lockfile::Unreadable { modified_ms })
+}
+
+/// Create a lockfile that doesn't exist yet; fails with `BundleLocked` when
+/// another instance created one first
+fn create_lock(lock_path: &Path, owner: &LockOwner) -> Result<(), CpresError> {
+    match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(lock_path)
+    {
+        Ok(mut file) => Ok(file.write_all(&serde_json::to_vec_pretty(owner)?)?),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            let current = match read_lock(lock_path)? {
+                Lockfile::Owner(owner) => Some(owner),
+                _ => None,
+            };
+            Err(CpresError::BundleLocked(describe_owner(current.as_ref())))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replace a lockfile whole, so it's never seen half-written
+fn write_lock(lock_path: &Path, owner: &LockOwner) -> Result<(), CpresError> {
+    let name = lock_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp_path = lock_path.with_file_name(format!("{name}.{}.tmp", owner.instance_id));
+    fs::write(&temp_path, serde_json::to_vec_pretty(owner)?)?;
+    if let Err(e) = fs::rename(&temp_path, lock_path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Held by someone else, or stale once the last sign of life is old enough
+fn heartbeat_state(heartbeat_ms: u64) -> LockState {
+    if unix_millis().saturating_sub(heartbeat_ms) > STALE_AFTER_MS {
+        LockState::Stale
+    } else {
+        LockState::HeldByOther
+    }
+}
+
+pub(crate) fn describe_owner(owner: Option<&LockOwner>) -> String {
+    match owner {
+        Some(owner) => format!("{} on {}", owner.user, owner.host),
+        None => "another computer".to_string(),
+    }
+}
+
+fn host_name() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .ok()
+        .or_else(|| {
+            fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|h| h.trim().to_string())
+        })
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn user_name() -> String {
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
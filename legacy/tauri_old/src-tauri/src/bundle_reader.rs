@@ -0,0 +1,75 @@
+//! Reading bundle archives, memory-mapped when they are large
+//!
+//! A `ZipArchive` over a `File` turns every seek and read into a system call,
+//! which adds up when a multi-gigabyte bundle is opened or its media streamed.
+//! Bundles of at least `MMAP_MIN_BYTES` are mapped into memory instead, so the
+//! central directory and entries are read straight from the page cache.
+//!
+//! Mapping needs the `mmap` feature (on by default), and is only used for
+//! bundles on a local disk outside cloud sync folders (see
+//! `storage::is_local_unsynced`); the rest, and bundles the OS refuses to
+//! map, are read through the file. A mapped file that's cut short while it's
+//! mapped takes the app down (SIGBUS, or an in-page error on Windows), and
+//! sync clients, NAS tools and other programs may rewrite files in place.
+
+use crate::cpres::CpresError;
+use crate::storage;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// Smaller bundles read just as fast through a buffered file
+#[cfg(feature = "mmap")]
+const MMAP_MIN_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Read access to a bundle file, mapped or buffered
+pub enum BundleReader {
+    Buffered(BufReader<File>),
+    #[cfg(feature = "mmap")]
+    Mapped(io::Cursor<memmap2::Mmap>),
+}
+
+impl BundleReader {
+    pub fn open(path: &Path) -> Result<Self, CpresError> {
+        let file = File::open(path)?;
+        #[cfg(feature = "mmap")]
+        if file.metadata()?.len() >= MMAP_MIN_BYTES && storage::is_local_unsynced(path) {
+            // SAFETY: this assumes nothing truncates the file while it's mapped.
+            // On a local disk outside sync folders only this app writes bundles,
+            // and it replaces them by rename (`cpres::persist_file`), leaving the
+            // mapped file intact; another program rewriting it in place there
+            // would still fault the process.
+            match unsafe { memmap2::Mmap::map(&file) } {
+                Ok(map) => return Ok(BundleReader::Mapped(io::Cursor::new(map))),
+                Err(e) => log::warn!("Could not map {}, reading it instead: {e}", path.display()),
+            }
+        }
+        Ok(BundleReader::Buffered(BufReader::new(file)))
+    }
+}
+
+impl Read for BundleReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            BundleReader::Buffered(reader) => reader.read(buf),
+            #[cfg(feature = "mmap")]
+            BundleReader::Mapped(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for BundleReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            BundleReader::Buffered(reader) => reader.seek(pos),
+            #[cfg(feature = "mmap")]
+            BundleReader::Mapped(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+/// Open a bundle's zip archive for reading
+pub fn open_archive(path: &Path) -> Result<ZipArchive<BundleReader>, CpresError> {
+    Ok(ZipArchive::new(BundleReader::open(path)?)?)
+}
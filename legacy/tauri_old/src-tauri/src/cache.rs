@@ -0,0 +1,279 @@
+//! Size limit across the app's caches
+//!
+//! Thumbnails, proxies, waveforms, font previews, remote downloads, and
+//! optimized and normalized imports are all made again when they go missing,
+//! so together
+//! they're kept under one limit the user sets (10 GB by default, kept in
+//! `<app data>/cache.json`). When they're over it, the least recently used
+//! entries are deleted first, across every kind: a file's modification time
+//! is its last use, and a remote download's folder counts as one entry.
+//! Entries used in the last few minutes are left alone, since they may still
+//! be being written or played. The caches are trimmed at startup, every half
+//! hour, and when the limit changes.
+
+use crate::cpres::CpresError;
+use crate::download;
+use crate::font_preview;
+use crate::image_optimize;
+use crate::loudness;
+use crate::proxy;
+use crate::thumbnails;
+use crate::waveform;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager};
+
+const SETTINGS_FILENAME: &str = "cache.json";
+
+pub const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+const MIN_MAX_BYTES: u64 = 256 * 1024 * 1024;
+/// Trim to this much below the limit so the next few files don't trim again
+const TRIM_TO_PERCENT: u64 = 90;
+
+/// Entries used more recently than this are never evicted
+const MIN_IDLE: Duration = Duration::from_secs(5 * 60);
+const TRIM_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheKind {
+    Thumbnails,
+    /// Lighter copies of heavy videos for the editor
+    Proxies,
+    Waveforms,
+    /// Sample text drawn in each font for the font picker
+    FontPreviews,
+    /// Files fetched from links and stock media sites
+    Downloads,
+    /// Imported images resized and re-encoded, and GIFs turned into video
+    Optimized,
+    /// Imported audio adjusted to the target loudness
+    Normalized,
+}
+
+impl CacheKind {
+    const ALL: [CacheKind; 7] = [
+        CacheKind::Thumbnails,
+        CacheKind::Proxies,
+        CacheKind::Waveforms,
+        CacheKind::FontPreviews,
+        CacheKind::Downloads,
+        CacheKind::Optimized,
+        CacheKind::Normalized,
+    ];
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheSettings {
+    max_bytes: u64,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheUsage {
+    pub kind: CacheKind,
+    pub bytes: u64,
+    pub entries: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheStats {
+    pub kinds: Vec<CacheUsage>,
+    pub total_bytes: u64,
+    pub max_bytes: u64,
+}
+
+/// Where each cache lives; some are in app data rather than the OS cache
+/// folder because they predate this module
+pub struct CacheDirs {
+    app_data_dir: PathBuf,
+    app_cache_dir: PathBuf,
+}
+
+impl CacheDirs {
+    pub fn new(app: &AppHandle) -> Result<Self, tauri::Error> {
+        Ok(Self {
+            app_data_dir: app.path().app_data_dir()?,
+            app_cache_dir: app.path().app_cache_dir()?,
+        })
+    }
+
+    fn dir(&self, kind: CacheKind) -> PathBuf {
+        match kind {
+            CacheKind::Thumbnails => self.app_data_dir.join(thumbnails::CACHE_DIR_NAME),
+            CacheKind::Proxies => self.app_cache_dir.join(proxy::CACHE_DIR_NAME),
+            CacheKind::Waveforms => self.app_cache_dir.join(waveform::CACHE_DIR_NAME),
+            CacheKind::FontPreviews => self.app_cache_dir.join(font_preview::CACHE_DIR_NAME),
+            CacheKind::Downloads => download::cache_root(&self.app_data_dir),
+            CacheKind::Optimized => self.app_cache_dir.join(image_optimize::OPTIMIZED_DIR_NAME),
+            CacheKind::Normalized => self.app_cache_dir.join(loudness::NORMALIZED_DIR_NAME),
+        }
+    }
+
+    /// The size limit the user set
+    pub fn max_bytes(&self) -> u64 {
+        std::fs::read(self.app_data_dir.join(SETTINGS_FILENAME))
+            .ok()
+            .and_then(|content| serde_json::from_slice::<CacheSettings>(&content).ok())
+            .unwrap_or_default()
+            .max_bytes
+    }
+
+    /// Change the size limit, raised to at least 256 MB
+    pub fn set_max_bytes(&self, max_bytes: u64) -> Result<(), CpresError> {
+        let settings = CacheSettings {
+            max_bytes: max_bytes.max(MIN_MAX_BYTES),
+        };
+        std::fs::create_dir_all(&self.app_data_dir)?;
+        std::fs::write(
+            self.app_data_dir.join(SETTINGS_FILENAME),
+            serde_json::to_vec_pretty(&settings)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Result<CacheStats, CpresError> {
+        let mut kinds = Vec::new();
+        for kind in CacheKind::ALL {
+            let entries = entries(&self.dir(kind))?;
+            kinds.push(CacheUsage {
+                kind,
+                bytes: entries.iter().map(|entry| entry.bytes).sum(),
+                entries: entries.len(),
+            });
+        }
+        Ok(CacheStats {
+            total_bytes: kinds.iter().map(|usage| usage.bytes).sum(),
+            kinds,
+            max_bytes: self.max_bytes(),
+        })
+    }
+
+    /// Delete everything in one cache, or in all of them; returns the bytes
+    /// freed. Files still being written are kept.
+    pub fn clear(&self, kind: Option<CacheKind>) -> Result<u64, CpresError> {
+        let mut freed = 0;
+        for kind in kind.map_or(CacheKind::ALL.to_vec(), |kind| vec![kind]) {
+            for entry in entries(&self.dir(kind))? {
+                freed += entry.remove();
+            }
+        }
+        Ok(freed)
+    }
+
+    /// Evict least recently used entries while the caches are over the limit;
+    /// returns the bytes freed
+    pub fn trim(&self) -> Result<u64, CpresError> {
+        let max_bytes = self.max_bytes();
+        let mut all = Vec::new();
+        for kind in CacheKind::ALL {
+            all.extend(entries(&self.dir(kind))?);
+        }
+        let mut total: u64 = all.iter().map(|entry| entry.bytes).sum();
+        if total <= max_bytes {
+            return Ok(0);
+        }
+        let target = max_bytes / 100 * TRIM_TO_PERCENT;
+        let idle_since = SystemTime::now() - MIN_IDLE;
+        all.sort_by_key(|entry| entry.used);
+        let mut freed = 0;
+        for entry in all {
+            if total <= target || entry.used > idle_since {
+                break;
+            }
+            let removed = entry.remove();
+            total -= removed;
+            freed += removed;
+        }
+        log::info!("Trimmed {freed} bytes from the caches");
+        Ok(freed)
+    }
+}
+
+/// A file, or a remote download's folder, evicted as a whole
+struct Entry {
+    path: PathBuf,
+    bytes: u64,
+    used: SystemTime,
+}
+
+impl Entry {
+    /// Delete the entry; returns the bytes freed, or 0 when it couldn't be
+    /// deleted (a proxy open for playback on Windows, say)
+    fn remove(&self) -> u64 {
+        let removed = if self.path.is_dir() {
+            std::fs::remove_dir_all(&self.path)
+        } else {
+            std::fs::remove_file(&self.path)
+        };
+        match removed {
+            Ok(()) => self.bytes,
+            Err(e) => {
+                log::info!("Could not evict {}: {e}", self.path.display());
+                0
+            }
+        }
+    }
+}
+
+/// The entries of one cache folder; a missing folder is an empty cache.
+/// Dot files are temporary files still being written.
+fn entries(dir: &Path) -> Result<Vec<Entry>, CpresError> {
+    let read = match std::fs::read_dir(dir) {
+        Ok(read) => read,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut entries = Vec::new();
+    for dir_entry in read {
+        let dir_entry = dir_entry?;
+        if dir_entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = dir_entry.path();
+        let (bytes, used) = usage(&path)?;
+        entries.push(Entry { path, bytes, used });
+    }
+    Ok(entries)
+}
+
+/// Size of a file or folder, and when it or anything in it was last changed
+fn usage(path: &Path) -> Result<(u64, SystemTime), CpresError> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    let mut used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    if !metadata.is_dir() {
+        return Ok((metadata.len(), used));
+    }
+    let mut bytes = 0;
+    for dir_entry in std::fs::read_dir(path)? {
+        let (entry_bytes, entry_used) = usage(&dir_entry?.path())?;
+        bytes += entry_bytes;
+        used = used.max(entry_used);
+    }
+    Ok((bytes, used))
+}
+
+/// Trim the caches now and every half hour after
+pub fn init(app: &AppHandle) {
+    let handle = app.clone();
+    std::thread::spawn(move || loop {
+        match CacheDirs::new(&handle) {
+            Ok(dirs) => {
+                if let Err(e) = dirs.trim() {
+                    log::warn!("Could not trim the caches: {e}");
+                }
+            }
+            Err(e) => log::warn!("Could not find the caches: {e}"),
+        }
+        std::thread::sleep(TRIM_INTERVAL);
+    });
+}
@@ -0,0 +1,427 @@
+//! Subtitle files and a clock that shows their cues in time with a video
+//!
+//! SRT and WebVTT files are read into a list of cues; the format is told by
+//! the `WEBVTT` header, and files that aren't UTF-8 are read as
+//! Windows-1252, which older SRT files often are. Styling tags are dropped,
+//! since the cues are drawn by the output's own text style.
+//!
+//! The clock runs on its own thread so a second output can show the cues
+//! (a translation, say) without being tied to the window playing the video.
+//! The frontend calls `sync` whenever the video plays, pauses, seeks, or
+//! changes speed, and from time to time while it plays to correct drift;
+//! between calls the clock runs on its own. It emits `captions:cue` with the
+//! cues showing each time they change.
+
+use crate::cpres::CpresError;
+use crate::importer;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const CUE_EVENT: &str = "captions:cue";
+
+/// Longest the clock sleeps while playing, so a missed `sync` can't leave a
+/// cue up for long
+const MAX_TICK: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Cue {
+    /// Position in the file, counting from 0
+    pub index: usize,
+    /// Seconds from the start of the video
+    pub start: f64,
+    pub end: f64,
+    /// Lines separated by `\n`
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptionTrack {
+    pub id: String,
+    pub path: String,
+    /// "srt" or "vtt"
+    pub format: String,
+    /// From the file name ("sermon.es.srt") or the WebVTT header, when given
+    pub language: Option<String>,
+    /// By start time
+    pub cues: Vec<Cue>,
+}
+
+/// Payload of `captions:cue`
+#[derive(Debug, Clone, Serialize)]
+pub struct CueChange {
+    pub track_id: String,
+    /// Cues showing now, usually one; empty between cues
+    pub cues: Vec<Cue>,
+    /// Seconds into the video
+    pub position: f64,
+}
+
+/// Read the SRT or WebVTT file at `path`
+pub fn load(path: &Path) -> Result<CaptionTrack, CpresError> {
+    let data = std::fs::read(path)?;
+    let content = match String::from_utf8(data) {
+        Ok(content) => content,
+        Err(e) => e
+            .into_bytes()
+            .into_iter()
+            .map(importer::cp1252_char)
+            .collect(),
+    };
+    let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let vtt = content.starts_with("WEBVTT");
+    let cues = parse(&content);
+    if cues.is_empty() {
+        return Err(CpresError::InvalidBundle(format!(
+            "No captions found in {}",
+            path.display()
+        )));
+    }
+
+    let header_language = vtt
+        .then(|| {
+            content
+                .lines()
+                .take_while(|line| !line.trim().is_empty())
+                .find_map(|line| line.strip_prefix("Language:"))
+                .map(|language| language.trim().to_string())
+        })
+        .flatten();
+    // "sermon.es.srt" or "sermon.pt-BR.vtt"
+    let name_language = path
+        .file_stem()
+        .and_then(|stem| Path::new(stem).extension())
+        .and_then(|language| language.to_str())
+        .filter(|language| {
+            (2..=8).contains(&language.len())
+                && language
+                    .chars()
+                    .all(|c| c.is_ascii_alphabetic() || c == '-')
+        })
+        .map(String::from);
+    Ok(CaptionTrack {
+        id: uuid::Uuid::new_v4().to_string(),
+        path: path.to_string_lossy().to_string(),
+        format: if vtt { "vtt" } else { "srt" }.to_string(),
+        language: header_language.or(name_language),
+        cues,
+    })
+}
+
+/// Cues of an SRT or WebVTT document with `\n` line endings, by start time
+fn parse(content: &str) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    for block in content.split("\n\n") {
+        let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+        // Blocks without a timing line are SRT numbers on their own, the
+        // WebVTT header, and NOTE, STYLE, and REGION blocks
+        let Some(timing) = lines.next() else {
+            continue;
+        };
+        let Some((start, end)) = timing.split_once("-->") else {
+            continue;
+        };
+        // WebVTT cue settings ("align:start") follow the end time
+        let end = end.split_whitespace().next().unwrap_or_default();
+        let (Some(start), Some(end)) = (parse_timestamp(start), parse_timestamp(end)) else {
+            continue;
+        };
+        let text: Vec<String> = lines
+            .map(strip_tags)
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect();
+        if text.is_empty() || end <= start {
+            continue;
+        }
+        cues.push(Cue {
+            index: 0,
+            start,
+            end,
+            text: text.join("\n"),
+        });
+    }
+    cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+    for (index, cue) in cues.iter_mut().enumerate() {
+        cue.index = index;
+    }
+    cues
+}
+
+/// "01:02:03,456" (SRT), "01:02:03.456" or "02:03.456" (WebVTT) to seconds
+fn parse_timestamp(stamp: &str) -> Option<f64> {
+    let stamp = stamp.trim().replace(',', ".");
+    let parts: Vec<&str> = stamp.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+    let mut seconds = 0.0;
+    for part in parts {
+        let value: f64 = part.parse().ok()?;
+        if value < 0.0 {
+            return None;
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Some(seconds)
+}
+
+/// A cue line without its `<i>`, `<c.yellow>`, `<v Speaker>`, or `{\an8}`
+/// tags, and with WebVTT's character references decoded
+fn strip_tags(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut closing = None;
+    for c in line.chars() {
+        match (closing, c) {
+            (None, '<') => closing = Some('>'),
+            (None, '{') => closing = Some('}'),
+            (Some(end), c) if c == end => closing = None,
+            (None, c) => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&amp;", "&")
+}
+
+/// Managed state: the track being shown and where its video is
+#[derive(Default)]
+pub struct CaptionClock {
+    state: Mutex<ClockState>,
+    /// Wakes the worker when the clock is synced or stopped
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct ClockState {
+    track: Option<Arc<CaptionTrack>>,
+    /// Video position at `anchor`
+    position: f64,
+    /// When `position` was taken; None while paused
+    anchor: Option<Instant>,
+    rate: f64,
+    /// Indexes of the cues last emitted
+    showing: Vec<usize>,
+    worker: bool,
+}
+
+impl ClockState {
+    fn now(&self) -> f64 {
+        match self.anchor {
+            Some(anchor) => self.position + anchor.elapsed().as_secs_f64() * self.rate,
+            None => self.position,
+        }
+    }
+}
+
+impl CaptionClock {
+    /// Show `track`, paused at the start, in place of any other
+    pub fn load(&self, app: &AppHandle, track: CaptionTrack) -> Result<(), CpresError> {
+        let mut state = self.lock()?;
+        state.track = Some(Arc::new(track));
+        state.position = 0.0;
+        state.anchor = None;
+        state.rate = 1.0;
+        if !state.worker {
+            state.worker = true;
+            let handle = app.clone();
+            std::thread::spawn(move || run_clock(&handle));
+        }
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    /// The video is at `position` seconds, and playing at `rate` or paused
+    pub fn sync(&self, position: f64, playing: bool, rate: f64) -> Result<(), CpresError> {
+        let mut state = self.lock()?;
+        state.position = position.max(0.0);
+        state.anchor = playing.then(Instant::now);
+        state.rate = if rate > 0.0 { rate } else { 1.0 };
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    /// Stop showing captions; the worker emits an empty cue list and exits
+    pub fn stop(&self) -> Result<(), CpresError> {
+        let mut state = self.lock()?;
+        state.track = None;
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, ClockState>, CpresError> {
+        self.state
+            .lock()
+            .map_err(|e| CpresError::InvalidBundle(e.to_string()))
+    }
+}
+
+/// Emit cue changes until the track is stopped
+fn run_clock(app: &AppHandle) {
+    let clock = app.state::<CaptionClock>();
+    let Ok(mut state) = clock.lock() else {
+        return;
+    };
+    let mut last_track = None;
+    loop {
+        let Some(track) = state.track.clone() else {
+            if let Some(track_id) = last_track {
+                let _ = app.emit(
+                    CUE_EVENT,
+                    CueChange {
+                        track_id,
+                        cues: Vec::new(),
+                        position: state.now(),
+                    },
+                );
+            }
+            state.worker = false;
+            return;
+        };
+        let position = state.now();
+        let showing: Vec<&Cue> = track
+            .cues
+            .iter()
+            .take_while(|cue| cue.start <= position)
+            .filter(|cue| cue.end > position)
+            .collect();
+        let indexes: Vec<usize> = showing.iter().map(|cue| cue.index).collect();
+        // A new track replaces whatever the last one showed, even nothing
+        let new_track = last_track.as_deref() != Some(track.id.as_str());
+        if indexes != state.showing || new_track {
+            last_track = Some(track.id.clone());
+            state.showing = indexes;
+            let _ = app.emit(
+                CUE_EVENT,
+                CueChange {
+                    track_id: track.id.clone(),
+                    cues: showing.into_iter().cloned().collect(),
+                    position,
+                },
+            );
+        }
+
+        // Sleep until the next cue starts or ends, or until synced
+        state = if state.anchor.is_some() {
+            let next = track
+                .cues
+                .iter()
+                .flat_map(|cue| [cue.start, cue.end])
+                .filter(|time| *time > position)
+                .min_by(f64::total_cmp);
+            let wait = next
+                .map(|time| Duration::from_secs_f64((time - position) / state.rate))
+                .unwrap_or(MAX_TICK)
+                .min(MAX_TICK);
+            match clock.changed.wait_timeout(state, wait) {
+                Ok((state, _)) => state,
+                Err(_) => return,
+            }
+        } else {
+            match clock.changed.wait(state) {
+                Ok(state) => state,
+                Err(_) => return,
+            }
+        };
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(CaptionClock::default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_tags() {
+        let cases = [
+            ("<i>Amazing</i> grace", "Amazing grace"),
+            ("<c.yellow>how</c> sweet", "how sweet"),
+            ("<v Pastor>the sound", "the sound"),
+            ("{\\an8}that saved", "that saved"),
+            ("a &lt;wretch&gt; &amp; me", "a <wretch> & me"),
+            ("&amp;lt;", "&lt;"),
+            ("no&nbsp;break", "no\u{a0}break"),
+            ("<b unclosed", ""),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(strip_tags(line), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn parses_srt() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,500\n<i>Amazing grace</i>\nhow sweet\n\n\
+                   3\n00:00:05,000 --> 00:00:06,000\nthe sound\n\n\
+                   2\n00:00:03,000 --> 00:00:04,000\nthat saved\n\n\
+                   4\n00:00:07,000 --> 00:00:07,000\nno time\n\n\
+                   5\n00:00:08,000 --> 00:00:09,000\n<i></i>\n\n\
+                   6\nnot a timing line\n";
+        let cues = parse(srt);
+        let summary: Vec<(usize, f64, f64, &str)> = cues
+            .iter()
+            .map(|cue| (cue.index, cue.start, cue.end, cue.text.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0, 1.0, 2.5, "Amazing grace\nhow sweet"),
+                (1, 3.0, 4.0, "that saved"),
+                (2, 5.0, 6.0, "the sound"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_webvtt() {
+        let vtt = "WEBVTT\nLanguage: es\n\n\
+                   NOTE this --> is not a cue\n\n\
+                   STYLE\n::cue { color: yellow }\n\n\
+                   intro\n00:01.000 --> 00:02.000 align:start position:10%\n<v Choir>Sublime gracia\n\n\
+                   00:00:03.000 --> 00:00:04.000\ndel Senor\n";
+        let cues = parse(vtt);
+        let summary: Vec<(f64, f64, &str)> = cues
+            .iter()
+            .map(|cue| (cue.start, cue.end, cue.text.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [(1.0, 2.0, "Sublime gracia"), (3.0, 4.0, "del Senor")]
+        );
+    }
+
+    #[test]
+    fn loads_language_and_windows_1252() {
+        let dir = tempfile::tempdir().unwrap();
+        let srt = dir.path().join("sermon.pt-BR.srt");
+        std::fs::write(
+            &srt,
+            b"1\r\n00:00:01,000 --> 00:00:02,000\r\nGra\xe7a \x93sublime\x94\r\n",
+        )
+        .unwrap();
+        let track = load(&srt).unwrap();
+        assert_eq!(track.format, "srt");
+        assert_eq!(track.language.as_deref(), Some("pt-BR"));
+        assert_eq!(track.cues[0].text, "Gra\u{e7}a \u{201c}sublime\u{201d}");
+
+        let vtt = dir.path().join("sermon.vtt");
+        std::fs::write(
+            &vtt,
+            "\u{feff}WEBVTT\nLanguage: es\n\n00:01.000 --> 00:02.000\nHola\n",
+        )
+        .unwrap();
+        let track = load(&vtt).unwrap();
+        assert_eq!(track.format, "vtt");
+        assert_eq!(track.language.as_deref(), Some("es"));
+
+        let empty = dir.path().join("empty.srt");
+        std::fs::write(&empty, "WEBVTT\n\nNOTE nothing here\n").unwrap();
+        assert!(load(&empty).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Per-entry checksums for .cpres bundles
+//!
+//! Every save writes `checksums.json` with the sha256 of each other entry's
+//! uncompressed content. Verifying it on open catches bundles that were
+//! truncated or corrupted by cloud sync before the damage reaches a service.
+//! Bundles written before checksums existed simply have no entry and pass.
+
+use crate::bundle_reader;
+use crate::cpres::{self, CpresError};
+use crate::tasks::CancelToken;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+pub const CHECKSUMS_FILE: &str = "checksums.json";
+const ALGORITHM: &str = "sha256";
+/// Read size when streaming media into an archive
+pub(crate) const COPY_CHUNK_BYTES: usize = 256 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChecksumsFile {
+    algorithm: String,
+    /// Entry name -> hex digest
+    files: BTreeMap<String, String>,
+}
+
+/// Digests collected while writing a bundle
+#[derive(Default)]
+pub struct Checksums {
+    files: BTreeMap<String, String>,
+}
+
+impl Checksums {
+    pub fn add(&mut self, name: &str, data: &[u8]) {
+        self.files
+            .insert(name.to_string(), hex::encode(Sha256::digest(data)));
+    }
+
+    /// Write an entry to `zip` and record its digest
+    pub fn write_entry(
+        &mut self,
+        zip: &mut ZipWriter<File>,
+        name: &str,
+        data: &[u8],
+        options: SimpleFileOptions,
+    ) -> Result<(), CpresError> {
+        zip.start_file(name, options)?;
+        zip.write_all(data)?;
+        self.add(name, data);
+        Ok(())
+    }
+
+    /// Stream `reader` into a new entry of `zip` in chunks, recording its digest.
+    /// Returns the number of bytes written; stops between chunks once `cancel` fires.
+    pub fn copy_entry(
+        &mut self,
+        zip: &mut ZipWriter<File>,
+        name: &str,
+        reader: &mut impl Read,
+        options: SimpleFileOptions,
+        cancel: &CancelToken,
+    ) -> Result<u64, CpresError> {
+        zip.start_file(name, options)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; COPY_CHUNK_BYTES];
+        let mut written = 0;
+        loop {
+            cancel.check()?;
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            hasher.update(&buffer[..read]);
+            zip.write_all(&buffer[..read])?;
+            written += read as u64;
+        }
+        self.files
+            .insert(name.to_string(), hex::encode(hasher.finalize()));
+        Ok(written)
+    }
+
+    /// Hash an entry of another archive that is about to be raw-copied
+    pub fn add_from_archive(
+        &mut self,
+        archive: &mut ZipArchive<impl Read + Seek>,
+        index: usize,
+        name: &str,
+    ) -> Result<(), CpresError> {
+        let digest = entry_digest(archive, index)?;
+        self.files.insert(name.to_string(), digest);
+        Ok(())
+    }
+
+    /// Write `checksums.json` as the final entry
+    pub fn write(
+        self,
+        zip: &mut ZipWriter<File>,
+        options: SimpleFileOptions,
+    ) -> Result<(), CpresError> {
+        let file = ChecksumsFile {
+            algorithm: ALGORITHM.to_string(),
+            files: self.files,
+        };
+        zip.start_file(CHECKSUMS_FILE, options)?;
+        zip.write_all(serde_json::to_string_pretty(&file)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChecksumReport {
+    /// False for bundles written before checksums were added
+    pub present: bool,
+    pub verified: u64,
+    /// Entries whose content doesn't match the recorded digest
+    pub mismatched: Vec<String>,
+    /// Entries listed in checksums.json but absent from the archive
+    pub missing: Vec<String>,
+    /// Entries in the archive that checksums.json doesn't cover
+    pub unlisted: Vec<String>,
+}
+
+impl ChecksumReport {
+    pub fn is_intact(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Recompute every entry's digest and compare against `checksums.json`
+pub fn verify_bundle(path: &Path) -> Result<ChecksumReport, CpresError> {
+    let mut archive = bundle_reader::open_archive(path)?;
+    let mut report = ChecksumReport {
+        present: false,
+        verified: 0,
+        mismatched: Vec::new(),
+        missing: Vec::new(),
+        unlisted: Vec::new(),
+    };
+
+    if archive.index_for_name(CHECKSUMS_FILE).is_none() {
+        return Ok(report);
+    }
+    let recorded: ChecksumsFile =
+        serde_json::from_str(&cpres::read_zip_file(&mut archive, CHECKSUMS_FILE)?)?;
+    if recorded.algorithm != ALGORITHM {
+        return Err(CpresError::InvalidBundle(format!(
+            "Unsupported checksum algorithm: {}",
+            recorded.algorithm
+        )));
+    }
+    report.present = true;
+
+    for (name, expected) in &recorded.files {
+        let Some(index) = archive.index_for_name(name) else {
+            report.missing.push(name.clone());
+            continue;
+        };
+        // A damaged entry can also fail to decompress; count that as a mismatch
+        match entry_digest(&mut archive, index) {
+            Ok(digest) if digest == *expected => report.verified += 1,
+            _ => report.mismatched.push(name.clone()),
+        }
+    }
+
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        let name = entry.name();
+        if !entry.is_dir() && name != CHECKSUMS_FILE && !recorded.files.contains_key(name) {
+            report.unlisted.push(name.to_string());
+        }
+    }
+
+    Ok(report)
+}
+
+/// The digest `checksums.json` records for an entry, without reading the entry;
+/// None for bundles without checksums or entries they don't list
+pub fn recorded_digest(
+    archive: &mut ZipArchive<impl Read + Seek>,
+    name: &str,
+) -> Result<Option<String>, CpresError> {
+    if archive.index_for_name(CHECKSUMS_FILE).is_none() {
+        return Ok(None);
+    }
+    let recorded: ChecksumsFile =
+        serde_json::from_str(&cpres::read_zip_file(archive, CHECKSUMS_FILE)?)?;
+    if recorded.algorithm != ALGORITHM {
+        return Ok(None);
+    }
+    Ok(recorded.files.get(name).cloned())
+}
+
+fn entry_digest(
+    archive: &mut ZipArchive<impl Read + Seek>,
+    index: usize,
+) -> Result<String, CpresError> {
+    let mut entry = archive.by_index(index)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut entry, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
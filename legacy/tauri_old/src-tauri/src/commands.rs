@@ -1,11 +1,103 @@
 //! Tauri commands for the Church Presenter app
 
-use crate::cpres::{self, BundleState, FontEntry, MediaEntry, ParsedBundle};
-use font_kit::handle::Handle;
-use font_kit::properties::Style;
+use crate::audio_engine::{
+    self, AudioDevice, AudioEngine, AudioStatus, AudioTrack, PlaylistOptions,
+};
+use crate::audio_extract::{self, AudioFormat, ExtractProgress};
+use crate::audio_routing::{AudioContext, AudioRoutes, AudioRouting, AUDIO_ROUTES_EVENT};
+use crate::autosave::{AutosaveConfig, AutosaveState, RecoveryFile, RecoveryListing};
+use crate::bundle_lock::{self, LockRegistry, LockState, LockStatus};
+use crate::cache::{CacheDirs, CacheKind, CacheStats};
+use crate::captions::{self, CaptionClock, CaptionTrack};
+use crate::checksums::{self, ChecksumReport};
+use crate::compatibility::{self, CompatibilityReport, MachineProfile, Resolution};
+use crate::cpres::{self, BundleState, CpresError, FontEntry, MediaImport, ParsedBundle};
+use crate::cpserv::{self, OpenedService, ServiceDocument};
+use crate::decklink::{self, DeckLinkFormat, DeckLinkOutput, DeckLinkPlayout};
+use crate::diagnostics::{self, DiagnosticsReport};
+use crate::diff::{self, BundleDiff};
+use crate::download::{self, CachedFile, Credentials};
+use crate::easyworship::{self, EasyWorshipScheduleImport, EasyWorshipSong};
+use crate::edge_blend::{OutputSpan, OutputSpans, SpanSlice, OUTPUT_SPAN_EVENT};
+use crate::error::AppError;
+use crate::exploded;
+use crate::export::{
+    self, ExportReport, ImageFormat, ImageSequenceReport, PdfOptions, VideoOptions, VideoReport,
+};
+use crate::extract::{self, ExtractReport};
+use crate::font_coverage::{self, FontCoverage, FontCoverageOptions};
+use crate::font_details::{self, FontDetails};
+use crate::font_install::{self, FontInstall, InstallScope};
+use crate::font_preview::{self, FontPreview, FontPreviewOptions};
+use crate::google_fonts::{self, GoogleFont, GoogleFontSearchOptions, InstalledGoogleFont};
+use crate::history::{self, BundleVersion};
+use crate::image_optimize::{ImageOptimizeOptions, ImageOptimizer};
+use crate::importer::{self, LibraryImport};
+use crate::keep_awake;
+use crate::loudness::{AudioNormalizer, LoudnessOptions};
+use crate::media_download::{DownloadJob, MediaDownloadOptions, MediaDownloads};
+use crate::media_library::{
+    self, Collection, Consolidation, DuplicateGroup, Folder, MediaItem, MediaLibrary, MediaPage,
+    MediaQuery, ScanReport, SmartCollection, TagCount, MEDIA_LIBRARY_DIR_NAME,
+};
+use crate::media_watch;
+use crate::merge;
+use crate::missing_fonts::{self, MissingFontReport};
+use crate::monitor_watch::{
+    OutputGeometry, OutputMonitors, OutputTransform, OUTPUT_MIRROR_EVENT, OUTPUT_TRANSFORM_EVENT,
+};
+use crate::ndi::{NdiOptions, NdiOutput, NdiStatus};
+use crate::openlyrics;
+use crate::output_color::{OutputColor, OutputColors, OUTPUT_COLOR_EVENT};
+use crate::output_frames::OutputSignal;
+use crate::output_guard::OutputGuard;
+use crate::output_health::OutputHealth;
+use crate::palette::{self, Palette};
+use crate::pdf_import::{self, PdfImportOptions};
+use crate::pointer_confine::PointerConfinement;
+use crate::pptx;
+use crate::program::{LiveState, Program, ProgramPresentation, ProgramScreen, ProgramState};
+use crate::propresenter;
+use crate::proxy::{self, MediaContext, Proxy, ProxyProgress, ResolvedSource};
+use crate::prune::{self, PruneReport};
+use crate::recorder::{Recorder, RecordingOptions, RecordingSource, RecordingStatus};
+use crate::remote_server::{RemoteOptions, RemoteServer, RemoteStatus};
+use crate::screen_capture::{self, OutputCapture, Region};
+use crate::search::{
+    self, LibraryReplaceReport, LibrarySearchReport, Pattern, ReplaceResult, TextMatch,
+};
+use crate::search_index::{self, SearchHit, SearchIndex};
+use crate::song_import;
+use crate::stats::{self, BundleStats};
+use crate::stock_media::{
+    self, ProviderStatus, StockDownload, StockProvider, StockSearchOptions, StockSearchPage,
+};
+use crate::storage::{self, StorageStatus};
+use crate::svg::{self, RasterizedSvg};
+use crate::system_fonts::{self, SystemFontInfo, SystemFonts};
+use crate::tasks::TaskRegistry;
+use crate::test_pattern::{self, OutputOverlay, TestPattern, OUTPUT_OVERLAY_EVENT};
+use crate::text_import::{self, TextImportOptions};
+use crate::texture_share::{SharedOutput, TextureShareOptions, TextureSharing};
+use crate::theme_pack::{self, ThemePackImport, ThemePackSummary};
+use crate::thumbnails::{self, BundleMedia, Thumbnail, ThumbnailCache};
+use crate::transcode::{TranscodeJob, TranscodePreset, TranscodeQueue};
+use crate::video_player::{PlaybackOptions, VideoPlayers, VideoSource, VideoStatus};
+use crate::video_thumbnails::{self, VideoThumbnailOptions, VideoThumbnails};
+use crate::virtual_output::{
+    VirtualOutput, VirtualOutputOptions, VirtualOutputStatus, VIRTUAL_OUTPUT_LABEL,
+};
+use crate::waveform::{self, Waveform};
+#[cfg(target_os = "macos")]
+use core_graphics::display::CGDisplay;
 use font_kit::source::SystemSource;
+#[cfg(target_os = "macos")]
+use objc2_app_kit::NSScreen;
+#[cfg(target_os = "macos")]
+use objc2_foundation::{NSNumber, NSString};
+use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 use tauri_plugin_fs::FsExt;
 #[cfg(target_os = "windows")]
 use windows::core::PCWSTR;
@@ -13,616 +105,3662 @@ use windows::core::PCWSTR;
 use windows::Win32::Graphics::Gdi::{
     EnumDisplayDevicesW, EnumDisplaySettingsW, DEVMODEW, DISPLAY_DEVICEW, ENUM_CURRENT_SETTINGS,
 };
+#[cfg(target_os = "windows")]
+use windows::Win32::UI::WindowsAndMessaging::EDD_GET_DEVICE_INTERFACE_NAME;
 
-/// Open a .cpres presentation bundle
+/// Open a .cpres presentation bundle; `for_write` also takes its advisory lock
+/// and `verify` checks every entry against checksums.json first
 #[tauri::command]
-pub async fn cpres_open(path: String) -> Result<ParsedBundle, String> {
-    let path = PathBuf::from(path);
-    cpres::open_bundle(&path).map_err(|e| e.to_string())
+pub async fn cpres_open(
+    locks: tauri::State<'_, LockRegistry>,
+    path: String,
+    for_write: Option<bool>,
+    verify: Option<bool>,
+) -> Result<ParsedBundle, AppError> {
+    diagnostics::traced("cpres_open", async move {
+        let path = PathBuf::from(path);
+        if verify.unwrap_or(false) {
+            let report = checksums::verify_bundle(&path)?;
+            if !report.is_intact() {
+                let damaged: Vec<String> = report
+                    .mismatched
+                    .into_iter()
+                    .chain(report.missing)
+                    .collect();
+                return Err(CpresError::Corrupted(damaged.join(", ")).into());
+            }
+        }
+        let bundle = cpres::open_bundle(&path)?;
+        if for_write.unwrap_or(false) {
+            locks.acquire(&path, false)?;
+        }
+        Ok(bundle)
+    })
+    .await
 }
 
-/// Save a presentation bundle atomically
+/// A bundle fetched from a URL, with where it was cached
+#[derive(serde::Serialize)]
+pub struct RemoteBundle {
+    pub file: CachedFile,
+    pub bundle: ParsedBundle,
+}
+
+/// Check every entry of a bundle against its checksums.json
 #[tauri::command]
-pub async fn cpres_save(path: String, state: BundleState) -> Result<(), String> {
-    let path = PathBuf::from(path);
-    cpres::save_bundle(&path, &state).map_err(|e| e.to_string())
+pub async fn cpres_verify(path: String) -> Result<ChecksumReport, AppError> {
+    diagnostics::traced("cpres_verify", async move {
+        let path = PathBuf::from(path);
+        checksums::verify_bundle(&path).map_err(AppError::from)
+    })
+    .await
 }
 
-/// Read media from a bundle as base64
+/// Download (or revalidate a cached copy of) a bundle from a URL and open it
 #[tauri::command]
-pub async fn cpres_read_media(bundle_path: String, media_path: String) -> Result<Vec<u8>, String> {
-    let path = PathBuf::from(bundle_path);
-    cpres::read_bundle_media(&path, &media_path).map_err(|e| e.to_string())
+pub async fn cpres_open_url(
+    app: tauri::AppHandle,
+    url: String,
+    credentials: Option<Credentials>,
+) -> Result<RemoteBundle, AppError> {
+    diagnostics::traced("cpres_open_url", async move {
+        let app_data_dir = app.path().app_data_dir()?;
+        let file = download::fetch_cached(
+            &app,
+            &download::cache_root(&app_data_dir),
+            &url,
+            credentials.as_ref(),
+        )
+        .await?;
+        let bundle = cpres::open_bundle(Path::new(&file.path))?;
+        Ok(RemoteBundle { file, bundle })
+    })
+    .await
 }
 
-/// Import media files and compute their metadata/hashes
+/// Save a presentation bundle atomically
 #[tauri::command]
-pub async fn cpres_import_media(paths: Vec<String>) -> Result<Vec<MediaEntry>, String> {
-    let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
-    cpres::import_media_files(&paths).map_err(|e| e.to_string())
+pub async fn cpres_save(
+    autosave: tauri::State<'_, AutosaveState>,
+    locks: tauri::State<'_, LockRegistry>,
+    tasks: tauri::State<'_, TaskRegistry>,
+    path: String,
+    state: BundleState,
+    keep_versions: Option<usize>,
+    task_id: Option<String>,
+) -> Result<(), AppError> {
+    diagnostics::traced("cpres_save", async move {
+        let task = tasks.start(task_id);
+        let path = PathBuf::from(path);
+        let keep = keep_versions.unwrap_or(history::DEFAULT_KEEP_VERSIONS);
+        // Refuses to overwrite a bundle another machine is editing
+        locks.acquire(&path, false)?;
+        history::snapshot(&path, keep)?;
+        cpres::save_bundle_cancellable(&path, &state, task.token())?;
+        Ok(autosave.mark_saved(&path, &state)?)
+    })
+    .await
 }
 
-/// Import font files and compute their metadata/hashes
+/// Open a .cpserv service playlist and resolve its presentation paths
 #[tauri::command]
-pub async fn cpres_import_fonts(paths: Vec<String>) -> Result<Vec<FontEntry>, String> {
-    let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
-    cpres::import_font_files(&paths).map_err(|e| e.to_string())
+pub async fn cpserv_open(path: String) -> Result<OpenedService, AppError> {
+    diagnostics::traced("cpserv_open", async move {
+        let path = PathBuf::from(path);
+        cpserv::open_service(&path).map_err(AppError::from)
+    })
+    .await
 }
 
-#[derive(serde::Serialize)]
-pub struct SystemFontInfo {
-    pub family: String,
-    pub full_name: String,
-    pub postscript_name: Option<String>,
-    pub path: String,
-    pub weight: u16,
-    pub style: String,
-}
-
-/// List installed system fonts with metadata and file paths
-#[tauri::command]
-pub async fn cpres_list_system_fonts() -> Result<Vec<SystemFontInfo>, String> {
-    let source = SystemSource::new();
-    let handles = source
-        .all_fonts()
-        .map_err(|e| format!("Failed to list fonts: {e}"))?;
-
-    let mut fonts = Vec::new();
-    for handle in handles {
-        let path = match &handle {
-            Handle::Path { path, .. } => path,
-            _ => continue,
-        };
+/// Save a .cpserv service playlist; presentation paths are stored relative to it
+#[tauri::command]
+pub async fn cpserv_save(path: String, service: ServiceDocument) -> Result<(), AppError> {
+    diagnostics::traced("cpserv_save", async move {
+        let path = PathBuf::from(path);
+        cpserv::save_service(&path, &service).map_err(AppError::from)
+    })
+    .await
+}
 
-        let font = match handle.load() {
-            Ok(font) => font,
-            Err(_) => continue,
-        };
+/// Convert a ProPresenter 6 (.pro6) or 7 (.pro) document into an unsaved bundle
+#[tauri::command]
+pub async fn import_pro_presenter(path: String) -> Result<BundleState, AppError> {
+    diagnostics::traced("import_pro_presenter", async move {
+        let path = PathBuf::from(path);
+        propresenter::import_pro_presenter(&path).map_err(AppError::from)
+    })
+    .await
+}
 
-        let properties = font.properties();
-        let style = match properties.style {
-            Style::Italic | Style::Oblique => "italic",
-            _ => "normal",
-        }
-        .to_string();
+/// Convert a PowerPoint (.pptx) deck into an unsaved bundle
+#[tauri::command]
+pub async fn import_pptx(app: tauri::AppHandle, path: String) -> Result<BundleState, AppError> {
+    diagnostics::traced("import_pptx", async move {
+        let path = PathBuf::from(path);
+        let cache_dir = app.path().app_cache_dir()?;
+        pptx::import_pptx(&path, &importer::staging_dir(&cache_dir)).map_err(AppError::from)
+    })
+    .await
+}
 
-        fonts.push(SystemFontInfo {
-            family: font.family_name(),
-            full_name: font.full_name(),
-            postscript_name: font.postscript_name(),
-            path: path.to_string_lossy().to_string(),
-            weight: properties.weight.0 as u16,
-            style,
-        });
-    }
+/// Convert the pages of a PDF into an unsaved bundle with one slide per page
+#[tauri::command]
+pub async fn import_pdf(
+    app: tauri::AppHandle,
+    tasks: tauri::State<'_, TaskRegistry>,
+    path: String,
+    options: Option<PdfImportOptions>,
+    task_id: Option<String>,
+) -> Result<BundleState, AppError> {
+    diagnostics::traced("import_pdf", async move {
+        let task = tasks.start(task_id);
+        let cache_dir = app.path().app_cache_dir()?;
+        pdf_import::import_pdf(
+            Path::new(&path),
+            &importer::staging_dir(&cache_dir),
+            &options.unwrap_or_default(),
+            task.token(),
+        )
+        .map_err(AppError::from)
+    })
+    .await
+}
 
-    fonts.sort_by(|a, b| {
-        a.family
-            .cmp(&b.family)
-            .then_with(|| a.full_name.cmp(&b.full_name))
-    });
+/// Import every song in an OpenLP songs.sqlite database as .cpres files in `output_dir`
+#[tauri::command]
+pub async fn import_openlp(
+    database: String,
+    output_dir: String,
+) -> Result<LibraryImport, AppError> {
+    diagnostics::traced("import_openlp", async move {
+        song_import::import_openlp(Path::new(&database), Path::new(&output_dir))
+            .map_err(AppError::from)
+    })
+    .await
+}
 
-    Ok(fonts)
+/// Import an OpenSong song file, or a folder of them, as .cpres files in `output_dir`
+#[tauri::command]
+pub async fn import_opensong(path: String, output_dir: String) -> Result<LibraryImport, AppError> {
+    diagnostics::traced("import_opensong", async move {
+        song_import::import_opensong(Path::new(&path), Path::new(&output_dir))
+            .map_err(AppError::from)
+    })
+    .await
 }
 
-const CONTENT_DIR_CONFIG_FILENAME: &str = "content_dir.json";
-const MEDIA_LIBRARY_DIR_NAME: &str = "media-library";
+/// Import OpenLyrics songs from an .xml file or a folder of them into `output_dir`
+#[tauri::command]
+pub async fn import_openlyrics(
+    path: String,
+    output_dir: String,
+) -> Result<LibraryImport, AppError> {
+    diagnostics::traced("import_openlyrics", async move {
+        openlyrics::import_openlyrics(Path::new(&path), Path::new(&output_dir))
+            .map_err(AppError::from)
+    })
+    .await
+}
 
-#[cfg(target_os = "windows")]
-fn get_monitor_friendly_name(device_name: &str) -> Option<String> {
-    let device_name_w: Vec<u16> = device_name.encode_utf16().chain(std::iter::once(0)).collect();
-    let mut display_device = DISPLAY_DEVICEW::default();
-    display_device.cb = std::mem::size_of::<DISPLAY_DEVICEW>() as u32;
+/// Convert the songs of an EasyWorship 6/7 profile (or its Databases/Data folder) into unsaved bundles
+#[tauri::command]
+pub async fn import_easyworship(dir: String) -> Result<Vec<EasyWorshipSong>, AppError> {
+    diagnostics::traced("import_easyworship", async move {
+        easyworship::import_easyworship(Path::new(&dir)).map_err(AppError::from)
+    })
+    .await
+}
 
-    let success = unsafe { EnumDisplayDevicesW(PCWSTR(device_name_w.as_ptr()), 0, &mut display_device, 0) }
-        .as_bool();
-    if !success {
-        return None;
-    }
+/// Import the songs of an EasyWorship schedule (.ewsx) into `output_dir`, with a .cpserv playlist of them
+#[tauri::command]
+pub async fn import_easyworship_schedule(
+    schedule: String,
+    output_dir: String,
+) -> Result<EasyWorshipScheduleImport, AppError> {
+    diagnostics::traced("import_easyworship_schedule", async move {
+        easyworship::import_easyworship_schedule(Path::new(&schedule), Path::new(&output_dir))
+            .map_err(AppError::from)
+    })
+    .await
+}
 
-    let len = display_device
-        .DeviceString
-        .iter()
-        .position(|c| *c == 0)
-        .unwrap_or(display_device.DeviceString.len());
-    let friendly = String::from_utf16_lossy(&display_device.DeviceString[..len]).trim().to_string();
-    if friendly.is_empty() {
-        None
-    } else {
-        Some(friendly)
-    }
+/// Convert a plain text or Markdown lyrics file into an unsaved bundle
+#[tauri::command]
+pub async fn import_text(
+    path: String,
+    options: Option<TextImportOptions>,
+) -> Result<BundleState, AppError> {
+    diagnostics::traced("import_text", async move {
+        text_import::import_text(Path::new(&path), &options.unwrap_or_default())
+            .map_err(AppError::from)
+    })
+    .await
 }
 
-#[cfg(target_os = "windows")]
-fn get_monitor_refresh_rate(device_name: &str) -> Option<u32> {
-    let device_name_w: Vec<u16> = device_name.encode_utf16().chain(std::iter::once(0)).collect();
-    let mut devmode = DEVMODEW::default();
-    devmode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+/// Render every slide of a bundle into a PDF at `output`
+#[tauri::command]
+pub async fn export_pdf(
+    tasks: tauri::State<'_, TaskRegistry>,
+    bundle: String,
+    output: String,
+    options: Option<PdfOptions>,
+    task_id: Option<String>,
+) -> Result<ExportReport, AppError> {
+    diagnostics::traced("export_pdf", async move {
+        let task = tasks.start(task_id);
+        export::export_pdf(
+            Path::new(&bundle),
+            Path::new(&output),
+            &options.unwrap_or_default(),
+            task.token(),
+        )
+        .map_err(AppError::from)
+    })
+    .await
+}
 
-    let success =
-        unsafe { EnumDisplaySettingsW(PCWSTR(device_name_w.as_ptr()), ENUM_CURRENT_SETTINGS, &mut devmode) }
-            .as_bool();
-    if !success {
-        return None;
-    }
+/// Render every slide of a bundle to numbered PNG (default) or WebP files in `dir`
+#[tauri::command]
+pub async fn export_images(
+    tasks: tauri::State<'_, TaskRegistry>,
+    bundle: String,
+    dir: String,
+    resolution: Resolution,
+    format: Option<ImageFormat>,
+    task_id: Option<String>,
+) -> Result<ImageSequenceReport, AppError> {
+    diagnostics::traced("export_images", async move {
+        let task = tasks.start(task_id);
+        export::export_images(
+            Path::new(&bundle),
+            Path::new(&dir),
+            resolution,
+            format.unwrap_or_default(),
+            task.token(),
+        )
+        .map_err(AppError::from)
+    })
+    .await
+}
 
-    let freq = devmode.dmDisplayFrequency;
-    if freq == 0 {
-        None
-    } else {
-        Some(freq)
-    }
+/// Render a bundle into an H.264 MP4 at `output` using ffmpeg
+#[tauri::command]
+pub async fn export_video(
+    tasks: tauri::State<'_, TaskRegistry>,
+    bundle: String,
+    output: String,
+    options: Option<VideoOptions>,
+    task_id: Option<String>,
+) -> Result<VideoReport, AppError> {
+    diagnostics::traced("export_video", async move {
+        let task = tasks.start(task_id);
+        export::export_video(
+            Path::new(&bundle),
+            Path::new(&output),
+            &options.unwrap_or_default(),
+            task.token(),
+        )
+        .map_err(AppError::from)
+    })
+    .await
 }
 
-#[cfg(not(target_os = "windows"))]
-fn get_monitor_friendly_name(_device_name: &str) -> Option<String> {
-    None
+/// Write a bundle's lyrics, sections, and arrangement order as an OpenLyrics file
+#[tauri::command]
+pub async fn export_openlyrics(bundle: String, output: String) -> Result<(), AppError> {
+    diagnostics::traced("export_openlyrics", async move {
+        openlyrics::export_openlyrics(Path::new(&bundle), Path::new(&output))
+            .map_err(AppError::from)
+    })
+    .await
 }
 
-#[cfg(not(target_os = "windows"))]
-fn get_monitor_refresh_rate(_device_name: &str) -> Option<u32> {
-    None
+/// Ask the command running under `task_id` to stop; false when no such task is running
+#[tauri::command]
+pub fn cancel_task(
+    tasks: tauri::State<'_, TaskRegistry>,
+    task_id: String,
+) -> Result<bool, AppError> {
+    Ok(tasks.cancel(&task_id))
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
-struct ContentDirConfig {
+/// Report writability, free space, and filesystem type at a bundle's location
+#[tauri::command]
+pub async fn cpres_storage_status(path: String) -> Result<StorageStatus, AppError> {
+    diagnostics::traced("cpres_storage_status", async move {
+        let path = PathBuf::from(path);
+        Ok(storage::storage_status(&path))
+    })
+    .await
+}
+
+/// Report who, if anyone, holds the advisory lock on a bundle
+#[tauri::command]
+pub async fn cpres_lock_status(
+    locks: tauri::State<'_, LockRegistry>,
     path: String,
+) -> Result<LockStatus, AppError> {
+    diagnostics::traced("cpres_lock_status", async move {
+        let path = PathBuf::from(path);
+        locks.status(&path).map_err(AppError::from)
+    })
+    .await
 }
 
-fn content_dir_config_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
-    app.path()
-        .app_data_dir()
-        .map(|dir| dir.join(CONTENT_DIR_CONFIG_FILENAME))
-        .map_err(|e| e.to_string())
+/// Take the advisory lock on a bundle; `force` takes over a lock held elsewhere
+#[tauri::command]
+pub async fn cpres_acquire_lock(
+    locks: tauri::State<'_, LockRegistry>,
+    path: String,
+    force: Option<bool>,
+) -> Result<LockStatus, AppError> {
+    diagnostics::traced("cpres_acquire_lock", async move {
+        let path = PathBuf::from(path);
+        locks
+            .acquire(&path, force.unwrap_or(false))
+            .map_err(AppError::from)
+    })
+    .await
 }
 
-fn read_content_dir_config(app: &tauri::AppHandle) -> Result<Option<PathBuf>, String> {
-    let config_path = content_dir_config_path(app)?;
-    if !config_path.exists() {
-        return Ok(None);
-    }
+/// Release this instance's advisory lock on a bundle (e.g. when it is closed)
+#[tauri::command]
+pub async fn cpres_release_lock(
+    locks: tauri::State<'_, LockRegistry>,
+    path: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("cpres_release_lock", async move {
+        let path = PathBuf::from(path);
+        locks.release(&path).map_err(AppError::from)
+    })
+    .await
+}
 
-    let content = std::fs::read_to_string(&config_path).map_err(|e| e.to_string())?;
-    let parsed: ContentDirConfig = serde_json::from_str(&content).map_err(|e| e.to_string())?;
-    if parsed.path.trim().is_empty() {
-        return Ok(None);
-    }
+/// Save a bundle as a media-free template
+#[tauri::command]
+pub async fn cpres_save_template(path: String, state: BundleState) -> Result<(), AppError> {
+    diagnostics::traced("cpres_save_template", async move {
+        let path = PathBuf::from(path);
+        cpres::save_template(&path, &state).map_err(AppError::from)
+    })
+    .await
+}
 
-    Ok(Some(PathBuf::from(parsed.path)))
+/// Size breakdown and content counts for a bundle
+#[tauri::command]
+pub async fn cpres_stats(path: String) -> Result<BundleStats, AppError> {
+    diagnostics::traced("cpres_stats", async move {
+        let path = PathBuf::from(path);
+        stats::bundle_stats(&path).map_err(AppError::from)
+    })
+    .await
 }
 
-fn write_content_dir_config(app: &tauri::AppHandle, path: &Path) -> Result<(), String> {
-    let config_path = content_dir_config_path(app)?;
-    if let Some(parent) = config_path.parent() {
-        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    }
+/// Merge several bundles (in order) into a new bundle
+#[tauri::command]
+pub async fn cpres_merge(paths: Vec<String>, output: String) -> Result<(), AppError> {
+    diagnostics::traced("cpres_merge", async move {
+        let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
+        let output = PathBuf::from(output);
+        merge::merge_bundles(&paths, &output).map_err(AppError::from)
+    })
+    .await
+}
 
-    let config = ContentDirConfig {
-        path: path.to_string_lossy().to_string(),
-    };
-    let content = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
-    std::fs::write(&config_path, content).map_err(|e| e.to_string())?;
-    Ok(())
+/// Remove media no slide or theme references; `dry_run` only reports what would go
+#[tauri::command]
+pub async fn cpres_prune_media(
+    locks: tauri::State<'_, LockRegistry>,
+    path: String,
+    dry_run: Option<bool>,
+) -> Result<PruneReport, AppError> {
+    diagnostics::traced("cpres_prune_media", async move {
+        let path = PathBuf::from(path);
+        let dry_run = dry_run.unwrap_or(false);
+        let _lock = if dry_run {
+            None
+        } else {
+            Some(locks.hold(&path)?)
+        };
+        prune::prune_media(&path, dry_run).map_err(AppError::from)
+    })
+    .await
 }
 
-/// `legacy/tauri_old/content` — all app content stays in-repo; never use Documents/OneDrive.
-fn repo_content_root_dir() -> Result<PathBuf, String> {
-    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    let candidate = manifest_dir.join("../content");
-    candidate.canonicalize().map_err(|_| {
-        "Repository content root not found: expected legacy/tauri_old/content beside src-tauri."
-            .to_string()
+/// Write every media file in a bundle to `target_dir` under its original filename
+#[tauri::command]
+pub async fn cpres_extract_media(
+    app: tauri::AppHandle,
+    bundle: String,
+    target_dir: String,
+) -> Result<ExtractReport, AppError> {
+    diagnostics::traced("cpres_extract_media", async move {
+        extract::extract_media(&app, Path::new(&bundle), Path::new(&target_dir))
+            .map_err(AppError::from)
     })
+    .await
 }
 
-fn default_bundled_church_presenter_dir() -> Result<PathBuf, String> {
-    let root = repo_content_root_dir()?;
-    let dir = root.join("Church Presenter");
-    let canonical = dir.canonicalize().map_err(|_| {
-        "Bundled content folder missing: legacy/tauri_old/content/Church Presenter".to_string()
-    })?;
-    if canonical.is_dir() {
-        Ok(canonical)
-    } else {
-        Err("Bundled content path exists but is not a directory.".to_string())
-    }
+/// Write a bundle as a folder of sorted, pretty-printed JSON and hash-named media for version control
+#[tauri::command]
+pub async fn cpres_explode(bundle: String, dir: String) -> Result<(), AppError> {
+    diagnostics::traced("cpres_explode", async move {
+        exploded::explode_bundle(Path::new(&bundle), Path::new(&dir)).map_err(AppError::from)
+    })
+    .await
 }
 
-fn path_is_within_repo_content(repo_root: &Path, path: &Path) -> bool {
-    path.starts_with(repo_root)
+/// Build a bundle from a folder written by `cpres_explode`
+#[tauri::command]
+pub async fn cpres_implode(
+    locks: tauri::State<'_, LockRegistry>,
+    dir: String,
+    bundle: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("cpres_implode", async move {
+        let bundle = PathBuf::from(bundle);
+        let _lock = locks.hold(&bundle)?;
+        exploded::implode_bundle(Path::new(&dir), &bundle).map_err(AppError::from)
+    })
+    .await
 }
 
-/// Drops stale `content_dir.json` if it pointed outside the repo (e.g. old Documents path).
-fn resolve_content_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
-    let repo_root = repo_content_root_dir()?;
+/// Find text in a bundle's slides: an open bundle's `state`, or the bundle file at `path`
+#[tauri::command]
+pub async fn bundle_search(
+    state: Option<BundleState>,
+    path: Option<String>,
+    query: String,
+    regex: Option<bool>,
+) -> Result<Vec<TextMatch>, AppError> {
+    diagnostics::traced("bundle_search", async move {
+        let pattern = Pattern::new(&query, regex.unwrap_or(false))?;
+        match (state, path) {
+            (Some(state), _) => Ok(search::search_slides(&state.slides, &pattern)?),
+            (None, Some(path)) => Ok(search::search_bundle(Path::new(&path), &pattern)?),
+            (None, None) => Err("Either state or path is required".into()),
+        }
+    })
+    .await
+}
 
-    if let Some(configured) = read_content_dir_config(app)? {
-        match configured.canonicalize() {
-            Ok(can) if can.is_dir() && path_is_within_repo_content(&repo_root, &can) => {
-                return Ok(can);
+/// Replace text in a bundle's slides. For `state` the updated slides.json is
+/// returned for the editor to load; a bundle at `path` is rewritten in place.
+#[tauri::command]
+pub async fn bundle_replace(
+    locks: tauri::State<'_, LockRegistry>,
+    state: Option<BundleState>,
+    path: Option<String>,
+    query: String,
+    replacement: String,
+    regex: Option<bool>,
+) -> Result<ReplaceResult, AppError> {
+    diagnostics::traced("bundle_replace", async move {
+        let pattern = Pattern::new(&query, regex.unwrap_or(false))?;
+        match (state, path) {
+            (Some(state), _) => {
+                let replaced = search::replace_slides(&state.slides, &pattern, &replacement)?;
+                Ok(match replaced {
+                    Some((slides, replacements)) => ReplaceResult {
+                        replacements,
+                        slides: Some(slides),
+                    },
+                    None => ReplaceResult {
+                        replacements: 0,
+                        slides: None,
+                    },
+                })
             }
-            _ => {
-                if let Ok(cfg_path) = content_dir_config_path(app) {
-                    let _ = std::fs::remove_file(cfg_path);
-                }
+            (None, Some(path)) => {
+                let path = PathBuf::from(path);
+                let _lock = locks.hold(&path)?;
+                let replacements = search::replace_in_bundle(&path, &pattern, &replacement)?;
+                Ok(ReplaceResult {
+                    replacements,
+                    slides: None,
+                })
             }
+            (None, None) => Err("Either state or path is required".into()),
         }
+    })
+    .await
+}
+
+/// Find text in the slides of every bundle in the content directory
+#[tauri::command]
+pub async fn library_search(
+    app: tauri::AppHandle,
+    query: String,
+    regex: Option<bool>,
+) -> Result<LibrarySearchReport, AppError> {
+    diagnostics::traced("library_search", async move {
+        let pattern = Pattern::new(&query, regex.unwrap_or(false))?;
+        let dir = resolve_content_dir(&app)?;
+        Ok(search::search_library(&dir, &pattern)?)
+    })
+    .await
+}
+
+/// Replace text in every bundle in the content directory. Bundles open in this
+/// app or locked by another machine are skipped and reported; open ones should be
+/// updated through `bundle_replace` with their state.
+#[tauri::command]
+pub async fn library_replace(
+    app: tauri::AppHandle,
+    locks: tauri::State<'_, LockRegistry>,
+    query: String,
+    replacement: String,
+    regex: Option<bool>,
+) -> Result<LibraryReplaceReport, AppError> {
+    diagnostics::traced("library_replace", async move {
+        let pattern = Pattern::new(&query, regex.unwrap_or(false))?;
+        let dir = resolve_content_dir(&app)?;
+        let report = search::replace_in_library(&dir, &pattern, &replacement, |path| {
+            locked_reason(&locks, path)
+        })?;
+        Ok(report)
+    })
+    .await
+}
+
+/// Ranked full-text search of the slides, titles, and authors of every bundle in
+/// the content directory; the index is updated first for bundles that changed
+#[tauri::command]
+pub async fn search_library(
+    app: tauri::AppHandle,
+    index: tauri::State<'_, SearchIndex>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, AppError> {
+    diagnostics::traced("search_library", async move {
+        let dir = resolve_content_dir(&app)?;
+        index.refresh(&dir)?;
+        Ok(index.search(&query, limit.unwrap_or(search_index::DEFAULT_LIMIT))?)
+    })
+    .await
+}
+
+/// Why a bundle can't be rewritten behind the editor's back, if it can't
+fn locked_reason(locks: &LockRegistry, path: &Path) -> Option<String> {
+    let status = match locks.status(path) {
+        Ok(status) => status,
+        Err(e) => return Some(e.to_string()),
+    };
+    match status.state {
+        LockState::HeldByUs => Some("Open in this app".to_string()),
+        LockState::HeldByOther => Some(format!(
+            "Being edited by {}",
+            bundle_lock::describe_owner(status.owner.as_ref())
+        )),
+        LockState::Unlocked | LockState::Stale => None,
     }
+}
 
-    default_bundled_church_presenter_dir()
+/// Compare two bundles: `a` is the original, `b` the updated file
+#[tauri::command]
+pub async fn cpres_diff(a: String, b: String) -> Result<BundleDiff, AppError> {
+    diagnostics::traced("cpres_diff", async move {
+        let a = PathBuf::from(a);
+        let b = PathBuf::from(b);
+        diff::diff_bundles(&a, &b).map_err(AppError::from)
+    })
+    .await
 }
 
-/// First existing ancestor of `path`, for validating paths that do not exist yet.
-fn existing_path_prefix(path: &Path) -> Result<PathBuf, String> {
-    let mut p = path;
-    loop {
-        if p.exists() {
-            return p.canonicalize().map_err(|e| e.to_string());
+/// Export selected themes (with their fonts and background media) as a .cptheme pack
+#[tauri::command]
+pub async fn cpres_export_theme_pack(
+    bundle_path: String,
+    theme_ids: Vec<String>,
+    output: String,
+    name: Option<String>,
+) -> Result<ThemePackSummary, AppError> {
+    diagnostics::traced("cpres_export_theme_pack", async move {
+        let bundle_path = PathBuf::from(bundle_path);
+        let output = PathBuf::from(output);
+        theme_pack::export_pack(&bundle_path, &theme_ids, name, &output).map_err(AppError::from)
+    })
+    .await
+}
+
+/// Import a .cptheme pack into a bundle, or into the theme library when no bundle is given
+#[tauri::command]
+pub async fn cpres_import_theme_pack(
+    app: tauri::AppHandle,
+    locks: tauri::State<'_, LockRegistry>,
+    pack_path: String,
+    bundle_path: Option<String>,
+) -> Result<ThemePackImport, AppError> {
+    diagnostics::traced("cpres_import_theme_pack", async move {
+        let pack_path = PathBuf::from(pack_path);
+        match bundle_path {
+            Some(bundle_path) => {
+                let bundle_path = PathBuf::from(bundle_path);
+                let _lock = locks.hold(&bundle_path)?;
+                theme_pack::import_into_bundle(&pack_path, &bundle_path)
+            }
+            None => {
+                let library_dir = resolve_content_dir(&app)?.join(THEME_PACK_LIBRARY_SUBDIR);
+                theme_pack::import_into_library(&pack_path, &library_dir)
+            }
         }
-        p = p
-            .parent()
-            .ok_or_else(|| "Content path has no valid parent directory".to_string())?;
-    }
+        .map_err(AppError::from)
+    })
+    .await
 }
 
-fn ensure_new_content_dir_under_repo(new_dir: &Path) -> Result<(), String> {
-    let repo_root = repo_content_root_dir()?;
-    let prefix = existing_path_prefix(new_dir)?;
-    if !path_is_within_repo_content(&repo_root, &prefix) {
-        return Err(
-            "Content folder must be under legacy/tauri_old/content in the repository (not Documents)."
-                .to_string(),
-        );
-    }
-    Ok(())
+/// List snapshots kept for a bundle, newest first
+#[tauri::command]
+pub async fn cpres_list_versions(path: String) -> Result<Vec<BundleVersion>, AppError> {
+    diagnostics::traced("cpres_list_versions", async move {
+        let path = PathBuf::from(path);
+        history::list_versions(&path).map_err(AppError::from)
+    })
+    .await
+}
+
+/// Roll a bundle back to one of its snapshots
+#[tauri::command]
+pub async fn cpres_restore_version(
+    locks: tauri::State<'_, LockRegistry>,
+    path: String,
+    version_id: String,
+    keep_versions: Option<usize>,
+) -> Result<(), AppError> {
+    diagnostics::traced("cpres_restore_version", async move {
+        let path = PathBuf::from(path);
+        let keep = keep_versions.unwrap_or(history::DEFAULT_KEEP_VERSIONS);
+        let _lock = locks.hold(&path)?;
+        history::restore_version(&path, &version_id, keep).map_err(AppError::from)
+    })
+    .await
+}
+
+/// Record the latest unsaved document state for the next autosave tick
+#[tauri::command]
+pub async fn cpres_autosave_update(
+    autosave: tauri::State<'_, AutosaveState>,
+    bundle_path: Option<String>,
+    state: BundleState,
+) -> Result<String, AppError> {
+    diagnostics::traced("cpres_autosave_update", async move {
+        Ok(autosave.update(bundle_path, state)?)
+    })
+    .await
+}
+
+/// Enable/disable autosave and set its interval
+#[tauri::command]
+pub fn cpres_autosave_configure(
+    autosave: tauri::State<'_, AutosaveState>,
+    config: AutosaveConfig,
+) -> Result<AutosaveConfig, AppError> {
+    diagnostics::traced_sync("cpres_autosave_configure", || {
+        Ok(autosave.configure(config)?)
+    })
+}
+
+/// List recovery files and whether the previous session crashed
+#[tauri::command]
+pub async fn cpres_list_recovery(
+    autosave: tauri::State<'_, AutosaveState>,
+) -> Result<RecoveryListing, AppError> {
+    diagnostics::traced("cpres_list_recovery", async move { Ok(autosave.list()?) }).await
+}
+
+/// Load a recovery file's bundle state
+#[tauri::command]
+pub async fn cpres_restore_recovery(
+    autosave: tauri::State<'_, AutosaveState>,
+    id: String,
+) -> Result<RecoveryFile, AppError> {
+    diagnostics::traced("cpres_restore_recovery", async move {
+        Ok(autosave.restore(&id)?)
+    })
+    .await
+}
+
+/// Delete a recovery file
+#[tauri::command]
+pub async fn cpres_discard_recovery(
+    autosave: tauri::State<'_, AutosaveState>,
+    id: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("cpres_discard_recovery", async move {
+        Ok(autosave.discard(&id)?)
+    })
+    .await
+}
+
+/// Read media from a bundle as base64
+#[tauri::command]
+pub async fn cpres_read_media(
+    bundle_path: String,
+    media_path: String,
+) -> Result<Vec<u8>, AppError> {
+    diagnostics::traced("cpres_read_media", async move {
+        let path = PathBuf::from(bundle_path);
+        cpres::read_bundle_media(&path, &media_path).map_err(AppError::from)
+    })
+    .await
+}
+
+/// Import media files and compute their metadata/hashes, emitting
+/// `media:import-progress` as each file finishes. HEIC and AVIF images are
+/// converted; with `optimize`, oversized images are replaced by scaled-down
+/// copies too. Audio is measured for loudness, and with `normalize` given it's
+/// brought to a common level. Files already in the media library are listed in
+/// `in_library`.
+#[tauri::command]
+pub async fn cpres_import_media(
+    app: tauri::AppHandle,
+    tasks: tauri::State<'_, TaskRegistry>,
+    library: tauri::State<'_, MediaLibrary>,
+    paths: Vec<String>,
+    optimize: Option<ImageOptimizeOptions>,
+    normalize: Option<LoudnessOptions>,
+    task_id: Option<String>,
+) -> Result<MediaImport, AppError> {
+    diagnostics::traced("cpres_import_media", async move {
+        let task = tasks.start(task_id);
+        let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
+        let cache_dir = app.path().app_cache_dir()?;
+        let optimizer = ImageOptimizer::new(&cache_dir, optimize);
+        let normalizer = normalize.map(|options| AudioNormalizer::new(&cache_dir, options));
+        let mut import = cpres::import_media_files(
+            &paths,
+            Some(&optimizer),
+            normalizer.as_ref(),
+            task.token(),
+            |progress| {
+                let _ = app.emit(MEDIA_IMPORT_PROGRESS_EVENT, progress);
+            },
+        )?;
+        let root = media_library::library_dir(&resolve_content_dir(&app)?);
+        for entry in &import.entries {
+            let existing = library.items_with_hash(&root, &entry.sha256)?;
+            if !existing.is_empty() {
+                let paths = existing.into_iter().map(|item| item.path).collect();
+                import.in_library.insert(entry.id.clone(), paths);
+            }
+        }
+        Ok(import)
+    })
+    .await
+}
+
+/// Import font files and compute their metadata/hashes
+#[tauri::command]
+pub async fn cpres_import_fonts(paths: Vec<String>) -> Result<Vec<FontEntry>, AppError> {
+    diagnostics::traced("cpres_import_fonts", async move {
+        let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
+        cpres::import_font_files(&paths).map_err(AppError::from)
+    })
+    .await
+}
+
+/// Install a bundle's embedded fonts (those with `font_ids`, or all) for the
+/// current user, or register them until the app exits
+#[tauri::command]
+pub async fn cpres_install_fonts(
+    app: tauri::AppHandle,
+    fonts: tauri::State<'_, SystemFonts>,
+    path: String,
+    font_ids: Option<Vec<String>>,
+    scope: InstallScope,
+) -> Result<Vec<FontInstall>, AppError> {
+    diagnostics::traced("cpres_install_fonts", async move {
+        let installed =
+            font_install::install_bundle_fonts(&app, Path::new(&path), font_ids.as_deref(), scope)?;
+        fonts.refresh_in_background(&app);
+        Ok(installed)
+    })
+    .await
+}
+
+/// Report what would break when presenting a bundle on another machine
+#[tauri::command]
+pub async fn cpres_compatibility_report(
+    path: String,
+    profile: MachineProfile,
+) -> Result<CompatibilityReport, AppError> {
+    diagnostics::traced("cpres_compatibility_report", async move {
+        let path = PathBuf::from(path);
+        compatibility::check_bundle(&path, &profile).map_err(AppError::from)
+    })
+    .await
+}
+
+/// Font families an opened bundle uses that are neither embedded nor
+/// installed here, with installed fonts to use instead
+#[tauri::command]
+pub async fn cpres_missing_fonts(
+    app: tauri::AppHandle,
+    fonts: tauri::State<'_, SystemFonts>,
+    path: String,
+) -> Result<MissingFontReport, AppError> {
+    diagnostics::traced("cpres_missing_fonts", async move {
+        let mut installed: Vec<String> = fonts
+            .list(&app)?
+            .into_iter()
+            .map(|font| font.family)
+            .collect();
+        installed.dedup();
+        Ok(missing_fonts::check(Path::new(&path), &installed)?)
+    })
+    .await
+}
+
+/// Describe this machine so it can be used as a target profile elsewhere
+#[tauri::command]
+pub async fn get_machine_profile(app: tauri::AppHandle) -> Result<MachineProfile, AppError> {
+    diagnostics::traced("get_machine_profile", async move {
+        let mut installed_fonts = SystemSource::new()
+            .all_families()
+            .map_err(|e| format!("Failed to list fonts: {e}"))?;
+        installed_fonts.sort();
+        installed_fonts.dedup();
+
+        let window = app
+            .get_webview_window("main")
+            .ok_or("Main window not found")?;
+        let output_resolutions = window
+            .available_monitors()?
+            .iter()
+            .map(|monitor| Resolution {
+                width: monitor.size().width,
+                height: monitor.size().height,
+            })
+            .collect();
+
+        Ok(MachineProfile {
+            os: std::env::consts::OS.to_string(),
+            codecs: Vec::new(),
+            installed_fonts,
+            output_resolutions,
+        })
+    })
+    .await
+}
+
+/// Characters of `text` that the font `font` can't draw, such as accented
+/// letters or curly quotes, so the editor can warn before they show as boxes
+#[tauri::command]
+pub async fn check_font_coverage(
+    font: String,
+    text: String,
+    options: Option<FontCoverageOptions>,
+) -> Result<FontCoverage, AppError> {
+    diagnostics::traced("check_font_coverage", async move {
+        Ok(font_coverage::check(
+            &font,
+            &text,
+            &options.unwrap_or_default(),
+        )?)
+    })
+    .await
+}
+
+/// PNG of `sample_text` (the font's name when empty) drawn in a font, given by
+/// family or by file path, at `size` pixels; drawn once, then served from
+/// the cache
+#[tauri::command]
+pub async fn render_font_preview(
+    app: tauri::AppHandle,
+    font: String,
+    sample_text: Option<String>,
+    size: Option<u32>,
+    options: Option<FontPreviewOptions>,
+) -> Result<FontPreview, AppError> {
+    diagnostics::traced("render_font_preview", async move {
+        Ok(font_preview::render_preview(
+            &app.path().app_cache_dir()?,
+            &font,
+            sample_text.as_deref().unwrap_or_default(),
+            size.unwrap_or(font_preview::DEFAULT_SIZE),
+            &options.unwrap_or_default(),
+        )?)
+    })
+    .await
+}
+
+/// List installed system fonts with metadata and file paths, from the cache
+/// while the font folders are unchanged
+#[tauri::command]
+pub async fn cpres_list_system_fonts(
+    app: tauri::AppHandle,
+    fonts: tauri::State<'_, SystemFonts>,
+) -> Result<Vec<SystemFontInfo>, AppError> {
+    diagnostics::traced(
+        "cpres_list_system_fonts",
+        async move { Ok(fonts.list(&app)?) },
+    )
+    .await
+}
+
+/// List the installed fonts again, for fonts the cache missed
+#[tauri::command]
+pub async fn refresh_fonts(
+    app: tauri::AppHandle,
+    fonts: tauri::State<'_, SystemFonts>,
+) -> Result<Vec<SystemFontInfo>, AppError> {
+    diagnostics::traced("refresh_fonts", async move { Ok(fonts.refresh(&app)?) }).await
+}
+
+/// Names, variation axes (weight, width, slant ranges), and named instances
+/// of a font file
+#[tauri::command]
+pub async fn get_font_details(
+    path: String,
+    font_index: Option<u32>,
+) -> Result<FontDetails, AppError> {
+    diagnostics::traced("get_font_details", async move {
+        Ok(font_details::details(
+            Path::new(&path),
+            font_index.unwrap_or(0),
+        )?)
+    })
+    .await
+}
+
+/// Whether a Google Fonts API key is set
+#[tauri::command]
+pub async fn google_fonts_has_key(app: tauri::AppHandle) -> Result<bool, AppError> {
+    diagnostics::traced("google_fonts_has_key", async move {
+        Ok(google_fonts::has_key(&app.path().app_data_dir()?))
+    })
+    .await
+}
+
+/// Store the Google Fonts API key; None removes it
+#[tauri::command]
+pub async fn set_google_fonts_key(
+    app: tauri::AppHandle,
+    key: Option<String>,
+) -> Result<(), AppError> {
+    diagnostics::traced("set_google_fonts_key", async move {
+        Ok(google_fonts::set_key(
+            &app.path().app_data_dir()?,
+            key.as_deref(),
+        )?)
+    })
+    .await
+}
+
+/// Search the Google Fonts catalog by family name, most popular first
+#[tauri::command]
+pub async fn search_google_fonts(
+    app: tauri::AppHandle,
+    query: String,
+    options: Option<GoogleFontSearchOptions>,
+) -> Result<Vec<GoogleFont>, AppError> {
+    diagnostics::traced("search_google_fonts", async move {
+        Ok(google_fonts::search(
+            &app.path().app_data_dir()?,
+            &query,
+            &options.unwrap_or_default(),
+        )
+        .await?)
+    })
+    .await
+}
+
+/// Google Fonts families downloaded into the app's fonts folder
+#[tauri::command]
+pub async fn list_google_fonts(
+    app: tauri::AppHandle,
+) -> Result<Vec<InstalledGoogleFont>, AppError> {
+    diagnostics::traced("list_google_fonts", async move {
+        Ok(google_fonts::installed(&app.path().app_data_dir()?)?)
+    })
+    .await
+}
+
+/// Download a Google Fonts family, or some of its styles, into the app's
+/// fonts folder; returns the fonts, ready to embed with `cpres_import_fonts`
+#[tauri::command]
+pub async fn download_google_font(
+    app: tauri::AppHandle,
+    fonts: tauri::State<'_, SystemFonts>,
+    family: String,
+    variants: Option<Vec<String>>,
+) -> Result<Vec<SystemFontInfo>, AppError> {
+    diagnostics::traced("download_google_font", async move {
+        let paths = google_fonts::install(
+            &app,
+            &app.path().app_data_dir()?,
+            &family,
+            variants.as_deref(),
+        )
+        .await?;
+        fonts.refresh_in_background(&app);
+        Ok(paths
+            .iter()
+            .filter_map(|path| system_fonts::load_info(path, 0, true))
+            .collect())
+    })
+    .await
+}
+
+/// Delete a downloaded Google Fonts family
+#[tauri::command]
+pub async fn remove_google_font(
+    app: tauri::AppHandle,
+    fonts: tauri::State<'_, SystemFonts>,
+    family: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("remove_google_font", async move {
+        google_fonts::uninstall(&app.path().app_data_dir()?, &family)?;
+        fonts.refresh_in_background(&app);
+        Ok(())
+    })
+    .await
+}
+
+const CONTENT_DIR_CONFIG_FILENAME: &str = "content_dir.json";
+/// Theme packs imported into the library live under `<content dir>/themes/packs/<pack id>/`
+const THEME_PACK_LIBRARY_SUBDIR: &str = "themes/packs";
+/// Emitted by `cpres_import_media` as each file finishes
+const MEDIA_IMPORT_PROGRESS_EVENT: &str = "media:import-progress";
+
+#[cfg(target_os = "windows")]
+fn get_monitor_friendly_name(device_name: &str) -> Option<String> {
+    let device_name_w: Vec<u16> = device_name
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut display_device = DISPLAY_DEVICEW::default();
+    display_device.cb = std::mem::size_of::<DISPLAY_DEVICEW>() as u32;
+
+    let success =
+        unsafe { EnumDisplayDevicesW(PCWSTR(device_name_w.as_ptr()), 0, &mut display_device, 0) }
+            .as_bool();
+    if !success {
+        return None;
+    }
+
+    let len = display_device
+        .DeviceString
+        .iter()
+        .position(|c| *c == 0)
+        .unwrap_or(display_device.DeviceString.len());
+    let friendly = String::from_utf16_lossy(&display_device.DeviceString[..len])
+        .trim()
+        .to_string();
+    if friendly.is_empty() {
+        None
+    } else {
+        Some(friendly)
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn get_monitor_refresh_rate(device_name: &str) -> Option<u32> {
+    let device_name_w: Vec<u16> = device_name
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut devmode = DEVMODEW::default();
+    devmode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+
+    let success = unsafe {
+        EnumDisplaySettingsW(
+            PCWSTR(device_name_w.as_ptr()),
+            ENUM_CURRENT_SETTINGS,
+            &mut devmode,
+        )
+    }
+    .as_bool();
+    if !success {
+        return None;
+    }
+
+    let freq = devmode.dmDisplayFrequency;
+    if freq == 0 {
+        None
+    } else {
+        Some(freq)
+    }
+}
+
+/// The monitor's device interface path, which names the display by its EDID
+/// (maker and model) and the port it's on, and survives re-enumeration
+#[cfg(target_os = "windows")]
+fn get_monitor_device_id(device_name: &str) -> Option<String> {
+    let device_name_w: Vec<u16> = device_name
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut display_device = DISPLAY_DEVICEW::default();
+    display_device.cb = std::mem::size_of::<DISPLAY_DEVICEW>() as u32;
+
+    let success = unsafe {
+        EnumDisplayDevicesW(
+            PCWSTR(device_name_w.as_ptr()),
+            0,
+            &mut display_device,
+            EDD_GET_DEVICE_INTERFACE_NAME,
+        )
+    }
+    .as_bool();
+    if !success {
+        return None;
+    }
+
+    let len = display_device
+        .DeviceID
+        .iter()
+        .position(|c| *c == 0)
+        .unwrap_or(display_device.DeviceID.len());
+    let device_id = String::from_utf16_lossy(&display_device.DeviceID[..len])
+        .trim()
+        .to_string();
+    if device_id.is_empty() {
+        None
+    } else {
+        Some(device_id)
+    }
+}
+
+/// The display tao calls "Monitor #<model number>"; with two monitors of the
+/// same model, the first
+#[cfg(target_os = "macos")]
+fn macos_display_id(device_name: &str) -> Option<u32> {
+    let model: u32 = device_name.strip_prefix("Monitor #")?.trim().parse().ok()?;
+    CGDisplay::active_displays()
+        .ok()?
+        .into_iter()
+        .find(|id| CGDisplay::new(*id).model_number() == model)
+}
+
+/// The name macOS shows for the display, such as "EB-2250U"
+#[cfg(target_os = "macos")]
+fn get_monitor_friendly_name(device_name: &str) -> Option<String> {
+    let display_id = macos_display_id(device_name)?;
+    // NSScreen may only be used on the main thread
+    let friendly = dispatch2::run_on_main(move |mtm| {
+        let key = NSString::from_str("NSScreenNumber");
+        NSScreen::screens(mtm).iter().find_map(|screen| {
+            let number = screen.deviceDescription().objectForKey(&key)?;
+            let number = number.downcast_ref::<NSNumber>()?;
+            (number.unsignedIntValue() == display_id).then(|| screen.localizedName().to_string())
+        })
+    })?;
+    let friendly = friendly.trim().to_string();
+    if friendly.is_empty() {
+        None
+    } else {
+        Some(friendly)
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn get_monitor_refresh_rate(device_name: &str) -> Option<u32> {
+    let display_id = macos_display_id(device_name)?;
+    let rate = CGDisplay::new(display_id).display_mode()?.refresh_rate();
+    // Built-in panels and some adapters report 0
+    if rate > 0.0 {
+        Some(rate.round() as u32)
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn get_monitor_friendly_name(device_name: &str) -> Option<String> {
+    crate::linux_monitors::friendly_name(device_name)
+}
+
+#[cfg(target_os = "linux")]
+fn get_monitor_refresh_rate(device_name: &str) -> Option<u32> {
+    crate::linux_monitors::refresh_rate(device_name)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn get_monitor_friendly_name(_device_name: &str) -> Option<String> {
+    None
+}
+
+#[cfg(not(target_os = "windows"))]
+fn get_monitor_device_id(_device_name: &str) -> Option<String> {
+    None
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn get_monitor_refresh_rate(_device_name: &str) -> Option<u32> {
+    None
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ContentDirConfig {
+    path: String,
+}
+
+fn content_dir_config_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(CONTENT_DIR_CONFIG_FILENAME))
+        .map_err(AppError::from)
+}
+
+fn read_content_dir_config(app: &tauri::AppHandle) -> Result<Option<PathBuf>, AppError> {
+    let config_path = content_dir_config_path(app)?;
+    if !config_path.exists() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(&config_path)?;
+    let parsed: ContentDirConfig = serde_json::from_str(&content)?;
+    if parsed.path.trim().is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(PathBuf::from(parsed.path)))
+}
+
+fn write_content_dir_config(app: &tauri::AppHandle, path: &Path) -> Result<(), AppError> {
+    let config_path = content_dir_config_path(app)?;
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let config = ContentDirConfig {
+        path: path.to_string_lossy().to_string(),
+    };
+    let content = serde_json::to_string_pretty(&config)?;
+    std::fs::write(&config_path, content)?;
+    Ok(())
+}
+
+/// `legacy/tauri_old/content` — all app content stays in-repo; never use Documents/OneDrive.
+fn repo_content_root_dir() -> Result<PathBuf, AppError> {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let candidate = manifest_dir.join("../content");
+    candidate.canonicalize().map_err(|_| {
+        "Repository content root not found: expected legacy/tauri_old/content beside src-tauri."
+            .into()
+    })
+}
+
+fn default_bundled_church_presenter_dir() -> Result<PathBuf, AppError> {
+    let root = repo_content_root_dir()?;
+    let dir = root.join("Church Presenter");
+    let canonical = dir.canonicalize().map_err(|_| {
+        AppError::from("Bundled content folder missing: legacy/tauri_old/content/Church Presenter")
+    })?;
+    if canonical.is_dir() {
+        Ok(canonical)
+    } else {
+        Err("Bundled content path exists but is not a directory.".into())
+    }
+}
+
+fn path_is_within_repo_content(repo_root: &Path, path: &Path) -> bool {
+    path.starts_with(repo_root)
+}
+
+/// Drops stale `content_dir.json` if it pointed outside the repo (e.g. old Documents path).
+pub(crate) fn resolve_content_dir(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let repo_root = repo_content_root_dir()?;
+
+    if let Some(configured) = read_content_dir_config(app)? {
+        match configured.canonicalize() {
+            Ok(can) if can.is_dir() && path_is_within_repo_content(&repo_root, &can) => {
+                return Ok(can);
+            }
+            _ => {
+                if let Ok(cfg_path) = content_dir_config_path(app) {
+                    let _ = std::fs::remove_file(cfg_path);
+                }
+            }
+        }
+    }
+
+    default_bundled_church_presenter_dir()
+}
+
+/// First existing ancestor of `path`, for validating paths that do not exist yet.
+fn existing_path_prefix(path: &Path) -> Result<PathBuf, AppError> {
+    let mut p = path;
+    loop {
+        if p.exists() {
+            return p.canonicalize().map_err(AppError::from);
+        }
+        p = p
+            .parent()
+            .ok_or_else(|| "Content path has no valid parent directory".to_string())?;
+    }
+}
+
+fn ensure_new_content_dir_under_repo(new_dir: &Path) -> Result<(), AppError> {
+    let repo_root = repo_content_root_dir()?;
+    let prefix = existing_path_prefix(new_dir)?;
+    if !path_is_within_repo_content(&repo_root, &prefix) {
+        return Err(
+            "Content folder must be under legacy/tauri_old/content in the repository (not Documents)."
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+/// True if `path` is absolute and its first existing ancestor lies under `legacy/tauri_old/content`.
+/// Used to ignore stale persisted paths (e.g. old Documents) without calling `set_content_dir`.
+#[tauri::command]
+pub fn is_content_dir_under_repo(path: String) -> Result<bool, AppError> {
+    diagnostics::traced_sync("is_content_dir_under_repo", || {
+        let new_dir = PathBuf::from(path.trim());
+        if new_dir.as_os_str().is_empty() || !new_dir.is_absolute() {
+            return Ok(false);
+        }
+        let repo_root = repo_content_root_dir()?;
+        match existing_path_prefix(&new_dir) {
+            Ok(prefix) => Ok(path_is_within_repo_content(&repo_root, &prefix)),
+            Err(_) => Ok(false),
+        }
+    })
+}
+
+fn move_file_with_fallback(source: &Path, destination: &Path) -> Result<(), AppError> {
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    match std::fs::rename(source, destination) {
+        Ok(()) => Ok(()),
+        Err(_error) => {
+            std::fs::copy(source, destination)?;
+            std::fs::remove_file(source)?;
+            Ok(())
+        }
+    }
+}
+
+fn move_dir_contents(source: &Path, destination: &Path) -> Result<(), AppError> {
+    if !source.exists() {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(destination)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let entry_path = entry.path();
+        let target_path = destination.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            move_dir_contents(&entry_path, &target_path)?;
+            if entry_path.read_dir()?.next().is_none() {
+                let _ = std::fs::remove_dir(&entry_path);
+            }
+        } else {
+            if target_path.exists() {
+                std::fs::remove_file(&target_path)?;
+            }
+            move_file_with_fallback(&entry_path, &target_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the app data directory path
+#[tauri::command]
+pub fn get_app_data_dir(app: tauri::AppHandle) -> Result<String, AppError> {
+    diagnostics::traced_sync("get_app_data_dir", || {
+        app.path()
+            .app_data_dir()
+            .map(|p| p.to_string_lossy().to_string())
+            .map_err(AppError::from)
+    })
+}
+
+/// Get the documents app data directory path
+#[tauri::command]
+pub fn get_documents_data_dir(app: tauri::AppHandle) -> Result<String, AppError> {
+    diagnostics::traced_sync("get_documents_data_dir", || {
+        resolve_content_dir(&app).map(|p| p.to_string_lossy().to_string())
+    })
+}
+
+/// Set the content directory (optionally moving existing data)
+#[tauri::command]
+pub async fn set_content_dir(
+    app: tauri::AppHandle,
+    path: String,
+    move_existing: bool,
+    media_library_dir: Option<String>,
+) -> Result<String, AppError> {
+    diagnostics::traced("set_content_dir", async move {
+        let new_dir = PathBuf::from(path.trim());
+        if new_dir.as_os_str().is_empty() {
+            return Err("Content folder path is required".into());
+        }
+        if !new_dir.is_absolute() {
+            return Err("Content folder path must be absolute".into());
+        }
+
+        ensure_new_content_dir_under_repo(&new_dir)?;
+
+        let current_dir = resolve_content_dir(&app)?;
+        if new_dir == current_dir {
+            write_content_dir_config(&app, &new_dir)?;
+            return Ok(new_dir.to_string_lossy().to_string());
+        }
+
+        if new_dir.starts_with(&current_dir) {
+            return Err("Content folder cannot be inside the current folder".into());
+        }
+
+        std::fs::create_dir_all(&new_dir)?;
+
+        if move_existing {
+            move_dir_contents(&current_dir, &new_dir)?;
+        }
+
+        if let Some(media_dir) = media_library_dir {
+            let media_source = PathBuf::from(media_dir);
+            let media_target = new_dir.join(MEDIA_LIBRARY_DIR_NAME);
+            if media_source.exists() && media_source != media_target {
+                move_dir_contents(&media_source, &media_target)?;
+                if media_source.read_dir()?.next().is_none() {
+                    let _ = std::fs::remove_dir(&media_source);
+                }
+            }
+        }
+
+        write_content_dir_config(&app, &new_dir)?;
+        media_watch::restart(&app);
+
+        Ok(new_dir.to_string_lossy().to_string())
+    })
+    .await
+}
+
+/// Ensure the app data directory exists
+#[tauri::command]
+pub async fn ensure_app_data_dir(app: tauri::AppHandle) -> Result<String, AppError> {
+    diagnostics::traced("ensure_app_data_dir", async move {
+        let dir = app.path().app_data_dir()?;
+
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(dir.to_string_lossy().to_string())
+    })
+    .await
+}
+
+/// Ensure the documents app data directory exists
+#[tauri::command]
+pub async fn ensure_documents_data_dir(app: tauri::AppHandle) -> Result<String, AppError> {
+    diagnostics::traced("ensure_documents_data_dir", async move {
+        let dir = resolve_content_dir(&app)?;
+
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(dir.to_string_lossy().to_string())
+    })
+    .await
+}
+
+/// Ensure a subdirectory exists within app data
+#[tauri::command]
+pub async fn ensure_app_data_subdir(
+    app: tauri::AppHandle,
+    sub_dir: String,
+) -> Result<String, AppError> {
+    diagnostics::traced("ensure_app_data_subdir", async move {
+        let base_dir = app.path().app_data_dir()?;
+
+        let dir = base_dir.join(&sub_dir);
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(dir.to_string_lossy().to_string())
+    })
+    .await
+}
+
+/// Ensure a subdirectory exists within documents app data
+#[tauri::command]
+pub async fn ensure_documents_data_subdir(
+    app: tauri::AppHandle,
+    sub_dir: String,
+) -> Result<String, AppError> {
+    diagnostics::traced("ensure_documents_data_subdir", async move {
+        let base_dir = resolve_content_dir(&app)?;
+
+        let dir = base_dir.join(&sub_dir);
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(dir.to_string_lossy().to_string())
+    })
+    .await
+}
+
+/// Read a JSON file from app data directory
+#[tauri::command]
+pub async fn read_app_data_file(
+    app: tauri::AppHandle,
+    filename: String,
+) -> Result<String, AppError> {
+    diagnostics::traced("read_app_data_file", async move {
+        let dir = app.path().app_data_dir()?;
+
+        let path = dir.join(&filename);
+
+        if !path.exists() {
+            return Err("File not found".into());
+        }
+
+        std::fs::read_to_string(&path).map_err(AppError::from)
+    })
+    .await
+}
+
+/// Read a JSON file from documents app data directory
+#[tauri::command]
+pub async fn read_documents_data_file(
+    app: tauri::AppHandle,
+    filename: String,
+) -> Result<String, AppError> {
+    diagnostics::traced("read_documents_data_file", async move {
+        let dir = resolve_content_dir(&app)?;
+
+        let path = dir.join(&filename);
+
+        if !path.exists() {
+            return Err("File not found".into());
+        }
+
+        std::fs::read_to_string(&path).map_err(AppError::from)
+    })
+    .await
+}
+
+/// Write a JSON file to app data directory
+#[tauri::command]
+pub async fn write_app_data_file(
+    app: tauri::AppHandle,
+    filename: String,
+    content: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("write_app_data_file", async move {
+        let dir = app.path().app_data_dir()?;
+
+        std::fs::create_dir_all(&dir)?;
+
+        let path = dir.join(&filename);
+        std::fs::write(&path, content).map_err(AppError::from)
+    })
+    .await
+}
+
+/// Write a JSON file to documents app data directory
+#[tauri::command]
+pub async fn write_documents_data_file(
+    app: tauri::AppHandle,
+    filename: String,
+    content: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("write_documents_data_file", async move {
+        let dir = resolve_content_dir(&app)?;
+
+        let path = dir.join(&filename);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(&path, content).map_err(AppError::from)
+    })
+    .await
+}
+
+/// Allow a media library directory in the fs scope (persisted)
+#[tauri::command]
+pub async fn allow_media_library_dir(app: tauri::AppHandle, path: String) -> Result<(), AppError> {
+    diagnostics::traced("allow_media_library_dir", async move {
+        let dir = PathBuf::from(path);
+        if !dir.exists() {
+            return Err("Directory not found".into());
+        }
+        if !dir.is_dir() {
+            return Err("Path is not a directory".into());
+        }
+
+        let scope = app.fs_scope();
+        scope.allow_directory(&dir, true)?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Update the media library index from the files in the media library folder
+#[tauri::command]
+pub async fn media_library_scan(
+    app: tauri::AppHandle,
+    library: tauri::State<'_, MediaLibrary>,
+    tasks: tauri::State<'_, TaskRegistry>,
+    task_id: Option<String>,
+) -> Result<ScanReport, AppError> {
+    diagnostics::traced("media_library_scan", async move {
+        let task = tasks.start(task_id);
+        let root = media_library::library_dir(&resolve_content_dir(&app)?);
+        Ok(library.scan(&root, task.token())?)
+    })
+    .await
+}
+
+/// Filter, sort, and page through the indexed media library
+#[tauri::command]
+pub async fn media_library_query(
+    app: tauri::AppHandle,
+    library: tauri::State<'_, MediaLibrary>,
+    query: Option<MediaQuery>,
+) -> Result<MediaPage, AppError> {
+    diagnostics::traced("media_library_query", async move {
+        let root = media_library::library_dir(&resolve_content_dir(&app)?);
+        Ok(library.query(&root, &query.unwrap_or_default())?)
+    })
+    .await
+}
+
+/// Thumbnail of an image file or of an image inside a bundle, at most `size`
+/// pixels on its longest edge; generated once, then served from the cache
+#[tauri::command]
+pub async fn generate_thumbnail(
+    app: tauri::AppHandle,
+    path: Option<String>,
+    bundle_media: Option<BundleMedia>,
+    size: Option<u32>,
+) -> Result<Thumbnail, AppError> {
+    diagnostics::traced("generate_thumbnail", async move {
+        let cache = ThumbnailCache::in_app_data(&app.path().app_data_dir()?);
+        let size = size.unwrap_or(thumbnails::DEFAULT_SIZE);
+        let thumbnail = match (path, bundle_media) {
+            (Some(path), None) => thumbnails::file_thumbnail(&cache, Path::new(&path), size)?,
+            (None, Some(media)) => thumbnails::bundle_thumbnail(&cache, &media, size)?,
+            _ => {
+                return Err(AppError::from(CpresError::InvalidBundle(
+                    "Pass either a path or bundle media".to_string(),
+                )))
+            }
+        };
+        Ok(thumbnail)
+    })
+    .await
+}
+
+/// PNG of an SVG file drawn as large as fits inside `width` × `height`, for
+/// showing a logo crisply at the output's resolution; drawn once per size,
+/// then served from the thumbnail cache
+#[tauri::command]
+pub async fn rasterize_svg(
+    app: tauri::AppHandle,
+    path: String,
+    width: u32,
+    height: u32,
+) -> Result<RasterizedSvg, AppError> {
+    diagnostics::traced("rasterize_svg", async move {
+        let cache = ThumbnailCache::in_app_data(&app.path().app_data_dir()?);
+        Ok(svg::rasterize_svg(&cache, Path::new(&path), width, height)?)
+    })
+    .await
+}
+
+/// Dominant and accent colours of an image or video file, or of one inside a
+/// bundle, with the text colour that reads best on it
+#[tauri::command]
+pub async fn extract_palette(
+    app: tauri::AppHandle,
+    path: Option<String>,
+    bundle_media: Option<BundleMedia>,
+) -> Result<Palette, AppError> {
+    diagnostics::traced("extract_palette", async move {
+        let cache = ThumbnailCache::in_app_data(&app.path().app_data_dir()?);
+        let palette = match (path, bundle_media) {
+            (Some(path), None) => palette::file_palette(&cache, Path::new(&path))?,
+            (None, Some(media)) => palette::bundle_palette(&cache, &media)?,
+            _ => {
+                return Err(AppError::from(CpresError::InvalidBundle(
+                    "Pass either a path or bundle media".to_string(),
+                )))
+            }
+        };
+        Ok(palette)
+    })
+    .await
+}
+
+/// Poster frame, and optionally a sprite sheet of seek thumbnails, for a video
+/// file or a video inside a bundle; needs ffmpeg unless they're already cached
+#[tauri::command]
+pub async fn probe_video_thumbnails(
+    app: tauri::AppHandle,
+    path: Option<String>,
+    bundle_media: Option<BundleMedia>,
+    options: Option<VideoThumbnailOptions>,
+) -> Result<VideoThumbnails, AppError> {
+    diagnostics::traced("probe_video_thumbnails", async move {
+        let cache = ThumbnailCache::in_app_data(&app.path().app_data_dir()?);
+        let options = options.unwrap_or_default();
+        let thumbnails = match (path, bundle_media) {
+            (Some(path), None) => {
+                video_thumbnails::file_video_thumbnails(&cache, Path::new(&path), &options)?
+            }
+            (None, Some(media)) => {
+                video_thumbnails::bundle_video_thumbnails(&cache, &media, &options)?
+            }
+            _ => {
+                return Err(AppError::from(CpresError::InvalidBundle(
+                    "Pass either a path or bundle media".to_string(),
+                )))
+            }
+        };
+        Ok(thumbnails)
+    })
+    .await
+}
+
+/// Min/max peaks of an audio or video file's sound in `resolution` buckets;
+/// decoded with ffmpeg once, then served from the cache
+#[tauri::command]
+pub async fn generate_waveform(
+    app: tauri::AppHandle,
+    tasks: tauri::State<'_, TaskRegistry>,
+    path: String,
+    resolution: Option<u32>,
+    task_id: Option<String>,
+) -> Result<Waveform, AppError> {
+    diagnostics::traced("generate_waveform", async move {
+        let task = tasks.start(task_id);
+        Ok(waveform::generate_waveform(
+            &app.path().app_cache_dir()?,
+            Path::new(&path),
+            resolution.unwrap_or(waveform::DEFAULT_RESOLUTION),
+            task.token(),
+        )?)
+    })
+    .await
+}
+
+/// Render a 540p proxy of a heavy video for the editor, or find the cached
+/// one; `path` is None when the video doesn't need one. Progress comes as
+/// `media:proxy-progress` events.
+#[tauri::command]
+pub async fn generate_proxy(
+    app: tauri::AppHandle,
+    tasks: tauri::State<'_, TaskRegistry>,
+    path: String,
+    sha256: Option<String>,
+    task_id: Option<String>,
+) -> Result<Proxy, AppError> {
+    diagnostics::traced("generate_proxy", async move {
+        let task = tasks.start(task_id);
+        Ok(proxy::generate_proxy(
+            &app.path().app_cache_dir()?,
+            Path::new(&path),
+            sha256.as_deref(),
+            task.token(),
+            |progress| {
+                let _ = app.emit(
+                    proxy::PROXY_PROGRESS_EVENT,
+                    ProxyProgress {
+                        source: path.clone(),
+                        progress,
+                    },
+                );
+            },
+        )?)
+    })
+    .await
+}
+
+/// The file to load for a video: its proxy in the editor once one has been
+/// rendered, the original everywhere else
+#[tauri::command]
+pub async fn resolve_media_source(
+    app: tauri::AppHandle,
+    path: String,
+    sha256: Option<String>,
+    context: Option<MediaContext>,
+) -> Result<ResolvedSource, AppError> {
+    diagnostics::traced("resolve_media_source", async move {
+        Ok(proxy::resolve_source(
+            &app.path().app_cache_dir()?,
+            Path::new(&path),
+            sha256.as_deref(),
+            context.unwrap_or_default(),
+        )?)
+    })
+    .await
+}
+
+/// Size of each cache (thumbnails, proxies, downloads, ...) and their limit
+#[tauri::command]
+pub async fn get_cache_stats(app: tauri::AppHandle) -> Result<CacheStats, AppError> {
+    diagnostics::traced("get_cache_stats", async move {
+        Ok(CacheDirs::new(&app)?.stats()?)
+    })
+    .await
+}
+
+/// Empty one cache, or every cache when `kind` is None
+#[tauri::command]
+pub async fn clear_cache(
+    app: tauri::AppHandle,
+    kind: Option<CacheKind>,
+) -> Result<CacheStats, AppError> {
+    diagnostics::traced("clear_cache", async move {
+        let dirs = CacheDirs::new(&app)?;
+        dirs.clear(kind)?;
+        Ok(dirs.stats()?)
+    })
+    .await
+}
+
+/// Change how large the caches may grow together, and trim them to fit
+#[tauri::command]
+pub async fn set_cache_limit(
+    app: tauri::AppHandle,
+    max_bytes: u64,
+) -> Result<CacheStats, AppError> {
+    diagnostics::traced("set_cache_limit", async move {
+        let dirs = CacheDirs::new(&app)?;
+        dirs.set_max_bytes(max_bytes)?;
+        dirs.trim()?;
+        Ok(dirs.stats()?)
+    })
+    .await
+}
+
+/// Queue a conversion of a library video to H.264 MP4, saved next to it;
+/// follow it with `media:transcode-progress` events
+#[tauri::command]
+pub async fn transcode_media(
+    app: tauri::AppHandle,
+    library: tauri::State<'_, MediaLibrary>,
+    queue: tauri::State<'_, TranscodeQueue>,
+    id: String,
+    preset: Option<TranscodePreset>,
+) -> Result<TranscodeJob, AppError> {
+    diagnostics::traced("transcode_media", async move {
+        let root = media_library::library_dir(&resolve_content_dir(&app)?);
+        let item = library
+            .item(&root, &id)?
+            .ok_or_else(|| CpresError::MissingFile(id.clone()))?;
+        let folder_root = library
+            .roots(&root)?
+            .into_iter()
+            .find(|(folder_id, _)| *folder_id == item.folder_id)
+            .map(|(_, path)| path)
+            .ok_or_else(|| CpresError::MissingFile(item.path.clone()))?;
+        Ok(queue.enqueue(&app, item, folder_root, preset.unwrap_or_default())?)
+    })
+    .await
+}
+
+/// Write the audio track of a library video to an audio file next to it and
+/// index it; progress comes as `media:extract-audio-progress` events
+#[tauri::command]
+pub async fn extract_audio(
+    app: tauri::AppHandle,
+    library: tauri::State<'_, MediaLibrary>,
+    tasks: tauri::State<'_, TaskRegistry>,
+    id: String,
+    format: Option<AudioFormat>,
+    task_id: Option<String>,
+) -> Result<MediaItem, AppError> {
+    diagnostics::traced("extract_audio", async move {
+        let task = tasks.start(task_id);
+        let root = media_library::library_dir(&resolve_content_dir(&app)?);
+        let item = library
+            .item(&root, &id)?
+            .ok_or_else(|| CpresError::MissingFile(id.clone()))?;
+        if item.media_type != "video" {
+            return Err(
+                CpresError::InvalidBundle(format!("{} isn't a video", item.filename)).into(),
+            );
+        }
+        let folder_root = library
+            .roots(&root)?
+            .into_iter()
+            .find(|(folder_id, _)| *folder_id == item.folder_id)
+            .map(|(_, path)| path)
+            .ok_or_else(|| CpresError::MissingFile(item.path.clone()))?;
+        let output = audio_extract::extract_audio(
+            Path::new(&item.path),
+            format.unwrap_or_default(),
+            task.token(),
+            |progress| {
+                let _ = app.emit(
+                    audio_extract::EXTRACT_PROGRESS_EVENT,
+                    ExtractProgress {
+                        source: item.path.clone(),
+                        progress,
+                    },
+                );
+            },
+        )?;
+
+        // Index the new file now rather than waiting for the watcher
+        library.scan_folder(item.folder_id, &folder_root, task.token())?;
+        let (sha256, _) = cpres::hash_file(&output, task.token())?;
+        let output = output.to_string_lossy().to_string();
+        Ok(library
+            .items_with_hash(&root, &sha256)?
+            .into_iter()
+            .find(|extracted| extracted.path == output)
+            .ok_or(CpresError::MissingFile(output))?)
+    })
+    .await
+}
+
+/// Queue a download of a media file from a link into the media library's
+/// `Downloads` folder; follow it with `media:download-progress` events
+#[tauri::command]
+pub async fn download_media(
+    app: tauri::AppHandle,
+    downloads: tauri::State<'_, MediaDownloads>,
+    url: String,
+    options: Option<MediaDownloadOptions>,
+) -> Result<DownloadJob, AppError> {
+    diagnostics::traced("download_media", async move {
+        let root = media_library::library_dir(&resolve_content_dir(&app)?);
+        Ok(downloads.enqueue(
+            &app,
+            &download::cache_root(&app.path().app_data_dir()?),
+            &root,
+            &url,
+            options.unwrap_or_default(),
+        )?)
+    })
+    .await
+}
+
+/// Queued, running, and recently finished media downloads, oldest first
+#[tauri::command]
+pub fn media_downloads(
+    downloads: tauri::State<'_, MediaDownloads>,
+) -> Result<Vec<DownloadJob>, AppError> {
+    Ok(downloads.jobs()?)
+}
+
+/// Stop a running media download, keeping what was received, or drop a
+/// queued one; false when the job isn't waiting or running
+#[tauri::command]
+pub fn cancel_media_download(
+    app: tauri::AppHandle,
+    downloads: tauri::State<'_, MediaDownloads>,
+    job_id: String,
+) -> Result<bool, AppError> {
+    Ok(downloads.cancel(&app, &job_id)?)
+}
+
+/// Queue a failed or cancelled media download again, picking up where it
+/// stopped; None when the job isn't stopped
+#[tauri::command]
+pub fn resume_media_download(
+    app: tauri::AppHandle,
+    downloads: tauri::State<'_, MediaDownloads>,
+    job_id: String,
+) -> Result<Option<DownloadJob>, AppError> {
+    Ok(downloads.resume(&app, &job_id)?)
+}
+
+/// Stock media providers, and which have an API key
+#[tauri::command]
+pub async fn stock_media_providers(app: tauri::AppHandle) -> Result<Vec<ProviderStatus>, AppError> {
+    diagnostics::traced("stock_media_providers", async move {
+        Ok(stock_media::providers(&app.path().app_data_dir()?))
+    })
+    .await
+}
+
+/// Store the API key for a stock media provider; None removes it
+#[tauri::command]
+pub async fn set_stock_media_key(
+    app: tauri::AppHandle,
+    provider: StockProvider,
+    key: Option<String>,
+) -> Result<(), AppError> {
+    diagnostics::traced("set_stock_media_key", async move {
+        Ok(stock_media::set_key(
+            &app.path().app_data_dir()?,
+            provider,
+            key.as_deref(),
+        )?)
+    })
+    .await
+}
+
+/// Search stock photos or videos on one provider or every one with a key
+#[tauri::command]
+pub async fn search_stock_media(
+    app: tauri::AppHandle,
+    query: String,
+    options: Option<StockSearchOptions>,
+) -> Result<StockSearchPage, AppError> {
+    diagnostics::traced("search_stock_media", async move {
+        Ok(stock_media::search(
+            &app.path().app_data_dir()?,
+            &query,
+            &options.unwrap_or_default(),
+        )
+        .await?)
+    })
+    .await
+}
+
+/// Download a stock photo or video found by `search_stock_media`, or reuse the
+/// copy downloaded before, as a media entry with its attribution
+#[tauri::command]
+pub async fn download_stock_media(
+    app: tauri::AppHandle,
+    id: String,
+) -> Result<StockDownload, AppError> {
+    diagnostics::traced("download_stock_media", async move {
+        Ok(stock_media::download(&app, &app.path().app_data_dir()?, &id).await?)
+    })
+    .await
+}
+
+/// Queued, running, and recently finished transcodes, oldest first
+#[tauri::command]
+pub fn transcode_jobs(
+    queue: tauri::State<'_, TranscodeQueue>,
+) -> Result<Vec<TranscodeJob>, AppError> {
+    Ok(queue.jobs()?)
+}
+
+/// Stop a running transcode or drop a queued one; false when the job isn't
+/// waiting or running
+#[tauri::command]
+pub fn cancel_transcode(
+    app: tauri::AppHandle,
+    queue: tauri::State<'_, TranscodeQueue>,
+    job_id: String,
+) -> Result<bool, AppError> {
+    Ok(queue.cancel(&app, &job_id)?)
+}
+
+/// Read an SRT or WebVTT file and show its cues as `captions:cue` events,
+/// paused at the start until `sync_captions` says the video is playing
+#[tauri::command]
+pub async fn load_captions(
+    app: tauri::AppHandle,
+    clock: tauri::State<'_, CaptionClock>,
+    path: String,
+) -> Result<CaptionTrack, AppError> {
+    diagnostics::traced("load_captions", async move {
+        let track = captions::load(Path::new(&path))?;
+        clock.load(&app, track.clone())?;
+        Ok(track)
+    })
+    .await
+}
+
+/// Tell the caption clock where the video is and whether it's playing; call
+/// on play, pause, seek, and rate changes, and now and then while playing
+#[tauri::command]
+pub fn sync_captions(
+    clock: tauri::State<'_, CaptionClock>,
+    position: f64,
+    playing: bool,
+    rate: Option<f64>,
+) -> Result<(), AppError> {
+    Ok(clock.sync(position, playing, rate.unwrap_or(1.0))?)
+}
+
+/// Stop showing captions; a last `captions:cue` event clears them
+#[tauri::command]
+pub fn stop_captions(clock: tauri::State<'_, CaptionClock>) -> Result<(), AppError> {
+    Ok(clock.stop()?)
+}
+
+/// Folders added to the media library from elsewhere on disk
+#[tauri::command]
+pub async fn media_library_folders(
+    library: tauri::State<'_, MediaLibrary>,
+) -> Result<Vec<Folder>, AppError> {
+    diagnostics::traced(
+        "media_library_folders",
+        async move { Ok(library.folders()?) },
+    )
+    .await
+}
+
+/// Add a folder to the media library and start watching it; run
+/// `media_library_scan` afterwards to index its files
+#[tauri::command]
+pub async fn media_library_add_folder(
+    app: tauri::AppHandle,
+    library: tauri::State<'_, MediaLibrary>,
+    path: String,
+) -> Result<Folder, AppError> {
+    diagnostics::traced("media_library_add_folder", async move {
+        let root = media_library::library_dir(&resolve_content_dir(&app)?);
+        let folder = library.add_folder(&root, Path::new(&path))?;
+        app.fs_scope().allow_directory(&folder.path, true)?;
+        media_watch::restart(&app);
+        Ok(folder)
+    })
+    .await
+}
+
+/// Files that are in the media library more than once, grouped by content
+#[tauri::command]
+pub async fn find_duplicate_media(
+    app: tauri::AppHandle,
+    library: tauri::State<'_, MediaLibrary>,
+) -> Result<Vec<DuplicateGroup>, AppError> {
+    diagnostics::traced("find_duplicate_media", async move {
+        let root = media_library::library_dir(&resolve_content_dir(&app)?);
+        Ok(library.duplicates(&root)?)
+    })
+    .await
+}
+
+/// Keep one of the library files with content `sha256` (`keep`, or the oldest)
+/// and delete the rest; the kept item gets their tags and collections
+#[tauri::command]
+pub async fn consolidate_duplicate_media(
+    app: tauri::AppHandle,
+    library: tauri::State<'_, MediaLibrary>,
+    sha256: String,
+    keep: Option<String>,
+) -> Result<Consolidation, AppError> {
+    diagnostics::traced("consolidate_duplicate_media", async move {
+        let root = media_library::library_dir(&resolve_content_dir(&app)?);
+        let consolidation = library.consolidate(&root, &sha256, keep.as_deref())?;
+        let report = ScanReport {
+            removed: consolidation.removed.len(),
+            ..ScanReport::default()
+        };
+        if !report.is_empty() {
+            let _ = app.emit(media_watch::MEDIA_LIBRARY_CHANGED_EVENT, report);
+        }
+        Ok(consolidation)
+    })
+    .await
+}
+
+/// Remove an added folder's items from the media library; the files stay on disk
+#[tauri::command]
+pub async fn media_library_remove_folder(
+    app: tauri::AppHandle,
+    library: tauri::State<'_, MediaLibrary>,
+    folder_id: i64,
+) -> Result<(), AppError> {
+    diagnostics::traced("media_library_remove_folder", async move {
+        library.remove_folder(folder_id)?;
+        media_watch::restart(&app);
+        Ok(())
+    })
+    .await
+}
+
+/// Every tag in the media library with how many items have it
+#[tauri::command]
+pub async fn media_library_tags(
+    library: tauri::State<'_, MediaLibrary>,
+) -> Result<Vec<TagCount>, AppError> {
+    diagnostics::traced("media_library_tags", async move { Ok(library.tags()?) }).await
+}
+
+/// Replace the tags of a media library item
+#[tauri::command]
+pub async fn media_library_set_tags(
+    library: tauri::State<'_, MediaLibrary>,
+    item_id: String,
+    tags: Vec<String>,
+) -> Result<(), AppError> {
+    diagnostics::traced("media_library_set_tags", async move {
+        Ok(library.set_tags(&item_id, &tags)?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn media_library_collections(
+    library: tauri::State<'_, MediaLibrary>,
+) -> Result<Vec<Collection>, AppError> {
+    diagnostics::traced("media_library_collections", async move {
+        Ok(library.collections()?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn media_library_create_collection(
+    library: tauri::State<'_, MediaLibrary>,
+    name: String,
+) -> Result<Collection, AppError> {
+    diagnostics::traced("media_library_create_collection", async move {
+        Ok(library.create_collection(&name)?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn media_library_rename_collection(
+    library: tauri::State<'_, MediaLibrary>,
+    collection_id: String,
+    name: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("media_library_rename_collection", async move {
+        Ok(library.rename_collection(&collection_id, &name)?)
+    })
+    .await
+}
+
+/// Delete a collection; its items stay in the library
+#[tauri::command]
+pub async fn media_library_delete_collection(
+    library: tauri::State<'_, MediaLibrary>,
+    collection_id: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("media_library_delete_collection", async move {
+        Ok(library.delete_collection(&collection_id)?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn media_library_add_to_collection(
+    library: tauri::State<'_, MediaLibrary>,
+    collection_id: String,
+    item_ids: Vec<String>,
+) -> Result<(), AppError> {
+    diagnostics::traced("media_library_add_to_collection", async move {
+        Ok(library.add_to_collection(&collection_id, &item_ids)?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn media_library_remove_from_collection(
+    library: tauri::State<'_, MediaLibrary>,
+    collection_id: String,
+    item_ids: Vec<String>,
+) -> Result<(), AppError> {
+    diagnostics::traced("media_library_remove_from_collection", async move {
+        Ok(library.remove_from_collection(&collection_id, &item_ids)?)
+    })
+    .await
+}
+
+/// Add and remove tags on many media library items at once
+#[tauri::command]
+pub async fn tag_media(
+    library: tauri::State<'_, MediaLibrary>,
+    item_ids: Vec<String>,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<(), AppError> {
+    diagnostics::traced("tag_media", async move {
+        Ok(library.tag_items(&item_ids, &add, &remove)?)
+    })
+    .await
+}
+
+/// Rate media library items from 1 to 5 stars, or 0 to clear their rating
+#[tauri::command]
+pub async fn rate_media(
+    library: tauri::State<'_, MediaLibrary>,
+    item_ids: Vec<String>,
+    rating: u8,
+) -> Result<(), AppError> {
+    diagnostics::traced("rate_media", async move {
+        Ok(library.rate_items(&item_ids, rating)?)
+    })
+    .await
+}
+
+/// Search the media library (`christmas AND video AND duration<30s`), sorted
+/// and paged by `options`
+#[tauri::command]
+pub async fn query_media(
+    app: tauri::AppHandle,
+    library: tauri::State<'_, MediaLibrary>,
+    query: String,
+    options: Option<MediaQuery>,
+) -> Result<MediaPage, AppError> {
+    diagnostics::traced("query_media", async move {
+        let root = media_library::library_dir(&resolve_content_dir(&app)?);
+        let mut options = options.unwrap_or_default();
+        options.search = Some(query);
+        Ok(library.query(&root, &options)?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn media_library_smart_collections(
+    library: tauri::State<'_, MediaLibrary>,
+) -> Result<Vec<SmartCollection>, AppError> {
+    diagnostics::traced("media_library_smart_collections", async move {
+        Ok(library.smart_collections()?)
+    })
+    .await
+}
+
+/// Save a search as a smart collection
+#[tauri::command]
+pub async fn media_library_create_smart_collection(
+    library: tauri::State<'_, MediaLibrary>,
+    name: String,
+    query: String,
+) -> Result<SmartCollection, AppError> {
+    diagnostics::traced("media_library_create_smart_collection", async move {
+        Ok(library.create_smart_collection(&name, &query)?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn media_library_update_smart_collection(
+    library: tauri::State<'_, MediaLibrary>,
+    collection_id: String,
+    name: String,
+    query: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("media_library_update_smart_collection", async move {
+        Ok(library.update_smart_collection(&collection_id, &name, &query)?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn media_library_delete_smart_collection(
+    library: tauri::State<'_, MediaLibrary>,
+    collection_id: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("media_library_delete_smart_collection", async move {
+        Ok(library.delete_smart_collection(&collection_id)?)
+    })
+    .await
+}
+
+pub(crate) fn output_window_label(monitor_id: &str) -> String {
+    format!("output-{}", monitor_id)
+}
+
+/// Ids for the monitors, in the same order, that stay the same when displays
+/// are unplugged or the OS lists them in another order. On Windows they come
+/// from the monitor's EDID and port; elsewhere from its name (a model name
+/// on macOS, a connector like "HDMI-1" on Linux), with the resolution and
+/// then the position in the list added only to tell identical monitors apart.
+fn monitor_ids(monitors: &[tauri::Monitor]) -> Vec<String> {
+    let keys: Vec<String> = monitors
+        .iter()
+        .enumerate()
+        .map(|(i, monitor)| {
+            let name = monitor.name().cloned();
+            name.as_deref()
+                .and_then(get_monitor_device_id)
+                .map(|device_id| format!("device:{device_id}"))
+                .or_else(|| name.map(|name| format!("name:{name}")))
+                .unwrap_or_else(|| format!("index:{i}"))
+        })
+        .collect();
+    let keys: Vec<String> = keys
+        .iter()
+        .zip(monitors)
+        .map(|(key, monitor)| {
+            if keys.iter().filter(|other| *other == key).count() > 1 {
+                format!("{key}:{}x{}", monitor.size().width, monitor.size().height)
+            } else {
+                key.clone()
+            }
+        })
+        .collect();
+    let mut seen: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    keys.iter()
+        .map(|key| {
+            let count = seen.entry(key.clone()).or_default();
+            *count += 1;
+            let key = if *count > 1 {
+                format!("{key}#{count}")
+            } else {
+                key.clone()
+            };
+            format!("mon-{}", &hex::encode(Sha256::digest(key.as_bytes()))[..12])
+        })
+        .collect()
+}
+
+/// Fill `monitor` with the output window, or with `windowed` set, place it
+/// on the monitor at that geometry
+fn position_output_window(
+    window: &tauri::WebviewWindow,
+    monitor: &tauri::Monitor,
+    windowed: Option<OutputGeometry>,
+) -> Result<(), AppError> {
+    let pos = monitor.position();
+    let Some(geometry) = windowed else {
+        window.set_position(tauri::Position::Physical(tauri::PhysicalPosition {
+            x: pos.x,
+            y: pos.y,
+        }))?;
+        window.set_fullscreen(true)?;
+        return Ok(());
+    };
+
+    window.set_fullscreen(false)?;
+    window.set_size(tauri::Size::Physical(tauri::PhysicalSize {
+        width: geometry.width.max(1),
+        height: geometry.height.max(1),
+    }))?;
+    window.set_position(tauri::Position::Physical(tauri::PhysicalPosition {
+        x: pos.x + geometry.x,
+        y: pos.y + geometry.y,
+    }))?;
+
+    Ok(())
+}
+
+/// Open output windows on the specified monitors, given by the stable ids
+/// from `get_monitors` or by position. Ids of monitors that aren't connected
+/// are skipped, and windows follow their monitor when the positions change.
+/// With auto-reopen on, the windows come back when a monitor is reconnected.
+/// `mirrors` opens more windows, on the monitors it maps (by id) to outputs
+/// being opened, that show exactly what those outputs do.
+#[tauri::command]
+pub async fn open_output_windows(
+    app: tauri::AppHandle,
+    outputs: tauri::State<'_, OutputMonitors>,
+    monitor_indices: Option<Vec<usize>>,
+    monitor_ids: Option<Vec<String>>,
+    mirrors: Option<std::collections::HashMap<String, String>>,
+) -> Result<(), AppError> {
+    diagnostics::traced("open_output_windows", async move {
+        let mut monitor_ids = monitor_ids.unwrap_or_default();
+        let mirrors = mirrors.unwrap_or_default();
+        for (mirror, source) in &mirrors {
+            if !monitor_ids.contains(source) {
+                return Err(format!("{source} isn't being opened, so it can't be mirrored").into());
+            }
+            if monitor_ids.contains(mirror) {
+                return Err(format!("{mirror} can't both be an output and mirror one").into());
+            }
+        }
+        monitor_ids.extend(mirrors.keys().cloned());
+        outputs.set_mirrors(mirrors);
+        let wanted = open_outputs(&app, &monitor_indices.unwrap_or_default(), &monitor_ids)?;
+        outputs.set_wanted(wanted);
+        Ok(())
+    })
+    .await
+}
+
+/// Show output windows on the monitors at `monitor_indices` and with
+/// `monitor_ids`, and close the others; returns the ids asked for, including
+/// those of monitors that aren't connected
+pub(crate) fn open_outputs(
+    app: &tauri::AppHandle,
+    monitor_indices: &[usize],
+    monitor_ids: &[String],
+) -> Result<Vec<String>, AppError> {
+    let monitors = app.available_monitors()?;
+    let ids = self::monitor_ids(&monitors);
+
+    let mut requested: Vec<String> = monitor_indices
+        .iter()
+        .filter_map(|idx| ids.get(*idx).cloned())
+        .collect();
+    let mut wanted: Vec<usize> = Vec::new();
+    for id in monitor_ids {
+        match ids.iter().position(|candidate| candidate == id) {
+            Some(idx) => wanted.push(idx),
+            None => log::info!("Monitor {id} isn't connected; no output window for it"),
+        }
+        requested.push(id.clone());
+    }
+    wanted.extend(monitor_indices.iter().filter(|idx| **idx < monitors.len()));
+    wanted.sort_unstable();
+    wanted.dedup();
+    requested.sort();
+    requested.dedup();
+
+    let mut desired_labels = std::collections::HashSet::new();
+
+    for idx in &wanted {
+        desired_labels.insert(output_window_label(&ids[*idx]));
+    }
+
+    // Close any output windows not in the desired set (including legacy "output" window)
+    for (label, window) in app.webview_windows() {
+        if (label == "output" || label.starts_with("output-")) && !desired_labels.contains(&label) {
+            close_output_window(app, &label, &window);
+        }
+    }
+
+    // Create or reposition desired output windows
+    for idx in wanted {
+        let label = output_window_label(&ids[idx]);
+        let outputs = app.try_state::<OutputMonitors>();
+        let windowed = outputs
+            .as_ref()
+            .and_then(|outputs| outputs.windowed(&ids[idx]));
+        if let Some(window) = app.get_webview_window(&label) {
+            window.show()?;
+            position_output_window(&window, &monitors[idx], windowed)?;
+            // New windows fetch theirs when they load
+            if let Some(outputs) = &outputs {
+                app.emit_to(&label, OUTPUT_TRANSFORM_EVENT, outputs.transform(&ids[idx]))?;
+                app.emit_to(&label, OUTPUT_MIRROR_EVENT, outputs.mirror_of(&ids[idx]))?;
+            }
+            continue;
+        }
+
+        let window = build_output_window(app, label, true)?;
+        position_output_window(&window, &monitors[idx], windowed)?;
+    }
+    send_output_spans(app)?;
+    keep_awake::refresh(app, None);
+
+    Ok(requested)
+}
+
+/// A new output window, black until its page has loaded
+fn build_output_window(
+    app: &tauri::AppHandle,
+    label: String,
+    visible: bool,
+) -> Result<tauri::WebviewWindow, AppError> {
+    let builder =
+        tauri::WebviewWindowBuilder::new(app, label, tauri::WebviewUrl::App("/output".into()))
+            .title("Presentation Output")
+            .decorations(false)
+            .always_on_top(true)
+            .visible(visible)
+            .background_color(tauri::window::Color(0, 0, 0, 255));
+
+    Ok(builder.build()?)
+}
+
+/// Close an output window, or with pre-warming on hide it to show again
+fn close_output_window(app: &tauri::AppHandle, label: &str, window: &tauri::WebviewWindow) {
+    let prewarm = app
+        .try_state::<OutputMonitors>()
+        .is_some_and(|outputs| outputs.prewarm());
+    if prewarm && label.starts_with("output-") {
+        let _ = window.hide();
+    } else {
+        let _ = window.close();
+    }
+}
+
+/// Make hidden output windows, ready to show, on the connected monitors
+/// outputs were last open on
+pub(crate) fn prewarm_outputs(app: &tauri::AppHandle) {
+    let Some(outputs) = app.try_state::<OutputMonitors>() else {
+        return;
+    };
+    let Ok(monitors) = app.available_monitors() else {
+        return;
+    };
+    let ids = monitor_ids(&monitors);
+    for id in outputs.recent() {
+        let Some(idx) = ids.iter().position(|candidate| *candidate == id) else {
+            continue;
+        };
+        let label = output_window_label(&id);
+        if app.get_webview_window(&label).is_some() {
+            continue;
+        }
+        let result = build_output_window(app, label, false).and_then(|window| {
+            window.set_position(tauri::Position::Physical(*monitors[idx].position()))?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::warn!("Could not pre-warm the output on {id}: {e}");
+        }
+    }
+}
+
+/// Close all output windows
+#[tauri::command]
+pub async fn close_output_windows(
+    app: tauri::AppHandle,
+    outputs: tauri::State<'_, OutputMonitors>,
+) -> Result<(), AppError> {
+    diagnostics::traced("close_output_windows", async move {
+        outputs.set_wanted(Vec::new());
+        for (label, window) in app.webview_windows() {
+            if label == "output" || label.starts_with("output-") {
+                close_output_window(&app, &label, &window);
+            }
+        }
+        keep_awake::refresh(&app, None);
+        Ok(())
+    })
+    .await
+}
+
+/// Keep output windows made, hidden, while the outputs are closed, so they
+/// show without the first-load delay
+#[tauri::command]
+pub async fn set_output_prewarm(
+    app: tauri::AppHandle,
+    outputs: tauri::State<'_, OutputMonitors>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    diagnostics::traced("set_output_prewarm", async move {
+        outputs.set_prewarm(enabled);
+        if enabled {
+            prewarm_outputs(&app);
+        } else {
+            // Output windows are only hidden to keep them warm
+            for (label, window) in app.webview_windows() {
+                if label.starts_with("output-") && !window.is_visible().unwrap_or(true) {
+                    let _ = window.close();
+                }
+            }
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Grab what the output window `label` is showing, from the screen. Saved as
+/// a PNG at `path`, or returned as one without a path.
+#[tauri::command]
+pub async fn capture_output(
+    app: tauri::AppHandle,
+    label: String,
+    path: Option<String>,
+) -> Result<OutputCapture, AppError> {
+    diagnostics::traced("capture_output", async move {
+        let window = app
+            .get_webview_window(&label)
+            .ok_or_else(|| format!("No output window {label}"))?;
+        let region = Region::of_window(&window)?;
+        Ok(screen_capture::capture_png(
+            &region,
+            path.as_deref().map(Path::new),
+        )?)
+    })
+    .await
+}
+
+/// Record what the output window `label` shows to an MP4 at `path`, with
+/// the computer's audio when `options` asks for it; "virtual" records the
+/// virtual output. `recording:status` reports the time and size every few
+/// seconds.
+#[tauri::command]
+pub async fn start_recording(
+    app: tauri::AppHandle,
+    recorder: tauri::State<'_, Recorder>,
+    virtual_output: tauri::State<'_, VirtualOutput>,
+    label: String,
+    path: String,
+    options: Option<RecordingOptions>,
+) -> Result<RecordingStatus, AppError> {
+    diagnostics::traced("start_recording", async move {
+        let source = if label == VIRTUAL_OUTPUT_LABEL {
+            let size = virtual_output
+                .options()
+                .ok_or("The virtual output isn't running")?;
+            RecordingSource::Virtual {
+                width: size.width,
+                height: size.height,
+                frame: virtual_output.frame(),
+            }
+        } else {
+            let window = app
+                .get_webview_window(&label)
+                .ok_or_else(|| format!("No output window {label}"))?;
+            RecordingSource::Screen(Region::of_window(&window)?)
+        };
+        Ok(recorder.start(source, Path::new(&path), options.unwrap_or_default())?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn pause_recording(
+    recorder: tauri::State<'_, Recorder>,
+) -> Result<RecordingStatus, AppError> {
+    diagnostics::traced("pause_recording", async move { Ok(recorder.pause()?) }).await
+}
+
+#[tauri::command]
+pub async fn resume_recording(
+    recorder: tauri::State<'_, Recorder>,
+) -> Result<RecordingStatus, AppError> {
+    diagnostics::traced("resume_recording", async move { Ok(recorder.resume()?) }).await
+}
+
+/// Finish the recording, joining the parts recorded between pauses
+#[tauri::command]
+pub async fn stop_recording(
+    recorder: tauri::State<'_, Recorder>,
+) -> Result<RecordingStatus, AppError> {
+    diagnostics::traced("stop_recording", async move { Ok(recorder.stop()?) }).await
+}
+
+#[tauri::command]
+pub async fn get_recording_status(
+    recorder: tauri::State<'_, Recorder>,
+) -> Result<RecordingStatus, AppError> {
+    diagnostics::traced("get_recording_status", async move { Ok(recorder.status()) }).await
+}
+
+/// Show `pattern` on every output window, drawn at the window's size, until
+/// `clear_output_overlay`
+#[tauri::command]
+pub async fn show_output_test_pattern(
+    app: tauri::AppHandle,
+    pattern: TestPattern,
+) -> Result<(), AppError> {
+    diagnostics::traced("show_output_test_pattern", async move {
+        for (number, window, monitor) in open_output_windows_in_order(&app)? {
+            let size = window.inner_size()?;
+            let label = match &monitor {
+                Some(monitor) => format!(
+                    "Output {number} · {} · {}×{}",
+                    monitor.name, size.width, size.height
+                ),
+                None => format!("Output {number} · {}×{}", size.width, size.height),
+            };
+            let image = test_pattern::draw_pattern(pattern, size.width, size.height, &label)?;
+            let overlay = OutputOverlay {
+                image: Some(test_pattern::data_url(&image)?),
+            };
+            app.emit_to(window.label(), OUTPUT_OVERLAY_EVENT, overlay)?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Show each output's number and monitor on it in large type, until
+/// `clear_output_overlay`
+#[tauri::command]
+pub async fn identify_outputs(app: tauri::AppHandle) -> Result<(), AppError> {
+    diagnostics::traced("identify_outputs", async move {
+        let windows = open_output_windows_in_order(&app)?;
+        let outputs = app.try_state::<OutputMonitors>();
+        let mirror_of = |monitor: &Option<MonitorInfo>| {
+            let outputs = outputs.as_ref()?;
+            outputs.mirror_of(&monitor.as_ref()?.id)
+        };
+        for (number, window, monitor) in &windows {
+            let size = window.inner_size()?;
+            let subtitle = match monitor {
+                Some(monitor) => format!("{} · {}×{}", monitor.name, size.width, size.height),
+                None => format!("{}×{}", size.width, size.height),
+            };
+            // Mirrors are named after the output they show
+            let source = mirror_of(monitor).and_then(|source| {
+                windows
+                    .iter()
+                    .find(|(_, _, other)| other.as_ref().is_some_and(|other| other.id == source))
+            });
+            let title = match source {
+                Some((source, _, _)) => format!("Mirror of Output {source}"),
+                None => format!("Output {number}"),
+            };
+            let image = test_pattern::draw_identify(size.width, size.height, &title, &subtitle)?;
+            let overlay = OutputOverlay {
+                image: Some(test_pattern::data_url(&image)?),
+            };
+            app.emit_to(window.label(), OUTPUT_OVERLAY_EVENT, overlay)?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Take test patterns and identification off the output windows
+#[tauri::command]
+pub async fn clear_output_overlay(app: tauri::AppHandle) -> Result<(), AppError> {
+    diagnostics::traced("clear_output_overlay", async move {
+        for (_, window, _) in open_output_windows_in_order(&app)? {
+            app.emit_to(
+                window.label(),
+                OUTPUT_OVERLAY_EVENT,
+                OutputOverlay { image: None },
+            )?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// The open output windows numbered from 1 in the order of their monitors,
+/// with the monitor each is on when it's connected
+fn open_output_windows_in_order(
+    app: &tauri::AppHandle,
+) -> Result<Vec<(usize, tauri::WebviewWindow, Option<MonitorInfo>)>, AppError> {
+    let monitors = list_monitors(app)?;
+    let mut windows: Vec<(tauri::WebviewWindow, Option<MonitorInfo>)> = app
+        .webview_windows()
+        .into_iter()
+        .filter(|(label, _)| label == "output" || label.starts_with("output-"))
+        .map(|(label, window)| {
+            let monitor = monitors
+                .iter()
+                .find(|monitor| output_window_label(&monitor.id) == label)
+                .cloned();
+            (window, monitor)
+        })
+        .collect();
+    windows.sort_by_key(|(window, monitor)| {
+        (
+            monitor.as_ref().map_or(usize::MAX, |monitor| monitor.index),
+            window.label().to_string(),
+        )
+    });
+    Ok(windows
+        .into_iter()
+        .enumerate()
+        .map(|(i, (window, monitor))| (i + 1, window, monitor))
+        .collect())
 }
 
-/// True if `path` is absolute and its first existing ancestor lies under `legacy/tauri_old/content`.
-/// Used to ignore stale persisted paths (e.g. old Documents) without calling `set_content_dir`.
+/// What the outputs are showing; windows call this when they load
 #[tauri::command]
-pub fn is_content_dir_under_repo(path: String) -> Result<bool, String> {
-    let new_dir = PathBuf::from(path.trim());
-    if new_dir.as_os_str().is_empty() || !new_dir.is_absolute() {
-        return Ok(false);
-    }
-    let repo_root = repo_content_root_dir()?;
-    match existing_path_prefix(&new_dir) {
-        Ok(prefix) => Ok(path_is_within_repo_content(&repo_root, &prefix)),
-        Err(_) => Ok(false),
-    }
+pub async fn get_program_state(
+    program: tauri::State<'_, Program>,
+) -> Result<ProgramState, AppError> {
+    diagnostics::traced("get_program_state", async move { Ok(program.state()) }).await
 }
 
-fn move_file_with_fallback(source: &Path, destination: &Path) -> Result<(), String> {
-    if let Some(parent) = destination.parent() {
-        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    }
+#[tauri::command]
+pub async fn get_program_presentation(
+    program: tauri::State<'_, Program>,
+) -> Result<ProgramPresentation, AppError> {
+    diagnostics::traced("get_program_presentation", async move {
+        Ok(program.presentation())
+    })
+    .await
+}
 
-  match std::fs::rename(source, destination) {
-    Ok(()) => Ok(()),
-    Err(_error) => {
-      std::fs::copy(source, destination).map_err(|e| e.to_string())?;
-      std::fs::remove_file(source).map_err(|e| e.to_string())?;
-      Ok(())
-    }
-  }
+/// Take on where the main window has the live presentation; broadcast as
+/// `program:state`
+#[tauri::command]
+pub async fn set_live_state(
+    program: tauri::State<'_, Program>,
+    state: LiveState,
+) -> Result<ProgramState, AppError> {
+    diagnostics::traced("set_live_state", async move { Ok(program.set_live(state)) }).await
 }
 
-fn move_dir_contents(source: &Path, destination: &Path) -> Result<(), String> {
-    if !source.exists() {
-        return Ok(());
-    }
+/// Replace the live presentation, or drop it with None; broadcast as
+/// `program:presentation`
+#[tauri::command]
+pub async fn set_program_presentation(
+    program: tauri::State<'_, Program>,
+    presentation: Option<serde_json::Value>,
+    path: Option<String>,
+) -> Result<ProgramPresentation, AppError> {
+    diagnostics::traced("set_program_presentation", async move {
+        Ok(program.set_presentation(presentation, path))
+    })
+    .await
+}
 
-    std::fs::create_dir_all(destination).map_err(|e| e.to_string())?;
-    for entry in std::fs::read_dir(source).map_err(|e| e.to_string())? {
-        let entry = entry.map_err(|e| e.to_string())?;
-        let entry_path = entry.path();
-        let target_path = destination.join(entry.file_name());
+/// Black out the outputs, show the logo, clear the text, or go back to
+/// the live slide
+#[tauri::command]
+pub async fn set_program_screen(
+    program: tauri::State<'_, Program>,
+    screen: ProgramScreen,
+) -> Result<ProgramState, AppError> {
+    diagnostics::traced("set_program_screen", async move {
+        Ok(program.set_screen(screen))
+    })
+    .await
+}
 
-        if entry.file_type().map_err(|e| e.to_string())?.is_dir() {
-            move_dir_contents(&entry_path, &target_path)?;
-            if entry_path.read_dir().map_err(|e| e.to_string())?.next().is_none() {
-                let _ = std::fs::remove_dir(&entry_path);
+/// Set the image shown for the logo screen
+#[tauri::command]
+pub async fn set_program_logo(
+    program: tauri::State<'_, Program>,
+    path: Option<String>,
+) -> Result<ProgramState, AppError> {
+    diagnostics::traced("set_program_logo", async move {
+        if let Some(path) = &path {
+            if !Path::new(path).is_file() {
+                return Err(format!("Logo image not found: {path}").into());
             }
-        } else {
-            if target_path.exists() {
-                std::fs::remove_file(&target_path).map_err(|e| e.to_string())?;
+        }
+        Ok(program.set_logo(path))
+    })
+    .await
+}
+
+/// The logo screen's image, for windows to show
+#[tauri::command]
+pub async fn read_program_logo(program: tauri::State<'_, Program>) -> Result<Vec<u8>, AppError> {
+    diagnostics::traced("read_program_logo", async move {
+        let path = program
+            .state()
+            .logo_path
+            .ok_or_else(|| "No logo image is set".to_string())?;
+        Ok(std::fs::read(path)?)
+    })
+    .await
+}
+
+/// Make the outputs on the monitors in `windows` (by monitor id) windows of
+/// the given size and place on their monitor, and the others fullscreen.
+/// Open output windows are moved to match.
+#[tauri::command]
+pub async fn set_output_windowed(
+    app: tauri::AppHandle,
+    outputs: tauri::State<'_, OutputMonitors>,
+    windows: std::collections::HashMap<String, OutputGeometry>,
+) -> Result<(), AppError> {
+    diagnostics::traced("set_output_windowed", async move {
+        outputs.set_windowed(windows);
+        let monitors = app.available_monitors()?;
+        for (id, monitor) in monitor_ids(&monitors).iter().zip(&monitors) {
+            if let Some(window) = app.get_webview_window(&output_window_label(id)) {
+                position_output_window(&window, monitor, outputs.windowed(id))?;
             }
-            move_file_with_fallback(&entry_path, &target_path)?;
         }
-    }
+        send_output_spans(&app)?;
+        Ok(())
+    })
+    .await
+}
 
-    Ok(())
+/// Fit the program differently on the outputs of the monitors in
+/// `transforms` (by monitor id), for 4:3 projectors, LED walls and the like,
+/// and as it is on the others. Open output windows get `output:transform`.
+#[tauri::command]
+pub async fn set_output_transforms(
+    app: tauri::AppHandle,
+    outputs: tauri::State<'_, OutputMonitors>,
+    transforms: std::collections::HashMap<String, OutputTransform>,
+) -> Result<(), AppError> {
+    diagnostics::traced("set_output_transforms", async move {
+        outputs.set_transforms(transforms);
+        for label in app.webview_windows().into_keys() {
+            if let Some(id) = label.strip_prefix("output-") {
+                app.emit_to(&label, OUTPUT_TRANSFORM_EVENT, outputs.transform(id))?;
+            }
+        }
+        Ok(())
+    })
+    .await
 }
 
-/// Get the app data directory path
+/// Id of the monitor whose output the output window calling mirrors, or
+/// None when it isn't a mirror
 #[tauri::command]
-pub fn get_app_data_dir(app: tauri::AppHandle) -> Result<String, String> {
-    app.path()
-        .app_data_dir()
-        .map(|p| p.to_string_lossy().to_string())
-        .map_err(|e| e.to_string())
+pub async fn get_output_mirror(
+    window: tauri::WebviewWindow,
+    outputs: tauri::State<'_, OutputMonitors>,
+) -> Result<Option<String>, AppError> {
+    diagnostics::traced("get_output_mirror", async move {
+        Ok(window
+            .label()
+            .strip_prefix("output-")
+            .and_then(|id| outputs.mirror_of(id)))
+    })
+    .await
 }
 
-/// Get the documents app data directory path
+/// The transform of the output window calling
 #[tauri::command]
-pub fn get_documents_data_dir(app: tauri::AppHandle) -> Result<String, String> {
-    resolve_content_dir(&app).map(|p| p.to_string_lossy().to_string())
+pub async fn get_output_transform(
+    window: tauri::WebviewWindow,
+    outputs: tauri::State<'_, OutputMonitors>,
+) -> Result<OutputTransform, AppError> {
+    diagnostics::traced("get_output_transform", async move {
+        Ok(window
+            .label()
+            .strip_prefix("output-")
+            .map(|id| outputs.transform(id))
+            .unwrap_or_default())
+    })
+    .await
 }
 
-/// Set the content directory (optionally moving existing data)
+/// Make one picture across the outputs in `span`, left to right, blended
+/// where they overlap; None gives each output the whole program again. Open
+/// output windows get their slice as `output:span`.
 #[tauri::command]
-pub async fn set_content_dir(
+pub async fn set_output_span(
     app: tauri::AppHandle,
-    path: String,
-    move_existing: bool,
-    media_library_dir: Option<String>,
-) -> Result<String, String> {
-    let new_dir = PathBuf::from(path.trim());
-    if new_dir.as_os_str().is_empty() {
-        return Err("Content folder path is required".to_string());
-    }
-    if !new_dir.is_absolute() {
-        return Err("Content folder path must be absolute".to_string());
-    }
+    spans: tauri::State<'_, OutputSpans>,
+    span: Option<OutputSpan>,
+) -> Result<(), AppError> {
+    diagnostics::traced("set_output_span", async move {
+        if let Some(span) = &span {
+            span.validate()?;
+        }
+        spans.set(span);
+        send_output_spans(&app)
+    })
+    .await
+}
 
-    ensure_new_content_dir_under_repo(&new_dir)?;
+/// The slice of the span the output window calling shows, or None when it
+/// isn't in one
+#[tauri::command]
+pub async fn get_output_span(
+    app: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+) -> Result<Option<SpanSlice>, AppError> {
+    diagnostics::traced("get_output_span", async move {
+        let Some(id) = window.label().strip_prefix("output-") else {
+            return Ok(None);
+        };
+        Ok(output_span_slices(&app)?.remove(id))
+    })
+    .await
+}
 
-    let current_dir = resolve_content_dir(&app)?;
-    if new_dir == current_dir {
-        write_content_dir_config(&app, &new_dir)?;
-        return Ok(new_dir.to_string_lossy().to_string());
-    }
+/// Each spanned output's slice, by monitor id, at its window's size
+fn output_span_slices(
+    app: &tauri::AppHandle,
+) -> Result<std::collections::HashMap<String, SpanSlice>, AppError> {
+    let Some(span) = app.try_state::<OutputSpans>().and_then(|spans| spans.get()) else {
+        return Ok(std::collections::HashMap::new());
+    };
+    let outputs = app.try_state::<OutputMonitors>();
+    let sizes = list_monitors(app)?
+        .into_iter()
+        .map(|monitor| {
+            let size = outputs
+                .as_ref()
+                .and_then(|outputs| outputs.windowed(&monitor.id))
+                .map_or((monitor.width, monitor.height), |geometry| {
+                    (geometry.width, geometry.height)
+                });
+            (monitor.id, size)
+        })
+        .collect();
+    Ok(span.slices(&sizes))
+}
 
-    if new_dir.starts_with(&current_dir) {
-        return Err("Content folder cannot be inside the current folder".to_string());
+/// Send every open output window its slice of the span, or None
+fn send_output_spans(app: &tauri::AppHandle) -> Result<(), AppError> {
+    let slices = output_span_slices(app)?;
+    for label in app.webview_windows().into_keys() {
+        if let Some(id) = label.strip_prefix("output-") {
+            app.emit_to(&label, OUTPUT_SPAN_EVENT, slices.get(id))?;
+        }
     }
+    Ok(())
+}
 
-    std::fs::create_dir_all(&new_dir).map_err(|e| e.to_string())?;
-
-    if move_existing {
-        move_dir_contents(&current_dir, &new_dir)?;
-    }
+/// The color correction of each output, by monitor id; outputs not listed
+/// are left as they are
+#[tauri::command]
+pub async fn get_output_colors(
+    colors: tauri::State<'_, OutputColors>,
+) -> Result<std::collections::HashMap<String, OutputColor>, AppError> {
+    diagnostics::traced("get_output_colors", async move { Ok(colors.all()) }).await
+}
 
-    if let Some(media_dir) = media_library_dir {
-        let media_source = PathBuf::from(media_dir);
-        let media_target = new_dir.join(MEDIA_LIBRARY_DIR_NAME);
-        if media_source.exists() && media_source != media_target {
-            move_dir_contents(&media_source, &media_target)?;
-            if media_source.read_dir().map_err(|e| e.to_string())?.next().is_none() {
-                let _ = std::fs::remove_dir(&media_source);
-            }
+/// Correct the colors of the output on the monitor `monitor_id`, or take the
+/// correction off with None. Saved, and shown live on the output window for
+/// calibration.
+#[tauri::command]
+pub async fn set_output_color(
+    app: tauri::AppHandle,
+    colors: tauri::State<'_, OutputColors>,
+    monitor_id: String,
+    color: Option<OutputColor>,
+) -> Result<OutputColor, AppError> {
+    diagnostics::traced("set_output_color", async move {
+        let color = colors.set(&monitor_id, color)?;
+        let label = output_window_label(&monitor_id);
+        if app.get_webview_window(&label).is_some() {
+            app.emit_to(&label, OUTPUT_COLOR_EVENT, color)?;
         }
-    }
+        Ok(color)
+    })
+    .await
+}
+
+/// The color correction of the output window calling
+#[tauri::command]
+pub async fn get_output_color(
+    window: tauri::WebviewWindow,
+    colors: tauri::State<'_, OutputColors>,
+) -> Result<OutputColor, AppError> {
+    diagnostics::traced("get_output_color", async move {
+        Ok(window
+            .label()
+            .strip_prefix("output-")
+            .map(|id| colors.get(id))
+            .unwrap_or_default())
+    })
+    .await
+}
 
-    write_content_dir_config(&app, &new_dir)?;
+/// Reopen output windows on their monitors when those are reconnected, and
+/// move them when the monitors are rearranged
+#[tauri::command]
+pub async fn set_output_auto_reopen(
+    outputs: tauri::State<'_, OutputMonitors>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    diagnostics::traced("set_output_auto_reopen", async move {
+        outputs.set_auto_reopen(enabled);
+        Ok(())
+    })
+    .await
+}
 
-    Ok(new_dir.to_string_lossy().to_string())
+/// Put output windows back in front when they're covered, minimized or
+/// dropped out of fullscreen, and with `block_popups` keep system pop-ups
+/// such as notifications off them where the system allows it
+#[tauri::command]
+pub async fn set_output_guard(
+    guard: tauri::State<'_, OutputGuard>,
+    keep_in_front: bool,
+    block_popups: bool,
+) -> Result<(), AppError> {
+    diagnostics::traced("set_output_guard", async move {
+        guard.set(keep_in_front, block_popups);
+        Ok(())
+    })
+    .await
 }
 
-/// Ensure the app data directory exists
+/// Tell the watchdog the calling output window is still running; see
+/// `output_health`
 #[tauri::command]
-pub async fn ensure_app_data_dir(app: tauri::AppHandle) -> Result<String, String> {
-    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+pub async fn report_output_heartbeat(
+    window: tauri::WebviewWindow,
+    health: tauri::State<'_, OutputHealth>,
+) -> Result<(), AppError> {
+    diagnostics::traced("report_output_heartbeat", async move {
+        health.beat(window.label());
+        Ok(())
+    })
+    .await
+}
 
-    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+/// Keep the mouse cursor from crossing onto the output windows
+#[tauri::command]
+pub async fn set_output_pointer_confined(
+    confinement: tauri::State<'_, PointerConfinement>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    diagnostics::traced("set_output_pointer_confined", async move {
+        confinement.set(enabled);
+        Ok(())
+    })
+    .await
+}
 
-    Ok(dir.to_string_lossy().to_string())
+/// Start sending the presentation as an NDI source, or restart it with new
+/// options
+#[tauri::command]
+pub async fn start_ndi_output(
+    ndi: tauri::State<'_, NdiOutput>,
+    options: Option<NdiOptions>,
+) -> Result<NdiStatus, AppError> {
+    diagnostics::traced("start_ndi_output", async move {
+        Ok(ndi.start(options.unwrap_or_default())?)
+    })
+    .await
 }
 
-/// Ensure the documents app data directory exists
 #[tauri::command]
-pub async fn ensure_documents_data_dir(app: tauri::AppHandle) -> Result<String, String> {
-    let dir = resolve_content_dir(&app)?;
+pub async fn stop_ndi_output(ndi: tauri::State<'_, NdiOutput>) -> Result<(), AppError> {
+    diagnostics::traced("stop_ndi_output", async move {
+        ndi.stop();
+        Ok(())
+    })
+    .await
+}
 
-    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn get_ndi_status(ndi: tauri::State<'_, NdiOutput>) -> Result<NdiStatus, AppError> {
+    diagnostics::traced("get_ndi_status", async move { Ok(ndi.status()) }).await
+}
 
-    Ok(dir.to_string_lossy().to_string())
+/// Show a slide of a saved bundle on the NDI source; black without one
+#[tauri::command]
+pub async fn set_ndi_slide(
+    ndi: tauri::State<'_, NdiOutput>,
+    path: Option<String>,
+    slide_id: Option<String>,
+) -> Result<(), AppError> {
+    diagnostics::traced("set_ndi_slide", async move {
+        match (path, slide_id) {
+            (Some(path), Some(slide_id)) => ndi.show(Path::new(&path), &slide_id)?,
+            _ => ndi.clear(),
+        }
+        Ok(())
+    })
+    .await
 }
 
-/// Ensure a subdirectory exists within app data
+/// Share an output as a Spout (Windows) or Syphon (macOS) texture, named
+/// after its monitor and at its size unless `options` say otherwise
 #[tauri::command]
-pub async fn ensure_app_data_subdir(
+pub async fn enable_texture_share(
     app: tauri::AppHandle,
-    sub_dir: String,
-) -> Result<String, String> {
-    let base_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    sharing: tauri::State<'_, TextureSharing>,
+    output_id: String,
+    options: Option<TextureShareOptions>,
+) -> Result<SharedOutput, AppError> {
+    diagnostics::traced("enable_texture_share", async move {
+        let options = options.unwrap_or_default();
+        let monitor = list_monitors(&app)?
+            .into_iter()
+            .find(|monitor| monitor.id == output_id);
+        let name = options
+            .name
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| {
+                let monitor = monitor
+                    .as_ref()
+                    .map_or(output_id.as_str(), |monitor| monitor.name.as_str());
+                format!("Church Presenter - {monitor}")
+            });
+        let width = options
+            .width
+            .or(monitor.as_ref().map(|monitor| monitor.width))
+            .unwrap_or(1920);
+        let height = options
+            .height
+            .or(monitor.as_ref().map(|monitor| monitor.height))
+            .unwrap_or(1080);
+        Ok(sharing.enable(&output_id, name.trim(), width, height)?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn disable_texture_share(
+    sharing: tauri::State<'_, TextureSharing>,
+    output_id: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("disable_texture_share", async move {
+        sharing.disable(&output_id);
+        Ok(())
+    })
+    .await
+}
 
-    let dir = base_dir.join(&sub_dir);
-    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn list_texture_shares(
+    sharing: tauri::State<'_, TextureSharing>,
+) -> Result<Vec<SharedOutput>, AppError> {
+    diagnostics::traced("list_texture_shares", async move { Ok(sharing.list()) }).await
+}
 
-    Ok(dir.to_string_lossy().to_string())
+/// Show a slide of a saved bundle on the shared textures; black without one
+#[tauri::command]
+pub async fn set_texture_share_slide(
+    sharing: tauri::State<'_, TextureSharing>,
+    path: Option<String>,
+    slide_id: Option<String>,
+) -> Result<(), AppError> {
+    diagnostics::traced("set_texture_share_slide", async move {
+        let slide = path.as_deref().map(Path::new).zip(slide_id.as_deref());
+        Ok(sharing.show(slide)?)
+    })
+    .await
 }
 
-/// Ensure a subdirectory exists within documents app data
+/// DeckLink outputs ffmpeg can play to; rejects with code 'ffmpeg' when
+/// ffmpeg was built without DeckLink support
 #[tauri::command]
-pub async fn ensure_documents_data_subdir(
-    app: tauri::AppHandle,
-    sub_dir: String,
-) -> Result<String, String> {
-    let base_dir = resolve_content_dir(&app)?;
+pub async fn list_decklink_devices() -> Result<Vec<String>, AppError> {
+    diagnostics::traced(
+        "list_decklink_devices",
+        async move { Ok(decklink::devices()?) },
+    )
+    .await
+}
 
-    let dir = base_dir.join(&sub_dir);
-    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn list_decklink_formats(device: String) -> Result<Vec<DeckLinkFormat>, AppError> {
+    diagnostics::traced("list_decklink_formats", async move {
+        Ok(decklink::formats(&device)?)
+    })
+    .await
+}
 
-    Ok(dir.to_string_lossy().to_string())
+/// Play the presentation out of a DeckLink card in the format with
+/// `format_code`, replacing a playout already on it. `signal` picks the
+/// whole slide (the default), or the fill or key for a switcher's keyer.
+#[tauri::command]
+pub async fn start_decklink_output(
+    decklink: tauri::State<'_, DeckLinkOutput>,
+    device: String,
+    format_code: String,
+    signal: Option<OutputSignal>,
+) -> Result<DeckLinkPlayout, AppError> {
+    diagnostics::traced("start_decklink_output", async move {
+        Ok(decklink.start(&device, &format_code, signal.unwrap_or_default())?)
+    })
+    .await
 }
 
-/// Read a JSON file from app data directory
 #[tauri::command]
-pub async fn read_app_data_file(app: tauri::AppHandle, filename: String) -> Result<String, String> {
-    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+pub async fn stop_decklink_output(
+    decklink: tauri::State<'_, DeckLinkOutput>,
+    device: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("stop_decklink_output", async move {
+        decklink.stop(&device);
+        Ok(())
+    })
+    .await
+}
 
-    let path = dir.join(&filename);
+#[tauri::command]
+pub async fn list_decklink_playouts(
+    decklink: tauri::State<'_, DeckLinkOutput>,
+) -> Result<Vec<DeckLinkPlayout>, AppError> {
+    diagnostics::traced("list_decklink_playouts", async move { Ok(decklink.list()) }).await
+}
 
-    if !path.exists() {
-        return Err("File not found".to_string());
-    }
+/// Play a slide of a saved bundle out of the DeckLink cards; black without
+/// one
+#[tauri::command]
+pub async fn set_decklink_slide(
+    decklink: tauri::State<'_, DeckLinkOutput>,
+    path: Option<String>,
+    slide_id: Option<String>,
+) -> Result<(), AppError> {
+    diagnostics::traced("set_decklink_slide", async move {
+        decklink.show(path.as_deref().map(Path::new).zip(slide_id.as_deref()));
+        Ok(())
+    })
+    .await
+}
 
-    std::fs::read_to_string(&path).map_err(|e| e.to_string())
+/// Start drawing the program offscreen, with no window, for recordings of
+/// "virtual"; or restart it at a new size
+#[tauri::command]
+pub async fn start_virtual_output(
+    virtual_output: tauri::State<'_, VirtualOutput>,
+    options: Option<VirtualOutputOptions>,
+) -> Result<VirtualOutputStatus, AppError> {
+    diagnostics::traced("start_virtual_output", async move {
+        Ok(virtual_output.start(options.unwrap_or_default()))
+    })
+    .await
 }
 
-/// Read a JSON file from documents app data directory
 #[tauri::command]
-pub async fn read_documents_data_file(
-    app: tauri::AppHandle,
-    filename: String,
-) -> Result<String, String> {
-    let dir = resolve_content_dir(&app)?;
+pub async fn stop_virtual_output(
+    virtual_output: tauri::State<'_, VirtualOutput>,
+) -> Result<(), AppError> {
+    diagnostics::traced("stop_virtual_output", async move {
+        virtual_output.stop();
+        Ok(())
+    })
+    .await
+}
 
-    let path = dir.join(&filename);
+#[tauri::command]
+pub async fn get_virtual_output_status(
+    virtual_output: tauri::State<'_, VirtualOutput>,
+) -> Result<VirtualOutputStatus, AppError> {
+    diagnostics::traced("get_virtual_output_status", async move {
+        Ok(virtual_output.status())
+    })
+    .await
+}
 
-    if !path.exists() {
-        return Err("File not found".to_string());
-    }
+/// Load a video to play in an output window as `player_id`, decoded natively
+/// rather than by the webview; its frames are drawn from `cpvideo://`
+#[tauri::command]
+pub async fn load_video(
+    players: tauri::State<'_, VideoPlayers>,
+    player_id: String,
+    source: VideoSource,
+    options: Option<PlaybackOptions>,
+) -> Result<VideoStatus, AppError> {
+    diagnostics::traced("load_video", async move {
+        Ok(players.load(&player_id, &source, options.unwrap_or_default())?)
+    })
+    .await
+}
 
-    std::fs::read_to_string(&path).map_err(|e| e.to_string())
+#[tauri::command]
+pub async fn play_video(
+    players: tauri::State<'_, VideoPlayers>,
+    player_id: String,
+) -> Result<VideoStatus, AppError> {
+    diagnostics::traced("play_video", async move { Ok(players.play(&player_id)?) }).await
 }
 
-/// Write a JSON file to app data directory
 #[tauri::command]
-pub async fn write_app_data_file(
-    app: tauri::AppHandle,
-    filename: String,
-    content: String,
-) -> Result<(), String> {
-    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+pub async fn pause_video(
+    players: tauri::State<'_, VideoPlayers>,
+    player_id: String,
+) -> Result<VideoStatus, AppError> {
+    diagnostics::traced("pause_video", async move { Ok(players.pause(&player_id)?) }).await
+}
 
-    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn seek_video(
+    players: tauri::State<'_, VideoPlayers>,
+    player_id: String,
+    seconds: f64,
+) -> Result<VideoStatus, AppError> {
+    diagnostics::traced("seek_video", async move {
+        Ok(players.seek(&player_id, seconds)?)
+    })
+    .await
+}
 
-    let path = dir.join(&filename);
-    std::fs::write(&path, content).map_err(|e| e.to_string())
+#[tauri::command]
+pub async fn set_video_loop(
+    players: tauri::State<'_, VideoPlayers>,
+    player_id: String,
+    looping: bool,
+) -> Result<VideoStatus, AppError> {
+    diagnostics::traced("set_video_loop", async move {
+        Ok(players.set_loop(&player_id, looping)?)
+    })
+    .await
 }
 
-/// Write a JSON file to documents app data directory
 #[tauri::command]
-pub async fn write_documents_data_file(
-    app: tauri::AppHandle,
-    filename: String,
-    content: String,
-) -> Result<(), String> {
-    let dir = resolve_content_dir(&app)?;
+pub async fn unload_video(
+    players: tauri::State<'_, VideoPlayers>,
+    player_id: String,
+) -> Result<(), AppError> {
+    diagnostics::traced("unload_video", async move {
+        players.unload(&player_id);
+        Ok(())
+    })
+    .await
+}
 
-    let path = dir.join(&filename);
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    }
+#[tauri::command]
+pub async fn get_video_status(
+    players: tauri::State<'_, VideoPlayers>,
+    player_id: String,
+) -> Result<VideoStatus, AppError> {
+    diagnostics::traced("get_video_status", async move {
+        Ok(players.status(&player_id)?)
+    })
+    .await
+}
 
-    std::fs::write(&path, content).map_err(|e| e.to_string())
+/// Load a playlist for the audio engine, replacing the one loaded; it starts
+/// from the first track unless `autoplay` is off
+#[tauri::command]
+pub async fn load_audio_playlist(
+    audio: tauri::State<'_, AudioEngine>,
+    tracks: Vec<AudioTrack>,
+    options: Option<PlaylistOptions>,
+) -> Result<AudioStatus, AppError> {
+    diagnostics::traced("load_audio_playlist", async move {
+        Ok(audio.load(tracks, options.unwrap_or_default())?)
+    })
+    .await
 }
 
-/// Allow a media library directory in the fs scope (persisted)
 #[tauri::command]
-pub async fn allow_media_library_dir(
-    app: tauri::AppHandle,
-    path: String,
-) -> Result<(), String> {
-    let dir = PathBuf::from(path);
-    if !dir.exists() {
-        return Err("Directory not found".to_string());
-    }
-    if !dir.is_dir() {
-        return Err("Path is not a directory".to_string());
-    }
+pub async fn play_audio(audio: tauri::State<'_, AudioEngine>) -> Result<AudioStatus, AppError> {
+    diagnostics::traced("play_audio", async move { Ok(audio.play()?) }).await
+}
 
-    let scope = app.fs_scope();
-    scope
-        .allow_directory(&dir, true)
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn pause_audio(audio: tauri::State<'_, AudioEngine>) -> Result<AudioStatus, AppError> {
+    diagnostics::traced("pause_audio", async move { Ok(audio.pause()?) }).await
+}
 
-    Ok(())
+#[tauri::command]
+pub async fn stop_audio(audio: tauri::State<'_, AudioEngine>) -> Result<AudioStatus, AppError> {
+    diagnostics::traced("stop_audio", async move { Ok(audio.stop()?) }).await
 }
-fn output_window_label(monitor_index: usize) -> String {
-    format!("output-{}", monitor_index)
+
+/// Play the track at `index` in the playlist
+#[tauri::command]
+pub async fn skip_audio(
+    audio: tauri::State<'_, AudioEngine>,
+    index: usize,
+) -> Result<AudioStatus, AppError> {
+    diagnostics::traced("skip_audio", async move { Ok(audio.skip(index)?) }).await
 }
 
-fn position_output_window(
-    window: &tauri::WebviewWindow,
-    monitor_index: usize,
-) -> Result<(), String> {
-    if let Some(monitor) = window
-        .available_monitors()
-        .map_err(|e| e.to_string())?
-        .get(monitor_index)
-    {
-        let pos = monitor.position();
-        window
-            .set_position(tauri::Position::Physical(tauri::PhysicalPosition {
-                x: pos.x,
-                y: pos.y,
-            }))
-            .map_err(|e| e.to_string())?;
-        window.set_fullscreen(true).map_err(|e| e.to_string())?;
-    }
+#[tauri::command]
+pub async fn seek_audio(
+    audio: tauri::State<'_, AudioEngine>,
+    seconds: f64,
+) -> Result<AudioStatus, AppError> {
+    diagnostics::traced("seek_audio", async move { Ok(audio.seek(seconds)?) }).await
+}
 
-    Ok(())
+#[tauri::command]
+pub async fn set_audio_volume(
+    audio: tauri::State<'_, AudioEngine>,
+    volume: f32,
+) -> Result<AudioStatus, AppError> {
+    diagnostics::traced(
+        "set_audio_volume",
+        async move { Ok(audio.set_volume(volume)?) },
+    )
+    .await
 }
 
-/// Open output windows on the specified monitors
+/// List the devices sound can be played on, for the audio engine and the
+/// routes
 #[tauri::command]
-pub async fn open_output_windows(
-    app: tauri::AppHandle,
-    monitor_indices: Vec<usize>,
-) -> Result<(), String> {
-    let mut desired_labels = std::collections::HashSet::new();
+pub async fn get_audio_devices() -> Result<Vec<AudioDevice>, AppError> {
+    diagnostics::traced("get_audio_devices", async move {
+        Ok(audio_engine::list_devices()?)
+    })
+    .await
+}
 
-    for idx in &monitor_indices {
-        desired_labels.insert(output_window_label(*idx));
-    }
+/// The device each context plays the webviews' sound on
+#[tauri::command]
+pub async fn get_audio_routes(
+    routing: tauri::State<'_, AudioRouting>,
+) -> Result<AudioRoutes, AppError> {
+    diagnostics::traced("get_audio_routes", async move { Ok(routing.routes()) }).await
+}
 
-    // Close any output windows not in the desired set (including legacy "output" window)
-    for (label, window) in app.webview_windows() {
-        if label == "output" || label.starts_with("output-") {
-            if !desired_labels.contains(&label) {
-                let _ = window.close();
-            }
+/// Play the sound of `context` on the device named `device`, or the
+/// system's default; the windows get `audio:routes` when it changes
+#[tauri::command]
+pub async fn set_audio_route(
+    app: tauri::AppHandle,
+    routing: tauri::State<'_, AudioRouting>,
+    context: AudioContext,
+    device: Option<String>,
+) -> Result<AudioRoutes, AppError> {
+    diagnostics::traced("set_audio_route", async move {
+        if routing.set(context, device)? {
+            app.emit(AUDIO_ROUTES_EVENT, routing.routes())?;
         }
-    }
+        Ok(routing.routes())
+    })
+    .await
+}
 
-    // Create or reposition desired output windows
-    for idx in monitor_indices {
-        let label = output_window_label(idx);
-        if let Some(window) = app.get_webview_window(&label) {
-            window.show().map_err(|e| e.to_string())?;
-            position_output_window(&window, idx)?;
-            continue;
-        }
+/// Play audio on the device named `device`, or the system's default
+#[tauri::command]
+pub async fn set_audio_device(
+    audio: tauri::State<'_, AudioEngine>,
+    device: Option<String>,
+) -> Result<AudioStatus, AppError> {
+    diagnostics::traced(
+        "set_audio_device",
+        async move { Ok(audio.set_device(device)?) },
+    )
+    .await
+}
 
-        let builder = tauri::WebviewWindowBuilder::new(
-            &app,
-            label,
-            tauri::WebviewUrl::App("/output".into()),
-        )
-        .title("Presentation Output")
-        .decorations(false)
-        .always_on_top(true);
+/// Play a sound once, over the playlist
+#[tauri::command]
+pub async fn play_sound_cue(
+    audio: tauri::State<'_, AudioEngine>,
+    track: AudioTrack,
+) -> Result<AudioStatus, AppError> {
+    diagnostics::traced("play_sound_cue", async move { Ok(audio.cue(track)?) }).await
+}
 
-        let window = builder.build().map_err(|e| e.to_string())?;
-        position_output_window(&window, idx)?;
-    }
+#[tauri::command]
+pub async fn stop_sound_cues(
+    audio: tauri::State<'_, AudioEngine>,
+) -> Result<AudioStatus, AppError> {
+    diagnostics::traced("stop_sound_cues", async move { Ok(audio.stop_cues()?) }).await
+}
 
-    Ok(())
+#[tauri::command]
+pub async fn get_audio_status(
+    audio: tauri::State<'_, AudioEngine>,
+) -> Result<AudioStatus, AppError> {
+    diagnostics::traced("get_audio_status", async move { Ok(audio.status()) }).await
 }
 
-/// Close all output windows
+/// Serve remote control over the local network, or serve it again with new
+/// options; see `remote_server` for what remotes can do
 #[tauri::command]
-pub async fn close_output_windows(app: tauri::AppHandle) -> Result<(), String> {
-    for (label, window) in app.webview_windows() {
-        if label == "output" || label.starts_with("output-") {
-            let _ = window.close();
-        }
-    }
-    Ok(())
+pub async fn start_remote_server(
+    server: tauri::State<'_, RemoteServer>,
+    options: Option<RemoteOptions>,
+) -> Result<RemoteStatus, AppError> {
+    diagnostics::traced("start_remote_server", async move {
+        Ok(server.start(options.unwrap_or_default()).await?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn stop_remote_server(server: tauri::State<'_, RemoteServer>) -> Result<(), AppError> {
+    diagnostics::traced("stop_remote_server", async move {
+        server.stop().await;
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_remote_server_status(
+    server: tauri::State<'_, RemoteServer>,
+) -> Result<RemoteStatus, AppError> {
+    diagnostics::traced(
+        "get_remote_server_status",
+        async move { Ok(server.status()) },
+    )
+    .await
 }
 
-/// Get list of available monitors
+/// Get list of available monitors; `monitors:changed` follows whenever it
+/// changes
 #[tauri::command]
-pub async fn get_monitors(app: tauri::AppHandle) -> Result<Vec<MonitorInfo>, String> {
-    let window = app
-        .get_webview_window("main")
-        .ok_or("Main window not found")?;
+pub async fn get_monitors(app: tauri::AppHandle) -> Result<Vec<MonitorInfo>, AppError> {
+    diagnostics::traced("get_monitors", async move { list_monitors(&app) }).await
+}
 
-    let monitors = window.available_monitors().map_err(|e| e.to_string())?;
-    let primary_monitor = window.primary_monitor().map_err(|e| e.to_string())?;
+pub(crate) fn list_monitors(app: &tauri::AppHandle) -> Result<Vec<MonitorInfo>, AppError> {
+    let monitors = app.available_monitors()?;
+    let primary_monitor = app.primary_monitor()?;
+    let ids = monitor_ids(&monitors);
 
     let mut info = Vec::new();
     for (i, monitor) in monitors.iter().enumerate() {
@@ -638,11 +3776,10 @@ pub async fn get_monitors(app: tauri::AppHandle) -> Result<Vec<MonitorInfo>, Str
             .and_then(get_monitor_friendly_name)
             .or_else(|| raw_name.clone())
             .unwrap_or_else(|| format!("Monitor {}", i + 1));
-        let refresh_rate = raw_name
-            .as_deref()
-            .and_then(get_monitor_refresh_rate);
+        let refresh_rate = raw_name.as_deref().and_then(get_monitor_refresh_rate);
 
         info.push(MonitorInfo {
+            id: ids[i].clone(),
             index: i,
             name,
             width: monitor.size().width,
@@ -657,8 +3794,11 @@ pub async fn get_monitors(app: tauri::AppHandle) -> Result<Vec<MonitorInfo>, Str
     Ok(info)
 }
 
-#[derive(serde::Serialize)]
+#[derive(Clone, PartialEq, serde::Serialize)]
 pub struct MonitorInfo {
+    /// Stays the same when displays are unplugged or reordered; see
+    /// `monitor_ids`
+    pub id: String,
     pub index: usize,
     pub name: String,
     pub width: u32,
@@ -668,3 +3808,16 @@ pub struct MonitorInfo {
     pub is_primary: bool,
     pub refresh_rate: Option<u32>,
 }
+
+/// Get per-command timing, payload size, and error-rate metrics
+#[tauri::command]
+pub fn get_command_diagnostics(recent_limit: Option<usize>) -> Result<DiagnosticsReport, AppError> {
+    Ok(diagnostics::report(recent_limit.unwrap_or(100)))
+}
+
+/// Clear recorded command diagnostics
+#[tauri::command]
+pub fn clear_command_diagnostics() -> Result<(), AppError> {
+    diagnostics::clear();
+    Ok(())
+}
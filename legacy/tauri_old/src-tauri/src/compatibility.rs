@@ -0,0 +1,288 @@
+//! Bundle compatibility report for a target machine
+//!
+//! Given a bundle and a description of the venue computer (OS, playable media
+//! types, installed fonts, output resolutions), report what would break before
+//! the file is carried there.
+
+use crate::cpres::{self, CpresError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Font families every renderer resolves without an installed face
+pub(crate) const GENERIC_FONT_FAMILIES: &[&str] = &[
+    "serif",
+    "sans-serif",
+    "monospace",
+    "cursive",
+    "fantasy",
+    "system-ui",
+    "inherit",
+];
+
+/// Aspect ratios within this tolerance are treated as equal
+const ASPECT_TOLERANCE: f64 = 0.01;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Description of the machine a bundle will be presented on
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MachineProfile {
+    /// "windows", "macos", or "linux"
+    pub os: String,
+    /// Playable MIME types or codec names; empty means the OS webview defaults
+    #[serde(default)]
+    pub codecs: Vec<String>,
+    /// Installed font family names
+    #[serde(default)]
+    pub installed_fonts: Vec<String>,
+    #[serde(default)]
+    pub output_resolutions: Vec<Resolution>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueSeverity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueKind {
+    MissingFont,
+    UnsupportedMedia,
+    ResolutionMismatch,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompatibilityIssue {
+    pub severity: IssueSeverity,
+    pub kind: IssueKind,
+    /// Font family, media filename, or resolution the issue is about
+    pub subject: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompatibilityReport {
+    pub compatible: bool,
+    pub issues: Vec<CompatibilityIssue>,
+}
+
+/// Check a bundle against a target machine profile
+pub fn check_bundle(
+    path: &Path,
+    profile: &MachineProfile,
+) -> Result<CompatibilityReport, CpresError> {
+    let bundle = cpres::open_bundle(path)?;
+    let manifest: serde_json::Value = serde_json::from_str(&bundle.manifest)?;
+    let slides: serde_json::Value = serde_json::from_str(&bundle.slides)?;
+
+    let mut issues = Vec::new();
+    check_fonts(&manifest, &slides, &bundle.themes, profile, &mut issues)?;
+    check_media(&manifest, profile, &mut issues);
+    check_resolution(&manifest, profile, &mut issues);
+
+    Ok(CompatibilityReport {
+        compatible: !issues.iter().any(|i| i.severity == IssueSeverity::Error),
+        issues,
+    })
+}
+
+fn check_fonts(
+    manifest: &serde_json::Value,
+    slides: &serde_json::Value,
+    themes: &[cpres::ThemeFile],
+    profile: &MachineProfile,
+    issues: &mut Vec<CompatibilityIssue>,
+) -> Result<(), CpresError> {
+    let mut used = BTreeSet::new();
+    cpres::collect_font_families(slides, &mut used);
+    for theme in themes {
+        let value: serde_json::Value = serde_json::from_str(&theme.content)?;
+        cpres::collect_font_families(&value, &mut used);
+    }
+
+    let available: BTreeSet<String> = manifest
+        .get("fonts")
+        .and_then(|f| f.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|font| font.get("family").and_then(|f| f.as_str()))
+        .chain(profile.installed_fonts.iter().map(String::as_str))
+        .map(|family| family.to_lowercase())
+        .collect();
+
+    for family in used {
+        let key = family.to_lowercase();
+        if GENERIC_FONT_FAMILIES.contains(&key.as_str()) || available.contains(&key) {
+            continue;
+        }
+        issues.push(CompatibilityIssue {
+            severity: IssueSeverity::Error,
+            kind: IssueKind::MissingFont,
+            message: format!(
+                "\"{family}\" is neither embedded in the bundle nor installed on the target machine"
+            ),
+            subject: family,
+        });
+    }
+
+    Ok(())
+}
+
+fn check_media(
+    manifest: &serde_json::Value,
+    profile: &MachineProfile,
+    issues: &mut Vec<CompatibilityIssue>,
+) {
+    let supported: BTreeSet<String> = if profile.codecs.is_empty() {
+        default_supported_types(&profile.os)
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    } else {
+        profile.codecs.iter().map(|c| c.to_lowercase()).collect()
+    };
+
+    let entries = manifest
+        .get("media")
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten();
+
+    for entry in entries {
+        let mime = entry
+            .get("mime")
+            .and_then(|m| m.as_str())
+            .unwrap_or("application/octet-stream")
+            .to_lowercase();
+        if !(mime.starts_with("video/") || mime.starts_with("audio/")) {
+            continue;
+        }
+
+        let codec = entry
+            .get("codec")
+            .and_then(|c| c.as_str())
+            .map(str::to_lowercase);
+        let playable =
+            supported.contains(&mime) || codec.as_ref().is_some_and(|c| supported.contains(c));
+        if playable {
+            continue;
+        }
+
+        let filename = entry
+            .get("filename")
+            .and_then(|f| f.as_str())
+            .unwrap_or("unknown")
+            .to_string();
+        let format = codec.unwrap_or(mime);
+        issues.push(CompatibilityIssue {
+            severity: IssueSeverity::Error,
+            kind: IssueKind::UnsupportedMedia,
+            message: format!("{filename} ({format}) cannot be played on {}", profile.os),
+            subject: filename,
+        });
+    }
+}
+
+fn check_resolution(
+    manifest: &serde_json::Value,
+    profile: &MachineProfile,
+    issues: &mut Vec<CompatibilityIssue>,
+) {
+    let Some(slide_size) = slide_size(manifest) else {
+        return;
+    };
+    let slide_aspect = slide_size.width as f64 / slide_size.height as f64;
+
+    for output in &profile.output_resolutions {
+        if output.width == 0 || output.height == 0 {
+            continue;
+        }
+        let subject = format!("{}x{}", output.width, output.height);
+        let output_aspect = output.width as f64 / output.height as f64;
+
+        if (output_aspect - slide_aspect).abs() > ASPECT_TOLERANCE {
+            issues.push(CompatibilityIssue {
+                severity: IssueSeverity::Warning,
+                kind: IssueKind::ResolutionMismatch,
+                message: format!(
+                    "Slides are {}x{} but the output is {subject}; content will be letterboxed",
+                    slide_size.width, slide_size.height
+                ),
+                subject,
+            });
+        } else if output.width < slide_size.width {
+            issues.push(CompatibilityIssue {
+                severity: IssueSeverity::Warning,
+                kind: IssueKind::ResolutionMismatch,
+                message: format!(
+                    "Slides are {}x{} but the output is only {subject}; content will be downscaled",
+                    slide_size.width, slide_size.height
+                ),
+                subject,
+            });
+        }
+    }
+}
+
+/// Presentation resolution from `slideSize`, falling back to the aspect ratio at 1080p
+fn slide_size(manifest: &serde_json::Value) -> Option<Resolution> {
+    if let Some(size) = manifest.get("slideSize") {
+        let width = size.get("width").and_then(|w| w.as_u64())?;
+        let height = size.get("height").and_then(|h| h.as_u64())?;
+        if width > 0 && height > 0 {
+            return Some(Resolution {
+                width: width as u32,
+                height: height as u32,
+            });
+        }
+    }
+
+    let (width, height) = match manifest.get("aspectRatio").and_then(|a| a.as_str())? {
+        "16:9" => (1920, 1080),
+        "16:10" => (1920, 1200),
+        "4:3" => (1440, 1080),
+        _ => return None,
+    };
+    Some(Resolution { width, height })
+}
+
+/// Media types the platform webview can play out of the box
+fn default_supported_types(os: &str) -> &'static [&'static str] {
+    match os.to_lowercase().as_str() {
+        // WKWebView: no WebM/Ogg video
+        "macos" => &[
+            "video/mp4",
+            "video/quicktime",
+            "audio/mpeg",
+            "audio/wav",
+            "audio/mp4",
+            "audio/aac",
+        ],
+        // WebKitGTK depends on installed GStreamer plugins; assume the common free set
+        "linux" => &[
+            "video/webm",
+            "video/mp4",
+            "audio/mpeg",
+            "audio/wav",
+            "audio/ogg",
+        ],
+        // WebView2 (Chromium)
+        _ => &[
+            "video/mp4",
+            "video/webm",
+            "audio/mpeg",
+            "audio/wav",
+            "audio/ogg",
+            "audio/mp4",
+            "audio/aac",
+        ],
+    }
+}
@@ -7,26 +7,53 @@
 //! - themes/*.json: Embedded themes
 //! - media/*: Media files (images, videos, audio)
 //! - fonts/*: Embedded font files
+//! - checksums.json: sha256 of every other entry (see `checksums`)
+//!
+//! Any other entries (written by newer versions or other tools) are preserved
+//! as-is when a bundle is re-saved.
 
+use crate::bundle_reader;
+use crate::checksums::{self, Checksums};
+use crate::font_details::{self, FontLicense};
+use crate::image_optimize::{self, ImageOptimizer, OptimizedImport};
+use crate::loudness::{AudioNormalizer, NormalizeMode, NormalizedImport};
+use crate::media_probe::{self, MediaMetadata};
+use crate::media_trim::{self, MediaTrim};
+use crate::placeholder;
+use crate::stock_media::Attribution;
+use crate::tasks::CancelToken;
 use font_kit::handle::Handle;
 use font_kit::properties::Style;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::io::{ErrorKind, Read, Seek, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
 use tempfile::NamedTempFile;
 use thiserror::Error;
 use zip::write::SimpleFileOptions;
 use zip::{ZipArchive, ZipWriter};
 
+/// Attempts made to rename over a bundle that another program holds open
+const PERSIST_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry; doubled after each attempt
+const PERSIST_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Files imported at once; more mostly adds disk seeking
+const MAX_IMPORT_WORKERS: usize = 4;
+
 #[derive(Error, Debug)]
 pub enum CpresError {
     #[error("IO error: {0}")]
-    Io(#[from] std::io::Error),
+    Io(std::io::Error),
 
     #[error("ZIP error: {0}")]
-    Zip(#[from] zip::result::ZipError),
+    Zip(zip::result::ZipError),
 
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
@@ -36,6 +63,66 @@ pub enum CpresError {
 
     #[error("Missing file in bundle: {0}")]
     MissingFile(String),
+
+    /// The target is held open by another program; the frontend should offer to save elsewhere
+    #[error("File is in use by another program: {0}")]
+    FileLocked(String),
+
+    /// Another instance of the app holds the bundle's advisory lock
+    #[error("Bundle is open for editing by {0}")]
+    BundleLocked(String),
+
+    /// Entries don't match the digests in checksums.json
+    #[error("Bundle is corrupted: {0}")]
+    Corrupted(String),
+
+    /// ffmpeg is missing or exited with an error
+    #[error("ffmpeg: {0}")]
+    Ffmpeg(String),
+
+    /// The task was cancelled with `cancel_task`
+    #[error("Cancelled")]
+    Cancelled,
+
+    /// The disk (or the user's quota on it) ran out of space
+    #[error("Not enough disk space: {0}")]
+    DiskFull(String),
+
+    /// The OS refused access, or the target is on a read-only volume
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    /// An app database (such as the search index) could not be read or written
+    #[error("Database error: {0}")]
+    Database(String),
+
+    /// Another program's song library or schedule could not be read
+    #[error("Import failed: {0}")]
+    Import(String),
+}
+
+impl From<std::io::Error> for CpresError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            ErrorKind::StorageFull | ErrorKind::QuotaExceeded => {
+                CpresError::DiskFull(error.to_string())
+            }
+            ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => {
+                CpresError::PermissionDenied(error.to_string())
+            }
+            _ => CpresError::Io(error),
+        }
+    }
+}
+
+impl From<zip::result::ZipError> for CpresError {
+    fn from(error: zip::result::ZipError) -> Self {
+        match error {
+            // Failed writes surface as zip errors while building an archive
+            zip::result::ZipError::Io(e) => e.into(),
+            other => CpresError::Zip(other),
+        }
+    }
 }
 
 impl Serialize for CpresError {
@@ -56,7 +143,7 @@ pub struct ParsedBundle {
     pub themes: Vec<ThemeFile>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ThemeFile {
     pub filename: String,
     pub content: String,
@@ -72,6 +159,55 @@ pub struct MediaEntry {
     pub sha256: String,
     pub byte_size: u64,
     pub media_type: String,
+    #[serde(flatten)]
+    pub metadata: MediaMetadata,
+    /// Playback gain in dB that brings an audio track to the loudness target
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gain: Option<f64>,
+    /// Placeholder to show while the thumbnail loads, for images and videos
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blurhash: Option<String>,
+    /// Credit owed to the author of stock media
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<Attribution>,
+}
+
+/// Result of importing a batch of media files
+#[derive(Debug, Serialize)]
+pub struct MediaImport {
+    /// In the order the paths were given
+    pub entries: Vec<MediaEntry>,
+    /// Images replaced by a scaled-down copy, by entry id
+    pub optimized: BTreeMap<String, OptimizedImport>,
+    /// Audio replaced by a copy at the target loudness, by entry id
+    pub normalized: BTreeMap<String, NormalizedImport>,
+    /// Paths of media library files with the same content, by entry id
+    pub in_library: BTreeMap<String, Vec<String>>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// One imported file, with what replaced it when it was converted
+pub struct ImportedFile {
+    pub entry: MediaEntry,
+    pub optimized: Option<OptimizedImport>,
+    pub normalized: Option<NormalizedImport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Sent as each file of a media import finishes
+#[derive(Debug, Serialize, Clone)]
+pub struct MediaImportProgress {
+    pub path: String,
+    /// Files done so far, including this one
+    pub finished: usize,
+    pub total: usize,
+    /// Why this file was skipped
+    pub error: Option<String>,
 }
 
 /// Font entry computed during import
@@ -87,10 +223,16 @@ pub struct FontEntry {
     pub byte_size: u64,
     pub weight: u16,
     pub style: String,
+    /// License and embedding permissions, from the font itself
+    #[serde(default)]
+    pub license: Option<FontLicense>,
+    /// Set when the license doesn't allow sharing the font inside a bundle
+    #[serde(default)]
+    pub embedding_warning: Option<String>,
 }
 
 /// Bundle state for saving - contains raw JSON strings from frontend
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BundleState {
     pub manifest: String,
     pub slides: String,
@@ -100,14 +242,17 @@ pub struct BundleState {
     pub fonts: Vec<FontFileRef>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MediaFileRef {
     pub id: String,
     pub source_path: String, // Absolute path to source file or "bundle:<path>" for existing
     pub bundle_path: String, // Path within the bundle (e.g., "media/abc123.jpg")
+    /// In and out points of audio and video (see `media_trim`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trim: Option<MediaTrim>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FontFileRef {
     pub id: String,
     pub source_path: String, // Absolute path to source file or "bundle:<path>" for existing
@@ -116,20 +261,19 @@ pub struct FontFileRef {
 
 /// Open and parse a .cpres bundle
 pub fn open_bundle(path: &Path) -> Result<ParsedBundle, CpresError> {
-    let file = File::open(path)?;
-    let mut archive = ZipArchive::new(file)?;
+    let mut archive = bundle_reader::open_archive(path)?;
 
     // Read manifest.json
     let manifest = read_zip_file(&mut archive, "manifest.json")?;
 
     // Validate manifest has required fields
     let manifest_json: serde_json::Value = serde_json::from_str(&manifest)?;
-    if !manifest_json.get("formatVersion").is_some() {
+    if manifest_json.get("formatVersion").is_none() {
         return Err(CpresError::InvalidBundle(
             "Missing formatVersion in manifest".to_string(),
         ));
     }
-    if !manifest_json.get("presentationId").is_some() {
+    if manifest_json.get("presentationId").is_none() {
         return Err(CpresError::InvalidBundle(
             "Missing presentationId in manifest".to_string(),
         ));
@@ -166,6 +310,36 @@ pub fn open_bundle(path: &Path) -> Result<ParsedBundle, CpresError> {
 
 /// Save a presentation bundle atomically (write to temp file, then rename)
 pub fn save_bundle(path: &Path, state: &BundleState) -> Result<(), CpresError> {
+    save_bundle_cancellable(path, state, &CancelToken::default())
+}
+
+/// `save_bundle` that stops with `Cancelled` when `cancel` fires; the existing
+/// bundle is left as it was
+pub fn save_bundle_cancellable(
+    path: &Path,
+    state: &BundleState,
+    cancel: &CancelToken,
+) -> Result<(), CpresError> {
+    write_bundle(path, state, &[], cancel)
+}
+
+/// `save_bundle` that also writes `extra` entries outside the bundle format,
+/// as `(entry name, source file)`; they take the place of entries of the same
+/// name in the bundle being replaced
+pub(crate) fn save_bundle_with_extra(
+    path: &Path,
+    state: &BundleState,
+    extra: &[(String, PathBuf)],
+) -> Result<(), CpresError> {
+    write_bundle(path, state, extra, &CancelToken::default())
+}
+
+fn write_bundle(
+    path: &Path,
+    state: &BundleState,
+    extra: &[(String, PathBuf)],
+    cancel: &CancelToken,
+) -> Result<(), CpresError> {
     // Create temp file in the same directory for atomic rename
     let parent = path.parent().unwrap_or(Path::new("."));
     fs::create_dir_all(parent)?;
@@ -174,73 +348,247 @@ pub fn save_bundle(path: &Path, state: &BundleState) -> Result<(), CpresError> {
     let file = temp_file.reopen()?;
     let mut zip = ZipWriter::new(file);
     let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut checksums = Checksums::default();
+
+    // Cut trimmed media first; the manifest has to describe the cut files
+    let trims = media_trim::render_trims(path, state, cancel)?;
+    let manifest = trims.manifest.as_deref().unwrap_or(&state.manifest);
 
     // Write manifest.json
     zip.start_file("manifest.json", options)?;
-    zip.write_all(state.manifest.as_bytes())?;
+    zip.write_all(manifest.as_bytes())?;
+    checksums.add("manifest.json", manifest.as_bytes());
 
     // Write slides.json
     zip.start_file("slides.json", options)?;
     zip.write_all(state.slides.as_bytes())?;
+    checksums.add("slides.json", state.slides.as_bytes());
 
     // Write arrangement.json
     zip.start_file("arrangement.json", options)?;
     zip.write_all(state.arrangement.as_bytes())?;
+    checksums.add("arrangement.json", state.arrangement.as_bytes());
 
     // Write theme files
     for theme in &state.themes {
         zip.start_file(&theme.filename, options)?;
         zip.write_all(theme.content.as_bytes())?;
+        checksums.add(&theme.filename, theme.content.as_bytes());
     }
 
-    // Write media files
-    for media_ref in &state.media {
-        let source_data = if let Some(bundle_path) = media_ref.source_path.strip_prefix("bundle:") {
-            if path.exists() {
-                read_bundle_media(path, bundle_path)?
-            } else {
-                continue;
-            }
+    // Stream media and font files; existing ones come from the bundle being replaced
+    let mut existing = open_replaced_bundle(path)?;
+    let replaces_bundle = existing.is_some();
+    let file_refs = state
+        .media
+        .iter()
+        .map(|m| (&m.source_path, &m.bundle_path))
+        .chain(state.fonts.iter().map(|f| (&f.source_path, &f.bundle_path)));
+    for (source_path, bundle_path) in file_refs {
+        if let Some(trimmed) = trims.file(bundle_path) {
+            let mut file = File::open(trimmed)?;
+            checksums.copy_entry(&mut zip, bundle_path, &mut file, options, cancel)?;
+        } else if let Some(existing_path) = source_path.strip_prefix("bundle:") {
+            let Some(archive) = existing.as_mut() else {
+                return Err(CpresError::MissingFile(existing_path.to_string()));
+            };
+            let mut entry = archive
+                .by_name(existing_path)
+                .map_err(|_| CpresError::MissingFile(existing_path.to_string()))?;
+            checksums.copy_entry(&mut zip, bundle_path, &mut entry, options, cancel)?;
         } else {
             // Read from source file
-            fs::read(&media_ref.source_path)?
-        };
+            let mut file = File::open(source_path)?;
+            checksums.copy_entry(&mut zip, bundle_path, &mut file, options, cancel)?;
+        }
+    }
+    let mut written = BTreeSet::new();
+    for (name, source) in extra {
+        let mut file = File::open(source)?;
+        checksums.copy_entry(&mut zip, name, &mut file, options, cancel)?;
+        written.insert(name.as_str());
+    }
+    // Windows can't replace a file that is still open
+    drop(existing);
 
-        zip.start_file(&media_ref.bundle_path, options)?;
-        zip.write_all(&source_data)?;
+    // Carry forward entries written by newer versions or other tools
+    if replaces_bundle {
+        copy_unknown_entries(path, &mut zip, &mut checksums, &written)?;
     }
 
-    // Write font files
-    for font_ref in &state.fonts {
-        let source_data = if let Some(bundle_path) = font_ref.source_path.strip_prefix("bundle:") {
-            if path.exists() {
-                read_bundle_media(path, bundle_path)?
-            } else {
-                continue;
+    cancel.check()?;
+    checksums.write(&mut zip, options)?;
+    zip.finish()?;
+
+    persist_file(temp_file, path)
+}
+
+/// The bundle a save replaces, or None when there is nothing to keep: no file
+/// yet, an empty one, or one cut short by a crash mid-save (no central
+/// directory). Read errors are returned, so a locked or unreachable bundle
+/// isn't saved over without its media.
+fn open_replaced_bundle(
+    path: &Path,
+) -> Result<Option<ZipArchive<bundle_reader::BundleReader>>, CpresError> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.len() == 0 => return Ok(None),
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    match bundle_reader::open_archive(path) {
+        Ok(archive) => Ok(Some(archive)),
+        Err(CpresError::Zip(zip::result::ZipError::InvalidArchive(reason))) => {
+            log::warn!(
+                "Saving over {}, which isn't a complete bundle: {reason}",
+                path.display()
+            );
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Atomically move a fully written temp file over `path`.
+///
+/// On Windows the rename fails while another program or an antivirus scan has
+/// the target open. The rename is retried with backoff; if the target is still
+/// locked, the old file is moved aside and the new one renamed into place
+/// (this works for programs that open files with delete sharing). When both
+/// fail, `CpresError::FileLocked` is returned and the original is untouched.
+pub(crate) fn persist_file(mut temp_file: NamedTempFile, path: &Path) -> Result<(), CpresError> {
+    let mut backoff = PERSIST_INITIAL_BACKOFF;
+    for attempt in 1..=PERSIST_ATTEMPTS {
+        match temp_file.persist(path) {
+            Ok(_) => return Ok(()),
+            Err(e) if is_lock_error(&e.error) => {
+                temp_file = e.file;
+                if attempt < PERSIST_ATTEMPTS {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                }
             }
-        } else {
-            // Read from source file
-            fs::read(&font_ref.source_path)?
-        };
+            Err(e) => return Err(e.error.into()),
+        }
+    }
 
-        zip.start_file(&font_ref.bundle_path, options)?;
-        zip.write_all(&source_data)?;
+    replace_via_rename_aside(temp_file, path)
+}
+
+fn replace_via_rename_aside(temp_file: NamedTempFile, path: &Path) -> Result<(), CpresError> {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let aside = path.with_file_name(format!(".{file_name}.replaced-{suffix}"));
+
+    fs::rename(path, &aside).map_err(|e| locked_or_io(e, path))?;
+
+    match temp_file.persist(path) {
+        Ok(_) => {
+            // Still open elsewhere; it is removed on a later save or can be deleted by hand
+            if let Err(e) = fs::remove_file(&aside) {
+                log::warn!("Could not remove replaced file {}: {e}", aside.display());
+            }
+            Ok(())
+        }
+        Err(e) => {
+            let _ = fs::rename(&aside, path);
+            Err(locked_or_io(e.error, path))
+        }
     }
+}
 
-    zip.finish()?;
+fn locked_or_io(error: std::io::Error, path: &Path) -> CpresError {
+    if is_lock_error(&error) {
+        CpresError::FileLocked(path.to_string_lossy().to_string())
+    } else {
+        error.into()
+    }
+}
+
+/// Whether an IO error means another process has the file open
+fn is_lock_error(error: &std::io::Error) -> bool {
+    // ERROR_ACCESS_DENIED, ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+    cfg!(windows) && matches!(error.raw_os_error(), Some(5 | 32 | 33))
+}
+
+/// Save a bundle as a reusable template: themes, arrangement, slides, and fonts
+/// are kept, while media files are dropped and their manifest entries become
+/// placeholders so slides still know which media slot to fill.
+pub fn save_template(path: &Path, state: &BundleState) -> Result<(), CpresError> {
+    let mut manifest: serde_json::Value = serde_json::from_str(&state.manifest)?;
+    let manifest_obj = manifest
+        .as_object_mut()
+        .ok_or_else(|| CpresError::InvalidBundle("Manifest is not an object".to_string()))?;
+
+    manifest_obj.insert(
+        "presentationId".to_string(),
+        serde_json::Value::String(uuid::Uuid::new_v4().to_string()),
+    );
+    manifest_obj.insert("template".to_string(), serde_json::Value::Bool(true));
+
+    if let Some(media) = manifest_obj.get_mut("media").and_then(|m| m.as_array_mut()) {
+        for entry in media.iter_mut().filter_map(|e| e.as_object_mut()) {
+            entry.insert("path".to_string(), serde_json::Value::String(String::new()));
+            entry.insert(
+                "sha256".to_string(),
+                serde_json::Value::String(String::new()),
+            );
+            entry.insert("byteSize".to_string(), serde_json::Value::from(0));
+            entry.insert("placeholder".to_string(), serde_json::Value::Bool(true));
+        }
+    }
+
+    let template = BundleState {
+        manifest: serde_json::to_string(&manifest)?,
+        slides: state.slides.clone(),
+        arrangement: state.arrangement.clone(),
+        themes: state.themes.clone(),
+        media: Vec::new(),
+        fonts: state.fonts.clone(),
+    };
+
+    save_bundle(path, &template)
+}
+
+/// Whether an archive entry is owned by `save_bundle` and rewritten from `BundleState`
+pub(crate) fn is_managed_entry(name: &str) -> bool {
+    matches!(name, "manifest.json" | "slides.json" | "arrangement.json")
+        || name == checksums::CHECKSUMS_FILE
+        || (name.starts_with("themes/") && name.ends_with(".json"))
+        || name.starts_with("media/")
+        || name.starts_with("fonts/")
+}
 
-    // Atomic rename
-    temp_file
-        .persist(path)
-        .map_err(|e| CpresError::Io(e.error))?;
+/// Copy entries this version does not understand from the existing bundle,
+/// untouched, except those in `written`
+fn copy_unknown_entries(
+    path: &Path,
+    zip: &mut ZipWriter<File>,
+    checksums: &mut Checksums,
+    written: &BTreeSet<&str>,
+) -> Result<(), CpresError> {
+    let mut archive = bundle_reader::open_archive(path)?;
+
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        if entry.is_dir() || is_managed_entry(entry.name()) || written.contains(entry.name()) {
+            continue;
+        }
+        let name = entry.name().to_string();
+        drop(entry);
+        checksums.add_from_archive(&mut archive, i, &name)?;
+        zip.raw_copy_file(archive.by_index_raw(i)?)?;
+    }
 
     Ok(())
 }
 
 /// Read media file from a bundle as base64
 pub fn read_bundle_media(bundle_path: &Path, media_path: &str) -> Result<Vec<u8>, CpresError> {
-    let file = File::open(bundle_path)?;
-    let mut archive = ZipArchive::new(file)?;
+    let mut archive = bundle_reader::open_archive(bundle_path)?;
 
     let mut media_file = archive
         .by_name(media_path)
@@ -252,73 +600,390 @@ pub fn read_bundle_media(bundle_path: &Path, media_path: &str) -> Result<Vec<u8>
     Ok(buffer)
 }
 
-/// Import media files and compute their hashes
-pub fn import_media_files(paths: &[PathBuf]) -> Result<Vec<MediaEntry>, CpresError> {
-    let mut entries = Vec::new();
+/// Import media files and compute their hashes, several at a time.
+/// `on_progress` is called from worker threads as each file finishes. A file
+/// that can't be read is reported in `skipped` rather than failing the batch;
+/// cancelling does stop the whole batch.
+pub fn import_media_files(
+    paths: &[PathBuf],
+    optimizer: Option<&ImageOptimizer>,
+    normalizer: Option<&AudioNormalizer>,
+    cancel: &CancelToken,
+    on_progress: impl Fn(MediaImportProgress) + Sync,
+) -> Result<MediaImport, CpresError> {
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_IMPORT_WORKERS)
+        .min(paths.len().max(1));
+    let next = AtomicUsize::new(0);
+    let finished = AtomicUsize::new(0);
 
-    for path in paths {
-        let id = uuid::Uuid::new_v4().to_string();
-        let filename = path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown")
-            .to_string();
+    let mut results: Vec<(usize, Result<_, CpresError>)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = paths.get(index) else {
+                            break;
+                        };
+                        if cancel.is_cancelled() {
+                            break;
+                        }
+                        let result = import_media_file(path, optimizer, normalizer, cancel);
+                        on_progress(MediaImportProgress {
+                            path: path.to_string_lossy().to_string(),
+                            finished: finished.fetch_add(1, Ordering::Relaxed) + 1,
+                            total: paths.len(),
+                            error: result.as_ref().err().map(|e| e.to_string()),
+                        });
+                        results.push((index, result));
+                    }
+                    results
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    });
+    cancel.check()?;
 
-        let extension = path
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("")
-            .to_lowercase();
-
-        let mime = match extension.as_str() {
-            "jpg" | "jpeg" => "image/jpeg",
-            "png" => "image/png",
-            "gif" => "image/gif",
-            "webp" => "image/webp",
-            "svg" => "image/svg+xml",
-            "mp4" => "video/mp4",
-            "webm" => "video/webm",
-            "mov" => "video/quicktime",
-            "mp3" => "audio/mpeg",
-            "wav" => "audio/wav",
-            "ogg" => "audio/ogg",
-            _ => "application/octet-stream",
+    // Same order as `paths`, whichever worker finished first
+    results.sort_by_key(|(index, _)| *index);
+    let mut report = MediaImport {
+        entries: Vec::with_capacity(results.len()),
+        optimized: BTreeMap::new(),
+        normalized: BTreeMap::new(),
+        in_library: BTreeMap::new(),
+        skipped: Vec::new(),
+    };
+    for (index, result) in results {
+        match result {
+            Ok(imported) => {
+                let id = &imported.entry.id;
+                if let Some(optimized) = imported.optimized {
+                    report.optimized.insert(id.clone(), optimized);
+                }
+                if let Some(normalized) = imported.normalized {
+                    report.normalized.insert(id.clone(), normalized);
+                }
+                report.entries.push(imported.entry);
+            }
+            Err(CpresError::Cancelled) => return Err(CpresError::Cancelled),
+            Err(e) => report.skipped.push(SkippedFile {
+                path: paths[index].to_string_lossy().to_string(),
+                reason: e.to_string(),
+            }),
         }
+    }
+    Ok(report)
+}
+
+/// Hash and probe one media file, swapping in a converted or scaled-down copy
+/// when `optimizer` is given and the file is an image that needs one, and
+/// normalizing audio when `normalizer` is given
+pub fn import_media_file(
+    path: &Path,
+    optimizer: Option<&ImageOptimizer>,
+    normalizer: Option<&AudioNormalizer>,
+    cancel: &CancelToken,
+) -> Result<ImportedFile, CpresError> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let filename = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
         .to_string();
 
-        let media_type = if mime.starts_with("image/") {
-            "image"
-        } else if mime.starts_with("video/") {
-            "video"
-        } else if mime.starts_with("audio/") {
-            "audio"
-        } else {
-            "unknown"
+    let kind = detect_media_kind(path)?;
+    let (sha256, byte_size) = hash_file(path, cancel)?;
+    if let (Some(normalizer), "audio") = (normalizer, kind.media_type) {
+        let file = SourceFile {
+            id,
+            filename,
+            path,
+            kind,
+            sha256,
+            byte_size,
+        };
+        return import_audio(file, normalizer, cancel);
+    }
+
+    let optimized = match optimizer {
+        Some(optimizer) if kind.media_type == "image" => optimizer
+            .optimize(path, kind.mime, &sha256, byte_size, cancel)?
+            .map(|optimized| (optimizer, optimized)),
+        _ => None,
+    };
+    let Some((optimizer, optimized)) = optimized else {
+        let entry = media_entry(id, filename, path, kind, sha256, byte_size);
+        return Ok(ImportedFile {
+            entry,
+            optimized: None,
+            normalized: None,
+        });
+    };
+
+    let (optimized_sha256, optimized_size) = hash_file(&optimized.path, cancel)?;
+    let report = OptimizedImport {
+        source_path: optimized.path.to_string_lossy().to_string(),
+        original_mime: kind.mime.to_string(),
+        original_width: optimized.original_width,
+        original_height: optimized.original_height,
+        original_byte_size: byte_size,
+        original_path: optimizer
+            .keep_original()
+            .then(|| image_optimize::original_bundle_path(&id, &kind.extension)),
+    };
+    let entry = media_entry(
+        id,
+        filename,
+        &optimized.path,
+        optimized.kind,
+        optimized_sha256,
+        optimized_size,
+    );
+    Ok(ImportedFile {
+        entry,
+        optimized: Some(report),
+        normalized: None,
+    })
+}
+
+/// A file picked for import, hashed and recognized
+struct SourceFile<'a> {
+    id: String,
+    filename: String,
+    path: &'a Path,
+    kind: MediaKind,
+    sha256: String,
+    byte_size: u64,
+}
+
+/// An audio track with a playback gain, or a copy rendered at the target
+/// loudness in place of the original
+fn import_audio(
+    file: SourceFile,
+    normalizer: &AudioNormalizer,
+    cancel: &CancelToken,
+) -> Result<ImportedFile, CpresError> {
+    if normalizer.mode() == NormalizeMode::Render {
+        match normalizer.render(file.path, &file.sha256, cancel) {
+            Ok(Some(normalized)) => {
+                let (normalized_sha256, normalized_size) = hash_file(&normalized.path, cancel)?;
+                let (mime, media_type) = media_kind("m4a");
+                let report = NormalizedImport {
+                    source_path: normalized.path.to_string_lossy().to_string(),
+                    original_mime: file.kind.mime.to_string(),
+                    original_byte_size: file.byte_size,
+                    original_loudness: normalized.original_loudness,
+                    gain: normalized.gain,
+                };
+                let kind = MediaKind {
+                    mime,
+                    media_type,
+                    extension: "m4a".to_string(),
+                };
+                let entry = media_entry(
+                    file.id,
+                    file.filename,
+                    &normalized.path,
+                    kind,
+                    normalized_sha256,
+                    normalized_size,
+                );
+                return Ok(ImportedFile {
+                    entry,
+                    optimized: None,
+                    normalized: Some(report),
+                });
+            }
+            Ok(None) => {}
+            Err(CpresError::Cancelled) => return Err(CpresError::Cancelled),
+            // Fall back to a playback gain rather than failing the import
+            Err(e) => log::warn!("Not normalizing {}: {e}", file.path.display()),
         }
-        .to_string();
+    }
+    let mut entry = media_entry(
+        file.id,
+        file.filename,
+        file.path,
+        file.kind,
+        file.sha256,
+        file.byte_size,
+    );
+    entry.gain = entry
+        .metadata
+        .loudness
+        .map(|loudness| normalizer.gain(loudness, entry.metadata.true_peak));
+    Ok(ImportedFile {
+        entry,
+        optimized: None,
+        normalized: None,
+    })
+}
 
-        // Read file and compute hash
-        let data = fs::read(path)?;
-        let byte_size = data.len() as u64;
+/// The manifest entry for `file`, to be stored under `media/<id>.<ext>`
+fn media_entry(
+    id: String,
+    filename: String,
+    file: &Path,
+    kind: MediaKind,
+    sha256: String,
+    byte_size: u64,
+) -> MediaEntry {
+    let metadata = media_probe::probe(file, kind.media_type);
+    let blurhash = placeholder::blurhash(file, kind.media_type, &metadata);
+    MediaEntry {
+        path: format!("media/{}.{}", &id[..8], kind.extension),
+        id,
+        filename,
+        mime: kind.mime.to_string(),
+        sha256,
+        byte_size,
+        media_type: kind.media_type.to_string(),
+        metadata,
+        gain: None,
+        blurhash,
+        attribution: None,
+    }
+}
 
-        let mut hasher = Sha256::new();
-        hasher.update(&data);
-        let sha256 = hex::encode(hasher.finalize());
+/// What a media file holds, from its content when that can be recognized
+#[derive(Debug, Clone)]
+pub struct MediaKind {
+    pub mime: &'static str,
+    /// "image", "video", "audio", or "unknown"
+    pub media_type: &'static str,
+    /// Lowercase extension to store the file under: its own, unless that
+    /// doesn't match the content (a photo saved as `.tmp`, a PNG named `.jpg`)
+    pub extension: String,
+}
 
-        let bundle_path = format!("media/{}.{}", &id[..8], extension);
+/// Bytes read from the start of a file to recognize its type
+const SNIFF_BYTES: u64 = 8 * 1024;
 
-        entries.push(MediaEntry {
-            id,
-            filename,
-            path: bundle_path,
+/// Media type of a file from its first bytes, falling back to the extension
+/// for types that have no signature (SVG) or that aren't recognized
+pub fn detect_media_kind(path: &Path) -> Result<MediaKind, CpresError> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    let mut header = Vec::new();
+    File::open(path)?
+        .take(SNIFF_BYTES)
+        .read_to_end(&mut header)?;
+
+    let (mime, media_type) = media_kind(&extension);
+    let sniffed = infer::get(&header).filter(|kind| {
+        matches!(
+            kind.matcher_type(),
+            infer::MatcherType::Image | infer::MatcherType::Video | infer::MatcherType::Audio
+        )
+    });
+    let Some(sniffed) = sniffed else {
+        return Ok(MediaKind {
+            mime,
+            media_type,
+            extension,
+        });
+    };
+    // Prefer the table's MIME names so a type is always spelled the same way
+    let (sniffed_mime, sniffed_type) = match media_kind(sniffed.extension()) {
+        (_, "unknown") => (sniffed.mime_type(), media_type_of(sniffed.mime_type())),
+        known => known,
+    };
+    // Ogg and MP4 hold audio or video, so the signature can't tell `.ogv`
+    // from `.ogg` or `.m4v` from `.m4a`; a known audio/video extension wins
+    let is_av = |media_type: &str| matches!(media_type, "audio" | "video");
+    if mime == sniffed_mime || (is_av(media_type) && is_av(sniffed_type)) {
+        return Ok(MediaKind {
             mime,
-            sha256,
-            byte_size,
             media_type,
+            extension,
         });
     }
+    Ok(MediaKind {
+        mime: sniffed_mime,
+        media_type: sniffed_type,
+        extension: sniffed.extension().to_string(),
+    })
+}
 
-    Ok(entries)
+/// MIME type and media type ("image", "video", "audio", or "unknown") for a
+/// lowercase file extension
+pub fn media_kind(extension: &str) -> (&'static str, &'static str) {
+    let mime = match extension {
+        "jpg" | "jpeg" | "jfif" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "heic" | "heif" => "image/heif",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "ico" => "image/x-icon",
+        "svg" => "image/svg+xml",
+        "mp4" => "video/mp4",
+        "m4v" => "video/x-m4v",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "avi" => "video/x-msvideo",
+        "wmv" => "video/x-ms-wmv",
+        "mpg" | "mpeg" => "video/mpeg",
+        "ogv" => "video/ogg",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "aac" => "audio/aac",
+        "wav" => "audio/wav",
+        "ogg" | "oga" => "audio/ogg",
+        "opus" => "audio/opus",
+        "flac" => "audio/flac",
+        "aif" | "aiff" => "audio/aiff",
+        "wma" => "audio/x-ms-wma",
+        _ => "application/octet-stream",
+    };
+    (mime, media_type_of(mime))
+}
+
+fn media_type_of(mime: &str) -> &'static str {
+    if mime.starts_with("image/") {
+        "image"
+    } else if mime.starts_with("video/") {
+        "video"
+    } else if mime.starts_with("audio/") {
+        "audio"
+    } else {
+        "unknown"
+    }
+}
+
+/// Hex SHA-256 and size of a file
+pub fn hash_file(path: &Path, cancel: &CancelToken) -> Result<(String, u64), CpresError> {
+    let mut file = File::open(path)?;
+    let byte_size = file.metadata()?.len();
+    // Stream the file through the hasher in fixed-size chunks so memory use
+    // stays flat however large the video is; the chunks are big enough that
+    // a BufReader would only add a copy
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; checksums::COPY_CHUNK_BYTES];
+    loop {
+        cancel.check()?;
+        let read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        hasher.update(&buffer[..read]);
+    }
+    Ok((hex::encode(hasher.finalize()), byte_size))
 }
 
 /// Import font files and compute their metadata/hashes
@@ -371,6 +1036,12 @@ pub fn import_font_files(paths: &[PathBuf]) -> Result<Vec<FontEntry>, CpresError
         }
         .to_string();
 
+        let license = font_details::license(&data, 0);
+        let embedding_warning = license.as_ref().and_then(FontLicense::embedding_warning);
+        if let Some(warning) = &embedding_warning {
+            log::warn!("{}: {warning}", path.display());
+        }
+
         entries.push(FontEntry {
             id,
             family: font.family_name(),
@@ -382,14 +1053,107 @@ pub fn import_font_files(paths: &[PathBuf]) -> Result<Vec<FontEntry>, CpresError
             byte_size,
             weight: properties.weight.0 as u16,
             style,
+            license,
+            embedding_warning,
         });
     }
 
     Ok(entries)
 }
 
+/// Collect every `font.family` referenced in a slides or theme JSON document
+pub fn collect_font_families(value: &serde_json::Value, families: &mut BTreeSet<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            if let Some(family) = map
+                .get("font")
+                .and_then(|font| font.get("family"))
+                .and_then(|family| family.as_str())
+            {
+                if !family.trim().is_empty() {
+                    families.insert(family.trim().to_string());
+                }
+            }
+            for child in map.values() {
+                collect_font_families(child, families);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for child in items {
+                collect_font_families(child, families);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Collect every string value (or array of strings) stored under `key` anywhere
+/// in a JSON document (e.g. all `mediaId` references in slides.json)
+pub fn collect_references(value: &serde_json::Value, key: &str, found: &mut BTreeSet<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (k, child) in map {
+                match child {
+                    serde_json::Value::String(s) if k == key => {
+                        found.insert(s.clone());
+                    }
+                    serde_json::Value::Array(items) if k == key => {
+                        found.extend(items.iter().filter_map(|i| i.as_str()).map(String::from));
+                    }
+                    _ => collect_references(child, key, found),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for child in items {
+                collect_references(child, key, found);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replace string values (or arrays of strings) stored under any of `keys` using
+/// `mapping`; values without a mapping are left untouched
+pub fn rewrite_references(
+    value: &mut serde_json::Value,
+    keys: &[&str],
+    mapping: &HashMap<String, String>,
+) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (k, child) in map.iter_mut() {
+                match child {
+                    serde_json::Value::String(s) if keys.contains(&k.as_str()) => {
+                        if let Some(replacement) = mapping.get(s.as_str()) {
+                            *s = replacement.clone();
+                        }
+                    }
+                    serde_json::Value::Array(items) if keys.contains(&k.as_str()) => {
+                        for item in items.iter_mut() {
+                            if let Some(replacement) = item.as_str().and_then(|s| mapping.get(s)) {
+                                *item = serde_json::Value::String(replacement.clone());
+                            }
+                        }
+                    }
+                    _ => rewrite_references(child, keys, mapping),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for child in items {
+                rewrite_references(child, keys, mapping);
+            }
+        }
+        _ => {}
+    }
+}
+
 /// Helper to read a file from a ZIP archive as a string
-fn read_zip_file(archive: &mut ZipArchive<File>, name: &str) -> Result<String, CpresError> {
+pub(crate) fn read_zip_file<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<String, CpresError> {
     let mut file = archive
         .by_name(name)
         .map_err(|_| CpresError::MissingFile(name.to_string()))?;
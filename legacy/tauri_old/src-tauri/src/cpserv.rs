@@ -0,0 +1,213 @@
+//! .cpserv service playlist format handler
+//!
+//! A .cpserv file is a ZIP archive containing:
+//! - service.json: Service metadata, the presentations it uses, and the ordered cue list
+//!
+//! Presentations are referenced, not embedded. Each reference stores a path
+//! relative to the .cpserv file (so a service folder can be moved or synced
+//! as a whole) and the absolute path at save time as a fallback.
+
+use crate::cpres::{self, CpresError};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use tempfile::NamedTempFile;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const SERVICE_FILE: &str = "service.json";
+pub(crate) const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceDocument {
+    #[serde(default = "default_format_version")]
+    pub format_version: u32,
+    pub service_id: String,
+    pub title: String,
+    /// Service date (ISO 8601), if scheduled
+    #[serde(default)]
+    pub date: Option<String>,
+    pub presentations: Vec<PresentationRef>,
+    /// Cues in running order
+    pub cues: Vec<ServiceCue>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PresentationRef {
+    /// Id of this reference within the service
+    pub id: String,
+    /// Path relative to the .cpserv file, with `/` separators
+    pub path: String,
+    /// Absolute path when the service was saved; used if the relative path doesn't resolve
+    #[serde(default)]
+    pub absolute_path: Option<String>,
+    #[serde(default)]
+    pub presentation_id: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceCue {
+    pub id: String,
+    /// Reference id from `presentations`
+    pub presentation: String,
+    pub label: String,
+    /// Part of the service, e.g. "announcements", "worship", "sermon"
+    #[serde(default)]
+    pub segment: Option<String>,
+    /// Slide to start from; the first slide in the arrangement when absent
+    #[serde(default)]
+    pub slide_id: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+/// A presentation reference resolved against the filesystem
+#[derive(Debug, Serialize)]
+pub struct ResolvedPresentation {
+    pub id: String,
+    /// Absolute path that was found, or the best guess when missing
+    pub path: String,
+    pub exists: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OpenedService {
+    pub service: ServiceDocument,
+    pub presentations: Vec<ResolvedPresentation>,
+}
+
+fn default_format_version() -> u32 {
+    FORMAT_VERSION
+}
+
+/// Open a .cpserv file and resolve its presentation paths
+pub fn open_service(path: &Path) -> Result<OpenedService, CpresError> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let service: ServiceDocument =
+        serde_json::from_str(&cpres::read_zip_file(&mut archive, SERVICE_FILE)?)?;
+    if service.format_version > FORMAT_VERSION {
+        return Err(CpresError::InvalidBundle(format!(
+            "Service format {} is newer than this version supports",
+            service.format_version
+        )));
+    }
+    validate(&service)?;
+
+    let base_dir = service_dir(path);
+    let presentations = service
+        .presentations
+        .iter()
+        .map(|presentation| resolve(presentation, &base_dir))
+        .collect();
+
+    Ok(OpenedService {
+        service,
+        presentations,
+    })
+}
+
+/// Save a service. Presentation paths may be absolute or relative to the
+/// .cpserv file; they are stored relative where possible.
+pub fn save_service(path: &Path, service: &ServiceDocument) -> Result<(), CpresError> {
+    validate(service)?;
+
+    let base_dir = service_dir(path);
+    let mut service = service.clone();
+    service.format_version = FORMAT_VERSION;
+    for presentation in &mut service.presentations {
+        let given = PathBuf::from(&presentation.path);
+        let absolute = if given.is_absolute() {
+            given
+        } else {
+            base_dir.join(given)
+        };
+        presentation.path = relative_path(&absolute, &base_dir)
+            .map(|relative| to_portable(&relative))
+            .unwrap_or_else(|| to_portable(&absolute));
+        presentation.absolute_path = Some(absolute.to_string_lossy().to_string());
+    }
+
+    let parent = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)?;
+    let temp_file = NamedTempFile::new_in(parent)?;
+    let mut zip = ZipWriter::new(temp_file.reopen()?);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    zip.start_file(SERVICE_FILE, options)?;
+    zip.write_all(serde_json::to_string_pretty(&service)?.as_bytes())?;
+    zip.finish()?;
+
+    cpres::persist_file(temp_file, path)
+}
+
+/// Every cue must point at a presentation in the service
+fn validate(service: &ServiceDocument) -> Result<(), CpresError> {
+    for cue in &service.cues {
+        if !service
+            .presentations
+            .iter()
+            .any(|p| p.id == cue.presentation)
+        {
+            return Err(CpresError::InvalidBundle(format!(
+                "Cue \"{}\" references unknown presentation {}",
+                cue.label, cue.presentation
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Try the relative path, then the saved absolute path, then the file name next to the service
+fn resolve(presentation: &PresentationRef, base_dir: &Path) -> ResolvedPresentation {
+    let relative = base_dir.join(&presentation.path);
+    let candidates = [
+        Some(relative.clone()),
+        presentation.absolute_path.as_ref().map(PathBuf::from),
+        Path::new(&presentation.path)
+            .file_name()
+            .map(|name| base_dir.join(name)),
+    ];
+
+    let found = candidates.into_iter().flatten().find(|c| c.is_file());
+    ResolvedPresentation {
+        id: presentation.id.clone(),
+        exists: found.is_some(),
+        path: found.unwrap_or(relative).to_string_lossy().to_string(),
+    }
+}
+
+fn service_dir(path: &Path) -> PathBuf {
+    path.parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// `target` relative to `base`; None when they are on different drives/roots
+fn relative_path(target: &Path, base: &Path) -> Option<PathBuf> {
+    let target: Vec<Component> = target.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    if target.first() != base.first() {
+        return None;
+    }
+
+    let common = target.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &target[common..] {
+        relative.push(component);
+    }
+    Some(relative)
+}
+
+fn to_portable(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
@@ -0,0 +1,406 @@
+//! Playing the slides out of a Blackmagic DeckLink card
+//!
+//! Churches feeding a broadcast video system send program output over SDI
+//! (or the card's HDMI) rather than to a monitor. DeckLink cards are driven
+//! through ffmpeg's `decklink` output device, which Blackmagic's Desktop
+//! Video drivers provide and an ffmpeg built with `--enable-decklink`
+//! exposes; the stock ffmpeg downloads aren't, because of the SDK's license.
+//!
+//! The card plays the display mode matching the frame size, rate, and field
+//! order it's given, so a format is picked from those the card lists and
+//! the slides are drawn at its size (see `output_frames`). Frames are piped
+//! to ffmpeg as fast as the card takes them, repeating the last one between
+//! slide changes.
+//!
+//! ffmpeg can't drive a card's own keyer, so for a switcher's downstream
+//! keyer the fill and the key each play out of their own output (the two
+//! connectors of a Duo, or two cards) in the same format. The two ffmpeg
+//! processes aren't locked together, so a slide change can land on them a
+//! frame apart.
+
+use crate::cpres::CpresError;
+use crate::ffmpeg;
+use crate::output_frames::{self, OutputFrames, OutputSignal};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+/// How long ffmpeg gets to reject a device or format before playout counts
+/// as started
+const START_GRACE: Duration = Duration::from_millis(750);
+
+/// The frame being played, swapped whole when the slide changes
+type SharedFrame = Arc<Mutex<Arc<Vec<u8>>>>;
+
+/// A display mode a card can play
+#[derive(Debug, Clone, Serialize)]
+pub struct DeckLinkFormat {
+    /// ffmpeg's code for the mode, such as "Hp30"
+    pub code: String,
+    pub width: u32,
+    pub height: u32,
+    /// Frames per second as a fraction, such as "30000/1001"
+    pub frame_rate: String,
+    pub interlaced: bool,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeckLinkPlayout {
+    pub device: String,
+    pub format: DeckLinkFormat,
+    pub signal: OutputSignal,
+    /// False once ffmpeg has stopped on its own, as when the card is
+    /// unplugged
+    pub running: bool,
+}
+
+/// Managed state: playouts by device name
+#[derive(Default)]
+pub struct DeckLinkOutput {
+    playouts: Mutex<BTreeMap<String, Playout>>,
+    /// The bundle and slide being shown, drawn for playouts started later;
+    /// None shows black
+    showing: Mutex<Option<(PathBuf, String)>>,
+    frames: Mutex<OutputFrames>,
+}
+
+struct Playout {
+    format: DeckLinkFormat,
+    signal: OutputSignal,
+    child: Child,
+    frame: SharedFrame,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DeckLinkOutput {
+    /// Start playing `signal` out of `device` in the format with `code`,
+    /// replacing a playout already on it
+    pub fn start(
+        &self,
+        device: &str,
+        code: &str,
+        signal: OutputSignal,
+    ) -> Result<DeckLinkPlayout, CpresError> {
+        let format = formats(device)?
+            .into_iter()
+            .find(|format| format.code == code)
+            .ok_or_else(|| CpresError::Ffmpeg(format!("{device} can't play the format {code}")))?;
+        self.stop(device);
+
+        let mut command = ffmpeg::command()?;
+        command
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba", "-s"])
+            .arg(format!("{}x{}", format.width, format.height))
+            .args(["-r", &format.frame_rate, "-i", "pipe:0"])
+            .args(["-pix_fmt", "uyvy422"]);
+        if format.interlaced {
+            let order = if format.description.contains("lower field") {
+                "bb"
+            } else {
+                "tt"
+            };
+            command.args(["-field_order", order]);
+        }
+        command.args(["-f", "decklink", device]);
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| CpresError::Ffmpeg("ffmpeg has no input pipe".to_string()))?;
+        // Read as it comes, so a chatty ffmpeg never blocks on a full pipe
+        let log = Arc::new(Mutex::new(Vec::new()));
+        if let Some(stderr) = child.stderr.take() {
+            let (log, device) = (log.clone(), device.to_string());
+            std::thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    log::debug!("ffmpeg ({device}): {line}");
+                    if let Ok(mut log) = log.lock() {
+                        log.push(line);
+                    }
+                }
+            });
+        }
+
+        let showing = self.showing.lock().ok().and_then(|showing| showing.clone());
+        let frame = Arc::new(Mutex::new(Arc::new(self.frame(
+            showing.as_ref(),
+            format.width,
+            format.height,
+            signal,
+        ))));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (frame, stop, device) = (frame.clone(), stop.clone(), device.to_string());
+            std::thread::spawn(move || {
+                // Blocks while the card's buffer is full, which paces it
+                while !stop.load(Ordering::SeqCst) {
+                    let Ok(data) = frame.lock().map(|frame| frame.clone()) else {
+                        break;
+                    };
+                    if let Err(e) = stdin.write_all(&data) {
+                        if !stop.load(Ordering::SeqCst) {
+                            log::warn!("DeckLink playout on {device} stopped: {e}");
+                        }
+                        break;
+                    }
+                }
+            })
+        };
+
+        std::thread::sleep(START_GRACE);
+        if let Ok(Some(status)) = child.try_wait() {
+            let _ = thread.join();
+            let log = log.lock().map(|log| log.join("\n")).unwrap_or_default();
+            return Err(CpresError::Ffmpeg(format!(
+                "DeckLink playout failed ({status}): {}",
+                log.trim()
+            )));
+        }
+        log::info!(
+            "Playing {signal:?} out of {device} as {}",
+            format.description
+        );
+        let playout = DeckLinkPlayout {
+            device: device.to_string(),
+            format: format.clone(),
+            signal,
+            running: true,
+        };
+        self.lock_playouts().insert(
+            device.to_string(),
+            Playout {
+                format,
+                signal,
+                child,
+                frame,
+                stop,
+                thread: Some(thread),
+            },
+        );
+        Ok(playout)
+    }
+
+    /// Stop playing out of `device`; the card outputs black
+    pub fn stop(&self, device: &str) {
+        let playout = self.lock_playouts().remove(device);
+        if let Some(playout) = playout {
+            playout.finish();
+            log::info!("Stopped playing out of {device}");
+        }
+    }
+
+    pub fn stop_all(&self) {
+        let playouts = std::mem::take(&mut *self.lock_playouts());
+        for playout in playouts.into_values() {
+            playout.finish();
+        }
+    }
+
+    pub fn list(&self) -> Vec<DeckLinkPlayout> {
+        self.lock_playouts()
+            .iter_mut()
+            .map(|(device, playout)| DeckLinkPlayout {
+                device: device.clone(),
+                format: playout.format.clone(),
+                signal: playout.signal,
+                running: matches!(playout.child.try_wait(), Ok(None)),
+            })
+            .collect()
+    }
+
+    /// Play the slide with `slide_id` from the bundle at `path` on every
+    /// card, or black with None
+    pub fn show(&self, slide: Option<(&Path, &str)>) {
+        let showing = slide.map(|(path, slide_id)| (path.to_path_buf(), slide_id.to_string()));
+        if let Ok(mut current) = self.showing.lock() {
+            current.clone_from(&showing);
+        }
+        let targets: Vec<(DeckLinkFormat, OutputSignal, SharedFrame)> = self
+            .lock_playouts()
+            .values()
+            .map(|playout| {
+                (
+                    playout.format.clone(),
+                    playout.signal,
+                    playout.frame.clone(),
+                )
+            })
+            .collect();
+        for (format, signal, frame) in targets {
+            let data = self.frame(showing.as_ref(), format.width, format.height, signal);
+            if let Ok(mut frame) = frame.lock() {
+                *frame = Arc::new(data);
+            }
+        }
+    }
+
+    /// The slide at the size, or black when there's none or it can't be drawn
+    fn frame(
+        &self,
+        showing: Option<&(PathBuf, String)>,
+        width: u32,
+        height: u32,
+        signal: OutputSignal,
+    ) -> Vec<u8> {
+        let Some((path, slide_id)) = showing else {
+            return output_frames::black(width, height);
+        };
+        let frame = self
+            .frames
+            .lock()
+            .map_err(|e| CpresError::InvalidBundle(e.to_string()))
+            .and_then(|mut frames| frames.render_signal(path, slide_id, width, height, signal));
+        frame.unwrap_or_else(|e| {
+            log::warn!("Could not draw the slide for DeckLink playout: {e}");
+            output_frames::black(width, height)
+        })
+    }
+
+    fn lock_playouts(&self) -> MutexGuard<'_, BTreeMap<String, Playout>> {
+        self.playouts
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Playout {
+    fn finish(mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Names of the DeckLink outputs ffmpeg can see
+pub fn devices() -> Result<Vec<String>, CpresError> {
+    let mut command = ffmpeg::command()?;
+    command.args(["-sinks", "decklink"]);
+    let output = command.stdin(Stdio::null()).output()?;
+    let text = String::from_utf8_lossy(&output.stdout).to_string()
+        + &String::from_utf8_lossy(&output.stderr);
+    if !text.contains("Auto-detected sinks") {
+        return Err(unsupported(&text));
+    }
+    Ok(parse_devices(&text))
+}
+
+/// The display modes `device` can play
+pub fn formats(device: &str) -> Result<Vec<DeckLinkFormat>, CpresError> {
+    let mut command = ffmpeg::analysis_command()?;
+    command
+        .args([
+            "-f",
+            "lavfi",
+            "-i",
+            "color=black:size=16x16",
+            "-frames:v",
+            "1",
+        ])
+        .args(["-list_formats", "1", "-f", "decklink", device]);
+    let output = command.stdin(Stdio::null()).output()?;
+    let text = String::from_utf8_lossy(&output.stderr).to_string();
+    if !text.contains("Supported formats") {
+        return Err(unsupported(&text));
+    }
+    Ok(parse_formats(&text))
+}
+
+/// ffmpeg's output when it lacks DeckLink support or can't find the card
+fn unsupported(log: &str) -> CpresError {
+    let reason = log
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("no output");
+    CpresError::Ffmpeg(format!(
+        "ffmpeg can't use DeckLink cards; it needs Desktop Video and an ffmpeg built with DeckLink support ({reason})"
+    ))
+}
+
+/// Devices in `-sinks decklink` output, one per line after the heading:
+///
+/// ```text
+/// Auto-detected sinks for decklink:
+///   DeckLink Mini Monitor 4K [DeckLink Mini Monitor 4K]
+/// ```
+fn parse_devices(text: &str) -> Vec<String> {
+    text.lines()
+        .skip_while(|line| !line.starts_with("Auto-detected sinks"))
+        .skip(1)
+        .filter_map(|line| {
+            let line = line.trim().trim_start_matches('*').trim();
+            let name = line.split(" [").next()?.trim();
+            (!name.is_empty()).then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// Formats in `-list_formats 1` output, a code and a description per line:
+///
+/// ```text
+/// [decklink @ 0x7f9] Supported formats for 'DeckLink Mini Monitor 4K':
+/// [decklink @ 0x7f9]     format_code     description
+/// [decklink @ 0x7f9]     Hp30            1920x1080 at 30000/1001 fps
+/// [decklink @ 0x7f9]     Hi59            1920x1080 at 30000/1001 fps (interlaced, upper field first)
+/// ```
+fn parse_formats(text: &str) -> Vec<DeckLinkFormat> {
+    let mut formats = Vec::new();
+    for line in text.lines() {
+        let line = match line.split_once("] ") {
+            Some((prefix, rest)) if prefix.starts_with('[') => rest,
+            _ => line,
+        };
+        let Some((code, description)) = line.trim().split_once(char::is_whitespace) else {
+            continue;
+        };
+        let description = description.trim();
+        let Some((size, rest)) = description.split_once(" at ") else {
+            continue;
+        };
+        let Some((width, height)) = size.split_once('x') else {
+            continue;
+        };
+        let (Ok(width), Ok(height)) = (width.parse(), height.parse()) else {
+            continue;
+        };
+        let Some(frame_rate) = rest.split_whitespace().next() else {
+            continue;
+        };
+        formats.push(DeckLinkFormat {
+            code: code.to_string(),
+            width,
+            height,
+            frame_rate: frame_rate.to_string(),
+            interlaced: rest.contains("interlaced"),
+            description: description.to_string(),
+        });
+    }
+    formats
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(DeckLinkOutput::default());
+}
+
+pub fn shutdown(app: &AppHandle) {
+    if let Some(output) = app.try_state::<DeckLinkOutput>() {
+        output.stop_all();
+    }
+}
@@ -0,0 +1,223 @@
+//! Per-command execution tracing
+//!
+//! Every invoke is recorded twice: once at dispatch (to capture the payload size
+//! the webview sent) and once when the command body finishes (to capture timing
+//! and the error, if any). Samples are kept in a fixed-size ring buffer so the
+//! diagnostics panel can show what was slow on the operator's machine without
+//! the history growing unbounded.
+
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tauri::ipc::InvokeBody;
+
+/// Maximum number of samples kept in the ring buffer
+const RING_CAPACITY: usize = 512;
+
+/// Dispatched payload sizes kept per command while waiting for completion;
+/// commands that are not traced would otherwise grow this forever
+const PENDING_CAPACITY: usize = 64;
+
+/// Commands slower than this are also written to the log
+const SLOW_COMMAND_MS: f64 = 1000.0;
+
+/// A single completed command invocation
+#[derive(Debug, Serialize, Clone)]
+pub struct CommandTrace {
+    pub command: String,
+    pub started_at_ms: u64,
+    pub duration_ms: f64,
+    pub payload_bytes: u64,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+/// Aggregated metrics for one command over the samples in the ring buffer
+#[derive(Debug, Serialize)]
+pub struct CommandStats {
+    pub command: String,
+    pub calls: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    pub avg_payload_bytes: u64,
+    pub max_payload_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiagnosticsReport {
+    pub capacity: usize,
+    pub commands: Vec<CommandStats>,
+    pub recent: Vec<CommandTrace>,
+}
+
+#[derive(Default)]
+struct Recorder {
+    samples: VecDeque<CommandTrace>,
+    /// Payload sizes recorded at dispatch, waiting for their command to finish
+    pending_payloads: HashMap<String, VecDeque<u64>>,
+}
+
+static RECORDER: LazyLock<Mutex<Recorder>> = LazyLock::new(|| Mutex::new(Recorder::default()));
+
+/// Size in bytes of an invoke payload as sent by the webview
+pub fn payload_size(body: &InvokeBody) -> u64 {
+    match body {
+        InvokeBody::Json(value) => serde_json::to_vec(value)
+            .map(|bytes| bytes.len() as u64)
+            .unwrap_or(0),
+        InvokeBody::Raw(bytes) => bytes.len() as u64,
+    }
+}
+
+/// Record that a command was dispatched with a payload of the given size
+pub fn record_dispatch(command: &str, payload_bytes: u64) {
+    let Ok(mut recorder) = RECORDER.lock() else {
+        return;
+    };
+    let pending = recorder
+        .pending_payloads
+        .entry(command.to_string())
+        .or_default();
+    if pending.len() == PENDING_CAPACITY {
+        pending.pop_front();
+    }
+    pending.push_back(payload_bytes);
+}
+
+/// Run an async command body and record its timing and outcome
+pub async fn traced<T, E, F>(command: &str, body: F) -> Result<T, E>
+where
+    E: Display,
+    F: Future<Output = Result<T, E>>,
+{
+    let started_at_ms = unix_millis();
+    let start = Instant::now();
+    let result = body.await;
+    record_completion(command, started_at_ms, start, &result);
+    result
+}
+
+/// Run a synchronous command body and record its timing and outcome
+pub fn traced_sync<T, E, F>(command: &str, body: F) -> Result<T, E>
+where
+    E: Display,
+    F: FnOnce() -> Result<T, E>,
+{
+    let started_at_ms = unix_millis();
+    let start = Instant::now();
+    let result = body();
+    record_completion(command, started_at_ms, start, &result);
+    result
+}
+
+fn record_completion<T, E: Display>(
+    command: &str,
+    started_at_ms: u64,
+    start: Instant,
+    result: &Result<T, E>,
+) {
+    let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+    let error = result.as_ref().err().map(|e| e.to_string());
+
+    if duration_ms >= SLOW_COMMAND_MS {
+        log::warn!("Slow command `{command}` took {duration_ms:.0} ms");
+    }
+
+    let Ok(mut recorder) = RECORDER.lock() else {
+        return;
+    };
+
+    let payload_bytes = recorder
+        .pending_payloads
+        .get_mut(command)
+        .and_then(|pending| pending.pop_front())
+        .unwrap_or(0);
+
+    if recorder.samples.len() == RING_CAPACITY {
+        recorder.samples.pop_front();
+    }
+    recorder.samples.push_back(CommandTrace {
+        command: command.to_string(),
+        started_at_ms,
+        duration_ms,
+        payload_bytes,
+        ok: error.is_none(),
+        error,
+    });
+}
+
+/// Build per-command aggregates plus the most recent samples (newest first)
+pub fn report(recent_limit: usize) -> DiagnosticsReport {
+    let recorder = match RECORDER.lock() {
+        Ok(recorder) => recorder,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let mut grouped: HashMap<&str, Vec<&CommandTrace>> = HashMap::new();
+    for sample in &recorder.samples {
+        grouped.entry(&sample.command).or_default().push(sample);
+    }
+
+    let mut commands: Vec<CommandStats> = grouped
+        .into_iter()
+        .map(|(command, samples)| {
+            let calls = samples.len() as u64;
+            let errors = samples.iter().filter(|s| !s.ok).count() as u64;
+            let mut durations: Vec<f64> = samples.iter().map(|s| s.duration_ms).collect();
+            durations.sort_by(|a, b| a.total_cmp(b));
+            let total_ms: f64 = durations.iter().sum();
+            let p95_index = ((durations.len() as f64 * 0.95).ceil() as usize).saturating_sub(1);
+            let total_payload: u64 = samples.iter().map(|s| s.payload_bytes).sum();
+
+            CommandStats {
+                command: command.to_string(),
+                calls,
+                errors,
+                error_rate: errors as f64 / calls as f64,
+                avg_ms: total_ms / calls as f64,
+                p95_ms: durations[p95_index.min(durations.len() - 1)],
+                max_ms: durations.last().copied().unwrap_or(0.0),
+                avg_payload_bytes: total_payload / calls,
+                max_payload_bytes: samples.iter().map(|s| s.payload_bytes).max().unwrap_or(0),
+            }
+        })
+        .collect();
+
+    // Slowest commands first so the panel leads with what matters
+    commands.sort_by(|a, b| b.max_ms.total_cmp(&a.max_ms));
+
+    let recent = recorder
+        .samples
+        .iter()
+        .rev()
+        .take(recent_limit)
+        .cloned()
+        .collect();
+
+    DiagnosticsReport {
+        capacity: RING_CAPACITY,
+        commands,
+        recent,
+    }
+}
+
+/// Drop all recorded samples
+pub fn clear() {
+    if let Ok(mut recorder) = RECORDER.lock() {
+        recorder.samples.clear();
+        recorder.pending_payloads.clear();
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,292 @@
+//! Structural diff between two .cpres bundles
+//!
+//! Compares slides, themes, and media by id rather than comparing bytes, so
+//! volunteers can see what a teammate actually changed in a service file.
+
+use crate::cpres::{self, CpresError};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Fields that change on every save and would otherwise mark everything as modified
+const VOLATILE_FIELDS: &[&str] = &["updatedAt"];
+
+/// Longest summary shown for a slide
+const SUMMARY_MAX_CHARS: usize = 60;
+
+#[derive(Debug, Serialize)]
+pub struct ItemRef {
+    pub id: String,
+    /// Human-readable label: slide text/section, theme name, or media filename
+    pub label: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangedItem {
+    pub id: String,
+    pub label: String,
+    /// Top-level fields whose values differ
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct SectionDiff {
+    pub added: Vec<ItemRef>,
+    pub removed: Vec<ItemRef>,
+    pub changed: Vec<ChangedItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BundleDiff {
+    /// True when nothing below differs
+    pub identical: bool,
+    /// Manifest fields (other than the media/font lists) that differ
+    pub manifest_fields: Vec<String>,
+    pub slides: SectionDiff,
+    pub order_changed: bool,
+    pub themes: SectionDiff,
+    pub media: SectionDiff,
+    pub fonts: SectionDiff,
+}
+
+impl SectionDiff {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Diff bundle `a` (before) against bundle `b` (after)
+pub fn diff_bundles(a: &Path, b: &Path) -> Result<BundleDiff, CpresError> {
+    let before = Parsed::open(a)?;
+    let after = Parsed::open(b)?;
+
+    let manifest_fields = changed_fields(&before.manifest, &after.manifest)
+        .into_iter()
+        .filter(|f| f != "media" && f != "fonts")
+        .collect();
+
+    let mut diff = BundleDiff {
+        identical: false,
+        manifest_fields,
+        slides: diff_items(
+            &index_by_id(&before.slides),
+            &index_by_id(&after.slides),
+            slide_label,
+        ),
+        order_changed: before.arrangement.get("order") != after.arrangement.get("order"),
+        themes: diff_items(&before.themes, &after.themes, |t| string_field(t, "name")),
+        media: diff_items(
+            &index_by_id(before.manifest.get("media").unwrap_or(&Value::Null)),
+            &index_by_id(after.manifest.get("media").unwrap_or(&Value::Null)),
+            |m| string_field(m, "filename"),
+        ),
+        fonts: diff_items(
+            &index_by_id(before.manifest.get("fonts").unwrap_or(&Value::Null)),
+            &index_by_id(after.manifest.get("fonts").unwrap_or(&Value::Null)),
+            |f| string_field(f, "fullName"),
+        ),
+    };
+    diff.identical = diff.manifest_fields.is_empty()
+        && !diff.order_changed
+        && [&diff.slides, &diff.themes, &diff.media, &diff.fonts]
+            .iter()
+            .all(|section| section.is_empty());
+
+    Ok(diff)
+}
+
+struct Parsed {
+    manifest: Value,
+    slides: Value,
+    arrangement: Value,
+    themes: BTreeMap<String, Value>,
+}
+
+impl Parsed {
+    fn open(path: &Path) -> Result<Self, CpresError> {
+        let bundle = cpres::open_bundle(path)?;
+        let mut themes = BTreeMap::new();
+        for theme in &bundle.themes {
+            let value: Value = serde_json::from_str(&theme.content)?;
+            let id = value
+                .get("id")
+                .and_then(|v| v.as_str())
+                .map(String::from)
+                .unwrap_or_else(|| theme.filename.clone());
+            themes.insert(id, value);
+        }
+
+        Ok(Self {
+            manifest: serde_json::from_str(&bundle.manifest)?,
+            slides: serde_json::from_str(&bundle.slides)?,
+            arrangement: serde_json::from_str(&bundle.arrangement)?,
+            themes,
+        })
+    }
+}
+
+fn index_by_id(items: &Value) -> BTreeMap<String, Value> {
+    items
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            let id = item.get("id")?.as_str()?.to_string();
+            Some((id, item.clone()))
+        })
+        .collect()
+}
+
+fn diff_items(
+    before: &BTreeMap<String, Value>,
+    after: &BTreeMap<String, Value>,
+    label: impl Fn(&Value) -> String,
+) -> SectionDiff {
+    let mut diff = SectionDiff::default();
+
+    for (id, item) in after {
+        match before.get(id) {
+            None => diff.added.push(ItemRef {
+                id: id.clone(),
+                label: label(item),
+            }),
+            Some(previous) => {
+                let fields = changed_fields(previous, item);
+                if !fields.is_empty() {
+                    diff.changed.push(ChangedItem {
+                        id: id.clone(),
+                        label: label(item),
+                        fields,
+                    });
+                }
+            }
+        }
+    }
+
+    for (id, item) in before {
+        if !after.contains_key(id) {
+            diff.removed.push(ItemRef {
+                id: id.clone(),
+                label: label(item),
+            });
+        }
+    }
+
+    diff
+}
+
+fn changed_fields(before: &Value, after: &Value) -> Vec<String> {
+    let empty = serde_json::Map::new();
+    let before = before.as_object().unwrap_or(&empty);
+    let after = after.as_object().unwrap_or(&empty);
+
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    keys.into_iter()
+        .filter(|key| !VOLATILE_FIELDS.contains(&key.as_str()))
+        .filter(|key| before.get(*key) != after.get(*key))
+        .cloned()
+        .collect()
+}
+
+/// Section label if present, otherwise the first line of the first text layer
+fn slide_label(slide: &Value) -> String {
+    if let Some(label) = slide.get("sectionLabel").and_then(|l| l.as_str()) {
+        if !label.is_empty() {
+            return label.to_string();
+        }
+    }
+
+    let text = slide
+        .get("layers")
+        .and_then(|l| l.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|layer| layer.get("type").and_then(|t| t.as_str()) == Some("text"))
+        .filter_map(|layer| layer.get("content").and_then(|c| c.as_str()))
+        .flat_map(|content| content.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+
+    let mut label: String = text.chars().take(SUMMARY_MAX_CHARS).collect();
+    if text.chars().count() > SUMMARY_MAX_CHARS {
+        label.push('…');
+    }
+    label
+}
+
+fn string_field(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn diff_items_by_id() {
+        let before = index_by_id(&json!([
+            {"id": "kept", "name": "Kept"},
+            {"id": "edited", "name": "Old", "color": "red"},
+            {"id": "gone", "name": "Gone"},
+            {"name": "No id"},
+        ]));
+        let after = index_by_id(&json!([
+            {"id": "kept", "name": "Kept", "updatedAt": "now"},
+            {"id": "edited", "name": "New", "color": "red"},
+            {"id": "new", "name": "New one"},
+        ]));
+        let diff = diff_items(&before, &after, |item| string_field(item, "name"));
+        let ids = |items: &[ItemRef]| -> Vec<(String, String)> {
+            items
+                .iter()
+                .map(|item| (item.id.clone(), item.label.clone()))
+                .collect()
+        };
+        assert_eq!(ids(&diff.added), [("new".into(), "New one".into())]);
+        assert_eq!(ids(&diff.removed), [("gone".into(), "Gone".into())]);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(
+            (diff.changed[0].id.as_str(), diff.changed[0].label.as_str()),
+            ("edited", "New")
+        );
+        assert_eq!(diff.changed[0].fields, ["name"]);
+    }
+
+    #[test]
+    fn slide_labels() {
+        let long = "x".repeat(SUMMARY_MAX_CHARS + 1);
+        let cases = [
+            (
+                json!({"sectionLabel": "Chorus", "layers": []}),
+                "Chorus".to_string(),
+            ),
+            (
+                json!({"sectionLabel": "", "layers": [
+                    {"type": "shape"},
+                    {"type": "text", "content": "\n  Amazing grace  \nhow sweet"},
+                ]}),
+                "Amazing grace".to_string(),
+            ),
+            (
+                json!({"layers": [{"type": "text", "content": "  "}, {"type": "text", "content": "Second"}]}),
+                "Second".to_string(),
+            ),
+            (
+                json!({"layers": [{"type": "text", "content": long}]}),
+                format!("{}…", "x".repeat(SUMMARY_MAX_CHARS)),
+            ),
+            (json!({"layers": [{"type": "media"}]}), String::new()),
+            (json!({}), String::new()),
+        ];
+        for (slide, expected) in cases {
+            assert_eq!(slide_label(&slide), expected, "{slide}");
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! Download manager for remote files
+//!
+//! Files fetched over HTTP(S) or WebDAV are cached under
+//! `<app data>/remote-cache/<url hash>/`, named after the server's ETag. Each
+//! fetch revalidates the cached copy with the server, interrupted downloads
+//! resume with a Range request, and the cached copy is used as-is when the
+//! server can't be reached.
+//!
+//! `fetch_cached` reports progress as `cpres:download-progress` events;
+//! `fetch_cached_with` hands it to the caller instead and can be stopped
+//! part way, leaving the partial file to resume from.
+
+use crate::cpres::CpresError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+
+const CACHE_DIR_NAME: &str = "remote-cache";
+const META_FILENAME: &str = "meta.json";
+const PARTIAL_FILENAME: &str = "download.partial";
+const PROGRESS_EVENT: &str = "cpres:download-progress";
+/// Minimum bytes between progress events
+const PROGRESS_STEP_BYTES: u64 = 256 * 1024;
+
+#[derive(Error, Debug)]
+pub enum DownloadError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Unsupported URL: {0}")]
+    UnsupportedUrl(String),
+
+    #[error("Server responded with status {0}")]
+    Status(u16),
+
+    #[error("Download incomplete: expected {expected} bytes, received {received}")]
+    Incomplete { expected: u64, received: u64 },
+
+    #[error("No API key is set for {0}")]
+    MissingApiKey(String),
+
+    #[error("Unexpected response: {0}")]
+    UnexpectedResponse(String),
+
+    #[error("Download cancelled")]
+    Cancelled,
+
+    #[error("Checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+}
+
+/// For work done on a downloaded file
+impl From<CpresError> for DownloadError {
+    fn from(error: CpresError) -> Self {
+        match error {
+            CpresError::Io(e) => DownloadError::Io(e),
+            CpresError::Cancelled => DownloadError::Cancelled,
+            e => DownloadError::UnexpectedResponse(e.to_string()),
+        }
+    }
+}
+
+/// HTTP basic auth, as used by most church file servers and WebDAV shares
+#[derive(Debug, Deserialize, Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// Cache bookkeeping stored next to the cached file
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct CacheMeta {
+    url: String,
+    /// Validators of the completed cached file
+    etag: Option<String>,
+    last_modified: Option<String>,
+    file_name: Option<String>,
+    byte_size: u64,
+    fetched_at_ms: u64,
+    /// ETag of the partial download, sent as If-Range when resuming
+    partial_etag: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CachedFile {
+    pub url: String,
+    pub path: String,
+    pub etag: Option<String>,
+    pub byte_size: u64,
+    /// True when the cached copy was used instead of downloading again
+    pub from_cache: bool,
+    /// True when the server could not be reached and an earlier copy was used
+    pub offline: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct DownloadProgress {
+    url: String,
+    received: u64,
+    total: Option<u64>,
+}
+
+/// Cache root under the app data directory
+pub fn cache_root(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(CACHE_DIR_NAME)
+}
+
+/// Fetch `url` into the cache under `cache_root`, reusing or resuming earlier downloads
+pub async fn fetch_cached(
+    app: &AppHandle,
+    cache_root: &Path,
+    url: &str,
+    credentials: Option<&Credentials>,
+) -> Result<CachedFile, DownloadError> {
+    fetch_cached_with(
+        cache_root,
+        url,
+        credentials,
+        || false,
+        |received, total| emit_progress(app, url, received, total),
+    )
+    .await
+}
+
+/// `fetch_cached`, passing the bytes received so far and the total, when the
+/// server gives it, to `on_progress`. The download stops with
+/// `DownloadError::Cancelled` once `cancelled` returns true.
+pub async fn fetch_cached_with(
+    cache_root: &Path,
+    url: &str,
+    credentials: Option<&Credentials>,
+    cancelled: impl Fn() -> bool,
+    on_progress: impl Fn(u64, Option<u64>),
+) -> Result<CachedFile, DownloadError> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|_| DownloadError::UnsupportedUrl(url.to_string()))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(DownloadError::UnsupportedUrl(url.to_string()));
+    }
+
+    let dir = cache_root.join(short_hash(url));
+    tokio::fs::create_dir_all(&dir).await?;
+    let meta = read_meta(&dir).await.unwrap_or_else(|| CacheMeta {
+        url: url.to_string(),
+        ..Default::default()
+    });
+    let cached = meta
+        .file_name
+        .as_ref()
+        .map(|name| dir.join(name))
+        .filter(|path| path.is_file());
+
+    let fetched = download(
+        &dir,
+        &parsed,
+        credentials,
+        meta,
+        cached.as_deref(),
+        cancelled,
+        on_progress,
+    )
+    .await;
+    match fetched {
+        Err(DownloadError::Http(e)) if cached.is_some() => {
+            log::warn!("Could not reach {url}, using cached copy: {e}");
+            let meta = read_meta(&dir).await.unwrap_or_default();
+            Ok(CachedFile {
+                url: url.to_string(),
+                path: path_string(cached.as_deref()),
+                etag: meta.etag,
+                byte_size: meta.byte_size,
+                from_cache: true,
+                offline: true,
+            })
+        }
+        result => result,
+    }
+}
+
+async fn download(
+    dir: &Path,
+    url: &reqwest::Url,
+    credentials: Option<&Credentials>,
+    mut meta: CacheMeta,
+    cached: Option<&Path>,
+    cancelled: impl Fn() -> bool,
+    on_progress: impl Fn(u64, Option<u64>),
+) -> Result<CachedFile, DownloadError> {
+    let partial_path = dir.join(PARTIAL_FILENAME);
+    let partial_len = tokio::fs::metadata(&partial_path)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    let mut request = reqwest::Client::new().get(url.clone());
+    if let Some(credentials) = credentials {
+        request = request.basic_auth(&credentials.username, Some(&credentials.password));
+    }
+    if cached.is_some() {
+        if let Some(etag) = &meta.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        } else if let Some(last_modified) = &meta.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    // Weak ETags can't be used to resume a byte range
+    let resumable = meta
+        .partial_etag
+        .as_ref()
+        .filter(|etag| !etag.starts_with("W/"));
+    if let (true, Some(etag)) = (partial_len > 0, resumable) {
+        request = request
+            .header(reqwest::header::RANGE, format!("bytes={partial_len}-"))
+            .header(reqwest::header::IF_RANGE, etag);
+    }
+
+    let mut response = request.send().await?;
+    let status = response.status();
+
+    if status == reqwest::StatusCode::NOT_MODIFIED {
+        if let Some(cached) = cached {
+            meta.fetched_at_ms = unix_millis();
+            write_meta(dir, &meta).await?;
+            return Ok(CachedFile {
+                url: url.to_string(),
+                path: path_string(Some(cached)),
+                etag: meta.etag,
+                byte_size: meta.byte_size,
+                from_cache: true,
+                offline: false,
+            });
+        }
+    }
+    if !status.is_success() {
+        return Err(DownloadError::Status(status.as_u16()));
+    }
+
+    let etag = header_string(&response, reqwest::header::ETAG);
+    let last_modified = header_string(&response, reqwest::header::LAST_MODIFIED);
+    let resuming = status == reqwest::StatusCode::PARTIAL_CONTENT;
+    let start = if resuming { partial_len } else { 0 };
+    let total = response.content_length().map(|len| len + start);
+
+    // Record the partial's validator first so a crash mid-download can still resume
+    meta.partial_etag = etag.clone();
+    write_meta(dir, &meta).await?;
+
+    let mut file = if resuming {
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&partial_path)
+            .await?
+    } else {
+        tokio::fs::File::create(&partial_path).await?
+    };
+
+    let mut received = start;
+    let mut last_emitted = 0;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        received += chunk.len() as u64;
+        if received - last_emitted >= PROGRESS_STEP_BYTES {
+            last_emitted = received;
+            on_progress(received, total);
+            // The partial file is kept, so the download can resume later
+            if cancelled() {
+                file.flush().await?;
+                return Err(DownloadError::Cancelled);
+            }
+        }
+    }
+    file.flush().await?;
+    drop(file);
+    on_progress(received, total);
+
+    if let Some(expected) = total.filter(|expected| *expected != received) {
+        return Err(DownloadError::Incomplete { expected, received });
+    }
+
+    let file_name = format!(
+        "{}.{}",
+        short_hash(etag.as_deref().unwrap_or(&received.to_string())),
+        file_extension(url)
+    );
+    let final_path = dir.join(&file_name);
+    tokio::fs::rename(&partial_path, &final_path).await?;
+    if let Some(previous) = cached.filter(|previous| *previous != final_path) {
+        let _ = tokio::fs::remove_file(previous).await;
+    }
+
+    let meta = CacheMeta {
+        url: url.to_string(),
+        etag: etag.clone(),
+        last_modified,
+        file_name: Some(file_name),
+        byte_size: received,
+        fetched_at_ms: unix_millis(),
+        partial_etag: None,
+    };
+    write_meta(dir, &meta).await?;
+
+    Ok(CachedFile {
+        url: url.to_string(),
+        path: final_path.to_string_lossy().to_string(),
+        etag,
+        byte_size: received,
+        from_cache: false,
+        offline: false,
+    })
+}
+
+fn emit_progress(app: &AppHandle, url: &str, received: u64, total: Option<u64>) {
+    let _ = app.emit(
+        PROGRESS_EVENT,
+        DownloadProgress {
+            url: url.to_string(),
+            received,
+            total,
+        },
+    );
+}
+
+async fn read_meta(dir: &Path) -> Option<CacheMeta> {
+    let content = tokio::fs::read(dir.join(META_FILENAME)).await.ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+async fn write_meta(dir: &Path, meta: &CacheMeta) -> Result<(), DownloadError> {
+    tokio::fs::write(dir.join(META_FILENAME), serde_json::to_vec_pretty(meta)?).await?;
+    Ok(())
+}
+
+fn header_string(
+    response: &reqwest::Response,
+    name: reqwest::header::HeaderName,
+) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+}
+
+/// Extension of the last URL path segment, defaulting to `cpres`
+fn file_extension(url: &reqwest::Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(Path::new)
+        .and_then(|name| name.extension())
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(str::to_lowercase)
+        .unwrap_or_else(|| "cpres".to_string())
+}
+
+fn short_hash(value: &str) -> String {
+    hex::encode(&Sha256::digest(value.as_bytes())[..8])
+}
+
+fn path_string(path: Option<&Path>) -> String {
+    path.map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
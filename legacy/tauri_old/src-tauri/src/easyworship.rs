@@ -0,0 +1,482 @@
+//! EasyWorship 6/7 song and schedule import
+//!
+//! EasyWorship keeps its song library in SQLite files under
+//! `<profile>/Databases/Data/`: `Songs.db` holds titles and authors in the
+//! `song` table, and `SongWords.db` holds each song's lyrics as RTF in the
+//! `word` table. Within the lyrics, a line such as "Verse 1" or "Chorus" starts
+//! a section and a blank line starts a new slide.
+//!
+//! A schedule (.ewsx) is a zip holding its own SQLite database (`main.db`)
+//! with a copy of every item: `presentation` has one row per item, in running
+//! order, and `slide` its slides, with RTF text and the section name as the
+//! slide title. Songs become .cpres files and the schedule a .cpserv playlist
+//! of them; scripture, media and other items without lyrics are skipped.
+
+use crate::bundle_reader;
+use crate::cpres::{BundleState, CpresError};
+use crate::cpserv::{self, PresentationRef, ServiceCue, ServiceDocument};
+use crate::importer::{self, rtf_to_text, LibraryImport, LyricSection};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+const SONGS_DB: &str = "Songs.db";
+const WORDS_DB: &str = "SongWords.db";
+/// The database inside a .ewsx schedule
+const SCHEDULE_DB: &str = "main.db";
+/// Columns that hold an item's or slide's lyrics, in order of preference
+const TEXT_COLUMNS: [&str; 3] = ["rtf", "words", "text"];
+/// How deep to look below the chosen folder for the database files
+const MAX_SEARCH_DEPTH: usize = 5;
+
+#[derive(Debug, Serialize)]
+pub struct EasyWorshipSong {
+    pub title: String,
+    pub author: Option<String>,
+    pub bundle: BundleState,
+}
+
+/// Convert every song in an EasyWorship profile into an unsaved bundle.
+/// `dir` may be the profile folder or the `Databases/Data` folder itself.
+pub fn import_easyworship(dir: &Path) -> Result<Vec<EasyWorshipSong>, CpresError> {
+    let data_dir = find_data_dir(dir, 0).ok_or_else(|| {
+        CpresError::Import(format!("No {SONGS_DB} found under {}", dir.display()))
+    })?;
+    let songs = open(&data_dir.join(SONGS_DB))?;
+    let words = open(&data_dir.join(WORDS_DB))?;
+
+    let mut lyrics: HashMap<i64, Vec<u8>> = HashMap::new();
+    {
+        let mut statement = words
+            .prepare("SELECT song_id, words FROM word")
+            .map_err(database_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, bytes(row.get_ref(1)?)))
+            })
+            .map_err(database_error)?;
+        for row in rows {
+            let (song_id, rtf) = row.map_err(database_error)?;
+            lyrics.insert(song_id, rtf);
+        }
+    }
+
+    let mut statement = songs
+        .prepare("SELECT rowid, title, author FROM song ORDER BY title")
+        .map_err(database_error)?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                row.get::<_, Option<String>>(2)?
+                    .filter(|a| !a.trim().is_empty()),
+            ))
+        })
+        .map_err(database_error)?;
+
+    let mut imported = Vec::new();
+    for row in rows {
+        let (id, title, author) = row.map_err(database_error)?;
+        let sections = lyrics
+            .get(&id)
+            .map(|rtf| sections(&rtf_to_text(rtf)))
+            .unwrap_or_default();
+        if sections.is_empty() {
+            log::warn!("EasyWorship song \"{title}\" has no lyrics; skipping");
+            continue;
+        }
+        let presentation =
+            importer::song_presentation(title.clone(), author.clone(), sections, None);
+        imported.push(EasyWorshipSong {
+            title,
+            author,
+            bundle: importer::to_bundle_state(presentation)?,
+        });
+    }
+    Ok(imported)
+}
+
+/// What `import_easyworship_schedule` wrote
+#[derive(Debug, Serialize)]
+pub struct EasyWorshipScheduleImport {
+    /// The .cpserv playlist that runs the songs in schedule order
+    pub service: String,
+    pub songs: LibraryImport,
+}
+
+/// An item of a schedule with its lyrics, or None for items without any
+struct ScheduleItem {
+    title: String,
+    author: Option<String>,
+    lyrics: Option<String>,
+}
+
+/// Save the songs of an EasyWorship schedule (.ewsx) as .cpres files in
+/// `output_dir`, and a .cpserv playlist running them in schedule order. A
+/// song the schedule uses twice is saved once and cued twice.
+pub fn import_easyworship_schedule(
+    schedule: &Path,
+    output_dir: &Path,
+) -> Result<EasyWorshipScheduleImport, CpresError> {
+    // The temp file holds the database the connection reads
+    let (_database, connection) = open_schedule(schedule)?;
+    let items = schedule_items(&connection)?;
+    let title = schedule
+        .file_stem()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "EasyWorship schedule".to_string());
+
+    let mut songs = LibraryImport::default();
+    let mut service = ServiceDocument {
+        format_version: cpserv::FORMAT_VERSION,
+        service_id: uuid::Uuid::new_v4().to_string(),
+        title: title.clone(),
+        date: None,
+        presentations: Vec::new(),
+        cues: Vec::new(),
+    };
+    // Reference id of each song already saved, by title and lyrics
+    let mut saved: HashMap<(String, String), String> = HashMap::new();
+    for item in items {
+        let Some(lyrics) = item.lyrics else {
+            songs.skip(item.title, "Not a song".to_string());
+            continue;
+        };
+        let key = (item.title.clone(), lyrics.clone());
+        let reference = match saved.get(&key) {
+            Some(reference) => reference.clone(),
+            None => {
+                let sections = sections(&lyrics);
+                if sections.is_empty() {
+                    songs.skip(item.title, "Song has no lyrics".to_string());
+                    continue;
+                }
+                let count = songs.imported.len();
+                songs.save(
+                    output_dir,
+                    importer::song_presentation(item.title.clone(), item.author, sections, None),
+                );
+                // A failed save was recorded as skipped
+                let Some(song) = songs.imported.get(count) else {
+                    continue;
+                };
+                let reference = uuid::Uuid::new_v4().to_string();
+                service.presentations.push(PresentationRef {
+                    id: reference.clone(),
+                    path: song.path.clone(),
+                    absolute_path: None,
+                    presentation_id: None,
+                    title: Some(song.title.clone()),
+                });
+                saved.insert(key, reference.clone());
+                reference
+            }
+        };
+        service.cues.push(ServiceCue {
+            id: uuid::Uuid::new_v4().to_string(),
+            presentation: reference,
+            label: item.title,
+            segment: None,
+            slide_id: None,
+            notes: None,
+        });
+    }
+
+    if service.cues.is_empty() {
+        return Err(CpresError::Import(format!(
+            "{} has no songs to import",
+            schedule.display()
+        )));
+    }
+    let service_path = free_path(output_dir, &title, "cpserv");
+    cpserv::save_service(&service_path, &service)?;
+    Ok(EasyWorshipScheduleImport {
+        service: service_path.to_string_lossy().to_string(),
+        songs,
+    })
+}
+
+/// Copy the database out of a .ewsx archive and open it. The connection
+/// reads the returned temp file, which has to be kept until it's closed.
+fn open_schedule(path: &Path) -> Result<(NamedTempFile, Connection), CpresError> {
+    let mut archive = bundle_reader::open_archive(path).map_err(|e| match e {
+        CpresError::Zip(e) => CpresError::Import(format!(
+            "{} is not an EasyWorship schedule: {e}",
+            path.display()
+        )),
+        e => e,
+    })?;
+    let name = archive
+        .file_names()
+        .filter(|name| name.to_lowercase().ends_with(".db"))
+        .min_by_key(|name| {
+            let file_name = name.rsplit('/').next().unwrap_or(name);
+            !file_name.eq_ignore_ascii_case(SCHEDULE_DB)
+        })
+        .map(str::to_string)
+        .ok_or_else(|| CpresError::Import(format!("No schedule database in {}", path.display())))?;
+
+    let mut database = tempfile::Builder::new().suffix(".db").tempfile()?;
+    std::io::copy(&mut archive.by_name(&name)?, &mut database)?;
+    database.flush()?;
+    let connection = open(database.path())?;
+    Ok((database, connection))
+}
+
+/// Schedule items in running order, with the lyrics of their slides (or of
+/// the item itself, when slides carry no text)
+fn schedule_items(connection: &Connection) -> Result<Vec<ScheduleItem>, CpresError> {
+    let columns = table_columns(connection, "presentation")?;
+    if !columns.iter().any(|c| c == "title") {
+        return Err(CpresError::Import(
+            "Schedule has no list of items".to_string(),
+        ));
+    }
+    let has = |column: &str| columns.iter().any(|c| c == column);
+    let author = if has("author") { "author" } else { "NULL" };
+    let text = TEXT_COLUMNS
+        .into_iter()
+        .find(|column| has(column))
+        .unwrap_or("NULL");
+    let order = if has("order_index") {
+        "order_index, rowid"
+    } else {
+        "rowid"
+    };
+
+    let mut slides = slide_lyrics(connection)?;
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT rowid, title, {author}, {text} FROM presentation ORDER BY {order}"
+        ))
+        .map_err(database_error)?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                row.get::<_, Option<String>>(2)?
+                    .filter(|a| !a.trim().is_empty()),
+                bytes(row.get_ref(3)?),
+            ))
+        })
+        .map_err(database_error)?;
+
+    let mut items = Vec::new();
+    for row in rows {
+        let (id, title, author, text) = row.map_err(database_error)?;
+        let lyrics = slides
+            .remove(&id)
+            .or_else(|| (!text.is_empty()).then(|| lyric_text(&text)))
+            .filter(|lyrics| !lyrics.trim().is_empty());
+        items.push(ScheduleItem {
+            title,
+            author,
+            lyrics,
+        });
+    }
+    Ok(items)
+}
+
+/// Lyrics of each schedule item from its slides, keyed by item. A slide
+/// titled with a section name ("Chorus") starts that section.
+fn slide_lyrics(connection: &Connection) -> Result<HashMap<i64, String>, CpresError> {
+    let columns = table_columns(connection, "slide")?;
+    let has = |column: &str| columns.iter().any(|c| c == column);
+    let Some(text) = TEXT_COLUMNS.into_iter().find(|column| has(column)) else {
+        return Ok(HashMap::new());
+    };
+    if !has("presentation_id") {
+        return Ok(HashMap::new());
+    }
+    let title = if has("title") { "title" } else { "NULL" };
+    let order = if has("order_index") {
+        "presentation_id, order_index, rowid"
+    } else {
+        "presentation_id, rowid"
+    };
+
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT presentation_id, {title}, {text} FROM slide ORDER BY {order}"
+        ))
+        .map_err(database_error)?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                bytes(row.get_ref(2)?),
+            ))
+        })
+        .map_err(database_error)?;
+
+    let mut lyrics: HashMap<i64, String> = HashMap::new();
+    for row in rows {
+        let (item, title, text) = row.map_err(database_error)?;
+        let body = lyrics.entry(item).or_default();
+        if let Some(label) = title.as_deref().and_then(importer::section_header) {
+            body.push_str(&label);
+            body.push('\n');
+        }
+        body.push_str(&lyric_text(&text));
+        body.push_str("\n\n");
+    }
+    Ok(lyrics)
+}
+
+/// Column names of a table; empty when there's no such table
+fn table_columns(connection: &Connection, table: &str) -> Result<Vec<String>, CpresError> {
+    let mut statement = connection
+        .prepare("SELECT name FROM pragma_table_info(?1)")
+        .map_err(database_error)?;
+    let columns = statement
+        .query_map([table], |row| row.get::<_, String>(0))
+        .map_err(database_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(database_error)?;
+    Ok(columns.into_iter().map(|c| c.to_lowercase()).collect())
+}
+
+/// A text or blob value's bytes; older databases store the RTF as a blob
+fn bytes(value: ValueRef) -> Vec<u8> {
+    match value {
+        ValueRef::Text(bytes) | ValueRef::Blob(bytes) => bytes.to_vec(),
+        _ => Vec::new(),
+    }
+}
+
+/// Lyrics stored as RTF, or as plain text
+fn lyric_text(text: &[u8]) -> String {
+    if text.starts_with(b"{\\rtf") {
+        rtf_to_text(text)
+    } else {
+        String::from_utf8_lossy(text).to_string()
+    }
+}
+
+/// `<dir>/<name>.<extension>`, numbered when that file exists
+fn free_path(dir: &Path, name: &str, extension: &str) -> PathBuf {
+    let mut path = dir.join(format!("{name}.{extension}"));
+    let mut number = 2;
+    while path.exists() {
+        path = dir.join(format!("{name} ({number}).{extension}"));
+        number += 1;
+    }
+    path
+}
+
+fn open(path: &Path) -> Result<Connection, CpresError> {
+    if !path.is_file() {
+        return Err(CpresError::Import(format!("{} not found", path.display())));
+    }
+    // EasyWorship may be running; read-only avoids touching its journal
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(database_error)
+}
+
+fn database_error(e: rusqlite::Error) -> CpresError {
+    CpresError::Import(format!("Could not read EasyWorship database: {e}"))
+}
+
+/// The folder containing Songs.db, searching below `dir`
+fn find_data_dir(dir: &Path, depth: usize) -> Option<PathBuf> {
+    if dir.join(SONGS_DB).is_file() {
+        return Some(dir.to_path_buf());
+    }
+    if depth >= MAX_SEARCH_DEPTH {
+        return None;
+    }
+    let mut children: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    children.sort();
+    children
+        .iter()
+        .find_map(|child| find_data_dir(child, depth + 1))
+}
+
+/// Split lyric text into sections at header lines and into slides at blank lines
+fn sections(text: &str) -> Vec<LyricSection> {
+    let mut sections: Vec<(String, String)> = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(label) = importer::section_header(trimmed) {
+            sections.push((label, String::new()));
+            continue;
+        }
+        if sections.is_empty() {
+            // Lyrics before any header form an unlabeled section
+            sections.push((String::new(), String::new()));
+        }
+        if let Some((_, body)) = sections.last_mut() {
+            body.push_str(trimmed);
+            body.push('\n');
+        }
+    }
+
+    sections
+        .into_iter()
+        .map(|(label, body)| LyricSection {
+            label,
+            slides: importer::split_slides(&body),
+        })
+        .filter(|section| !section.slides.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use zip::write::SimpleFileOptions;
+
+    #[test]
+    fn imports_schedule_songs_as_a_playlist() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("main.db");
+        let connection = Connection::open(&database).unwrap();
+        connection
+            .execute_batch(
+                r"CREATE TABLE presentation (title TEXT, author TEXT, order_index INTEGER);
+                CREATE TABLE slide (presentation_id INTEGER, order_index INTEGER, title TEXT, rtf TEXT);
+                INSERT INTO presentation (rowid, title, author, order_index) VALUES
+                    (1, 'Amazing Grace', 'John Newton', 0), (2, 'John 3:16', NULL, 1),
+                    (3, 'Amazing Grace', 'John Newton', 2);
+                INSERT INTO slide VALUES
+                    (1, 1, 'Chorus', '{\rtf1 Was blind\par but now I see}'),
+                    (1, 0, 'Verse 1', '{\rtf1 Amazing grace}'),
+                    (3, 0, 'Verse 1', '{\rtf1 Amazing grace}'),
+                    (3, 1, 'Chorus', '{\rtf1 Was blind\par but now I see}');",
+            )
+            .unwrap();
+        drop(connection);
+
+        let schedule = dir.path().join("Sunday.ewsx");
+        let mut zip = zip::ZipWriter::new(File::create(&schedule).unwrap());
+        zip.start_file("main.db", SimpleFileOptions::default())
+            .unwrap();
+        std::io::copy(&mut File::open(&database).unwrap(), &mut zip).unwrap();
+        zip.finish().unwrap();
+
+        let output = dir.path().join("library");
+        let import = import_easyworship_schedule(&schedule, &output).unwrap();
+        assert_eq!(import.songs.imported.len(), 1);
+        assert_eq!(import.songs.skipped.len(), 1);
+
+        let opened = cpserv::open_service(Path::new(&import.service)).unwrap();
+        assert_eq!(opened.service.title, "Sunday");
+        assert_eq!(opened.service.cues.len(), 2);
+        assert!(opened.presentations.iter().all(|p| p.exists));
+
+        let bundle = crate::cpres::open_bundle(Path::new(&import.songs.imported[0].path)).unwrap();
+        assert!(bundle.slides.contains("Was blind\\nbut now I see"));
+        assert!(bundle.slides.contains("Chorus"));
+    }
+}
@@ -0,0 +1,196 @@
+//! One picture across several projectors
+//!
+//! Two or more projectors side by side can make one wide picture, usually a
+//! background across the front wall. The outputs in a span share a canvas
+//! as wide as all of them less the overlaps, each showing its own slice of
+//! it. Where neighbours overlap the image is doubled, so each side fades
+//! across the overlap along a blend curve; with the projector's gamma taken
+//! into account the two add up to an even brightness and the seam goes.
+//!
+//! Slices are worked out here from the monitors' sizes, or the windowed
+//! size for windowed outputs, and sent to the output windows as
+//! `output:span`.
+
+use crate::monitor_watch::OutputScale;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+pub const OUTPUT_SPAN_EVENT: &str = "output:span";
+
+/// How a side's brightness falls off across an overlap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendCurve {
+    /// Straight down
+    Linear,
+    /// Slow at both ends, which hides the seam best on most projectors
+    #[default]
+    Smooth,
+}
+
+/// Where two neighbouring outputs overlap
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BlendEdge {
+    /// Physical pixels the two outputs share
+    pub overlap: u32,
+    pub curve: BlendCurve,
+    /// The projectors' gamma, which the fade is corrected for
+    pub gamma: f64,
+}
+
+impl Default for BlendEdge {
+    fn default() -> Self {
+        Self {
+            overlap: 0,
+            curve: BlendCurve::default(),
+            gamma: 2.2,
+        }
+    }
+}
+
+/// Outputs making one picture, left to right
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputSpan {
+    /// Monitor ids, left to right
+    pub outputs: Vec<String>,
+    /// Between each output and the next, so one fewer than the outputs
+    pub edges: Vec<BlendEdge>,
+    /// How the program is fitted into the canvas
+    #[serde(default = "default_span_scale")]
+    pub scale: OutputScale,
+}
+
+/// Spans are usually for backgrounds, which should reach both ends
+fn default_span_scale() -> OutputScale {
+    OutputScale::Fill
+}
+
+/// An output's part of a span, in physical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanSlice {
+    pub canvas_width: u32,
+    pub canvas_height: u32,
+    /// Where the output's top-left corner is on the canvas
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub scale: OutputScale,
+    /// The overlap with the output on the left, faded in from this edge
+    pub left: Option<BlendEdge>,
+    /// The overlap with the output on the right, faded out to this edge
+    pub right: Option<BlendEdge>,
+}
+
+impl OutputSpan {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.outputs.len() < 2 {
+            return Err("A span needs at least two outputs".to_string());
+        }
+        if self.edges.len() != self.outputs.len() - 1 {
+            return Err(format!(
+                "A span of {} outputs needs {} edges, not {}",
+                self.outputs.len(),
+                self.outputs.len() - 1,
+                self.edges.len()
+            ));
+        }
+        let mut seen = HashSet::new();
+        if let Some(id) = self.outputs.iter().find(|id| !seen.insert(id.as_str())) {
+            return Err(format!("Output {id} is in the span twice"));
+        }
+        if let Some(edge) = self
+            .edges
+            .iter()
+            .find(|edge| !edge.gamma.is_finite() || !(1.0..=3.0).contains(&edge.gamma))
+        {
+            return Err(format!("A blend gamma of {} is outside 1 to 3", edge.gamma));
+        }
+        Ok(())
+    }
+
+    /// Each output's slice, by monitor id, given the outputs' sizes; outputs
+    /// whose size isn't known (their monitor isn't connected) are taken to
+    /// be the size of the one before
+    pub fn slices(&self, sizes: &HashMap<String, (u32, u32)>) -> HashMap<String, SpanSlice> {
+        let Some(fallback) = self.outputs.iter().find_map(|id| sizes.get(id)) else {
+            return HashMap::new();
+        };
+        let mut last = *fallback;
+        let sizes: Vec<(u32, u32)> = self
+            .outputs
+            .iter()
+            .map(|id| {
+                last = sizes.get(id).copied().unwrap_or(last);
+                last
+            })
+            .collect();
+        // An overlap can't be wider than either output
+        let edges: Vec<BlendEdge> = self
+            .edges
+            .iter()
+            .zip(sizes.windows(2))
+            .map(|(edge, pair)| BlendEdge {
+                overlap: edge.overlap.min(pair[0].0).min(pair[1].0),
+                ..*edge
+            })
+            .collect();
+
+        let mut xs = Vec::with_capacity(sizes.len());
+        let mut x = 0u32;
+        for (i, (width, _)) in sizes.iter().enumerate() {
+            xs.push(x);
+            x += width - edges.get(i).map_or(0, |edge| edge.overlap);
+        }
+        let canvas_width = xs.last().copied().unwrap_or(0) + sizes.last().map_or(0, |size| size.0);
+        let canvas_height = sizes.iter().map(|size| size.1).max().unwrap_or(0);
+
+        self.outputs
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                let (width, height) = sizes[i];
+                let slice = SpanSlice {
+                    canvas_width,
+                    canvas_height,
+                    x: xs[i],
+                    // Shorter outputs are centered
+                    y: (canvas_height - height) / 2,
+                    width,
+                    height,
+                    scale: self.scale,
+                    left: i.checked_sub(1).and_then(|i| edges.get(i)).copied(),
+                    right: edges.get(i).copied(),
+                };
+                (id.clone(), slice)
+            })
+            .collect()
+    }
+}
+
+/// Managed state: the span the outputs make, if any
+#[derive(Default)]
+pub struct OutputSpans {
+    span: Mutex<Option<OutputSpan>>,
+}
+
+impl OutputSpans {
+    pub fn set(&self, span: Option<OutputSpan>) {
+        if let Ok(mut current) = self.span.lock() {
+            *current = span;
+        }
+    }
+
+    pub fn get(&self) -> Option<OutputSpan> {
+        self.span.lock().ok().and_then(|span| span.clone())
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(OutputSpans::default());
+}
@@ -0,0 +1,237 @@
+//! Errors returned to the frontend by commands
+//!
+//! Commands reject with an `AppError` instead of a bare string, so the UI can
+//! branch on `code` (offer "Save As…" for `file-locked`, free space for
+//! `disk-full`, ...) rather than matching error text. `message` is the
+//! human-readable text; `details` carries the path, lock owner, or underlying
+//! error when there is one.
+
+use crate::audio_engine::AudioError;
+use crate::cpres::CpresError;
+use crate::download::DownloadError;
+use crate::ndi::NdiError;
+use crate::recorder::RecordingError;
+use crate::remote_server::RemoteError;
+use crate::screen_capture::ScreenCaptureError;
+use crate::texture_share::TextureShareError;
+use crate::video_player::VideoError;
+use serde::Serialize;
+use std::fmt;
+use std::io::ErrorKind;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCode {
+    Io,
+    NotFound,
+    PermissionDenied,
+    DiskFull,
+    InvalidBundle,
+    MissingFile,
+    /// Another program has the file open
+    FileLocked,
+    /// Another instance of the app is editing the bundle
+    BundleLocked,
+    Corrupted,
+    Ffmpeg,
+    Network,
+    Cancelled,
+    Database,
+    /// Another program's song library or schedule couldn't be read
+    Import,
+    /// A stock media provider was used before its API key was set
+    MissingApiKey,
+    /// The NDI runtime is missing or failed
+    Ndi,
+    /// Spout or Syphon is missing or failed
+    TextureShare,
+    /// The screen couldn't be grabbed
+    ScreenCapture,
+    /// A recording couldn't start, or stopped
+    Recording,
+    /// A video couldn't be played natively
+    Video,
+    /// Audio couldn't be played, or the device wasn't found
+    Audio,
+    /// The remote control server couldn't start, as when its port is taken
+    RemoteControl,
+    /// Errors that haven't been given a code yet
+    Unknown,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AppError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub details: Option<String>,
+}
+
+impl AppError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<CpresError> for AppError {
+    fn from(error: CpresError) -> Self {
+        let message = error.to_string();
+        let (code, details) = match error {
+            CpresError::Io(e) if e.kind() == ErrorKind::NotFound => (ErrorCode::NotFound, None),
+            CpresError::Io(e) => (ErrorCode::Io, Some(format!("{:?}", e.kind()))),
+            CpresError::Zip(_) | CpresError::Json(_) | CpresError::InvalidBundle(_) => {
+                (ErrorCode::InvalidBundle, None)
+            }
+            CpresError::MissingFile(path) => (ErrorCode::MissingFile, Some(path)),
+            CpresError::FileLocked(path) => (ErrorCode::FileLocked, Some(path)),
+            CpresError::BundleLocked(owner) => (ErrorCode::BundleLocked, Some(owner)),
+            CpresError::Corrupted(entries) => (ErrorCode::Corrupted, Some(entries)),
+            CpresError::Ffmpeg(output) => (ErrorCode::Ffmpeg, Some(output)),
+            CpresError::DiskFull(detail) => (ErrorCode::DiskFull, Some(detail)),
+            CpresError::PermissionDenied(detail) => (ErrorCode::PermissionDenied, Some(detail)),
+            CpresError::Cancelled => (ErrorCode::Cancelled, None),
+            CpresError::Database(detail) => (ErrorCode::Database, Some(detail)),
+            CpresError::Import(detail) => (ErrorCode::Import, Some(detail)),
+        };
+        Self {
+            code,
+            message,
+            details,
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(error: std::io::Error) -> Self {
+        CpresError::from(error).into()
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(error: serde_json::Error) -> Self {
+        CpresError::from(error).into()
+    }
+}
+
+impl From<DownloadError> for AppError {
+    fn from(error: DownloadError) -> Self {
+        let code = match &error {
+            DownloadError::Io(e)
+                if matches!(e.kind(), ErrorKind::StorageFull | ErrorKind::QuotaExceeded) =>
+            {
+                ErrorCode::DiskFull
+            }
+            DownloadError::Io(e) if e.kind() == ErrorKind::PermissionDenied => {
+                ErrorCode::PermissionDenied
+            }
+            DownloadError::Io(_) | DownloadError::Json(_) => ErrorCode::Io,
+            DownloadError::MissingApiKey(_) => ErrorCode::MissingApiKey,
+            DownloadError::Cancelled => ErrorCode::Cancelled,
+            DownloadError::ChecksumMismatch { .. } => ErrorCode::Corrupted,
+            _ => ErrorCode::Network,
+        };
+        Self::new(code, error.to_string())
+    }
+}
+
+impl From<NdiError> for AppError {
+    fn from(error: NdiError) -> Self {
+        match error {
+            NdiError::Render(e) => e.into(),
+            e => Self::new(ErrorCode::Ndi, e.to_string()),
+        }
+    }
+}
+
+impl From<TextureShareError> for AppError {
+    fn from(error: TextureShareError) -> Self {
+        match error {
+            TextureShareError::Render(e) => e.into(),
+            e => Self::new(ErrorCode::TextureShare, e.to_string()),
+        }
+    }
+}
+
+impl From<ScreenCaptureError> for AppError {
+    fn from(error: ScreenCaptureError) -> Self {
+        match error {
+            ScreenCaptureError::Io(e) => e.into(),
+            ScreenCaptureError::Ffmpeg(e) => e.into(),
+            #[cfg(target_os = "macos")]
+            ScreenCaptureError::PermissionDenied(detail) => Self {
+                code: ErrorCode::PermissionDenied,
+                message: format!("Screen capture needs permission: {detail}"),
+                details: Some(detail),
+            },
+            e => Self::new(ErrorCode::ScreenCapture, e.to_string()),
+        }
+    }
+}
+
+impl From<RecordingError> for AppError {
+    fn from(error: RecordingError) -> Self {
+        match error {
+            RecordingError::Ffmpeg(e) => e.into(),
+            RecordingError::DiskFull(detail) => Self {
+                code: ErrorCode::DiskFull,
+                message: format!("Not enough free space to record: {detail}"),
+                details: Some(detail),
+            },
+            e => Self::new(ErrorCode::Recording, e.to_string()),
+        }
+    }
+}
+
+impl From<VideoError> for AppError {
+    fn from(error: VideoError) -> Self {
+        match error {
+            VideoError::Ffmpeg(e) => e.into(),
+            e => Self::new(ErrorCode::Video, e.to_string()),
+        }
+    }
+}
+
+impl From<AudioError> for AppError {
+    fn from(error: AudioError) -> Self {
+        match error {
+            AudioError::Bundle(e) => e.into(),
+            e => Self::new(ErrorCode::Audio, e.to_string()),
+        }
+    }
+}
+
+impl From<RemoteError> for AppError {
+    fn from(error: RemoteError) -> Self {
+        Self::new(ErrorCode::RemoteControl, error.to_string())
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(error: tauri::Error) -> Self {
+        Self::new(ErrorCode::Unknown, error.to_string())
+    }
+}
+
+/// Messages from code that still reports errors as text
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorCode::Unknown, message)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        Self::new(ErrorCode::Unknown, message)
+    }
+}
@@ -0,0 +1,380 @@
+//! Exploded bundles: a .cpres as a folder of canonical JSON files
+//!
+//! A zip diffs as one binary blob. Exploding writes the same content as
+//! plain files that diff line by line in git:
+//! - `manifest.json`, `slides.json`, `arrangement.json`, and `themes/*.json`,
+//!   pretty-printed with object keys sorted
+//! - `media/<sha256>.<ext>` for every media and font file, so an unchanged
+//!   file keeps its name and identical files are stored once
+//! - `media.json`, mapping each path inside the bundle to its file in `media/`
+//! - `extra/`, every other entry (written by newer versions or other tools)
+//!   as it is, under its path in the bundle
+//!
+//! Exploding the same bundle twice produces identical files. Imploding a
+//! folder writes a regular bundle again; `checksums.json` is recomputed.
+
+use crate::bundle_reader;
+use crate::cpres::{self, BundleState, CpresError, FontFileRef, MediaFileRef, ThemeFile};
+use crate::export;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tempfile::NamedTempFile;
+
+const MEDIA_DIR: &str = "media";
+const THEMES_DIR: &str = "themes";
+const EXTRA_DIR: &str = "extra";
+const MEDIA_INDEX: &str = "media.json";
+const DOCUMENTS: [&str; 3] = ["manifest.json", "slides.json", "arrangement.json"];
+
+/// Write `bundle` as an exploded folder at `dir`, replacing an earlier explode there.
+/// Files in `dir` that aren't part of the exploded form (`.git`, notes) are left alone.
+pub fn explode_bundle(bundle: &Path, dir: &Path) -> Result<(), CpresError> {
+    let parsed = cpres::open_bundle(bundle)?;
+    let media_dir = dir.join(MEDIA_DIR);
+    let themes_dir = dir.join(THEMES_DIR);
+    fs::create_dir_all(&media_dir)?;
+    fs::create_dir_all(&themes_dir)?;
+
+    let documents = [&parsed.manifest, &parsed.slides, &parsed.arrangement];
+    for (name, content) in DOCUMENTS.iter().zip(documents) {
+        export::write_atomic(&dir.join(name), &canonical_json(content)?)?;
+    }
+
+    let mut written = HashSet::new();
+    for theme in &parsed.themes {
+        let relative = theme
+            .filename
+            .strip_prefix(THEMES_DIR)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .and_then(enclosed)
+            .ok_or_else(|| unplaceable(&theme.filename))?;
+        let path = themes_dir.join(&relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        export::write_atomic(&path, &canonical_json(&theme.content)?)?;
+        written.insert(relative);
+    }
+    remove_stale(&themes_dir, &written)?;
+
+    let extra_dir = dir.join(EXTRA_DIR);
+    let mut archive = bundle_reader::open_archive(bundle)?;
+    let mut index = BTreeMap::new();
+    let mut written = HashSet::new();
+    let mut extra = HashSet::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+        if entry.is_dir() {
+            continue;
+        }
+        if !cpres::is_managed_entry(&name) {
+            let relative = enclosed(&name).ok_or_else(|| unplaceable(&name))?;
+            store_file(&mut entry, &extra_dir.join(&relative))?;
+            extra.insert(relative);
+            continue;
+        }
+        // Documents and themes were written above; checksums are recomputed
+        if !(name.starts_with("media/") || name.starts_with("fonts/")) {
+            continue;
+        }
+        let blob = store_blob(&mut entry, &name, &media_dir)?;
+        written.insert(blob.clone());
+        index.insert(name, blob);
+    }
+    remove_stale(&media_dir, &written)?;
+    if extra_dir.is_dir() {
+        remove_stale(&extra_dir, &extra)?;
+    }
+
+    let mut index_json = serde_json::to_vec_pretty(&index)?;
+    index_json.push(b'\n');
+    export::write_atomic(&dir.join(MEDIA_INDEX), &index_json)
+}
+
+/// Write the exploded folder at `dir` back into a bundle at `bundle`
+pub fn implode_bundle(dir: &Path, bundle: &Path) -> Result<(), CpresError> {
+    let read_document = |name: &str| -> Result<String, CpresError> {
+        let path = dir.join(name);
+        if !path.is_file() {
+            return Err(CpresError::MissingFile(name.to_string()));
+        }
+        let value: Value = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(serde_json::to_string(&value)?)
+    };
+    let manifest = read_document(DOCUMENTS[0])?;
+    let slides = read_document(DOCUMENTS[1])?;
+    let arrangement = read_document(DOCUMENTS[2])?;
+
+    let mut themes = Vec::new();
+    for name in files_under(&dir.join(THEMES_DIR))? {
+        if !name.ends_with(".json") {
+            continue;
+        }
+        let filename = format!("{THEMES_DIR}/{name}");
+        themes.push(ThemeFile {
+            content: read_document(&filename)?,
+            filename,
+        });
+    }
+
+    let extra_dir = dir.join(EXTRA_DIR);
+    let mut extra = Vec::new();
+    for name in files_under(&extra_dir)? {
+        if cpres::is_managed_entry(&name) {
+            return Err(CpresError::InvalidBundle(format!(
+                "{EXTRA_DIR}/{name} would replace part of the bundle"
+            )));
+        }
+        extra.push((name.clone(), extra_dir.join(&name)));
+    }
+
+    let index_path = dir.join(MEDIA_INDEX);
+    let index: BTreeMap<String, String> = if index_path.is_file() {
+        serde_json::from_slice(&fs::read(index_path)?)?
+    } else {
+        BTreeMap::new()
+    };
+    let manifest_value: Value = serde_json::from_str(&manifest)?;
+    let id_for = |list: &str, bundle_path: &str| {
+        manifest_value
+            .get(list)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .find(|entry| entry.get("path").and_then(Value::as_str) == Some(bundle_path))
+            .and_then(|entry| entry.get("id")?.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    let mut media = Vec::new();
+    let mut fonts = Vec::new();
+    for (bundle_path, blob) in index {
+        let Some(blob) = child_name(&format!("{MEDIA_DIR}/{blob}"), MEDIA_DIR) else {
+            return Err(CpresError::InvalidBundle(format!(
+                "{MEDIA_INDEX} points {bundle_path} outside {MEDIA_DIR}/"
+            )));
+        };
+        let source = dir.join(MEDIA_DIR).join(&blob);
+        if !source.is_file() {
+            return Err(CpresError::MissingFile(format!("{MEDIA_DIR}/{blob}")));
+        }
+        let source_path = source.to_string_lossy().to_string();
+        if bundle_path.starts_with("fonts/") {
+            fonts.push(FontFileRef {
+                id: id_for("fonts", &bundle_path),
+                source_path,
+                bundle_path,
+            });
+        } else {
+            media.push(MediaFileRef {
+                id: id_for("media", &bundle_path),
+                source_path,
+                bundle_path,
+                trim: None,
+            });
+        }
+    }
+
+    cpres::save_bundle_with_extra(
+        bundle,
+        &BundleState {
+            manifest,
+            slides,
+            arrangement,
+            themes,
+            media,
+            fonts,
+        },
+        &extra,
+    )
+}
+
+/// Pretty-printed JSON with sorted keys and a trailing newline
+fn canonical_json(content: &str) -> Result<Vec<u8>, CpresError> {
+    // serde_json's maps are ordered by key, so parsing sorts every object
+    let value: Value = serde_json::from_str(content)?;
+    let mut json = serde_json::to_vec_pretty(&value)?;
+    json.push(b'\n');
+    Ok(json)
+}
+
+/// Copy an archive entry into `media_dir` as `<sha256>.<ext>`; returns that file name
+fn store_blob(entry: &mut impl Read, name: &str, media_dir: &Path) -> Result<String, CpresError> {
+    let mut temp_file = NamedTempFile::new_in(media_dir)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = entry.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        temp_file.write_all(&buffer[..read])?;
+    }
+
+    let hash = hex::encode(hasher.finalize());
+    let blob = match Path::new(name).extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{hash}.{}", ext.to_lowercase()),
+        None => hash,
+    };
+    let path = media_dir.join(&blob);
+    // Same name, same content: the copy from an earlier explode can stay
+    if !path.is_file() {
+        cpres::persist_file(temp_file, &path)?;
+    }
+    Ok(blob)
+}
+
+/// Copy an archive entry to `path` as it is
+fn store_file(entry: &mut impl Read, path: &Path) -> Result<(), CpresError> {
+    let parent = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)?;
+    let mut temp_file = NamedTempFile::new_in(parent)?;
+    std::io::copy(entry, &mut temp_file)?;
+    cpres::persist_file(temp_file, path)
+}
+
+/// `path` with `/` separators when it stays inside the folder it's joined to
+fn enclosed(path: &str) -> Option<String> {
+    let parts: Vec<&str> = Path::new(path)
+        .components()
+        .map(|component| match component {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+fn unplaceable(name: &str) -> CpresError {
+    CpresError::InvalidBundle(format!("Entry {name} can't be written to a folder"))
+}
+
+/// Files below `dir` as sorted `/`-separated relative paths, skipping hidden
+/// ones; empty when `dir` doesn't exist
+fn files_under(dir: &Path) -> Result<Vec<String>, CpresError> {
+    let mut files = Vec::new();
+    if dir.is_dir() {
+        collect_files(dir, "", &mut files)?;
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn collect_files(dir: &Path, prefix: &str, files: &mut Vec<String>) -> Result<(), CpresError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), &format!("{prefix}{name}/"), files)?;
+        } else if file_type.is_file() {
+            files.push(format!("{prefix}{name}"));
+        }
+    }
+    Ok(())
+}
+
+/// The file name in `path` when it is directly inside `dir`, like `themes/dark.json`
+fn child_name(path: &str, dir: &str) -> Option<String> {
+    let mut components = Path::new(path).components();
+    let parent = components.next()?;
+    let Component::Normal(name) = components.next()? else {
+        return None;
+    };
+    (parent == Component::Normal(dir.as_ref()) && components.next().is_none())
+        .then(|| name.to_str().map(String::from))
+        .flatten()
+}
+
+/// Delete files below `dir` left over from an earlier explode; `keep` holds
+/// the paths to keep, relative to `dir`
+fn remove_stale(dir: &Path, keep: &HashSet<String>) -> Result<(), CpresError> {
+    let stale: Vec<PathBuf> = files_under(dir)?
+        .into_iter()
+        .filter(|name| !keep.contains(name))
+        .map(|name| dir.join(name))
+        .collect();
+    for path in stale {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn entries(bundle: &Path) -> BTreeMap<String, Vec<u8>> {
+        let mut archive = bundle_reader::open_archive(bundle).unwrap();
+        let names: Vec<String> = archive.file_names().map(String::from).collect();
+        names
+            .into_iter()
+            .filter(|name| name != "checksums.json")
+            .map(|name| {
+                let mut content = Vec::new();
+                archive
+                    .by_name(&name)
+                    .unwrap()
+                    .read_to_end(&mut content)
+                    .unwrap();
+                (name, content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip_keeps_every_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("original.cpres");
+        let mut zip = ZipWriter::new(File::create(&original).unwrap());
+        let files: [(&str, &[u8]); 7] = [
+            (
+                "manifest.json",
+                br#"{"formatVersion":1,"presentationId":"p","media":[{"id":"m","path":"media/a.png"}]}"#,
+            ),
+            ("slides.json", b"[]"),
+            ("arrangement.json", br#"{"order":[]}"#),
+            ("themes/dark.json", br#"{"id":"dark"}"#),
+            ("themes/seasonal/advent.json", br#"{"id":"advent"}"#),
+            ("media/a.png", b"\x89PNG"),
+            ("plugins/notes.bin", b"\x00\x01 from a newer version"),
+        ];
+        for (name, content) in files {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let exploded = dir.path().join("exploded");
+        explode_bundle(&original, &exploded).unwrap();
+        assert!(exploded.join("themes/seasonal/advent.json").is_file());
+        assert!(exploded.join("extra/plugins/notes.bin").is_file());
+
+        let rebuilt = dir.path().join("rebuilt.cpres");
+        implode_bundle(&exploded, &rebuilt).unwrap();
+        let before = entries(&original);
+        let after = entries(&rebuilt);
+        assert_eq!(
+            before.keys().collect::<Vec<_>>(),
+            after.keys().collect::<Vec<_>>()
+        );
+        for name in ["media/a.png", "plugins/notes.bin"] {
+            assert_eq!(before[name], after[name], "{name}");
+        }
+    }
+}
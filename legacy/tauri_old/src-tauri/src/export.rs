@@ -0,0 +1,535 @@
+//! Presentation export
+//!
+//! Renders a bundle's slides in presentation order (see `render`) and writes
+//! them to formats that don't need the app to view:
+//! - PDF: one page per slide, each page a JPEG of the rendered slide
+//! - Image sequence: one PNG or WebP per slide at a fixed resolution, for
+//!   house systems that play a folder of images
+//! - MP4: H.264 video with per-slide durations and the slides' entry
+//!   transitions, encoded by ffmpeg from frames piped to it
+
+use crate::compatibility::Resolution;
+use crate::cpres::{self, CpresError};
+use crate::ffmpeg;
+use crate::render::SlideRenderer;
+use crate::tasks::CancelToken;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ExtendedColorType, ImageEncoder};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, TextStr};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::Stdio;
+use tempfile::NamedTempFile;
+use tiny_skia::{Color, Pixmap, PixmapPaint, Transform};
+
+const DEFAULT_PDF_WIDTH: u32 = 1920;
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+const DEFAULT_FPS: u32 = 30;
+const DEFAULT_SLIDE_SECONDS: f64 = 5.0;
+const DEFAULT_CRF: u8 = 20;
+const DEFAULT_VIDEO_RESOLUTION: Resolution = Resolution {
+    width: 1920,
+    height: 1080,
+};
+/// Page width in points (13.33in, PowerPoint's widescreen page); height follows the slide
+const PDF_PAGE_WIDTH: f32 = 960.0;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PdfOptions {
+    /// Pixel width slides are rendered at
+    pub width: Option<u32>,
+    /// JPEG quality for the page images, 1-100
+    pub quality: Option<u8>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    /// Lossless WebP
+    Webp,
+}
+
+impl ImageFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportReport {
+    pub path: String,
+    pub pages: usize,
+    pub bytes: u64,
+}
+
+/// Render every slide of `bundle` to a PDF at `output`
+pub fn export_pdf(
+    bundle: &Path,
+    output: &Path,
+    options: &PdfOptions,
+    cancel: &CancelToken,
+) -> Result<ExportReport, CpresError> {
+    let mut renderer = SlideRenderer::open(bundle)?;
+    if renderer.is_empty() {
+        return Err(CpresError::InvalidBundle(
+            "Presentation has no slides".to_string(),
+        ));
+    }
+    let width = options.width.unwrap_or(DEFAULT_PDF_WIDTH).clamp(16, 8192);
+    let quality = options
+        .quality
+        .unwrap_or(DEFAULT_JPEG_QUALITY)
+        .clamp(1, 100);
+    let page_height = PDF_PAGE_WIDTH * renderer.height_for(width) as f32 / width as f32;
+
+    let mut pdf = Pdf::new();
+    let catalog_id = Ref::new(1);
+    let tree_id = Ref::new(2);
+    let info_id = Ref::new(3);
+    let mut next_id = 4;
+    let mut page_ids = Vec::with_capacity(renderer.len());
+
+    for position in 0..renderer.len() {
+        cancel.check()?;
+        let pixmap = renderer.render(position, width)?;
+        let jpeg = encode_jpeg(&pixmap, quality)?;
+        let page_id = Ref::new(next_id);
+        let image_id = Ref::new(next_id + 1);
+        let content_id = Ref::new(next_id + 2);
+        next_id += 3;
+        page_ids.push(page_id);
+
+        let image_name = Name(b"Slide");
+        let mut image = pdf.image_xobject(image_id, &jpeg);
+        image.filter(Filter::DctDecode);
+        image.width(pixmap.width() as i32);
+        image.height(pixmap.height() as i32);
+        image.color_space().device_rgb();
+        image.bits_per_component(8);
+        image.finish();
+
+        let mut content = Content::new();
+        content.save_state();
+        content.transform([PDF_PAGE_WIDTH, 0.0, 0.0, page_height, 0.0, 0.0]);
+        content.x_object(image_name);
+        content.restore_state();
+        pdf.stream(content_id, &content.finish());
+
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, PDF_PAGE_WIDTH, page_height));
+        page.parent(tree_id);
+        page.contents(content_id);
+        page.resources().x_objects().pair(image_name, image_id);
+        page.finish();
+    }
+
+    pdf.catalog(catalog_id).pages(tree_id);
+    pdf.pages(tree_id)
+        .kids(page_ids.iter().copied())
+        .count(page_ids.len() as i32);
+    let title = renderer.title();
+    if !title.is_empty() {
+        pdf.document_info(info_id).title(TextStr(title));
+    }
+
+    let data = pdf.finish();
+    write_atomic(output, &data)?;
+    Ok(ExportReport {
+        path: output.to_string_lossy().to_string(),
+        pages: page_ids.len(),
+        bytes: data.len() as u64,
+    })
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VideoOptions {
+    pub resolution: Option<Resolution>,
+    pub fps: Option<u32>,
+    /// Seconds each slide stays up unless listed in `durations`
+    pub slide_seconds: Option<f64>,
+    /// Seconds per slide in presentation order; missing entries use `slide_seconds`
+    pub durations: Vec<f64>,
+    /// x264 constant rate factor, 0-51; lower is higher quality
+    pub crf: Option<u8>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VideoReport {
+    pub path: String,
+    pub frames: u64,
+    pub seconds: f64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageSequenceReport {
+    /// Written files, in presentation order
+    pub files: Vec<String>,
+    pub bytes: u64,
+}
+
+/// Render every slide of `bundle` to numbered image files in `dir`.
+///
+/// Images are exactly `resolution`; a slide with a different aspect ratio is
+/// centered on black.
+pub fn export_images(
+    bundle: &Path,
+    dir: &Path,
+    resolution: Resolution,
+    format: ImageFormat,
+    cancel: &CancelToken,
+) -> Result<ImageSequenceReport, CpresError> {
+    if resolution.width == 0 || resolution.height == 0 {
+        return Err(CpresError::InvalidBundle(format!(
+            "Invalid resolution {}x{}",
+            resolution.width, resolution.height
+        )));
+    }
+    let mut renderer = SlideRenderer::open(bundle)?;
+    let fit_width = (resolution.height as f64 * renderer.aspect_ratio()).round() as u32;
+    let width = fit_width.clamp(1, resolution.width);
+    let digits = renderer.len().to_string().len().max(3);
+
+    fs::create_dir_all(dir)?;
+    let mut report = ImageSequenceReport {
+        files: Vec::with_capacity(renderer.len()),
+        bytes: 0,
+    };
+    for position in 0..renderer.len() {
+        cancel.check()?;
+        let slide = renderer.render(position, width)?;
+        let frame = letterbox(&slide, resolution)?;
+        let data = encode_image(&frame, format)?;
+        let path = dir.join(format!(
+            "Slide {:0digits$}.{}",
+            position + 1,
+            format.extension()
+        ));
+        write_atomic(&path, &data)?;
+        report.bytes += data.len() as u64;
+        report.files.push(path.to_string_lossy().to_string());
+    }
+    Ok(report)
+}
+
+/// Render the presentation into an H.264 MP4 at `output`.
+///
+/// Each slide's entry transition plays during the start of its own duration.
+pub fn export_video(
+    bundle: &Path,
+    output: &Path,
+    options: &VideoOptions,
+    cancel: &CancelToken,
+) -> Result<VideoReport, CpresError> {
+    let mut renderer = SlideRenderer::open(bundle)?;
+    if renderer.is_empty() {
+        return Err(CpresError::InvalidBundle(
+            "Presentation has no slides".to_string(),
+        ));
+    }
+    let requested = options.resolution.unwrap_or(DEFAULT_VIDEO_RESOLUTION);
+    // 4:2:0 chroma needs even dimensions
+    let resolution = Resolution {
+        width: requested.width & !1,
+        height: requested.height & !1,
+    };
+    if resolution.width == 0 || resolution.height == 0 {
+        return Err(CpresError::InvalidBundle(format!(
+            "Invalid resolution {}x{}",
+            requested.width, requested.height
+        )));
+    }
+    let fps = options.fps.unwrap_or(DEFAULT_FPS).clamp(1, 120);
+    let crf = options.crf.unwrap_or(DEFAULT_CRF).min(51);
+
+    let parent = output.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)?;
+    let temp_file = tempfile::Builder::new()
+        .suffix(".mp4")
+        .tempfile_in(parent)?;
+    let mut child = ffmpeg::command()?
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba", "-s"])
+        .arg(format!("{}x{}", resolution.width, resolution.height))
+        .args(["-r", &fps.to_string(), "-i", "-"])
+        .args([
+            "-c:v",
+            "libx264",
+            "-preset",
+            "medium",
+            "-crf",
+            &crf.to_string(),
+        ])
+        .args([
+            "-pix_fmt",
+            "yuv420p",
+            "-movflags",
+            "+faststart",
+            "-f",
+            "mp4",
+        ])
+        .arg(temp_file.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Drain stderr on its own thread so a chatty ffmpeg can't block the pipe
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let errors = std::thread::spawn(move || {
+        let mut errors = String::new();
+        let _ = stderr.read_to_string(&mut errors);
+        errors
+    });
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let written = write_frames(&mut renderer, &mut stdin, resolution, fps, options, cancel);
+    drop(stdin);
+    let cancelled = matches!(written, Err(CpresError::Cancelled));
+    if cancelled {
+        // No point letting ffmpeg finish a file that is about to be deleted
+        let _ = child.kill();
+    }
+
+    let status = child.wait()?;
+    let errors = errors.join().unwrap_or_default();
+    if !status.success() && !cancelled {
+        return Err(CpresError::Ffmpeg(format!(
+            "encoding failed ({status}): {}",
+            errors.trim()
+        )));
+    }
+    let frames = written?;
+
+    let bytes = temp_file.as_file().metadata()?.len();
+    cpres::persist_file(temp_file, output)?;
+    Ok(VideoReport {
+        path: output.to_string_lossy().to_string(),
+        frames,
+        seconds: frames as f64 / fps as f64,
+        bytes,
+    })
+}
+
+/// Write every video frame as raw RGBA; returns the number of frames
+fn write_frames(
+    renderer: &mut SlideRenderer,
+    out: &mut impl Write,
+    resolution: Resolution,
+    fps: u32,
+    options: &VideoOptions,
+    cancel: &CancelToken,
+) -> Result<u64, CpresError> {
+    let slide_width = ((resolution.height as f64 * renderer.aspect_ratio()).round() as u32)
+        .clamp(1, resolution.width);
+    let default_seconds = options.slide_seconds.unwrap_or(DEFAULT_SLIDE_SECONDS);
+    let mut previous: Option<Pixmap> = None;
+    let mut frames = 0;
+
+    for position in 0..renderer.len() {
+        let slide = letterbox(&renderer.render(position, slide_width)?, resolution)?;
+        let seconds = options
+            .durations
+            .get(position)
+            .copied()
+            .filter(|seconds| *seconds > 0.0)
+            .unwrap_or(default_seconds);
+        let count = ((seconds * fps as f64).round() as u64).max(1);
+
+        let transition = renderer.transition(position);
+        let transition_frames = match previous {
+            Some(_) if transition.kind != "none" => {
+                ((transition.seconds * fps as f64).round() as u64).min(count)
+            }
+            _ => 0,
+        };
+        for frame in 0..count {
+            cancel.check()?;
+            match &previous {
+                Some(from) if frame < transition_frames => {
+                    let progress = (frame + 1) as f32 / (transition_frames + 1) as f32;
+                    let progress = ease(&transition.easing, progress);
+                    let blended = transition_frame(from, &slide, &transition.kind, progress)?;
+                    out.write_all(blended.data())?;
+                }
+                _ => out.write_all(slide.data())?,
+            }
+        }
+        frames += count;
+        previous = Some(slide);
+    }
+    Ok(frames)
+}
+
+/// Approximation of the CSS easing keywords
+fn ease(easing: &str, t: f32) -> f32 {
+    match easing {
+        "linear" => t,
+        "ease-in" => t * t,
+        "ease" | "ease-in-out" => t * t * (3.0 - 2.0 * t),
+        _ => 1.0 - (1.0 - t) * (1.0 - t),
+    }
+}
+
+/// Where a slide sits during a transition: offset in frames, scale, and opacity
+struct Pose {
+    dx: f32,
+    dy: f32,
+    scale_x: f32,
+    scale_y: f32,
+    opacity: f32,
+}
+
+/// One frame of `kind` at `progress`, matching the output window's animations:
+/// the old slide plays its exit while the new one plays its entrance.
+fn transition_frame(
+    from: &Pixmap,
+    to: &Pixmap,
+    kind: &str,
+    progress: f32,
+) -> Result<Pixmap, CpresError> {
+    let (width, height) = (to.width() as f32, to.height() as f32);
+    let p = progress.clamp(0.0, 1.0);
+    let pose = |dx: f32, dy: f32, scale_x: f32, scale_y: f32, opacity: f32| Pose {
+        dx: dx * width,
+        dy: dy * height,
+        scale_x,
+        scale_y,
+        opacity,
+    };
+    let (exit, entry) = match kind {
+        "slide-left" => (
+            pose(-p, 0.0, 1.0, 1.0, 1.0 - p),
+            pose(1.0 - p, 0.0, 1.0, 1.0, p),
+        ),
+        "slide-right" => (
+            pose(p, 0.0, 1.0, 1.0, 1.0 - p),
+            pose(p - 1.0, 0.0, 1.0, 1.0, p),
+        ),
+        "slide-up" => (
+            pose(0.0, -p, 1.0, 1.0, 1.0 - p),
+            pose(0.0, 1.0 - p, 1.0, 1.0, p),
+        ),
+        "slide-down" => (
+            pose(0.0, p, 1.0, 1.0, 1.0 - p),
+            pose(0.0, p - 1.0, 1.0, 1.0, p),
+        ),
+        "zoom-in" => {
+            let (out, into) = (1.0 + 0.2 * p, 0.8 + 0.2 * p);
+            (
+                pose(0.0, 0.0, out, out, 1.0 - p),
+                pose(0.0, 0.0, into, into, p),
+            )
+        }
+        "zoom-out" => {
+            let (out, into) = (1.0 - 0.2 * p, 1.2 - 0.2 * p);
+            (
+                pose(0.0, 0.0, out, out, 1.0 - p),
+                pose(0.0, 0.0, into, into, p),
+            )
+        }
+        // A 90 degree turn about the vertical axis, seen head on
+        "flip" => {
+            let turn = std::f32::consts::FRAC_PI_2;
+            (
+                pose(0.0, 0.0, (turn * p).cos(), 1.0, 1.0 - p),
+                pose(0.0, 0.0, (turn * p).sin(), 1.0, p),
+            )
+        }
+        _ => (
+            pose(0.0, 0.0, 1.0, 1.0, 1.0 - p),
+            pose(0.0, 0.0, 1.0, 1.0, p),
+        ),
+    };
+
+    let mut frame = Pixmap::new(to.width(), to.height())
+        .ok_or_else(|| CpresError::InvalidBundle("Invalid frame size".to_string()))?;
+    frame.fill(Color::BLACK);
+    for (pixmap, pose) in [(from, exit), (to, entry)] {
+        let transform = Transform::from_translate(width / 2.0 + pose.dx, height / 2.0 + pose.dy)
+            .pre_scale(pose.scale_x, pose.scale_y)
+            .pre_translate(-width / 2.0, -height / 2.0);
+        let paint = PixmapPaint {
+            opacity: pose.opacity.clamp(0.0, 1.0),
+            ..PixmapPaint::default()
+        };
+        frame.draw_pixmap(0, 0, pixmap.as_ref(), &paint, transform, None);
+    }
+    Ok(frame)
+}
+
+/// The slide centered on a black frame of exactly `resolution`
+fn letterbox(slide: &Pixmap, resolution: Resolution) -> Result<Pixmap, CpresError> {
+    let mut frame = Pixmap::new(resolution.width, resolution.height).ok_or_else(|| {
+        CpresError::InvalidBundle(format!(
+            "Invalid resolution {}x{}",
+            resolution.width, resolution.height
+        ))
+    })?;
+    frame.fill(Color::BLACK);
+    frame.draw_pixmap(
+        (resolution.width.saturating_sub(slide.width()) / 2) as i32,
+        (resolution.height.saturating_sub(slide.height()) / 2) as i32,
+        slide.as_ref(),
+        &PixmapPaint::default(),
+        Transform::identity(),
+        None,
+    );
+    Ok(frame)
+}
+
+fn encode_image(pixmap: &Pixmap, format: ImageFormat) -> Result<Vec<u8>, CpresError> {
+    // Frames are opaque, so premultiplied and straight color are the same
+    let rgba = pixmap.data();
+    let (width, height) = (pixmap.width(), pixmap.height());
+    let mut data = Vec::new();
+    let result = match format {
+        ImageFormat::Png => {
+            PngEncoder::new(&mut data).write_image(rgba, width, height, ExtendedColorType::Rgba8)
+        }
+        ImageFormat::Webp => WebPEncoder::new_lossless(&mut data).write_image(
+            rgba,
+            width,
+            height,
+            ExtendedColorType::Rgba8,
+        ),
+    };
+    result.map_err(|e| CpresError::InvalidBundle(format!("Could not encode slide image: {e}")))?;
+    Ok(data)
+}
+
+/// JPEG of a rendered slide; transparent areas come out black, like the output window
+fn encode_jpeg(pixmap: &Pixmap, quality: u8) -> Result<Vec<u8>, CpresError> {
+    // Premultiplied color is already the slide composited over black
+    let rgb: Vec<u8> = pixmap
+        .data()
+        .chunks_exact(4)
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect();
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, quality)
+        .encode(
+            &rgb,
+            pixmap.width(),
+            pixmap.height(),
+            ExtendedColorType::Rgb8,
+        )
+        .map_err(|e| CpresError::InvalidBundle(format!("Could not encode slide image: {e}")))?;
+    Ok(jpeg)
+}
+
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> Result<(), CpresError> {
+    let parent = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)?;
+    let temp_file = NamedTempFile::new_in(parent)?;
+    fs::write(temp_file.path(), data)?;
+    cpres::persist_file(temp_file, path)
+}
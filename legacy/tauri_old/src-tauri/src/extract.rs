@@ -0,0 +1,182 @@
+//! Extract a bundle's media files to a folder
+//!
+//! Media is stored in the bundle under `media/<id>.<ext>`; the manifest keeps
+//! the file's original name. Extraction writes each file under that original
+//! name, adding " (2)", " (3)", ... when the name is already taken, and never
+//! overwrites existing files. Files under `media/` that the manifest doesn't
+//! list are extracted under their archive name.
+
+use crate::bundle_reader;
+use crate::cpres::{self, CpresError};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+const PROGRESS_EVENT: &str = "cpres:extract-progress";
+/// Longest file stem written, leaving room for a collision suffix
+const MAX_STEM_CHARS: usize = 120;
+
+#[derive(Debug, Serialize)]
+pub struct ExtractedMedia {
+    /// Manifest id; None for files under media/ that the manifest doesn't list
+    pub id: Option<String>,
+    /// Path inside the bundle
+    pub source: String,
+    /// Where the file was written
+    pub path: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExtractReport {
+    pub files: Vec<ExtractedMedia>,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct ExtractProgress {
+    bundle: String,
+    filename: String,
+    extracted: usize,
+    total: usize,
+}
+
+/// Write every media file in `bundle` to `target_dir` under its original name
+pub fn extract_media(
+    app: &AppHandle,
+    bundle: &Path,
+    target_dir: &Path,
+) -> Result<ExtractReport, CpresError> {
+    let parsed = cpres::open_bundle(bundle)?;
+    let manifest: Value = serde_json::from_str(&parsed.manifest)?;
+    let mut archive = bundle_reader::open_archive(bundle)?;
+
+    // (id, archive path, original filename), one per archive file
+    let mut entries: Vec<(Option<String>, String, String)> = Vec::new();
+    let mut listed = HashSet::new();
+    let media = manifest.get("media").and_then(Value::as_array);
+    for entry in media.into_iter().flatten() {
+        let field = |key: &str| entry.get(key).and_then(Value::as_str).unwrap_or_default();
+        let source = field("path");
+        // Two manifest entries may share a file; extract it once
+        if source.is_empty() || !listed.insert(source.to_string()) {
+            continue;
+        }
+        if archive.index_for_name(source).is_none() {
+            log::warn!(
+                "Media {} is listed but missing from the bundle",
+                field("id")
+            );
+            continue;
+        }
+        entries.push((
+            Some(field("id").to_string()),
+            source.to_string(),
+            field("filename").to_string(),
+        ));
+    }
+    let unlisted: Vec<String> = archive
+        .file_names()
+        .filter(|name| name.starts_with("media/") && !name.ends_with('/'))
+        .filter(|name| !listed.contains(*name))
+        .map(String::from)
+        .collect();
+    for source in unlisted {
+        let filename = source.rsplit('/').next().unwrap_or_default().to_string();
+        entries.push((None, source, filename));
+    }
+
+    std::fs::create_dir_all(target_dir)?;
+    let total = entries.len();
+    let mut taken = HashSet::new();
+    let mut files = Vec::with_capacity(total);
+    for (index, (id, source, filename)) in entries.into_iter().enumerate() {
+        let fallback = source.rsplit('/').next().unwrap_or_default();
+        let name = safe_filename(&filename).unwrap_or_else(|| fallback.to_string());
+        let (path, mut file) = create_unique(target_dir, &name, &mut taken)?;
+
+        let mut zip_file = archive.by_name(&source)?;
+        let copied = std::io::copy(&mut zip_file, &mut file);
+        drop(file);
+        let bytes = match copied {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                return Err(e.into());
+            }
+        };
+
+        let _ = app.emit(
+            PROGRESS_EVENT,
+            ExtractProgress {
+                bundle: bundle.to_string_lossy().to_string(),
+                filename: name,
+                extracted: index + 1,
+                total,
+            },
+        );
+        files.push(ExtractedMedia {
+            id,
+            source,
+            path: path.to_string_lossy().to_string(),
+            bytes,
+        });
+    }
+
+    Ok(ExtractReport {
+        bytes: files.iter().map(|f| f.bytes).sum(),
+        files,
+    })
+}
+
+/// `name` without path components or characters Windows rejects; None when nothing usable is left
+pub(crate) fn safe_filename(name: &str) -> Option<String> {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim().trim_end_matches('.').to_string();
+    (!cleaned.is_empty() && cleaned != "..").then_some(cleaned)
+}
+
+/// Create a new file for `name` in `dir`, adding " (n)" before the extension
+/// until the name is free both on disk and among files already extracted
+fn create_unique(
+    dir: &Path,
+    name: &str,
+    taken: &mut HashSet<String>,
+) -> Result<(PathBuf, File), CpresError> {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{ext}")),
+        _ => (name, String::new()),
+    };
+    let stem: String = stem.chars().take(MAX_STEM_CHARS).collect();
+
+    for attempt in 1.. {
+        let candidate = if attempt == 1 {
+            format!("{stem}{extension}")
+        } else {
+            format!("{stem} ({attempt}){extension}")
+        };
+        // Case-insensitive filesystems treat "A.mp4" and "a.mp4" as one file
+        if !taken.insert(candidate.to_lowercase()) {
+            continue;
+        }
+        let path = dir.join(&candidate);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!("ran out of candidate filenames")
+}
@@ -0,0 +1,218 @@
+//! Locating and running ffmpeg
+//!
+//! ffmpeg isn't linked into the app; it runs as a separate process. The
+//! binary is looked up in this order:
+//! 1. `CHURCH_PRESENTER_FFMPEG`, a full path to the executable
+//! 2. next to the app executable, where a bundled sidecar is installed
+//! 3. every folder on `PATH`
+//!
+//! ffprobe, used to read media metadata, is expected next to ffmpeg.
+
+use crate::cpres::CpresError;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const ENV_OVERRIDE: &str = "CHURCH_PRESENTER_FFMPEG";
+#[cfg(target_os = "windows")]
+const BINARY: &str = "ffmpeg.exe";
+#[cfg(not(target_os = "windows"))]
+const BINARY: &str = "ffmpeg";
+#[cfg(target_os = "windows")]
+const PROBE_BINARY: &str = "ffprobe.exe";
+#[cfg(not(target_os = "windows"))]
+const PROBE_BINARY: &str = "ffprobe";
+/// Keep ffmpeg from opening a console window on Windows
+#[cfg(target_os = "windows")]
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// Path to the ffmpeg executable
+pub fn locate() -> Result<PathBuf, CpresError> {
+    if let Some(path) = std::env::var_os(ENV_OVERRIDE).map(PathBuf::from) {
+        if path.is_file() {
+            return Ok(path);
+        }
+        log::warn!(
+            "{ENV_OVERRIDE} points at {}, which doesn't exist",
+            path.display()
+        );
+    }
+
+    let beside_app = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(BINARY)));
+    let on_path: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(BINARY))
+                .collect()
+        })
+        .unwrap_or_default();
+    beside_app
+        .into_iter()
+        .chain(on_path)
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| {
+            CpresError::Ffmpeg(format!(
+                "{BINARY} not found; install ffmpeg or set {ENV_OVERRIDE}"
+            ))
+        })
+}
+
+/// An ffmpeg command that overwrites its output and only logs errors
+pub fn command() -> Result<Command, CpresError> {
+    let mut command = base_command()?;
+    command.args(["-loglevel", "error", "-y"]);
+    Ok(command)
+}
+
+/// An ffmpeg command that logs at the default level, for filters that print
+/// their measurements there
+pub fn analysis_command() -> Result<Command, CpresError> {
+    let mut command = base_command()?;
+    command.arg("-nostats");
+    Ok(command)
+}
+
+/// Run an ffmpeg `command` that writes to stdout and return what it wrote;
+/// `task` names the job in the error
+pub fn capture(mut command: Command, task: &str) -> Result<Vec<u8>, CpresError> {
+    let output = command.stdin(Stdio::null()).output()?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(CpresError::Ffmpeg(format!(
+            "{task} failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Arguments that make ffmpeg report progress on stdout; they go before the
+/// output file of a command passed to `run_with_progress`
+pub const PROGRESS_ARGS: [&str; 3] = ["-progress", "pipe:1", "-nostats"];
+
+/// Run an ffmpeg `command` that writes a file, with `PROGRESS_ARGS`, passing
+/// the share done to `on_progress` at most once per percent when the input's
+/// `duration` is known. ffmpeg is killed once `cancelled` returns true. `task`
+/// names the job in the error.
+pub fn run_with_progress(
+    mut command: Command,
+    duration: Option<f64>,
+    task: &str,
+    cancelled: impl Fn() -> bool,
+    on_progress: impl Fn(f64),
+) -> Result<(), CpresError> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Drain stderr on its own thread so a chatty ffmpeg can't block the pipe
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let errors = std::thread::spawn(move || {
+        let mut errors = String::new();
+        let _ = stderr.read_to_string(&mut errors);
+        errors
+    });
+
+    // -progress writes a block of key=value lines about twice a second
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut reported = 0.0;
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if cancelled() {
+            // No point letting ffmpeg finish a file that is about to be deleted
+            let _ = child.kill();
+            break;
+        }
+        let micros = line
+            .strip_prefix("out_time_us=")
+            .and_then(|micros| micros.trim().parse::<f64>().ok());
+        if let (Some(micros), Some(seconds)) = (micros, duration.filter(|s| *s > 0.0)) {
+            let progress = (micros / 1_000_000.0 / seconds).clamp(0.0, 1.0);
+            if progress - reported >= 0.01 {
+                reported = progress;
+                on_progress(progress);
+            }
+        }
+    }
+
+    let status = child.wait()?;
+    let errors = errors.join().unwrap_or_default();
+    if cancelled() {
+        return Err(CpresError::Cancelled);
+    }
+    if !status.success() {
+        return Err(CpresError::Ffmpeg(format!(
+            "{task} failed ({status}): {}",
+            errors.trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Length of a media file in seconds; None when ffmpeg can't tell (a still
+/// image, a live stream)
+pub fn duration(path: &Path) -> Result<Option<f64>, CpresError> {
+    let output = base_command()?
+        .arg("-i")
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()?;
+    // Without an output file ffmpeg exits with an error, but only after
+    // describing the input on stderr
+    let log = String::from_utf8_lossy(&output.stderr);
+    if !log.contains("Input #0") {
+        return Err(CpresError::Ffmpeg(format!(
+            "could not read {}: {}",
+            path.display(),
+            log.trim()
+        )));
+    }
+    Ok(parse_duration(&log))
+}
+
+/// "Duration: 00:03:25.04, start: ..." to seconds
+fn parse_duration(log: &str) -> Option<f64> {
+    let stamp = log.split("Duration: ").nth(1)?.split(',').next()?.trim();
+    let mut seconds = 0.0;
+    for part in stamp.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+/// An ffprobe command that only logs errors
+pub fn probe_command() -> Result<Command, CpresError> {
+    let ffmpeg = locate()?;
+    let ffprobe = ffmpeg.with_file_name(PROBE_BINARY);
+    if !ffprobe.is_file() {
+        return Err(CpresError::Ffmpeg(format!(
+            "{PROBE_BINARY} not found next to {}",
+            ffmpeg.display()
+        )));
+    }
+    Ok(new_command(
+        ffprobe,
+        &["-hide_banner", "-loglevel", "error"],
+    ))
+}
+
+fn base_command() -> Result<Command, CpresError> {
+    Ok(new_command(locate()?, &["-hide_banner"]))
+}
+
+/// A command for `program` that doesn't open a console window
+fn new_command(program: PathBuf, args: &[&str]) -> Command {
+    let mut command = Command::new(program);
+    command.args(args);
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    command
+}
@@ -0,0 +1,181 @@
+//! Characters a font has no glyph for
+//!
+//! A slide typed in a font without, say, `ñ` or curly quotes shows those
+//! characters in whatever font the system falls back to, or as empty boxes
+//! when none has them, and nobody notices until it's on the screen. The
+//! editor checks slide text against its font first. A font embedded in the
+//! bundle is checked when there is one, as the output would use it, and the
+//! installed font of that family otherwise.
+
+use crate::bundle_reader;
+use crate::cpres::{self, CpresError};
+use ab_glyph::{Font, FontVec};
+use font_kit::family_name::FamilyName;
+use font_kit::handle::Handle;
+use font_kit::properties::{Properties, Style, Weight};
+use font_kit::source::SystemSource;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FontCoverageOptions {
+    /// 400 when not given
+    pub weight: Option<u16>,
+    pub italic: bool,
+    /// Bundle whose embedded fonts are checked before installed ones
+    pub bundle: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FontCoverage {
+    /// False when the font is neither embedded nor installed; nothing is
+    /// checked then
+    pub found: bool,
+    /// True when the font checked is the bundle's own
+    pub embedded: bool,
+    /// In order of first appearance
+    pub missing: Vec<MissingCharacter>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MissingCharacter {
+    pub character: String,
+    /// "U+00F1"
+    pub code_point: String,
+    /// Times it appears in the text
+    pub count: usize,
+}
+
+/// Characters of `text` the font `family` can't draw
+pub fn check(
+    family: &str,
+    text: &str,
+    options: &FontCoverageOptions,
+) -> Result<FontCoverage, CpresError> {
+    let weight = options.weight.unwrap_or(400);
+    let embedded = match &options.bundle {
+        Some(bundle) => bundle_font(Path::new(bundle), family, weight, options.italic)?,
+        None => None,
+    };
+    let is_embedded = embedded.is_some();
+    let Some(font) = embedded.or_else(|| system_font(family, weight, options.italic)) else {
+        return Ok(FontCoverage {
+            found: false,
+            embedded: false,
+            missing: Vec::new(),
+        });
+    };
+
+    let mut counts: BTreeMap<char, (usize, usize)> = BTreeMap::new();
+    for (position, c) in text.chars().enumerate() {
+        if is_invisible(c) || font.glyph_id(c).0 != 0 {
+            continue;
+        }
+        counts.entry(c).or_insert((position, 0)).1 += 1;
+    }
+    let mut missing: Vec<(usize, MissingCharacter)> = counts
+        .into_iter()
+        .map(|(c, (first, count))| {
+            (
+                first,
+                MissingCharacter {
+                    character: c.to_string(),
+                    code_point: format!("U+{:04X}", c as u32),
+                    count,
+                },
+            )
+        })
+        .collect();
+    missing.sort_by_key(|(first, _)| *first);
+    Ok(FontCoverage {
+        found: true,
+        embedded: is_embedded,
+        missing: missing.into_iter().map(|(_, missing)| missing).collect(),
+    })
+}
+
+/// Whitespace and formatting characters, which no font needs a glyph for
+fn is_invisible(c: char) -> bool {
+    c.is_whitespace()
+        || c.is_control()
+        // Zero-width space, joiners, and marks, and the word joiner
+        || ('\u{200B}'..='\u{200F}').contains(&c)
+        || c == '\u{2060}'
+        || c == '\u{FEFF}'
+        // Variation selectors, which pick between emoji and text style
+        || ('\u{FE00}'..='\u{FE0F}').contains(&c)
+}
+
+/// The bundle's embedded font of `family` closest in weight and style
+fn bundle_font(
+    bundle: &Path,
+    family: &str,
+    weight: u16,
+    italic: bool,
+) -> Result<Option<FontVec>, CpresError> {
+    let mut archive = bundle_reader::open_archive(bundle)?;
+    let manifest: Value =
+        serde_json::from_str(&cpres::read_zip_file(&mut archive, "manifest.json")?)?;
+    let path = manifest
+        .get("fonts")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|font| {
+            font.get("family")
+                .and_then(Value::as_str)
+                .is_some_and(|name| name.eq_ignore_ascii_case(family))
+        })
+        .min_by_key(|font| {
+            let font_weight = font.get("weight").and_then(Value::as_u64).unwrap_or(400) as u16;
+            let font_italic = font.get("style").and_then(Value::as_str) == Some("italic");
+            (font_italic != italic, font_weight.abs_diff(weight))
+        })
+        .and_then(|font| font.get("path").and_then(Value::as_str))
+        .map(String::from);
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let mut data = Vec::new();
+    archive
+        .by_name(&path)
+        .map_err(|_| CpresError::MissingFile(path.clone()))?
+        .read_to_end(&mut data)?;
+    match FontVec::try_from_vec(data) {
+        Ok(font) => Ok(Some(font)),
+        Err(e) => {
+            log::warn!("Could not read bundle font {path}: {e}");
+            Ok(None)
+        }
+    }
+}
+
+/// The installed font of `family` closest in weight and style, without
+/// falling back to another family; CSS generic families pick the system's
+pub(crate) fn system_font(family: &str, weight: u16, italic: bool) -> Option<FontVec> {
+    let mut properties = Properties::new();
+    properties.weight = Weight(weight as f32);
+    if italic {
+        properties.style = Style::Italic;
+    }
+    let family = match family.to_lowercase().as_str() {
+        "serif" => FamilyName::Serif,
+        "sans-serif" | "system-ui" => FamilyName::SansSerif,
+        "monospace" => FamilyName::Monospace,
+        "cursive" => FamilyName::Cursive,
+        "fantasy" => FamilyName::Fantasy,
+        _ => FamilyName::Title(family.to_string()),
+    };
+    let handle = SystemSource::new()
+        .select_best_match(&[family], &properties)
+        .ok()?;
+    let (data, index) = match handle {
+        Handle::Path { path, font_index } => (std::fs::read(path).ok()?, font_index),
+        Handle::Memory { bytes, font_index } => (bytes.to_vec(), font_index),
+    };
+    FontVec::try_from_vec_and_index(data, index).ok()
+}
@@ -0,0 +1,248 @@
+//! Variation axes and named instances of variable fonts
+//!
+//! A variable font is one file covering a range of weights, widths, or
+//! slants, so "normal or italic at one weight" undersells it. Its axes
+//! (`wght` 100 to 900, say) and the named instances the designer picked
+//! along them ("Semibold Condensed") are read from the `fvar` table, so
+//! themes can ask for any weight the font has.
+//!
+//! The font's license and embedding permissions are read here too: the
+//! `name` table's copyright and license entries and the OS/2 `fsType` bits,
+//! which say whether a font may be shipped inside a document others open.
+
+use crate::cpres::CpresError;
+use font_kit::handle::Handle;
+use font_kit::properties::Style;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use ttf_parser::{name_id, Face, Language, Permissions, Tag};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FontAxis {
+    /// "wght", "wdth", "ital", "slnt", "opsz", or a custom axis
+    pub tag: String,
+    /// As the font names it; not read when listing fonts
+    pub name: Option<String>,
+    pub min: f32,
+    pub default: f32,
+    pub max: f32,
+    /// Axes the designer meant to keep out of font menus
+    pub hidden: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NamedInstance {
+    pub name: Option<String>,
+    pub postscript_name: Option<String>,
+    /// Value on each axis, by tag
+    pub coordinates: BTreeMap<String, f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FontDetails {
+    pub family: String,
+    pub full_name: String,
+    pub postscript_name: Option<String>,
+    /// Of the default instance for variable fonts
+    pub weight: u16,
+    pub style: String,
+    /// Empty for fonts that aren't variable
+    pub axes: Vec<FontAxis>,
+    pub instances: Vec<NamedInstance>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmbeddingPermission {
+    /// May be embedded and installed where the document is opened
+    Installable,
+    /// May be embedded in documents that are edited
+    Editable,
+    /// May be embedded only in documents that are viewed or printed
+    PreviewAndPrint,
+    /// May not be embedded at all
+    Restricted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FontLicense {
+    pub copyright: Option<String>,
+    /// License description, often the whole text
+    pub license: Option<String>,
+    pub license_url: Option<String>,
+    /// None when the font doesn't say (no OS/2 table)
+    pub embedding: Option<EmbeddingPermission>,
+    /// Whether only the used characters may be embedded instead of the
+    /// whole font
+    pub subsetting_allowed: bool,
+}
+
+impl FontLicense {
+    /// Why the font shouldn't go in a bundle that's shared, if it shouldn't
+    pub fn embedding_warning(&self) -> Option<String> {
+        match self.embedding? {
+            EmbeddingPermission::Restricted => Some(
+                "The font's license doesn't allow embedding it; \
+                 others opening the bundle need their own copy"
+                    .to_string(),
+            ),
+            EmbeddingPermission::PreviewAndPrint => Some(
+                "The font's license only allows embedding it in documents \
+                 that aren't edited"
+                    .to_string(),
+            ),
+            EmbeddingPermission::Installable | EmbeddingPermission::Editable => None,
+        }
+    }
+}
+
+/// License and embedding permissions of the font at `font_index` in a font
+/// file's data; None when it isn't a font ttf-parser reads (WOFF, say)
+pub fn license(data: &[u8], font_index: u32) -> Option<FontLicense> {
+    let face = Face::parse(data, font_index).ok()?;
+    let embedding = face.permissions().map(|permissions| match permissions {
+        Permissions::Installable => EmbeddingPermission::Installable,
+        Permissions::Editable => EmbeddingPermission::Editable,
+        Permissions::PreviewAndPrint => EmbeddingPermission::PreviewAndPrint,
+        Permissions::Restricted => EmbeddingPermission::Restricted,
+    });
+    Some(FontLicense {
+        copyright: name(&face, name_id::COPYRIGHT_NOTICE),
+        license: name(&face, name_id::LICENSE),
+        license_url: name(&face, name_id::LICENSE_URL),
+        embedding,
+        subsetting_allowed: face.is_subsetting_allowed(),
+    })
+}
+
+/// Names, axes, and named instances of the font at `font_index` in the file
+/// at `path` (0 except in collections)
+pub fn details(path: &Path, font_index: u32) -> Result<FontDetails, CpresError> {
+    let font = Handle::Path {
+        path: path.to_path_buf(),
+        font_index,
+    }
+    .load()
+    .map_err(|e| CpresError::InvalidBundle(format!("Failed to load font: {e}")))?;
+    let properties = font.properties();
+    let style = match properties.style {
+        Style::Italic | Style::Oblique => "italic",
+        _ => "normal",
+    };
+
+    let data = std::fs::read(path)?;
+    let face = Face::parse(&data, font_index)
+        .map_err(|e| CpresError::InvalidBundle(format!("Failed to read font: {e}")))?;
+    let axes: Vec<FontAxis> = face
+        .variation_axes()
+        .into_iter()
+        .map(|axis| FontAxis {
+            name: name(&face, axis.name_id),
+            ..to_axis(&axis)
+        })
+        .collect();
+    let instances = face
+        .raw_face()
+        .table(Tag::from_bytes(b"fvar"))
+        .map(|fvar| named_instances(&face, fvar, &axes))
+        .unwrap_or_default();
+
+    Ok(FontDetails {
+        family: font.family_name(),
+        full_name: font.full_name(),
+        postscript_name: font.postscript_name(),
+        weight: properties.weight.0 as u16,
+        style: style.to_string(),
+        axes,
+        instances,
+    })
+}
+
+/// Axes in a raw `fvar` table, without their names; for listing every
+/// installed font without parsing each one in full
+pub fn axes(fvar: &[u8]) -> Vec<FontAxis> {
+    ttf_parser::fvar::Table::parse(fvar)
+        .map(|table| table.axes.into_iter().map(|axis| to_axis(&axis)).collect())
+        .unwrap_or_default()
+}
+
+fn to_axis(axis: &ttf_parser::VariationAxis) -> FontAxis {
+    FontAxis {
+        tag: axis.tag.to_string(),
+        name: None,
+        min: axis.min_value,
+        default: axis.def_value,
+        max: axis.max_value,
+        hidden: axis.hidden,
+    }
+}
+
+/// The instance records that follow the axes in `fvar`, which ttf-parser
+/// doesn't read
+fn named_instances(face: &Face, fvar: &[u8], axes: &[FontAxis]) -> Vec<NamedInstance> {
+    let u16_at = |offset: usize| {
+        fvar.get(offset..offset + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    };
+    let (Some(axes_offset), Some(axis_count), Some(axis_size), Some(count), Some(size)) =
+        (u16_at(4), u16_at(8), u16_at(10), u16_at(12), u16_at(14))
+    else {
+        return Vec::new();
+    };
+    let (axis_count, size) = (axis_count as usize, size as usize);
+    if axis_count != axes.len() || size < axis_count * 4 + 4 {
+        return Vec::new();
+    }
+    // Subfamily name ID, flags, one 16.16 coordinate per axis, and
+    // optionally a PostScript name ID
+    let has_postscript_name = size >= axis_count * 4 + 6;
+    let start = axes_offset as usize + axis_count * axis_size as usize;
+    (0..count as usize)
+        .map_while(|index| {
+            let offset = start + index * size;
+            let record = fvar.get(offset..offset + size)?;
+            let coordinates = axes
+                .iter()
+                .enumerate()
+                .map(|(axis, FontAxis { tag, .. })| {
+                    let at = 4 + axis * 4;
+                    let fixed = i32::from_be_bytes(record[at..at + 4].try_into().ok()?);
+                    Some((tag.clone(), fixed as f32 / 65536.0))
+                })
+                .collect::<Option<_>>()?;
+            let postscript_name = if has_postscript_name {
+                let at = 4 + axis_count * 4;
+                let id = u16::from_be_bytes([record[at], record[at + 1]]);
+                // 0xFFFF means the instance has none
+                (id != 0xFFFF).then(|| name(face, id)).flatten()
+            } else {
+                None
+            };
+            Some(NamedInstance {
+                name: name(face, u16::from_be_bytes([record[0], record[1]])),
+                postscript_name,
+                coordinates,
+            })
+        })
+        .collect()
+}
+
+/// Entry `name_id` of the `name` table, in US English when the font has it
+fn name(face: &Face, name_id: u16) -> Option<String> {
+    let mut fallback = None;
+    for entry in face
+        .names()
+        .into_iter()
+        .filter(|entry| entry.name_id == name_id)
+    {
+        let Some(text) = entry.to_string() else {
+            continue;
+        };
+        if entry.language() == Language::English_UnitedStates {
+            return Some(text);
+        }
+        fallback.get_or_insert(text);
+    }
+    fallback
+}
@@ -0,0 +1,346 @@
+//! Installing a bundle's embedded fonts on this machine
+//!
+//! Embedded fonts travel with the bundle, but only the app reads them from
+//! there; a video stage display or a program the slides are handed to
+//! still needs the font installed. A bundle's fonts can be installed for
+//! the current user, which needs no admin rights: into the user's own fonts
+//! folder (and, on Windows, its registry list), where they stay. Or they can
+//! be registered for this session only: copied into
+//! `<app cache>/session-fonts/`, added to the font list, and on Windows
+//! registered with the system until the app exits. Web fonts (WOFF, WOFF2)
+//! can't be installed and are reported as such.
+
+use crate::bundle_reader;
+use crate::cpres::{self, CpresError};
+use crate::export;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+pub(crate) const SESSION_DIR_NAME: &str = "session-fonts";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallScope {
+    /// Installed for the current user, and kept
+    User,
+    /// Until the app exits
+    Session,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FontInstallStatus {
+    Installed,
+    /// The same file was already there
+    AlreadyInstalled,
+    /// A web font, which the OS can't install
+    Unsupported,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FontInstall {
+    /// The font's id in the bundle manifest
+    pub id: String,
+    pub family: String,
+    /// Where the font file was put
+    pub path: Option<String>,
+    pub status: FontInstallStatus,
+}
+
+/// Install the bundle's embedded fonts with `ids`, or all of them
+pub fn install_bundle_fonts(
+    app: &AppHandle,
+    bundle: &Path,
+    ids: Option<&[String]>,
+    scope: InstallScope,
+) -> Result<Vec<FontInstall>, CpresError> {
+    let dir = match scope {
+        InstallScope::User => user_fonts_dir()
+            .ok_or_else(|| CpresError::MissingFile("user fonts folder".to_string()))?,
+        InstallScope::Session => session_dir(
+            &app.path()
+                .app_cache_dir()
+                .map_err(|e| CpresError::MissingFile(format!("app cache directory: {e}")))?,
+        ),
+    };
+
+    let mut archive = bundle_reader::open_archive(bundle)?;
+    let manifest: Value =
+        serde_json::from_str(&cpres::read_zip_file(&mut archive, "manifest.json")?)?;
+    let fonts: Vec<Value> = manifest
+        .get("fonts")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    let mut results = Vec::new();
+    for font in &fonts {
+        let text = |key: &str| {
+            font.get(key)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let (id, family, bundle_path) = (text("id"), text("family"), text("path"));
+        if ids.is_some_and(|ids| !ids.contains(&id)) || bundle_path.is_empty() {
+            continue;
+        }
+        let extension = Path::new(&bundle_path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        if !matches!(extension.as_str(), "ttf" | "otf" | "ttc" | "otc") {
+            results.push(FontInstall {
+                id,
+                family,
+                path: None,
+                status: FontInstallStatus::Unsupported,
+            });
+            continue;
+        }
+
+        let mut data = Vec::new();
+        archive
+            .by_name(&bundle_path)
+            .map_err(|_| CpresError::MissingFile(bundle_path.clone()))?
+            .read_to_end(&mut data)?;
+        let full_name = face_name(font);
+        let path = dir.join(format!("{}.{extension}", file_stem(&full_name)));
+
+        let status = if std::fs::read(&path).is_ok_and(|existing| existing == data) {
+            FontInstallStatus::AlreadyInstalled
+        } else {
+            export::write_atomic(&path, &data)?;
+            FontInstallStatus::Installed
+        };
+        // Registering again is harmless, and covers a file copied in
+        // before a failed registration
+        register(&path, &full_name, scope)?;
+        results.push(FontInstall {
+            id,
+            family,
+            path: Some(path.to_string_lossy().to_string()),
+            status,
+        });
+    }
+    fonts_changed();
+    Ok(results)
+}
+
+/// The name a face is installed under, which has to tell the faces of a
+/// family apart: they'd overwrite each other's file otherwise
+fn face_name(font: &Value) -> String {
+    let text = |key: &str| {
+        font.get(key)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+    };
+    if let Some(name) = text("fullName").or_else(|| text("postscriptName")) {
+        return name.to_string();
+    }
+    let family = text("family").unwrap_or("Font");
+    let weight = font.get("weight").and_then(Value::as_u64).unwrap_or(400);
+    match text("style") {
+        Some("italic") => format!("{family} {weight} Italic"),
+        _ => format!("{family} {weight}"),
+    }
+}
+
+/// Where session fonts are copied
+pub(crate) fn session_dir(app_cache_dir: &Path) -> PathBuf {
+    app_cache_dir.join(SESSION_DIR_NAME)
+}
+
+/// The per-user fonts folder, which needs no admin rights to write to
+fn user_fonts_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        let local = std::env::var_os("LOCALAPPDATA")?;
+        Some(PathBuf::from(local).join(r"Microsoft\Windows\Fonts"))
+    } else if cfg!(target_os = "macos") {
+        Some(PathBuf::from(std::env::var_os("HOME")?).join("Library/Fonts"))
+    } else {
+        let data = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".local/share")))?;
+        Some(data.join("fonts"))
+    }
+}
+
+/// A file name for the font, without characters file systems reject
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match stem.trim() {
+        "" => "font".to_string(),
+        stem => stem.to_string(),
+    }
+}
+
+/// Tell the OS about a font file. macOS and Linux pick up the user's fonts
+/// folder on their own, and have no session-wide registration, so session
+/// fonts there are known to this app only.
+#[cfg(target_os = "windows")]
+fn register(path: &Path, full_name: &str, scope: InstallScope) -> Result<(), CpresError> {
+    windows_fonts::add(path)?;
+    if scope == InstallScope::User {
+        windows_fonts::remember(path, full_name)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn register(_path: &Path, _full_name: &str, _scope: InstallScope) -> Result<(), CpresError> {
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn fonts_changed() {
+    windows_fonts::broadcast_change();
+}
+
+#[cfg(not(target_os = "windows"))]
+fn fonts_changed() {}
+
+/// Drop the session fonts: unregister them and delete the copies. Run at exit,
+/// and at startup in case the last run didn't get to it.
+fn clear_session(app: &AppHandle) {
+    let Ok(app_cache_dir) = app.path().app_cache_dir() else {
+        return;
+    };
+    let dir = session_dir(&app_cache_dir);
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        #[cfg(target_os = "windows")]
+        windows_fonts::remove(&path);
+        if let Err(e) = std::fs::remove_file(&path) {
+            log::info!("Could not delete session font {}: {e}", path.display());
+        }
+    }
+    fonts_changed();
+}
+
+pub fn init(app: &AppHandle) {
+    clear_session(app);
+}
+
+pub fn shutdown(app: &AppHandle) {
+    clear_session(app);
+}
+
+#[cfg(target_os = "windows")]
+mod windows_fonts {
+    use crate::cpres::CpresError;
+    use std::path::Path;
+    use windows::core::HSTRING;
+    use windows::Win32::Foundation::{LPARAM, WPARAM};
+    use windows::Win32::Graphics::Gdi::{AddFontResourceW, RemoveFontResourceW};
+    use windows::Win32::System::Registry::{RegSetKeyValueW, HKEY_CURRENT_USER, REG_SZ};
+    use windows::Win32::UI::WindowsAndMessaging::{PostMessageW, HWND_BROADCAST, WM_FONTCHANGE};
+
+    /// Where Windows lists the fonts to load at sign-in
+    const FONTS_KEY: &str = r"Software\Microsoft\Windows NT\CurrentVersion\Fonts";
+
+    /// Make the font usable by every program until sign-out
+    pub fn add(path: &Path) -> Result<(), CpresError> {
+        let added = unsafe { AddFontResourceW(&HSTRING::from(path.as_os_str())) };
+        if added == 0 {
+            return Err(CpresError::InvalidBundle(format!(
+                "Windows could not load the font {}",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn remove(path: &Path) {
+        let _ = unsafe { RemoveFontResourceW(&HSTRING::from(path.as_os_str())) };
+    }
+
+    /// List the font under the current user, so it's loaded at each sign-in
+    pub fn remember(path: &Path, full_name: &str) -> Result<(), CpresError> {
+        let kind = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("otf" | "OTF") => "OpenType",
+            _ => "TrueType",
+        };
+        let value: Vec<u16> = path
+            .to_string_lossy()
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .collect();
+        unsafe {
+            RegSetKeyValueW(
+                HKEY_CURRENT_USER,
+                &HSTRING::from(FONTS_KEY),
+                &HSTRING::from(format!("{full_name} ({kind})")),
+                REG_SZ.0,
+                Some(value.as_ptr().cast()),
+                (value.len() * 2) as u32,
+            )
+        }
+        .ok()
+        .map_err(|e| CpresError::InvalidBundle(format!("Could not register the font: {e}")))
+    }
+
+    /// Tell running programs the fonts changed, so they list them again
+    pub fn broadcast_change() {
+        let _ = unsafe { PostMessageW(Some(HWND_BROADCAST), WM_FONTCHANGE, WPARAM(0), LPARAM(0)) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn faces_of_one_family_get_their_own_files() {
+        let cases = [
+            (
+                json!({"family": "Inter", "fullName": "Inter Regular", "weight": 400}),
+                json!({"family": "Inter", "fullName": "Inter Bold", "weight": 700}),
+            ),
+            (
+                json!({"family": "Inter", "postscriptName": "Inter-Regular"}),
+                json!({"family": "Inter", "postscriptName": "Inter-Italic"}),
+            ),
+            (
+                json!({"family": "Inter", "fullName": "", "weight": 400}),
+                json!({"family": "Inter", "fullName": "", "weight": 400, "style": "italic"}),
+            ),
+        ];
+        for (regular, other) in cases {
+            let (a, b) = (face_name(&regular), face_name(&other));
+            assert_ne!(file_stem(&a), file_stem(&b), "{regular} and {other}");
+        }
+    }
+
+    #[test]
+    fn face_name_prefers_the_full_name() {
+        let font = json!({
+            "family": "Inter",
+            "fullName": "Inter Bold",
+            "postscriptName": "Inter-Bold",
+            "weight": 700,
+        });
+        assert_eq!(face_name(&font), "Inter Bold");
+        assert_eq!(
+            face_name(&json!({"family": "Inter", "weight": 700})),
+            "Inter 700"
+        );
+    }
+}
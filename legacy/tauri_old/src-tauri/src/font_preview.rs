@@ -0,0 +1,174 @@
+//! Sample text drawn in a font, for the font picker
+//!
+//! Showing every installed font in its own face would mean loading hundreds
+//! of webfonts into the picker. Instead each preview is drawn here with the
+//! same glyph rasterizer as the slide renderer, into a transparent PNG cached
+//! under `<app cache>/font-previews/`. A font is given by family name, or by
+//! the path of a font file not yet installed or embedded (one being imported,
+//! say). The cache key covers the font file's size and modification time, so
+//! an updated font is drawn again.
+
+use crate::cpres::CpresError;
+use crate::export;
+use crate::font_coverage;
+use crate::render;
+use ab_glyph::{point, Font, FontVec, GlyphId, ScaleFont};
+use image::codecs::png::PngEncoder;
+use image::{ImageEncoder, RgbaImage};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+pub(crate) const CACHE_DIR_NAME: &str = "font-previews";
+
+/// Font size in pixels when the caller doesn't ask for one
+pub const DEFAULT_SIZE: u32 = 32;
+const MIN_SIZE: u32 = 8;
+const MAX_SIZE: u32 = 256;
+/// Longer samples are cut off at this width
+const MAX_WIDTH: u32 = 4096;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FontPreviewOptions {
+    /// 400 when not given; ignored for font files
+    pub weight: Option<u16>,
+    pub italic: bool,
+    /// CSS color of the text; black when not given
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FontPreview {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    /// False when it had to be drawn
+    pub cached: bool,
+}
+
+/// `sample` (the family name when empty) drawn in `font` at `size` pixels
+/// per em, on one line
+pub fn render_preview(
+    app_cache_dir: &Path,
+    font: &str,
+    sample: &str,
+    size: u32,
+    options: &FontPreviewOptions,
+) -> Result<FontPreview, CpresError> {
+    let size = size.clamp(MIN_SIZE, MAX_SIZE);
+    let weight = options.weight.unwrap_or(400);
+    let file = Path::new(font);
+    let is_file = file.is_file();
+    let sample = match sample.trim() {
+        "" if is_file => file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        "" => font.to_string(),
+        sample => sample.replace(['\r', '\n', '\t'], " "),
+    };
+    let color = options
+        .color
+        .as_deref()
+        .and_then(render::parse_color)
+        .map(|color| color.to_color_u8())
+        .map_or([0, 0, 0, 255], |color| {
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        });
+
+    let mut hasher = Sha256::new();
+    if is_file {
+        let metadata = std::fs::metadata(file)?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_nanos());
+        hasher.update(format!("file\0{font}\0{}\0{modified}", metadata.len()));
+    } else {
+        hasher.update(format!("family\0{font}\0{weight}\0{}", options.italic));
+    }
+    hasher.update(format!("\0{sample}\0{size}\0{color:?}"));
+    let output = preview_path(app_cache_dir, &hex::encode(hasher.finalize()));
+    if let Ok(image) = image::image_dimensions(&output) {
+        return Ok(FontPreview {
+            path: output.to_string_lossy().to_string(),
+            width: image.0,
+            height: image.1,
+            cached: true,
+        });
+    }
+
+    let loaded = if is_file {
+        FontVec::try_from_vec(std::fs::read(file)?).ok()
+    } else {
+        font_coverage::system_font(font, weight, options.italic)
+    };
+    let loaded = loaded.ok_or_else(|| CpresError::MissingFile(format!("font {font}")))?;
+    let image = draw(&loaded, &sample, size as f32, color);
+    let mut data = Vec::new();
+    PngEncoder::new(&mut data)
+        .write_image(
+            &image,
+            image.width(),
+            image.height(),
+            image::ExtendedColorType::Rgba8,
+        )
+        .map_err(|e| CpresError::InvalidBundle(format!("Could not encode preview: {e}")))?;
+    export::write_atomic(&output, &data)?;
+    Ok(FontPreview {
+        path: output.to_string_lossy().to_string(),
+        width: image.width(),
+        height: image.height(),
+        cached: false,
+    })
+}
+
+/// The sample on a transparent image as tall as the font's line
+fn draw(font: &FontVec, sample: &str, size: f32, color: [u8; 4]) -> RgbaImage {
+    let scaled = font.as_scaled(render::px_scale(font, size));
+    // A little room on each side for glyphs that overhang their advance
+    let padding = (size / 8.0).ceil();
+    let mut glyphs = Vec::new();
+    let mut x = padding;
+    let mut previous: Option<GlyphId> = None;
+    for c in sample.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            x += scaled.kern(previous, id);
+        }
+        glyphs.push(id.with_scale_and_position(scaled.scale(), point(x, scaled.ascent())));
+        x += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    let width = ((x + padding).ceil() as u32).clamp(1, MAX_WIDTH);
+    let height = (scaled.ascent() - scaled.descent()).ceil().max(1.0) as u32;
+
+    let mut image = RgbaImage::new(width, height);
+    for glyph in glyphs {
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + gx as i64;
+            let py = bounds.min.y as i64 + gy as i64;
+            if px < 0 || py < 0 || px >= width as i64 || py >= height as i64 {
+                return;
+            }
+            let pixel = image.get_pixel_mut(px as u32, py as u32);
+            // Overlapping glyphs add up, as in the slide renderer
+            let alpha = pixel[3] as f32 + coverage * color[3] as f32;
+            *pixel = image::Rgba([color[0], color[1], color[2], alpha.min(255.0) as u8]);
+        });
+    }
+    image
+}
+
+fn preview_path(app_cache_dir: &Path, digest: &str) -> PathBuf {
+    app_cache_dir
+        .join(CACHE_DIR_NAME)
+        .join(format!("{digest}.png"))
+}
@@ -0,0 +1,83 @@
+//! Animated GIFs as looping video clips
+//!
+//! The webview decodes a GIF frame by frame on the CPU, so a GIF background
+//! stutters at anything above a small size and keeps a core busy while it's
+//! on screen. The same animation as a video is decoded by the GPU and is
+//! usually a fraction of the size. An animated GIF is imported as an H.264
+//! MP4 without audio, or as a VP9 WebM when its first frame has transparency,
+//! since only WebM keeps the alpha channel. Still GIFs are left as they are.
+
+use crate::cpres::CpresError;
+use crate::ffmpeg;
+use crate::tasks::CancelToken;
+use image::codecs::gif::GifDecoder;
+use image::AnimationDecoder;
+use serde::Deserialize;
+use std::io::BufReader;
+use std::path::Path;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GifConversion {
+    /// MP4, or WebM for GIFs with transparency
+    #[default]
+    Auto,
+    Mp4,
+    Webm,
+    /// Import animated GIFs as they are
+    Off,
+}
+
+/// An animated GIF, as found by `inspect`
+pub struct AnimatedGif {
+    pub width: u32,
+    pub height: u32,
+    /// Whether the first frame has transparent pixels
+    pub transparent: bool,
+}
+
+/// The GIF at `path` when it has more than one frame; None for still and
+/// unreadable GIFs
+pub fn inspect(path: &Path) -> Option<AnimatedGif> {
+    let file = std::fs::File::open(path).ok()?;
+    let decoder = GifDecoder::new(BufReader::new(file)).ok()?;
+    // Only the first two frames are decoded
+    let mut frames = decoder.into_frames();
+    let first = frames.next()?.ok()?.into_buffer();
+    frames.next()?.ok()?;
+    Some(AnimatedGif {
+        width: first.width(),
+        height: first.height(),
+        transparent: first.pixels().any(|pixel| pixel[3] < 255),
+    })
+}
+
+/// Transcode the GIF at `path` to `output`, an `.mp4` or `.webm` file, played
+/// through once; the player loops it
+pub fn to_video(path: &Path, output: &Path, cancel: &CancelToken) -> Result<(), CpresError> {
+    let webm = output
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("webm"));
+    let mut command = ffmpeg::command()?;
+    command.arg("-i").arg(path).args(["-map", "0:v:0", "-an"]);
+    if webm {
+        command
+            .args(["-c:v", "libvpx-vp9", "-pix_fmt", "yuva420p"])
+            .args(["-crf", "32", "-b:v", "0", "-auto-alt-ref", "0"])
+            .args(["-f", "webm"]);
+    } else {
+        // 4:2:0 video needs even dimensions
+        command
+            .args(["-vf", "scale=trunc(iw/2)*2:trunc(ih/2)*2,format=yuv420p"])
+            .args(["-c:v", "libx264", "-preset", "medium", "-crf", "20"])
+            .args(["-movflags", "+faststart", "-f", "mp4"]);
+    }
+    command.args(ffmpeg::PROGRESS_ARGS).arg(output);
+    ffmpeg::run_with_progress(
+        command,
+        None,
+        "GIF conversion",
+        || cancel.is_cancelled(),
+        |_| {},
+    )
+}
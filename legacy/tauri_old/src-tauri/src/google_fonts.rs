@@ -0,0 +1,320 @@
+//! Browsing and downloading Google Fonts
+//!
+//! A church's brand font is often on Google Fonts, but installing it needs
+//! admin rights the volunteer at the booth doesn't have. Families are
+//! downloaded into the app's own fonts folder instead, where the font list
+//! picks them up, so they can be chosen and embedded in a bundle like an
+//! installed font.
+//!
+//! The catalog comes from the Google Fonts Developer API, which needs an API
+//! key of the church's own, kept in `<app data>/google-fonts.json`. The
+//! catalog is saved in `<app data>/google-fonts-catalog.json` and fetched
+//! again once a week, so searching works offline and without a key once it
+//! has been fetched.
+
+use crate::download::{self, DownloadError};
+use crate::system_fonts;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+const KEY_FILENAME: &str = "google-fonts.json";
+const CATALOG_FILENAME: &str = "google-fonts-catalog.json";
+const API_URL: &str = "https://www.googleapis.com/webfonts/v1/webfonts";
+const PROVIDER_NAME: &str = "Google Fonts";
+
+/// The catalog is fetched again when older than this
+const CATALOG_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GoogleFontsKey {
+    api_key: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Catalog {
+    /// Unix milliseconds
+    fetched_at: u64,
+    /// Most popular first
+    families: Vec<CatalogFamily>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CatalogResponse {
+    items: Vec<CatalogFamily>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CatalogFamily {
+    family: String,
+    category: String,
+    /// "regular", "italic", "700", "700italic", ...
+    variants: Vec<String>,
+    #[serde(default)]
+    subsets: Vec<String>,
+    /// Font file by variant
+    files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GoogleFontSearchOptions {
+    /// "serif", "sans-serif", "display", "handwriting", or "monospace"
+    pub category: Option<String>,
+    /// Only families with this character set, such as "latin-ext"
+    pub subset: Option<String>,
+    /// Fetch the catalog even when the saved one is recent
+    pub refresh: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GoogleFont {
+    pub family: String,
+    pub category: String,
+    pub variants: Vec<String>,
+    pub subsets: Vec<String>,
+    /// Downloaded into the app's fonts folder
+    pub installed: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InstalledGoogleFont {
+    pub family: String,
+    /// The font files, for embedding
+    pub paths: Vec<String>,
+}
+
+/// Whether an API key is set
+pub fn has_key(app_data_dir: &Path) -> bool {
+    read_key(app_data_dir).is_some()
+}
+
+/// Store the API key, or forget it with None
+pub fn set_key(app_data_dir: &Path, key: Option<&str>) -> Result<(), DownloadError> {
+    let key = GoogleFontsKey {
+        api_key: key
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(String::from),
+    };
+    std::fs::create_dir_all(app_data_dir)?;
+    std::fs::write(
+        app_data_dir.join(KEY_FILENAME),
+        serde_json::to_vec_pretty(&key)?,
+    )?;
+    Ok(())
+}
+
+/// Families whose name contains `query`, most popular first; an empty query
+/// lists the whole catalog
+pub async fn search(
+    app_data_dir: &Path,
+    query: &str,
+    options: &GoogleFontSearchOptions,
+) -> Result<Vec<GoogleFont>, DownloadError> {
+    let catalog = catalog(app_data_dir, options.refresh).await?;
+    let query = query.trim().to_lowercase();
+    let fonts_dir = system_fonts::managed_dir(app_data_dir);
+    Ok(catalog
+        .families
+        .into_iter()
+        .filter(|family| family.family.to_lowercase().contains(&query))
+        .filter(|family| {
+            options
+                .category
+                .as_ref()
+                .is_none_or(|category| family.category.eq_ignore_ascii_case(category))
+        })
+        .filter(|family| {
+            options
+                .subset
+                .as_ref()
+                .is_none_or(|subset| family.subsets.contains(subset))
+        })
+        .map(|family| GoogleFont {
+            installed: family_dir(&fonts_dir, &family.family).is_dir(),
+            family: family.family,
+            category: family.category,
+            variants: family.variants,
+            subsets: family.subsets,
+        })
+        .collect())
+}
+
+/// Families downloaded so far, with their files
+pub fn installed(app_data_dir: &Path) -> Result<Vec<InstalledGoogleFont>, DownloadError> {
+    let fonts_dir = system_fonts::managed_dir(app_data_dir);
+    let entries = match std::fs::read_dir(&fonts_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut installed = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let mut paths: Vec<String> = std::fs::read_dir(entry.path())?
+            .flatten()
+            .map(|file| file.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "ttf"))
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        paths.sort();
+        installed.push(InstalledGoogleFont {
+            family: entry.file_name().to_string_lossy().to_string(),
+            paths,
+        });
+    }
+    installed.sort_by(|a, b| a.family.cmp(&b.family));
+    Ok(installed)
+}
+
+/// Download `variants` of `family` (all of them when None) into the app's
+/// fonts folder; returns the font files
+pub async fn install(
+    app: &AppHandle,
+    app_data_dir: &Path,
+    family: &str,
+    variants: Option<&[String]>,
+) -> Result<Vec<PathBuf>, DownloadError> {
+    let catalog = catalog(app_data_dir, false).await?;
+    let entry = catalog
+        .families
+        .into_iter()
+        .find(|entry| entry.family.eq_ignore_ascii_case(family.trim()))
+        .ok_or_else(|| DownloadError::UnexpectedResponse(format!("No font family {family}")))?;
+    let variants: Vec<String> = match variants {
+        Some(variants) => variants.to_vec(),
+        None => entry.variants.clone(),
+    };
+    let mut files = Vec::new();
+    for variant in &variants {
+        let url = entry.files.get(variant).ok_or_else(|| {
+            DownloadError::UnexpectedResponse(format!("{} has no {variant} style", entry.family))
+        })?;
+        files.push((variant, url));
+    }
+
+    let dir = family_dir(&system_fonts::managed_dir(app_data_dir), &entry.family);
+    std::fs::create_dir_all(&dir)?;
+    let cache_root = download::cache_root(app_data_dir);
+    let mut paths = Vec::new();
+    for (variant, url) in files {
+        // The API still lists some files with plain http links
+        let url = match url.strip_prefix("http://") {
+            Some(rest) => format!("https://{rest}"),
+            None => url.clone(),
+        };
+        let cached = download::fetch_cached(app, &cache_root, &url, None).await?;
+        let path = dir.join(format!("{}-{variant}.ttf", entry.family.replace(' ', "")));
+        std::fs::copy(&cached.path, &path)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Delete a downloaded family; bundles it's embedded in keep their copy
+pub fn uninstall(app_data_dir: &Path, family: &str) -> Result<(), DownloadError> {
+    if family.trim().is_empty() {
+        return Err(DownloadError::UnexpectedResponse(
+            "No font family".to_string(),
+        ));
+    }
+    let dir = family_dir(&system_fonts::managed_dir(app_data_dir), family);
+    match std::fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// The saved catalog while it's recent, and a new one otherwise; the saved
+/// one is used when the API can't be reached
+async fn catalog(app_data_dir: &Path, refresh: bool) -> Result<Catalog, DownloadError> {
+    let saved = read_catalog(app_data_dir);
+    let now = now_millis();
+    if let Some(saved) = &saved {
+        let age = Duration::from_millis(now.saturating_sub(saved.fetched_at));
+        if !refresh && age < CATALOG_MAX_AGE {
+            return Ok(saved.clone());
+        }
+    }
+    let Some(key) = read_key(app_data_dir) else {
+        return saved.ok_or_else(|| DownloadError::MissingApiKey(PROVIDER_NAME.to_string()));
+    };
+    match fetch_catalog(&key).await {
+        Ok(families) => {
+            let catalog = Catalog {
+                fetched_at: now,
+                families,
+            };
+            std::fs::create_dir_all(app_data_dir)?;
+            std::fs::write(
+                app_data_dir.join(CATALOG_FILENAME),
+                serde_json::to_vec(&catalog)?,
+            )?;
+            Ok(catalog)
+        }
+        Err(e) => match saved {
+            Some(saved) => {
+                log::warn!("Could not fetch the Google Fonts catalog, using the saved one: {e}");
+                Ok(saved)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+async fn fetch_catalog(key: &str) -> Result<Vec<CatalogFamily>, DownloadError> {
+    let url = Url::parse_with_params(API_URL, [("key", key), ("sort", "popularity")])
+        .map_err(|_| DownloadError::UnsupportedUrl(API_URL.to_string()))?;
+    let response = reqwest::Client::new().get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(DownloadError::Status(response.status().as_u16()));
+    }
+    let catalog: CatalogResponse = serde_json::from_slice(&response.bytes().await?)?;
+    Ok(catalog.items)
+}
+
+fn read_key(app_data_dir: &Path) -> Option<String> {
+    std::fs::read(app_data_dir.join(KEY_FILENAME))
+        .ok()
+        .and_then(|content| serde_json::from_slice::<GoogleFontsKey>(&content).ok())
+        .and_then(|key| key.api_key)
+}
+
+fn read_catalog(app_data_dir: &Path) -> Option<Catalog> {
+    let data = std::fs::read(app_data_dir.join(CATALOG_FILENAME)).ok()?;
+    serde_json::from_slice(&data)
+        .inspect_err(|e| log::info!("Ignoring the saved Google Fonts catalog: {e}"))
+        .ok()
+}
+
+/// A family's folder; family names are letters, digits, and spaces, but the
+/// name also comes from the frontend
+fn family_dir(fonts_dir: &Path, family: &str) -> PathBuf {
+    let name: String = family
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == ' ' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    fonts_dir.join(name)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
@@ -0,0 +1,1206 @@
+//! Drawing slides on the GPU
+//!
+//! With the `gpu` feature, `SlideRenderer::render` draws the background and
+//! text and puts the slide together with wgpu instead of on the CPU: solid
+//! and gradient backgrounds are drawn by a shader, background images are kept
+//! on the GPU between slides and scaled there, and the rest of the slide
+//! (shapes, layer images and media cues, still drawn by `render` on
+//! transparency) is blended over them in layer order. Exports, thumbnails and
+//! the outputs drawn offscreen (NDI, DeckLink, shared textures, the virtual
+//! output) all draw through it, so they match each other whatever the
+//! webview does.
+//!
+//! Text layers are laid out by `render`, and each glyph's coverage is kept in
+//! an atlas on the GPU between slides. A layer's glyphs are drawn into a mask
+//! there, which is blurred for the text shadow and grown for the outline,
+//! then painted and placed on the slide with the layer's transform. Text
+//! layers with effects or a blend mode are still drawn by `render`.
+//!
+//! Colors are blended in the same non-linear, premultiplied RGBA that
+//! tiny-skia uses, so a slide looks the same drawn either way. The GPU is
+//! set up the first time a slide is drawn; without one, or with
+//! `CHURCH_PRESENTER_RENDERER=cpu`, slides are drawn on the CPU as before.
+
+use crate::cpres::CpresError;
+use crate::render::{self, Backdrop, GlyphKey, Piece, SlideRenderer, TextRun};
+use ab_glyph::{FontVec, OutlinedGlyph};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use thiserror::Error;
+use tiny_skia::{Color, IntSize, Pixmap, Rect};
+
+const ENV_OVERRIDE: &str = "CHURCH_PRESENTER_RENDERER";
+/// Premultiplied RGBA with no sRGB conversion, as tiny-skia draws
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+/// Glyph coverage in the atlas, a byte a pixel
+const GLYPH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+/// A text layer's coverage, which overlapping glyphs may push past 1
+const MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+/// Gradient stops the shader takes; later ones are left out
+const MAX_STOPS: usize = 8;
+/// Background images kept on the GPU
+const MAX_CACHED_IMAGES: usize = 16;
+/// Width and height of the glyph atlas
+const ATLAS_SIZE: u32 = 2048;
+/// Fonts whose glyphs are kept in the atlas
+const MAX_CACHED_FONTS: usize = 16;
+const SHADER: &str = r#"
+struct Params {
+    // Where the quad goes, in pixels: x, y, width, height
+    rect: vec4<f32>,
+    // Target width and height, opacity, and 0 for a gradient or 1 for a texture
+    output: vec4<f32>,
+    // Gradient start x and y, and its direction over its squared length
+    gradient: vec4<f32>,
+    // Stop count in x
+    count: vec4<f32>,
+    colors: array<vec4<f32>, 8>,
+    offsets: array<vec4<f32>, 2>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var image: texture_2d<f32>;
+@group(0) @binding(2) var image_sampler: sampler;
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 0.0), vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0), vec2<f32>(1.0, 0.0), vec2<f32>(1.0, 1.0),
+    );
+    let uv = corners[index];
+    let pixel = params.rect.xy + uv * params.rect.zw;
+    var out: VertexOut;
+    out.position = vec4<f32>(
+        pixel.x / params.output.x * 2.0 - 1.0,
+        1.0 - pixel.y / params.output.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.uv = uv;
+    return out;
+}
+
+fn offset(i: u32) -> f32 {
+    return params.offsets[i / 4u][i % 4u];
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    if params.output.w > 0.5 {
+        return textureSample(image, image_sampler, in.uv) * params.output.z;
+    }
+    // Straight colors are mixed, then premultiplied, as tiny-skia does
+    let t = dot(in.position.xy - params.gradient.xy, params.gradient.zw);
+    let count = u32(params.count.x);
+    var color = params.colors[0];
+    for (var i = 1u; i < count; i++) {
+        if t <= offset(i) {
+            let span = max(offset(i) - offset(i - 1u), 0.00001);
+            let f = clamp((t - offset(i - 1u)) / span, 0.0, 1.0);
+            color = mix(params.colors[i - 1u], params.colors[i], f);
+            break;
+        }
+        color = params.colors[i];
+    }
+    return vec4<f32>(color.rgb * color.a, color.a) * params.output.z;
+}
+"#;
+
+/// Adds up glyph coverage from the atlas into a text layer's mask
+const GLYPH_SHADER: &str = r#"
+struct Params {
+    // Mask width and height
+    size: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var atlas: texture_2d<f32>;
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) origin: vec2<f32>,
+    @location(1) @interpolate(flat) slot: vec2<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    // Where the glyph goes on the mask, in pixels: x, y, width, height
+    @location(0) rect: vec4<f32>,
+    // Where its coverage is in the atlas, in x and y
+    @location(1) slot: vec4<f32>,
+) -> VertexOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 0.0), vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0), vec2<f32>(1.0, 0.0), vec2<f32>(1.0, 1.0),
+    );
+    let pixel = rect.xy + corners[index] * rect.zw;
+    var out: VertexOut;
+    out.position = vec4<f32>(
+        pixel.x / params.size.x * 2.0 - 1.0,
+        1.0 - pixel.y / params.size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.origin = rect.xy;
+    out.slot = slot.xy;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    let texel = vec2<i32>(floor(in.position.xy - in.origin + in.slot));
+    return vec4<f32>(textureLoad(atlas, texel, 0).r, 0.0, 0.0, 0.0);
+}
+"#;
+
+/// One pass of `render::blur` or `render::dilate` over a mask
+const FILTER_SHADER: &str = r#"
+struct Params {
+    // One pixel along the pass in x and y, the radius in pixels, and 1 to
+    // keep the largest value or 0 to take the mean
+    settings: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var source: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle over the whole mask
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(source));
+    let at = vec2<i32>(position.xy);
+    let along = vec2<i32>(params.settings.xy);
+    let radius = i32(params.settings.z);
+    let grow = params.settings.w > 0.5;
+    // Past the edges counts as empty
+    var total = 0.0;
+    for (var i = -radius; i <= radius; i++) {
+        let texel = at + along * i;
+        if all(texel >= vec2<i32>(0)) && all(texel < size) {
+            let value = clamp(textureLoad(source, texel, 0).r, 0.0, 1.0);
+            if grow {
+                total = max(total, value);
+            } else {
+                total += value;
+            }
+        }
+    }
+    if !grow {
+        total /= f32(radius * 2 + 1);
+    }
+    return vec4<f32>(total, 0.0, 0.0, 0.0);
+}
+"#;
+
+/// Paints a text layer's masks, as `render::paint_mask` does, and places
+/// its surface on the slide
+const TEXT_SHADER: &str = r#"
+struct Params {
+    // The surface's x and y axes on the slide: sx, ky, then kx, sy
+    axes: vec4<f32>,
+    // Where the surface's origin goes, then the target width and height
+    origin: vec4<f32>,
+    // Surface width and height, then the shadow offset
+    surface: vec4<f32>,
+    // Opacity in x, and 1 in y to take the nearest pixel, as tiny-skia
+    // does when the surface is only moved
+    opacity: vec4<f32>,
+    // Premultiplied colors, bottom to top; a clear one paints nothing
+    shadow: vec4<f32>,
+    outline: vec4<f32>,
+    fill: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var shadow_mask: texture_2d<f32>;
+@group(0) @binding(2) var outline_mask: texture_2d<f32>;
+@group(0) @binding(3) var fill_mask: texture_2d<f32>;
+@group(0) @binding(4) var mask_sampler: sampler;
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    // Where on the surface, in pixels
+    @location(0) local: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 0.0), vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0), vec2<f32>(1.0, 0.0), vec2<f32>(1.0, 1.0),
+    );
+    let local = corners[index] * params.surface.xy;
+    let pixel = params.origin.xy + params.axes.xy * local.x + params.axes.zw * local.y;
+    var out: VertexOut;
+    out.position = vec4<f32>(
+        pixel.x / params.origin.z * 2.0 - 1.0,
+        1.0 - pixel.y / params.origin.w * 2.0,
+        0.0,
+        1.0,
+    );
+    out.local = local;
+    return out;
+}
+
+fn coverage(mask: texture_2d<f32>, at: vec2<f32>) -> f32 {
+    let value = textureSampleLevel(mask, mask_sampler, at / params.surface.xy, 0.0).r;
+    return clamp(value, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    var local = in.local;
+    if params.opacity.y > 0.5 {
+        local = floor(local) + 0.5;
+    }
+    let shifted = local - params.surface.zw;
+    var shadow = coverage(shadow_mask, shifted);
+    if any(shifted < vec2<f32>(0.0)) || any(shifted > params.surface.xy) {
+        shadow = 0.0;
+    }
+    let outline = coverage(outline_mask, local);
+    let fill = coverage(fill_mask, local);
+    var color = params.shadow * shadow;
+    color = params.outline * outline + color * (1.0 - params.outline.a * outline);
+    color = params.fill * fill + color * (1.0 - params.fill.a * fill);
+    return color * params.opacity.x;
+}
+"#;
+
+static RENDERER: OnceLock<Option<Mutex<GpuRenderer>>> = OnceLock::new();
+
+#[derive(Error, Debug)]
+pub enum GpuError {
+    #[error("{0}x{1} is larger than the GPU can draw")]
+    TooLarge(u32, u32),
+
+    #[error("GPU error: {0}")]
+    Failed(String),
+
+    #[error(transparent)]
+    Render(#[from] CpresError),
+}
+
+/// The GPU, set up on first use; None without one
+pub fn renderer() -> Option<&'static Mutex<GpuRenderer>> {
+    RENDERER
+        .get_or_init(|| {
+            if std::env::var(ENV_OVERRIDE).is_ok_and(|value| value.eq_ignore_ascii_case("cpu")) {
+                log::info!("{ENV_OVERRIDE} is cpu; drawing slides on the CPU");
+                return None;
+            }
+            match pollster::block_on(GpuRenderer::new()) {
+                Ok(renderer) => Some(Mutex::new(renderer)),
+                Err(e) => {
+                    log::info!("No GPU to draw slides with; drawing them on the CPU: {e}");
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+/// A background image on the GPU, kept while its decoded image is the same
+struct CachedImage {
+    image: Arc<Pixmap>,
+    view: wgpu::TextureView,
+}
+
+/// Glyph coverage kept on the GPU between slides, packed in rows
+struct GlyphAtlas {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    /// Top left corner of each glyph's coverage
+    slots: HashMap<GlyphKey, (u32, u32)>,
+    /// Fonts of the glyphs kept, so no other font is loaded at their address
+    fonts: Vec<Arc<FontVec>>,
+    /// Where the next glyph goes, and the height of the row so far
+    cursor: (u32, u32),
+    row_height: u32,
+}
+
+impl GlyphAtlas {
+    fn new(device: &wgpu::Device) -> Self {
+        let texture = create_texture(
+            device,
+            ATLAS_SIZE,
+            ATLAS_SIZE,
+            GLYPH_FORMAT,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        );
+        Self {
+            view: texture.create_view(&Default::default()),
+            texture,
+            slots: HashMap::new(),
+            fonts: Vec::new(),
+            cursor: (0, 0),
+            row_height: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.slots.clear();
+        self.fonts.clear();
+        self.cursor = (0, 0);
+        self.row_height = 0;
+    }
+
+    /// Add the glyphs of `run` that aren't here yet; false once it's full
+    fn add_run(&mut self, queue: &wgpu::Queue, run: &TextRun) -> bool {
+        if !self.fonts.iter().any(|font| Arc::ptr_eq(font, &run.font)) {
+            self.fonts.push(run.font.clone());
+        }
+        run.glyphs
+            .iter()
+            .all(|(key, glyph)| self.slots.contains_key(key) || self.add(queue, *key, glyph))
+    }
+
+    fn add(&mut self, queue: &wgpu::Queue, key: GlyphKey, glyph: &OutlinedGlyph) -> bool {
+        let bounds = glyph.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        if width == 0 || height == 0 {
+            return true;
+        }
+        if self.cursor.0 + width > ATLAS_SIZE {
+            self.cursor = (0, self.cursor.1 + self.row_height);
+            self.row_height = 0;
+        }
+        if width > ATLAS_SIZE || self.cursor.1 + height > ATLAS_SIZE {
+            return false;
+        }
+
+        let mut coverage = vec![0u8; width as usize * height as usize];
+        glyph.draw(|x, y, value| {
+            if let Some(cell) = coverage.get_mut((y * width + x) as usize) {
+                *cell = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        });
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: self.cursor.0,
+                    y: self.cursor.1,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &coverage,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: None,
+            },
+            extent(width, height),
+        );
+        self.slots.insert(key, self.cursor);
+        self.cursor.0 += width;
+        self.row_height = self.row_height.max(height);
+        true
+    }
+}
+
+/// One draw of the slide pass, bottom to top
+enum Draw {
+    Quad(wgpu::BindGroup),
+    Text(wgpu::BindGroup),
+}
+
+pub struct GpuRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    /// Adds up glyph coverage into a text layer's mask
+    glyph_pipeline: wgpu::RenderPipeline,
+    /// Blurs or grows a mask along one axis
+    filter_pipeline: wgpu::RenderPipeline,
+    /// Takes a uniform and a mask or the atlas, for the two pipelines above
+    mask_layout: wgpu::BindGroupLayout,
+    /// Paints a text layer's masks onto the slide
+    text_pipeline: wgpu::RenderPipeline,
+    text_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    /// Bound when a quad draws no texture
+    blank: wgpu::TextureView,
+    images: HashMap<String, CachedImage>,
+    atlas: GlyphAtlas,
+}
+
+impl GpuRenderer {
+    async fn new() -> Result<Self, GpuError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                force_fallback_adapter: false,
+                compatible_surface: None,
+            })
+            .await
+            .map_err(|e| GpuError::Failed(e.to_string()))?;
+        let info = adapter.get_info();
+        if info.device_type == wgpu::DeviceType::Cpu {
+            return Err(GpuError::Failed(format!(
+                "{} is a software renderer",
+                info.name
+            )));
+        }
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("slides"),
+                required_features: wgpu::Features::empty(),
+                // Whatever the GPU allows, for 4K and larger slides
+                required_limits: adapter.limits(),
+                memory_hints: wgpu::MemoryHints::Performance,
+                trace: wgpu::Trace::Off,
+            })
+            .await
+            .map_err(|e| GpuError::Failed(e.to_string()))?;
+        log::info!("Drawing slides on {} ({:?})", info.name, info.backend);
+
+        let layout = create_layout(&device, 1, true);
+        let pipeline = create_pipeline(
+            &device,
+            SHADER,
+            &layout,
+            &[],
+            FORMAT,
+            wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+        );
+        let mask_layout = create_layout(&device, 1, false);
+        let glyph_pipeline = create_pipeline(
+            &device,
+            GLYPH_SHADER,
+            &mask_layout,
+            &[wgpu::VertexBufferLayout {
+                array_stride: 8 * 4,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4],
+            }],
+            MASK_FORMAT,
+            // Overlapping glyphs add up, as `render` draws them
+            wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::REPLACE,
+            },
+        );
+        let filter_pipeline = create_pipeline(
+            &device,
+            FILTER_SHADER,
+            &mask_layout,
+            &[],
+            MASK_FORMAT,
+            wgpu::BlendState::REPLACE,
+        );
+        let text_layout = create_layout(&device, 3, true);
+        let text_pipeline = create_pipeline(
+            &device,
+            TEXT_SHADER,
+            &text_layout,
+            &[],
+            FORMAT,
+            wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+        );
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("slides"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            blank: upload(&device, &queue, &[0; 4], 1, 1),
+            atlas: GlyphAtlas::new(&device),
+            device,
+            queue,
+            pipeline,
+            layout,
+            glyph_pipeline,
+            filter_pipeline,
+            mask_layout,
+            text_pipeline,
+            text_layout,
+            sampler,
+            images: HashMap::new(),
+        })
+    }
+
+    /// Draw the slide at `position` of `slides`, `width` pixels wide
+    pub fn render(
+        &mut self,
+        slides: &mut SlideRenderer,
+        position: usize,
+        width: u32,
+    ) -> Result<Pixmap, GpuError> {
+        let (below, above, backdrop) = slides.render_above_background(position, width)?;
+        let (width, height) = (above.width(), above.height());
+        let max = self.device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(GpuError::TooLarge(width, height));
+        }
+        let full = (0.0, 0.0, width as f32, height as f32);
+
+        let mut quads = Vec::new();
+        match &backdrop {
+            Some(Backdrop::Solid(color)) => {
+                quads.push((gradient_params(full, full, 0.0, &[(0.0, *color)]), None));
+            }
+            Some(Backdrop::Gradient {
+                angle,
+                stops,
+                fallback,
+            }) => {
+                let stops: Vec<(f32, Color)> = match stops.as_slice() {
+                    [] => fallback.iter().map(|color| (0.0, *color)).collect(),
+                    stops => stops.to_vec(),
+                };
+                if !stops.is_empty() {
+                    quads.push((gradient_params(full, full, *angle, &stops), None));
+                }
+            }
+            Some(Backdrop::Image {
+                key,
+                image,
+                fit,
+                position,
+                opacity,
+            }) => {
+                if image.width() > max || image.height() > max {
+                    return Err(GpuError::TooLarge(image.width(), image.height()));
+                }
+                let area = Rect::from_xywh(0.0, 0.0, width as f32, height as f32)
+                    .expect("the slide is never empty");
+                let size = (image.width() as f32, image.height() as f32);
+                let (x, y, sx, sy) = render::fit_image(size, area, fit, *position);
+                let rect = (x, y, size.0 * sx, size.1 * sy);
+                let view = self.image(key, image);
+                quads.push((texture_params(rect, full, *opacity), Some(view)));
+            }
+            None => {}
+        }
+        let mut draws: Vec<Draw> = quads
+            .iter()
+            .map(|(params, view)| {
+                Draw::Quad(self.bind_group(params, view.as_ref().unwrap_or(&self.blank)))
+            })
+            .collect();
+
+        let runs: Vec<&TextRun> = below
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Text(run) => Some(run),
+                Piece::Pixels(_) => None,
+            })
+            .collect();
+        self.cache_glyphs(&runs)?;
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("slides"),
+            });
+        for piece in below.iter().chain([Piece::Pixels(above)].iter()) {
+            match piece {
+                // Most of a slide is often text, with nothing drawn between it
+                Piece::Pixels(pixmap) if pixmap.pixels().iter().all(|p| p.alpha() == 0) => {}
+                Piece::Pixels(pixmap) => {
+                    let view = upload(&self.device, &self.queue, pixmap.data(), width, height);
+                    draws.push(Draw::Quad(
+                        self.bind_group(&texture_params(full, full, 1.0), &view),
+                    ));
+                }
+                Piece::Text(run) => {
+                    draws.push(Draw::Text(self.prepare_text(&mut encoder, run, full)?));
+                }
+            }
+        }
+
+        let target = create_texture(
+            &self.device,
+            width,
+            height,
+            FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let target_view = target.create_view(&Default::default());
+        {
+            let mut pass = begin_pass(&mut encoder, &target_view);
+            for draw in &draws {
+                let (pipeline, bind_group) = match draw {
+                    Draw::Quad(bind_group) => (&self.pipeline, bind_group),
+                    Draw::Text(bind_group) => (&self.text_pipeline, bind_group),
+                };
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.draw(0..6, 0..1);
+            }
+        }
+        self.read_back(encoder, &target, width, height)
+    }
+
+    /// Put the glyphs of `runs` in the atlas, emptying it first if they
+    /// don't fit with what's there
+    fn cache_glyphs(&mut self, runs: &[&TextRun]) -> Result<(), GpuError> {
+        let new_fonts = runs
+            .iter()
+            .filter(|run| !self.atlas.fonts.iter().any(|f| Arc::ptr_eq(f, &run.font)))
+            .count();
+        if self.atlas.fonts.len() + new_fonts > MAX_CACHED_FONTS {
+            self.atlas.clear();
+        }
+        if runs.iter().all(|run| self.atlas.add_run(&self.queue, run)) {
+            return Ok(());
+        }
+        self.atlas.clear();
+        if runs.iter().all(|run| self.atlas.add_run(&self.queue, run)) {
+            return Ok(());
+        }
+        self.atlas.clear();
+        Err(GpuError::Failed(
+            "The slide's text has more glyphs than the GPU keeps".to_string(),
+        ))
+    }
+
+    /// Draw the masks of the text layer `run`, and bind them to paint it
+    /// onto the slide `target`
+    fn prepare_text(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        run: &TextRun,
+        target: Rect4,
+    ) -> Result<wgpu::BindGroup, GpuError> {
+        let (width, height) = run.size;
+        let max = self.device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(GpuError::TooLarge(width, height));
+        }
+
+        let instances: Vec<u8> = run
+            .glyphs
+            .iter()
+            .filter_map(|(key, glyph)| {
+                let &(x, y) = self.atlas.slots.get(key)?;
+                let bounds = glyph.px_bounds();
+                Some([
+                    bounds.min.x,
+                    bounds.min.y,
+                    bounds.width(),
+                    bounds.height(),
+                    x as f32,
+                    y as f32,
+                    0.0,
+                    0.0,
+                ])
+            })
+            .flatten()
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        let fill = self.mask_texture(width, height);
+        {
+            let bind_group =
+                self.mask_bind_group(&[width as f32, height as f32, 0.0, 0.0], &self.atlas.view);
+            let mut pass = begin_pass(encoder, &fill);
+            if !instances.is_empty() {
+                let buffer = wgpu::util::DeviceExt::create_buffer_init(
+                    &self.device,
+                    &wgpu::util::BufferInitDescriptor {
+                        label: Some("slides"),
+                        contents: &instances,
+                        usage: wgpu::BufferUsages::VERTEX,
+                    },
+                );
+                pass.set_pipeline(&self.glyph_pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.set_vertex_buffer(0, buffer.slice(..));
+                pass.draw(0..6, 0..(instances.len() / 32) as u32);
+            }
+        }
+
+        let paint = &run.paint;
+        let shadow = match paint.shadow {
+            Some((_, radius, _)) => self.filter(encoder, &fill, run.size, radius, false),
+            None => fill.clone(),
+        };
+        let outline = match paint.outline {
+            Some((_, radius)) => self.filter(encoder, &fill, run.size, radius, true),
+            None => fill.clone(),
+        };
+
+        let t = run.placement;
+        let offset = paint.shadow.map_or((0, 0), |(_, _, offset)| offset);
+        let mut params = [0.0; 28];
+        params[..4].copy_from_slice(&[t.sx, t.ky, t.kx, t.sy]);
+        params[4..8].copy_from_slice(&[t.tx, t.ty, target.2, target.3]);
+        params[8..12].copy_from_slice(&[
+            width as f32,
+            height as f32,
+            offset.0 as f32,
+            offset.1 as f32,
+        ]);
+        params[12] = run.opacity.clamp(0.0, 1.0);
+        if t.is_identity() || t.is_translate() {
+            params[13] = 1.0;
+        }
+        params[16..20].copy_from_slice(&premultiplied(paint.shadow.map(|(color, ..)| color)));
+        params[20..24].copy_from_slice(&premultiplied(paint.outline.map(|(color, _)| color)));
+        params[24..28].copy_from_slice(&premultiplied(paint.fill));
+
+        let buffer = self.uniform(&params);
+        Ok(self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("slides"),
+            layout: &self.text_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&shadow),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&outline),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&fill),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        }))
+    }
+
+    /// `mask` blurred by `radius` pixels as `render::blur` does, or grown by
+    /// it as `render::dilate` does, one axis a pass
+    fn filter(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        mask: &wgpu::TextureView,
+        size: (u32, u32),
+        radius: f32,
+        grow: bool,
+    ) -> wgpu::TextureView {
+        let radius = radius.round();
+        if radius < 1.0 {
+            return mask.clone();
+        }
+        let rounds = if grow { 1 } else { 3 };
+        let scratch = [
+            self.mask_texture(size.0, size.1),
+            self.mask_texture(size.0, size.1),
+        ];
+        let mut source = mask.clone();
+        for pass_index in 0..rounds * 2 {
+            let along = if pass_index % 2 == 0 {
+                (1.0, 0.0)
+            } else {
+                (0.0, 1.0)
+            };
+            let bind_group = self.mask_bind_group(
+                &[along.0, along.1, radius, if grow { 1.0 } else { 0.0 }],
+                &source,
+            );
+            let target = &scratch[pass_index % 2];
+            {
+                let mut pass = begin_pass(encoder, target);
+                pass.set_pipeline(&self.filter_pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+            source = target.clone();
+        }
+        source
+    }
+
+    /// A mask a text layer is drawn into, blurred or grown
+    fn mask_texture(&self, width: u32, height: u32) -> wgpu::TextureView {
+        create_texture(
+            &self.device,
+            width,
+            height,
+            MASK_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        )
+        .create_view(&Default::default())
+    }
+
+    /// The background image `image` on the GPU, uploaded unless it's there
+    fn image(&mut self, key: &str, image: &Arc<Pixmap>) -> wgpu::TextureView {
+        if let Some(cached) = self.images.get(key) {
+            if Arc::ptr_eq(&cached.image, image) {
+                return cached.view.clone();
+            }
+        }
+        if self.images.len() >= MAX_CACHED_IMAGES {
+            self.images.clear();
+        }
+        let view = upload(
+            &self.device,
+            &self.queue,
+            image.data(),
+            image.width(),
+            image.height(),
+        );
+        self.images.insert(
+            key.to_string(),
+            CachedImage {
+                image: image.clone(),
+                view: view.clone(),
+            },
+        );
+        view
+    }
+
+    fn uniform(&self, values: &[f32]) -> wgpu::Buffer {
+        let contents: Vec<u8> = values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        wgpu::util::DeviceExt::create_buffer_init(
+            &self.device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("slides"),
+                contents: &contents,
+                usage: wgpu::BufferUsages::UNIFORM,
+            },
+        )
+    }
+
+    fn bind_group(&self, params: &[f32], view: &wgpu::TextureView) -> wgpu::BindGroup {
+        let buffer = self.uniform(params);
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("slides"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+
+    fn mask_bind_group(&self, params: &[f32], view: &wgpu::TextureView) -> wgpu::BindGroup {
+        let buffer = self.uniform(params);
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("slides"),
+            layout: &self.mask_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+            ],
+        })
+    }
+
+    /// Copy `target` back from the GPU into a pixmap
+    fn read_back(
+        &self,
+        mut encoder: wgpu::CommandEncoder,
+        target: &wgpu::Texture,
+        width: u32,
+        height: u32,
+    ) -> Result<Pixmap, GpuError> {
+        // Rows copied out of a texture are padded to 256 bytes
+        let row = width * 4;
+        let padded =
+            row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("slides"),
+            size: u64::from(padded) * u64::from(height),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: target,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded),
+                    rows_per_image: None,
+                },
+            },
+            extent(width, height),
+        );
+        self.queue.submit([encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |mapped| {
+            let _ = sender.send(mapped);
+        });
+        self.device
+            .poll(wgpu::PollType::Wait)
+            .map_err(|e| GpuError::Failed(e.to_string()))?;
+        receiver
+            .recv()
+            .map_err(|e| GpuError::Failed(e.to_string()))?
+            .map_err(|e| GpuError::Failed(e.to_string()))?;
+
+        let mut data = Vec::with_capacity(row as usize * height as usize);
+        {
+            let mapped = slice.get_mapped_range();
+            for line in mapped.chunks_exact(padded as usize) {
+                data.extend_from_slice(&line[..row as usize]);
+            }
+        }
+        buffer.unmap();
+        let size = IntSize::from_wh(width, height).ok_or(GpuError::TooLarge(width, height))?;
+        Pixmap::from_vec(data, size).ok_or(GpuError::TooLarge(width, height))
+    }
+}
+
+/// A texture holding premultiplied RGBA `data`
+fn upload(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    data: &[u8],
+    width: u32,
+    height: u32,
+) -> wgpu::TextureView {
+    let texture = create_texture(
+        device,
+        width,
+        height,
+        FORMAT,
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    );
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        data,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(width * 4),
+            rows_per_image: None,
+        },
+        extent(width, height),
+    );
+    texture.create_view(&Default::default())
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("slides"),
+        size: extent(width, height),
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage,
+        view_formats: &[],
+    })
+}
+
+/// A uniform, then `textures` textures and, when `sampled`, a sampler
+fn create_layout(device: &wgpu::Device, textures: u32, sampled: bool) -> wgpu::BindGroupLayout {
+    let mut entries = vec![wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }];
+    entries.extend((1..=textures).map(|binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }));
+    if sampled {
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: textures + 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        });
+    }
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("slides"),
+        entries: &entries,
+    })
+}
+
+/// A pipeline drawing triangles with `vs_main` and `fs_main` of `source`
+fn create_pipeline(
+    device: &wgpu::Device,
+    source: &'static str,
+    layout: &wgpu::BindGroupLayout,
+    buffers: &[wgpu::VertexBufferLayout],
+    format: wgpu::TextureFormat,
+    blend: wgpu::BlendState,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("slides"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source)),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("slides"),
+        bind_group_layouts: &[layout],
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("slides"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            compilation_options: Default::default(),
+            buffers,
+        },
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+        cache: None,
+    })
+}
+
+/// A pass drawing into `view`, cleared to transparency
+fn begin_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("slides"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    })
+}
+
+fn extent(width: u32, height: u32) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    }
+}
+
+/// x, y, width and height in pixels
+type Rect4 = (f32, f32, f32, f32);
+
+/// Shader parameters, laid out as `Params`
+fn params(rect: Rect4, target: Rect4, opacity: f32, texture: bool) -> [f32; 56] {
+    let mut params = [0.0; 56];
+    params[..4].copy_from_slice(&[rect.0, rect.1, rect.2, rect.3]);
+    params[4..8].copy_from_slice(&[target.2, target.3, opacity.clamp(0.0, 1.0), 0.0]);
+    if texture {
+        params[7] = 1.0;
+    }
+    params
+}
+
+fn texture_params(rect: Rect4, target: Rect4, opacity: f32) -> [f32; 56] {
+    params(rect, target, opacity, true)
+}
+
+/// A CSS linear gradient at `angle` degrees over `rect`; one stop fills it
+fn gradient_params(rect: Rect4, target: Rect4, angle: f32, stops: &[(f32, Color)]) -> [f32; 56] {
+    let mut params = params(rect, target, 1.0, false);
+    let ((x0, y0), (x1, y1)) = render::gradient_line(angle, rect.2, rect.3);
+    // Dividing by the squared length makes the dot product run 0 to 1
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let squared = (dx * dx + dy * dy).max(f32::EPSILON);
+    params[8..12].copy_from_slice(&[rect.0 + x0, rect.1 + y0, dx / squared, dy / squared]);
+    let stops = &stops[..stops.len().min(MAX_STOPS)];
+    params[12] = stops.len() as f32;
+    for (i, (offset, color)) in stops.iter().enumerate() {
+        let at = 16 + i * 4;
+        params[at..at + 4].copy_from_slice(&[
+            color.red(),
+            color.green(),
+            color.blue(),
+            color.alpha(),
+        ]);
+        params[48 + i] = *offset;
+    }
+    params
+}
+
+/// `color` premultiplied, or clear when there's none
+fn premultiplied(color: Option<Color>) -> [f32; 4] {
+    color.map_or([0.0; 4], |color| {
+        let color = color.premultiply();
+        [color.red(), color.green(), color.blue(), color.alpha()]
+    })
+}
@@ -0,0 +1,144 @@
+//! Rolling snapshot history for .cpres bundles
+//!
+//! Before a save replaces a bundle, the previous file is kept as
+//! `<name>.cpres.bak/<unix millis>.cpres` next to it. Because `save_bundle`
+//! writes a temp file and renames it over the target, the old file can be
+//! hard-linked into the history folder instead of copied; a copy is only made
+//! when the filesystem doesn't support links.
+
+use crate::cpres::{self, CpresError};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tempfile::NamedTempFile;
+
+pub const DEFAULT_KEEP_VERSIONS: usize = 10;
+
+const HISTORY_DIR_SUFFIX: &str = ".bak";
+
+#[derive(Debug, Serialize)]
+pub struct BundleVersion {
+    pub id: String,
+    pub path: String,
+    pub saved_at_ms: u64,
+    pub byte_size: u64,
+}
+
+/// Directory holding snapshots of `bundle_path`
+pub fn history_dir(bundle_path: &Path) -> PathBuf {
+    let mut name = bundle_path
+        .file_name()
+        .map(|n| n.to_os_string())
+        .unwrap_or_default();
+    name.push(HISTORY_DIR_SUFFIX);
+    bundle_path.with_file_name(name)
+}
+
+/// Whether `dir` is the snapshot folder of some bundle
+pub fn is_history_dir(dir: &Path) -> bool {
+    dir.file_name()
+        .and_then(|n| n.to_str())
+        .and_then(|n| n.strip_suffix(HISTORY_DIR_SUFFIX))
+        .is_some_and(|bundle| {
+            Path::new(bundle)
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("cpres"))
+        })
+}
+
+/// Keep the current contents of `bundle_path` as a snapshot, then prune to `keep` versions.
+/// Does nothing when the bundle doesn't exist yet or `keep` is zero.
+pub fn snapshot(bundle_path: &Path, keep: usize) -> Result<(), CpresError> {
+    if keep == 0 || !bundle_path.is_file() {
+        return Ok(());
+    }
+
+    let dir = history_dir(bundle_path);
+    fs::create_dir_all(&dir)?;
+
+    let mut millis = unix_millis();
+    let mut target = dir.join(format!("{millis}.cpres"));
+    while target.exists() {
+        millis += 1;
+        target = dir.join(format!("{millis}.cpres"));
+    }
+
+    if fs::hard_link(bundle_path, &target).is_err() {
+        fs::copy(bundle_path, &target)?;
+    }
+
+    prune(bundle_path, keep)
+}
+
+/// List snapshots of a bundle, newest first
+pub fn list_versions(bundle_path: &Path) -> Result<Vec<BundleVersion>, CpresError> {
+    let dir = history_dir(bundle_path);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut versions = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(saved_at_ms) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .filter(|_| path.extension().and_then(|e| e.to_str()) == Some("cpres"))
+            .and_then(|s| s.parse::<u64>().ok())
+        else {
+            continue;
+        };
+
+        versions.push(BundleVersion {
+            id: saved_at_ms.to_string(),
+            path: path.to_string_lossy().to_string(),
+            saved_at_ms,
+            byte_size: entry.metadata()?.len(),
+        });
+    }
+
+    versions.sort_by_key(|v| std::cmp::Reverse(v.saved_at_ms));
+    Ok(versions)
+}
+
+/// Replace a bundle with one of its snapshots. The current file is snapshotted
+/// first so the restore itself can be undone.
+pub fn restore_version(
+    bundle_path: &Path,
+    version_id: &str,
+    keep: usize,
+) -> Result<(), CpresError> {
+    let version = list_versions(bundle_path)?
+        .into_iter()
+        .find(|v| v.id == version_id)
+        .ok_or_else(|| CpresError::MissingFile(format!("version {version_id}")))?;
+    let version_path = PathBuf::from(&version.path);
+
+    // Stage the copy before snapshotting, since pruning may remove the version itself
+    let parent = bundle_path.parent().unwrap_or(Path::new("."));
+    let temp_file = NamedTempFile::new_in(parent)?;
+    fs::copy(&version_path, temp_file.path())?;
+
+    snapshot(bundle_path, keep.max(1))?;
+
+    cpres::persist_file(temp_file, bundle_path)?;
+
+    Ok(())
+}
+
+/// Delete the oldest snapshots beyond `keep`
+fn prune(bundle_path: &Path, keep: usize) -> Result<(), CpresError> {
+    for version in list_versions(bundle_path)?.into_iter().skip(keep) {
+        fs::remove_file(&version.path)?;
+    }
+    Ok(())
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
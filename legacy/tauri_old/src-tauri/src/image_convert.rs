@@ -0,0 +1,49 @@
+//! Decoding image formats the webview can't show
+//!
+//! Phones save photos as HEIC, and AVIF shows up in downloads; not every
+//! platform's webview displays them, and the `image` crate can't decode them.
+//! ffmpeg can, so these images are decoded by having ffmpeg write the first
+//! frame as PNG. Imports convert them (see `image_optimize`) and the thumbnail
+//! service falls back to this when the `image` crate gives up.
+
+use crate::cpres::CpresError;
+use crate::ffmpeg;
+use image::{DynamicImage, ImageFormat};
+use std::io::Write;
+use std::path::Path;
+
+/// MIME types that are converted on import
+const CONVERTED_MIMES: [&str; 2] = ["image/heif", "image/avif"];
+
+pub fn needs_conversion(mime: &str) -> bool {
+    CONVERTED_MIMES.contains(&mime)
+}
+
+/// Whether `data` looks like one of the formats decoded through ffmpeg
+pub fn is_convertible(data: &[u8]) -> bool {
+    infer::get(data).is_some_and(|kind| needs_conversion(kind.mime_type()))
+}
+
+/// Decode an image file with ffmpeg
+pub fn decode_file(path: &Path) -> Result<DynamicImage, CpresError> {
+    let mut command = ffmpeg::command()?;
+    command
+        .arg("-i")
+        .arg(path)
+        .args(["-frames:v", "1", "-c:v", "png", "-f", "image2pipe", "-"]);
+    let png = ffmpeg::capture(command, "image conversion")?;
+    image::load_from_memory_with_format(&png, ImageFormat::Png)
+        .map_err(|e| CpresError::Ffmpeg(format!("unreadable conversion output: {e}")))
+}
+
+/// Decode image bytes with ffmpeg. HEIF is read through ffmpeg's MP4 reader,
+/// which needs to seek, so the bytes go through a temporary file.
+pub fn decode_bytes(data: &[u8]) -> Result<DynamicImage, CpresError> {
+    let extension = infer::get(data).map_or("bin", |kind| kind.extension());
+    let mut temp_file = tempfile::Builder::new()
+        .suffix(&format!(".{extension}"))
+        .tempfile()?;
+    temp_file.write_all(data)?;
+    temp_file.flush()?;
+    decode_file(temp_file.path())
+}
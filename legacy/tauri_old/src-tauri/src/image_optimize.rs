@@ -0,0 +1,281 @@
+//! Converting and shrinking images on import
+//!
+//! HEIC and AVIF images are always converted, since the webview may not show
+//! them. A 45-megapixel camera photo used as a background makes every copy of
+//! the bundle 20 MB bigger for no visible gain on a 4K screen, so when the
+//! import asks for it, images larger than `max_dimension` are scaled down too.
+//! Either way the image is re-encoded as JPEG when it's opaque and as
+//! (lossless) WebP when it has transparency. The result is written to a folder
+//! in the app cache, named after the original's hash, and imported in place of
+//! the original; the original can be kept in the bundle too.
+//!
+//! Animated GIFs are imported as looping video clips unless the import turns
+//! that off; see `gif_video`. When ffmpeg isn't available the GIF is imported
+//! as it is.
+
+use crate::cpres::{self, CpresError, MediaKind};
+use crate::gif_video::{self, GifConversion};
+use crate::image_convert;
+use crate::tasks::CancelToken;
+use crate::thumbnails;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ExtendedColorType, ImageEncoder};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub(crate) const OPTIMIZED_DIR_NAME: &str = "optimized-media";
+
+/// Longest edge kept by default: a 4K screen's width
+pub const DEFAULT_MAX_DIMENSION: u32 = 3840;
+const MIN_MAX_DIMENSION: u32 = 640;
+const JPEG_QUALITY: u8 = 90;
+
+/// Formats the `image` crate can read that are worth scaling down; GIFs are
+/// left alone so animations survive, and animated ones become videos
+const OPTIMIZABLE_MIMES: [&str; 3] = ["image/jpeg", "image/png", "image/webp"];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptimizedFormat {
+    /// JPEG for opaque images, WebP for images with transparency
+    #[default]
+    Auto,
+    Jpeg,
+    Webp,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ImageOptimizeOptions {
+    pub max_dimension: Option<u32>,
+    pub format: OptimizedFormat,
+    /// Also store the original in the bundle, under `media/originals/`
+    pub keep_original: bool,
+    /// What animated GIFs are imported as
+    pub animated_gifs: GifConversion,
+}
+
+/// How an imported image was converted or scaled down
+#[derive(Debug, Clone, Serialize)]
+pub struct OptimizedImport {
+    /// The new file: copy this into the bundle instead of the file picked
+    pub source_path: String,
+    pub original_mime: String,
+    pub original_width: u32,
+    pub original_height: u32,
+    pub original_byte_size: u64,
+    /// Bundle path for the original, when it's kept
+    pub original_path: Option<String>,
+}
+
+/// A converted or scaled-down copy of an image
+pub struct Optimized {
+    pub path: PathBuf,
+    pub kind: MediaKind,
+    pub original_width: u32,
+    pub original_height: u32,
+}
+
+/// Converts and optimizes images into a folder under the app cache
+pub struct ImageOptimizer {
+    /// None to only convert formats the webview can't show
+    options: Option<ImageOptimizeOptions>,
+    dir: PathBuf,
+}
+
+impl ImageOptimizer {
+    pub fn new(app_cache_dir: &Path, options: Option<ImageOptimizeOptions>) -> Self {
+        Self {
+            options,
+            dir: app_cache_dir.join(OPTIMIZED_DIR_NAME),
+        }
+    }
+
+    pub fn keep_original(&self) -> bool {
+        self.options
+            .as_ref()
+            .is_some_and(|options| options.keep_original)
+    }
+
+    /// A converted or smaller copy of the image at `path`, or None when it's
+    /// usable as it is: small enough already, undecodable, or not worth
+    /// re-encoding
+    pub fn optimize(
+        &self,
+        path: &Path,
+        mime: &str,
+        sha256: &str,
+        byte_size: u64,
+        cancel: &CancelToken,
+    ) -> Result<Option<Optimized>, CpresError> {
+        if mime == "image/gif" {
+            return self.convert_gif(path, sha256, cancel);
+        }
+        let convert = image_convert::needs_conversion(mime);
+        let max_dimension = self.options.as_ref().map(|options| {
+            options
+                .max_dimension
+                .unwrap_or(DEFAULT_MAX_DIMENSION)
+                .max(MIN_MAX_DIMENSION)
+        });
+        if !convert {
+            let Some(max_dimension) = max_dimension else {
+                return Ok(None);
+            };
+            if !OPTIMIZABLE_MIMES.contains(&mime) {
+                return Ok(None);
+            }
+            // The header is enough to rule out images that are small already
+            let Ok((width, height)) = image::image_dimensions(path) else {
+                return Ok(None);
+            };
+            if width.max(height) <= max_dimension {
+                return Ok(None);
+            }
+        }
+
+        cancel.check()?;
+        let decoded = if convert {
+            image_convert::decode_file(path)
+        } else {
+            thumbnails::decode(&std::fs::read(path)?)
+        };
+        let image = match decoded {
+            Ok(image) => image,
+            // Import the original rather than failing; it still works where
+            // the webview can show it
+            Err(e) => {
+                log::warn!("Not converting {}: {e}", path.display());
+                return Ok(None);
+            }
+        };
+        cancel.check()?;
+        let (width, height) = (image.width(), image.height());
+        let scaled = match max_dimension {
+            Some(max_dimension) if width.max(height) > max_dimension => {
+                image.resize(max_dimension, max_dimension, FilterType::CatmullRom)
+            }
+            _ => image,
+        };
+        let (extension, data) = self.encode(&scaled)?;
+        if !convert && data.len() as u64 >= byte_size {
+            return Ok(None);
+        }
+
+        // Named after the original, so importing it again reuses the copy
+        let size = max_dimension.map_or("full".to_string(), |max| max.to_string());
+        let output = self.dir.join(format!("{sha256}-{size}.{extension}"));
+        if !output.is_file() {
+            crate::export::write_atomic(&output, &data)?;
+        }
+        let (mime, media_type) = cpres::media_kind(extension);
+        Ok(Some(Optimized {
+            path: output,
+            kind: MediaKind {
+                mime,
+                media_type,
+                extension: extension.to_string(),
+            },
+            original_width: width,
+            original_height: height,
+        }))
+    }
+
+    /// A looping video clip of the GIF at `path`, or None when it's a still
+    /// image, conversion is off, or ffmpeg fails
+    fn convert_gif(
+        &self,
+        path: &Path,
+        sha256: &str,
+        cancel: &CancelToken,
+    ) -> Result<Option<Optimized>, CpresError> {
+        let conversion = self
+            .options
+            .as_ref()
+            .map(|options| options.animated_gifs)
+            .unwrap_or_default();
+        if conversion == GifConversion::Off {
+            return Ok(None);
+        }
+        let Some(gif) = gif_video::inspect(path) else {
+            return Ok(None);
+        };
+        let extension = match conversion {
+            GifConversion::Auto if gif.transparent => "webm",
+            GifConversion::Webm => "webm",
+            _ => "mp4",
+        };
+
+        cancel.check()?;
+        let output = self.dir.join(format!("{sha256}-loop.{extension}"));
+        if !output.is_file() {
+            std::fs::create_dir_all(&self.dir)?;
+            let temp_file = tempfile::Builder::new()
+                .suffix(&format!(".{extension}"))
+                .tempfile_in(&self.dir)?;
+            match gif_video::to_video(path, temp_file.path(), cancel) {
+                Ok(()) => cpres::persist_file(temp_file, &output)?,
+                Err(CpresError::Cancelled) => return Err(CpresError::Cancelled),
+                // The GIF still plays, just not as smoothly
+                Err(e) => {
+                    log::warn!("Importing {} as a GIF: {e}", path.display());
+                    return Ok(None);
+                }
+            }
+        }
+        let (mime, media_type) = cpres::media_kind(extension);
+        Ok(Some(Optimized {
+            path: output,
+            kind: MediaKind {
+                mime,
+                media_type,
+                extension: extension.to_string(),
+            },
+            original_width: gif.width,
+            original_height: gif.height,
+        }))
+    }
+
+    fn encode(&self, image: &DynamicImage) -> Result<(&'static str, Vec<u8>), CpresError> {
+        let encode_error = |e: image::ImageError| CpresError::InvalidBundle(e.to_string());
+        let has_alpha = image.color().has_alpha();
+        let format = self
+            .options
+            .as_ref()
+            .map(|options| options.format)
+            .unwrap_or_default();
+        let webp = match format {
+            OptimizedFormat::Auto => has_alpha,
+            OptimizedFormat::Webp => true,
+            OptimizedFormat::Jpeg => false,
+        };
+        let mut data = Vec::new();
+        if webp {
+            if has_alpha {
+                let rgba = image.to_rgba8();
+                WebPEncoder::new_lossless(&mut data)
+                    .write_image(&rgba, rgba.width(), rgba.height(), ExtendedColorType::Rgba8)
+                    .map_err(encode_error)?;
+            } else {
+                let rgb = image.to_rgb8();
+                WebPEncoder::new_lossless(&mut data)
+                    .write_image(&rgb, rgb.width(), rgb.height(), ExtendedColorType::Rgb8)
+                    .map_err(encode_error)?;
+            }
+            return Ok(("webp", data));
+        }
+        // JPEG has no alpha channel; transparent areas come out black
+        let rgb = image.to_rgb8();
+        JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY)
+            .write_image(&rgb, rgb.width(), rgb.height(), ExtendedColorType::Rgb8)
+            .map_err(encode_error)?;
+        Ok(("jpg", data))
+    }
+}
+
+/// Bundle path for a kept original: `media/originals/<id>.<ext>`
+pub fn original_bundle_path(id: &str, extension: &str) -> String {
+    format!("media/originals/{}.{}", &id[..8], extension)
+}
@@ -0,0 +1,817 @@
+//! Shared conversion from foreign presentation formats to a bundle
+//!
+//! Format parsers (ProPresenter, OpenLP, ...) only extract slide text, group
+//! labels, notes, and media paths into an [`ImportedPresentation`]. This module
+//! turns that into the manifest/slides/arrangement JSON the editor expects,
+//! with media referenced by source path so the first save copies it into the
+//! bundle. Themes are left empty; the editor applies its default theme.
+//!
+//! Container formats (e.g. .pptx) extract embedded media into a staging folder
+//! under the app cache first, since the bundle is only written on save. Song
+//! databases instead write one .cpres per song into a library folder.
+
+use crate::cpres::{self, BundleState, CpresError, MediaFileRef};
+use crate::tasks::CancelToken;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const STAGING_DIR_NAME: &str = "imports";
+const FORMAT_VERSION: &str = "1.0.0";
+const DEFAULT_SLIDE_SIZE: (u32, u32) = (1920, 1080);
+/// Matches `defaultLayerTransform` in the frontend for a 1920x1080 slide
+const TEXT_MARGIN_X: f64 = 96.0;
+const TEXT_MARGIN_Y: f64 = 108.0;
+/// First words of a lyric line that starts a new section
+const SECTION_WORDS: [&str; 12] = [
+    "verse",
+    "chorus",
+    "pre-chorus",
+    "prechorus",
+    "bridge",
+    "tag",
+    "intro",
+    "outro",
+    "ending",
+    "refrain",
+    "interlude",
+    "vamp",
+];
+
+#[derive(Debug, Default)]
+pub struct ImportedPresentation {
+    pub title: String,
+    pub author: Option<String>,
+    /// Pixel size of the source slides, when the format records it
+    pub slide_size: Option<(u32, u32)>,
+    pub slides: Vec<ImportedSlide>,
+    /// Playback order as indices into `slides`; slide order when None.
+    /// Indices may repeat (e.g. a chorus sung twice).
+    pub order: Option<Vec<usize>>,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportedSlide {
+    /// Group name such as "Verse 1" or "Chorus"
+    pub label: Option<String>,
+    /// One entry per text box, top to bottom
+    pub text: Vec<String>,
+    pub notes: Option<String>,
+    /// Absolute path of a background image or video
+    pub background: Option<PathBuf>,
+    /// Text boxes and pictures with their own position, back to front
+    pub placed: Vec<PlacedItem>,
+}
+
+/// Slide content positioned in slide pixels
+#[derive(Debug)]
+pub struct PlacedItem {
+    pub content: PlacedContent,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug)]
+pub enum PlacedContent {
+    Text(String),
+    /// Absolute path of an image or video
+    Media(PathBuf),
+}
+
+/// A lyric section such as "Verse 1", already split into slides
+#[derive(Debug)]
+pub struct LyricSection {
+    pub label: String,
+    pub slides: Vec<String>,
+}
+
+/// A song written into a library folder
+#[derive(Debug, Serialize)]
+pub struct SavedSong {
+    pub title: String,
+    pub path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedSong {
+    pub title: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct LibraryImport {
+    pub imported: Vec<SavedSong>,
+    pub skipped: Vec<SkippedSong>,
+}
+
+impl LibraryImport {
+    /// Save `presentation` into `dir`, recording a failure instead of aborting the batch
+    pub fn save(&mut self, dir: &Path, presentation: ImportedPresentation) {
+        let title = presentation.title.clone();
+        match save_to_library(dir, presentation) {
+            Ok(path) => self.imported.push(SavedSong {
+                title,
+                path: path.to_string_lossy().to_string(),
+            }),
+            Err(e) => self.skip(title, e.to_string()),
+        }
+    }
+
+    pub fn skip(&mut self, title: String, reason: String) {
+        log::warn!("Skipped song \"{title}\": {reason}");
+        self.skipped.push(SkippedSong { title, reason });
+    }
+}
+
+/// A song presentation with one slide per section slide. `order` lists
+/// section indices in singing order and may repeat sections.
+pub fn song_presentation(
+    title: String,
+    author: Option<String>,
+    sections: Vec<LyricSection>,
+    order: Option<Vec<usize>>,
+) -> ImportedPresentation {
+    let mut slides = Vec::new();
+    let mut section_slides = Vec::with_capacity(sections.len());
+    for section in sections {
+        let start = slides.len();
+        for text in section.slides {
+            slides.push(ImportedSlide {
+                label: Some(section.label.clone()),
+                text: vec![text],
+                ..Default::default()
+            });
+        }
+        section_slides.push(start..slides.len());
+    }
+
+    let order = order
+        .map(|order| {
+            order
+                .into_iter()
+                .filter_map(|i| section_slides.get(i).cloned())
+                .flatten()
+                .collect::<Vec<usize>>()
+        })
+        .filter(|order| !order.is_empty());
+
+    ImportedPresentation {
+        title,
+        author,
+        slides,
+        order,
+        ..Default::default()
+    }
+}
+
+/// Split lyric text into slides at blank lines
+pub fn split_slides(text: &str) -> Vec<String> {
+    let mut slides = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if !line.is_empty() {
+            current.push(line);
+        } else if !current.is_empty() {
+            slides.push(current.join("\n"));
+            current.clear();
+        }
+    }
+    if !current.is_empty() {
+        slides.push(current.join("\n"));
+    }
+    slides
+}
+
+/// Display label for a short section tag as used by OpenLP, OpenSong, and
+/// OpenLyrics: "v1" -> "Verse 1", "C" -> "Chorus", "p2" -> "Pre-Chorus 2"
+pub fn label_for_tag(tag: &str) -> String {
+    let tag = tag.trim();
+    let split = tag.find(|c: char| c.is_ascii_digit()).unwrap_or(tag.len());
+    let (kind, number) = tag.split_at(split);
+    let name = match kind.to_lowercase().as_str() {
+        "v" => "Verse",
+        "c" => "Chorus",
+        "b" => "Bridge",
+        "p" => "Pre-Chorus",
+        "t" => "Tag",
+        "i" => "Intro",
+        "e" => "Ending",
+        "o" => "Outro",
+        _ => return tag.to_string(),
+    };
+    if number.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name} {number}")
+    }
+}
+
+/// The section label when a lyric line is a header such as "Verse 1",
+/// "Chorus:" or "Pre-Chorus 2"
+pub fn section_header(line: &str) -> Option<String> {
+    let label = line.trim().trim_end_matches(':').trim_end();
+    let lower = label.to_lowercase();
+    let mut words = lower.split_whitespace();
+    let first = words.next()?;
+    let rest: Vec<&str> = words.collect();
+    let is_header = SECTION_WORDS.contains(&first)
+        && rest.len() <= 1
+        && rest
+            .iter()
+            .all(|word| word.chars().all(|c| c.is_ascii_digit()));
+    is_header.then(|| label.to_string())
+}
+
+/// Save an imported presentation as `<title>_<id>.cpres` in `dir`, named the
+/// way the editor names new presentations
+pub fn save_to_library(
+    dir: &Path,
+    presentation: ImportedPresentation,
+) -> Result<PathBuf, CpresError> {
+    let title = presentation.title.clone();
+    let state = to_bundle_state(presentation)?;
+    let manifest: Value = serde_json::from_str(&state.manifest)?;
+    let id = manifest["presentationId"].as_str().unwrap_or_default();
+
+    let sanitized: String = title
+        .chars()
+        .filter(|c| !matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*'))
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("_")
+        .chars()
+        .take(50)
+        .collect();
+    let path = dir.join(format!("{sanitized}_{}.cpres", &id[..8.min(id.len())]));
+
+    std::fs::create_dir_all(dir)?;
+    cpres::save_bundle(&path, &state)?;
+    Ok(path)
+}
+
+/// Extract plain text from an RTF document.
+///
+/// Handles paragraph/line breaks, `\'hh` and `\uN` escapes, and skips font,
+/// color, and other destination groups. Formatting is discarded.
+pub fn rtf_to_text(rtf: &[u8]) -> String {
+    const SKIP_DESTINATIONS: [&str; 9] = [
+        "fonttbl",
+        "colortbl",
+        "expandedcolortbl",
+        "stylesheet",
+        "info",
+        "pict",
+        "listtable",
+        "listoverridetable",
+        "header",
+    ];
+
+    let mut out = String::new();
+    // Per-group state: (skipping, unicode fallback length)
+    let mut stack: Vec<(bool, usize)> = vec![(false, 1)];
+    let mut pending_skip = 0usize;
+    let mut i = 0;
+
+    while i < rtf.len() {
+        let skipping = stack.last().is_some_and(|(skip, _)| *skip);
+        match rtf[i] {
+            b'{' => {
+                stack.push(stack.last().copied().unwrap_or((false, 1)));
+                i += 1;
+            }
+            b'}' => {
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+                i += 1;
+            }
+            b'\\' => {
+                i += 1;
+                let Some(&next) = rtf.get(i) else { break };
+                if next.is_ascii_alphabetic() {
+                    let start = i;
+                    while i < rtf.len() && rtf[i].is_ascii_alphabetic() {
+                        i += 1;
+                    }
+                    let word = std::str::from_utf8(&rtf[start..i]).unwrap_or_default();
+                    let num_start = i;
+                    if i < rtf.len() && (rtf[i] == b'-' || rtf[i].is_ascii_digit()) {
+                        i += 1;
+                        while i < rtf.len() && rtf[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                    let param: Option<i32> = std::str::from_utf8(&rtf[num_start..i])
+                        .ok()
+                        .and_then(|n| n.parse().ok());
+                    if rtf.get(i) == Some(&b' ') {
+                        i += 1;
+                    }
+
+                    if SKIP_DESTINATIONS.contains(&word) {
+                        if let Some(top) = stack.last_mut() {
+                            top.0 = true;
+                        }
+                        continue;
+                    }
+                    if skipping {
+                        continue;
+                    }
+                    match word {
+                        "par" | "line" => out.push('\n'),
+                        "tab" => out.push('\t'),
+                        "uc" => {
+                            if let Some(top) = stack.last_mut() {
+                                top.1 = param.unwrap_or(1).max(0) as usize;
+                            }
+                        }
+                        "u" => {
+                            if let Some(code) = param {
+                                // Negative values encode code points above 32767
+                                let code = if code < 0 { code + 65_536 } else { code } as u32;
+                                out.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                            }
+                            pending_skip = stack.last().map_or(1, |(_, uc)| *uc);
+                        }
+                        _ => {}
+                    }
+                } else {
+                    i += 1;
+                    match next {
+                        b'*' => {
+                            if let Some(top) = stack.last_mut() {
+                                top.0 = true;
+                            }
+                        }
+                        b'\'' => {
+                            let hex = rtf.get(i..i + 2).and_then(|h| std::str::from_utf8(h).ok());
+                            i += 2;
+                            if skipping {
+                                continue;
+                            }
+                            if pending_skip > 0 {
+                                pending_skip -= 1;
+                                continue;
+                            }
+                            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                                out.push(cp1252_char(byte));
+                            }
+                        }
+                        b'\\' | b'{' | b'}' if !skipping => {
+                            out.push(next as char);
+                        }
+                        b'~' if !skipping => out.push('\u{00A0}'),
+                        b'\n' | b'\r' if !skipping => out.push('\n'),
+                        _ => {}
+                    }
+                }
+            }
+            b'\r' | b'\n' => i += 1,
+            byte => {
+                i += 1;
+                if skipping {
+                    continue;
+                }
+                if pending_skip > 0 {
+                    pending_skip -= 1;
+                    continue;
+                }
+                if byte.is_ascii() {
+                    out.push(byte as char);
+                } else {
+                    out.push(cp1252_char(byte));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Windows-1252, the default RTF code page; differs from Latin-1 in 0x80-0x9F
+pub(crate) fn cp1252_char(byte: u8) -> char {
+    const HIGH: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž',
+        '\u{8F}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}',
+        'ž', 'Ÿ',
+    ];
+    match byte {
+        0x80..=0x9F => HIGH[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+    }
+}
+
+/// A new folder under the app cache for media extracted during an import
+pub fn staging_dir(app_cache_dir: &Path) -> PathBuf {
+    app_cache_dir
+        .join(STAGING_DIR_NAME)
+        .join(uuid::Uuid::new_v4().to_string())
+}
+
+/// Build a bundle state for the editor from a parsed foreign presentation
+pub fn to_bundle_state(presentation: ImportedPresentation) -> Result<BundleState, CpresError> {
+    let now = iso_now();
+    let (width, height) = presentation.slide_size.unwrap_or(DEFAULT_SLIDE_SIZE);
+
+    // Import each distinct, existing media file once
+    let mut media_ids: HashMap<PathBuf, (String, String)> = HashMap::new();
+    let mut media_entries = Vec::new();
+    let mut media_refs = Vec::new();
+    let sources = presentation.slides.iter().flat_map(|slide| {
+        slide
+            .background
+            .iter()
+            .chain(slide.placed.iter().filter_map(|item| match &item.content {
+                PlacedContent::Media(path) => Some(path),
+                PlacedContent::Text(_) => None,
+            }))
+    });
+    for source in sources {
+        if media_ids.contains_key(source) {
+            continue;
+        }
+        if !source.is_file() {
+            log::warn!("Imported media not found: {}", source.display());
+            continue;
+        }
+        let entry = cpres::import_media_file(source, None, None, &CancelToken::default())?.entry;
+        if !matches!(entry.media_type.as_str(), "image" | "video") {
+            log::warn!("Skipping unsupported media: {}", source.display());
+            continue;
+        }
+        media_ids.insert(source.clone(), (entry.id.clone(), entry.media_type.clone()));
+        media_refs.push(MediaFileRef {
+            id: entry.id.clone(),
+            source_path: source.to_string_lossy().to_string(),
+            bundle_path: entry.path.clone(),
+            trim: None,
+        });
+        media_entries.push(json!({
+            "id": entry.id,
+            "filename": entry.filename,
+            "path": entry.path,
+            "mime": entry.mime,
+            "sha256": entry.sha256,
+            "byteSize": entry.byte_size,
+            "type": entry.media_type,
+        }));
+    }
+
+    let mut slide_ids = Vec::with_capacity(presentation.slides.len());
+    let mut slides = Vec::with_capacity(presentation.slides.len());
+    let mut section_counts: HashMap<&'static str, u32> = HashMap::new();
+    let mut sections: Vec<(String, &'static str, Vec<String>)> = Vec::new();
+
+    for slide in &presentation.slides {
+        let id = uuid::Uuid::new_v4().to_string();
+        let label = slide
+            .label
+            .as_deref()
+            .map(str::trim)
+            .filter(|l| !l.is_empty());
+        let section = label.map(section_for_label);
+
+        let mut layers = text_layers(&slide.text, width, height);
+        let stacked = layers.len();
+        layers.extend(placed_layers(&slide.placed, &media_ids, stacked));
+        let has_text = layers.iter().any(|layer| layer["type"] == "text");
+        let slide_type = if has_text && section.is_some() {
+            "song"
+        } else if has_text {
+            "announcement"
+        } else if !layers.is_empty() || slide.background.is_some() {
+            "media"
+        } else {
+            "blank"
+        };
+
+        let mut value = json!({
+            "id": id,
+            "type": slide_type,
+            "layers": layers,
+            "mediaCues": [],
+            "animations": {
+                "transition": { "type": "fade", "duration": 300, "easing": "ease-out" },
+                "buildIn": [],
+                "buildOut": [],
+            },
+            "createdAt": now,
+            "updatedAt": now,
+        });
+
+        if let (Some(label), Some(section)) = (label, section) {
+            let count = section_counts.entry(section).or_insert(0);
+            value["section"] = json!(section);
+            value["sectionLabel"] = json!(label);
+            value["sectionIndex"] = json!(*count);
+            *count += 1;
+
+            match sections.iter_mut().find(|(l, _, _)| l == label) {
+                Some((_, _, ids)) => ids.push(id.clone()),
+                None => sections.push((label.to_string(), section, vec![id.clone()])),
+            }
+        }
+        if let Some(notes) = slide.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+            value["notes"] = json!(notes);
+        }
+        if let Some((media_id, media_type)) =
+            slide.background.as_ref().and_then(|p| media_ids.get(p))
+        {
+            value["mediaCues"] = json!([{
+                "id": uuid::Uuid::new_v4().to_string(),
+                "mediaId": media_id,
+                "mediaType": media_type,
+                "target": "mediaUnderlay",
+                "fit": "cover",
+                "loop": media_type == "video",
+                "muted": true,
+                "autoplay": true,
+            }]);
+        }
+
+        slide_ids.push(id);
+        slides.push(value);
+    }
+
+    let order: Vec<&String> = match &presentation.order {
+        Some(order) => order.iter().filter_map(|i| slide_ids.get(*i)).collect(),
+        None => slide_ids.iter().collect(),
+    };
+    let arrangement = json!({
+        "order": order,
+        "sections": sections
+            .into_iter()
+            .map(|(label, section, ids)| json!({
+                "section": section,
+                "label": label,
+                "slideIds": ids,
+            }))
+            .collect::<Vec<Value>>(),
+    });
+
+    let mut manifest = json!({
+        "formatVersion": FORMAT_VERSION,
+        "presentationId": uuid::Uuid::new_v4().to_string(),
+        "title": presentation.title,
+        "createdAt": now,
+        "updatedAt": now,
+        "aspectRatio": aspect_ratio(width, height),
+        "slideSize": { "width": width, "height": height },
+        "media": media_entries,
+        "fonts": [],
+    });
+    if let Some(author) = presentation.author.filter(|a| !a.trim().is_empty()) {
+        manifest["author"] = json!(author);
+    }
+
+    Ok(BundleState {
+        manifest: serde_json::to_string_pretty(&manifest)?,
+        slides: serde_json::to_string_pretty(&slides)?,
+        arrangement: serde_json::to_string_pretty(&arrangement)?,
+        themes: Vec::new(),
+        media: media_refs,
+        fonts: Vec::new(),
+    })
+}
+
+/// Text layers stacked evenly inside the slide margins
+fn text_layers(text: &[String], width: u32, height: u32) -> Vec<Value> {
+    let boxes: Vec<&String> = text.iter().filter(|t| !t.trim().is_empty()).collect();
+    if boxes.is_empty() {
+        return Vec::new();
+    }
+
+    let scale_x = width as f64 / DEFAULT_SLIDE_SIZE.0 as f64;
+    let scale_y = height as f64 / DEFAULT_SLIDE_SIZE.1 as f64;
+    let x = TEXT_MARGIN_X * scale_x;
+    let top = TEXT_MARGIN_Y * scale_y;
+    let box_width = width as f64 - 2.0 * x;
+    let box_height = (height as f64 - 2.0 * top) / boxes.len() as f64;
+
+    boxes
+        .into_iter()
+        .enumerate()
+        .map(|(i, content)| {
+            json!({
+                "id": uuid::Uuid::new_v4().to_string(),
+                "type": "text",
+                "name": format!("Text {}", i + 1),
+                "locked": false,
+                "visible": true,
+                "transform": {
+                    "x": x.round(),
+                    "y": (top + box_height * i as f64).round(),
+                    "width": box_width.round(),
+                    "height": box_height.round(),
+                    "rotation": 0,
+                    "opacity": 1,
+                },
+                "content": content.trim(),
+                "textFit": "shrink",
+            })
+        })
+        .collect()
+}
+
+/// Text and media layers at their source positions; text numbering continues after `text_count`
+fn placed_layers(
+    placed: &[PlacedItem],
+    media_ids: &HashMap<PathBuf, (String, String)>,
+    text_count: usize,
+) -> Vec<Value> {
+    let mut text_count = text_count;
+    let mut media_count = 0;
+    placed
+        .iter()
+        .filter_map(|item| {
+            let mut layer = match &item.content {
+                PlacedContent::Text(content) if !content.trim().is_empty() => {
+                    text_count += 1;
+                    json!({
+                        "type": "text",
+                        "name": format!("Text {text_count}"),
+                        "content": content.trim(),
+                        "textFit": "shrink",
+                    })
+                }
+                PlacedContent::Text(_) => return None,
+                PlacedContent::Media(path) => {
+                    let (media_id, media_type) = media_ids.get(path)?;
+                    let video = media_type == "video";
+                    media_count += 1;
+                    json!({
+                        "type": "media",
+                        "name": format!("{} {media_count}", if video { "Video" } else { "Image" }),
+                        "mediaId": media_id,
+                        "mediaType": media_type,
+                        "fit": "contain",
+                        "loop": video,
+                        "muted": true,
+                        "autoplay": true,
+                    })
+                }
+            };
+            layer["id"] = json!(uuid::Uuid::new_v4().to_string());
+            layer["locked"] = json!(false);
+            layer["visible"] = json!(true);
+            layer["transform"] = json!({
+                "x": item.x.round(),
+                "y": item.y.round(),
+                "width": item.width.round().max(1.0),
+                "height": item.height.round().max(1.0),
+                "rotation": 0,
+                "opacity": 1,
+            });
+            Some(layer)
+        })
+        .collect()
+}
+
+/// Same keyword mapping the frontend uses for imported song sections
+fn section_for_label(label: &str) -> &'static str {
+    let lower = label.to_lowercase();
+    [
+        ("title", "title"),
+        ("pre-chorus", "pre-chorus"),
+        ("prechorus", "pre-chorus"),
+        ("chorus", "chorus"),
+        ("verse", "verse"),
+        ("bridge", "bridge"),
+        ("outro", "outro"),
+        ("intro", "intro"),
+        ("tag", "tag"),
+        ("interlude", "interlude"),
+        ("ending", "ending"),
+        ("vamp", "vamp"),
+    ]
+    .into_iter()
+    .find(|(keyword, _)| lower.contains(keyword))
+    .map(|(_, section)| section)
+    .unwrap_or("custom")
+}
+
+fn aspect_ratio(width: u32, height: u32) -> &'static str {
+    let ratio = width as f64 / height.max(1) as f64;
+    if (ratio - 4.0 / 3.0).abs() < 0.02 {
+        "4:3"
+    } else if (ratio - 1.6).abs() < 0.02 {
+        "16:10"
+    } else {
+        "16:9"
+    }
+}
+
+/// Current time as an ISO 8601 UTC timestamp, matching `new Date().toISOString()`
+pub(crate) fn iso_now() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let secs = millis / 1000;
+    let (hour, minute, second) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
+
+    // Civil date from days since epoch (Howard Hinnant's algorithm)
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}.{:03}Z",
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rtf_to_text_decodes_escapes() {
+        let cases: [(&str, &str); 10] = [
+            (r"{\rtf1\ansi Hello\par World}", "Hello\nWorld"),
+            (r"{\rtf1 Line\line two\tab end}", "Line\ntwo\tend"),
+            // The fallback after \uN is skipped, one character by default
+            (r"{\rtf1 It\u8217?s}", "It\u{2019}s"),
+            (r"{\rtf1 \uc2\u8220 ab x}", "\u{201C} x"),
+            (r"{\rtf1 \u8217\'92s}", "\u{2019}s"),
+            (r"{\rtf1 \uc0\u233 e}", "\u{E9}e"),
+            // Negative values stand for code points above 32767
+            (r"{\rtf1 \u-3913?}", "\u{F0B7}"),
+            (
+                r"{\rtf1 caf\'e9 \'93quoted\'94}",
+                "caf\u{E9} \u{201C}quoted\u{201D}",
+            ),
+            (r"{\rtf1 \{braces\} and\~space}", "{braces} and\u{A0}space"),
+            (
+                r"{\rtf1{\fonttbl{\f0 Arial;}}{\colortbl;\red0;}{\*\generator Test;}\f0 Text}",
+                "Text",
+            ),
+        ];
+        for (rtf, expected) in cases {
+            assert_eq!(rtf_to_text(rtf.as_bytes()), expected, "{rtf}");
+        }
+    }
+
+    #[test]
+    fn split_slides_at_blank_lines() {
+        let cases: [(&str, &[&str]); 5] = [
+            ("", &[]),
+            ("one\ntwo", &["one\ntwo"]),
+            ("one\ntwo\n\nthree", &["one\ntwo", "three"]),
+            ("\n\n  one  \n\n\n\t\ntwo\n", &["one", "two"]),
+            ("one\r\n\r\ntwo\r\n", &["one", "two"]),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(split_slides(text), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn section_header_finds_labels() {
+        let cases = [
+            ("Verse 1", Some("Verse 1")),
+            ("Chorus:", Some("Chorus")),
+            ("  pre-chorus 2 : ", Some("pre-chorus 2")),
+            ("BRIDGE", Some("BRIDGE")),
+            ("Refrain 12", Some("Refrain 12")),
+            ("Verse one", None),
+            ("Verse 1 2", None),
+            ("Chorus of angels", None),
+            ("Amazing grace", None),
+            ("", None),
+            (":", None),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(section_header(line).as_deref(), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn label_for_tag_names_sections() {
+        let cases = [
+            ("v1", "Verse 1"),
+            ("V10", "Verse 10"),
+            ("C", "Chorus"),
+            ("c2", "Chorus 2"),
+            ("p2", "Pre-Chorus 2"),
+            (" b ", "Bridge"),
+            ("e1", "Ending 1"),
+            ("x3", "x3"),
+            ("Verse", "Verse"),
+        ];
+        for (tag, expected) in cases {
+            assert_eq!(label_for_tag(tag), expected, "{tag:?}");
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Keeping the displays awake while outputs are open
+//!
+//! A projector that goes to the screensaver halfway through the sermon is
+//! worse than one that was never on, so while any output window is open the
+//! computer is told not to sleep or blank its displays for being idle:
+//! `SetThreadExecutionState` on Windows, an IOKit power assertion on macOS
+//! and a `systemd-inhibit` lock on Linux. Letting go when the last output
+//! closes hands power back to the system settings.
+//!
+//! On Linux the lock is held by a `systemd-inhibit` child that waits on its
+//! stdin, so it ends with the app even if the app doesn't get to release it.
+//! Desktops that don't follow logind's idle locks may still blank.
+
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+const REASON: &str = "Presentation outputs are showing";
+
+/// Managed state: the power assertion, while outputs are open
+#[derive(Default)]
+pub struct KeepAwake {
+    assertion: Mutex<Option<platform::Assertion>>,
+}
+
+impl KeepAwake {
+    /// Keep the displays awake, or let them sleep again
+    pub fn set(&self, awake: bool) {
+        let Ok(mut assertion) = self.assertion.lock() else {
+            return;
+        };
+        if awake == assertion.is_some() {
+            return;
+        }
+        if !awake {
+            *assertion = None;
+            log::info!("Outputs closed; the displays can sleep again");
+            return;
+        }
+        match platform::acquire(REASON) {
+            Ok(acquired) => {
+                *assertion = Some(acquired);
+                log::info!("Keeping the displays awake while outputs are open");
+            }
+            Err(e) => log::warn!("Couldn't keep the displays awake: {e}"),
+        }
+    }
+}
+
+/// Hold the assertion if any output window is showing, leaving out
+/// `closing`, which is on its way out; pre-warmed windows are hidden
+pub fn refresh(app: &AppHandle, closing: Option<&str>) {
+    let Some(keep_awake) = app.try_state::<KeepAwake>() else {
+        return;
+    };
+    let open = app.webview_windows().iter().any(|(label, window)| {
+        Some(label.as_str()) != closing
+            && (label == "output" || label.starts_with("output-"))
+            && window.is_visible().unwrap_or(false)
+    });
+    keep_awake.set(open);
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(KeepAwake::default());
+}
+
+pub fn shutdown(app: &AppHandle) {
+    if let Some(keep_awake) = app.try_state::<KeepAwake>() {
+        keep_awake.set(false);
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::sync::mpsc;
+    use std::thread::JoinHandle;
+    use windows::Win32::System::Power::{
+        SetThreadExecutionState, ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED,
+    };
+
+    /// The execution state belongs to the thread that set it, so a thread
+    /// of its own holds it until released
+    pub struct Assertion {
+        release: mpsc::Sender<()>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    pub fn acquire(_reason: &str) -> Result<Assertion, String> {
+        let (release, released) = mpsc::channel::<()>();
+        let thread = std::thread::Builder::new()
+            .name("keep-awake".to_string())
+            .spawn(move || {
+                let _ = unsafe {
+                    SetThreadExecutionState(
+                        ES_CONTINUOUS | ES_DISPLAY_REQUIRED | ES_SYSTEM_REQUIRED,
+                    )
+                };
+                let _ = released.recv();
+                let _ = unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+            })
+            .map_err(|e| e.to_string())?;
+        Ok(Assertion {
+            release,
+            thread: Some(thread),
+        })
+    }
+
+    impl Drop for Assertion {
+        fn drop(&mut self) {
+            let _ = self.release.send(());
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use objc2::rc::Retained;
+    use objc2_foundation::NSString;
+    use std::ffi::c_void;
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPMAssertionCreateWithName(
+            assertion_type: *const c_void,
+            level: u32,
+            name: *const c_void,
+            id: *mut u32,
+        ) -> i32;
+        fn IOPMAssertionRelease(id: u32) -> i32;
+    }
+
+    /// `kIOPMAssertionTypePreventUserIdleDisplaySleep`, which keeps the
+    /// system awake too
+    const PREVENT_DISPLAY_SLEEP: &str = "PreventUserIdleDisplaySleep";
+    /// `kIOPMAssertionLevelOn`
+    const LEVEL_ON: u32 = 255;
+
+    pub struct Assertion(u32);
+
+    pub fn acquire(reason: &str) -> Result<Assertion, String> {
+        // NSString is toll-free bridged to the CFString IOKit wants
+        let kind = NSString::from_str(PREVENT_DISPLAY_SLEEP);
+        let name = NSString::from_str(reason);
+        let mut id = 0;
+        let result = unsafe {
+            IOPMAssertionCreateWithName(
+                Retained::as_ptr(&kind).cast(),
+                LEVEL_ON,
+                Retained::as_ptr(&name).cast(),
+                &mut id,
+            )
+        };
+        if result != 0 {
+            return Err(format!("IOPMAssertionCreateWithName failed ({result:#x})"));
+        }
+        Ok(Assertion(id))
+    }
+
+    impl Drop for Assertion {
+        fn drop(&mut self) {
+            unsafe {
+                IOPMAssertionRelease(self.0);
+            }
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use std::process::{Child, Command, Stdio};
+    use std::time::Duration;
+
+    /// How long systemd-inhibit gets to fail, such as without logind
+    const START_GRACE: Duration = Duration::from_millis(200);
+
+    pub struct Assertion(Child);
+
+    pub fn acquire(reason: &str) -> Result<Assertion, String> {
+        // `cat` holds the lock until its stdin closes, with the app if need be
+        let mut child = Command::new("systemd-inhibit")
+            .args([
+                "--what=idle:sleep",
+                "--who=Church Presenter",
+                &format!("--why={reason}"),
+                "--mode=block",
+                "cat",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Couldn't run systemd-inhibit: {e}"))?;
+        std::thread::sleep(START_GRACE);
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(format!("systemd-inhibit exited ({status})"));
+        }
+        Ok(Assertion(child))
+    }
+
+    impl Drop for Assertion {
+        fn drop(&mut self) {
+            drop(self.0.stdin.take());
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+}
+
+#[cfg(not(any(windows, unix)))]
+mod platform {
+    pub struct Assertion;
+
+    pub fn acquire(_reason: &str) -> Result<Assertion, String> {
+        Err("Not supported on this platform".to_string())
+    }
+}
@@ -1,5 +1,90 @@
+mod audio_engine;
+mod audio_extract;
+mod audio_routing;
+mod autosave;
+mod bundle_lock;
+mod bundle_reader;
+mod cache;
+mod captions;
+mod checksums;
 mod commands;
+mod compatibility;
 mod cpres;
+mod cpserv;
+mod decklink;
+mod diagnostics;
+mod diff;
+mod download;
+mod easyworship;
+mod edge_blend;
+mod error;
+mod exploded;
+mod export;
+mod extract;
+mod ffmpeg;
+mod font_coverage;
+mod font_details;
+mod font_install;
+mod font_preview;
+mod gif_video;
+mod google_fonts;
+#[cfg(feature = "gpu")]
+mod gpu_render;
+mod history;
+mod image_convert;
+mod image_optimize;
+mod importer;
+mod keep_awake;
+#[cfg(target_os = "linux")]
+mod linux_monitors;
+mod loudness;
+mod media_download;
+mod media_library;
+mod media_probe;
+mod media_trim;
+mod media_watch;
+mod merge;
+mod missing_fonts;
+mod monitor_watch;
+mod ndi;
+mod openlyrics;
+mod output_color;
+mod output_frames;
+mod output_guard;
+mod output_health;
+mod palette;
+mod pdf_import;
+mod placeholder;
+mod pointer_confine;
+mod pptx;
+mod program;
+mod propresenter;
+mod proxy;
+mod prune;
+mod recorder;
+mod remote_server;
+mod render;
+mod screen_capture;
+mod search;
+mod search_index;
+mod smart_query;
+mod song_import;
+mod stats;
+mod stock_media;
+mod storage;
+mod svg;
+mod system_fonts;
+mod tasks;
+mod test_pattern;
+mod text_import;
+mod texture_share;
+mod theme_pack;
+mod thumbnails;
+mod transcode;
+mod video_player;
+mod video_thumbnails;
+mod virtual_output;
+mod waveform;
 
 use commands::*;
 use tauri::{Emitter, Manager};
@@ -7,6 +92,209 @@ use tauri_plugin_log::{Target, TargetKind};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let handler = tauri::generate_handler![
+        cpres_open,
+        cpres_open_url,
+        cpres_verify,
+        cpres_save,
+        cpserv_open,
+        cpserv_save,
+        import_pro_presenter,
+        import_pptx,
+        import_pdf,
+        import_openlp,
+        import_opensong,
+        import_openlyrics,
+        import_easyworship,
+        import_easyworship_schedule,
+        import_text,
+        export_pdf,
+        export_images,
+        export_video,
+        export_openlyrics,
+        cancel_task,
+        cpres_storage_status,
+        cpres_lock_status,
+        cpres_acquire_lock,
+        cpres_release_lock,
+        cpres_save_template,
+        cpres_stats,
+        cpres_merge,
+        cpres_diff,
+        cpres_prune_media,
+        cpres_extract_media,
+        cpres_explode,
+        cpres_implode,
+        bundle_search,
+        bundle_replace,
+        library_search,
+        library_replace,
+        search_library,
+        cpres_export_theme_pack,
+        cpres_import_theme_pack,
+        cpres_list_versions,
+        cpres_restore_version,
+        cpres_autosave_update,
+        cpres_autosave_configure,
+        cpres_list_recovery,
+        cpres_restore_recovery,
+        cpres_discard_recovery,
+        cpres_read_media,
+        cpres_import_media,
+        cpres_import_fonts,
+        cpres_list_system_fonts,
+        get_font_details,
+        refresh_fonts,
+        google_fonts_has_key,
+        set_google_fonts_key,
+        search_google_fonts,
+        list_google_fonts,
+        download_google_font,
+        remove_google_font,
+        check_font_coverage,
+        render_font_preview,
+        cpres_compatibility_report,
+        cpres_missing_fonts,
+        cpres_install_fonts,
+        get_machine_profile,
+        get_app_data_dir,
+        get_documents_data_dir,
+        set_content_dir,
+        is_content_dir_under_repo,
+        ensure_app_data_dir,
+        ensure_documents_data_dir,
+        ensure_app_data_subdir,
+        ensure_documents_data_subdir,
+        read_app_data_file,
+        read_documents_data_file,
+        write_app_data_file,
+        write_documents_data_file,
+        allow_media_library_dir,
+        media_library_scan,
+        media_library_query,
+        media_library_folders,
+        media_library_add_folder,
+        media_library_remove_folder,
+        find_duplicate_media,
+        consolidate_duplicate_media,
+        generate_thumbnail,
+        rasterize_svg,
+        extract_palette,
+        probe_video_thumbnails,
+        generate_waveform,
+        generate_proxy,
+        resolve_media_source,
+        get_cache_stats,
+        clear_cache,
+        set_cache_limit,
+        transcode_media,
+        transcode_jobs,
+        cancel_transcode,
+        load_captions,
+        sync_captions,
+        stop_captions,
+        extract_audio,
+        download_media,
+        media_downloads,
+        cancel_media_download,
+        resume_media_download,
+        stock_media_providers,
+        set_stock_media_key,
+        search_stock_media,
+        download_stock_media,
+        media_library_tags,
+        media_library_set_tags,
+        media_library_collections,
+        media_library_create_collection,
+        media_library_rename_collection,
+        media_library_delete_collection,
+        media_library_add_to_collection,
+        media_library_remove_from_collection,
+        tag_media,
+        rate_media,
+        query_media,
+        media_library_smart_collections,
+        media_library_create_smart_collection,
+        media_library_update_smart_collection,
+        media_library_delete_smart_collection,
+        open_output_windows,
+        close_output_windows,
+        set_output_prewarm,
+        capture_output,
+        start_recording,
+        pause_recording,
+        resume_recording,
+        stop_recording,
+        get_recording_status,
+        set_output_auto_reopen,
+        set_output_guard,
+        report_output_heartbeat,
+        set_output_pointer_confined,
+        set_output_windowed,
+        set_output_transforms,
+        get_output_mirror,
+        get_output_transform,
+        set_output_span,
+        get_output_span,
+        get_output_colors,
+        set_output_color,
+        get_output_color,
+        show_output_test_pattern,
+        identify_outputs,
+        clear_output_overlay,
+        get_program_state,
+        get_program_presentation,
+        set_live_state,
+        set_program_presentation,
+        set_program_screen,
+        set_program_logo,
+        read_program_logo,
+        start_ndi_output,
+        stop_ndi_output,
+        get_ndi_status,
+        set_ndi_slide,
+        enable_texture_share,
+        disable_texture_share,
+        list_texture_shares,
+        set_texture_share_slide,
+        list_decklink_devices,
+        list_decklink_formats,
+        start_decklink_output,
+        stop_decklink_output,
+        list_decklink_playouts,
+        set_decklink_slide,
+        start_virtual_output,
+        stop_virtual_output,
+        get_virtual_output_status,
+        load_video,
+        play_video,
+        pause_video,
+        seek_video,
+        set_video_loop,
+        unload_video,
+        get_video_status,
+        load_audio_playlist,
+        play_audio,
+        pause_audio,
+        stop_audio,
+        skip_audio,
+        seek_audio,
+        set_audio_volume,
+        get_audio_devices,
+        get_audio_routes,
+        set_audio_route,
+        start_remote_server,
+        stop_remote_server,
+        get_remote_server_status,
+        set_audio_device,
+        play_sound_cue,
+        stop_sound_cues,
+        get_audio_status,
+        get_monitors,
+        get_command_diagnostics,
+        clear_command_diagnostics,
+    ];
+
     tauri::Builder::default()
         .plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
             if let Some(window) = app.get_webview_window("main") {
@@ -54,32 +342,75 @@ pub fn run() {
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_window_state::Builder::new().build())
-        .invoke_handler(tauri::generate_handler![
-            cpres_open,
-            cpres_save,
-            cpres_read_media,
-            cpres_import_media,
-            cpres_import_fonts,
-            cpres_list_system_fonts,
-            get_app_data_dir,
-            get_documents_data_dir,
-            set_content_dir,
-            is_content_dir_under_repo,
-            ensure_app_data_dir,
-            ensure_documents_data_dir,
-            ensure_app_data_subdir,
-            ensure_documents_data_subdir,
-            read_app_data_file,
-            read_documents_data_file,
-            write_app_data_file,
-            write_documents_data_file,
-            allow_media_library_dir,
-            open_output_windows,
-            close_output_windows,
-            get_monitors,
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .invoke_handler(move |invoke| {
+            diagnostics::record_dispatch(
+                invoke.message.command(),
+                diagnostics::payload_size(invoke.message.payload()),
+            );
+            handler(invoke)
+        })
+        .register_asynchronous_uri_scheme_protocol(
+            video_player::VIDEO_PROTOCOL,
+            |ctx, request, responder| {
+                let players = ctx.app_handle().state::<video_player::VideoPlayers>();
+                players.serve(&request, responder);
+            },
+        )
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Destroyed = event {
+                keep_awake::refresh(window.app_handle(), Some(window.label()));
+            }
+        })
+        .setup(|app| {
+            autosave::init(app.handle())?;
+            bundle_lock::init(app.handle());
+            tasks::init(app.handle());
+            search_index::init(app.handle())?;
+            media_library::init(app.handle())?;
+            media_watch::init(app.handle());
+            transcode::init(app.handle());
+            media_download::init(app.handle());
+            captions::init(app.handle());
+            cache::init(app.handle());
+            font_install::init(app.handle());
+            system_fonts::init(app.handle());
+            monitor_watch::init(app.handle());
+            edge_blend::init(app.handle());
+            output_color::init(app.handle());
+            output_guard::init(app.handle());
+            output_health::init(app.handle());
+            pointer_confine::init(app.handle());
+            ndi::init(app.handle());
+            texture_share::init(app.handle());
+            decklink::init(app.handle());
+            virtual_output::init(app.handle());
+            video_player::init(app.handle());
+            audio_engine::init(app.handle());
+            audio_routing::init(app.handle());
+            remote_server::init(app.handle());
+            program::init(app.handle());
+            recorder::init(app.handle());
+            keep_awake::init(app.handle());
+            monitor_watch::restore(app.handle());
+            Ok(())
+        })
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                autosave::shutdown(app);
+                bundle_lock::shutdown(app);
+                font_install::shutdown(app);
+                ndi::shutdown(app);
+                texture_share::shutdown(app);
+                decklink::shutdown(app);
+                recorder::shutdown(app);
+                video_player::shutdown(app);
+                audio_engine::shutdown(app);
+                remote_server::shutdown(app);
+                keep_awake::shutdown(app);
+            }
+        });
 }
 
 #[derive(Clone, serde::Serialize)]
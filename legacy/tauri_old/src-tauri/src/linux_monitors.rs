@@ -0,0 +1,264 @@
+//! Display names and refresh rates on Linux
+//!
+//! GTK names a monitor by its connector ("HDMI-1") on X11, or by the model
+//! the compositor reports on Wayland, and gives no refresh rate. The display
+//! servers know more: `wlr-randr` lists each output's make, model, and
+//! current mode on wlroots compositors (Sway, Hyprland, and others), and
+//! `xrandr --verbose` each output's EDID and current mode on X11 (or under
+//! XWayland). When neither tool is there, the EDIDs the kernel exposes under
+//! `/sys/class/drm` still give the names. The list is kept for a few
+//! seconds, since the monitor list asks once per monitor.
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const CACHE_TTL: Duration = Duration::from_secs(5);
+
+static CACHE: Mutex<Option<(Instant, Vec<Output>)>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default)]
+struct Output {
+    /// Connector, such as "HDMI-1" or "HDMI-A-1"
+    connector: String,
+    /// Model name from the EDID or the compositor
+    product: Option<String>,
+    refresh_rate: Option<u32>,
+}
+
+/// The product name of the monitor GTK calls `device_name`
+pub fn friendly_name(device_name: &str) -> Option<String> {
+    find(device_name)?.product
+}
+
+/// The current refresh rate of the monitor GTK calls `device_name`, in Hz
+pub fn refresh_rate(device_name: &str) -> Option<u32> {
+    find(device_name)?.refresh_rate
+}
+
+fn find(device_name: &str) -> Option<Output> {
+    let outputs = outputs();
+    let wanted = normalize_connector(device_name);
+    outputs
+        .iter()
+        .find(|output| normalize_connector(&output.connector) == wanted)
+        .or_else(|| {
+            outputs.iter().find(|output| {
+                output
+                    .product
+                    .as_deref()
+                    .is_some_and(|product| product.eq_ignore_ascii_case(device_name.trim()))
+            })
+        })
+        .cloned()
+}
+
+/// "HDMI-A-1" and "card0-HDMI-A-1" (the kernel's names) and "HDMI-1" (most
+/// X drivers') all become "hdmi1"
+fn normalize_connector(name: &str) -> String {
+    let name = name.trim();
+    let name = match name.split_once('-') {
+        Some((card, rest)) if card.starts_with("card") => rest,
+        _ => name,
+    };
+    name.to_lowercase()
+        .replace("-a-", "-")
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect()
+}
+
+fn outputs() -> Vec<Output> {
+    if let Ok(cache) = CACHE.lock() {
+        if let Some((at, outputs)) = cache.as_ref() {
+            if at.elapsed() < CACHE_TTL {
+                return outputs.clone();
+            }
+        }
+    }
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let mut outputs = if wayland {
+        run("wlr-randr", &[]).map(|text| parse_wlr_randr(&text))
+    } else {
+        None
+    }
+    .filter(|outputs| !outputs.is_empty())
+    .or_else(|| run("xrandr", &["--verbose"]).map(|text| parse_xrandr(&text)))
+    .unwrap_or_default();
+    if outputs.is_empty() {
+        outputs = sysfs_outputs(Path::new("/sys/class/drm"));
+    }
+    if let Ok(mut cache) = CACHE.lock() {
+        *cache = Some((Instant::now(), outputs.clone()));
+    }
+    outputs
+}
+
+/// The tool's output, or None when it isn't installed or fails
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Outputs in `wlr-randr`'s listing:
+///
+/// ```text
+/// HDMI-A-1 "Seiko Epson Corporation EB-2250U (HDMI-A-1)"
+///   Make: Seiko Epson Corporation
+///   Model: EB-2250U
+///   Modes:
+///     1920x1200 px, 59.950001 Hz (preferred, current)
+/// ```
+fn parse_wlr_randr(text: &str) -> Vec<Output> {
+    let mut outputs: Vec<Output> = Vec::new();
+    for line in text.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            if let Some(connector) = line.split_whitespace().next() {
+                outputs.push(Output {
+                    connector: connector.to_string(),
+                    ..Output::default()
+                });
+            }
+            continue;
+        }
+        let Some(output) = outputs.last_mut() else {
+            continue;
+        };
+        let line = line.trim();
+        if let Some(model) = line.strip_prefix("Model:") {
+            let model = model.trim();
+            if !model.is_empty() && model != "Unknown" {
+                output.product = Some(model.to_string());
+            }
+        } else if line.contains("current") && line.contains(" Hz") {
+            output.refresh_rate = line
+                .split(',')
+                .nth(1)
+                .and_then(|rate| rate.split_whitespace().next())
+                .and_then(|rate| rate.parse::<f64>().ok())
+                .filter(|rate| *rate > 0.0)
+                .map(|rate| rate.round() as u32);
+        }
+    }
+    outputs
+}
+
+/// Connected outputs in `xrandr --verbose`'s listing: the EDID in hex below
+/// each output, and the current mode's refresh rate on the `v:` line after
+/// the mode marked `*current`
+fn parse_xrandr(text: &str) -> Vec<Output> {
+    let mut outputs: Vec<Output> = Vec::new();
+    let mut edid = String::new();
+    let mut in_edid = false;
+    let mut in_current_mode = false;
+    let finish = |outputs: &mut Vec<Output>, edid: &mut String| {
+        if let Some(output) = outputs.last_mut() {
+            if output.product.is_none() {
+                output.product = hex_decode(edid).and_then(|edid| edid_name(&edid));
+            }
+        }
+        edid.clear();
+    };
+    for line in text.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            finish(&mut outputs, &mut edid);
+            in_edid = false;
+            in_current_mode = false;
+            let mut words = line.split_whitespace();
+            if let (Some(connector), Some("connected")) = (words.next(), words.next()) {
+                outputs.push(Output {
+                    connector: connector.to_string(),
+                    ..Output::default()
+                });
+            } else if !line.starts_with("Screen ") {
+                // A disconnected output; its modes aren't wanted
+                outputs.push(Output::default());
+            }
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed == "EDID:" {
+            in_edid = true;
+            continue;
+        }
+        if in_edid {
+            if trimmed.chars().all(|c| c.is_ascii_hexdigit()) && !trimmed.is_empty() {
+                edid.push_str(trimmed);
+                continue;
+            }
+            in_edid = false;
+        }
+        if trimmed.contains("*current") {
+            in_current_mode = true;
+        } else if in_current_mode && trimmed.starts_with("v:") {
+            in_current_mode = false;
+            let rate = trimmed
+                .split_whitespace()
+                .skip_while(|word| *word != "clock")
+                .nth(1)
+                .and_then(|rate| rate.strip_suffix("Hz"))
+                .and_then(|rate| rate.parse::<f64>().ok());
+            if let (Some(output), Some(rate)) = (outputs.last_mut(), rate) {
+                output.refresh_rate = Some(rate.round() as u32);
+            }
+        }
+    }
+    finish(&mut outputs, &mut edid);
+    outputs.retain(|output| !output.connector.is_empty());
+    outputs
+}
+
+/// Connected connectors the kernel lists, named from their EDIDs
+fn sysfs_outputs(drm: &Path) -> Vec<Output> {
+    let Ok(entries) = std::fs::read_dir(drm) else {
+        return Vec::new();
+    };
+    let mut outputs = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let connected = std::fs::read_to_string(path.join("status"))
+            .is_ok_and(|status| status.trim() == "connected");
+        if !connected {
+            continue;
+        }
+        let connector = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        outputs.push(Output {
+            connector,
+            product: std::fs::read(path.join("edid"))
+                .ok()
+                .and_then(|edid| edid_name(&edid)),
+            refresh_rate: None,
+        });
+    }
+    outputs
+}
+
+/// The monitor name descriptor (tag 0xFC) of an EDID base block
+fn edid_name(edid: &[u8]) -> Option<String> {
+    if edid.len() < 128 {
+        return None;
+    }
+    (0..4).find_map(|i| {
+        let descriptor = &edid[54 + i * 18..72 + i * 18];
+        if descriptor[..3] != [0, 0, 0] || descriptor[3] != 0xFC {
+            return None;
+        }
+        let text = &descriptor[5..];
+        let end = text.iter().position(|b| *b == 0x0A).unwrap_or(text.len());
+        let name = String::from_utf8_lossy(&text[..end]).trim().to_string();
+        (!name.is_empty()).then_some(name)
+    })
+}
+
+fn hex_decode(text: &str) -> Option<Vec<u8>> {
+    if text.is_empty() {
+        return None;
+    }
+    hex::decode(text).ok()
+}
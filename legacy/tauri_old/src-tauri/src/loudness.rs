@@ -0,0 +1,235 @@
+//! EBU R128 loudness of audio, and evening it out between tracks
+//!
+//! Every audio import is measured with ffmpeg's `loudnorm` filter: integrated
+//! loudness (LUFS), true peak (dBTP), and loudness range (LU) end up in the
+//! media entry. When the import asks for normalization, each track is brought
+//! to a common target so a pre-service playlist doesn't jump 10 dB from one
+//! song to the next, either by
+//! - recording a playback gain in the entry, which leaves the file untouched, or
+//! - rendering a normalized AAC copy into a folder in the app cache, named
+//!   after the original's hash, which is imported in place of the original.
+//!
+//! Either way the gain is capped so the true peak stays under
+//! `max_true_peak`; a quiet track with loud transients ends up a little under
+//! the target rather than clipping.
+
+use crate::cpres::{self, CpresError};
+use crate::ffmpeg;
+use crate::tasks::CancelToken;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+pub(crate) const NORMALIZED_DIR_NAME: &str = "normalized-media";
+
+/// Loudness of streaming services and most worship backing tracks
+pub const DEFAULT_TARGET: f64 = -16.0;
+const DEFAULT_MAX_TRUE_PEAK: f64 = -1.0;
+/// Targets outside this range are clamped; EBU R128 broadcast is -23
+const TARGET_RANGE: (f64, f64) = (-30.0, -5.0);
+const NORMALIZED_BITRATE: &str = "256k";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalizeMode {
+    /// Store a playback gain; the file is imported as it is
+    #[default]
+    Gain,
+    /// Import a re-encoded copy at the target loudness
+    Render,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LoudnessOptions {
+    /// Integrated loudness to aim for, in LUFS
+    pub target: Option<f64>,
+    /// Highest true peak allowed after the gain, in dBTP
+    pub max_true_peak: Option<f64>,
+    pub mode: NormalizeMode,
+}
+
+/// A measurement; None where the track is silent
+#[derive(Debug, Clone, Copy)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS
+    pub integrated: Option<f64>,
+    /// dBTP
+    pub true_peak: Option<f64>,
+    /// LU
+    pub range: Option<f64>,
+    /// Gating threshold in LUFS, needed for the rendering pass
+    threshold: Option<f64>,
+}
+
+/// How an imported track was rendered at the target loudness
+#[derive(Debug, Clone, Serialize)]
+pub struct NormalizedImport {
+    /// The new file: copy this into the bundle instead of the file picked
+    pub source_path: String,
+    pub original_mime: String,
+    pub original_byte_size: u64,
+    pub original_loudness: f64,
+    /// Gain applied, in dB
+    pub gain: f64,
+}
+
+/// `loudnorm`'s report, printed as JSON with every number as a string
+#[derive(Deserialize)]
+struct LoudnormReport {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+}
+
+/// Measure the first audio stream of `path`; ffmpeg decodes all of it
+pub fn measure(path: &Path) -> Result<Loudness, CpresError> {
+    let output = ffmpeg::analysis_command()?
+        .arg("-i")
+        .arg(path)
+        .args(["-vn", "-sn", "-dn", "-map", "0:a:0"])
+        .args(["-af", "loudnorm=print_format=json", "-f", "null", "-"])
+        .stdin(Stdio::null())
+        .output()?;
+    let log = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(CpresError::Ffmpeg(format!(
+            "loudness analysis failed ({}): {}",
+            output.status,
+            log.trim()
+        )));
+    }
+    // The report is the last thing logged
+    let report = log
+        .rfind('{')
+        .and_then(|start| Some(&log[start..=start + log[start..].find('}')?]))
+        .and_then(|json| serde_json::from_str::<LoudnormReport>(json).ok())
+        .ok_or_else(|| CpresError::Ffmpeg("loudness analysis printed no report".to_string()))?;
+    Ok(Loudness {
+        integrated: level(&report.input_i),
+        true_peak: level(&report.input_tp),
+        range: level(&report.input_lra),
+        threshold: level(&report.input_thresh),
+    })
+}
+
+/// A level from the report; silence measures "-inf"
+fn level(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+/// A copy of a track rendered at the target loudness
+pub struct Normalized {
+    pub path: PathBuf,
+    pub original_loudness: f64,
+    /// Gain applied, in dB
+    pub gain: f64,
+}
+
+/// Normalizes imported audio, rendering copies into a folder under the app cache
+pub struct AudioNormalizer {
+    options: LoudnessOptions,
+    dir: PathBuf,
+}
+
+impl AudioNormalizer {
+    pub fn new(app_cache_dir: &Path, options: LoudnessOptions) -> Self {
+        Self {
+            options,
+            dir: app_cache_dir.join(NORMALIZED_DIR_NAME),
+        }
+    }
+
+    pub fn mode(&self) -> NormalizeMode {
+        self.options.mode
+    }
+
+    fn target(&self) -> f64 {
+        self.options
+            .target
+            .unwrap_or(DEFAULT_TARGET)
+            .clamp(TARGET_RANGE.0, TARGET_RANGE.1)
+    }
+
+    fn max_true_peak(&self) -> f64 {
+        self.options.max_true_peak.unwrap_or(DEFAULT_MAX_TRUE_PEAK)
+    }
+
+    /// Gain in dB, to a tenth, that brings a track measured at `integrated`
+    /// LUFS with peaks at `true_peak` to the target
+    pub fn gain(&self, integrated: f64, true_peak: Option<f64>) -> f64 {
+        let mut gain = self.target() - integrated;
+        if let Some(peak) = true_peak {
+            gain = gain.min(self.max_true_peak() - peak);
+        }
+        (gain * 10.0).round() / 10.0
+    }
+
+    /// A copy of the track at `path` at the target loudness, encoded as AAC;
+    /// None when it's silent or already within a decibel of the target
+    pub fn render(
+        &self,
+        path: &Path,
+        sha256: &str,
+        cancel: &CancelToken,
+    ) -> Result<Option<Normalized>, CpresError> {
+        cancel.check()?;
+        let loudness = measure(path)?;
+        let (Some(integrated), Some(true_peak), Some(range), Some(threshold)) = (
+            loudness.integrated,
+            loudness.true_peak,
+            loudness.range,
+            loudness.threshold,
+        ) else {
+            return Ok(None);
+        };
+        let gain = self.gain(integrated, Some(true_peak));
+        if gain.abs() < 1.0 {
+            return Ok(None);
+        }
+        let normalized = |path| Normalized {
+            path,
+            original_loudness: integrated,
+            gain,
+        };
+
+        // Named after the original and target, so importing it again reuses the copy
+        let output = self.dir.join(format!("{sha256}-{:.1}.m4a", self.target()));
+        if output.is_file() {
+            return Ok(Some(normalized(output)));
+        }
+        cancel.check()?;
+        std::fs::create_dir_all(&self.dir)?;
+        let temp_file = tempfile::Builder::new()
+            .suffix(".m4a")
+            .tempfile_in(&self.dir)?;
+        // With the first pass's measurements and linear=true, loudnorm applies
+        // one fixed gain instead of compressing the track. The level asked
+        // for is the one the capped gain reaches, so the peak check passes.
+        let filter = format!(
+            "loudnorm=I={:.1}:TP={:.1}:LRA=20:measured_I={integrated}:measured_TP={true_peak}\
+             :measured_LRA={range}:measured_thresh={threshold}:linear=true,aresample=48000",
+            integrated + gain,
+            self.max_true_peak(),
+        );
+        let rendered = ffmpeg::command()?
+            .arg("-i")
+            .arg(path)
+            .args(["-vn", "-sn", "-dn", "-map", "0:a:0", "-af", &filter])
+            .args(["-c:a", "aac", "-b:a", NORMALIZED_BITRATE])
+            .args(["-movflags", "+faststart", "-f", "mp4"])
+            .arg(temp_file.path())
+            .stdin(Stdio::null())
+            .output()?;
+        if !rendered.status.success() {
+            return Err(CpresError::Ffmpeg(format!(
+                "normalizing failed ({}): {}",
+                rendered.status,
+                String::from_utf8_lossy(&rendered.stderr).trim()
+            )));
+        }
+        cpres::persist_file(temp_file, &output)?;
+        Ok(Some(normalized(output)))
+    }
+}
@@ -0,0 +1,408 @@
+//! Downloading media from a link into the media library
+//!
+//! A link to a file on a church file server, or a Vimeo download link, is
+//! queued here. Downloads run one at a time through the download manager's
+//! cache, so a dropped connection resumes where it stopped instead of
+//! starting over: a download retries by itself a few times, and one that
+//! failed or was cancelled can be resumed later. When a SHA-256 was given
+//! with the link, the file is checked against it. A finished file is copied
+//! into `Downloads/` in the media library folder and indexed. Every change to
+//! a job is emitted as `media:download-progress`.
+//!
+//! Only links to the file itself work; a video's web page isn't media.
+
+use crate::cpres::{self, CpresError};
+use crate::download::{self, Credentials, DownloadError};
+use crate::extract;
+use crate::media_library::LIBRARY_FOLDER_ID;
+use crate::media_watch;
+use crate::tasks::CancelToken;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const DOWNLOAD_PROGRESS_EVENT: &str = "media:download-progress";
+
+/// Folder in the media library that downloads land in
+const DOWNLOADS_DIR_NAME: &str = "Downloads";
+/// Finished jobs still listed by `media_downloads`
+const MAX_FINISHED_JOBS: usize = 50;
+/// Tries per download before it's marked failed
+const MAX_ATTEMPTS: u32 = 4;
+/// Wait before the first retry; doubles for each one after
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadState {
+    Queued,
+    Running,
+    /// Downloaded; being checked and copied into the library
+    Verifying,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MediaDownloadOptions {
+    /// Expected SHA-256 of the file, in hex
+    pub sha256: Option<String>,
+    /// Name to save the file under; from the link when None. The extension
+    /// always matches the file's content.
+    pub filename: Option<String>,
+    pub credentials: Option<Credentials>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadJob {
+    pub id: String,
+    pub url: String,
+    pub state: DownloadState,
+    pub received: u64,
+    /// None until the server says
+    pub total: Option<u64>,
+    /// Tries so far, including the running one
+    pub attempts: u32,
+    pub expected_sha256: Option<String>,
+    /// The file in the library, once done
+    pub output: Option<String>,
+    pub error: Option<String>,
+    #[serde(skip)]
+    filename: Option<String>,
+    #[serde(skip)]
+    credentials: Option<Credentials>,
+    #[serde(skip)]
+    cache_root: PathBuf,
+    #[serde(skip)]
+    library_root: PathBuf,
+}
+
+/// Managed state: queued, running, and recently finished downloads
+#[derive(Default)]
+pub struct MediaDownloads {
+    state: Mutex<QueueState>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Oldest first
+    jobs: Vec<DownloadJob>,
+    /// Id and stop flag of the running job
+    running: Option<(String, Arc<AtomicBool>)>,
+    worker: bool,
+}
+
+impl MediaDownloads {
+    pub fn jobs(&self) -> Result<Vec<DownloadJob>, CpresError> {
+        Ok(self.lock()?.jobs.clone())
+    }
+
+    /// Queue a download of `url` into the library folder `library_root`,
+    /// cached under `cache_root`; starts the worker when it's idle
+    pub fn enqueue(
+        &self,
+        app: &AppHandle,
+        cache_root: &Path,
+        library_root: &Path,
+        url: &str,
+        options: MediaDownloadOptions,
+    ) -> Result<DownloadJob, DownloadError> {
+        let url = url.trim();
+        let parsed =
+            reqwest::Url::parse(url).map_err(|_| DownloadError::UnsupportedUrl(url.to_string()))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(DownloadError::UnsupportedUrl(url.to_string()));
+        }
+        let expected_sha256 = match options.sha256.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(digest) if digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                Some(digest.to_ascii_lowercase())
+            }
+            Some(digest) => {
+                return Err(DownloadError::UnexpectedResponse(format!(
+                    "\"{digest}\" isn't a SHA-256"
+                )))
+            }
+        };
+        let job = DownloadJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            url: url.to_string(),
+            state: DownloadState::Queued,
+            received: 0,
+            total: None,
+            attempts: 0,
+            expected_sha256,
+            output: None,
+            error: None,
+            filename: options.filename,
+            credentials: options.credentials,
+            cache_root: cache_root.to_path_buf(),
+            library_root: library_root.to_path_buf(),
+        };
+        let mut state = self.lock()?;
+        state.jobs.push(job.clone());
+        self.start_worker(app, &mut state);
+        drop(state);
+        let _ = app.emit(DOWNLOAD_PROGRESS_EVENT, &job);
+        Ok(job)
+    }
+
+    /// Queue a failed or cancelled job again; it picks up from what was
+    /// downloaded before. None when no such job is stopped.
+    pub fn resume(&self, app: &AppHandle, job_id: &str) -> Result<Option<DownloadJob>, CpresError> {
+        let mut state = self.lock()?;
+        let Some(job) = state.jobs.iter_mut().find(|job| {
+            job.id == job_id
+                && matches!(job.state, DownloadState::Failed | DownloadState::Cancelled)
+        }) else {
+            return Ok(None);
+        };
+        job.state = DownloadState::Queued;
+        job.attempts = 0;
+        job.error = None;
+        let job = job.clone();
+        self.start_worker(app, &mut state);
+        drop(state);
+        let _ = app.emit(DOWNLOAD_PROGRESS_EVENT, &job);
+        Ok(Some(job))
+    }
+
+    /// Take a queued job off the queue or stop the running one; false when
+    /// no unfinished job has that id
+    pub fn cancel(&self, app: &AppHandle, job_id: &str) -> Result<bool, CpresError> {
+        let mut state = self.lock()?;
+        if let Some((id, stop)) = &state.running {
+            if id == job_id {
+                stop.store(true, Ordering::Relaxed);
+                return Ok(true);
+            }
+        }
+        let Some(job) = state
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == job_id && job.state == DownloadState::Queued)
+        else {
+            return Ok(false);
+        };
+        job.state = DownloadState::Cancelled;
+        let _ = app.emit(DOWNLOAD_PROGRESS_EVENT, &*job);
+        Ok(true)
+    }
+
+    fn start_worker(&self, app: &AppHandle, state: &mut QueueState) {
+        if !state.worker {
+            state.worker = true;
+            let handle = app.clone();
+            tauri::async_runtime::spawn(async move { run_queue(&handle).await });
+        }
+    }
+
+    /// Change a job and tell the frontend
+    fn update(&self, app: &AppHandle, job_id: &str, change: impl FnOnce(&mut DownloadJob)) {
+        let Ok(mut state) = self.lock() else {
+            return;
+        };
+        if let Some(job) = state.jobs.iter_mut().find(|job| job.id == job_id) {
+            change(job);
+            let _ = app.emit(DOWNLOAD_PROGRESS_EVENT, &*job);
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, QueueState>, CpresError> {
+        self.state
+            .lock()
+            .map_err(|e| CpresError::InvalidBundle(e.to_string()))
+    }
+}
+
+/// Run queued jobs until there are none left
+async fn run_queue(app: &AppHandle) {
+    let downloads = app.state::<MediaDownloads>();
+    loop {
+        let (job, stop) = {
+            let Ok(mut state) = downloads.lock() else {
+                return;
+            };
+            let Some(job) = state
+                .jobs
+                .iter_mut()
+                .find(|job| job.state == DownloadState::Queued)
+            else {
+                state.worker = false;
+                state.running = None;
+                forget_finished(&mut state.jobs);
+                return;
+            };
+            job.state = DownloadState::Running;
+            let job = job.clone();
+            let stop = Arc::new(AtomicBool::new(false));
+            state.running = Some((job.id.clone(), stop.clone()));
+            (job, stop)
+        };
+
+        let result = fetch(app, &downloads, &job, &stop).await;
+        if let Ok(mut state) = downloads.lock() {
+            state.running = None;
+        }
+        match result {
+            Ok(output) => {
+                log::info!("Downloaded {} to {}", job.url, output.display());
+                downloads.update(app, &job.id, |job| {
+                    job.state = DownloadState::Done;
+                    job.output = Some(output.to_string_lossy().to_string());
+                });
+                // Index the new file now rather than waiting for the watcher
+                media_watch::rescan(
+                    app,
+                    std::iter::once((LIBRARY_FOLDER_ID, job.library_root.clone())),
+                );
+            }
+            Err(DownloadError::Cancelled) => {
+                downloads.update(app, &job.id, |job| job.state = DownloadState::Cancelled);
+            }
+            Err(e) => {
+                log::warn!("Could not download {}: {e}", job.url);
+                downloads.update(app, &job.id, |job| {
+                    job.state = DownloadState::Failed;
+                    job.error = Some(e.to_string());
+                });
+            }
+        }
+    }
+}
+
+/// Drop the oldest finished jobs beyond `MAX_FINISHED_JOBS`
+fn forget_finished(jobs: &mut Vec<DownloadJob>) {
+    let finished = |job: &DownloadJob| {
+        matches!(
+            job.state,
+            DownloadState::Done | DownloadState::Failed | DownloadState::Cancelled
+        )
+    };
+    let mut excess = jobs
+        .iter()
+        .filter(|job| finished(job))
+        .count()
+        .saturating_sub(MAX_FINISHED_JOBS);
+    jobs.retain(|job| {
+        if excess > 0 && finished(job) {
+            excess -= 1;
+            return false;
+        }
+        true
+    });
+}
+
+/// Download `job`, retrying dropped connections, and copy the file into the
+/// library; returns the library file
+async fn fetch(
+    app: &AppHandle,
+    downloads: &MediaDownloads,
+    job: &DownloadJob,
+    stop: &AtomicBool,
+) -> Result<PathBuf, DownloadError> {
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 0;
+    let cached = loop {
+        attempt += 1;
+        downloads.update(app, &job.id, |job| job.attempts = attempt);
+        let fetched = download::fetch_cached_with(
+            &job.cache_root,
+            &job.url,
+            job.credentials.as_ref(),
+            || stop.load(Ordering::Relaxed),
+            |received, total| {
+                downloads.update(app, &job.id, |job| {
+                    job.received = received;
+                    job.total = total;
+                })
+            },
+        )
+        .await;
+        match fetched {
+            Ok(file) => break PathBuf::from(file.path),
+            Err(e @ (DownloadError::Http(_) | DownloadError::Incomplete { .. }))
+                if attempt < MAX_ATTEMPTS =>
+            {
+                log::info!("Download of {} dropped, retrying: {e}", job.url);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                if stop.load(Ordering::Relaxed) {
+                    return Err(DownloadError::Cancelled);
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    };
+
+    downloads.update(app, &job.id, |job| job.state = DownloadState::Verifying);
+    let job = job.clone();
+    tauri::async_runtime::spawn_blocking(move || land(&cached, &job))
+        .await
+        .map_err(|e| DownloadError::UnexpectedResponse(e.to_string()))?
+}
+
+/// Check the downloaded file at `cached` and copy it into the library
+fn land(cached: &Path, job: &DownloadJob) -> Result<PathBuf, DownloadError> {
+    let kind = cpres::detect_media_kind(cached)?;
+    if kind.media_type == "unknown" {
+        return Err(DownloadError::UnexpectedResponse(
+            "The link leads to a web page or a file that isn't media".to_string(),
+        ));
+    }
+    if let Some(expected) = &job.expected_sha256 {
+        let (actual, _) = cpres::hash_file(cached, &CancelToken::default())?;
+        if actual != *expected {
+            // Fetch it afresh next time rather than resuming a bad file
+            let _ = std::fs::remove_file(cached);
+            return Err(DownloadError::ChecksumMismatch {
+                expected: expected.clone(),
+                actual,
+            });
+        }
+    }
+
+    let dir = job.library_root.join(DOWNLOADS_DIR_NAME);
+    std::fs::create_dir_all(&dir)?;
+    let name = job
+        .filename
+        .as_deref()
+        .or_else(|| job.url.split(['?', '#']).next()?.rsplit('/').next())
+        .map(|name| percent_encoding::percent_decode_str(name).decode_utf8_lossy())
+        .and_then(|name| extract::safe_filename(&name))
+        .unwrap_or_else(|| "Download".to_string());
+    let stem = Path::new(&name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or(name);
+
+    // A dot name keeps the library scan away from the unfinished copy
+    let mut temp_file = tempfile::Builder::new()
+        .prefix(".download-")
+        .tempfile_in(&dir)?;
+    std::io::copy(&mut std::fs::File::open(cached)?, temp_file.as_file_mut())?;
+    let output = output_path(&dir, &stem, &kind.extension);
+    cpres::persist_file(temp_file, &output)?;
+    Ok(output)
+}
+
+/// `<stem>.<extension>` in `dir`, numbered when that's taken
+fn output_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    let mut output = dir.join(format!("{stem}.{extension}"));
+    for attempt in 2.. {
+        if !output.exists() {
+            break;
+        }
+        output = dir.join(format!("{stem} ({attempt}).{extension}"));
+    }
+    output
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(MediaDownloads::default());
+}
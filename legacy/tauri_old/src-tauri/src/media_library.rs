@@ -0,0 +1,1281 @@
+//! Index of the media library folder
+//!
+//! Backgrounds and other media in `<content dir>/media-library` are listed in a
+//! SQLite database in app data together with their tags, the collections they
+//! belong to, and their content hashes, so thousands of files can be filtered
+//! and sorted without walking the folder. `scan` reconciles the database with
+//! the folder. Paths are stored relative to the folder so tags survive moving
+//! the content directory, and a file that was renamed or moved inside the
+//! folder is recognized by its hash and keeps its tags and collections.
+//! The same hashes find files that are in the library more than once; those
+//! can be consolidated into one item that keeps every tag and collection.
+//! Folders elsewhere on disk can be added to the library too; their items are
+//! stored relative to the added folder.
+//! Items can be rated from one to five stars, and a search in the language of
+//! `smart_query` can be saved as a smart collection, which lists whatever
+//! matches it at the time. Videos and audio are indexed with their length so
+//! searches can ask for it.
+
+use crate::cpres::{self, CpresError, SkippedFile};
+use crate::ffmpeg;
+use crate::smart_query::SmartQuery;
+use crate::song_import;
+use crate::tasks::CancelToken;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+/// Folder under the content directory holding the media library
+pub const MEDIA_LIBRARY_DIR_NAME: &str = "media-library";
+
+const DATABASE_FILENAME: &str = "media-library.sqlite";
+
+pub const DEFAULT_PAGE_SIZE: usize = 200;
+
+/// Separates tag names in `group_concat`; can't appear in a tag typed by a user
+const TAG_SEPARATOR: char = '\u{1F}';
+
+/// Schema changes, applied in order; `PRAGMA user_version` counts how many have
+/// run. Released migrations must never be edited, only appended to.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE items (
+        id TEXT PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        filename TEXT NOT NULL,
+        mime TEXT NOT NULL,
+        media_type TEXT NOT NULL,
+        byte_size INTEGER NOT NULL,
+        modified_ms INTEGER NOT NULL,
+        added_ms INTEGER NOT NULL
+    );
+    CREATE INDEX items_media_type ON items (media_type);
+    CREATE TABLE hashes (
+        item_id TEXT PRIMARY KEY REFERENCES items (id) ON DELETE CASCADE,
+        sha256 TEXT NOT NULL
+    );
+    CREATE INDEX hashes_sha256 ON hashes (sha256);
+    CREATE TABLE tags (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE
+    );
+    CREATE TABLE item_tags (
+        item_id TEXT NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+        tag_id INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+        PRIMARY KEY (item_id, tag_id)
+    );
+    CREATE TABLE collections (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        created_ms INTEGER NOT NULL
+    );
+    CREATE TABLE collection_items (
+        collection_id TEXT NOT NULL REFERENCES collections (id) ON DELETE CASCADE,
+        item_id TEXT NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        PRIMARY KEY (collection_id, item_id)
+    );
+",
+    "
+    CREATE TABLE folders (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        added_ms INTEGER NOT NULL
+    );
+    CREATE TABLE items_v2 (
+        id TEXT PRIMARY KEY,
+        folder_id INTEGER NOT NULL DEFAULT 0,
+        path TEXT NOT NULL,
+        filename TEXT NOT NULL,
+        mime TEXT NOT NULL,
+        media_type TEXT NOT NULL,
+        byte_size INTEGER NOT NULL,
+        modified_ms INTEGER NOT NULL,
+        added_ms INTEGER NOT NULL,
+        UNIQUE (folder_id, path)
+    );
+    INSERT INTO items_v2 (id, path, filename, mime, media_type, byte_size, modified_ms, added_ms)
+        SELECT id, path, filename, mime, media_type, byte_size, modified_ms, added_ms FROM items;
+    DROP TABLE items;
+    ALTER TABLE items_v2 RENAME TO items;
+    CREATE INDEX items_media_type ON items (media_type);
+",
+    "
+    ALTER TABLE items ADD COLUMN rating INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE items ADD COLUMN duration REAL;
+    CREATE TABLE smart_collections (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        query TEXT NOT NULL,
+        created_ms INTEGER NOT NULL
+    );
+",
+];
+
+/// `folder_id` of items in the media library folder itself; added folders
+/// are numbered from 1
+pub const LIBRARY_FOLDER_ID: i64 = 0;
+
+const ITEM_COLUMNS: &str = "i.id, i.path, i.filename, i.mime, i.media_type, i.byte_size,
+    h.sha256, i.modified_ms, i.added_ms,
+    (SELECT group_concat(t.name, char(31)) FROM item_tags it
+        JOIN tags t ON t.id = it.tag_id WHERE it.item_id = i.id),
+    i.folder_id, i.rating, i.duration";
+
+#[derive(Debug, Serialize)]
+pub struct MediaItem {
+    pub id: String,
+    pub folder_id: i64,
+    pub path: String,
+    /// Path inside the item's folder, with `/` separators
+    pub relative_path: String,
+    pub filename: String,
+    pub mime: String,
+    pub media_type: String,
+    pub byte_size: u64,
+    pub sha256: Option<String>,
+    pub modified_ms: u64,
+    pub added_ms: u64,
+    pub tags: Vec<String>,
+    /// 1 to 5 stars; 0 when unrated
+    pub rating: u8,
+    /// Seconds, for videos and audio ffmpeg could read
+    pub duration: Option<f64>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaSort {
+    #[default]
+    Name,
+    Added,
+    Modified,
+    Size,
+    Type,
+    Rating,
+    /// Items of unknown length last
+    Duration,
+    /// Order within `collection_id`; the same as `Name` without one
+    Collection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MediaQuery {
+    /// Part of the filename, case-insensitive
+    pub text: Option<String>,
+    pub media_type: Option<String>,
+    /// Items must have every one of these tags
+    pub tags: Vec<String>,
+    /// A search in the language of `smart_query`
+    pub search: Option<String>,
+    pub collection_id: Option<String>,
+    /// Items must also match this smart collection's search
+    pub smart_collection_id: Option<String>,
+    pub folder_id: Option<i64>,
+    pub sort: MediaSort,
+    pub descending: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MediaPage {
+    pub items: Vec<MediaItem>,
+    /// Items matching the query, across all pages
+    pub total: usize,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ScanReport {
+    pub added: usize,
+    pub updated: usize,
+    /// Renamed or moved inside their folder, recognized by hash
+    pub moved: usize,
+    pub removed: usize,
+}
+
+impl ScanReport {
+    pub fn is_empty(&self) -> bool {
+        self.added + self.updated + self.moved + self.removed == 0
+    }
+
+    pub fn merge(&mut self, other: ScanReport) {
+        self.added += other.added;
+        self.updated += other.updated;
+        self.moved += other.moved;
+        self.removed += other.removed;
+    }
+}
+
+/// Items with the same content
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub sha256: String,
+    pub byte_size: u64,
+    /// Oldest first
+    pub items: Vec<MediaItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Consolidation {
+    /// The item that was kept, with the others' tags and collections
+    pub kept: MediaItem,
+    /// Files deleted
+    pub removed: Vec<String>,
+    /// Files that couldn't be deleted; their items stay in the library
+    pub failed: Vec<SkippedFile>,
+}
+
+/// A folder added to the library from elsewhere on disk
+#[derive(Debug, Serialize)]
+pub struct Folder {
+    pub id: i64,
+    pub path: String,
+    pub added_ms: u64,
+    /// False while the folder is missing (e.g. on an unplugged drive); its
+    /// items are kept until it is removed from the library
+    pub available: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagCount {
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    pub created_ms: u64,
+    pub item_count: usize,
+}
+
+/// A saved search; its items are whatever matches it now
+#[derive(Debug, Serialize)]
+pub struct SmartCollection {
+    pub id: String,
+    pub name: String,
+    pub query: String,
+    pub created_ms: u64,
+    pub item_count: usize,
+}
+
+/// A file found in the folder that isn't indexed as-is
+struct ScannedFile {
+    relative_path: String,
+    filename: String,
+    mime: &'static str,
+    media_type: &'static str,
+    byte_size: u64,
+    modified_ms: u64,
+    sha256: String,
+    duration: Option<f64>,
+}
+
+/// Managed state: the open media library database
+pub struct MediaLibrary {
+    connection: Mutex<Connection>,
+}
+
+impl MediaLibrary {
+    pub fn open(path: &Path) -> Result<Self, CpresError> {
+        let mut connection = Connection::open(path).map_err(database_error)?;
+        connection
+            .execute_batch("PRAGMA journal_mode = WAL;")
+            .map_err(database_error)?;
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Bring the index in line with the library folder `library_root` and every
+    /// added folder that is available
+    pub fn scan(
+        &self,
+        library_root: &Path,
+        cancel: &CancelToken,
+    ) -> Result<ScanReport, CpresError> {
+        std::fs::create_dir_all(library_root)?;
+        let mut report = ScanReport::default();
+        for (folder_id, root) in self.roots(library_root)? {
+            if root.is_dir() {
+                report.merge(self.scan_folder(folder_id, &root, cancel)?);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Bring the index in line with the files in one folder. Files are hashed
+    /// without holding the database, so queries keep working during a long scan.
+    pub fn scan_folder(
+        &self,
+        folder_id: i64,
+        root: &Path,
+        cancel: &CancelToken,
+    ) -> Result<ScanReport, CpresError> {
+        let indexed: HashMap<String, (String, u64, u64, bool)> = {
+            let connection = self.lock()?;
+            let mut statement = connection
+                .prepare(
+                    "SELECT path, id, byte_size, modified_ms,
+                        duration IS NULL AND media_type IN ('video', 'audio')
+                    FROM items WHERE folder_id = ?1",
+                )
+                .map_err(database_error)?;
+            let rows = statement
+                .query_map([folder_id], |row| {
+                    Ok((
+                        row.get(0)?,
+                        (row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?),
+                    ))
+                })
+                .map_err(database_error)?;
+            rows.collect::<Result<_, _>>().map_err(database_error)?
+        };
+        // Without ffmpeg lengths stay unknown until a scan that has it
+        let read_durations = ffmpeg::locate().is_ok();
+        let duration = |path: &Path| {
+            read_durations
+                .then(|| ffmpeg::duration(path).ok().flatten())
+                .flatten()
+        };
+
+        let mut files = Vec::new();
+        song_import::collect_files(root, 0, &mut files)?;
+        let mut seen = HashSet::new();
+        let mut changed = Vec::new();
+        // Unchanged items indexed before lengths were, or without ffmpeg
+        let mut durations = Vec::new();
+        for path in files {
+            cancel.check()?;
+            let Some(relative_path) = relative_path(root, &path) else {
+                continue;
+            };
+            let extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("")
+                .to_lowercase();
+            // The extension decides what's worth indexing; the content
+            // decides the type of the files that are
+            if cpres::media_kind(&extension).1 == "unknown" {
+                continue;
+            }
+            let metadata = std::fs::metadata(&path)?;
+            let modified_ms = metadata.modified().map(unix_millis).unwrap_or(0);
+            let current = indexed.get(&relative_path);
+            seen.insert(relative_path.clone());
+            if let Some((id, size, modified, needs_duration)) = current {
+                if *size == metadata.len() && *modified == modified_ms {
+                    if let Some(seconds) = needs_duration.then(|| duration(&path)).flatten() {
+                        durations.push((id.clone(), seconds));
+                    }
+                    continue;
+                }
+            }
+            let (sha256, byte_size) = cpres::hash_file(&path, cancel)?;
+            let kind = cpres::detect_media_kind(&path)?;
+            changed.push(ScannedFile {
+                filename: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                relative_path,
+                mime: kind.mime,
+                media_type: kind.media_type,
+                byte_size,
+                modified_ms,
+                sha256,
+                duration: matches!(kind.media_type, "video" | "audio")
+                    .then(|| duration(&path))
+                    .flatten(),
+            });
+        }
+
+        let mut report = ScanReport::default();
+        let mut connection = self.lock()?;
+        let transaction = connection.transaction().map_err(database_error)?;
+        // Items whose file is gone; a new file with the same hash takes over one of them
+        let mut missing: Vec<(String, String)> = indexed
+            .iter()
+            .filter(|(path, _)| !seen.contains(*path))
+            .map(|(path, (id, _, _, _))| (path.clone(), id.clone()))
+            .collect();
+        missing.sort();
+        for file in changed {
+            if let Some((id, _, _, _)) = indexed.get(&file.relative_path) {
+                update_item(&transaction, id, &file)?;
+                report.updated += 1;
+                continue;
+            }
+            let same_hash: Vec<String> = transaction
+                .prepare_cached("SELECT item_id FROM hashes WHERE sha256 = ?1")
+                .and_then(|mut statement| {
+                    statement
+                        .query_map([&file.sha256], |row| row.get(0))?
+                        .collect()
+                })
+                .map_err(database_error)?;
+            let moved_from = missing.iter().position(|(_, id)| same_hash.contains(id));
+            if let Some(index) = moved_from {
+                let (_, id) = missing.remove(index);
+                update_item(&transaction, &id, &file)?;
+                report.moved += 1;
+                continue;
+            }
+            let id = uuid::Uuid::new_v4().to_string();
+            transaction
+                .execute(
+                    "INSERT INTO items (id, folder_id, path, filename, mime, media_type, byte_size, modified_ms, added_ms, duration)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                    params![
+                        id,
+                        folder_id,
+                        file.relative_path,
+                        file.filename,
+                        file.mime,
+                        file.media_type,
+                        file.byte_size as i64,
+                        file.modified_ms as i64,
+                        unix_millis(SystemTime::now()) as i64,
+                        file.duration,
+                    ],
+                )
+                .map_err(database_error)?;
+            transaction
+                .execute(
+                    "INSERT INTO hashes (item_id, sha256) VALUES (?1, ?2)",
+                    params![id, file.sha256],
+                )
+                .map_err(database_error)?;
+            report.added += 1;
+        }
+        for (id, seconds) in durations {
+            transaction
+                .execute(
+                    "UPDATE items SET duration = ?2 WHERE id = ?1",
+                    params![id, seconds],
+                )
+                .map_err(database_error)?;
+        }
+        for (_, id) in missing {
+            transaction
+                .execute("DELETE FROM items WHERE id = ?1", [&id])
+                .map_err(database_error)?;
+            report.removed += 1;
+        }
+        remove_unused_tags(&transaction)?;
+        transaction.commit().map_err(database_error)?;
+        Ok(report)
+    }
+
+    /// One page of the items matching `query`; paths in the library folder are
+    /// resolved against `library_root`
+    pub fn query(&self, library_root: &Path, query: &MediaQuery) -> Result<MediaPage, CpresError> {
+        let roots: HashMap<i64, PathBuf> = self.roots(library_root)?.into_iter().collect();
+        let mut conditions = Vec::new();
+        let mut values: Vec<SqlValue> = Vec::new();
+        if let Some(text) = query.text.as_deref().filter(|t| !t.trim().is_empty()) {
+            conditions.push("i.filename LIKE ? ESCAPE '\\'".to_string());
+            values.push(SqlValue::Text(format!("%{}%", escape_like(text.trim()))));
+        }
+        if let Some(folder_id) = query.folder_id {
+            conditions.push("i.folder_id = ?".to_string());
+            values.push(SqlValue::Integer(folder_id));
+        }
+        if let Some(media_type) = &query.media_type {
+            conditions.push("i.media_type = ?".to_string());
+            values.push(SqlValue::Text(media_type.clone()));
+        }
+        for tag in &query.tags {
+            conditions.push(
+                "EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON t.id = it.tag_id
+                    WHERE it.item_id = i.id AND t.name = ?)"
+                    .to_string(),
+            );
+            values.push(SqlValue::Text(tag.clone()));
+        }
+        if let Some(search) = query.search.as_deref().filter(|s| !s.trim().is_empty()) {
+            let (condition, search_values) = SmartQuery::parse(search)?.to_sql();
+            conditions.push(condition);
+            values.extend(search_values);
+        }
+        if let Some(id) = &query.smart_collection_id {
+            let search = self.smart_collection_query(id)?;
+            let (condition, search_values) = SmartQuery::parse(&search)?.to_sql();
+            conditions.push(condition);
+            values.extend(search_values);
+        }
+        let mut join = String::new();
+        if let Some(collection_id) = &query.collection_id {
+            join =
+                "JOIN collection_items c ON c.item_id = i.id AND c.collection_id = ?".to_string();
+            values.insert(0, SqlValue::Text(collection_id.clone()));
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
+        let direction = if query.descending { "DESC" } else { "ASC" };
+        let order = match query.sort {
+            MediaSort::Name => format!("i.filename COLLATE NOCASE {direction}"),
+            MediaSort::Added => format!("i.added_ms {direction}"),
+            MediaSort::Modified => format!("i.modified_ms {direction}"),
+            MediaSort::Size => format!("i.byte_size {direction}"),
+            MediaSort::Type => format!("i.media_type {direction}, i.filename COLLATE NOCASE"),
+            MediaSort::Rating => format!("i.rating {direction}, i.filename COLLATE NOCASE"),
+            MediaSort::Duration => format!("i.duration IS NULL, i.duration {direction}"),
+            MediaSort::Collection if query.collection_id.is_some() => {
+                format!("c.position {direction}")
+            }
+            MediaSort::Collection => format!("i.filename COLLATE NOCASE {direction}"),
+        };
+
+        let connection = self.lock()?;
+        let total: i64 = connection
+            .query_row(
+                &format!("SELECT COUNT(*) FROM items i {join} {filter}"),
+                params_from_iter(values.iter()),
+                |row| row.get(0),
+            )
+            .map_err(database_error)?;
+
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        values.push(SqlValue::Integer(limit as i64));
+        values.push(SqlValue::Integer(query.offset as i64));
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {ITEM_COLUMNS} FROM items i LEFT JOIN hashes h ON h.item_id = i.id
+                {join} {filter} ORDER BY {order}, i.id LIMIT ? OFFSET ?"
+            ))
+            .map_err(database_error)?;
+        let items = statement
+            .query_map(params_from_iter(values.iter()), |row| {
+                item_from_row(&roots, row)
+            })
+            .map_err(database_error)?
+            .collect::<Result<_, _>>()
+            .map_err(database_error)?;
+
+        Ok(MediaPage {
+            items,
+            total: total as usize,
+        })
+    }
+
+    /// The item with `id`, if it's still in the index
+    pub fn item(&self, library_root: &Path, id: &str) -> Result<Option<MediaItem>, CpresError> {
+        let roots: HashMap<i64, PathBuf> = self.roots(library_root)?.into_iter().collect();
+        let connection = self.lock()?;
+        connection
+            .query_row(
+                &format!(
+                    "SELECT {ITEM_COLUMNS} FROM items i LEFT JOIN hashes h ON h.item_id = i.id
+                    WHERE i.id = ?1"
+                ),
+                params![id],
+                |row| item_from_row(&roots, row),
+            )
+            .optional()
+            .map_err(database_error)
+    }
+
+    /// Items whose content is `sha256`, oldest first
+    pub fn items_with_hash(
+        &self,
+        library_root: &Path,
+        sha256: &str,
+    ) -> Result<Vec<MediaItem>, CpresError> {
+        let roots: HashMap<i64, PathBuf> = self.roots(library_root)?.into_iter().collect();
+        let connection = self.lock()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {ITEM_COLUMNS} FROM items i JOIN hashes h ON h.item_id = i.id
+                WHERE h.sha256 = ?1 ORDER BY i.added_ms, i.id"
+            ))
+            .map_err(database_error)?;
+        let items = statement
+            .query_map([sha256], |row| item_from_row(&roots, row))
+            .map_err(database_error)?
+            .collect::<Result<_, _>>()
+            .map_err(database_error)?;
+        Ok(items)
+    }
+
+    /// Every set of items with the same content, largest files first
+    pub fn duplicates(&self, library_root: &Path) -> Result<Vec<DuplicateGroup>, CpresError> {
+        let roots: HashMap<i64, PathBuf> = self.roots(library_root)?.into_iter().collect();
+        let connection = self.lock()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {ITEM_COLUMNS} FROM items i JOIN hashes h ON h.item_id = i.id
+                WHERE h.sha256 IN (SELECT sha256 FROM hashes GROUP BY sha256 HAVING COUNT(*) > 1)
+                ORDER BY i.byte_size DESC, h.sha256, i.added_ms, i.id"
+            ))
+            .map_err(database_error)?;
+        let items = statement
+            .query_map([], |row| item_from_row(&roots, row))
+            .map_err(database_error)?;
+        let mut groups: Vec<DuplicateGroup> = Vec::new();
+        for item in items {
+            let item = item.map_err(database_error)?;
+            let sha256 = item.sha256.clone().unwrap_or_default();
+            match groups.last_mut() {
+                Some(group) if group.sha256 == sha256 => group.items.push(item),
+                _ => groups.push(DuplicateGroup {
+                    sha256,
+                    byte_size: item.byte_size,
+                    items: vec![item],
+                }),
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Merge every item whose content is `sha256` into one: `keep`, or the
+    /// oldest. The kept item gets the others' tags and collections, then the
+    /// other files are deleted from disk.
+    pub fn consolidate(
+        &self,
+        library_root: &Path,
+        sha256: &str,
+        keep: Option<&str>,
+    ) -> Result<Consolidation, CpresError> {
+        let mut items = self.items_with_hash(library_root, sha256)?;
+        let kept_index = match keep {
+            Some(id) => items
+                .iter()
+                .position(|item| item.id == id)
+                .ok_or_else(|| CpresError::MissingFile(format!("item {id} with this content")))?,
+            None if items.is_empty() => {
+                return Err(CpresError::MissingFile(format!("items with hash {sha256}")))
+            }
+            None => 0,
+        };
+        let kept = items.remove(kept_index);
+
+        let mut removed = Vec::new();
+        let mut failed = Vec::new();
+        let mut connection = self.lock()?;
+        let transaction = connection.transaction().map_err(database_error)?;
+        for duplicate in items {
+            transaction
+                .execute(
+                    "INSERT OR IGNORE INTO item_tags (item_id, tag_id)
+                    SELECT ?1, tag_id FROM item_tags WHERE item_id = ?2",
+                    params![kept.id, duplicate.id],
+                )
+                .map_err(database_error)?;
+            // The kept item takes the duplicate's place in collections it
+            // wasn't in yet
+            transaction
+                .execute(
+                    "INSERT OR IGNORE INTO collection_items (collection_id, item_id, position)
+                    SELECT collection_id, ?1, position FROM collection_items WHERE item_id = ?2",
+                    params![kept.id, duplicate.id],
+                )
+                .map_err(database_error)?;
+            match std::fs::remove_file(&duplicate.path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    failed.push(SkippedFile {
+                        path: duplicate.path,
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+            }
+            transaction
+                .execute("DELETE FROM items WHERE id = ?1", [&duplicate.id])
+                .map_err(database_error)?;
+            removed.push(duplicate.path);
+        }
+        transaction.commit().map_err(database_error)?;
+        drop(connection);
+
+        let kept = self
+            .item(library_root, &kept.id)?
+            .ok_or_else(|| CpresError::MissingFile(format!("item {}", kept.id)))?;
+        Ok(Consolidation {
+            kept,
+            removed,
+            failed,
+        })
+    }
+
+    /// Every tag with the number of items that have it
+    pub fn tags(&self) -> Result<Vec<TagCount>, CpresError> {
+        let connection = self.lock()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT t.name, COUNT(it.item_id) FROM tags t
+                JOIN item_tags it ON it.tag_id = t.id
+                GROUP BY t.id ORDER BY t.name COLLATE NOCASE",
+            )
+            .map_err(database_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok(TagCount {
+                    name: row.get(0)?,
+                    count: row.get::<_, i64>(1)? as usize,
+                })
+            })
+            .map_err(database_error)?;
+        rows.collect::<Result<_, _>>().map_err(database_error)
+    }
+
+    /// Replace an item's tags
+    pub fn set_tags(&self, item_id: &str, tags: &[String]) -> Result<(), CpresError> {
+        let mut connection = self.lock()?;
+        let transaction = connection.transaction().map_err(database_error)?;
+        require_item(&transaction, item_id)?;
+        transaction
+            .execute("DELETE FROM item_tags WHERE item_id = ?1", [item_id])
+            .map_err(database_error)?;
+        for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            let tag: String = tag.chars().filter(|c| *c != TAG_SEPARATOR).collect();
+            transaction
+                .execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [&tag])
+                .map_err(database_error)?;
+            transaction
+                .execute(
+                    "INSERT OR IGNORE INTO item_tags (item_id, tag_id)
+                    SELECT ?1, id FROM tags WHERE name = ?2",
+                    params![item_id, tag],
+                )
+                .map_err(database_error)?;
+        }
+        remove_unused_tags(&transaction)?;
+        transaction.commit().map_err(database_error)
+    }
+
+    /// Add and remove tags on many items at once; their other tags stay
+    pub fn tag_items(
+        &self,
+        item_ids: &[String],
+        add: &[String],
+        remove: &[String],
+    ) -> Result<(), CpresError> {
+        let mut connection = self.lock()?;
+        let transaction = connection.transaction().map_err(database_error)?;
+        let add: Vec<String> = add
+            .iter()
+            .map(|t| t.trim())
+            .filter(|t| !t.is_empty())
+            .map(|t| t.chars().filter(|c| *c != TAG_SEPARATOR).collect())
+            .collect();
+        for tag in &add {
+            transaction
+                .execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [tag])
+                .map_err(database_error)?;
+        }
+        for item_id in item_ids {
+            require_item(&transaction, item_id)?;
+            for tag in &add {
+                transaction
+                    .execute(
+                        "INSERT OR IGNORE INTO item_tags (item_id, tag_id)
+                        SELECT ?1, id FROM tags WHERE name = ?2",
+                        params![item_id, tag],
+                    )
+                    .map_err(database_error)?;
+            }
+            for tag in remove {
+                transaction
+                    .execute(
+                        "DELETE FROM item_tags WHERE item_id = ?1
+                        AND tag_id IN (SELECT id FROM tags WHERE name = ?2)",
+                        params![item_id, tag.trim()],
+                    )
+                    .map_err(database_error)?;
+            }
+        }
+        remove_unused_tags(&transaction)?;
+        transaction.commit().map_err(database_error)
+    }
+
+    /// Give items a rating of 1 to 5 stars, or 0 to clear it
+    pub fn rate_items(&self, item_ids: &[String], rating: u8) -> Result<(), CpresError> {
+        if rating > 5 {
+            return Err(CpresError::InvalidBundle(format!(
+                "Rating must be 0 to 5, not {rating}"
+            )));
+        }
+        let mut connection = self.lock()?;
+        let transaction = connection.transaction().map_err(database_error)?;
+        for item_id in item_ids {
+            require_item(&transaction, item_id)?;
+            transaction
+                .execute(
+                    "UPDATE items SET rating = ?2 WHERE id = ?1",
+                    params![item_id, rating],
+                )
+                .map_err(database_error)?;
+        }
+        transaction.commit().map_err(database_error)
+    }
+
+    pub fn smart_collections(&self) -> Result<Vec<SmartCollection>, CpresError> {
+        let saved: Vec<(String, String, String, i64)> = {
+            let connection = self.lock()?;
+            let mut statement = connection
+                .prepare(
+                    "SELECT id, name, query, created_ms FROM smart_collections
+                    ORDER BY name COLLATE NOCASE",
+                )
+                .map_err(database_error)?;
+            let rows = statement
+                .query_map([], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                })
+                .map_err(database_error)?;
+            rows.collect::<Result<_, _>>().map_err(database_error)?
+        };
+        saved
+            .into_iter()
+            .map(|(id, name, query, created_ms)| {
+                // A search saved by a newer version may not parse here
+                let item_count = self.count_matching(&query).unwrap_or(0);
+                Ok(SmartCollection {
+                    id,
+                    name,
+                    query,
+                    created_ms: created_ms as u64,
+                    item_count,
+                })
+            })
+            .collect()
+    }
+
+    pub fn create_smart_collection(
+        &self,
+        name: &str,
+        query: &str,
+    ) -> Result<SmartCollection, CpresError> {
+        let name = collection_name(name)?;
+        let item_count = self.count_matching(query)?;
+        let collection = SmartCollection {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            query: query.trim().to_string(),
+            created_ms: unix_millis(SystemTime::now()),
+            item_count,
+        };
+        self.lock()?
+            .execute(
+                "INSERT INTO smart_collections (id, name, query, created_ms)
+                VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    collection.id,
+                    collection.name,
+                    collection.query,
+                    collection.created_ms as i64
+                ],
+            )
+            .map_err(database_error)?;
+        Ok(collection)
+    }
+
+    /// Rename a smart collection and change its search
+    pub fn update_smart_collection(
+        &self,
+        id: &str,
+        name: &str,
+        query: &str,
+    ) -> Result<(), CpresError> {
+        let name = collection_name(name)?;
+        SmartQuery::parse(query)?;
+        let updated = self
+            .lock()?
+            .execute(
+                "UPDATE smart_collections SET name = ?2, query = ?3 WHERE id = ?1",
+                params![id, name, query.trim()],
+            )
+            .map_err(database_error)?;
+        if updated == 0 {
+            return Err(CpresError::MissingFile(format!("smart collection {id}")));
+        }
+        Ok(())
+    }
+
+    pub fn delete_smart_collection(&self, id: &str) -> Result<(), CpresError> {
+        self.lock()?
+            .execute("DELETE FROM smart_collections WHERE id = ?1", [id])
+            .map_err(database_error)?;
+        Ok(())
+    }
+
+    fn smart_collection_query(&self, id: &str) -> Result<String, CpresError> {
+        self.lock()?
+            .query_row(
+                "SELECT query FROM smart_collections WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(database_error)?
+            .ok_or_else(|| CpresError::MissingFile(format!("smart collection {id}")))
+    }
+
+    /// How many items match a search
+    fn count_matching(&self, query: &str) -> Result<usize, CpresError> {
+        let (condition, values) = SmartQuery::parse(query)?.to_sql();
+        let count: i64 = self
+            .lock()?
+            .query_row(
+                &format!("SELECT COUNT(*) FROM items i WHERE {condition}"),
+                params_from_iter(values.iter()),
+                |row| row.get(0),
+            )
+            .map_err(database_error)?;
+        Ok(count as usize)
+    }
+
+    pub fn collections(&self) -> Result<Vec<Collection>, CpresError> {
+        let connection = self.lock()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT c.id, c.name, c.created_ms,
+                    (SELECT COUNT(*) FROM collection_items ci WHERE ci.collection_id = c.id)
+                FROM collections c ORDER BY c.name COLLATE NOCASE",
+            )
+            .map_err(database_error)?;
+        let rows = statement
+            .query_map([], collection_from_row)
+            .map_err(database_error)?;
+        rows.collect::<Result<_, _>>().map_err(database_error)
+    }
+
+    pub fn create_collection(&self, name: &str) -> Result<Collection, CpresError> {
+        let name = collection_name(name)?;
+        let collection = Collection {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            created_ms: unix_millis(SystemTime::now()),
+            item_count: 0,
+        };
+        self.lock()?
+            .execute(
+                "INSERT INTO collections (id, name, created_ms) VALUES (?1, ?2, ?3)",
+                params![collection.id, collection.name, collection.created_ms as i64],
+            )
+            .map_err(database_error)?;
+        Ok(collection)
+    }
+
+    pub fn rename_collection(&self, id: &str, name: &str) -> Result<(), CpresError> {
+        let name = collection_name(name)?;
+        let updated = self
+            .lock()?
+            .execute(
+                "UPDATE collections SET name = ?2 WHERE id = ?1",
+                params![id, name],
+            )
+            .map_err(database_error)?;
+        if updated == 0 {
+            return Err(CpresError::MissingFile(format!("collection {id}")));
+        }
+        Ok(())
+    }
+
+    /// Delete a collection; its items stay in the library
+    pub fn delete_collection(&self, id: &str) -> Result<(), CpresError> {
+        self.lock()?
+            .execute("DELETE FROM collections WHERE id = ?1", [id])
+            .map_err(database_error)?;
+        Ok(())
+    }
+
+    /// Append items to the end of a collection; items already in it stay where they are
+    pub fn add_to_collection(&self, id: &str, item_ids: &[String]) -> Result<(), CpresError> {
+        let mut connection = self.lock()?;
+        let transaction = connection.transaction().map_err(database_error)?;
+        let exists = transaction
+            .query_row("SELECT 1 FROM collections WHERE id = ?1", [id], |_| Ok(()))
+            .optional()
+            .map_err(database_error)?;
+        if exists.is_none() {
+            return Err(CpresError::MissingFile(format!("collection {id}")));
+        }
+        for item_id in item_ids {
+            require_item(&transaction, item_id)?;
+            transaction
+                .execute(
+                    "INSERT OR IGNORE INTO collection_items (collection_id, item_id, position)
+                    SELECT ?1, ?2, COALESCE(MAX(position) + 1, 0)
+                    FROM collection_items WHERE collection_id = ?1",
+                    params![id, item_id],
+                )
+                .map_err(database_error)?;
+        }
+        transaction.commit().map_err(database_error)
+    }
+
+    pub fn remove_from_collection(&self, id: &str, item_ids: &[String]) -> Result<(), CpresError> {
+        let mut connection = self.lock()?;
+        let transaction = connection.transaction().map_err(database_error)?;
+        for item_id in item_ids {
+            transaction
+                .execute(
+                    "DELETE FROM collection_items WHERE collection_id = ?1 AND item_id = ?2",
+                    params![id, item_id],
+                )
+                .map_err(database_error)?;
+        }
+        transaction.commit().map_err(database_error)
+    }
+
+    pub fn folders(&self) -> Result<Vec<Folder>, CpresError> {
+        let connection = self.lock()?;
+        let mut statement = connection
+            .prepare("SELECT id, path, added_ms FROM folders ORDER BY path")
+            .map_err(database_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                let path: String = row.get(1)?;
+                Ok(Folder {
+                    id: row.get(0)?,
+                    available: Path::new(&path).is_dir(),
+                    path,
+                    added_ms: row.get::<_, i64>(2)? as u64,
+                })
+            })
+            .map_err(database_error)?;
+        rows.collect::<Result<_, _>>().map_err(database_error)
+    }
+
+    /// Add a folder from elsewhere on disk to the library; scan it to index its files
+    pub fn add_folder(&self, library_root: &Path, path: &Path) -> Result<Folder, CpresError> {
+        if !path.is_absolute() || !path.is_dir() {
+            return Err(CpresError::MissingFile(path.to_string_lossy().to_string()));
+        }
+        // Nested folders would index the same files twice
+        for (_, root) in self.roots(library_root)? {
+            if path.starts_with(&root) || root.starts_with(path) {
+                return Err(CpresError::InvalidBundle(format!(
+                    "{} overlaps {}, which is already in the media library",
+                    path.display(),
+                    root.display()
+                )));
+            }
+        }
+        let path_text = path.to_string_lossy().to_string();
+        let added_ms = unix_millis(SystemTime::now());
+        let connection = self.lock()?;
+        connection
+            .execute(
+                "INSERT INTO folders (path, added_ms) VALUES (?1, ?2)",
+                params![path_text, added_ms as i64],
+            )
+            .map_err(database_error)?;
+        Ok(Folder {
+            id: connection.last_insert_rowid(),
+            path: path_text,
+            added_ms,
+            available: true,
+        })
+    }
+
+    /// Remove an added folder and its items from the library; the files stay on disk
+    pub fn remove_folder(&self, folder_id: i64) -> Result<(), CpresError> {
+        let mut connection = self.lock()?;
+        let transaction = connection.transaction().map_err(database_error)?;
+        transaction
+            .execute("DELETE FROM items WHERE folder_id = ?1", [folder_id])
+            .map_err(database_error)?;
+        transaction
+            .execute("DELETE FROM folders WHERE id = ?1", [folder_id])
+            .map_err(database_error)?;
+        remove_unused_tags(&transaction)?;
+        transaction.commit().map_err(database_error)
+    }
+
+    /// Every folder in the library with its id, the library folder first
+    pub fn roots(&self, library_root: &Path) -> Result<Vec<(i64, PathBuf)>, CpresError> {
+        let mut roots = vec![(LIBRARY_FOLDER_ID, library_root.to_path_buf())];
+        roots.extend(
+            self.folders()?
+                .into_iter()
+                .map(|folder| (folder.id, PathBuf::from(folder.path))),
+        );
+        Ok(roots)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Connection>, CpresError> {
+        self.connection
+            .lock()
+            .map_err(|e| CpresError::Database(e.to_string()))
+    }
+}
+
+/// Run the migrations this database hasn't seen yet. Foreign keys are off
+/// meanwhile so a migration can rebuild a table without cascading deletes.
+fn migrate(connection: &mut Connection) -> Result<(), CpresError> {
+    let applied: usize = connection
+        .query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))
+        .map_err(database_error)? as usize;
+    if applied > MIGRATIONS.len() {
+        return Err(CpresError::Database(format!(
+            "media library database is from a newer version (schema {applied})"
+        )));
+    }
+    connection
+        .execute_batch("PRAGMA foreign_keys = OFF;")
+        .map_err(database_error)?;
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let transaction = connection.transaction().map_err(database_error)?;
+        transaction
+            .execute_batch(migration)
+            .map_err(database_error)?;
+        transaction
+            .pragma_update(None, "user_version", (version + 1) as i64)
+            .map_err(database_error)?;
+        transaction.commit().map_err(database_error)?;
+    }
+    connection
+        .execute_batch("PRAGMA foreign_keys = ON;")
+        .map_err(database_error)?;
+    Ok(())
+}
+
+fn update_item(connection: &Connection, id: &str, file: &ScannedFile) -> Result<(), CpresError> {
+    connection
+        .execute(
+            "UPDATE items SET path = ?2, filename = ?3, mime = ?4, media_type = ?5,
+                byte_size = ?6, modified_ms = ?7, duration = ?8
+            WHERE id = ?1",
+            params![
+                id,
+                file.relative_path,
+                file.filename,
+                file.mime,
+                file.media_type,
+                file.byte_size as i64,
+                file.modified_ms as i64,
+                file.duration,
+            ],
+        )
+        .map_err(database_error)?;
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO hashes (item_id, sha256) VALUES (?1, ?2)",
+            params![id, file.sha256],
+        )
+        .map_err(database_error)?;
+    Ok(())
+}
+
+fn require_item(connection: &Connection, item_id: &str) -> Result<(), CpresError> {
+    connection
+        .query_row("SELECT 1 FROM items WHERE id = ?1", [item_id], |_| Ok(()))
+        .optional()
+        .map_err(database_error)?
+        .ok_or_else(|| CpresError::MissingFile(format!("media item {item_id}")))
+}
+
+fn remove_unused_tags(connection: &Connection) -> Result<(), CpresError> {
+    connection
+        .execute(
+            "DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM item_tags)",
+            [],
+        )
+        .map_err(database_error)?;
+    Ok(())
+}
+
+fn item_from_row(roots: &HashMap<i64, PathBuf>, row: &Row) -> rusqlite::Result<MediaItem> {
+    let relative_path: String = row.get(1)?;
+    let tags: Option<String> = row.get(9)?;
+    let folder_id: i64 = row.get(10)?;
+    let path = roots
+        .get(&folder_id)
+        .map(|root| root.join(&relative_path).to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(MediaItem {
+        id: row.get(0)?,
+        folder_id,
+        path,
+        relative_path,
+        filename: row.get(2)?,
+        mime: row.get(3)?,
+        media_type: row.get(4)?,
+        byte_size: row.get::<_, i64>(5)? as u64,
+        sha256: row.get(6)?,
+        modified_ms: row.get::<_, i64>(7)? as u64,
+        added_ms: row.get::<_, i64>(8)? as u64,
+        tags: tags
+            .map(|tags| tags.split(TAG_SEPARATOR).map(String::from).collect())
+            .unwrap_or_default(),
+        rating: row.get(11)?,
+        duration: row.get(12)?,
+    })
+}
+
+fn collection_from_row(row: &Row) -> rusqlite::Result<Collection> {
+    Ok(Collection {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        created_ms: row.get::<_, i64>(2)? as u64,
+        item_count: row.get::<_, i64>(3)? as usize,
+    })
+}
+
+fn collection_name(name: &str) -> Result<String, CpresError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CpresError::InvalidBundle(
+            "Collection name is required".to_string(),
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// `path` relative to `root` with `/` separators, so the index reads the same on every OS
+fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let parts: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    Some(parts.join("/"))
+}
+
+pub(crate) fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn database_error(e: rusqlite::Error) -> CpresError {
+    CpresError::Database(e.to_string())
+}
+
+/// Folder of the media library under the content directory
+pub fn library_dir(content_dir: &Path) -> PathBuf {
+    content_dir.join(MEDIA_LIBRARY_DIR_NAME)
+}
+
+pub fn init(app: &AppHandle) -> Result<(), String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let library = MediaLibrary::open(&dir.join(DATABASE_FILENAME)).map_err(|e| e.to_string())?;
+    app.manage(library);
+    Ok(())
+}
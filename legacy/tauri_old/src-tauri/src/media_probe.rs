@@ -0,0 +1,196 @@
+//! Reading duration, dimensions, codecs, and loudness of imported media
+//!
+//! Images are measured with the `image` crate, which also reads their EXIF
+//! orientation: phones store portrait photos sideways and flag the rotation,
+//! so the recorded size is the upright one. Video and audio are described
+//! by ffprobe, which ships alongside ffmpeg, and audio is also measured for
+//! loudness (see `loudness`). Probing is best effort: a file ffprobe can't
+//! read, or a machine without ffprobe, still imports, just without the extra
+//! fields.
+
+use crate::ffmpeg;
+use crate::loudness;
+use image::metadata::Orientation;
+use image::{ImageDecoder, ImageReader};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+
+/// What's known about a media file beyond its type and size
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MediaMetadata {
+    /// Seconds, for video and audio
+    pub duration: Option<f64>,
+    /// As displayed, after any EXIF rotation
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// EXIF orientation (2 to 8) of a photo stored rotated or mirrored
+    pub orientation: Option<u8>,
+    /// Frames per second
+    pub frame_rate: Option<f64>,
+    /// ffmpeg codec names, e.g. "h264", "hevc", "aac"
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    pub audio_channels: Option<u32>,
+    /// EBU R128 integrated loudness in LUFS, for audio
+    pub loudness: Option<f64>,
+    /// dBTP
+    pub true_peak: Option<f64>,
+    /// EBU R128 loudness range in LU
+    pub loudness_range: Option<f64>,
+}
+
+/// Metadata for a file of the given media type ("image", "video", "audio");
+/// fields that couldn't be read are left empty
+pub fn probe(path: &Path, media_type: &str) -> MediaMetadata {
+    let probed = match media_type {
+        "image" => probe_image(path),
+        "video" | "audio" => probe_stream(path),
+        _ => return MediaMetadata::default(),
+    };
+    let mut metadata = probed.unwrap_or_else(|e| {
+        log::info!("Could not read metadata of {}: {e}", path.display());
+        MediaMetadata::default()
+    });
+    if media_type == "audio" {
+        match loudness::measure(path) {
+            Ok(measured) => {
+                metadata.loudness = measured.integrated;
+                metadata.true_peak = measured.true_peak;
+                metadata.loudness_range = measured.range;
+            }
+            Err(e) => log::info!("Could not measure loudness of {}: {e}", path.display()),
+        }
+    }
+    metadata
+}
+
+fn probe_image(path: &Path) -> Result<MediaMetadata, String> {
+    // SVGs have no pixel size, and the image crate doesn't read them
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"))
+    {
+        return Ok(MediaMetadata::default());
+    }
+    let mut decoder = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| e.to_string())?
+        .into_decoder()
+        .map_err(|e| e.to_string())?;
+    let (width, height) = decoder.dimensions();
+    // Unreadable EXIF just means the photo is shown as stored
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let turned = matches!(
+        orientation,
+        Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH
+    );
+    let (width, height) = if turned {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    Ok(MediaMetadata {
+        width: Some(width),
+        height: Some(height),
+        orientation: Some(orientation.to_exif()).filter(|&exif| exif != 1),
+        ..MediaMetadata::default()
+    })
+}
+
+/// The parts of `ffprobe -print_format json` output that are used
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    avg_frame_rate: Option<String>,
+    r_frame_rate: Option<String>,
+    channels: Option<u32>,
+    duration: Option<String>,
+    #[serde(default)]
+    disposition: ProbeDisposition,
+}
+
+#[derive(Default, Deserialize)]
+struct ProbeDisposition {
+    /// Cover art in an audio file shows up as a one-frame video stream
+    #[serde(default)]
+    attached_pic: u8,
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+}
+
+fn probe_stream(path: &Path) -> Result<MediaMetadata, String> {
+    let output = ffmpeg::probe_command()
+        .map_err(|e| e.to_string())?
+        .args(["-print_format", "json", "-show_format", "-show_streams"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let probed: ProbeOutput = serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
+    Ok(metadata_from(probed))
+}
+
+fn metadata_from(probed: ProbeOutput) -> MediaMetadata {
+    let video = probed.streams.iter().find(|stream| {
+        stream.codec_type.as_deref() == Some("video") && stream.disposition.attached_pic == 0
+    });
+    let audio = probed
+        .streams
+        .iter()
+        .find(|stream| stream.codec_type.as_deref() == Some("audio"));
+
+    // The container's duration covers every stream; fall back to the streams'
+    let duration = probed
+        .format
+        .and_then(|format| format.duration)
+        .or_else(|| video.and_then(|s| s.duration.clone()))
+        .or_else(|| audio.and_then(|s| s.duration.clone()))
+        .and_then(|seconds| seconds.parse::<f64>().ok())
+        .filter(|seconds| seconds.is_finite() && *seconds > 0.0);
+
+    MediaMetadata {
+        duration,
+        width: video.and_then(|s| s.width),
+        height: video.and_then(|s| s.height),
+        frame_rate: video.and_then(|s| {
+            // avg_frame_rate is "0/0" for some variable-rate files
+            parse_rate(s.avg_frame_rate.as_deref())
+                .or_else(|| parse_rate(s.r_frame_rate.as_deref()))
+        }),
+        video_codec: video.and_then(|s| s.codec_name.clone()),
+        audio_codec: audio.and_then(|s| s.codec_name.clone()),
+        audio_channels: audio.and_then(|s| s.channels),
+        ..MediaMetadata::default()
+    }
+}
+
+/// "30000/1001" to 29.97
+fn parse_rate(rate: Option<&str>) -> Option<f64> {
+    let (numerator, denominator) = rate?.split_once('/')?;
+    let numerator: f64 = numerator.parse().ok()?;
+    let denominator: f64 = denominator.parse().ok()?;
+    if numerator <= 0.0 || denominator <= 0.0 {
+        return None;
+    }
+    Some(numerator / denominator)
+}
@@ -0,0 +1,271 @@
+//! In and out points for audio and video, optionally baked in on save
+//!
+//! A media reference can carry in and out points (`MediaFileRef::trim`); the
+//! manifest entry mirrors them as `trimStart` and `trimEnd` so playback
+//! honours them. With `render` set, saving stores only the part between the
+//! points: ffmpeg copies it into a new file, which goes into the bundle in
+//! place of the original, and the manifest entry gets the new hash, size, and
+//! duration. The points are baked in at that point, so they're dropped from
+//! the entry and `trimmedFrom` records what the file was cut from. A later
+//! save with the same points, from an editor that hasn't reopened the bundle,
+//! finds that record and keeps the cut file instead of cutting it again.
+//!
+//! Streams are copied, not re-encoded, so cutting is quick and lossless, but
+//! a video cut starts at the keyframe at or before the in point.
+
+use crate::bundle_reader;
+use crate::checksums;
+use crate::cpres::{self, BundleState, CpresError};
+use crate::ffmpeg;
+use crate::tasks::CancelToken;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use tempfile::NamedTempFile;
+
+/// In and out points, in seconds from the start of the file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MediaTrim {
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    /// Store only the trimmed part in the bundle on save
+    pub render: bool,
+}
+
+impl MediaTrim {
+    fn start_seconds(&self) -> f64 {
+        self.start.unwrap_or(0.0).max(0.0)
+    }
+
+    /// Whether the points cut anything off
+    fn cuts(&self) -> bool {
+        self.start_seconds() > 0.0 || self.end.is_some()
+    }
+}
+
+/// `trimmedFrom` of a manifest entry: the file a cut was made from, and where
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrimRecord {
+    sha256: String,
+    start: f64,
+    end: Option<f64>,
+}
+
+/// Files cut for a save, and the manifest that describes them
+#[derive(Default)]
+pub struct RenderedTrims {
+    /// None when nothing was cut
+    pub manifest: Option<String>,
+    /// By bundle path
+    files: HashMap<String, NamedTempFile>,
+}
+
+impl RenderedTrims {
+    /// The cut file to store at `bundle_path`, if there is one
+    pub fn file(&self, bundle_path: &str) -> Option<&Path> {
+        self.files.get(bundle_path).map(|file| file.path())
+    }
+}
+
+/// Cut every media file in `state` whose trim asks to be rendered; `bundle`
+/// is the bundle being replaced, which `bundle:` sources are read from
+pub fn render_trims(
+    bundle: &Path,
+    state: &BundleState,
+    cancel: &CancelToken,
+) -> Result<RenderedTrims, CpresError> {
+    let mut rendered = RenderedTrims::default();
+    let requested: Vec<_> = state
+        .media
+        .iter()
+        .filter_map(|media| Some((media, media.trim.filter(|t| t.render && t.cuts())?)))
+        .collect();
+    if requested.is_empty() {
+        return Ok(rendered);
+    }
+
+    let mut manifest: Value = serde_json::from_str(&state.manifest)?;
+    let mut existing = if bundle.exists() {
+        Some(bundle_reader::open_archive(bundle)?)
+    } else {
+        None
+    };
+    let saved_manifest: Option<Value> = match existing.as_mut() {
+        Some(archive) => {
+            serde_json::from_str(&cpres::read_zip_file(archive, "manifest.json")?).ok()
+        }
+        None => None,
+    };
+
+    for (media, trim) in requested {
+        cancel.check()?;
+        let start = trim.start_seconds();
+        if trim.end.is_some_and(|end| end <= start) {
+            return Err(CpresError::InvalidBundle(format!(
+                "The out point of {} is before its in point",
+                media.bundle_path
+            )));
+        }
+        let Some(entry) = manifest_entry(&mut manifest, &media.id) else {
+            log::warn!("Not trimming {}: it isn't in the manifest", media.id);
+            continue;
+        };
+        let record = TrimRecord {
+            sha256: entry
+                .get("sha256")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            start,
+            end: trim.end,
+        };
+
+        // A `bundle:` source is either the untouched original or a file
+        // already cut on an earlier save
+        let mut extracted = None;
+        if let Some(existing_path) = media.source_path.strip_prefix("bundle:") {
+            let archive = existing
+                .as_mut()
+                .ok_or_else(|| CpresError::MissingFile(existing_path.to_string()))?;
+            let saved_entry = saved_manifest
+                .as_ref()
+                .and_then(|saved| saved_entry(saved, &media.id));
+            if let Some(saved) = saved_entry.filter(|saved| cut_from(saved, &record)) {
+                bake(
+                    entry,
+                    saved.get("sha256").cloned(),
+                    saved.get("byteSize").cloned(),
+                    saved.get("duration").cloned(),
+                    &record,
+                )?;
+                continue;
+            }
+            let recorded = checksums::recorded_digest(archive, existing_path)?;
+            if recorded.is_some_and(|digest| digest != record.sha256) {
+                log::warn!(
+                    "Not trimming {existing_path}: it isn't the file the points were set on"
+                );
+                continue;
+            }
+            let mut temp_file = tempfile::Builder::new()
+                .suffix(&format!(".{}", extension(&media.bundle_path)))
+                .tempfile()?;
+            let mut zip_entry = archive
+                .by_name(existing_path)
+                .map_err(|_| CpresError::MissingFile(existing_path.to_string()))?;
+            std::io::copy(&mut zip_entry, temp_file.as_file_mut())?;
+            extracted = Some(temp_file);
+        }
+        let source = match &extracted {
+            Some(temp_file) => temp_file.path(),
+            None => Path::new(&media.source_path),
+        };
+
+        let cut = cut_file(source, &trim, extension(&media.bundle_path))?;
+        let (sha256, byte_size) = cpres::hash_file(cut.path(), cancel)?;
+        let duration = ffmpeg::duration(cut.path()).ok().flatten();
+        bake(
+            entry,
+            Some(Value::from(sha256)),
+            Some(Value::from(byte_size)),
+            duration.map(Value::from),
+            &record,
+        )?;
+        rendered.files.insert(media.bundle_path.clone(), cut);
+    }
+
+    rendered.manifest = Some(serde_json::to_string(&manifest)?);
+    Ok(rendered)
+}
+
+fn manifest_entry<'a>(manifest: &'a mut Value, id: &str) -> Option<&'a mut Map<String, Value>> {
+    manifest
+        .get_mut("media")?
+        .as_array_mut()?
+        .iter_mut()
+        .filter_map(Value::as_object_mut)
+        .find(|entry| entry.get("id").and_then(Value::as_str) == Some(id))
+}
+
+fn saved_entry<'a>(manifest: &'a Value, id: &str) -> Option<&'a Map<String, Value>> {
+    manifest
+        .get("media")?
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_object)
+        .find(|entry| entry.get("id").and_then(Value::as_str) == Some(id))
+}
+
+/// Whether the saved entry is a cut made as `record` describes
+fn cut_from(saved: &Map<String, Value>, record: &TrimRecord) -> bool {
+    saved
+        .get("trimmedFrom")
+        .and_then(|from| serde_json::from_value::<TrimRecord>(from.clone()).ok())
+        .is_some_and(|from| from == *record)
+}
+
+/// Describe the cut file in the manifest entry, with the points baked in
+fn bake(
+    entry: &mut Map<String, Value>,
+    sha256: Option<Value>,
+    byte_size: Option<Value>,
+    duration: Option<Value>,
+    record: &TrimRecord,
+) -> Result<(), CpresError> {
+    let fields = [
+        ("sha256", sha256),
+        ("byteSize", byte_size),
+        ("duration", duration),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            entry.insert(key.to_string(), value);
+        }
+    }
+    entry.remove("trimStart");
+    entry.remove("trimEnd");
+    entry.insert("trimmedFrom".to_string(), serde_json::to_value(record)?);
+    Ok(())
+}
+
+fn extension(bundle_path: &str) -> &str {
+    Path::new(bundle_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("bin")
+}
+
+/// Copy the part of `source` between the trim points into a temporary file
+/// of the same format
+fn cut_file(source: &Path, trim: &MediaTrim, extension: &str) -> Result<NamedTempFile, CpresError> {
+    let temp_file = tempfile::Builder::new()
+        .suffix(&format!(".{extension}"))
+        .tempfile()?;
+    let start = trim.start_seconds();
+    let mut command = ffmpeg::command()?;
+    // -ss before -i seeks to a keyframe, the only place a stream copy can start
+    command
+        .args(["-ss", &format!("{start:.3}"), "-i"])
+        .arg(source);
+    if let Some(end) = trim.end {
+        command.args(["-t", &format!("{:.3}", end - start)]);
+    }
+    let output = command
+        .args(["-map", "0:v?", "-map", "0:a?", "-c", "copy"])
+        .args(["-avoid_negative_ts", "make_zero"])
+        .arg(temp_file.path())
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(CpresError::Ffmpeg(format!(
+            "trimming failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(temp_file)
+}
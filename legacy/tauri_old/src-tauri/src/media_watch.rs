@@ -0,0 +1,128 @@
+//! Keeps the media library index current while the app runs
+//!
+//! Watches the media library folder and every folder added to the library.
+//! Bursts of changes (a copy of hundreds of files, an editor saving through a
+//! temp file) are debounced, the folders they touched are rescanned, and
+//! `media-library:changed` is emitted when the index changed, so files dropped
+//! in from Explorer or Finder show up in the app right away.
+
+use crate::commands;
+use crate::media_library::{self, MediaLibrary, ScanReport};
+use crate::tasks::CancelToken;
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const MEDIA_LIBRARY_CHANGED_EVENT: &str = "media-library:changed";
+
+/// Quiet time before a burst of file events is handled
+const DEBOUNCE: Duration = Duration::from_millis(750);
+
+/// Managed state: the running watcher, replaced whenever the folders change
+#[derive(Default)]
+pub struct MediaWatcher {
+    debouncer: Mutex<Option<Debouncer<RecommendedWatcher>>>,
+}
+
+/// (Re)start watching the library folders; call after the content directory
+/// or the list of added folders changes. Errors are logged, not returned: the
+/// library still works without live updates.
+pub fn restart(app: &AppHandle) {
+    if let Err(e) = try_restart(app) {
+        log::warn!("Could not watch the media library: {e}");
+    }
+}
+
+fn try_restart(app: &AppHandle) -> Result<(), String> {
+    let state = app.state::<MediaWatcher>();
+    let mut current = state.debouncer.lock().map_err(|e| e.to_string())?;
+    // Stop the old watcher first so its events can't race the new one
+    current.take();
+
+    let library_root =
+        media_library::library_dir(&commands::resolve_content_dir(app).map_err(|e| e.to_string())?);
+    std::fs::create_dir_all(&library_root).map_err(|e| e.to_string())?;
+    let roots = app
+        .state::<MediaLibrary>()
+        .roots(&library_root)
+        .map_err(|e| e.to_string())?;
+
+    let handle = app.clone();
+    let watched = roots.clone();
+    let mut debouncer = new_debouncer(DEBOUNCE, move |result: DebounceEventResult| {
+        let events = match result {
+            Ok(events) => events,
+            Err(e) => {
+                log::warn!("Media library watcher error: {e}");
+                return;
+            }
+        };
+        // The folder each changed path is in
+        let touched: BTreeSet<usize> = events
+            .iter()
+            .filter_map(|event| {
+                watched
+                    .iter()
+                    .position(|(_, root)| event.path.starts_with(root))
+            })
+            .collect();
+        rescan(&handle, touched.into_iter().map(|i| watched[i].clone()));
+    })
+    .map_err(|e| e.to_string())?;
+
+    for (_, root) in &roots {
+        if !root.is_dir() {
+            continue;
+        }
+        if let Err(e) = debouncer.watcher().watch(root, RecursiveMode::Recursive) {
+            log::warn!("Could not watch {}: {e}", root.display());
+        }
+    }
+    *current = Some(debouncer);
+    Ok(())
+}
+
+pub(crate) fn rescan(app: &AppHandle, folders: impl Iterator<Item = (i64, PathBuf)>) {
+    let library = app.state::<MediaLibrary>();
+    let mut report = ScanReport::default();
+    for (folder_id, root) in folders {
+        match library.scan_folder(folder_id, &root, &CancelToken::default()) {
+            Ok(folder_report) => report.merge(folder_report),
+            Err(e) => log::warn!("Could not rescan {}: {e}", root.display()),
+        }
+    }
+    if report.is_empty() {
+        return;
+    }
+    if let Err(e) = app.emit(MEDIA_LIBRARY_CHANGED_EVENT, report) {
+        log::warn!("Could not emit {MEDIA_LIBRARY_CHANGED_EVENT}: {e}");
+    }
+}
+
+/// Scan the library for changes made while the app was closed, then start watching
+pub fn init(app: &AppHandle) {
+    app.manage(MediaWatcher::default());
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let scan = commands::resolve_content_dir(&handle)
+            .map_err(|e| e.to_string())
+            .and_then(|dir| {
+                handle
+                    .state::<MediaLibrary>()
+                    .scan(&media_library::library_dir(&dir), &CancelToken::default())
+                    .map_err(|e| e.to_string())
+            });
+        match scan {
+            Ok(report) if !report.is_empty() => {
+                let _ = handle.emit(MEDIA_LIBRARY_CHANGED_EVENT, report);
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Could not scan the media library: {e}"),
+        }
+        restart(&handle);
+    });
+}
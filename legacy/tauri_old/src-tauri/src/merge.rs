@@ -0,0 +1,320 @@
+//! Merge several .cpres bundles into one
+//!
+//! Slides and arrangements are concatenated in input order. Slide, media,
+//! theme, and font ids that collide with an earlier bundle are remapped, and
+//! media/fonts with identical content (same sha256) are stored only once.
+//! Media and font entries are raw-copied between archives, so nothing is
+//! recompressed.
+
+use crate::bundle_reader::{self, BundleReader};
+use crate::checksums::Checksums;
+use crate::cpres::{self, CpresError};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// Keys in slides/themes/manifest that hold a media id
+const MEDIA_ID_KEYS: &[&str] = &["mediaId"];
+/// Keys that hold a theme id
+const THEME_ID_KEYS: &[&str] = &["themeId"];
+/// Keys in arrangement.json that hold slide ids
+const SLIDE_ID_KEYS: &[&str] = &["order", "slideIds"];
+
+#[derive(Default)]
+struct Merged {
+    manifest: Option<Value>,
+    titles: Vec<String>,
+    slides: Vec<Value>,
+    order: Vec<Value>,
+    sections: Vec<Value>,
+    themes: Vec<(String, Value)>,
+    media: Vec<Value>,
+    fonts: Vec<Value>,
+    slide_ids: HashSet<String>,
+    theme_ids: HashSet<String>,
+    media_ids: HashSet<String>,
+    font_ids: HashSet<String>,
+    bundle_paths: HashSet<String>,
+    /// sha256 -> id of the entry already in the merged bundle
+    media_by_hash: HashMap<String, String>,
+    fonts_by_hash: HashMap<String, String>,
+    /// (source bundle index, path in source, path in output)
+    copies: Vec<(usize, String, String)>,
+}
+
+/// Merge `paths` (in order) into a new bundle at `output`
+pub fn merge_bundles(paths: &[PathBuf], output: &Path) -> Result<(), CpresError> {
+    if paths.len() < 2 {
+        return Err(CpresError::InvalidBundle(
+            "At least two bundles are required to merge".to_string(),
+        ));
+    }
+
+    let mut merged = Merged::default();
+    for (index, path) in paths.iter().enumerate() {
+        merge_one(&mut merged, index, path)?;
+    }
+
+    write_merged(merged, paths, output)
+}
+
+fn merge_one(merged: &mut Merged, index: usize, path: &Path) -> Result<(), CpresError> {
+    let bundle = cpres::open_bundle(path)?;
+    let mut manifest: Value = serde_json::from_str(&bundle.manifest)?;
+    let mut slides: Value = serde_json::from_str(&bundle.slides)?;
+    let mut arrangement: Value = serde_json::from_str(&bundle.arrangement)?;
+
+    if let Some(title) = manifest.get("title").and_then(|t| t.as_str()) {
+        merged.titles.push(title.to_string());
+    }
+
+    // Media: dedupe by content, otherwise remap colliding ids and paths
+    let mut media_map = HashMap::new();
+    for mut entry in take_array(&mut manifest, "media") {
+        let id = str_field(&entry, "id");
+        let sha = str_field(&entry, "sha256");
+        if let Some(existing) = merged.media_by_hash.get(&sha).filter(|_| !sha.is_empty()) {
+            media_map.insert(id, existing.clone());
+            continue;
+        }
+
+        let new_id = unique_id(&id, &merged.media_ids);
+        let source_path = str_field(&entry, "path");
+        let new_path = unique_bundle_path(&source_path, &new_id, &merged.bundle_paths);
+        entry["id"] = json!(new_id);
+        entry["path"] = json!(new_path);
+
+        merged.media_ids.insert(new_id.clone());
+        merged.bundle_paths.insert(new_path.clone());
+        if !sha.is_empty() {
+            merged.media_by_hash.insert(sha, new_id.clone());
+        }
+        if !source_path.is_empty() {
+            merged.copies.push((index, source_path, new_path));
+        }
+        if new_id != id {
+            media_map.insert(id, new_id);
+        }
+        merged.media.push(entry);
+    }
+
+    // Fonts are referenced by family, so only paths/ids need to stay unique
+    for mut entry in take_array(&mut manifest, "fonts") {
+        let id = str_field(&entry, "id");
+        let sha = str_field(&entry, "sha256");
+        if !sha.is_empty() && merged.fonts_by_hash.contains_key(&sha) {
+            continue;
+        }
+
+        let new_id = unique_id(&id, &merged.font_ids);
+        let source_path = str_field(&entry, "path");
+        let new_path = unique_bundle_path(&source_path, &new_id, &merged.bundle_paths);
+        entry["id"] = json!(new_id);
+        entry["path"] = json!(new_path);
+
+        merged.font_ids.insert(new_id.clone());
+        merged.bundle_paths.insert(new_path.clone());
+        if !sha.is_empty() {
+            merged.fonts_by_hash.insert(sha, new_id);
+        }
+        if !source_path.is_empty() {
+            merged.copies.push((index, source_path, new_path));
+        }
+        merged.fonts.push(entry);
+    }
+
+    // Themes: identical themes collapse, colliding ids get a new one
+    let mut theme_map = HashMap::new();
+    for theme_file in bundle.themes {
+        let mut theme: Value = serde_json::from_str(&theme_file.content)?;
+        cpres::rewrite_references(&mut theme, MEDIA_ID_KEYS, &media_map);
+        let id = str_field(&theme, "id");
+
+        if merged.theme_ids.contains(&id) {
+            let identical = merged
+                .themes
+                .iter()
+                .any(|(existing, content)| *existing == id && *content == theme);
+            if identical {
+                continue;
+            }
+            let new_id = unique_id(&id, &merged.theme_ids);
+            theme["id"] = json!(new_id);
+            theme_map.insert(id, new_id.clone());
+            merged.theme_ids.insert(new_id.clone());
+            merged.themes.push((new_id, theme));
+        } else {
+            merged.theme_ids.insert(id.clone());
+            merged.themes.push((id, theme));
+        }
+    }
+
+    // Slides: remap colliding ids, then media/theme references
+    let mut slide_map = HashMap::new();
+    if let Some(items) = slides.as_array_mut() {
+        for slide in items.iter_mut() {
+            let id = str_field(slide, "id");
+            let new_id = unique_id(&id, &merged.slide_ids);
+            if new_id != id {
+                slide["id"] = json!(new_id);
+                slide_map.insert(id, new_id.clone());
+            }
+            merged.slide_ids.insert(new_id);
+        }
+    }
+    cpres::rewrite_references(&mut slides, MEDIA_ID_KEYS, &media_map);
+    cpres::rewrite_references(&mut slides, THEME_ID_KEYS, &theme_map);
+    if let Value::Array(items) = slides {
+        merged.slides.extend(items);
+    }
+
+    cpres::rewrite_references(&mut arrangement, SLIDE_ID_KEYS, &slide_map);
+    merged.order.extend(take_array(&mut arrangement, "order"));
+    merged
+        .sections
+        .extend(take_array(&mut arrangement, "sections"));
+
+    if merged.manifest.is_none() {
+        cpres::rewrite_references(&mut manifest, THEME_ID_KEYS, &theme_map);
+        merged.manifest = Some(manifest);
+    }
+
+    Ok(())
+}
+
+fn write_merged(merged: Merged, paths: &[PathBuf], output: &Path) -> Result<(), CpresError> {
+    let mut manifest = merged
+        .manifest
+        .ok_or_else(|| CpresError::InvalidBundle("No bundles to merge".to_string()))?;
+    manifest["presentationId"] = json!(uuid::Uuid::new_v4().to_string());
+    if !merged.titles.is_empty() {
+        manifest["title"] = json!(merged.titles.join(" + "));
+    }
+    manifest["media"] = Value::Array(merged.media);
+    manifest["fonts"] = Value::Array(merged.fonts);
+
+    let arrangement = json!({
+        "order": merged.order,
+        "sections": merged.sections,
+    });
+
+    let parent = output.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)?;
+    let temp_file = NamedTempFile::new_in(parent)?;
+    let mut zip = ZipWriter::new(temp_file.reopen()?);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let mut checksums = Checksums::default();
+
+    checksums.write_entry(
+        &mut zip,
+        "manifest.json",
+        serde_json::to_string_pretty(&manifest)?.as_bytes(),
+        options,
+    )?;
+    checksums.write_entry(
+        &mut zip,
+        "slides.json",
+        serde_json::to_string_pretty(&merged.slides)?.as_bytes(),
+        options,
+    )?;
+    checksums.write_entry(
+        &mut zip,
+        "arrangement.json",
+        serde_json::to_string_pretty(&arrangement)?.as_bytes(),
+        options,
+    )?;
+
+    for (id, theme) in &merged.themes {
+        checksums.write_entry(
+            &mut zip,
+            &format!("themes/{id}.json"),
+            serde_json::to_string_pretty(theme)?.as_bytes(),
+            options,
+        )?;
+    }
+
+    let mut archives: HashMap<usize, ZipArchive<BundleReader>> = HashMap::new();
+    for (index, source_path, target_path) in &merged.copies {
+        if !archives.contains_key(index) {
+            archives.insert(*index, bundle_reader::open_archive(&paths[*index])?);
+        }
+        let archive = archives.get_mut(index).expect("archive opened above");
+        let entry_index = archive
+            .index_for_name(source_path)
+            .ok_or_else(|| CpresError::MissingFile(source_path.clone()))?;
+        checksums.add_from_archive(archive, entry_index, target_path)?;
+        let entry = archive.by_index_raw(entry_index)?;
+        zip.raw_copy_file_rename(entry, target_path)?;
+    }
+
+    checksums.write(&mut zip, options)?;
+    zip.finish()?;
+    cpres::persist_file(temp_file, output)?;
+
+    Ok(())
+}
+
+fn take_array(value: &mut Value, key: &str) -> Vec<Value> {
+    match value.get_mut(key).map(Value::take) {
+        Some(Value::Array(items)) => items,
+        _ => Vec::new(),
+    }
+}
+
+fn str_field(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Keep `id` when free, otherwise generate a fresh uuid
+pub(crate) fn unique_id(id: &str, taken: &HashSet<String>) -> String {
+    if !id.is_empty() && !taken.contains(id) {
+        id.to_string()
+    } else {
+        uuid::Uuid::new_v4().to_string()
+    }
+}
+
+/// Keep the original bundle path when free, otherwise derive a free one from
+/// the new id (or from fresh uuids, when that is taken too)
+pub(crate) fn unique_bundle_path(path: &str, id: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(path) {
+        return path.to_string();
+    }
+
+    let source = Path::new(path);
+    let dir = source
+        .parent()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = source.extension().and_then(|e| e.to_str());
+    let mut seed = id.to_string();
+    loop {
+        let stem: String = seed
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .take(8)
+            .collect();
+        let name = match extension {
+            Some(ext) => format!("{stem}.{ext}"),
+            None => stem.clone(),
+        };
+        let candidate = if dir.is_empty() {
+            name
+        } else {
+            format!("{dir}/{name}")
+        };
+        if !stem.is_empty() && !taken.contains(&candidate) {
+            return candidate;
+        }
+        seed = uuid::Uuid::new_v4().to_string();
+    }
+}
@@ -0,0 +1,304 @@
+//! Fonts a bundle uses that this machine can't show
+//!
+//! A bundle made on another computer can name fonts in its themes and slides
+//! that were never embedded and aren't installed here; the text silently
+//! falls back to a default font. After a bundle is opened, its font families
+//! are checked against the bundle's embedded fonts and the fonts on this
+//! machine, and each missing one comes with installed fonts to use instead:
+//! the same family under its base name ("Montserrat" for "Montserrat
+//! SemiBold"), a font with the same metrics ("Liberation Sans" for "Arial"),
+//! one with a similar name, and last a generic family.
+
+use crate::compatibility::GENERIC_FONT_FAMILIES;
+use crate::cpres::{self, CpresError};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+const MAX_SUGGESTIONS: usize = 4;
+
+/// Words naming a style rather than a family, as in "Lato Black Italic"
+const STYLE_WORDS: &[&str] = &[
+    "thin",
+    "hairline",
+    "extralight",
+    "ultralight",
+    "light",
+    "book",
+    "regular",
+    "normal",
+    "medium",
+    "semibold",
+    "demibold",
+    "bold",
+    "extrabold",
+    "ultrabold",
+    "black",
+    "heavy",
+    "italic",
+    "oblique",
+    "condensed",
+    "narrow",
+    "extra",
+    "ultra",
+    "semi",
+    "demi",
+];
+
+/// Families drawn with the same character widths, so text wraps the same
+const METRIC_COMPATIBLE: &[&[&str]] = &[
+    &[
+        "Arial",
+        "Helvetica",
+        "Liberation Sans",
+        "Arimo",
+        "Nimbus Sans",
+    ],
+    &[
+        "Times New Roman",
+        "Times",
+        "Liberation Serif",
+        "Tinos",
+        "Nimbus Roman",
+    ],
+    &[
+        "Courier New",
+        "Courier",
+        "Liberation Mono",
+        "Cousine",
+        "Nimbus Mono PS",
+    ],
+    &["Calibri", "Carlito"],
+    &["Cambria", "Caladea"],
+    &["Georgia", "Gelasio"],
+    &["Arial Narrow", "Liberation Sans Narrow"],
+    &["Segoe UI", "Selawik"],
+];
+
+#[derive(Debug, Serialize)]
+pub struct MissingFontReport {
+    /// Every font family the bundle uses, missing or not
+    pub used: Vec<String>,
+    pub missing: Vec<MissingFont>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MissingFont {
+    pub family: String,
+    /// Names of the themes that use it
+    pub themes: Vec<String>,
+    /// Slides that use it directly rather than through a theme
+    pub slide_count: usize,
+    /// Best first
+    pub suggestions: Vec<FontSuggestion>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FontSuggestion {
+    pub family: String,
+    pub reason: SuggestionReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SuggestionReason {
+    /// The same family, named without its style
+    SameFamily,
+    /// A different family with the same character widths
+    MetricCompatible,
+    SimilarName,
+    /// A CSS generic family like "serif", when nothing closer is installed
+    Generic,
+}
+
+/// Font families the bundle at `path` uses that are neither embedded in it
+/// nor among `installed`
+pub fn check(path: &Path, installed: &[String]) -> Result<MissingFontReport, CpresError> {
+    let bundle = cpres::open_bundle(path)?;
+    let manifest: Value = serde_json::from_str(&bundle.manifest)?;
+    let slides: Value = serde_json::from_str(&bundle.slides)?;
+
+    // Family, by lowercase name, to the themes using it
+    let mut used: BTreeMap<String, (String, BTreeSet<String>)> = BTreeMap::new();
+    for theme in &bundle.themes {
+        let value: Value = serde_json::from_str(&theme.content)?;
+        let name = value
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or(&theme.filename)
+            .to_string();
+        let mut families = BTreeSet::new();
+        cpres::collect_font_families(&value, &mut families);
+        for family in families {
+            used.entry(family.to_lowercase())
+                .or_insert_with(|| (family, BTreeSet::new()))
+                .1
+                .insert(name.clone());
+        }
+    }
+    let mut slide_counts: BTreeMap<String, usize> = BTreeMap::new();
+    for slide in slides_of(&slides) {
+        let mut families = BTreeSet::new();
+        cpres::collect_font_families(slide, &mut families);
+        for family in families {
+            *slide_counts.entry(family.to_lowercase()).or_default() += 1;
+            used.entry(family.to_lowercase())
+                .or_insert_with(|| (family, BTreeSet::new()));
+        }
+    }
+
+    let embedded: BTreeSet<String> = manifest
+        .get("fonts")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|font| font.get("family").and_then(Value::as_str))
+        .map(str::to_lowercase)
+        .collect();
+    let installed_keys: BTreeSet<String> = installed.iter().map(|f| f.to_lowercase()).collect();
+
+    let mut missing = Vec::new();
+    for (key, (family, themes)) in &used {
+        if GENERIC_FONT_FAMILIES.contains(&key.as_str())
+            || embedded.contains(key)
+            || installed_keys.contains(key)
+        {
+            continue;
+        }
+        missing.push(MissingFont {
+            family: family.clone(),
+            themes: themes.iter().cloned().collect(),
+            slide_count: slide_counts.get(key).copied().unwrap_or(0),
+            suggestions: suggestions(family, installed),
+        });
+    }
+    Ok(MissingFontReport {
+        used: used.into_values().map(|(family, _)| family).collect(),
+        missing,
+    })
+}
+
+/// The slides of slides.json, or the whole document when it isn't a list
+fn slides_of(slides: &Value) -> Vec<&Value> {
+    match slides
+        .get("slides")
+        .and_then(Value::as_array)
+        .or_else(|| slides.as_array())
+    {
+        Some(list) => list.iter().collect(),
+        None => vec![slides],
+    }
+}
+
+/// Installed families to use instead of `family`, best first
+fn suggestions(family: &str, installed: &[String]) -> Vec<FontSuggestion> {
+    let mut found: Vec<FontSuggestion> = Vec::new();
+    let mut add = |suggestion: &str, reason: SuggestionReason| {
+        if !found
+            .iter()
+            .any(|f| f.family.eq_ignore_ascii_case(suggestion))
+        {
+            found.push(FontSuggestion {
+                family: suggestion.to_string(),
+                reason,
+            });
+        }
+    };
+    let installed_named = |name: &str| {
+        let key = squash(name);
+        installed.iter().find(|family| squash(family) == key)
+    };
+
+    // "OpenSans" for "Open Sans", or "Lato" for "Lato Black"
+    if let Some(same) = installed_named(family).or_else(|| installed_named(&base_name(family))) {
+        add(same, SuggestionReason::SameFamily);
+    }
+    for group in METRIC_COMPATIBLE {
+        if group.iter().any(|name| squash(name) == squash(family)) {
+            for name in group.iter() {
+                if let Some(compatible) = installed_named(name) {
+                    add(compatible, SuggestionReason::MetricCompatible);
+                }
+            }
+        }
+    }
+    // Families sharing the first word, as "Source Sans 3" and "Source Sans Pro"
+    let first_word = family
+        .split_whitespace()
+        .next()
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    if first_word.len() >= 4 {
+        let mut similar: Vec<&String> = installed
+            .iter()
+            .filter(|name| {
+                name.split_whitespace()
+                    .next()
+                    .is_some_and(|word| word.eq_ignore_ascii_case(&first_word))
+            })
+            .collect();
+        similar.sort_by_key(|name| name.len());
+        for name in similar {
+            add(name, SuggestionReason::SimilarName);
+        }
+    }
+    add(generic_family(family), SuggestionReason::Generic);
+
+    found.truncate(MAX_SUGGESTIONS);
+    found
+}
+
+/// The family without style words at the end: "Montserrat" for
+/// "Montserrat ExtraBold Italic"
+fn base_name(family: &str) -> String {
+    let mut words: Vec<&str> = family.split([' ', '-']).filter(|w| !w.is_empty()).collect();
+    while words.len() > 1
+        && words
+            .last()
+            .is_some_and(|word| STYLE_WORDS.contains(&word.to_lowercase().as_str()))
+    {
+        words.pop();
+    }
+    words.join(" ")
+}
+
+/// Lowercase letters and digits only, so "Open Sans" matches "OpenSans"
+fn squash(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// A guess at the kind of font from its name
+fn generic_family(family: &str) -> &'static str {
+    let name = family.to_lowercase();
+    if ["mono", "code", "courier", "console", "typewriter"]
+        .iter()
+        .any(|word| name.contains(word))
+    {
+        "monospace"
+    } else if ["script", "hand", "brush", "marker", "signature"]
+        .iter()
+        .any(|word| name.contains(word))
+    {
+        "cursive"
+    } else if name.contains("sans") {
+        "sans-serif"
+    } else if [
+        "serif",
+        "times",
+        "garamond",
+        "georgia",
+        "roman",
+        "baskerville",
+    ]
+    .iter()
+    .any(|word| name.contains(word))
+    {
+        "serif"
+    } else {
+        "sans-serif"
+    }
+}
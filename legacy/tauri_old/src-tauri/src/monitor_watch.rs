@@ -0,0 +1,375 @@
+//! Noticing displays being connected, disconnected, or rearranged
+//!
+//! Tauri has no display-change event, so a background thread compares the
+//! monitor list every couple of seconds, which is cheap, and emits
+//! `monitors:changed` with the new list when it differs. With auto-reopen on,
+//! the output windows are then put back on the monitors they were opened on:
+//! reopened on a projector plugged back in, and moved when positions change.
+//! Monitors are matched by their stable ids, so a window follows its
+//! projector rather than a position in the list.
+//!
+//! An output can also be windowed rather than fullscreen, at a size and
+//! place on its monitor: the part of an LED wall processor's input the wall
+//! shows, or a corner of a display shared during rehearsal. That geometry is
+//! kept here too, so reopened windows get it back.
+//!
+//! Each output also has a transform for displays that don't take the
+//! program as it is: the picture shape a 4:3 projector or an LED wall
+//! processor expects, part of the program cropped out, how it's scaled into
+//! the picture, black margins, and a quarter turn or more for side screens
+//! hung in portrait and LED panels mounted on their side. Output windows fetch theirs when they
+//! load, and get `output:transform` when it's changed or they're moved.
+//!
+//! Lobby TVs and overflow rooms can mirror an output: their windows are
+//! opened and reopened with the others and show the same program with the
+//! same signal, while fitting and color correction stay their own.
+//!
+//! The outputs that are open, mirrors included, are saved in the app data
+//! folder as they're opened and closed, and opened again when the app
+//! starts, before the main window has loaded. A computer restarted minutes
+//! before the service comes back with its outputs up; projectors that
+//! aren't on yet get theirs when they're connected.
+//!
+//! Making an output's webview takes a few seconds the first time, showing
+//! white meanwhile. With pre-warming on, windows for the monitors outputs
+//! were last on are made hidden at startup, with the program loaded, and
+//! closing the outputs hides their windows rather than closing them, so
+//! showing them again is immediate.
+
+use crate::commands::{self, MonitorInfo};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const MONITORS_CHANGED_EVENT: &str = "monitors:changed";
+pub const OUTPUT_TRANSFORM_EVENT: &str = "output:transform";
+pub const OUTPUT_MIRROR_EVENT: &str = "output:mirror";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+const OPEN_OUTPUTS_FILENAME: &str = "open-outputs.json";
+
+/// Where a windowed output sits, in physical pixels from its monitor's
+/// top-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// How the program is fitted into the picture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputScale {
+    /// All of it, letterboxed
+    #[default]
+    Fit,
+    /// Covering the picture, with the overflow cut off
+    Fill,
+    /// Covering the picture, out of proportion
+    Stretch,
+}
+
+/// Part of the program, as fractions of its width and height
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OutputCrop {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Physical pixels kept black at each edge of the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct OutputMargins {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+/// How an output fits the program to its display
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OutputTransform {
+    /// Width over height of the picture the display shows, when its pixels
+    /// aren't square; None for the window's own shape
+    pub aspect: Option<f64>,
+    /// Part of the program shown; None for all of it
+    pub crop: Option<OutputCrop>,
+    pub scale: OutputScale,
+    pub margins: OutputMargins,
+    /// Degrees the picture is turned clockwise: 0, 90, 180 or 270
+    pub rotation: u16,
+}
+
+impl OutputTransform {
+    /// The transform with a usable aspect, a crop inside the program and a
+    /// whole number of quarter turns
+    pub fn normalized(mut self) -> Self {
+        self.aspect = self
+            .aspect
+            .filter(|aspect| aspect.is_finite() && *aspect > 0.0);
+        self.crop = self.crop.and_then(|crop| {
+            let x = crop.x.clamp(0.0, 1.0);
+            let y = crop.y.clamp(0.0, 1.0);
+            let width = crop.width.min(1.0 - x);
+            let height = crop.height.min(1.0 - y);
+            // Too small to be anything but a mistake
+            (width >= 0.01 && height >= 0.01).then_some(OutputCrop {
+                x,
+                y,
+                width,
+                height,
+            })
+        });
+        // To the nearest quarter turn
+        self.rotation = (self.rotation % 360 + 45) / 90 % 4 * 90;
+        self
+    }
+}
+
+/// The outputs open when the app last ran
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct OpenOutputs {
+    monitor_ids: Vec<String>,
+    mirrors: HashMap<String, String>,
+    /// Monitors outputs were last open on, kept after they're closed
+    recent: Vec<String>,
+    prewarm: bool,
+}
+
+/// Managed state: the monitors output windows were last opened on
+#[derive(Default)]
+pub struct OutputMonitors {
+    /// Where the open outputs are saved
+    path: Option<PathBuf>,
+    wanted: Mutex<Vec<String>>,
+    auto_reopen: AtomicBool,
+    /// Geometry of the windowed outputs by monitor id; the others are
+    /// fullscreen
+    windowed: Mutex<HashMap<String, OutputGeometry>>,
+    /// Transforms by monitor id; the others show the program as it is
+    transforms: Mutex<HashMap<String, OutputTransform>>,
+    /// The output each mirror shows, by the mirror's monitor id
+    mirrors: Mutex<HashMap<String, String>>,
+    /// Monitor ids outputs were last open on
+    recent: Mutex<Vec<String>>,
+    /// Whether output windows are kept hidden, ready to show, when closed
+    prewarm: AtomicBool,
+}
+
+impl OutputMonitors {
+    fn new(app_data_dir: Option<&Path>) -> Self {
+        let outputs = Self {
+            path: app_data_dir.map(|dir| dir.join(OPEN_OUTPUTS_FILENAME)),
+            ..Self::default()
+        };
+        let open = outputs.load_open();
+        outputs.prewarm.store(open.prewarm, Ordering::SeqCst);
+        if let Ok(mut recent) = outputs.recent.lock() {
+            *recent = open.recent;
+        }
+        outputs
+    }
+
+    /// Remember the monitor ids output windows should be on, and save them
+    /// with the mirrors for the next launch; empty when they're closed
+    pub fn set_wanted(&self, ids: Vec<String>) {
+        if !ids.is_empty() {
+            if let Ok(mut recent) = self.recent.lock() {
+                recent.clone_from(&ids);
+            }
+        }
+        if let Ok(mut wanted) = self.wanted.lock() {
+            *wanted = ids;
+        }
+        if let Err(e) = self.save_open() {
+            log::warn!("Could not save the open outputs: {e}");
+        }
+    }
+
+    fn save_open(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let monitor_ids = self.wanted();
+        let mirrors = if monitor_ids.is_empty() {
+            HashMap::new()
+        } else {
+            self.mirrors
+                .lock()
+                .map(|mirrors| mirrors.clone())
+                .unwrap_or_default()
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let open = OpenOutputs {
+            monitor_ids,
+            mirrors,
+            recent: self.recent(),
+            prewarm: self.prewarm(),
+        };
+        std::fs::write(path, serde_json::to_vec_pretty(&open)?)
+    }
+
+    fn load_open(&self) -> OpenOutputs {
+        self.path
+            .as_ref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Keep output windows hidden, ready to show, while closed, and save
+    /// that for the next launch
+    pub fn set_prewarm(&self, enabled: bool) {
+        self.prewarm.store(enabled, Ordering::SeqCst);
+        if let Err(e) = self.save_open() {
+            log::warn!("Could not save the open outputs: {e}");
+        }
+    }
+
+    pub fn prewarm(&self) -> bool {
+        self.prewarm.load(Ordering::SeqCst)
+    }
+
+    /// Monitor ids outputs were last open on
+    pub fn recent(&self) -> Vec<String> {
+        self.recent
+            .lock()
+            .map(|recent| recent.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_auto_reopen(&self, enabled: bool) {
+        self.auto_reopen.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Make the outputs on the monitors in `windowed` windows of the given
+    /// geometry, and the others fullscreen
+    pub fn set_windowed(&self, windowed: HashMap<String, OutputGeometry>) {
+        if let Ok(mut current) = self.windowed.lock() {
+            *current = windowed;
+        }
+    }
+
+    /// Geometry of the output on the monitor with `id`, or None when it's
+    /// fullscreen
+    pub fn windowed(&self, id: &str) -> Option<OutputGeometry> {
+        self.windowed
+            .lock()
+            .ok()
+            .and_then(|windowed| windowed.get(id).copied())
+    }
+
+    /// Give the outputs on the monitors in `transforms` those transforms,
+    /// and the others none
+    pub fn set_transforms(&self, transforms: HashMap<String, OutputTransform>) {
+        if let Ok(mut current) = self.transforms.lock() {
+            *current = transforms
+                .into_iter()
+                .map(|(id, transform)| (id, transform.normalized()))
+                .collect();
+        }
+    }
+
+    /// Transform of the output on the monitor with `id`
+    pub fn transform(&self, id: &str) -> OutputTransform {
+        self.transforms
+            .lock()
+            .ok()
+            .and_then(|transforms| transforms.get(id).copied())
+            .unwrap_or_default()
+    }
+
+    /// Make the monitors in `mirrors` show the output on the monitor each
+    /// maps to, and the others their own
+    pub fn set_mirrors(&self, mirrors: HashMap<String, String>) {
+        if let Ok(mut current) = self.mirrors.lock() {
+            *current = mirrors;
+        }
+    }
+
+    /// Id of the monitor whose output the monitor with `id` mirrors
+    pub fn mirror_of(&self, id: &str) -> Option<String> {
+        self.mirrors
+            .lock()
+            .ok()
+            .and_then(|mirrors| mirrors.get(id).cloned())
+    }
+
+    /// Monitor ids output windows should be on
+    pub fn wanted(&self) -> Vec<String> {
+        self.wanted
+            .lock()
+            .map(|wanted| wanted.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Start watching the monitors
+pub fn init(app: &AppHandle) {
+    let app_data_dir = app.path().app_data_dir().ok();
+    app.manage(OutputMonitors::new(app_data_dir.as_deref()));
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        let mut last: Option<Vec<MonitorInfo>> = None;
+        loop {
+            match commands::list_monitors(&handle) {
+                Ok(monitors) => {
+                    if last.as_ref().is_some_and(|last| *last != monitors) {
+                        changed(&handle, &monitors);
+                    }
+                    last = Some(monitors);
+                }
+                Err(e) => log::warn!("Could not list the monitors: {e}"),
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+/// Open the outputs that were open when the app last quit, and with
+/// pre-warming on make the others hidden
+pub fn restore(app: &AppHandle) {
+    let outputs = app.state::<OutputMonitors>();
+    let open = outputs.load_open();
+    if open.monitor_ids.is_empty() {
+        if outputs.prewarm() {
+            commands::prewarm_outputs(app);
+        }
+        return;
+    }
+    log::info!(
+        "Reopening {} output windows from the last run",
+        open.monitor_ids.len()
+    );
+    outputs.set_mirrors(open.mirrors);
+    match commands::open_outputs(app, &[], &open.monitor_ids) {
+        Ok(wanted) => outputs.set_wanted(wanted),
+        Err(e) => log::warn!("Could not reopen the output windows: {e}"),
+    }
+}
+
+fn changed(app: &AppHandle, monitors: &[MonitorInfo]) {
+    log::info!("Monitors changed: {} connected", monitors.len());
+    let _ = app.emit(MONITORS_CHANGED_EVENT, monitors);
+
+    let outputs = app.state::<OutputMonitors>();
+    let wanted = outputs.wanted();
+    if !outputs.auto_reopen.load(Ordering::SeqCst) || wanted.is_empty() {
+        return;
+    }
+    if let Err(e) = commands::open_outputs(app, &[], &wanted) {
+        log::warn!("Could not reopen the output windows: {e}");
+    }
+}
@@ -0,0 +1,412 @@
+//! Sending the presentation as an NDI source
+//!
+//! A livestream computer running OBS or vMix can take the slides over the
+//! network as an NDI source instead of through a capture card. The slides
+//! are drawn offscreen (see `output_frames`) at the configured resolution,
+//! and sent at a steady frame rate, repeating the last frame between slide
+//! changes as receivers expect. Video and web layers, which the renderer
+//! skips, are missing from the feed.
+//!
+//! NDI itself is a free runtime from Vizrt that can't be shipped with the
+//! app. It's loaded when the output starts: from `CHURCH_PRESENTER_NDI`,
+//! where the NDI installer puts it, or from the system library path.
+
+use crate::cpres::CpresError;
+use crate::output_frames::{self, OutputFrames};
+use libloading::Library;
+use serde::{Deserialize, Serialize};
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use thiserror::Error;
+
+const ENV_OVERRIDE: &str = "CHURCH_PRESENTER_NDI";
+/// How often receivers are counted
+const CONNECTIONS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `NDIlib_FourCC_video_type_RGBX`: 8-bit RGB with the fourth byte unused
+const FOURCC_RGBX: u32 = u32::from_le_bytes(*b"RGBX");
+/// `NDIlib_frame_format_type_progressive`
+const FRAME_FORMAT_PROGRESSIVE: c_int = 1;
+/// `NDIlib_send_timecode_synthesize`
+const TIMECODE_SYNTHESIZE: i64 = i64::MAX;
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// The frame being sent, swapped whole when the slide changes
+type SharedFrame = Arc<Mutex<Arc<Vec<u8>>>>;
+
+#[derive(Error, Debug)]
+pub enum NdiError {
+    #[error("The NDI runtime is not installed; get it from ndi.video or set {ENV_OVERRIDE}")]
+    RuntimeNotFound,
+
+    #[error("NDI error: {0}")]
+    Failed(String),
+
+    #[error(transparent)]
+    Render(#[from] CpresError),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NdiOptions {
+    /// Source name shown in OBS and vMix, after the computer's name
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: u32,
+}
+
+impl Default for NdiOptions {
+    fn default() -> Self {
+        Self {
+            name: "Church Presenter".to_string(),
+            width: 1920,
+            height: 1080,
+            frame_rate: 30,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NdiStatus {
+    pub running: bool,
+    /// Whether the NDI runtime could be loaded
+    pub runtime_available: bool,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: u32,
+    /// Receivers showing the source
+    pub connections: u32,
+}
+
+/// Managed state: the running sender, and the bundle slides are drawn from
+#[derive(Default)]
+pub struct NdiOutput {
+    sender: Mutex<Option<Sender>>,
+    /// The bundle and slide being shown, kept so a restart at another size
+    /// can draw it again; None shows black
+    showing: Mutex<Option<(PathBuf, String)>>,
+    frames: Mutex<OutputFrames>,
+}
+
+struct Sender {
+    options: NdiOptions,
+    frame: SharedFrame,
+    stop: Arc<AtomicBool>,
+    connections: Arc<AtomicU32>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl NdiOutput {
+    /// Start sending, replacing a running sender so new options take effect
+    pub fn start(&self, options: NdiOptions) -> Result<NdiStatus, NdiError> {
+        let options = NdiOptions {
+            name: match options.name.trim() {
+                "" => NdiOptions::default().name,
+                name => name.to_string(),
+            },
+            width: options.width.clamp(16, 7680) & !1,
+            height: options.height.clamp(16, 4320) & !1,
+            frame_rate: options.frame_rate.clamp(1, 60),
+        };
+        self.stop();
+        let runtime = runtime()?;
+        let name = CString::new(options.name.clone())
+            .map_err(|_| NdiError::Failed("The source name can't contain NUL".to_string()))?;
+        let settings = SendCreate {
+            ndi_name: name.as_ptr(),
+            groups: std::ptr::null(),
+            clock_video: true,
+            clock_audio: false,
+        };
+        let instance = unsafe { (runtime.send_create)(&settings) };
+        if instance.is_null() {
+            return Err(NdiError::Failed(format!(
+                "Could not create the NDI source {}",
+                options.name
+            )));
+        }
+        let instance = Instance(instance);
+
+        let frame = Arc::new(Mutex::new(Arc::new(output_frames::black(
+            options.width,
+            options.height,
+        ))));
+        let stop = Arc::new(AtomicBool::new(false));
+        let connections = Arc::new(AtomicU32::new(0));
+        let thread = {
+            let (options, frame, stop, connections) = (
+                options.clone(),
+                frame.clone(),
+                stop.clone(),
+                connections.clone(),
+            );
+            std::thread::spawn(move || {
+                send_loop(runtime, instance, &options, &frame, &stop, &connections)
+            })
+        };
+        log::info!(
+            "Sending NDI source {} at {}x{}, {} fps",
+            options.name,
+            options.width,
+            options.height,
+            options.frame_rate
+        );
+        *self.lock_sender() = Some(Sender {
+            options,
+            frame,
+            stop,
+            connections,
+            thread: Some(thread),
+        });
+
+        let showing = self.showing.lock().ok().and_then(|showing| showing.clone());
+        if let Some((path, slide_id)) = showing {
+            if let Err(e) = self.show(&path, &slide_id) {
+                log::warn!("Could not draw the slide for NDI: {e}");
+            }
+        }
+        Ok(self.status())
+    }
+
+    /// Stop sending; receivers see the source disappear
+    pub fn stop(&self) {
+        let sender = self.lock_sender().take();
+        if let Some(mut sender) = sender {
+            sender.stop.store(true, Ordering::SeqCst);
+            if let Some(thread) = sender.thread.take() {
+                let _ = thread.join();
+            }
+            log::info!("Stopped NDI source {}", sender.options.name);
+        }
+    }
+
+    /// Send the slide with `slide_id` from the bundle at `path`, drawn
+    /// offscreen; the bundle is kept open for the next slide until it's
+    /// saved again
+    pub fn show(&self, path: &Path, slide_id: &str) -> Result<(), NdiError> {
+        if let Ok(mut showing) = self.showing.lock() {
+            *showing = Some((path.to_path_buf(), slide_id.to_string()));
+        }
+        let Some((options, frame)) = self.target() else {
+            return Ok(());
+        };
+
+        let data = self
+            .frames
+            .lock()
+            .map_err(|e| NdiError::Failed(e.to_string()))?
+            .render(path, slide_id, options.width, options.height)?;
+        if let Ok(mut frame) = frame.lock() {
+            *frame = Arc::new(data);
+        }
+        Ok(())
+    }
+
+    /// Send black, as when the presentation is cleared
+    pub fn clear(&self) {
+        if let Ok(mut showing) = self.showing.lock() {
+            *showing = None;
+        }
+        if let Some((options, frame)) = self.target() {
+            if let Ok(mut frame) = frame.lock() {
+                *frame = Arc::new(output_frames::black(options.width, options.height));
+            }
+        }
+    }
+
+    pub fn status(&self) -> NdiStatus {
+        let sender = self.lock_sender();
+        let options = sender
+            .as_ref()
+            .map_or_else(NdiOptions::default, |sender| sender.options.clone());
+        NdiStatus {
+            running: sender.is_some(),
+            runtime_available: runtime().is_ok(),
+            name: options.name,
+            width: options.width,
+            height: options.height,
+            frame_rate: options.frame_rate,
+            connections: sender
+                .as_ref()
+                .map_or(0, |sender| sender.connections.load(Ordering::SeqCst)),
+        }
+    }
+
+    /// The running sender's options and frame, or None when it's stopped
+    fn target(&self) -> Option<(NdiOptions, SharedFrame)> {
+        self.lock_sender()
+            .as_ref()
+            .map(|sender| (sender.options.clone(), sender.frame.clone()))
+    }
+
+    fn lock_sender(&self) -> std::sync::MutexGuard<'_, Option<Sender>> {
+        self.sender
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Send the current frame until stopped; the runtime's clock paces it
+fn send_loop(
+    runtime: &Runtime,
+    instance: Instance,
+    options: &NdiOptions,
+    frame: &Mutex<Arc<Vec<u8>>>,
+    stop: &AtomicBool,
+    connections: &AtomicU32,
+) {
+    let mut counted = Instant::now() - CONNECTIONS_INTERVAL;
+    while !stop.load(Ordering::SeqCst) {
+        let Ok(data) = frame.lock().map(|frame| frame.clone()) else {
+            break;
+        };
+        let video = VideoFrame {
+            xres: options.width as c_int,
+            yres: options.height as c_int,
+            four_cc: FOURCC_RGBX,
+            frame_rate_n: options.frame_rate as c_int,
+            frame_rate_d: 1,
+            picture_aspect_ratio: options.width as f32 / options.height as f32,
+            frame_format_type: FRAME_FORMAT_PROGRESSIVE,
+            timecode: TIMECODE_SYNTHESIZE,
+            data: data.as_ptr(),
+            line_stride_in_bytes: options.width as c_int * 4,
+            metadata: std::ptr::null(),
+            timestamp: 0,
+        };
+        // Returns once the frame is copied, at the frame rate
+        unsafe { (runtime.send_video)(instance.0, &video) };
+        if counted.elapsed() >= CONNECTIONS_INTERVAL {
+            let count = unsafe { (runtime.connections)(instance.0, 0) };
+            connections.store(count.max(0) as u32, Ordering::SeqCst);
+            counted = Instant::now();
+        }
+    }
+    unsafe { (runtime.send_destroy)(instance.0) };
+}
+
+/// `NDIlib_send_create_t`
+#[repr(C)]
+struct SendCreate {
+    ndi_name: *const c_char,
+    groups: *const c_char,
+    clock_video: bool,
+    clock_audio: bool,
+}
+
+/// `NDIlib_video_frame_v2_t`
+#[repr(C)]
+struct VideoFrame {
+    xres: c_int,
+    yres: c_int,
+    four_cc: u32,
+    frame_rate_n: c_int,
+    frame_rate_d: c_int,
+    picture_aspect_ratio: f32,
+    frame_format_type: c_int,
+    timecode: i64,
+    data: *const u8,
+    line_stride_in_bytes: c_int,
+    metadata: *const c_char,
+    timestamp: i64,
+}
+
+/// An `NDIlib_send_instance_t`, used only by the thread sending on it
+struct Instance(*mut c_void);
+
+unsafe impl Send for Instance {}
+
+/// The NDI runtime's sending functions
+struct Runtime {
+    send_create: unsafe extern "C" fn(*const SendCreate) -> *mut c_void,
+    send_destroy: unsafe extern "C" fn(*mut c_void),
+    send_video: unsafe extern "C" fn(*mut c_void, *const VideoFrame),
+    connections: unsafe extern "C" fn(*mut c_void, u32) -> c_int,
+    /// Kept loaded for the functions above
+    _library: Library,
+}
+
+/// The runtime, loaded and initialized on first use; a failed load is tried
+/// again next time, in case NDI was installed since
+fn runtime() -> Result<&'static Runtime, NdiError> {
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+    let runtime = load()?;
+    let _ = RUNTIME.set(runtime);
+    RUNTIME.get().ok_or(NdiError::RuntimeNotFound)
+}
+
+fn load() -> Result<Runtime, NdiError> {
+    let library = candidates()
+        .into_iter()
+        .find_map(|candidate| unsafe { Library::new(&candidate) }.ok())
+        .ok_or(NdiError::RuntimeNotFound)?;
+    let missing = |e: libloading::Error| NdiError::Failed(format!("Unsupported NDI runtime: {e}"));
+    unsafe {
+        let initialize = *library
+            .get::<unsafe extern "C" fn() -> bool>(b"NDIlib_initialize\0")
+            .map_err(missing)?;
+        let runtime = Runtime {
+            send_create: *library.get(b"NDIlib_send_create\0").map_err(missing)?,
+            send_destroy: *library.get(b"NDIlib_send_destroy\0").map_err(missing)?,
+            send_video: *library
+                .get(b"NDIlib_send_send_video_v2\0")
+                .map_err(missing)?,
+            connections: *library
+                .get(b"NDIlib_send_get_no_connections\0")
+                .map_err(missing)?,
+            _library: library,
+        };
+        if !initialize() {
+            return Err(NdiError::Failed(
+                "NDI isn't supported on this computer's processor".to_string(),
+            ));
+        }
+        Ok(runtime)
+    }
+}
+
+/// Where to look for the runtime, in order
+fn candidates() -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = std::env::var_os(ENV_OVERRIDE)
+        .map(PathBuf::from)
+        .into_iter()
+        .collect();
+    if cfg!(target_os = "windows") {
+        let name = "Processing.NDI.Lib.x64.dll";
+        for variable in ["NDI_RUNTIME_DIR_V6", "NDI_RUNTIME_DIR_V5"] {
+            if let Some(dir) = std::env::var_os(variable) {
+                candidates.push(PathBuf::from(dir).join(name));
+            }
+        }
+        candidates.push(PathBuf::from(name));
+    } else if cfg!(target_os = "macos") {
+        candidates.push(PathBuf::from("/usr/local/lib/libndi.dylib"));
+        candidates.push(PathBuf::from(
+            "/Library/NDI SDK for Apple/lib/macOS/libndi.dylib",
+        ));
+        candidates.push(PathBuf::from("libndi.dylib"));
+    } else {
+        candidates.extend(["libndi.so.6", "libndi.so.5", "libndi.so"].map(PathBuf::from));
+    }
+    candidates
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(NdiOutput::default());
+}
+
+pub fn shutdown(app: &AppHandle) {
+    if let Some(ndi) = app.try_state::<NdiOutput>() {
+        ndi.stop();
+    }
+}
@@ -0,0 +1,344 @@
+//! OpenLyrics song import and export
+//!
+//! OpenLyrics (<https://docs.openlyrics.org>) is an XML format with one song
+//! per file:
+//! - `properties` holds the titles, authors, and `verseOrder`, a list of
+//!   verse names such as `v1 c v2 c`
+//! - `lyrics` holds `<verse name="v1">` elements, each with one or more
+//!   `<lines>` elements; each `<lines>` is one slide and `<br/>` breaks a line
+//!
+//! Imported songs become .cpres files in a library folder. Export writes the
+//! arrangement's sections as verses and its order as `verseOrder`.
+
+use crate::cpres::{self, CpresError};
+use crate::export;
+use crate::importer::{self, LibraryImport, LyricSection};
+use crate::song_import;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+const NAMESPACE: &str = "http://openlyrics.info/namespace/2009/song";
+const VERSION: &str = "0.9";
+const APPLICATION: &str = "Church Presenter";
+
+/// Import an OpenLyrics file, or a folder of them, as .cpres files in `output_dir`
+pub fn import_openlyrics(path: &Path, output_dir: &Path) -> Result<LibraryImport, CpresError> {
+    let mut files = Vec::new();
+    if path.is_dir() {
+        song_import::collect_files(path, 0, &mut files)?;
+        files.retain(|file| {
+            file.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("xml"))
+        });
+    } else {
+        files.push(path.to_path_buf());
+    }
+
+    let mut report = LibraryImport::default();
+    for file in files {
+        let fallback_title = file
+            .file_stem()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+            .to_string();
+        match parse_song(&file) {
+            Ok(Some(mut song)) => {
+                if song.title.trim().is_empty() {
+                    song.title = fallback_title;
+                }
+                report.save(output_dir, song);
+            }
+            // Some other XML file in the folder
+            Ok(None) => {}
+            Err(e) => report.skip(fallback_title, e),
+        }
+    }
+    Ok(report)
+}
+
+/// Parse one OpenLyrics file; None when the file isn't an OpenLyrics song
+fn parse_song(path: &Path) -> Result<Option<importer::ImportedPresentation>, String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    let xml = String::from_utf8_lossy(&data);
+    let doc = roxmltree::Document::parse(&xml).map_err(|e| format!("Invalid XML: {e}"))?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "song" || root.tag_name().namespace() != Some(NAMESPACE) {
+        return Ok(None);
+    }
+
+    let Some(properties) = named(root, "properties").next() else {
+        return Err("Missing <properties>".to_string());
+    };
+    let title = named(properties, "title")
+        .next()
+        .and_then(|n| n.text())
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    let authors: Vec<String> = named(properties, "author")
+        .filter_map(|n| n.text())
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+        .collect();
+    let verse_order = named(properties, "verseOrder")
+        .next()
+        .and_then(|n| n.text())
+        .unwrap_or_default()
+        .to_string();
+
+    let mut sections = Vec::new();
+    let mut tags: Vec<String> = Vec::new();
+    for verse in named(root, "lyrics").flat_map(|lyrics| named(lyrics, "verse")) {
+        let name = verse.attribute("name").unwrap_or_default().trim();
+        // Translations repeat verse names in other languages; keep the first
+        if name.is_empty() || tags.iter().any(|t| t.eq_ignore_ascii_case(name)) {
+            continue;
+        }
+
+        let slides: Vec<String> = named(verse, "lines")
+            .map(lines_text)
+            .filter(|text| !text.is_empty())
+            .collect();
+        if slides.is_empty() {
+            continue;
+        }
+        sections.push(LyricSection {
+            label: importer::label_for_tag(name),
+            slides,
+        });
+        tags.push(name.to_lowercase());
+    }
+    if sections.is_empty() {
+        return Err("Song has no lyrics".to_string());
+    }
+
+    let order = song_import::section_order(&verse_order, &tags);
+    Ok(Some(importer::song_presentation(
+        title,
+        (!authors.is_empty()).then(|| authors.join(", ")),
+        sections,
+        order,
+    )))
+}
+
+/// Descendants of `parent` with the local name `name`, in document order
+fn named<'a, 'input>(
+    parent: roxmltree::Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    parent
+        .descendants()
+        .filter(move |n| n.tag_name().name() == name)
+}
+
+/// Text of a `<lines>` element: `<br/>` and 0.8-style `<line>` break lines,
+/// chords and comments are dropped
+fn lines_text(lines: roxmltree::Node) -> String {
+    fn collect(node: roxmltree::Node, out: &mut String) {
+        for child in node.children() {
+            if child.is_text() {
+                // Source indentation isn't part of the lyrics
+                let text = child.text().unwrap_or_default();
+                out.push_str(&text.split_whitespace().collect::<Vec<_>>().join(" "));
+                if text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+                    out.push(' ');
+                }
+                continue;
+            }
+            match child.tag_name().name() {
+                "br" => out.push('\n'),
+                "comment" => {}
+                "line" => {
+                    collect(child, out);
+                    out.push('\n');
+                }
+                _ => collect(child, out),
+            }
+        }
+    }
+    let mut text = String::new();
+    collect(lines, &mut text);
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Write a bundle's lyrics as an OpenLyrics file at `output`
+pub fn export_openlyrics(bundle: &Path, output: &Path) -> Result<(), CpresError> {
+    let parsed = cpres::open_bundle(bundle)?;
+    let manifest: Value = serde_json::from_str(&parsed.manifest)?;
+    let slides: Vec<Value> = serde_json::from_str(&parsed.slides)?;
+    let arrangement: Value = serde_json::from_str(&parsed.arrangement)?;
+
+    let slide_text: HashMap<&str, String> = slides
+        .iter()
+        .filter_map(|slide| Some((slide.get("id")?.as_str()?, text_of(slide))))
+        .collect();
+
+    // Verses from the arrangement's sections, then any slides outside them
+    let mut verses: Vec<(String, Vec<&str>)> = Vec::new();
+    let mut verse_of: HashMap<&str, usize> = HashMap::new();
+    let mut used_names: HashMap<String, u32> = HashMap::new();
+    let mut others = 0;
+    let sections = arrangement.get("sections").and_then(Value::as_array);
+    for section in sections.into_iter().flatten() {
+        let label = section.get("label").and_then(Value::as_str).unwrap_or("");
+        let ids: Vec<&str> = section
+            .get("slideIds")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .filter(|id| slide_text.get(id).is_some_and(|t| !t.is_empty()))
+            .filter(|id| !verse_of.contains_key(id))
+            .collect();
+        if ids.is_empty() {
+            continue;
+        }
+        let name = unique_name(tag_for_label(label), &mut used_names);
+        for id in &ids {
+            verse_of.insert(id, verses.len());
+        }
+        verses.push((name, ids));
+    }
+    let order: Vec<&str> = match arrangement.get("order").and_then(Value::as_array) {
+        Some(order) if !order.is_empty() => order.iter().filter_map(Value::as_str).collect(),
+        _ => slides
+            .iter()
+            .filter_map(|s| s.get("id")?.as_str())
+            .collect(),
+    };
+    for id in &order {
+        if !verse_of.contains_key(id) && slide_text.get(id).is_some_and(|t| !t.is_empty()) {
+            others += 1;
+            let name = unique_name(format!("o{others}"), &mut used_names);
+            verse_of.insert(id, verses.len());
+            verses.push((name, vec![id]));
+        }
+    }
+    if verses.is_empty() {
+        return Err(CpresError::InvalidBundle(
+            "Presentation has no lyrics to export".to_string(),
+        ));
+    }
+
+    // A verse is sung again whenever its first slide comes round or another verse came between
+    let mut verse_order: Vec<&str> = Vec::new();
+    let mut previous = None;
+    for id in &order {
+        let Some(&verse) = verse_of.get(id) else {
+            continue;
+        };
+        if previous != Some(verse) || verses[verse].1.first() == Some(id) {
+            verse_order.push(&verses[verse].0);
+        }
+        previous = Some(verse);
+    }
+
+    let title = manifest
+        .get("title")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let authors: Vec<&str> = manifest
+        .get("author")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .split([',', '&', ';'])
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .collect();
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<song xmlns=\"{NAMESPACE}\" version=\"{VERSION}\" createdIn=\"{APPLICATION}\" modifiedIn=\"{APPLICATION}\" modifiedDate=\"{}\">\n",
+        importer::iso_now()
+    ));
+    xml.push_str("  <properties>\n    <titles>\n");
+    xml.push_str(&format!("      <title>{}</title>\n", escape(title)));
+    xml.push_str("    </titles>\n");
+    if !authors.is_empty() {
+        xml.push_str("    <authors>\n");
+        for author in authors {
+            xml.push_str(&format!("      <author>{}</author>\n", escape(author)));
+        }
+        xml.push_str("    </authors>\n");
+    }
+    xml.push_str(&format!(
+        "    <verseOrder>{}</verseOrder>\n",
+        verse_order.join(" ")
+    ));
+    xml.push_str("  </properties>\n  <lyrics>\n");
+    for (name, ids) in &verses {
+        xml.push_str(&format!("    <verse name=\"{name}\">\n"));
+        for id in ids {
+            let lines: Vec<String> = slide_text[id].lines().map(escape).collect();
+            xml.push_str(&format!("      <lines>{}</lines>\n", lines.join("<br/>")));
+        }
+        xml.push_str("    </verse>\n");
+    }
+    xml.push_str("  </lyrics>\n</song>\n");
+
+    export::write_atomic(output, xml.as_bytes())
+}
+
+/// Text of a slide's text layers, top to bottom in layer order
+fn text_of(slide: &Value) -> String {
+    slide
+        .get("layers")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|layer| layer.get("type").and_then(Value::as_str) == Some("text"))
+        .filter_map(|layer| layer.get("content").and_then(Value::as_str))
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// OpenLyrics verse name for a label, the reverse of `importer::label_for_tag`:
+/// "Verse 2" -> "v2", "Chorus" -> "c"; other labels become "o"
+fn tag_for_label(label: &str) -> String {
+    let lower = label.trim().to_lowercase();
+    let kind = [
+        ("pre-chorus", "p"),
+        ("prechorus", "p"),
+        ("chorus", "c"),
+        ("verse", "v"),
+        ("bridge", "b"),
+        ("tag", "t"),
+        ("intro", "i"),
+        ("ending", "e"),
+        ("outro", "o"),
+    ]
+    .into_iter()
+    .find(|(keyword, _)| lower.contains(keyword))
+    .map(|(_, kind)| kind)
+    .unwrap_or("o");
+    let number: String = lower.chars().filter(char::is_ascii_digit).collect();
+    format!("{kind}{number}")
+}
+
+/// `name`, or `name` with a letter suffix when a verse already uses it
+fn unique_name(name: String, used: &mut HashMap<String, u32>) -> String {
+    let count = used.entry(name.clone()).or_insert(0);
+    *count += 1;
+    if *count == 1 {
+        name
+    } else {
+        let suffix = (b'a' + (*count as u8 - 2).min(25)) as char;
+        format!("{name}{suffix}")
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
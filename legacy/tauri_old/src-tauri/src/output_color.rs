@@ -0,0 +1,141 @@
+//! Color correction for each output
+//!
+//! Projectors in the same room rarely match, so each output has its own
+//! brightness, contrast, gamma and red, green and blue gains, kept by
+//! monitor id in the app data folder. They belong to the projector rather
+//! than to any presentation or to the settings synced between computers.
+//! Output windows fetch theirs when they load and get `output:color` as
+//! it's adjusted, so calibration is seen live.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+pub const OUTPUT_COLOR_EVENT: &str = "output:color";
+
+const COLORS_FILENAME: &str = "output-colors.json";
+
+/// How an output's colors are corrected; each channel goes through
+/// `gain * contrast * x^(1 / gamma) + (1 - contrast) / 2 + brightness`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OutputColor {
+    /// Added to every channel, from -1 to 1
+    pub brightness: f64,
+    /// Spread around mid gray, from 0 to 3
+    pub contrast: f64,
+    /// Above 1 lifts the midtones, from 0.1 to 4
+    pub gamma: f64,
+    /// Each channel's gain, from 0 to 2, for matching color temperature
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+}
+
+impl Default for OutputColor {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            gamma: 1.0,
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+        }
+    }
+}
+
+impl OutputColor {
+    /// The correction with every value in its range
+    pub fn normalized(self) -> Self {
+        let clamp = |value: f64, min: f64, max: f64, default: f64| {
+            if value.is_finite() {
+                value.clamp(min, max)
+            } else {
+                default
+            }
+        };
+        Self {
+            brightness: clamp(self.brightness, -1.0, 1.0, 0.0),
+            contrast: clamp(self.contrast, 0.0, 3.0, 1.0),
+            gamma: clamp(self.gamma, 0.1, 4.0, 1.0),
+            red: clamp(self.red, 0.0, 2.0, 1.0),
+            green: clamp(self.green, 0.0, 2.0, 1.0),
+            blue: clamp(self.blue, 0.0, 2.0, 1.0),
+        }
+    }
+}
+
+/// Managed state: the color correction of each output, by monitor id
+pub struct OutputColors {
+    path: Option<PathBuf>,
+    colors: Mutex<HashMap<String, OutputColor>>,
+}
+
+impl OutputColors {
+    fn load(app_data_dir: Option<&Path>) -> Self {
+        let path = app_data_dir.map(|dir| dir.join(COLORS_FILENAME));
+        let colors = path
+            .as_ref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|content| {
+                serde_json::from_slice::<HashMap<String, OutputColor>>(&content).ok()
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            colors: Mutex::new(colors),
+        }
+    }
+
+    pub fn all(&self) -> HashMap<String, OutputColor> {
+        self.colors
+            .lock()
+            .map(|colors| colors.clone())
+            .unwrap_or_default()
+    }
+
+    /// Correction of the output on the monitor with `id`
+    pub fn get(&self, id: &str) -> OutputColor {
+        self.colors
+            .lock()
+            .ok()
+            .and_then(|colors| colors.get(id).copied())
+            .unwrap_or_default()
+    }
+
+    /// Correct the output on the monitor with `id` by `color`, or take its
+    /// correction off with None, and save
+    pub fn set(&self, id: &str, color: Option<OutputColor>) -> std::io::Result<OutputColor> {
+        let color = color.map(OutputColor::normalized);
+        let colors = {
+            let mut colors = self
+                .colors
+                .lock()
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
+            match color {
+                Some(color) if color != OutputColor::default() => {
+                    colors.insert(id.to_string(), color);
+                }
+                _ => {
+                    colors.remove(id);
+                }
+            }
+            colors.clone()
+        };
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(path, serde_json::to_vec_pretty(&colors)?)?;
+        }
+        Ok(color.unwrap_or_default())
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    let app_data_dir = app.path().app_data_dir().ok();
+    app.manage(OutputColors::load(app_data_dir.as_deref()));
+}
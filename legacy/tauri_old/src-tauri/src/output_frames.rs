@@ -0,0 +1,125 @@
+//! Slides drawn offscreen for the NDI, shared-texture, and DeckLink outputs
+//!
+//! Those outputs don't capture the output window; they draw the live slide
+//! of the saved bundle with the exporters' renderer, letterboxed on black at
+//! their own resolution, as opaque RGBA. The bundle stays open between
+//! slides, and is opened again once it's saved.
+//!
+//! For a broadcast keyer the slide can instead be split into fill and key:
+//! the fill is what's in front of the background (lyrics, overlay media) on
+//! black, and the key is how opaque that is, white where it covers the
+//! camera and black where the camera shows through. The keyer gets each as
+//! its own signal, so both are opaque here too.
+
+use crate::cpres::CpresError;
+use crate::render::SlideRenderer;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Which picture of the slide an output carries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputSignal {
+    /// The whole slide, background and all
+    #[default]
+    Program,
+    /// The slide's foreground on black, for the keyer's fill input
+    Fill,
+    /// The foreground's opacity in grayscale, for the keyer's key input
+    Key,
+}
+
+/// The bundle being drawn from
+#[derive(Default)]
+pub struct OutputFrames {
+    /// The open bundle and its modification time when opened
+    renderer: Option<(PathBuf, Option<SystemTime>, SlideRenderer)>,
+}
+
+impl OutputFrames {
+    /// The slide with `slide_id` from the bundle at `path`, `width` by
+    /// `height` pixels
+    pub fn render(
+        &mut self,
+        path: &Path,
+        slide_id: &str,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, CpresError> {
+        self.render_signal(path, slide_id, width, height, OutputSignal::Program)
+    }
+
+    /// The `signal` picture of the slide with `slide_id` from the bundle at
+    /// `path`, `width` by `height` pixels
+    pub fn render_signal(
+        &mut self,
+        path: &Path,
+        slide_id: &str,
+        width: u32,
+        height: u32,
+        signal: OutputSignal,
+    ) -> Result<Vec<u8>, CpresError> {
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let renderer = match &mut self.renderer {
+            Some((open, opened, renderer)) if open == path && *opened == modified => renderer,
+            renderer => {
+                let (_, _, open) =
+                    renderer.insert((path.to_path_buf(), modified, SlideRenderer::open(path)?));
+                open
+            }
+        };
+        let position = renderer
+            .position_of(slide_id)
+            .ok_or_else(|| CpresError::InvalidBundle(format!("No slide {slide_id}")))?;
+        // As wide as fits in the frame at the slide's aspect ratio
+        let slide_width =
+            ((height as f64 * renderer.aspect_ratio()).floor() as u32).clamp(1, width);
+        let pixmap = match signal {
+            OutputSignal::Program => renderer.render(position, slide_width)?,
+            OutputSignal::Fill | OutputSignal::Key => {
+                renderer.render_foreground(position, slide_width)?
+            }
+        };
+
+        let mut data = black(width, height);
+        let (frame_width, frame_height) = (width as usize, height as usize);
+        let (width, height) = (
+            (pixmap.width() as usize).min(frame_width),
+            (pixmap.height() as usize).min(frame_height),
+        );
+        let (left, top) = ((frame_width - width) / 2, (frame_height - height) / 2);
+        // Premultiplied color over black is the color itself
+        let source = pixmap.data();
+        for row in 0..height {
+            let from = row * pixmap.width() as usize * 4;
+            let to = ((top + row) * frame_width + left) * 4;
+            let (from, to) = (
+                &source[from..from + width * 4],
+                &mut data[to..to + width * 4],
+            );
+            if signal == OutputSignal::Key {
+                for (from, to) in from.chunks_exact(4).zip(to.chunks_exact_mut(4)) {
+                    to[..3].fill(from[3]);
+                }
+            } else {
+                to.copy_from_slice(from);
+            }
+        }
+        for pixel in data.chunks_exact_mut(4) {
+            pixel[3] = 255;
+        }
+        Ok(data)
+    }
+}
+
+/// An opaque black frame
+pub fn black(width: u32, height: u32) -> Vec<u8> {
+    let mut data = vec![0; width as usize * height as usize * 4];
+    for pixel in data.chunks_exact_mut(4) {
+        pixel[3] = 255;
+    }
+    data
+}
@@ -0,0 +1,185 @@
+//! Keeping output windows in front
+//!
+//! Output windows are opened always on top and fullscreen, but Windows
+//! notifications, update prompts and apps that grab the foreground can still
+//! end up over them, and a stray click can minimize one. A background thread
+//! checks the output windows every second and puts back what was lost:
+//! minimized or hidden windows are shown, fullscreen outputs that dropped out
+//! of it go back, and windows that lost their place on top, or have another
+//! window over their middle on Windows, are raised again without taking the
+//! focus from the operator's window.
+//!
+//! Blocking pop-ups goes further where the system allows it: on Windows the
+//! outputs are raised over other topmost windows, toasts included, four
+//! times a second, and on macOS they're lifted to the screen saver level,
+//! above notification banners. Linux desktops place notifications
+//! themselves, so there it does no more than keeping them on top.
+
+use crate::monitor_watch::OutputMonitors;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+const GUARD_INTERVAL: Duration = Duration::from_secs(1);
+/// Often enough that a pop-up is gone before it can be read
+const BLOCKING_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Managed state: what the guard does for the output windows
+pub struct OutputGuard {
+    keep_in_front: AtomicBool,
+    block_popups: AtomicBool,
+}
+
+impl Default for OutputGuard {
+    fn default() -> Self {
+        Self {
+            keep_in_front: AtomicBool::new(true),
+            block_popups: AtomicBool::new(false),
+        }
+    }
+}
+
+impl OutputGuard {
+    pub fn set(&self, keep_in_front: bool, block_popups: bool) {
+        self.keep_in_front.store(keep_in_front, Ordering::SeqCst);
+        self.block_popups.store(block_popups, Ordering::SeqCst);
+    }
+}
+
+/// Start guarding the output windows
+pub fn init(app: &AppHandle) {
+    app.manage(OutputGuard::default());
+    let handle = app.clone();
+    std::thread::spawn(move || loop {
+        let guard = handle.state::<OutputGuard>();
+        let keep_in_front = guard.keep_in_front.load(Ordering::SeqCst);
+        let block_popups = keep_in_front && guard.block_popups.load(Ordering::SeqCst);
+        if keep_in_front {
+            // Only outputs that are open; pre-warmed ones are hidden on purpose
+            let wanted = handle.state::<OutputMonitors>().wanted();
+            for (label, window) in handle.webview_windows() {
+                let open = label
+                    .strip_prefix("output-")
+                    .is_some_and(|id| wanted.iter().any(|wanted| wanted == id));
+                if open {
+                    restore(&handle, &label, &window, block_popups);
+                }
+            }
+        }
+        std::thread::sleep(if block_popups {
+            BLOCKING_INTERVAL
+        } else {
+            GUARD_INTERVAL
+        });
+    });
+}
+
+/// Put back whatever the output window `label` has lost
+fn restore(app: &AppHandle, label: &str, window: &tauri::WebviewWindow, block_popups: bool) {
+    if window.is_minimized().unwrap_or(false) {
+        log::info!("Output window {label} was minimized; restoring it");
+        let _ = window.unminimize();
+    }
+    if !window.is_visible().unwrap_or(true) {
+        log::info!("Output window {label} was hidden; showing it");
+        let _ = window.show();
+    }
+    let windowed = label
+        .strip_prefix("output-")
+        .and_then(|id| app.try_state::<OutputMonitors>()?.windowed(id));
+    if windowed.is_none() && !window.is_fullscreen().unwrap_or(true) {
+        log::info!("Output window {label} left fullscreen; putting it back");
+        let _ = window.set_fullscreen(true);
+    }
+    platform::raise(label, window, block_popups);
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows::Win32::Foundation::{HWND, POINT, RECT};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetAncestor, GetWindowLongW, GetWindowRect, SetWindowPos, WindowFromPoint, GA_ROOT,
+        GWL_EXSTYLE, HWND_TOPMOST, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOOWNERZORDER, SWP_NOSIZE,
+        WS_EX_TOPMOST,
+    };
+
+    /// Whether another window is over the middle of `hwnd`
+    fn covered(hwnd: HWND) -> bool {
+        let mut rect = RECT::default();
+        if unsafe { GetWindowRect(hwnd, &mut rect) }.is_err() {
+            return false;
+        }
+        let middle = POINT {
+            x: rect.left + (rect.right - rect.left) / 2,
+            y: rect.top + (rect.bottom - rect.top) / 2,
+        };
+        let top = unsafe { GetAncestor(WindowFromPoint(middle), GA_ROOT) };
+        !top.is_invalid() && top != hwnd
+    }
+
+    pub fn raise(label: &str, window: &tauri::WebviewWindow, block_popups: bool) {
+        let Ok(hwnd) = window.hwnd() else {
+            return;
+        };
+        let hwnd = HWND(hwnd.0);
+        let topmost = unsafe { GetWindowLongW(hwnd, GWL_EXSTYLE) } as u32 & WS_EX_TOPMOST.0 != 0;
+        if topmost && !block_popups && !covered(hwnd) {
+            return;
+        }
+        if !block_popups {
+            log::debug!("Output window {label} was covered; raising it");
+        }
+        let _ = unsafe {
+            SetWindowPos(
+                hwnd,
+                Some(HWND_TOPMOST),
+                0,
+                0,
+                0,
+                0,
+                SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE | SWP_NOOWNERZORDER,
+            )
+        };
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use objc2::msg_send;
+    use objc2::runtime::AnyObject;
+
+    /// `NSFloatingWindowLevel`, where always-on-top windows go
+    const FLOATING_LEVEL: isize = 3;
+    /// `NSScreenSaverWindowLevel`, above notification banners
+    const SCREEN_SAVER_LEVEL: isize = 1000;
+
+    pub fn raise(_label: &str, window: &tauri::WebviewWindow, block_popups: bool) {
+        let level = if block_popups {
+            SCREEN_SAVER_LEVEL
+        } else {
+            FLOATING_LEVEL
+        };
+        // AppKit windows are only touched on the main thread
+        let target = window.clone();
+        let _ = window.run_on_main_thread(move || {
+            let Ok(ns_window) = target.ns_window() else {
+                return;
+            };
+            let ns_window = unsafe { &*(ns_window as *const AnyObject) };
+            let current: isize = unsafe { msg_send![ns_window, level] };
+            // Always on top is a window level, so a lower one means it was lost
+            if current != level {
+                let _: () = unsafe { msg_send![ns_window, setLevel: level] };
+            }
+        });
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+mod platform {
+    pub fn raise(_label: &str, window: &tauri::WebviewWindow, _block_popups: bool) {
+        if !window.is_always_on_top().unwrap_or(true) {
+            let _ = window.set_always_on_top(true);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Noticing output windows that have died
+//!
+//! An output whose webview crashed or hung shows black, or a frozen slide,
+//! and nothing tells the operator. Output windows report a heartbeat every
+//! second; a background thread checks the outputs that should be open, and
+//! one whose window has been gone for two checks running, so windows being
+//! swapped over aren't caught halfway, or that has missed its heartbeats for
+//! a few seconds, is reported with `output:unhealthy` and its window is made
+//! again. New windows get longer for their first heartbeat, while they load.
+//!
+//! Outputs on monitors that aren't connected are left to `monitor_watch`.
+
+use crate::commands;
+use crate::monitor_watch::OutputMonitors;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const OUTPUT_UNHEALTHY_EVENT: &str = "output:unhealthy";
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Heartbeats missed for this long mean the webview isn't running
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a new window gets to load and send its first heartbeat
+const LOAD_TIMEOUT: Duration = Duration::from_secs(20);
+/// How long a hung window gets to go before its new one is opened
+const DESTROY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What's wrong with an output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnhealthyReason {
+    /// The window is gone
+    Closed,
+    /// The window is there but its webview stopped answering
+    Unresponsive,
+}
+
+/// Payload of `output:unhealthy`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputUnhealthy {
+    pub monitor_id: String,
+    pub reason: UnhealthyReason,
+    /// Whether a new window could be opened for it
+    pub recreated: bool,
+}
+
+/// When an output was last heard from, and how long it has to answer
+struct Beat {
+    at: Instant,
+    timeout: Duration,
+}
+
+impl Beat {
+    fn loading() -> Self {
+        Self {
+            at: Instant::now(),
+            timeout: LOAD_TIMEOUT,
+        }
+    }
+}
+
+/// Managed state: the last heartbeat of each output window, by label
+#[derive(Default)]
+pub struct OutputHealth {
+    beats: Mutex<HashMap<String, Beat>>,
+    /// Labels of wanted windows found missing at the last check
+    missing: Mutex<HashSet<String>>,
+}
+
+impl OutputHealth {
+    /// Note a heartbeat from the output window `label`
+    pub fn beat(&self, label: &str) {
+        if let Ok(mut beats) = self.beats.lock() {
+            beats.insert(
+                label.to_string(),
+                Beat {
+                    at: Instant::now(),
+                    timeout: HEARTBEAT_TIMEOUT,
+                },
+            );
+        }
+    }
+
+    /// Whether the output window `label` has gone quiet; windows not seen
+    /// before start loading
+    fn missed(&self, label: &str) -> bool {
+        let Ok(mut beats) = self.beats.lock() else {
+            return false;
+        };
+        let beat = beats.entry(label.to_string()).or_insert_with(Beat::loading);
+        beat.at.elapsed() > beat.timeout
+    }
+
+    /// Whether the output window `label` was also missing at the last check
+    fn still_missing(&self, label: &str) -> bool {
+        self.missing
+            .lock()
+            .is_ok_and(|mut missing| !missing.insert(label.to_string()))
+    }
+
+    fn forget(&self, label: &str) {
+        if let Ok(mut beats) = self.beats.lock() {
+            beats.remove(label);
+        }
+        if let Ok(mut missing) = self.missing.lock() {
+            missing.remove(label);
+        }
+    }
+
+    /// Drop the heartbeats of windows that were closed or hidden, whose
+    /// timers may have been slowed, so ones shown again start loading
+    fn prune(&self, app: &AppHandle) {
+        let windows = app.webview_windows();
+        if let Ok(mut beats) = self.beats.lock() {
+            beats.retain(|label, _| {
+                windows
+                    .get(label)
+                    .is_some_and(|window| window.is_visible().unwrap_or(true))
+            });
+        }
+        if let Ok(mut missing) = self.missing.lock() {
+            missing.retain(|label| !windows.contains_key(label));
+        }
+    }
+}
+
+/// Start checking the output windows
+pub fn init(app: &AppHandle) {
+    app.manage(OutputHealth::default());
+    let handle = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(CHECK_INTERVAL);
+        check(&handle);
+    });
+}
+
+fn check(app: &AppHandle) {
+    let outputs = app.state::<OutputMonitors>();
+    let health = app.state::<OutputHealth>();
+    health.prune(app);
+    let wanted = outputs.wanted();
+    if wanted.is_empty() {
+        if let Ok(mut missing) = health.missing.lock() {
+            missing.clear();
+        }
+        return;
+    }
+    let Ok(monitors) = commands::list_monitors(app) else {
+        return;
+    };
+
+    let mut unhealthy = Vec::new();
+    for id in &wanted {
+        if !monitors.iter().any(|monitor| monitor.id == *id) {
+            continue;
+        }
+        let label = commands::output_window_label(id);
+        match app.get_webview_window(&label) {
+            None if health.still_missing(&label) => {
+                unhealthy.push((id.clone(), UnhealthyReason::Closed));
+            }
+            None => {}
+            Some(window) if health.missed(&label) => {
+                let _ = window.destroy();
+                let started = Instant::now();
+                while app.get_webview_window(&label).is_some()
+                    && started.elapsed() < DESTROY_TIMEOUT
+                {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                unhealthy.push((id.clone(), UnhealthyReason::Unresponsive));
+            }
+            Some(_) => {}
+        }
+    }
+    if unhealthy.is_empty() {
+        return;
+    }
+
+    for (id, _) in &unhealthy {
+        health.forget(&commands::output_window_label(id));
+    }
+    let recreated = match commands::open_outputs(app, &[], &wanted) {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!("Could not recreate the output windows: {e}");
+            false
+        }
+    };
+    for (monitor_id, reason) in unhealthy {
+        log::warn!("Output on {monitor_id} was {reason:?}; recreated: {recreated}");
+        let _ = app.emit(
+            OUTPUT_UNHEALTHY_EVENT,
+            OutputUnhealthy {
+                monitor_id,
+                reason,
+                recreated,
+            },
+        );
+    }
+}
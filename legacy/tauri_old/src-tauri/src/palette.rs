@@ -0,0 +1,243 @@
+//! Dominant and accent colours of images and videos
+//!
+//! The palette is read from the thumbnail (a video's poster frame), which the
+//! media browser has usually made already, so it costs almost nothing. Its
+//! pixels are grouped into a handful of swatches with k-means: the dominant
+//! colour is the biggest group, and the accent is the most vivid of the rest
+//! that's clearly different from it. Every swatch carries its WCAG relative
+//! luminance, and the palette suggests black or white text for the dominant
+//! colour along with the contrast ratio that gives, so a theme built on a
+//! background can be checked for legibility before it's used.
+
+use crate::cpres::{self, CpresError};
+use crate::thumbnails::{self, BundleMedia, ThumbnailCache};
+use crate::video_thumbnails::{self, VideoThumbnailOptions};
+use serde::Serialize;
+use std::path::Path;
+
+const MAX_SWATCHES: usize = 6;
+/// Pixels sampled from the thumbnail at most
+const MAX_SAMPLES: usize = 4096;
+const ITERATIONS: usize = 12;
+/// Swatches smaller than this share of the image can't be the accent
+const MIN_ACCENT_SHARE: f64 = 0.02;
+/// Saturation below which a colour is too grey to be an accent
+const MIN_ACCENT_SATURATION: f64 = 0.2;
+/// How far the accent is from the dominant colour at least, as `distance`
+/// measures: about 40 levels in every channel
+const MIN_ACCENT_DISTANCE: f64 = 9.0 * 40.0 * 40.0;
+
+#[derive(Debug, Serialize)]
+pub struct Swatch {
+    /// "#rrggbb"
+    pub color: String,
+    /// Share of the image, 0 to 1
+    pub share: f64,
+    /// WCAG relative luminance, 0 (black) to 1 (white)
+    pub luminance: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Palette {
+    pub dominant: String,
+    /// None when every other colour is grey or close to the dominant one
+    pub accent: Option<String>,
+    /// Largest first
+    pub swatches: Vec<Swatch>,
+    /// "#ffffff" or "#000000", whichever reads better on the dominant colour
+    pub text_color: String,
+    /// WCAG contrast ratio of `text_color` on the dominant colour, 1 to 21
+    pub text_contrast: f64,
+}
+
+/// Palette of an image or video file
+pub fn file_palette(cache: &ThumbnailCache, path: &Path) -> Result<Palette, CpresError> {
+    let thumbnail = if cpres::detect_media_kind(path)?.media_type == "video" {
+        video_thumbnails::file_video_thumbnails(cache, path, &VideoThumbnailOptions::default())?
+            .poster
+    } else {
+        thumbnails::file_thumbnail(cache, path, thumbnails::DEFAULT_SIZE)?
+    };
+    palette(Path::new(&thumbnail.path))
+}
+
+/// Palette of an image or video stored in a bundle
+pub fn bundle_palette(cache: &ThumbnailCache, media: &BundleMedia) -> Result<Palette, CpresError> {
+    let extension = Path::new(&media.media_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let thumbnail = if cpres::media_kind(&extension).1 == "video" {
+        video_thumbnails::bundle_video_thumbnails(cache, media, &VideoThumbnailOptions::default())?
+            .poster
+    } else {
+        thumbnails::bundle_thumbnail(cache, media, thumbnails::DEFAULT_SIZE)?
+    };
+    palette(Path::new(&thumbnail.path))
+}
+
+/// Palette of a thumbnail
+fn palette(thumbnail: &Path) -> Result<Palette, CpresError> {
+    let image = image::open(thumbnail)
+        .map_err(|e| CpresError::InvalidBundle(format!("Unreadable thumbnail: {e}")))?
+        .to_rgba8();
+    // Transparent pixels aren't part of what's seen
+    let opaque: Vec<[f64; 3]> = image
+        .pixels()
+        .filter(|pixel| pixel[3] >= 128)
+        .map(|pixel| [pixel[0] as f64, pixel[1] as f64, pixel[2] as f64])
+        .collect();
+    let step = opaque.len().div_ceil(MAX_SAMPLES).max(1);
+    let samples: Vec<[f64; 3]> = opaque.into_iter().step_by(step).collect();
+    if samples.is_empty() {
+        return Err(CpresError::InvalidBundle(
+            "The image is fully transparent".to_string(),
+        ));
+    }
+
+    let mut clusters = cluster(&samples);
+    clusters.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let swatches: Vec<Swatch> = clusters
+        .iter()
+        .map(|(color, count)| Swatch {
+            color: hex(color),
+            share: *count as f64 / samples.len() as f64,
+            luminance: luminance(color),
+        })
+        .collect();
+
+    let dominant = clusters[0].0;
+    let accent = clusters[1..]
+        .iter()
+        .filter(|(_, count)| *count as f64 / samples.len() as f64 >= MIN_ACCENT_SHARE)
+        .filter(|(color, _)| saturation(color) >= MIN_ACCENT_SATURATION)
+        // Near the dominant colour it wouldn't stand out against it
+        .filter(|(color, _)| distance(color, &dominant) >= MIN_ACCENT_DISTANCE)
+        .max_by(|a, b| saturation(&a.0).total_cmp(&saturation(&b.0)))
+        .map(|(color, _)| hex(color));
+
+    let background = luminance(&dominant);
+    let (on_white, on_black) = (contrast(1.0, background), contrast(0.0, background));
+    let (text_color, text_contrast) = if on_white >= on_black {
+        ("#ffffff", on_white)
+    } else {
+        ("#000000", on_black)
+    };
+    Ok(Palette {
+        dominant: hex(&dominant),
+        accent,
+        swatches,
+        text_color: text_color.to_string(),
+        text_contrast: (text_contrast * 100.0).round() / 100.0,
+    })
+}
+
+/// k-means over the samples; returns each non-empty cluster's mean colour and
+/// size. Starts from the mean colour and adds the sample farthest from the
+/// centres so far, so the result doesn't depend on chance.
+fn cluster(samples: &[[f64; 3]]) -> Vec<([f64; 3], usize)> {
+    let mut centres = vec![mean(samples.iter()).expect("there are samples")];
+    while centres.len() < MAX_SWATCHES {
+        let farthest = samples
+            .iter()
+            .map(|sample| (sample, nearest(sample, &centres).1))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match farthest {
+            Some((sample, d)) if d > 0.0 => centres.push(*sample),
+            // Fewer distinct colours than swatches
+            _ => break,
+        }
+    }
+
+    let mut assignment = vec![0; samples.len()];
+    for _ in 0..ITERATIONS {
+        let mut changed = false;
+        for (sample, assigned) in samples.iter().zip(assignment.iter_mut()) {
+            let (index, _) = nearest(sample, &centres);
+            changed |= *assigned != index;
+            *assigned = index;
+        }
+        for (index, centre) in centres.iter_mut().enumerate() {
+            let members = samples
+                .iter()
+                .zip(&assignment)
+                .filter(|(_, assigned)| **assigned == index)
+                .map(|(sample, _)| sample);
+            if let Some(mean) = mean(members) {
+                *centre = mean;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    centres
+        .into_iter()
+        .enumerate()
+        .map(|(index, centre)| (centre, assignment.iter().filter(|a| **a == index).count()))
+        .filter(|(_, count)| *count > 0)
+        .collect()
+}
+
+fn nearest(sample: &[f64; 3], centres: &[[f64; 3]]) -> (usize, f64) {
+    centres
+        .iter()
+        .map(|centre| distance(sample, centre))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("there is always a centre")
+}
+
+fn mean<'a>(colors: impl Iterator<Item = &'a [f64; 3]>) -> Option<[f64; 3]> {
+    let mut sum = [0.0; 3];
+    let mut count = 0;
+    for color in colors {
+        for (total, channel) in sum.iter_mut().zip(color) {
+            *total += channel;
+        }
+        count += 1;
+    }
+    (count > 0).then(|| sum.map(|total| total / count as f64))
+}
+
+/// Squared distance, with channels weighted by how much the eye notices them
+fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    2.0 * d[0] * d[0] + 4.0 * d[1] * d[1] + 3.0 * d[2] * d[2]
+}
+
+/// HSV saturation, 0 to 1
+fn saturation(color: &[f64; 3]) -> f64 {
+    let max = color.iter().copied().fold(0.0, f64::max);
+    let min = color.iter().copied().fold(255.0, f64::min);
+    if max == 0.0 {
+        0.0
+    } else {
+        (max - min) / max
+    }
+}
+
+/// WCAG relative luminance of an sRGB colour
+fn luminance(color: &[f64; 3]) -> f64 {
+    let linear = color.map(|channel| {
+        let c = channel / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    0.2126 * linear[0] + 0.7152 * linear[1] + 0.0722 * linear[2]
+}
+
+/// WCAG contrast ratio between two luminances
+fn contrast(a: f64, b: f64) -> f64 {
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+fn hex(color: &[f64; 3]) -> String {
+    let [r, g, b] = color.map(|channel| channel.round().clamp(0.0, 255.0) as u8);
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
@@ -0,0 +1,213 @@
+//! PDF import
+//!
+//! Each page of a PDF (a guest speaker's notes, a bulletin, a handout)
+//! becomes a slide showing the page whole, rendered by pdfium at the DPI
+//! asked for. The page's text goes into the slide's notes so it can still be
+//! read from the stage display. Pages are written as PNGs to a staging folder
+//! so the first save can copy them into the bundle. Landscape documents,
+//! typically slides exported from another program, set the slide size;
+//! portrait pages are shown on 16:9 slides.
+//!
+//! pdfium is a separate library loaded at runtime, looked up like ffmpeg:
+//! `CHURCH_PRESENTER_PDFIUM`, then next to the app executable, then the
+//! system's library path.
+
+use crate::cpres::{BundleState, CpresError};
+use crate::importer::{self, ImportedPresentation, ImportedSlide, PlacedContent, PlacedItem};
+use crate::tasks::CancelToken;
+use pdfium_render::prelude::*;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+const ENV_OVERRIDE: &str = "CHURCH_PRESENTER_PDFIUM";
+
+/// Slides are this wide, like other imports
+const TARGET_WIDTH: f64 = 1920.0;
+/// 16:9, used for portrait pages
+const DEFAULT_SLIDE_SIZE: (f64, f64) = (1920.0, 1080.0);
+const DEFAULT_DPI: f32 = 150.0;
+const DPI_RANGE: (f32, f32) = (36.0, 600.0);
+/// Longest edge of a rendered page, whatever the DPI
+const MAX_PAGE_PIXELS: i32 = 8000;
+/// PDF sizes are in points
+const POINTS_PER_INCH: f32 = 72.0;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PdfImportOptions {
+    /// Resolution pages are rendered at
+    pub dpi: Option<f32>,
+    /// Pages to import, counting from 1, e.g. "1-3, 5, 8-"; all when None
+    pub pages: Option<String>,
+    pub password: Option<String>,
+}
+
+/// Render the pages of the PDF at `path` into `staging_dir` and build a
+/// bundle state with one slide per page
+pub fn import_pdf(
+    path: &Path,
+    staging_dir: &Path,
+    options: &PdfImportOptions,
+    cancel: &CancelToken,
+) -> Result<BundleState, CpresError> {
+    let pdfium = Pdfium::new(bind()?);
+    let document = pdfium
+        .load_pdf_from_file(path, options.password.as_deref())
+        .map_err(|e| match e {
+            PdfiumError::PdfiumLibraryInternalError(PdfiumInternalError::PasswordError) => {
+                CpresError::InvalidBundle("The PDF needs a password".to_string())
+            }
+            e => pdf_error(e),
+        })?;
+
+    let pages = document.pages();
+    let numbers = match options
+        .pages
+        .as_deref()
+        .filter(|spec| !spec.trim().is_empty())
+    {
+        Some(spec) => parse_page_range(spec, pages.len())?,
+        None => (1..=pages.len()).collect(),
+    };
+    let dpi = options
+        .dpi
+        .unwrap_or(DEFAULT_DPI)
+        .clamp(DPI_RANGE.0, DPI_RANGE.1);
+    let render_config = PdfRenderConfig::new()
+        .scale_page_by_factor(dpi / POINTS_PER_INCH)
+        .set_maximum_width(MAX_PAGE_PIXELS)
+        .set_maximum_height(MAX_PAGE_PIXELS)
+        .render_form_data(true);
+
+    let title = document
+        .metadata()
+        .get(PdfDocumentMetadataTagType::Title)
+        .map(|tag| tag.value().trim().to_string())
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("Imported PDF")
+                .to_string()
+        });
+    let author = document
+        .metadata()
+        .get(PdfDocumentMetadataTagType::Author)
+        .map(|tag| tag.value().trim().to_string())
+        .filter(|author| !author.is_empty());
+
+    let mut presentation = ImportedPresentation {
+        title,
+        author,
+        ..Default::default()
+    };
+    let Some(first) = numbers.first() else {
+        return Err(CpresError::InvalidBundle(
+            "The PDF has no pages".to_string(),
+        ));
+    };
+    let first = pages.get(first - 1).map_err(pdf_error)?;
+    let (width, height) = (first.width().value as f64, first.height().value as f64);
+    let slide_size = if width > height && height > 0.0 {
+        let size = (TARGET_WIDTH, (TARGET_WIDTH * height / width).round());
+        presentation.slide_size = Some((size.0 as u32, size.1 as u32));
+        size
+    } else {
+        DEFAULT_SLIDE_SIZE
+    };
+
+    std::fs::create_dir_all(staging_dir)?;
+    for number in numbers {
+        cancel.check()?;
+        let page = pages.get(number - 1).map_err(pdf_error)?;
+        let image = page
+            .render_with_config(&render_config)
+            .map_err(pdf_error)?
+            .as_image();
+        let image_path = staging_dir.join(format!("page-{number:04}.png"));
+        image
+            .save(&image_path)
+            .map_err(|e| CpresError::InvalidBundle(format!("Could not save page {number}: {e}")))?;
+
+        let notes = page
+            .text()
+            .map(|text| text.all().trim().to_string())
+            .ok()
+            .filter(|text| !text.is_empty());
+        presentation.slides.push(ImportedSlide {
+            notes,
+            placed: vec![PlacedItem {
+                content: PlacedContent::Media(image_path),
+                x: 0.0,
+                y: 0.0,
+                width: slide_size.0,
+                height: slide_size.1,
+            }],
+            ..Default::default()
+        });
+    }
+
+    importer::to_bundle_state(presentation)
+}
+
+/// Page numbers from a spec such as "1-3, 5, 8-", in the order given;
+/// `count` is the number of pages in the document
+fn parse_page_range(spec: &str, count: u16) -> Result<Vec<u16>, CpresError> {
+    let invalid = |part: &str| {
+        CpresError::InvalidBundle(format!(
+            "\"{part}\" is not a page or range of pages between 1 and {count}"
+        ))
+    };
+    let mut numbers = Vec::new();
+    for part in spec
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let number = |text: &str, default: u16| match text.trim() {
+            "" => Some(default),
+            text => text.parse::<u16>().ok(),
+        };
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (number(first, 1), number(last, count)),
+            None => (number(part, 0), number(part, 0)),
+        };
+        match (first, last) {
+            (Some(first), Some(last)) if first >= 1 && first <= last && last <= count => {
+                numbers.extend(first..=last)
+            }
+            _ => return Err(invalid(part)),
+        }
+    }
+    Ok(numbers)
+}
+
+/// pdfium, from `CHURCH_PRESENTER_PDFIUM`, beside the app, or the system
+fn bind() -> Result<Box<dyn PdfiumLibraryBindings>, CpresError> {
+    if let Some(path) = std::env::var_os(ENV_OVERRIDE).map(PathBuf::from) {
+        if path.is_file() {
+            return Pdfium::bind_to_library(&path).map_err(pdf_error);
+        }
+        log::warn!(
+            "{ENV_OVERRIDE} points at {}, which doesn't exist",
+            path.display()
+        );
+    }
+    let beside_app = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(Pdfium::pdfium_platform_library_name_at_path(exe.parent()?)))
+        .filter(|path| path.is_file());
+    if let Some(path) = beside_app {
+        return Pdfium::bind_to_library(&path).map_err(pdf_error);
+    }
+    Pdfium::bind_to_system_library().map_err(|e| {
+        log::debug!("Could not load the system's pdfium: {e}");
+        CpresError::InvalidBundle(format!(
+            "pdfium not found; install it or set {ENV_OVERRIDE}"
+        ))
+    })
+}
+
+fn pdf_error(e: PdfiumError) -> CpresError {
+    CpresError::InvalidBundle(format!("Could not read the PDF: {e}"))
+}
@@ -0,0 +1,55 @@
+//! Blurhash placeholders for media grids
+//!
+//! A blurhash is a string of about 30 characters holding a handful of blurred
+//! colour components of an image. The media grid decodes it into a soft
+//! gradient as soon as an entry appears, while the real thumbnail loads. It's
+//! computed on import from the upright image, or from a video's poster frame,
+//! and stored as `blurhash` in the media entry.
+
+use crate::cpres::CpresError;
+use crate::media_probe::MediaMetadata;
+use crate::thumbnails;
+use crate::video_thumbnails;
+use image::DynamicImage;
+use std::path::Path;
+
+/// Longest edge the image is shrunk to first; the hash only keeps a few
+/// components, and encoding time grows with the pixel count
+const SAMPLE_SIZE: u32 = 64;
+/// Components along the long and short edge
+const COMPONENTS: (u32, u32) = (4, 3);
+
+/// Blurhash of an image or video; None for other media and for files that
+/// can't be decoded
+pub fn blurhash(path: &Path, media_type: &str, metadata: &MediaMetadata) -> Option<String> {
+    let decoded = match media_type {
+        "image" => std::fs::read(path)
+            .map_err(CpresError::from)
+            .and_then(|data| thumbnails::decode(&data)),
+        "video" => video_thumbnails::poster_frame(path, metadata.duration)
+            .and_then(|frame| thumbnails::decode(&frame)),
+        _ => return None,
+    };
+    decoded
+        .and_then(|image| encode(&image))
+        .inspect_err(|e| log::info!("No placeholder for {}: {e}", path.display()))
+        .ok()
+}
+
+fn encode(image: &DynamicImage) -> Result<String, CpresError> {
+    let sample = image.thumbnail(SAMPLE_SIZE, SAMPLE_SIZE).to_rgba8();
+    let (long, short) = COMPONENTS;
+    let (components_x, components_y) = if sample.width() >= sample.height() {
+        (long, short)
+    } else {
+        (short, long)
+    };
+    blurhash::encode(
+        components_x,
+        components_y,
+        sample.width(),
+        sample.height(),
+        sample.as_raw(),
+    )
+    .map_err(|e| CpresError::InvalidBundle(format!("Could not encode a blurhash: {e}")))
+}
@@ -0,0 +1,115 @@
+//! Keeping the mouse off the outputs
+//!
+//! Output windows hide the cursor once it stops moving, but an arrow
+//! drifting across a projector still shows on the way, and a click there
+//! takes the focus from the operator's window. With confinement on, a
+//! background thread follows the cursor while outputs are open and puts it
+//! back where it last was whenever it crosses onto an output window, as if
+//! the output's edge were the edge of the desktop.
+//!
+//! Wayland doesn't let apps move the cursor, so it isn't held back there.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+/// About once a frame, so the cursor is back before it's drawn far in
+const POLL_INTERVAL: Duration = Duration::from_millis(16);
+/// While off or with no outputs open
+const IDLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Managed state: whether the cursor is kept off the outputs
+#[derive(Default)]
+pub struct PointerConfinement {
+    enabled: AtomicBool,
+}
+
+impl PointerConfinement {
+    pub fn set(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+}
+
+/// Part of the desktop, in physical pixels
+struct Area {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Area {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// Start following the cursor
+pub fn init(app: &AppHandle) {
+    app.manage(PointerConfinement::default());
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        // Where the cursor last was off the outputs
+        let mut last_outside: Option<(f64, f64)> = None;
+        loop {
+            let enabled = handle
+                .state::<PointerConfinement>()
+                .enabled
+                .load(Ordering::SeqCst);
+            let outputs = if enabled {
+                output_areas(&handle)
+            } else {
+                Vec::new()
+            };
+            if outputs.is_empty() {
+                last_outside = None;
+                std::thread::sleep(IDLE_INTERVAL);
+                continue;
+            }
+            if let Ok(cursor) = handle.cursor_position() {
+                if !outputs.iter().any(|area| area.contains(cursor.x, cursor.y)) {
+                    last_outside = Some((cursor.x, cursor.y));
+                } else if let Some((x, y)) = last_outside {
+                    move_cursor(&handle, x, y);
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+/// Where the visible output windows are
+fn output_areas(app: &AppHandle) -> Vec<Area> {
+    app.webview_windows()
+        .into_iter()
+        .filter(|(label, window)| {
+            (label == "output" || label.starts_with("output-"))
+                && window.is_visible().unwrap_or(false)
+        })
+        .filter_map(|(_, window)| {
+            let position = window.outer_position().ok()?;
+            let size = window.outer_size().ok()?;
+            Some(Area {
+                x: f64::from(position.x),
+                y: f64::from(position.y),
+                width: f64::from(size.width),
+                height: f64::from(size.height),
+            })
+        })
+        .collect()
+}
+
+/// Put the cursor at `x`, `y` on the desktop; windows place it relative to
+/// themselves, so the main window does
+fn move_cursor(app: &AppHandle, x: f64, y: f64) {
+    let Some(main) = app.get_webview_window("main") else {
+        return;
+    };
+    let Ok(origin) = main.inner_position() else {
+        return;
+    };
+    let _ = main.set_cursor_position(tauri::Position::Physical(tauri::PhysicalPosition {
+        x: (x - f64::from(origin.x)).round() as i32,
+        y: (y - f64::from(origin.y)).round() as i32,
+    }));
+}
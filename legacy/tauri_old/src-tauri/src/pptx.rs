@@ -0,0 +1,429 @@
+//! PowerPoint (.pptx) import
+//!
+//! A .pptx is a ZIP of OOXML parts. Slides are listed in
+//! `ppt/presentation.xml`, and every part resolves images, videos, and notes
+//! through its `_rels/*.rels` relationships. Text boxes and pictures keep their
+//! positions; text formatting, shapes, and animations are not imported.
+//! Embedded media is extracted to a staging folder so the first save can copy
+//! it into the bundle.
+
+use crate::cpres::{self, BundleState, CpresError};
+use crate::importer::{self, ImportedPresentation, ImportedSlide, PlacedContent, PlacedItem};
+use roxmltree::Node;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+const PRESENTATION_PART: &str = "ppt/presentation.xml";
+const CORE_PROPERTIES_PART: &str = "docProps/core.xml";
+/// Slides are scaled to this width, keeping the deck's aspect ratio
+const TARGET_WIDTH: f64 = 1920.0;
+/// PowerPoint's default 16:9 slide size in EMU
+const DEFAULT_SLIDE_EMU: (f64, f64) = (12_192_000.0, 6_858_000.0);
+
+/// A relationship target, resolved to a part name inside the package
+struct Relationship {
+    kind: String,
+    target: String,
+    external: bool,
+}
+
+/// Maps a part's EMU coordinates to slide pixels: `pixel = emu * scale + offset`
+#[derive(Clone, Copy)]
+struct Transform {
+    scale_x: f64,
+    scale_y: f64,
+    offset_x: f64,
+    offset_y: f64,
+}
+
+struct Package {
+    archive: ZipArchive<File>,
+    staging_dir: PathBuf,
+    /// Part name -> extracted file
+    extracted: HashMap<String, PathBuf>,
+}
+
+/// Parse a .pptx deck into a bundle state, extracting its media into `staging_dir`
+pub fn import_pptx(path: &Path, staging_dir: &Path) -> Result<BundleState, CpresError> {
+    let mut package = Package {
+        archive: ZipArchive::new(File::open(path)?)?,
+        staging_dir: staging_dir.to_path_buf(),
+        extracted: HashMap::new(),
+    };
+
+    let presentation_xml = package.read(PRESENTATION_PART)?;
+    let doc = parse_xml(&presentation_xml, PRESENTATION_PART)?;
+    let root = doc.root_element();
+
+    let (width_emu, height_emu) = child(root, "sldSz")
+        .and_then(|size| Some((emu_attr(size, "cx")?, emu_attr(size, "cy")?)))
+        .filter(|(cx, cy)| *cx > 0.0 && *cy > 0.0)
+        .unwrap_or(DEFAULT_SLIDE_EMU);
+    let scale = TARGET_WIDTH / width_emu;
+    let base = Transform {
+        scale_x: scale,
+        scale_y: scale,
+        offset_x: 0.0,
+        offset_y: 0.0,
+    };
+
+    let mut presentation = ImportedPresentation {
+        slide_size: Some((TARGET_WIDTH as u32, (height_emu * scale).round() as u32)),
+        ..Default::default()
+    };
+    if let Ok(core) = package.read(CORE_PROPERTIES_PART) {
+        if let Ok(core) = roxmltree::Document::parse(&core) {
+            let text = |name: &str| {
+                core.descendants()
+                    .find(|n| n.tag_name().name() == name)
+                    .and_then(|n| n.text())
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+            };
+            presentation.title = text("title").unwrap_or_default();
+            presentation.author = text("creator");
+        }
+    }
+    if presentation.title.is_empty() {
+        presentation.title = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Imported Presentation")
+            .to_string();
+    }
+
+    let relationships = package.relationships(PRESENTATION_PART);
+    let slide_parts: Vec<String> = child(root, "sldIdLst")
+        .into_iter()
+        .flat_map(|list| list.children().filter(|n| named(n, "sldId")))
+        .filter_map(|id| rel_attr(id, "id"))
+        .filter_map(|id| relationships.get(id))
+        .map(|rel| rel.target.clone())
+        .collect();
+
+    for part in slide_parts {
+        match package.slide(&part, base)? {
+            Some(slide) => presentation.slides.push(slide),
+            None => log::info!("Skipping hidden slide {part}"),
+        }
+    }
+
+    importer::to_bundle_state(presentation)
+}
+
+impl Package {
+    fn read(&mut self, part: &str) -> Result<String, CpresError> {
+        cpres::read_zip_file(&mut self.archive, part)
+    }
+
+    /// Relationships of `part`, keyed by id; empty when the part has none
+    fn relationships(&mut self, part: &str) -> HashMap<String, Relationship> {
+        let (dir, name) = part.rsplit_once('/').unwrap_or(("", part));
+        let rels_part = format!("{dir}/_rels/{name}.rels");
+        let Ok(xml) = self.read(&rels_part) else {
+            return HashMap::new();
+        };
+        let Ok(doc) = roxmltree::Document::parse(&xml) else {
+            log::warn!("Unreadable relationships in {rels_part}");
+            return HashMap::new();
+        };
+
+        doc.root_element()
+            .children()
+            .filter(|n| named(n, "Relationship"))
+            .filter_map(|rel| {
+                let id = rel.attribute("Id")?;
+                let target = rel.attribute("Target")?;
+                let external = rel.attribute("TargetMode") == Some("External");
+                Some((
+                    id.to_string(),
+                    Relationship {
+                        kind: rel.attribute("Type").unwrap_or_default().to_string(),
+                        target: if external {
+                            target.to_string()
+                        } else {
+                            resolve_part(dir, target)
+                        },
+                        external,
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// Parse one slide part; None for hidden slides
+    fn slide(&mut self, part: &str, base: Transform) -> Result<Option<ImportedSlide>, CpresError> {
+        let xml = self.read(part)?;
+        let doc = parse_xml(&xml, part)?;
+        let root = doc.root_element();
+        if root.attribute("show") == Some("0") {
+            return Ok(None);
+        }
+        let relationships = self.relationships(part);
+
+        let mut slide = ImportedSlide::default();
+        if let Some(common) = child(root, "cSld") {
+            slide.background = child(common, "bg")
+                .and_then(|bg| bg.descendants().find(|n| named(n, "blip")))
+                .and_then(|blip| rel_attr(blip, "embed"))
+                .and_then(|id| relationships.get(id))
+                .and_then(|rel| self.media_path(rel));
+
+            if let Some(tree) = child(common, "spTree") {
+                self.shapes(tree, base, &relationships, &mut slide);
+            }
+        }
+
+        let notes_part = relationships
+            .values()
+            .find(|rel| rel.kind.ends_with("/notesSlide") && !rel.external)
+            .map(|rel| rel.target.clone());
+        if let Some(notes_part) = notes_part {
+            slide.notes = self.notes(&notes_part);
+        }
+
+        Ok(Some(slide))
+    }
+
+    /// Collect text boxes and pictures from a shape tree, descending into groups
+    fn shapes(
+        &mut self,
+        tree: Node,
+        transform: Transform,
+        relationships: &HashMap<String, Relationship>,
+        slide: &mut ImportedSlide,
+    ) {
+        for node in tree.children().filter(Node::is_element) {
+            match node.tag_name().name() {
+                "sp" => {
+                    let text = child(node, "txBody")
+                        .map(paragraph_text)
+                        .unwrap_or_default();
+                    if text.is_empty() {
+                        continue;
+                    }
+                    match shape_bounds(node, "spPr", transform) {
+                        Some(bounds) => {
+                            slide.placed.push(placed(PlacedContent::Text(text), bounds))
+                        }
+                        // Placeholders without their own frame inherit the layout's position
+                        None => slide.text.push(text),
+                    }
+                }
+                "pic" => {
+                    let Some(bounds) = shape_bounds(node, "spPr", transform) else {
+                        continue;
+                    };
+                    if let Some(media) = self.picture_media(node, relationships) {
+                        slide
+                            .placed
+                            .push(placed(PlacedContent::Media(media), bounds));
+                    }
+                }
+                "grpSp" => {
+                    let group_transform = child(node, "grpSpPr")
+                        .and_then(|props| child(props, "xfrm"))
+                        .and_then(|xfrm| group_transform(xfrm, transform))
+                        .unwrap_or(transform);
+                    self.shapes(node, group_transform, relationships, slide);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The video of a movie shape if it is embedded, else the picture itself
+    fn picture_media(
+        &mut self,
+        picture: Node,
+        relationships: &HashMap<String, Relationship>,
+    ) -> Option<PathBuf> {
+        let video = picture
+            .descendants()
+            .filter(|n| named(n, "media") || named(n, "videoFile"))
+            .filter_map(|n| rel_attr(n, "embed").or_else(|| rel_attr(n, "link")))
+            .filter_map(|id| relationships.get(id))
+            .find_map(|rel| self.media_path(rel));
+        if video.is_some() {
+            return video;
+        }
+
+        picture
+            .descendants()
+            .find(|n| named(n, "blip"))
+            .and_then(|blip| rel_attr(blip, "embed"))
+            .and_then(|id| relationships.get(id))
+            .and_then(|rel| self.media_path(rel))
+    }
+
+    /// Local path of a media relationship, extracting embedded parts on first use
+    fn media_path(&mut self, rel: &Relationship) -> Option<PathBuf> {
+        if rel.external {
+            let target = rel.target.strip_prefix("file:///").unwrap_or(&rel.target);
+            let path = PathBuf::from(target);
+            return path.is_file().then_some(path);
+        }
+        if let Some(path) = self.extracted.get(&rel.target) {
+            return Some(path.clone());
+        }
+
+        match self.extract(&rel.target) {
+            Ok(path) => {
+                self.extracted.insert(rel.target.clone(), path.clone());
+                Some(path)
+            }
+            Err(e) => {
+                log::warn!("Could not extract {}: {e}", rel.target);
+                None
+            }
+        }
+    }
+
+    fn extract(&mut self, part: &str) -> Result<PathBuf, CpresError> {
+        let name = part.rsplit('/').next().unwrap_or(part);
+        // Parts in different folders may share a file name
+        let dir = self.staging_dir.join(self.extracted.len().to_string());
+        fs::create_dir_all(&dir)?;
+        let destination = dir.join(name);
+        let mut entry = self
+            .archive
+            .by_name(part)
+            .map_err(|_| CpresError::MissingFile(part.to_string()))?;
+        let mut file = File::create(&destination)?;
+        std::io::copy(&mut entry, &mut file)?;
+        Ok(destination)
+    }
+
+    /// Body text of a notes slide
+    fn notes(&mut self, part: &str) -> Option<String> {
+        let xml = self.read(part).ok()?;
+        let doc = roxmltree::Document::parse(&xml).ok()?;
+        let text: Vec<String> = doc
+            .descendants()
+            .filter(|n| named(n, "sp"))
+            .filter(|shape| {
+                shape
+                    .descendants()
+                    .find(|n| named(n, "ph"))
+                    .is_some_and(|ph| ph.attribute("type") == Some("body"))
+            })
+            .filter_map(|shape| child(shape, "txBody"))
+            .map(paragraph_text)
+            .filter(|t| !t.is_empty())
+            .collect();
+        Some(text.join("\n\n")).filter(|t| !t.is_empty())
+    }
+}
+
+/// Text of a `txBody`, one line per paragraph
+fn paragraph_text(body: Node) -> String {
+    body.children()
+        .filter(|n| named(n, "p"))
+        .map(|paragraph| {
+            let mut line = String::new();
+            for run in paragraph.descendants() {
+                match run.tag_name().name() {
+                    "t" => line.push_str(run.text().unwrap_or_default()),
+                    "br" => line.push('\n'),
+                    _ => {}
+                }
+            }
+            line.trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Slide-pixel bounds (x, y, width, height) of a shape with its own `a:xfrm`
+fn shape_bounds(shape: Node, props: &str, transform: Transform) -> Option<(f64, f64, f64, f64)> {
+    let xfrm = child(child(shape, props)?, "xfrm")?;
+    let (x, y) = point(xfrm, "off", "x", "y")?;
+    let (cx, cy) = point(xfrm, "ext", "cx", "cy")?;
+    Some((
+        x * transform.scale_x + transform.offset_x,
+        y * transform.scale_y + transform.offset_y,
+        cx * transform.scale_x,
+        cy * transform.scale_y,
+    ))
+}
+
+/// Child coordinates of a group map `chOff`/`chExt` onto its `off`/`ext`
+fn group_transform(xfrm: Node, parent: Transform) -> Option<Transform> {
+    let (off_x, off_y) = point(xfrm, "off", "x", "y")?;
+    let (ext_x, ext_y) = point(xfrm, "ext", "cx", "cy")?;
+    let (child_off_x, child_off_y) = point(xfrm, "chOff", "x", "y")?;
+    let (child_ext_x, child_ext_y) = point(xfrm, "chExt", "cx", "cy")?;
+    if child_ext_x <= 0.0 || child_ext_y <= 0.0 {
+        return None;
+    }
+    let ratio_x = ext_x / child_ext_x;
+    let ratio_y = ext_y / child_ext_y;
+    Some(Transform {
+        scale_x: parent.scale_x * ratio_x,
+        scale_y: parent.scale_y * ratio_y,
+        offset_x: parent.offset_x + parent.scale_x * (off_x - child_off_x * ratio_x),
+        offset_y: parent.offset_y + parent.scale_y * (off_y - child_off_y * ratio_y),
+    })
+}
+
+fn placed(content: PlacedContent, (x, y, width, height): (f64, f64, f64, f64)) -> PlacedItem {
+    PlacedItem {
+        content,
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
+fn point(xfrm: Node, name: &str, x: &str, y: &str) -> Option<(f64, f64)> {
+    let node = child(xfrm, name)?;
+    Some((emu_attr(node, x)?, emu_attr(node, y)?))
+}
+
+fn emu_attr(node: Node, name: &str) -> Option<f64> {
+    node.attribute(name)?.parse().ok()
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| named(n, name))
+}
+
+/// A relationship-id attribute such as `r:embed`, whichever OOXML namespace it uses
+fn rel_attr<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|attr| attr.name() == name && attr.namespace().is_some())
+        .map(|attr| attr.value())
+}
+
+/// Resolve a relationship target against the folder of the part that declares it
+fn resolve_part(dir: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+    let mut segments: Vec<&str> = dir.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in target.split('/') {
+        match segment {
+            ".." => {
+                segments.pop();
+            }
+            "." | "" => {}
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+fn parse_xml<'a>(xml: &'a str, part: &str) -> Result<roxmltree::Document<'a>, CpresError> {
+    roxmltree::Document::parse(xml)
+        .map_err(|e| CpresError::InvalidBundle(format!("Invalid XML in {part}: {e}")))
+}
+
+/// Match on the local name; transitional and strict OOXML use different namespaces
+fn named(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name
+}
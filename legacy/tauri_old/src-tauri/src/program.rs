@@ -0,0 +1,273 @@
+//! What the outputs are showing
+//!
+//! The program (the live presentation, slide, builds and media layers, and
+//! whether the screens are blacked out, showing the logo, or cleared of
+//! text) is kept here rather than in any one webview. The main window works
+//! out slide changes and pushes them in; output windows, stage displays and
+//! remotes read it back with `get_program_state` whenever they load, so a
+//! reloaded window picks up where the others are instead of waiting on the
+//! main window. Every change is broadcast as `program:state`, with a
+//! revision number so late events can be told from new ones.
+//!
+//! The outputs drawn offscreen (NDI, DeckLink, shared textures and the
+//! virtual output) follow the program from here too. They show black for
+//! anything but a live slide, the logo included. Remotes connected over the
+//! network (see `remote_server`) are sent each change as well.
+
+use crate::decklink::DeckLinkOutput;
+use crate::ndi::NdiOutput;
+use crate::remote_server::RemoteServer;
+use crate::texture_share::TextureSharing;
+use crate::virtual_output::VirtualOutput;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const PROGRAM_STATE_EVENT: &str = "program:state";
+pub const PROGRAM_PRESENTATION_EVENT: &str = "program:presentation";
+
+/// What covers the slides on every output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProgramScreen {
+    /// The live slide and media
+    #[default]
+    Live,
+    /// Nothing at all
+    Blackout,
+    /// The logo image, on black
+    Logo,
+    /// The background and media, with the slide's text and other elements
+    /// taken off
+    Clear,
+}
+
+/// A flag for each of the operator's two clearable groups
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ProgramGroups {
+    pub presentation: bool,
+    pub media: bool,
+}
+
+/// Where the live presentation is at, as the main window works it out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveState {
+    pub is_live: bool,
+    pub presentation_id: Option<String>,
+    pub presentation_path: Option<String>,
+    pub current_slide_id: Option<String>,
+    pub current_slide_index: i32,
+    /// -1 before the slide's first build
+    pub current_build_index: i32,
+    pub total_build_steps: u32,
+    pub visible_layer_ids: Vec<String>,
+    /// The media on each layer, as the webviews describe it
+    pub media_layers: serde_json::Value,
+    pub suppress: ProgramGroups,
+    /// Groups fading out before they're cleared
+    pub is_clearing: ProgramGroups,
+}
+
+impl Default for LiveState {
+    fn default() -> Self {
+        Self {
+            is_live: false,
+            presentation_id: None,
+            presentation_path: None,
+            current_slide_id: None,
+            current_slide_index: 0,
+            current_build_index: -1,
+            total_build_steps: 0,
+            visible_layer_ids: Vec::new(),
+            media_layers: serde_json::json!({
+                "mediaUnderlay": null,
+                "mediaOverlay": null,
+                "audio": null,
+            }),
+            suppress: ProgramGroups::default(),
+            is_clearing: ProgramGroups::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgramState {
+    /// Goes up by one with every change
+    pub revision: u64,
+    pub screen: ProgramScreen,
+    /// Image shown for the logo screen
+    pub logo_path: Option<String>,
+    #[serde(flatten)]
+    pub live: LiveState,
+}
+
+/// The live presentation itself, which changes far less often than the
+/// state and is sent separately
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgramPresentation {
+    pub revision: u64,
+    pub presentation: Option<serde_json::Value>,
+    pub presentation_path: Option<String>,
+}
+
+/// Managed state: the program
+pub struct Program {
+    app: AppHandle,
+    state: Mutex<ProgramState>,
+    presentation: Mutex<ProgramPresentation>,
+    /// The bundle and slide the offscreen outputs were last sent
+    drawn: Mutex<Option<(PathBuf, String)>>,
+}
+
+impl Program {
+    fn new(app: &AppHandle) -> Self {
+        Self {
+            app: app.clone(),
+            state: Mutex::new(ProgramState::default()),
+            presentation: Mutex::new(ProgramPresentation::default()),
+            drawn: Mutex::new(None),
+        }
+    }
+
+    pub fn state(&self) -> ProgramState {
+        self.state
+            .lock()
+            .map(|state| state.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn presentation(&self) -> ProgramPresentation {
+        self.presentation
+            .lock()
+            .map(|presentation| presentation.clone())
+            .unwrap_or_default()
+    }
+
+    /// Take on where the live presentation is at. Going live or moving to
+    /// another slide takes the outputs out of blackout, logo or clear, as
+    /// it does for the operator.
+    pub fn set_live(&self, live: LiveState) -> ProgramState {
+        self.change(|state| {
+            let moved = live.current_slide_id.is_some()
+                && live.current_slide_id != state.live.current_slide_id;
+            if moved || !live.is_live || !state.live.is_live {
+                state.screen = ProgramScreen::Live;
+            }
+            state.live = live;
+        })
+    }
+
+    pub fn set_screen(&self, screen: ProgramScreen) -> ProgramState {
+        self.change(|state| state.screen = screen)
+    }
+
+    pub fn set_logo(&self, path: Option<String>) -> ProgramState {
+        self.change(|state| state.logo_path = path)
+    }
+
+    /// Replace the live presentation, or drop it with None
+    pub fn set_presentation(
+        &self,
+        presentation: Option<serde_json::Value>,
+        path: Option<String>,
+    ) -> ProgramPresentation {
+        let presentation = match self.presentation.lock() {
+            Ok(mut current) => {
+                current.revision += 1;
+                current.presentation = presentation;
+                current.presentation_path = path;
+                current.clone()
+            }
+            Err(_) => return ProgramPresentation::default(),
+        };
+        let _ = self
+            .app
+            .emit(PROGRAM_PRESENTATION_EVENT, presentation.clone());
+        presentation
+    }
+
+    fn change(&self, apply: impl FnOnce(&mut ProgramState)) -> ProgramState {
+        let state = match self.state.lock() {
+            Ok(mut state) => {
+                let before = state.clone();
+                apply(&mut state);
+                let changed = state.screen != before.screen
+                    || state.logo_path != before.logo_path
+                    || state.live != before.live;
+                if !changed {
+                    return state.clone();
+                }
+                state.revision += 1;
+                state.clone()
+            }
+            Err(_) => return ProgramState::default(),
+        };
+        let _ = self.app.emit(PROGRAM_STATE_EVENT, state.clone());
+        if let Some(remote) = self.app.try_state::<RemoteServer>() {
+            remote.broadcast(&state);
+        }
+        self.draw(&state);
+        state
+    }
+
+    /// Send the offscreen outputs the live slide, or black, when that's
+    /// changed
+    fn draw(&self, state: &ProgramState) {
+        let showing = match (&state.live.presentation_path, &state.live.current_slide_id) {
+            (Some(path), Some(slide_id))
+                if state.live.is_live && state.screen == ProgramScreen::Live =>
+            {
+                Some((PathBuf::from(path), slide_id.clone()))
+            }
+            _ => None,
+        };
+        match self.drawn.lock() {
+            Ok(mut drawn) if *drawn != showing => drawn.clone_from(&showing),
+            _ => return,
+        }
+
+        let slide = showing
+            .as_ref()
+            .map(|(path, slide_id)| (path.as_path(), slide_id.as_str()));
+        if let Some(ndi) = self.app.try_state::<NdiOutput>() {
+            let shown = match slide {
+                Some((path, slide_id)) => ndi.show(path, slide_id),
+                None => {
+                    ndi.clear();
+                    Ok(())
+                }
+            };
+            if let Err(e) = shown {
+                log::warn!("Could not update the NDI output: {e}");
+            }
+        }
+        if let Some(decklink) = self.app.try_state::<DeckLinkOutput>() {
+            decklink.show(slide);
+        }
+        if let Some(sharing) = self.app.try_state::<TextureSharing>() {
+            if let Err(e) = sharing.show(slide) {
+                log::warn!("Could not update the shared textures: {e}");
+            }
+        }
+        if let Some(output) = self.app.try_state::<VirtualOutput>() {
+            let shown = match slide {
+                Some((path, slide_id)) => output.show(path, slide_id),
+                None => {
+                    output.clear();
+                    Ok(())
+                }
+            };
+            if let Err(e) = shown {
+                log::warn!("Could not update the virtual output: {e}");
+            }
+        }
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(Program::new(app));
+}
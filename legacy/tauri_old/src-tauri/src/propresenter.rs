@@ -0,0 +1,450 @@
+//! ProPresenter document import
+//!
+//! - `.pro6` (ProPresenter 6) is XML. Slides live in `RVSlideGrouping` groups,
+//!   text elements carry base64 `PlainText`/`RTFData`, and arrangements list
+//!   group UUIDs.
+//! - `.pro` (ProPresenter 7) is protobuf. The schema isn't published, so only
+//!   the stable top-level fields (name, arrangements, cue groups, cues) are
+//!   decoded by number; slide text and media are found by scanning each cue
+//!   for RTF payloads and `file:` URLs.
+
+use crate::cpres::{BundleState, CpresError};
+use crate::importer::{self, rtf_to_text, ImportedPresentation, ImportedSlide};
+use base64::Engine;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Nesting limit when scanning protobuf payloads of unknown shape
+const MAX_SCAN_DEPTH: usize = 24;
+
+/// Parse a ProPresenter 6 or 7 document into a bundle state
+pub fn import_pro_presenter(path: &Path) -> Result<BundleState, CpresError> {
+    let data = std::fs::read(path)?;
+    let fallback_title = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Imported Presentation")
+        .to_string();
+
+    let is_xml = data
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .is_some_and(|b| *b == b'<');
+    let mut presentation = if is_xml {
+        parse_pro6(&String::from_utf8_lossy(&data))?
+    } else {
+        parse_pro7(&data)?
+    };
+    if presentation.title.trim().is_empty() {
+        presentation.title = fallback_title;
+    }
+
+    importer::to_bundle_state(presentation)
+}
+
+// ============================================================================
+// ProPresenter 6 (XML)
+// ============================================================================
+
+fn parse_pro6(xml: &str) -> Result<ImportedPresentation, CpresError> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|e| CpresError::InvalidBundle(format!("Invalid .pro6 XML: {e}")))?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "RVPresentationDocument" {
+        return Err(CpresError::InvalidBundle(
+            "Not a ProPresenter 6 document".to_string(),
+        ));
+    }
+
+    let mut presentation = ImportedPresentation {
+        title: root
+            .attribute("CCLISongTitle")
+            .unwrap_or_default()
+            .to_string(),
+        author: root.attribute("CCLIAuthor").map(String::from),
+        slide_size: match (
+            root.attribute("width").and_then(|w| w.parse().ok()),
+            root.attribute("height").and_then(|h| h.parse().ok()),
+        ) {
+            (Some(w), Some(h)) if w > 0 && h > 0 => Some((w, h)),
+            _ => None,
+        },
+        ..Default::default()
+    };
+
+    // Group UUID -> indices of its slides, for arrangements
+    let mut group_slides: HashMap<String, Vec<usize>> = HashMap::new();
+    for group in root
+        .descendants()
+        .filter(|n| n.has_tag_name("RVSlideGrouping"))
+    {
+        let label = group.attribute("name").map(String::from);
+        let indices = group_slides
+            .entry(group.attribute("uuid").unwrap_or_default().to_uppercase())
+            .or_default();
+        for slide in group
+            .descendants()
+            .filter(|n| n.has_tag_name("RVDisplaySlide"))
+        {
+            if slide.attribute("enabled") == Some("false") {
+                continue;
+            }
+            indices.push(presentation.slides.len());
+            presentation.slides.push(pro6_slide(slide, label.clone()));
+        }
+    }
+
+    // Use the first arrangement that resolves to any slides
+    presentation.order = root
+        .descendants()
+        .filter(|n| n.has_tag_name("RVSongArrangement"))
+        .map(|arrangement| {
+            arrangement
+                .descendants()
+                .filter(|n| n.has_tag_name("NSString"))
+                .filter_map(|n| n.text())
+                .filter_map(|id| group_slides.get(&id.trim().to_uppercase()))
+                .flatten()
+                .copied()
+                .collect::<Vec<usize>>()
+        })
+        .find(|order| !order.is_empty());
+
+    Ok(presentation)
+}
+
+fn pro6_slide(slide: roxmltree::Node, label: Option<String>) -> ImportedSlide {
+    let text = slide
+        .descendants()
+        .filter(|n| n.has_tag_name("RVTextElement"))
+        .filter_map(|element| {
+            let field = |name: &str| {
+                element
+                    .children()
+                    .find(|c| c.attribute("rvXMLIvarName") == Some(name))
+                    .and_then(|c| c.text())
+                    .and_then(decode_base64)
+            };
+            field("PlainText")
+                .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+                .or_else(|| field("RTFData").map(|bytes| rtf_to_text(&bytes)))
+        })
+        .map(|text| normalize_lines(&text))
+        .filter(|text| !text.is_empty())
+        .collect();
+
+    // The slide background lives in its backgroundMediaCue
+    let background = slide
+        .children()
+        .find(|c| c.attribute("rvXMLIvarName") == Some("backgroundMediaCue"))
+        .and_then(|cue| {
+            cue.descendants()
+                .filter_map(|n| n.attribute("source"))
+                .find_map(path_from_source)
+        });
+
+    ImportedSlide {
+        label,
+        text,
+        notes: slide
+            .attribute("notes")
+            .map(String::from)
+            .filter(|n| !n.is_empty()),
+        background,
+        ..Default::default()
+    }
+}
+
+fn decode_base64(value: &str) -> Option<Vec<u8>> {
+    let compact: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    base64::engine::general_purpose::STANDARD
+        .decode(compact)
+        .ok()
+}
+
+// ============================================================================
+// ProPresenter 7 (protobuf)
+// ============================================================================
+
+mod pro7_fields {
+    // Presentation
+    pub const NAME: u32 = 3;
+    pub const ARRANGEMENTS: u32 = 11;
+    pub const CUE_GROUPS: u32 = 12;
+    pub const CUES: u32 = 13;
+    pub const CCLI: u32 = 14;
+    // CCLI
+    pub const CCLI_AUTHOR: u32 = 1;
+    pub const CCLI_SONG_TITLE: u32 = 3;
+    // Arrangement
+    pub const ARRANGEMENT_GROUP_IDS: u32 = 3;
+    // CueGroup
+    pub const CUE_GROUP_GROUP: u32 = 1;
+    pub const CUE_GROUP_CUE_IDS: u32 = 2;
+    // Group / Cue / Arrangement share uuid = 1 and name = 2
+    pub const UUID: u32 = 1;
+    pub const LABEL: u32 = 2;
+    // UUID
+    pub const UUID_STRING: u32 = 1;
+}
+
+fn parse_pro7(data: &[u8]) -> Result<ImportedPresentation, CpresError> {
+    use pro7_fields::*;
+
+    let fields = decode_message(data)
+        .filter(|fields| !fields.is_empty())
+        .ok_or_else(|| CpresError::InvalidBundle("Not a ProPresenter document".to_string()))?;
+
+    let mut presentation = ImportedPresentation {
+        title: string_field(&fields, NAME).unwrap_or_default(),
+        ..Default::default()
+    };
+    if let Some(ccli) = message_field(&fields, CCLI) {
+        if let Some(title) = string_field(&ccli, CCLI_SONG_TITLE).filter(|t| !t.is_empty()) {
+            presentation.title = title;
+        }
+        presentation.author = string_field(&ccli, CCLI_AUTHOR);
+    }
+
+    // Cue UUID -> slide content
+    let mut cues: HashMap<String, ImportedSlide> = HashMap::new();
+    let mut cue_order = Vec::new();
+    for cue in messages(&fields, CUES) {
+        let Some(id) = uuid_field(&cue, UUID) else {
+            continue;
+        };
+        let mut slide = ImportedSlide::default();
+        for payload in all_bytes(&cue) {
+            scan_cue_payload(payload, &mut slide, 0);
+        }
+        cue_order.push(id.clone());
+        cues.insert(id, slide);
+    }
+
+    // Slides follow cue-group order; cues not in any group are appended
+    let mut group_slides: HashMap<String, Vec<usize>> = HashMap::new();
+    for cue_group in messages(&fields, CUE_GROUPS) {
+        let group = message_field(&cue_group, CUE_GROUP_GROUP).unwrap_or_default();
+        let group_id = uuid_field(&group, UUID).unwrap_or_default();
+        let label = string_field(&group, LABEL);
+        let indices = group_slides.entry(group_id).or_default();
+        for cue_id in messages(&cue_group, CUE_GROUP_CUE_IDS)
+            .iter()
+            .filter_map(|uuid| string_field(uuid, UUID_STRING))
+        {
+            if let Some(mut slide) = cues.remove(&cue_id.to_uppercase()) {
+                slide.label = label.clone();
+                indices.push(presentation.slides.len());
+                presentation.slides.push(slide);
+            }
+        }
+    }
+    for id in cue_order {
+        if let Some(slide) = cues.remove(&id) {
+            presentation.slides.push(slide);
+        }
+    }
+
+    presentation.order = messages(&fields, ARRANGEMENTS)
+        .iter()
+        .map(|arrangement| {
+            messages(arrangement, ARRANGEMENT_GROUP_IDS)
+                .iter()
+                .filter_map(|uuid| string_field(uuid, UUID_STRING))
+                .filter_map(|id| group_slides.get(&id.to_uppercase()))
+                .flatten()
+                .copied()
+                .collect::<Vec<usize>>()
+        })
+        .find(|order| !order.is_empty());
+
+    Ok(presentation)
+}
+
+/// Collect RTF text boxes and the first media file URL from a cue payload
+fn scan_cue_payload(payload: &[u8], slide: &mut ImportedSlide, depth: usize) {
+    if depth > MAX_SCAN_DEPTH {
+        return;
+    }
+    if payload.starts_with(b"{\\rtf") {
+        let text = normalize_lines(&rtf_to_text(payload));
+        if !text.is_empty() {
+            slide.text.push(text);
+        }
+        return;
+    }
+    if payload.starts_with(b"file:") {
+        if slide.background.is_none() {
+            slide.background = std::str::from_utf8(payload)
+                .ok()
+                .and_then(path_from_source)
+                .filter(|p| is_background_media(p));
+        }
+        return;
+    }
+    if let Some(fields) = decode_message(payload) {
+        for nested in all_bytes(&fields) {
+            scan_cue_payload(nested, slide, depth + 1);
+        }
+    }
+}
+
+fn is_background_media(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    matches!(
+        extension.as_str(),
+        "jpg" | "jpeg" | "png" | "gif" | "webp" | "mp4" | "mov" | "webm"
+    )
+}
+
+enum WireValue<'a> {
+    Varint,
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+impl<'a> WireValue<'a> {
+    fn bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            WireValue::Bytes(bytes) => Some(bytes),
+            WireValue::Varint | WireValue::Fixed => None,
+        }
+    }
+}
+
+/// Decode one protobuf message into (field, value) pairs; None if `data` isn't a valid message
+fn decode_message(data: &[u8]) -> Option<Vec<(u32, WireValue<'_>)>> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let key = read_varint(data, &mut pos)?;
+        let field = u32::try_from(key >> 3).ok().filter(|f| *f > 0)?;
+        let value = match key & 7 {
+            0 => {
+                read_varint(data, &mut pos)?;
+                WireValue::Varint
+            }
+            1 => {
+                pos = pos.checked_add(8).filter(|p| *p <= data.len())?;
+                WireValue::Fixed
+            }
+            2 => {
+                let len = usize::try_from(read_varint(data, &mut pos)?).ok()?;
+                let end = pos.checked_add(len).filter(|e| *e <= data.len())?;
+                let bytes = &data[pos..end];
+                pos = end;
+                WireValue::Bytes(bytes)
+            }
+            5 => {
+                pos = pos.checked_add(4).filter(|p| *p <= data.len())?;
+                WireValue::Fixed
+            }
+            _ => return None,
+        };
+        fields.push((field, value));
+    }
+    Some(fields)
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Length-delimited values of `field`
+fn repeated_bytes<'a>(fields: &[(u32, WireValue<'a>)], field: u32) -> Vec<&'a [u8]> {
+    fields
+        .iter()
+        .filter(|(number, _)| *number == field)
+        .filter_map(|(_, value)| value.bytes())
+        .collect()
+}
+
+/// Length-delimited values of every field
+fn all_bytes<'a>(fields: &[(u32, WireValue<'a>)]) -> Vec<&'a [u8]> {
+    fields
+        .iter()
+        .filter_map(|(_, value)| value.bytes())
+        .collect()
+}
+
+fn messages<'a>(fields: &[(u32, WireValue<'a>)], field: u32) -> Vec<Vec<(u32, WireValue<'a>)>> {
+    repeated_bytes(fields, field)
+        .into_iter()
+        .filter_map(decode_message)
+        .collect()
+}
+
+fn message_field<'a>(
+    fields: &[(u32, WireValue<'a>)],
+    field: u32,
+) -> Option<Vec<(u32, WireValue<'a>)>> {
+    repeated_bytes(fields, field)
+        .into_iter()
+        .next()
+        .and_then(decode_message)
+}
+
+fn string_field(fields: &[(u32, WireValue)], field: u32) -> Option<String> {
+    repeated_bytes(fields, field)
+        .into_iter()
+        .next()
+        .and_then(|bytes| std::str::from_utf8(bytes).ok())
+        .map(String::from)
+}
+
+fn uuid_field(fields: &[(u32, WireValue)], field: u32) -> Option<String> {
+    message_field(fields, field)
+        .and_then(|uuid| string_field(&uuid, pro7_fields::UUID_STRING))
+        .map(|id| id.to_uppercase())
+}
+
+// ============================================================================
+// Shared helpers
+// ============================================================================
+
+/// Filesystem path from a media `source` attribute or URL (`file:///...` or a plain path)
+fn path_from_source(source: &str) -> Option<PathBuf> {
+    let source = source.trim();
+    if source.is_empty() {
+        return None;
+    }
+    let Some(rest) = source.strip_prefix("file://") else {
+        return Some(PathBuf::from(source));
+    };
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    let decoded = percent_encoding::percent_decode_str(rest)
+        .decode_utf8_lossy()
+        .to_string();
+    // file:///C:/Media/bg.jpg -> C:/Media/bg.jpg
+    let is_drive_path =
+        decoded.len() > 2 && decoded.as_bytes()[0] == b'/' && decoded.as_bytes()[2] == b':';
+    Some(PathBuf::from(if is_drive_path {
+        &decoded[1..]
+    } else {
+        decoded.as_str()
+    }))
+}
+
+fn normalize_lines(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace(['\r', '\u{2028}', '\u{2029}'], "\n")
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
@@ -0,0 +1,189 @@
+//! Low-resolution stand-ins for heavy videos while editing
+//!
+//! Scrubbing through a 4K ProRes background or a 60 fps HEVC clip can stall
+//! the editor on an older laptop, even though the output machine plays it
+//! fine. A proxy is a 540p H.264 copy with a keyframe every half second,
+//! cheap to decode and to seek in, rendered into `proxies/` in the app cache
+//! and named after the original's hash. The editor asks which file to load
+//! with `resolve_source`: it gets the proxy once there is one, while the
+//! output window and exports always get the original.
+
+use crate::cpres::{self, CpresError};
+use crate::ffmpeg;
+use crate::media_probe::{self, MediaMetadata};
+use crate::tasks::CancelToken;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const PROXY_PROGRESS_EVENT: &str = "media:proxy-progress";
+
+pub(crate) const CACHE_DIR_NAME: &str = "proxies";
+
+/// Short edge of a proxy
+const PROXY_SIZE: u32 = 540;
+/// Short edge above which a video is heavy whatever its codec
+const HEAVY_SIZE: u32 = 1080;
+/// Frame rate above which a video larger than a proxy is heavy
+const HEAVY_FRAME_RATE: f64 = 31.0;
+/// Codecs that decode cheaply in software; ProRes, HEVC, and AV1 don't
+const LIGHT_CODECS: [&str; 4] = ["h264", "vp8", "mpeg4", "mjpeg"];
+
+/// Where a file is being shown
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaContext {
+    /// The editor's canvas, previews, and scrub bars
+    Editor,
+    /// The output window and exports, which always get the original
+    #[default]
+    Output,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Proxy {
+    /// None when the video is light enough to edit as it is
+    pub path: Option<String>,
+    /// False when the proxy had to be rendered
+    pub cached: bool,
+}
+
+/// Payload of `media:proxy-progress`
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyProgress {
+    /// The original's path
+    pub source: String,
+    /// Share done, 0 to 1
+    pub progress: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResolvedSource {
+    /// The file to load
+    pub path: String,
+    /// Whether `path` is a proxy rather than the original
+    pub proxy: bool,
+}
+
+/// Whether a video is worth a proxy: larger than one, and either big, fast,
+/// or in a codec that's slow to decode
+pub fn needs_proxy(metadata: &MediaMetadata) -> bool {
+    let short_edge = metadata
+        .width
+        .zip(metadata.height)
+        .map(|(width, height)| width.min(height));
+    if short_edge.is_some_and(|edge| edge <= PROXY_SIZE) {
+        return false;
+    }
+    let light_codec = metadata
+        .video_codec
+        .as_deref()
+        .is_some_and(|codec| LIGHT_CODECS.contains(&codec));
+    let fast = metadata
+        .frame_rate
+        .is_some_and(|rate| rate > HEAVY_FRAME_RATE);
+    !light_codec || fast || short_edge.is_none_or(|edge| edge > HEAVY_SIZE)
+}
+
+/// Render a proxy of the video at `path` into the cache in `app_cache_dir`,
+/// or find the one rendered before. `sha256` saves hashing the file when the
+/// caller knows it. `on_progress` gets the share done.
+pub fn generate_proxy(
+    app_cache_dir: &Path,
+    path: &Path,
+    sha256: Option<&str>,
+    cancel: &CancelToken,
+    on_progress: impl Fn(f64),
+) -> Result<Proxy, CpresError> {
+    let digest = digest(path, sha256, cancel)?;
+    let output = proxy_path(app_cache_dir, &digest);
+    if output.is_file() {
+        return Ok(Proxy {
+            path: Some(output.to_string_lossy().to_string()),
+            cached: true,
+        });
+    }
+    if !needs_proxy(&media_probe::probe(path, "video")) {
+        return Ok(Proxy {
+            path: None,
+            cached: false,
+        });
+    }
+
+    cancel.check()?;
+    let dir = app_cache_dir.join(CACHE_DIR_NAME);
+    std::fs::create_dir_all(&dir)?;
+    let temp_file = tempfile::Builder::new().suffix(".mp4").tempfile_in(&dir)?;
+    // Portrait videos are fitted by their width, so the short edge is 540
+    // either way
+    let filter = format!(
+        "scale=w='if(gte(iw,ih),-2,{PROXY_SIZE})':h='if(gte(iw,ih),{PROXY_SIZE},-2)',\
+         format=yuv420p"
+    );
+    let mut command = ffmpeg::command()?;
+    command
+        .arg("-i")
+        .arg(path)
+        .args(["-map", "0:v:0", "-map", "0:a:0?", "-sn", "-dn"])
+        .args(["-vf", &filter])
+        .args(["-c:v", "libx264", "-preset", "veryfast", "-crf", "26"])
+        .args(["-tune", "fastdecode"])
+        .args(["-force_key_frames", "expr:gte(t,n_forced*0.5)"])
+        .args(["-c:a", "aac", "-b:a", "128k", "-ac", "2"])
+        .args(["-movflags", "+faststart", "-f", "mp4"])
+        .args(ffmpeg::PROGRESS_ARGS)
+        .arg(temp_file.path());
+    ffmpeg::run_with_progress(
+        command,
+        ffmpeg::duration(path)?,
+        "proxy rendering",
+        || cancel.is_cancelled(),
+        on_progress,
+    )?;
+    cpres::persist_file(temp_file, &output)?;
+    Ok(Proxy {
+        path: Some(output.to_string_lossy().to_string()),
+        cached: false,
+    })
+}
+
+/// The file to load for the video at `path` in `context`: its proxy in the
+/// editor when one has been rendered, otherwise the original
+pub fn resolve_source(
+    app_cache_dir: &Path,
+    path: &Path,
+    sha256: Option<&str>,
+    context: MediaContext,
+) -> Result<ResolvedSource, CpresError> {
+    let original = ResolvedSource {
+        path: path.to_string_lossy().to_string(),
+        proxy: false,
+    };
+    if context == MediaContext::Output {
+        return Ok(original);
+    }
+    let digest = digest(path, sha256, &CancelToken::default())?;
+    let proxy = proxy_path(app_cache_dir, &digest);
+    if !proxy.is_file() {
+        return Ok(original);
+    }
+    Ok(ResolvedSource {
+        path: proxy.to_string_lossy().to_string(),
+        proxy: true,
+    })
+}
+
+/// The caller's hash when it looks like one, since it names a file
+fn digest(path: &Path, sha256: Option<&str>, cancel: &CancelToken) -> Result<String, CpresError> {
+    match sha256 {
+        Some(digest) if digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            Ok(digest.to_ascii_lowercase())
+        }
+        _ => Ok(cpres::hash_file(path, cancel)?.0),
+    }
+}
+
+fn proxy_path(app_cache_dir: &Path, digest: &str) -> PathBuf {
+    app_cache_dir
+        .join(CACHE_DIR_NAME)
+        .join(format!("{digest}-{PROXY_SIZE}p.mp4"))
+}
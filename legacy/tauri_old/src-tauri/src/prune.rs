@@ -0,0 +1,165 @@
+//! Remove media that no slide or theme references
+//!
+//! Deleting a slide leaves its background in `media/` forever. Pruning finds
+//! manifest media entries whose id is no longer referenced by slides, themes,
+//! or the arrangement, plus files under `media/` that no manifest entry points
+//! to, and rewrites the bundle without them.
+
+use crate::checksums::{Checksums, CHECKSUMS_FILE};
+use crate::cpres::{self, CpresError};
+use crate::history;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashSet};
+use std::fs::File;
+use std::path::Path;
+use tempfile::NamedTempFile;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const MEDIA_ID_KEY: &str = "mediaId";
+
+#[derive(Debug, Serialize)]
+pub struct PrunedMedia {
+    /// Manifest id; None for files under media/ that the manifest doesn't list
+    pub id: Option<String>,
+    pub filename: String,
+    pub path: String,
+    pub compressed_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    pub removed: Vec<PrunedMedia>,
+    /// Bytes the bundle shrinks by
+    pub bytes_reclaimed: u64,
+}
+
+/// Find unreferenced media and, unless `dry_run`, rewrite the bundle without it.
+/// The previous bundle is kept as a history snapshot.
+pub fn prune_media(path: &Path, dry_run: bool) -> Result<PruneReport, CpresError> {
+    let bundle = cpres::open_bundle(path)?;
+    let mut manifest: Value = serde_json::from_str(&bundle.manifest)?;
+    let slides: Value = serde_json::from_str(&bundle.slides)?;
+    let arrangement: Value = serde_json::from_str(&bundle.arrangement)?;
+
+    let mut referenced = BTreeSet::new();
+    cpres::collect_references(&slides, MEDIA_ID_KEY, &mut referenced);
+    cpres::collect_references(&arrangement, MEDIA_ID_KEY, &mut referenced);
+    for theme in &bundle.themes {
+        let theme: Value = serde_json::from_str(&theme.content)?;
+        cpres::collect_references(&theme, MEDIA_ID_KEY, &mut referenced);
+    }
+    // Manifest-level references (e.g. a presentation thumbnail), excluding the media list itself
+    let mut manifest_fields = manifest.clone();
+    if let Some(fields) = manifest_fields.as_object_mut() {
+        fields.remove("media");
+    }
+    cpres::collect_references(&manifest_fields, MEDIA_ID_KEY, &mut referenced);
+
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut removed = Vec::new();
+    let mut kept_paths = HashSet::new();
+    let mut removed_paths = HashSet::new();
+
+    if let Some(media) = manifest.get_mut("media").and_then(|m| m.as_array_mut()) {
+        media.retain(|entry| {
+            let id = str_field(entry, "id");
+            let entry_path = str_field(entry, "path");
+            if referenced.contains(&id) {
+                kept_paths.insert(entry_path);
+                return true;
+            }
+            removed.push(PrunedMedia {
+                id: Some(id),
+                filename: str_field(entry, "filename"),
+                path: entry_path,
+                compressed_bytes: 0,
+            });
+            false
+        });
+    }
+
+    // Files in media/ that no remaining entry points to
+    let stray: Vec<String> = archive
+        .file_names()
+        .filter(|name| name.starts_with("media/") && !name.ends_with('/'))
+        .filter(|name| !kept_paths.contains(*name))
+        .filter(|name| !removed.iter().any(|r| r.path == *name))
+        .map(String::from)
+        .collect();
+    for name in stray {
+        removed.push(PrunedMedia {
+            id: None,
+            filename: name.rsplit('/').next().unwrap_or_default().to_string(),
+            path: name,
+            compressed_bytes: 0,
+        });
+    }
+
+    for item in &mut removed {
+        // Two manifest entries may share a file; only drop it when nothing kept uses it
+        if item.path.is_empty() || kept_paths.contains(&item.path) {
+            continue;
+        }
+        if let Some(index) = archive.index_for_name(&item.path) {
+            item.compressed_bytes = archive.by_index_raw(index)?.compressed_size();
+            removed_paths.insert(item.path.clone());
+        }
+    }
+
+    let report = PruneReport {
+        dry_run,
+        bytes_reclaimed: removed.iter().map(|r| r.compressed_bytes).sum(),
+        removed,
+    };
+    if dry_run || report.removed.is_empty() {
+        return Ok(report);
+    }
+
+    history::snapshot(path, history::DEFAULT_KEEP_VERSIONS)?;
+
+    let parent = path.parent().unwrap_or(Path::new("."));
+    let temp_file = NamedTempFile::new_in(parent)?;
+    let mut zip = ZipWriter::new(temp_file.reopen()?);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut checksums = Checksums::default();
+
+    checksums.write_entry(
+        &mut zip,
+        "manifest.json",
+        serde_json::to_string_pretty(&manifest)?.as_bytes(),
+        options,
+    )?;
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        let name = entry.name().to_string();
+        if entry.is_dir()
+            || name == "manifest.json"
+            || name == CHECKSUMS_FILE
+            || removed_paths.contains(&name)
+        {
+            continue;
+        }
+        drop(entry);
+        checksums.add_from_archive(&mut archive, i, &name)?;
+        zip.raw_copy_file(archive.by_index_raw(i)?)?;
+    }
+    checksums.write(&mut zip, options)?;
+    zip.finish()?;
+
+    // Release the source handle before replacing it (required on Windows)
+    drop(archive);
+    cpres::persist_file(temp_file, path)?;
+
+    Ok(report)
+}
+
+fn str_field(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
@@ -0,0 +1,765 @@
+//! Recording an output to a video file
+//!
+//! ffmpeg grabs the output window's area of the screen (`gdigrab` on
+//! Windows, `avfoundation` on macOS, `x11grab` on Linux), with the computer's
+//! audio from a loopback device when asked, and encodes it to an H.264/AAC
+//! MP4. The virtual output has no window to grab, so its frames are written
+//! to ffmpeg's input at the frame rate instead. The MP4 is written in fragments, so a crash or power cut loses only
+//! the last couple of seconds.
+//!
+//! ffmpeg can't pause a grab, so pausing ends the current segment and
+//! resuming starts another; stopping joins the segments into the file. The
+//! free space on the file's disk is checked before starting and every few
+//! seconds while recording, and the recording stops itself before the disk
+//! fills, emitting `recording:stopped`. `recording:status` follows every
+//! check.
+
+use crate::cpres::CpresError;
+use crate::ffmpeg;
+use crate::screen_capture::Region;
+use crate::storage;
+use crate::virtual_output::VirtualFrame;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use thiserror::Error;
+
+pub const RECORDING_STATUS_EVENT: &str = "recording:status";
+pub const RECORDING_STOPPED_EVENT: &str = "recording:stopped";
+
+/// Free space needed to start recording
+const START_FREE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+/// Free space below which a recording stops itself
+const STOP_FREE_BYTES: u64 = 512 * 1024 * 1024;
+const CHECK_INTERVAL: Duration = Duration::from_secs(3);
+/// How long ffmpeg gets to finish a segment after being asked to quit
+const FINISH_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long ffmpeg gets to reject the screen or audio device
+const START_GRACE: Duration = Duration::from_millis(750);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Error, Debug)]
+pub enum RecordingError {
+    #[error("Recording the screen isn't supported here: {0}")]
+    Unsupported(String),
+
+    #[error("Already recording to {0}")]
+    AlreadyRecording(String),
+
+    #[error("Not recording")]
+    NotRecording,
+
+    #[error("Not enough free space to record: {0}")]
+    DiskFull(String),
+
+    #[error("Recording failed: {0}")]
+    Failed(String),
+
+    #[error(transparent)]
+    Ffmpeg(#[from] CpresError),
+}
+
+impl From<std::io::Error> for RecordingError {
+    fn from(error: std::io::Error) -> Self {
+        CpresError::from(error).into()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RecordingOptions {
+    pub frame_rate: u32,
+    /// H.264 quality (CRF); lower is better and larger, 23 is ffmpeg's default
+    pub quality: u8,
+    /// Record the computer's audio too
+    pub system_audio: bool,
+    /// Device to record it from: a DirectShow device such as "Stereo Mix" on
+    /// Windows (needed there), an AVFoundation device such as "BlackHole
+    /// 2ch" on macOS (needed there), or a PulseAudio source on Linux, where
+    /// the default output's monitor is used without one
+    pub audio_device: Option<String>,
+}
+
+impl Default for RecordingOptions {
+    fn default() -> Self {
+        Self {
+            frame_rate: 30,
+            quality: 23,
+            system_audio: false,
+            audio_device: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingState {
+    Idle,
+    Recording,
+    Paused,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingStatus {
+    pub state: RecordingState,
+    pub path: Option<String>,
+    /// Time recorded, not counting pauses
+    pub seconds: f64,
+    pub bytes: u64,
+    pub free_bytes: Option<u64>,
+}
+
+/// Why a recording ended without being stopped
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingStopped {
+    pub path: Option<String>,
+    pub reason: String,
+}
+
+/// What a recording is taken from
+pub enum RecordingSource {
+    /// The area of the screen an output window covers
+    Screen(Region),
+    /// The virtual output's frames, at its size when the recording started
+    Virtual {
+        width: u32,
+        height: u32,
+        frame: VirtualFrame,
+    },
+}
+
+/// Managed state: the recording in progress
+#[derive(Clone)]
+pub struct Recorder {
+    app: AppHandle,
+    session: Arc<Mutex<Option<Session>>>,
+}
+
+struct Session {
+    /// Told apart from a later recording by the checking thread
+    id: u64,
+    path: PathBuf,
+    source: RecordingSource,
+    options: RecordingOptions,
+    /// Finished segments, joined into `path` when stopped
+    segments: Vec<PathBuf>,
+    current: Option<Segment>,
+    /// Time in the finished segments
+    recorded: Duration,
+}
+
+struct Segment {
+    child: Child,
+    path: PathBuf,
+    started: Instant,
+    log: Arc<Mutex<Vec<String>>>,
+    /// Writing the virtual output's frames to ffmpeg, until told to stop
+    feeder: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl Recorder {
+    pub fn new(app: AppHandle) -> Self {
+        Self {
+            app,
+            session: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Start recording `source` to an MP4 at `path`
+    pub fn start(
+        &self,
+        source: RecordingSource,
+        path: &Path,
+        options: RecordingOptions,
+    ) -> Result<RecordingStatus, RecordingError> {
+        let mut session = self.lock_session();
+        if let Some(session) = session.as_ref() {
+            return Err(RecordingError::AlreadyRecording(
+                session.path.display().to_string(),
+            ));
+        }
+        let storage = storage::storage_status(path);
+        if let Some(reason) = storage.reason {
+            return Err(RecordingError::Failed(reason));
+        }
+        if let Some(free) = storage.free_bytes.filter(|free| *free < START_FREE_BYTES) {
+            return Err(RecordingError::DiskFull(format!(
+                "{} MB free, {} MB needed",
+                free / 1_000_000,
+                START_FREE_BYTES / 1_000_000
+            )));
+        }
+
+        let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+        let mut started = Session {
+            id,
+            path: path.to_path_buf(),
+            source,
+            options,
+            segments: Vec::new(),
+            current: None,
+            recorded: Duration::ZERO,
+        };
+        started.current = Some(started.start_segment()?);
+        log::info!("Recording to {}", path.display());
+        *session = Some(started);
+        let status = status_of(session.as_ref());
+        drop(session);
+
+        let recorder = self.clone();
+        std::thread::spawn(move || recorder.check_loop(id));
+        Ok(status)
+    }
+
+    /// End the current segment; the time paused isn't recorded
+    pub fn pause(&self) -> Result<RecordingStatus, RecordingError> {
+        let mut session = self.lock_session();
+        let active = session.as_mut().ok_or(RecordingError::NotRecording)?;
+        if let Some(segment) = active.current.take() {
+            active.recorded += segment.started.elapsed();
+            active.segments.push(segment.finish()?);
+        }
+        Ok(status_of(session.as_ref()))
+    }
+
+    /// Start a new segment after a pause
+    pub fn resume(&self) -> Result<RecordingStatus, RecordingError> {
+        let mut session = self.lock_session();
+        let active = session.as_mut().ok_or(RecordingError::NotRecording)?;
+        if active.current.is_none() {
+            active.current = Some(active.start_segment()?);
+        }
+        Ok(status_of(session.as_ref()))
+    }
+
+    /// Finish the recording and join its segments into the file
+    pub fn stop(&self) -> Result<RecordingStatus, RecordingError> {
+        let session = self.lock_session().take();
+        let session = session.ok_or(RecordingError::NotRecording)?;
+        let path = session.finish()?;
+        log::info!("Recorded {}", path.display());
+        Ok(RecordingStatus {
+            state: RecordingState::Idle,
+            bytes: std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+            free_bytes: storage::storage_status(&path).free_bytes,
+            path: Some(path.to_string_lossy().to_string()),
+            seconds: 0.0,
+        })
+    }
+
+    pub fn status(&self) -> RecordingStatus {
+        status_of(self.lock_session().as_ref())
+    }
+
+    /// Stop the recording when ffmpeg quits or the disk runs low, reporting
+    /// the status until recording `id` ends
+    fn check_loop(&self, id: u64) {
+        loop {
+            std::thread::sleep(CHECK_INTERVAL);
+            let mut session = self.lock_session();
+            let Some(active) = session.as_mut().filter(|active| active.id == id) else {
+                return;
+            };
+            let status = status_of(Some(active));
+            let reason = match (&mut active.current, status.free_bytes) {
+                (_, Some(free)) if free < STOP_FREE_BYTES => Some(format!(
+                    "The disk is almost full ({} MB free)",
+                    free / 1_000_000
+                )),
+                (Some(segment), _) => match segment.child.try_wait() {
+                    Ok(Some(exit)) => {
+                        Some(format!("ffmpeg stopped ({exit}): {}", segment.log_text()))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+            let Some(reason) = reason else {
+                drop(session);
+                let _ = self.app.emit(RECORDING_STATUS_EVENT, status);
+                continue;
+            };
+
+            log::warn!("Recording stopped: {reason}");
+            let ended = session.take();
+            drop(session);
+            let path = ended.and_then(|ended| match ended.finish() {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    log::warn!("Could not finish the recording: {e}");
+                    None
+                }
+            });
+            let _ = self.app.emit(
+                RECORDING_STOPPED_EVENT,
+                RecordingStopped {
+                    path: path.map(|path| path.to_string_lossy().to_string()),
+                    reason,
+                },
+            );
+            let _ = self.app.emit(RECORDING_STATUS_EVENT, status_of(None));
+            return;
+        }
+    }
+
+    fn lock_session(&self) -> MutexGuard<'_, Option<Session>> {
+        self.session
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Session {
+    fn start_segment(&self) -> Result<Segment, RecordingError> {
+        let path = segment_path(&self.path, self.segments.len() + 1);
+        let mut command = ffmpeg::command()?;
+        match &self.source {
+            RecordingSource::Screen(region) => grab_args(&mut command, region, &self.options)?,
+            RecordingSource::Virtual { width, height, .. } => {
+                command
+                    .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+                    .args(["-video_size", &format!("{width}x{height}")])
+                    .arg("-framerate")
+                    .arg(self.options.frame_rate.to_string())
+                    .args(["-i", "-"]);
+                audio_args(&mut command, &self.options)?;
+            }
+        }
+        command
+            .args(["-c:v", "libx264", "-preset", "veryfast", "-crf"])
+            .arg(self.options.quality.to_string())
+            .args(["-pix_fmt", "yuv420p"]);
+        if self.options.system_audio {
+            command.args(["-c:a", "aac", "-b:a", "192k"]);
+        }
+        command
+            .args(["-movflags", "+frag_keyframe+empty_moov+default_base_moof"])
+            .arg(&path);
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let log = Arc::new(Mutex::new(Vec::new()));
+        if let Some(stderr) = child.stderr.take() {
+            let log = log.clone();
+            std::thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    log::debug!("ffmpeg (recording): {line}");
+                    if let Ok(mut log) = log.lock() {
+                        log.push(line);
+                    }
+                }
+            });
+        }
+        let feeder = match (&self.source, child.stdin.take()) {
+            (
+                RecordingSource::Virtual {
+                    width,
+                    height,
+                    frame,
+                },
+                Some(stdin),
+            ) => {
+                let stop = Arc::new(AtomicBool::new(false));
+                let (width, height, frame) = (*width, *height, frame.clone());
+                let frame_rate = self.options.frame_rate;
+                let stopped = stop.clone();
+                let thread = std::thread::spawn(move || {
+                    feed(stdin, width, height, &frame, frame_rate, &stopped);
+                });
+                Some((stop, thread))
+            }
+            (_, stdin) => {
+                child.stdin = stdin;
+                None
+            }
+        };
+        let mut segment = Segment {
+            child,
+            path,
+            started: Instant::now(),
+            log,
+            feeder,
+        };
+
+        std::thread::sleep(START_GRACE);
+        if let Ok(Some(exit)) = segment.child.try_wait() {
+            if let Some((stop, _)) = &segment.feeder {
+                stop.store(true, Ordering::SeqCst);
+            }
+            let _ = std::fs::remove_file(&segment.path);
+            return Err(CpresError::Ffmpeg(format!(
+                "Recording failed to start ({exit}): {}",
+                segment.log_text()
+            ))
+            .into());
+        }
+        segment.started = Instant::now();
+        Ok(segment)
+    }
+
+    /// End the current segment and join them all into the file
+    fn finish(mut self) -> Result<PathBuf, RecordingError> {
+        if let Some(segment) = self.current.take() {
+            self.segments.push(segment.finish()?);
+        }
+        let segments: Vec<PathBuf> = self
+            .segments
+            .into_iter()
+            .filter(|segment| segment.is_file())
+            .collect();
+        match segments.as_slice() {
+            [] => Err(RecordingError::Failed("Nothing was recorded".to_string())),
+            [only] => {
+                std::fs::rename(only, &self.path)?;
+                Ok(self.path)
+            }
+            _ => {
+                join(&segments, &self.path)?;
+                for segment in &segments {
+                    let _ = std::fs::remove_file(segment);
+                }
+                Ok(self.path)
+            }
+        }
+    }
+}
+
+impl Segment {
+    /// Ask ffmpeg to quit, or end the frames it's fed, so the file is
+    /// finished, and wait for it
+    fn finish(mut self) -> Result<PathBuf, RecordingError> {
+        if let Some((stop, _)) = &self.feeder {
+            stop.store(true, Ordering::SeqCst);
+        } else if let Some(mut stdin) = self.child.stdin.take() {
+            let _ = stdin.write_all(b"q");
+        }
+        let deadline = Instant::now() + FINISH_TIMEOUT;
+        loop {
+            match self.child.try_wait()? {
+                Some(_) => break,
+                None if Instant::now() >= deadline => {
+                    log::warn!("ffmpeg didn't finish the recording segment; stopping it");
+                    let _ = self.child.kill();
+                    let _ = self.child.wait();
+                    break;
+                }
+                None => std::thread::sleep(Duration::from_millis(100)),
+            }
+        }
+        if let Some((_, thread)) = self.feeder.take() {
+            let _ = thread.join();
+        }
+        Ok(self.path)
+    }
+
+    fn log_text(&self) -> String {
+        self.log
+            .lock()
+            .map(|log| log.join("\n").trim().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Write `frame` to ffmpeg's input `frame_rate` times a second until
+/// `stop`, then close it; black is written while the frame isn't
+/// `width`x`height`, as when the virtual output is stopped or resized
+fn feed(
+    mut stdin: ChildStdin,
+    width: u32,
+    height: u32,
+    frame: &VirtualFrame,
+    frame_rate: u32,
+    stop: &AtomicBool,
+) {
+    let size = width as usize * height as usize * 4;
+    let black = crate::output_frames::black(width, height);
+    let interval = Duration::from_secs(1) / frame_rate.max(1);
+    let mut next = Instant::now();
+    while !stop.load(Ordering::SeqCst) {
+        let Ok(data) = frame.lock().map(|frame| frame.clone()) else {
+            return;
+        };
+        let data = if data.len() == size {
+            &data[..]
+        } else {
+            &black[..]
+        };
+        if let Err(e) = stdin.write_all(data) {
+            log::debug!("Stopped feeding the recording: {e}");
+            return;
+        }
+        next += interval;
+        match next.checked_duration_since(Instant::now()) {
+            Some(wait) => std::thread::sleep(wait),
+            // Behind, as after a slow slide; carry on from now
+            None => next = Instant::now(),
+        }
+    }
+}
+
+/// Where segment `number` of the recording at `path` is written:
+/// "Service.part2.mp4" beside "Service.mp4"
+fn segment_path(path: &Path, number: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "recording".to_string());
+    path.with_file_name(format!("{stem}.part{number}.mp4"))
+}
+
+/// Join `segments` end to end into `path` without re-encoding
+fn join(segments: &[PathBuf], path: &Path) -> Result<(), RecordingError> {
+    let size: u64 = segments
+        .iter()
+        .filter_map(|segment| std::fs::metadata(segment).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    if let Some(free) = storage::storage_status(path)
+        .free_bytes
+        .filter(|free| *free < size)
+    {
+        return Err(RecordingError::DiskFull(format!(
+            "joining the paused parts needs {} MB, {} MB free; the parts were kept",
+            size / 1_000_000,
+            free / 1_000_000
+        )));
+    }
+
+    let list = segments
+        .iter()
+        .map(|segment| {
+            let segment = segment.to_string_lossy().replace('\'', r"'\''");
+            format!("file '{segment}'\n")
+        })
+        .collect::<String>();
+    let mut list_file = tempfile::NamedTempFile::new()?;
+    list_file.write_all(list.as_bytes())?;
+    let mut command = ffmpeg::command()?;
+    command
+        .args(["-f", "concat", "-safe", "0", "-i"])
+        .arg(list_file.path())
+        .args(["-c", "copy", "-movflags", "+faststart"])
+        .arg(path);
+    let output = command.stdin(Stdio::null()).output()?;
+    if !output.status.success() {
+        return Err(CpresError::Ffmpeg(format!(
+            "Joining the recording failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+fn status_of(session: Option<&Session>) -> RecordingStatus {
+    let Some(session) = session else {
+        return RecordingStatus {
+            state: RecordingState::Idle,
+            path: None,
+            seconds: 0.0,
+            bytes: 0,
+            free_bytes: None,
+        };
+    };
+    let current = session.current.as_ref();
+    let seconds =
+        session.recorded + current.map_or(Duration::ZERO, |segment| segment.started.elapsed());
+    let bytes = session
+        .segments
+        .iter()
+        .chain(current.map(|segment| &segment.path))
+        .filter_map(|segment| std::fs::metadata(segment).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    RecordingStatus {
+        state: if current.is_some() {
+            RecordingState::Recording
+        } else {
+            RecordingState::Paused
+        },
+        path: Some(session.path.to_string_lossy().to_string()),
+        seconds: seconds.as_secs_f64(),
+        bytes,
+        free_bytes: storage::storage_status(&session.path).free_bytes,
+    }
+}
+
+/// ffmpeg inputs grabbing `region` and, when asked, the system audio
+#[cfg(windows)]
+fn grab_args(
+    command: &mut Command,
+    region: &Region,
+    options: &RecordingOptions,
+) -> Result<(), RecordingError> {
+    command
+        .args(["-f", "gdigrab", "-draw_mouse", "0", "-framerate"])
+        .arg(options.frame_rate.to_string())
+        .args(["-offset_x", &region.x.to_string()])
+        .args(["-offset_y", &region.y.to_string()])
+        .args([
+            "-video_size",
+            &format!("{}x{}", region.width, region.height),
+        ])
+        .args(["-i", "desktop"]);
+    audio_args(command, options)
+}
+
+/// ffmpeg input for the system audio, when asked
+#[cfg(windows)]
+fn audio_args(command: &mut Command, options: &RecordingOptions) -> Result<(), RecordingError> {
+    if options.system_audio {
+        let device = options.audio_device.as_deref().ok_or_else(|| {
+            RecordingError::Unsupported(
+                "pick a loopback audio device, such as Stereo Mix, to record the computer's audio"
+                    .to_string(),
+            )
+        })?;
+        command
+            .args(["-f", "dshow", "-i"])
+            .arg(format!("audio={device}"));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn grab_args(
+    command: &mut Command,
+    region: &Region,
+    options: &RecordingOptions,
+) -> Result<(), RecordingError> {
+    use core_graphics::display::CGDisplay;
+
+    // AVFoundation numbers the screens in Core Graphics' order, which places
+    // them in points
+    let (x, y) = (
+        (region.x as f64 + region.width as f64 / 2.0) / region.scale,
+        (region.y as f64 + region.height as f64 / 2.0) / region.scale,
+    );
+    let displays = CGDisplay::active_displays()
+        .map_err(|e| RecordingError::Failed(format!("Could not list the displays ({e})")))?;
+    let screen = displays
+        .iter()
+        .position(|id| {
+            let bounds = CGDisplay::new(*id).bounds();
+            x >= bounds.origin.x
+                && x < bounds.origin.x + bounds.size.width
+                && y >= bounds.origin.y
+                && y < bounds.origin.y + bounds.size.height
+        })
+        .ok_or_else(|| RecordingError::Failed("The window isn't on a display".to_string()))?;
+    let audio = match (options.system_audio, options.audio_device.as_deref()) {
+        (false, _) => "none",
+        (true, Some(device)) => device,
+        (true, None) => {
+            return Err(RecordingError::Unsupported(
+                "macOS can't record its own audio without a loopback device such as BlackHole"
+                    .to_string(),
+            ))
+        }
+    };
+    command
+        .args(["-f", "avfoundation", "-capture_cursor", "0", "-framerate"])
+        .arg(options.frame_rate.to_string())
+        .arg("-i")
+        .arg(format!("Capture screen {screen}:{audio}"));
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn audio_args(command: &mut Command, options: &RecordingOptions) -> Result<(), RecordingError> {
+    if !options.system_audio {
+        return Ok(());
+    }
+    let device = options.audio_device.as_deref().ok_or_else(|| {
+        RecordingError::Unsupported(
+            "macOS can't record its own audio without a loopback device such as BlackHole"
+                .to_string(),
+        )
+    })?;
+    command
+        .args(["-f", "avfoundation", "-i"])
+        .arg(format!(":{device}"));
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn grab_args(
+    command: &mut Command,
+    region: &Region,
+    options: &RecordingOptions,
+) -> Result<(), RecordingError> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() && std::env::var_os("DISPLAY").is_none() {
+        return Err(RecordingError::Unsupported(
+            "ffmpeg can only grab X11 screens; log in to an X11 session to record".to_string(),
+        ));
+    }
+    let display = std::env::var("DISPLAY")
+        .map_err(|_| RecordingError::Unsupported("no X11 display".to_string()))?;
+    command
+        .args(["-f", "x11grab", "-draw_mouse", "0", "-framerate"])
+        .arg(options.frame_rate.to_string())
+        .args([
+            "-video_size",
+            &format!("{}x{}", region.width, region.height),
+        ])
+        .arg("-i")
+        .arg(format!("{display}+{},{}", region.x, region.y));
+    audio_args(command, options)
+}
+
+#[cfg(target_os = "linux")]
+fn audio_args(command: &mut Command, options: &RecordingOptions) -> Result<(), RecordingError> {
+    if options.system_audio {
+        let source = options
+            .audio_device
+            .as_deref()
+            .unwrap_or("@DEFAULT_MONITOR@");
+        command.args(["-f", "pulse", "-i", source]);
+    }
+    Ok(())
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn grab_args(
+    _command: &mut Command,
+    _region: &Region,
+    _options: &RecordingOptions,
+) -> Result<(), RecordingError> {
+    Err(RecordingError::Unsupported(
+        "no screen grabber for this system".to_string(),
+    ))
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn audio_args(_command: &mut Command, options: &RecordingOptions) -> Result<(), RecordingError> {
+    if options.system_audio {
+        return Err(RecordingError::Unsupported(
+            "no audio grabber for this system".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(Recorder::new(app.clone()));
+}
+
+pub fn shutdown(app: &AppHandle) {
+    if let Some(recorder) = app.try_state::<Recorder>() {
+        if recorder.status().state != RecordingState::Idle {
+            if let Err(e) = recorder.stop() {
+                log::warn!("Could not finish the recording: {e}");
+            }
+        }
+    }
+}
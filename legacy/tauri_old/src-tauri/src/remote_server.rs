@@ -0,0 +1,356 @@
+//! Controlling the show from phones, tablets and other programs
+//!
+//! The app can serve the local network over HTTP, with no cloud service in
+//! between, while it's turned on:
+//!
+//! - `GET /state` answers with the program state as JSON (see `program`)
+//! - `GET /ws` is a WebSocket sent the program state when it connects and
+//!   whenever it changes. It takes commands as JSON: `{"type": "next"}`,
+//!   `{"type": "previous"}`, `{"type": "goTo", "index": 3}`,
+//!   `{"type": "screen", "screen": "blackout"}` (or `live`, `logo`,
+//!   `clear`), and `{"type": "state"}` to be sent the state again.
+//!
+//! Both need `?token=` to match the server's token, which is made up when
+//! none is given: the live output shouldn't be open to anyone who can reach
+//! the port. It only listens on this computer unless told to listen on the
+//! network, and WebSockets opened from other sites' pages are turned away,
+//! since a browser would connect them from the operator's own machine.
+//! Slide changes are worked out by the main window, as for its own keys, so
+//! they're passed on to it as `remote:command`; the screen is changed here.
+
+use crate::program::{Program, ProgramScreen, ProgramState};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::header::{HOST, ORIGIN};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, watch};
+
+pub const REMOTE_COMMAND_EVENT: &str = "remote:command";
+
+/// Program states kept for connections that fall behind
+const UPDATE_BACKLOG: usize = 16;
+
+#[derive(Error, Debug)]
+pub enum RemoteError {
+    #[error("Could not listen on port {port}: {source}")]
+    Bind {
+        port: u16,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RemoteOptions {
+    /// 0 to have one picked
+    pub port: u16,
+    /// What clients have to give as `?token=`; None to have one made up
+    pub token: Option<String>,
+    /// Listen on the local network; only this computer otherwise
+    pub lan: bool,
+}
+
+impl Default for RemoteOptions {
+    fn default() -> Self {
+        Self {
+            port: 8765,
+            token: None,
+            lan: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteStatus {
+    pub running: bool,
+    /// The port it's listening on, while running
+    pub port: Option<u16>,
+    /// What remotes have to give, while running
+    pub token: Option<String>,
+}
+
+/// What remotes can ask for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RemoteCommand {
+    Next,
+    Previous,
+    /// The slide at `index` in the live presentation
+    GoTo {
+        index: u32,
+    },
+    Screen {
+        screen: ProgramScreen,
+    },
+    State,
+}
+
+/// What remotes are sent
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum RemoteMessage {
+    State { state: ProgramState },
+    Error { message: String },
+}
+
+#[derive(Deserialize)]
+struct Auth {
+    token: Option<String>,
+}
+
+/// What each request is handled with
+#[derive(Clone)]
+struct Shared {
+    app: AppHandle,
+    token: String,
+    updates: broadcast::Sender<ProgramState>,
+    stopped: watch::Receiver<bool>,
+}
+
+impl Shared {
+    fn allowed(&self, auth: &Auth) -> bool {
+        auth.token.as_deref() == Some(self.token.as_str())
+    }
+
+    fn state(&self) -> ProgramState {
+        self.app.state::<Program>().state()
+    }
+
+    /// Carry out `text` as a command, and say what to send back
+    fn command(&self, text: &str) -> Option<RemoteMessage> {
+        let command = match serde_json::from_str::<RemoteCommand>(text) {
+            Ok(command) => command,
+            Err(e) => {
+                return Some(RemoteMessage::Error {
+                    message: format!("Unknown command: {e}"),
+                })
+            }
+        };
+        match command {
+            RemoteCommand::State => Some(RemoteMessage::State {
+                state: self.state(),
+            }),
+            // The change comes back as an update
+            RemoteCommand::Screen { screen } => {
+                self.app.state::<Program>().set_screen(screen);
+                None
+            }
+            command => {
+                let _ = self.app.emit_to("main", REMOTE_COMMAND_EVENT, command);
+                None
+            }
+        }
+    }
+}
+
+struct Running {
+    port: u16,
+    token: String,
+    stop: watch::Sender<bool>,
+    /// Done once the port is free again
+    task: JoinHandle<()>,
+}
+
+/// Managed state: the server while it's running
+pub struct RemoteServer {
+    app: AppHandle,
+    updates: broadcast::Sender<ProgramState>,
+    running: Mutex<Option<Running>>,
+}
+
+impl RemoteServer {
+    fn new(app: &AppHandle) -> Self {
+        Self {
+            app: app.clone(),
+            updates: broadcast::channel(UPDATE_BACKLOG).0,
+            running: Mutex::new(None),
+        }
+    }
+
+    /// Start serving, or start again with new options
+    pub async fn start(&self, options: RemoteOptions) -> Result<RemoteStatus, RemoteError> {
+        self.stop().await;
+        let bind = |source| RemoteError::Bind {
+            port: options.port,
+            source,
+        };
+        let host = if options.lan { "0.0.0.0" } else { "127.0.0.1" };
+        let listener = TcpListener::bind((host, options.port))
+            .await
+            .map_err(bind)?;
+        let port = listener.local_addr().map_err(bind)?.port();
+
+        let token = options
+            .token
+            .filter(|token| !token.is_empty())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+        let (stop, stopped) = watch::channel(false);
+        let shared = Shared {
+            app: self.app.clone(),
+            token: token.clone(),
+            updates: self.updates.clone(),
+            stopped: stopped.clone(),
+        };
+        let router = Router::new()
+            .route("/state", get(state))
+            .route("/ws", get(socket))
+            .with_state(shared);
+        let task = tauri::async_runtime::spawn(async move {
+            let mut stopped = stopped;
+            let served = axum::serve(listener, router)
+                .with_graceful_shutdown(async move {
+                    let _ = stopped.wait_for(|stopped| *stopped).await;
+                })
+                .await;
+            if let Err(e) = served {
+                log::warn!("The remote control server failed: {e}");
+            }
+        });
+        log::info!("Serving remote control on {host}:{port}");
+
+        if let Ok(mut running) = self.running.lock() {
+            *running = Some(Running {
+                port,
+                token,
+                stop,
+                task,
+            });
+        }
+        Ok(self.status())
+    }
+
+    /// Stop serving, closing the connections, and wait for the port to be free
+    pub async fn stop(&self) {
+        if let Some(task) = self.signal_stop() {
+            let _ = task.await;
+        }
+    }
+
+    /// Tell the server to stop without waiting for it
+    fn signal_stop(&self) -> Option<JoinHandle<()>> {
+        let running = self
+            .running
+            .lock()
+            .ok()
+            .and_then(|mut running| running.take())?;
+        let _ = running.stop.send(true);
+        log::info!("Stopped serving remote control on port {}", running.port);
+        Some(running.task)
+    }
+
+    pub fn status(&self) -> RemoteStatus {
+        let running = self.running.lock().ok();
+        let running = running.as_ref().and_then(|running| running.as_ref());
+        RemoteStatus {
+            running: running.is_some(),
+            port: running.map(|running| running.port),
+            token: running.map(|running| running.token.clone()),
+        }
+    }
+
+    /// Send the connected remotes the program state
+    pub fn broadcast(&self, state: &ProgramState) {
+        // Nobody listening is fine
+        let _ = self.updates.send(state.clone());
+    }
+}
+
+async fn state(State(shared): State<Shared>, Query(auth): Query<Auth>) -> Response {
+    if !shared.allowed(&auth) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    Json(shared.state()).into_response()
+}
+
+async fn socket(
+    upgrade: WebSocketUpgrade,
+    headers: HeaderMap,
+    State(shared): State<Shared>,
+    Query(auth): Query<Auth>,
+) -> Response {
+    if !same_origin(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if !shared.allowed(&auth) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    upgrade.on_upgrade(move |socket| connected(socket, shared))
+}
+
+/// Whether a WebSocket was opened from this server's own page, or by
+/// something other than a browser, which sends no `Origin`
+fn same_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(ORIGIN) else {
+        return true;
+    };
+    let host = headers.get(HOST).and_then(|host| host.to_str().ok());
+    match (origin.to_str(), host) {
+        (Ok(origin), Some(host)) => {
+            origin.strip_prefix("http://") == Some(host)
+                || origin.strip_prefix("https://") == Some(host)
+        }
+        _ => false,
+    }
+}
+
+/// Serve a remote until it disconnects or the server stops
+async fn connected(mut socket: WebSocket, shared: Shared) {
+    let mut updates = shared.updates.subscribe();
+    let mut stopped = shared.stopped.clone();
+    let state = shared.state();
+    if send(&mut socket, &RemoteMessage::State { state })
+        .await
+        .is_err()
+    {
+        return;
+    }
+    loop {
+        let reply = tokio::select! {
+            update = updates.recv() => match update {
+                Ok(state) => Some(RemoteMessage::State { state }),
+                // Fell behind: the latest state covers what was missed
+                Err(broadcast::error::RecvError::Lagged(_)) => Some(RemoteMessage::State {
+                    state: shared.state(),
+                }),
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => shared.command(text.as_str()),
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => None,
+            },
+            _ = stopped.wait_for(|stopped| *stopped) => return,
+        };
+        if let Some(reply) = reply {
+            if send(&mut socket, &reply).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, message: &RemoteMessage) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).unwrap_or_default();
+    socket.send(Message::Text(text.into())).await
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(RemoteServer::new(app));
+}
+
+pub fn shutdown(app: &AppHandle) {
+    if let Some(server) = app.try_state::<RemoteServer>() {
+        server.signal_stop();
+    }
+}
@@ -0,0 +1,1579 @@
+//! Slide rasterizer used by the exporters
+//!
+//! Draws a slide the way the output window shows it: the background, image
+//! cues on the underlay, the visible layers bottom to top, then image cues on
+//! the overlay. Text uses the fonts embedded in the bundle first and falls
+//! back to installed system fonts.
+//!
+//! SVG images are drawn at the size they cover, so logos stay sharp. Video
+//! (backgrounds, layers and cues), web layers and vector layers are not drawn.
+//!
+//! Built with the `gpu` feature, backgrounds and text are drawn and
+//! everything is composited on the GPU (see `gpu_render`): text is laid out
+//! here, and shapes, images and text layers with effects or a blend mode are
+//! still drawn here. Without a usable GPU the whole slide is drawn here.
+
+use crate::bundle_reader::{self, BundleReader};
+use crate::cpres::{self, CpresError};
+use crate::svg;
+use crate::thumbnails;
+use ab_glyph::{point, Font, FontVec, GlyphId, PxScale, ScaleFont};
+use font_kit::family_name::FamilyName;
+use font_kit::handle::Handle;
+use font_kit::properties::{Properties, Style, Weight};
+use font_kit::source::SystemSource;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use tiny_skia::{
+    BlendMode, Color, FillRule, FilterQuality, GradientStop, LinearGradient, Paint, PathBuilder,
+    Pattern, Pixmap, PixmapPaint, Point, Rect, SpreadMode, Stroke, Transform,
+};
+use zip::ZipArchive;
+
+const DEFAULT_SLIDE_SIZE: (f64, f64) = (1920.0, 1080.0);
+/// Text may spill past its box unless the layer clips; room kept on each side, as a share of the box
+const TEXT_OVERFLOW: f32 = 0.5;
+/// Text padding when the layer doesn't set one, as a percentage of the box width
+const DEFAULT_TEXT_PADDING: f32 = 2.0;
+/// Bezier handle length for a quarter circle
+const ARC_HANDLE: f32 = 0.552_284_8;
+/// Subpixel positions a glyph drawn on the GPU can take, per pixel
+#[cfg(feature = "gpu")]
+const GLYPH_BINS: f32 = 4.0;
+
+/// An open bundle whose slides can be drawn in presentation order
+pub struct SlideRenderer {
+    archive: ZipArchive<BundleReader>,
+    title: String,
+    slides: Vec<Value>,
+    order: Vec<usize>,
+    theme: Option<Value>,
+    /// Bundle path of each media entry, by media id
+    media: HashMap<String, String>,
+    bundle_fonts: Vec<BundleFont>,
+    base_size: (f64, f64),
+    images: HashMap<String, Option<Arc<Pixmap>>>,
+    fonts: HashMap<(String, u16, bool), Option<Arc<FontVec>>>,
+}
+
+struct BundleFont {
+    family: String,
+    weight: u16,
+    italic: bool,
+    path: String,
+}
+
+/// How a slide enters, from its animations
+pub struct Transition {
+    /// "none", "fade", "slide-left", "zoom-in", ...
+    pub kind: String,
+    pub seconds: f64,
+    /// CSS easing keyword
+    pub easing: String,
+}
+
+/// A slide's background, as found on the slide, its overrides or the theme
+pub(crate) enum Backdrop {
+    Solid(Color),
+    Gradient {
+        /// CSS angle in degrees; 180 runs top to bottom
+        angle: f32,
+        /// Positions from 0 to 1, in order
+        stops: Vec<(f32, Color)>,
+        /// The first stop's color, for gradients that can't be drawn
+        fallback: Option<Color>,
+    },
+    Image {
+        /// Tells images apart for the GPU's texture cache
+        #[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+        key: String,
+        image: Arc<Pixmap>,
+        /// CSS object-fit
+        fit: String,
+        /// CSS object-position, in percent
+        position: (f32, f32),
+        opacity: f32,
+    },
+}
+
+/// What of a slide is drawn
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Part {
+    Whole,
+    /// Everything but the background itself, for the GPU to draw over it
+    #[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+    AboveBackground,
+    /// The layers and overlay media, for keying
+    Foreground,
+}
+
+/// One laid-out line of text, with glyph offsets from the line start
+struct Line {
+    glyphs: Vec<(GlyphId, f32)>,
+    width: f32,
+}
+
+/// A text layer laid out on its surface
+struct TextLayout {
+    font: Arc<FontVec>,
+    scale: PxScale,
+    /// Each glyph with its position on the baseline
+    glyphs: Vec<(GlyphId, ab_glyph::Point)>,
+    paint: TextPaint,
+}
+
+/// How a text layer's glyph coverage is painted, bottom to top
+#[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+pub(crate) struct TextPaint {
+    /// Color, blur radius and offset, in pixels
+    pub shadow: Option<(Color, f32, (i32, i32))>,
+    /// Color and how far the outline reaches past the glyph edge, in pixels
+    pub outline: Option<(Color, f32)>,
+    pub fill: Option<Color>,
+}
+
+/// Part of what sits above a slide's background, bottom to top
+#[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+pub(crate) enum Piece {
+    /// Drawn here, on transparency, the size of the slide
+    Pixels(Pixmap),
+    /// A text layer for the GPU to draw
+    Text(TextRun),
+}
+
+/// A text layer for the GPU to draw: its glyphs, and how to paint them and
+/// place them on the slide
+#[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+pub(crate) struct TextRun {
+    /// Size of the layer's surface, its overflow margin included
+    pub size: (u32, u32),
+    /// Kept so the glyph keys stay unique while the font is cached
+    pub font: Arc<FontVec>,
+    /// Glyphs positioned on the surface
+    pub glyphs: Vec<(GlyphKey, ab_glyph::OutlinedGlyph)>,
+    pub paint: TextPaint,
+    /// From the surface onto the slide
+    pub placement: Transform,
+    pub opacity: f32,
+}
+
+/// Tells a glyph's coverage apart: its font, id, size and subpixel offset
+#[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct GlyphKey {
+    pub font: usize,
+    pub id: u16,
+    pub size: (u32, u32),
+    pub bins: (u8, u8),
+}
+
+/// Where a layer's surface goes on the slide, and how it's blended
+struct LayerPlacement<'a> {
+    /// The layer's box on its surface, inside the margin
+    frame: Rect,
+    /// Surface size, with room for text overflow, strokes and effects
+    size: (u32, u32),
+    transform: Transform,
+    opacity: f32,
+    blend_mode: BlendMode,
+    /// Enabled layer effects
+    effects: Vec<&'a Value>,
+}
+
+impl SlideRenderer {
+    pub fn open(path: &Path) -> Result<Self, CpresError> {
+        let parsed = cpres::open_bundle(path)?;
+        let manifest: Value = serde_json::from_str(&parsed.manifest)?;
+        let slides: Vec<Value> = serde_json::from_str(&parsed.slides)?;
+        let arrangement: Value = serde_json::from_str(&parsed.arrangement)?;
+
+        let ids: HashMap<&str, usize> = slides
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slide)| Some((slide.get("id")?.as_str()?, i)))
+            .collect();
+        let mut order: Vec<usize> = arrangement
+            .get("order")
+            .and_then(Value::as_array)
+            .map(|order| {
+                order
+                    .iter()
+                    .filter_map(|id| ids.get(id.as_str()?).copied())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if order.is_empty() {
+            order = (0..slides.len()).collect();
+        }
+
+        let themes: Vec<Value> = parsed
+            .themes
+            .iter()
+            .filter_map(|theme| serde_json::from_str(&theme.content).ok())
+            .collect();
+        let theme_id = manifest.get("themeId").and_then(Value::as_str);
+        let theme = themes
+            .iter()
+            .find(|theme| theme_id.is_some() && theme.get("id").and_then(Value::as_str) == theme_id)
+            .or_else(|| themes.first())
+            .cloned();
+
+        let media = entries(&manifest, "media")
+            .filter_map(|entry| {
+                Some((
+                    entry.get("id")?.as_str()?.to_string(),
+                    entry.get("path")?.as_str()?.to_string(),
+                ))
+            })
+            .collect();
+        let bundle_fonts = entries(&manifest, "fonts")
+            .filter_map(|entry| {
+                Some(BundleFont {
+                    family: entry.get("family")?.as_str()?.to_string(),
+                    weight: entry.get("weight").and_then(Value::as_u64).unwrap_or(400) as u16,
+                    italic: entry.get("style").and_then(Value::as_str) == Some("italic"),
+                    path: entry.get("path")?.as_str()?.to_string(),
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            archive: bundle_reader::open_archive(path)?,
+            title: str_field(&manifest, "title")
+                .unwrap_or_default()
+                .to_string(),
+            base_size: slide_size(&manifest),
+            slides,
+            order,
+            theme,
+            media,
+            bundle_fonts,
+            images: HashMap::new(),
+            fonts: HashMap::new(),
+        })
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Number of slides in presentation order (repeats included)
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Where the slide with `slide_id` first appears in presentation order
+    pub fn position_of(&self, slide_id: &str) -> Option<usize> {
+        self.order
+            .iter()
+            .position(|&i| self.slides[i].get("id").and_then(Value::as_str) == Some(slide_id))
+    }
+
+    /// Width divided by height
+    pub fn aspect_ratio(&self) -> f64 {
+        self.base_size.0 / self.base_size.1
+    }
+
+    /// Pixel height for an image `width` pixels wide, keeping the slide's aspect ratio
+    pub fn height_for(&self, width: u32) -> u32 {
+        ((width as f64 * self.base_size.1 / self.base_size.0).round() as u32).max(1)
+    }
+
+    /// Entry transition of the slide at `position`; the output window fades for 300ms by default
+    pub fn transition(&self, position: usize) -> Transition {
+        let transition = self
+            .order
+            .get(position)
+            .and_then(|&i| self.slides.get(i))
+            .and_then(|slide| slide.pointer("/animations/transition"));
+        let field = |key: &str| transition.and_then(|t| t.get(key));
+        Transition {
+            kind: field("type")
+                .and_then(Value::as_str)
+                .unwrap_or("fade")
+                .to_string(),
+            seconds: field("duration").and_then(Value::as_f64).unwrap_or(300.0) / 1000.0,
+            easing: field("easing")
+                .and_then(Value::as_str)
+                .unwrap_or("ease-out")
+                .to_string(),
+        }
+    }
+
+    /// Draw the slide at `position` in presentation order, `width` pixels wide
+    pub fn render(&mut self, position: usize, width: u32) -> Result<Pixmap, CpresError> {
+        #[cfg(feature = "gpu")]
+        if let Some(gpu) = crate::gpu_render::renderer() {
+            let drawn = gpu
+                .lock()
+                .map_err(|e| e.to_string())
+                .and_then(|mut gpu| gpu.render(self, position, width).map_err(|e| e.to_string()));
+            match drawn {
+                Ok(pixmap) => return Ok(pixmap),
+                Err(e) => log::warn!("Could not draw the slide on the GPU; drawing it here: {e}"),
+            }
+        }
+        self.draw_slide(position, width, Part::Whole)
+            .map(|(_, pixmap, _)| pixmap)
+    }
+
+    /// Draw only what sits in front of the slide's background, on
+    /// transparency, for keying over another picture: the layers and the
+    /// overlay media, without the background or the underlay media
+    pub fn render_foreground(&mut self, position: usize, width: u32) -> Result<Pixmap, CpresError> {
+        self.draw_slide(position, width, Part::Foreground)
+            .map(|(_, pixmap, _)| pixmap)
+    }
+
+    /// Draw everything above the background of the slide at `position` on
+    /// transparency, underlay media included, but for the text layers the
+    /// GPU draws between the pieces, and find the background for the GPU to
+    /// draw under it
+    #[cfg(feature = "gpu")]
+    pub(crate) fn render_above_background(
+        &mut self,
+        position: usize,
+        width: u32,
+    ) -> Result<(Vec<Piece>, Pixmap, Option<Backdrop>), CpresError> {
+        self.draw_slide(position, width, Part::AboveBackground)
+    }
+
+    /// Draw the slide, and find its background unless it's drawn. Text
+    /// layers left for the GPU split the slide: what comes before the
+    /// returned pixmap is returned with them, bottom to top.
+    fn draw_slide(
+        &mut self,
+        position: usize,
+        width: u32,
+        part: Part,
+    ) -> Result<(Vec<Piece>, Pixmap, Option<Backdrop>), CpresError> {
+        let slide = self
+            .order
+            .get(position)
+            .and_then(|&i| self.slides.get(i))
+            .cloned()
+            .ok_or_else(|| CpresError::InvalidBundle(format!("No slide at position {position}")))?;
+        let height = self.height_for(width);
+        let mut pixmap = Pixmap::new(width.max(1), height)
+            .ok_or_else(|| CpresError::InvalidBundle(format!("Invalid size {width}x{height}")))?;
+        let scale = (width as f64 / self.base_size.0) as f32;
+
+        let mut backdrop = None;
+        if part != Part::Foreground {
+            let background = slide
+                .pointer("/overrides/background")
+                .or_else(|| slide.get("background"))
+                .or_else(|| self.theme.as_ref().and_then(|t| t.get("background")))
+                .cloned();
+            backdrop = background.and_then(|background| self.backdrop(&pixmap, &background));
+            if part == Part::Whole {
+                if let Some(backdrop) = backdrop.take() {
+                    draw_backdrop(&mut pixmap, &backdrop);
+                }
+            }
+
+            self.draw_cues(
+                &mut pixmap,
+                &slide,
+                &["mediaUnderlay", "slideBackgroundMedia"],
+            );
+        }
+        #[cfg_attr(not(feature = "gpu"), allow(unused_mut))]
+        let mut below = Vec::new();
+        let layers = slide.get("layers").and_then(Value::as_array);
+        for layer in layers.into_iter().flatten() {
+            if layer.get("visible").and_then(Value::as_bool) == Some(false) {
+                continue;
+            }
+            #[cfg(feature = "gpu")]
+            if part == Part::AboveBackground {
+                if let Some(run) = self.text_run(&slide, layer, scale) {
+                    let blank = Pixmap::new(pixmap.width(), pixmap.height())
+                        .expect("same size as the slide");
+                    below.push(Piece::Pixels(std::mem::replace(&mut pixmap, blank)));
+                    below.push(Piece::Text(run));
+                    continue;
+                }
+            }
+            self.draw_layer(&mut pixmap, &slide, layer, scale);
+        }
+        self.draw_cues(
+            &mut pixmap,
+            &slide,
+            &["mediaOverlay", "slideForegroundMedia"],
+        );
+
+        Ok((below, pixmap, backdrop))
+    }
+
+    /// The background to draw on `pixmap`, with its image decoded
+    fn backdrop(&mut self, pixmap: &Pixmap, background: &Value) -> Option<Backdrop> {
+        let area = Rect::from_xywh(0.0, 0.0, pixmap.width() as f32, pixmap.height() as f32)
+            .expect("pixmap is never empty");
+        match str_field(background, "type") {
+            Some("solid") => str_field(background, "color")
+                .and_then(parse_color)
+                .map(Backdrop::Solid),
+            Some("gradient") => Some(Backdrop::Gradient {
+                angle: num_field(background, "angle").unwrap_or(180.0),
+                stops: background
+                    .get("stops")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|stop| {
+                        let color = parse_color(str_field(stop, "color")?)?;
+                        let position = num_field(stop, "position").unwrap_or(0.0) / 100.0;
+                        Some((position, color))
+                    })
+                    .collect(),
+                fallback: background
+                    .pointer("/stops/0/color")
+                    .and_then(Value::as_str)
+                    .and_then(parse_color),
+            }),
+            Some("image") => {
+                let media_id = str_field(background, "mediaId")?;
+                let image = self.image(media_id, area)?;
+                let position = background.get("position");
+                Some(Backdrop::Image {
+                    key: format!("{media_id}@{}x{}", image.width(), image.height()),
+                    image,
+                    fit: str_field(background, "fit").unwrap_or("cover").to_string(),
+                    position: (
+                        position.and_then(|p| num_field(p, "x")).unwrap_or(50.0),
+                        position.and_then(|p| num_field(p, "y")).unwrap_or(50.0),
+                    ),
+                    opacity: num_field(background, "opacity").unwrap_or(1.0),
+                })
+            }
+            Some("video") => {
+                log::debug!("Video backgrounds are not rendered");
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Image cues on the given targets, fitted to the whole slide
+    fn draw_cues(&mut self, pixmap: &mut Pixmap, slide: &Value, targets: &[&str]) {
+        let area = Rect::from_xywh(0.0, 0.0, pixmap.width() as f32, pixmap.height() as f32)
+            .expect("pixmap is never empty");
+        let cues = slide.get("mediaCues").and_then(Value::as_array);
+        for cue in cues.into_iter().flatten() {
+            if !str_field(cue, "target").is_some_and(|t| targets.contains(&t))
+                || str_field(cue, "mediaType") != Some("image")
+            {
+                continue;
+            }
+            if let Some(image) = str_field(cue, "mediaId").and_then(|id| self.image(id, area)) {
+                let fit = str_field(cue, "fit").unwrap_or("cover");
+                draw_image(pixmap, &image, area, fit, (50.0, 50.0), 1.0);
+            }
+        }
+    }
+
+    /// Draw one layer onto its own surface, then place it with its transform
+    fn draw_layer(&mut self, pixmap: &mut Pixmap, slide: &Value, layer: &Value, scale: f32) {
+        let Some(place) = place_layer(layer, scale) else {
+            return;
+        };
+        let Some(mut surface) = Pixmap::new(place.size.0, place.size.1) else {
+            return;
+        };
+        let frame = place.frame;
+        match str_field(layer, "type").unwrap_or_default() {
+            "text" => self.draw_text(&mut surface, frame, slide, layer, scale),
+            "shape" => draw_shape(&mut surface, frame, layer, scale),
+            "media" if str_field(layer, "mediaType") == Some("image") => {
+                if let Some(image) =
+                    str_field(layer, "mediaId").and_then(|id| self.image(id, frame))
+                {
+                    let fit = str_field(layer, "fit").unwrap_or("contain");
+                    draw_image(&mut surface, &image, frame, fit, (50.0, 50.0), 1.0);
+                }
+            }
+            other => log::debug!("Layer type \"{other}\" is not rendered"),
+        }
+
+        let placement = place.transform;
+        let paint = PixmapPaint {
+            opacity: place.opacity,
+            blend_mode: place.blend_mode,
+            quality: FilterQuality::Bicubic,
+        };
+
+        for effect in &place.effects {
+            match str_field(effect, "type") {
+                Some("layer-blur") => {
+                    let radius = num_field(effect, "radius").unwrap_or(0.0) * scale / 2.0;
+                    blur_pixmap(&mut surface, radius);
+                }
+                Some("drop-shadow") => {
+                    let color = str_field(effect, "color")
+                        .and_then(parse_color)
+                        .unwrap_or(Color::BLACK);
+                    let mut alpha: Vec<f32> = surface
+                        .pixels()
+                        .iter()
+                        .map(|p| p.alpha() as f32 / 255.0)
+                        .collect();
+                    let (w, h) = (surface.width() as usize, surface.height() as usize);
+                    dilate(
+                        &mut alpha,
+                        w,
+                        h,
+                        num_field(effect, "spread").unwrap_or(0.0) * scale,
+                    );
+                    blur(
+                        &mut alpha,
+                        w,
+                        h,
+                        num_field(effect, "blur").unwrap_or(0.0) * scale / 2.0,
+                    );
+                    let mut shadow = Pixmap::new(w as u32, h as u32).expect("same size as surface");
+                    paint_mask(
+                        &mut shadow,
+                        &alpha,
+                        with_opacity(color, num_field(effect, "opacity").unwrap_or(1.0)),
+                        0,
+                        0,
+                    );
+                    let offset = Transform::from_translate(
+                        num_field(effect, "offsetX").unwrap_or(0.0) * scale,
+                        num_field(effect, "offsetY").unwrap_or(0.0) * scale,
+                    );
+                    pixmap.draw_pixmap(
+                        0,
+                        0,
+                        shadow.as_ref(),
+                        &paint,
+                        offset.pre_concat(placement),
+                        None,
+                    );
+                }
+                _ => {}
+            }
+        }
+        pixmap.draw_pixmap(0, 0, surface.as_ref(), &paint, placement, None);
+    }
+
+    fn draw_text(
+        &mut self,
+        surface: &mut Pixmap,
+        frame: Rect,
+        slide: &Value,
+        layer: &Value,
+        scale: f32,
+    ) {
+        let Some(text) = self.text_layout(frame, slide, layer, scale) else {
+            return;
+        };
+        let (w, h) = (surface.width() as usize, surface.height() as usize);
+        let mut mask = vec![0.0f32; w * h];
+        for &(id, position) in &text.glyphs {
+            let Some(outline) = text
+                .font
+                .outline_glyph(id.with_scale_and_position(text.scale, position))
+            else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, coverage| {
+                let px = bounds.min.x as i64 + gx as i64;
+                let py = bounds.min.y as i64 + gy as i64;
+                if px >= 0 && py >= 0 && (px as usize) < w && (py as usize) < h {
+                    let cell = &mut mask[py as usize * w + px as usize];
+                    *cell = (*cell + coverage).min(1.0);
+                }
+            });
+        }
+
+        let paint = text.paint;
+        if let Some((color, radius, (dx, dy))) = paint.shadow {
+            let mut shadow_mask = mask.clone();
+            blur(&mut shadow_mask, w, h, radius);
+            paint_mask(surface, &shadow_mask, color, dx, dy);
+        }
+        if let Some((color, radius)) = paint.outline {
+            let mut outline_mask = mask.clone();
+            dilate(&mut outline_mask, w, h, radius);
+            paint_mask(surface, &outline_mask, color, 0, 0);
+        }
+        if let Some(color) = paint.fill {
+            paint_mask(surface, &mask, color, 0, 0);
+        }
+    }
+
+    /// A text layer to be drawn on the GPU, when nothing about it needs the
+    /// CPU: layers with effects or a blend mode are drawn by `draw_layer`
+    #[cfg(feature = "gpu")]
+    fn text_run(&mut self, slide: &Value, layer: &Value, scale: f32) -> Option<TextRun> {
+        if str_field(layer, "type") != Some("text") {
+            return None;
+        }
+        let place = place_layer(layer, scale)?;
+        if !place.effects.is_empty() || place.blend_mode != BlendMode::SourceOver {
+            return None;
+        }
+        let text = self.text_layout(place.frame, slide, layer, scale)?;
+        let font_key = Arc::as_ptr(&text.font) as *const () as usize;
+        let glyphs = text
+            .glyphs
+            .iter()
+            .filter_map(|&(id, position)| {
+                // At quarter pixels, so each glyph's coverage can be reused
+                let snap = |v: f32| (v * GLYPH_BINS).round() / GLYPH_BINS;
+                let position = point(snap(position.x), snap(position.y));
+                let bins = (
+                    (position.x.rem_euclid(1.0) * GLYPH_BINS) as u8,
+                    (position.y.rem_euclid(1.0) * GLYPH_BINS) as u8,
+                );
+                let outline = text
+                    .font
+                    .outline_glyph(id.with_scale_and_position(text.scale, position))?;
+                let key = GlyphKey {
+                    font: font_key,
+                    id: id.0,
+                    size: (text.scale.x.to_bits(), text.scale.y.to_bits()),
+                    bins,
+                };
+                Some((key, outline))
+            })
+            .collect();
+        Some(TextRun {
+            size: place.size,
+            font: text.font,
+            glyphs,
+            paint: text.paint,
+            placement: place.transform,
+            opacity: place.opacity,
+        })
+    }
+
+    /// Lay out a text layer in `frame`, with the style its theme, slide and
+    /// layer give it
+    fn text_layout(
+        &mut self,
+        frame: Rect,
+        slide: &Value,
+        layer: &Value,
+        scale: f32,
+    ) -> Option<TextLayout> {
+        let content = str_field(layer, "content").unwrap_or_default();
+        if content.trim().is_empty() {
+            return None;
+        }
+        let mut style = self
+            .theme
+            .as_ref()
+            .and_then(|theme| theme.get("primaryText"))
+            .cloned()
+            .unwrap_or_else(default_text_style);
+        if let Some(overrides) = slide.pointer("/overrides/primaryText") {
+            merge_text_style(&mut style, overrides);
+        }
+        if let Some(overrides) = layer.get("style") {
+            merge_text_style(&mut style, overrides);
+        }
+
+        let font_style = style.get("font").cloned().unwrap_or(Value::Null);
+        let family = str_field(&font_style, "family")
+            .unwrap_or("Inter")
+            .to_string();
+        let weight = num_field(&font_style, "weight").unwrap_or(400.0) as u16;
+        let italic = font_style.get("italic").and_then(Value::as_bool) == Some(true);
+        let Some(font) = self.font(&family, weight, italic) else {
+            log::warn!("No font available for \"{family}\"; skipping text layer");
+            return None;
+        };
+        let size = num_field(&font_style, "size").unwrap_or(72.0) * scale;
+        let spacing = num_field(&font_style, "letterSpacing").unwrap_or(0.0) * scale;
+        let line_height = num_field(&font_style, "lineHeight").unwrap_or(1.2);
+
+        // CSS percentage padding is relative to the width on every side
+        let padding =
+            frame.width() * num_field(layer, "padding").unwrap_or(DEFAULT_TEXT_PADDING) / 100.0;
+        let available = (
+            (frame.width() - padding * 2.0).max(1.0),
+            (frame.height() - padding * 2.0).max(1.0),
+        );
+
+        let mut lines = layout(&font, size, spacing, content, available.0);
+        let mut fit = 1.0;
+        let text_fit = str_field(layer, "textFit").unwrap_or("auto");
+        if text_fit != "auto" {
+            let text_width = lines.iter().map(|l| l.width).fold(0.0, f32::max);
+            let text_height = lines.len() as f32 * size * line_height;
+            if text_width > 0.0 && text_height > 0.0 {
+                fit = (available.0 / text_width).min(available.1 / text_height);
+                if text_fit == "shrink" {
+                    fit = fit.min(1.0);
+                }
+                fit = fit.clamp(0.1, 5.0);
+            }
+            if (fit - 1.0).abs() > 0.001 {
+                lines = layout(&font, size * fit, spacing * fit, content, available.0);
+            }
+        }
+        let size = size * fit;
+        let line_box = size * line_height;
+
+        let scaled = font.as_scaled(px_scale(&font, size));
+        let block_height = lines.len() as f32 * line_box;
+        let top = frame.top()
+            + padding
+            + match str_field(&style, "verticalAlignment") {
+                Some("top") => 0.0,
+                Some("bottom") => available.1 - block_height,
+                _ => (available.1 - block_height) / 2.0,
+            };
+        let alignment = str_field(&style, "alignment").unwrap_or("center");
+        let half_leading = (line_box - (scaled.ascent() - scaled.descent())) / 2.0;
+
+        let mut glyphs = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            let left = frame.left()
+                + padding
+                + match alignment {
+                    "left" => 0.0,
+                    "right" => available.0 - line.width,
+                    _ => (available.0 - line.width) / 2.0,
+                };
+            let baseline = top + i as f32 * line_box + half_leading + scaled.ascent();
+            glyphs.extend(
+                line.glyphs
+                    .iter()
+                    .map(|&(id, offset)| (id, point(left + offset, baseline))),
+            );
+        }
+
+        let shadow = style
+            .get("shadow")
+            .filter(|s| enabled(s))
+            .and_then(|shadow| {
+                let color = str_field(shadow, "color").and_then(parse_color)?;
+                Some((
+                    color,
+                    num_field(shadow, "blur").unwrap_or(0.0) * scale / 2.0,
+                    (
+                        (num_field(shadow, "offsetX").unwrap_or(0.0) * scale).round() as i32,
+                        (num_field(shadow, "offsetY").unwrap_or(0.0) * scale).round() as i32,
+                    ),
+                ))
+            });
+
+        // A layer stroke replaces the theme outline
+        let outline = layer_strokes(layer)
+            .first()
+            .map(|stroke| {
+                (
+                    str_field(stroke, "color")
+                        .and_then(parse_color)
+                        .map(|c| with_opacity(c, num_field(stroke, "opacity").unwrap_or(1.0))),
+                    num_field(stroke, "width").unwrap_or(0.0),
+                )
+            })
+            .or_else(|| {
+                let outline = style.get("outline").filter(|o| enabled(o))?;
+                Some((
+                    str_field(outline, "color").and_then(parse_color),
+                    num_field(outline, "width").unwrap_or(0.0),
+                ))
+            })
+            .and_then(|(color, width)| {
+                // The stroke is centered on the glyph edge, so half of it shows outside
+                Some((color?, width * scale / 2.0)).filter(|_| width > 0.0)
+            });
+
+        Some(TextLayout {
+            scale: scaled.scale,
+            font,
+            glyphs,
+            paint: TextPaint {
+                shadow,
+                outline,
+                fill: text_color(layer, &style),
+            },
+        })
+    }
+
+    /// Decoded image for a media id, cached for the life of the renderer. An
+    /// SVG is drawn large enough to cover `frame`, and cached per frame size.
+    fn image(&mut self, media_id: &str, frame: Rect) -> Option<Arc<Pixmap>> {
+        let path = self.media.get(media_id).cloned()?;
+        let size = (frame.width().ceil() as u32, frame.height().ceil() as u32);
+        let vector = path.to_lowercase().ends_with(".svg");
+        let key = if vector {
+            format!("{media_id}@{}x{}", size.0, size.1)
+        } else {
+            media_id.to_string()
+        };
+        if let Some(image) = self.images.get(&key) {
+            return image.clone();
+        }
+        let result = self.read(&path).and_then(|data| {
+            if vector {
+                svg::rasterize(&data, None, size.0, size.1, true).map(Some)
+            } else {
+                // Upright, as the webview shows photos with EXIF rotation
+                thumbnails::decode(&data).map(|image| to_pixmap(image.to_rgba8()))
+            }
+        });
+        let decoded = match result {
+            Ok(pixmap) => pixmap.map(Arc::new),
+            Err(e) => {
+                log::warn!("Could not decode image {path}: {e}");
+                None
+            }
+        };
+        self.images.insert(key, decoded.clone());
+        decoded
+    }
+
+    /// Bundle font matching family, weight and style, else the closest installed font
+    fn font(&mut self, family: &str, weight: u16, italic: bool) -> Option<Arc<FontVec>> {
+        let key = (family.to_lowercase(), weight, italic);
+        if let Some(font) = self.fonts.get(&key) {
+            return font.clone();
+        }
+
+        let embedded = self
+            .bundle_fonts
+            .iter()
+            .filter(|font| font.family.eq_ignore_ascii_case(family))
+            .min_by_key(|font| (font.italic != italic, font.weight.abs_diff(weight)))
+            .map(|font| font.path.clone());
+        let font = embedded
+            .and_then(|path| match self.read(&path) {
+                Ok(data) => FontVec::try_from_vec(data).ok(),
+                Err(e) => {
+                    log::warn!("Could not read bundle font {path}: {e}");
+                    None
+                }
+            })
+            .or_else(|| system_font(family, weight, italic))
+            .map(Arc::new);
+        self.fonts.insert(key, font.clone());
+        font
+    }
+
+    fn read(&mut self, path: &str) -> Result<Vec<u8>, CpresError> {
+        let mut file = self
+            .archive
+            .by_name(path)
+            .map_err(|_| CpresError::MissingFile(path.to_string()))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    }
+}
+
+fn entries<'a>(manifest: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    manifest
+        .get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(Value::as_str)
+}
+
+fn num_field(value: &Value, key: &str) -> Option<f32> {
+    value.get(key).and_then(Value::as_f64).map(|n| n as f32)
+}
+
+fn enabled(value: &Value) -> bool {
+    value.get("enabled").and_then(Value::as_bool) == Some(true)
+}
+
+/// Base slide size from the manifest's slideSize, else its aspect ratio
+fn slide_size(manifest: &Value) -> (f64, f64) {
+    let size = manifest.get("slideSize").and_then(|size| {
+        let width = size.get("width")?.as_f64()?;
+        let height = size.get("height")?.as_f64()?;
+        (width > 0.0 && height > 0.0).then_some((width, height))
+    });
+    size.unwrap_or(match str_field(manifest, "aspectRatio") {
+        Some("4:3") => (1440.0, 1080.0),
+        Some("16:10") => (1920.0, 1200.0),
+        _ => DEFAULT_SLIDE_SIZE,
+    })
+}
+
+/// The editor's default primary text style, for bundles without a theme
+fn default_text_style() -> Value {
+    json!({
+        "font": { "family": "Inter", "size": 72, "weight": 700, "italic": false, "lineHeight": 1.2, "letterSpacing": 0 },
+        "color": "#FFFFFF",
+        "alignment": "center",
+        "verticalAlignment": "middle",
+        "shadow": { "enabled": false },
+        "outline": { "enabled": false },
+    })
+}
+
+/// Overlay a partial text style; font, shadow and outline merge field by field
+fn merge_text_style(base: &mut Value, overrides: &Value) {
+    let (Some(base), Some(overrides)) = (base.as_object_mut(), overrides.as_object()) else {
+        return;
+    };
+    for (key, value) in overrides {
+        match (base.get_mut(key), value) {
+            (Some(Value::Object(nested)), Value::Object(fields))
+                if matches!(key.as_str(), "font" | "shadow" | "outline") =>
+            {
+                for (field, value) in fields {
+                    nested.insert(field.clone(), value.clone());
+                }
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Enabled fills, top first; None when the layer doesn't list any
+fn layer_fills(layer: &Value) -> Option<Vec<&Value>> {
+    layer.get("fills").and_then(Value::as_array).map(|fills| {
+        fills
+            .iter()
+            .filter(|fill| fill.get("enabled").and_then(Value::as_bool) != Some(false))
+            .collect()
+    })
+}
+
+/// Enabled strokes; shapes without a strokes list use their legacy style stroke
+fn layer_strokes(layer: &Value) -> Vec<Value> {
+    match layer.get("strokes").and_then(Value::as_array) {
+        Some(strokes) => strokes
+            .iter()
+            .filter(|stroke| stroke.get("enabled").and_then(Value::as_bool) != Some(false))
+            .cloned()
+            .collect(),
+        None if str_field(layer, "type") == Some("shape") => {
+            let style = layer.get("style").cloned().unwrap_or(Value::Null);
+            vec![json!({
+                "color": str_field(&style, "stroke").unwrap_or("#1d4ed8"),
+                "opacity": num_field(&style, "strokeOpacity").unwrap_or(1.0),
+                "width": num_field(&style, "strokeWidth").unwrap_or(2.0),
+                "position": "inside",
+            })]
+        }
+        None => Vec::new(),
+    }
+}
+
+fn fill_color(fill: &Value) -> Option<Color> {
+    let color = parse_color(str_field(fill, "color")?)?;
+    Some(with_opacity(
+        color,
+        num_field(fill, "opacity").unwrap_or(1.0),
+    ))
+}
+
+/// Text color from the top fill, else the style color; an empty fills list is transparent
+fn text_color(layer: &Value, style: &Value) -> Option<Color> {
+    match layer_fills(layer) {
+        Some(fills) => fill_color(fills.first()?),
+        None => parse_color(str_field(style, "color").unwrap_or("#FFFFFF")),
+    }
+}
+
+/// Where `layer` goes, or None when it has no size
+fn place_layer(layer: &Value, scale: f32) -> Option<LayerPlacement<'_>> {
+    let transform = layer.get("transform")?;
+    let field = |name: &str| num_field(transform, name).unwrap_or(0.0) * scale;
+    let (x, y, width, height) = (field("x"), field("y"), field("width"), field("height"));
+    if width < 1.0 || height < 1.0 {
+        return None;
+    }
+    let effects: Vec<&Value> = layer
+        .get("effects")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|effect| effect.get("enabled").and_then(Value::as_bool) != Some(false))
+        .collect();
+
+    let kind = str_field(layer, "type").unwrap_or_default();
+    let clip = transform.get("clipContent").and_then(Value::as_bool) == Some(true);
+    let mut margin = match kind {
+        "text" if !clip => width.max(height) * TEXT_OVERFLOW,
+        "shape" => layer_strokes(layer)
+            .iter()
+            .map(|stroke| num_field(stroke, "width").unwrap_or(0.0) * scale)
+            .fold(0.0, f32::max),
+        _ => 0.0,
+    };
+    margin += effects
+        .iter()
+        .map(|effect| match str_field(effect, "type") {
+            Some("drop-shadow") => {
+                let offset = num_field(effect, "offsetX")
+                    .unwrap_or(0.0)
+                    .abs()
+                    .max(num_field(effect, "offsetY").unwrap_or(0.0).abs());
+                (offset
+                    + num_field(effect, "blur").unwrap_or(0.0) * 2.0
+                    + num_field(effect, "spread").unwrap_or(0.0))
+                    * scale
+            }
+            Some("layer-blur") => num_field(effect, "radius").unwrap_or(0.0) * 2.0 * scale,
+            _ => 0.0,
+        })
+        .fold(0.0, f32::max);
+    let margin = margin.ceil();
+
+    let flip = |name: &str| {
+        if transform.get(name).and_then(Value::as_bool) == Some(true) {
+            -1.0
+        } else {
+            1.0
+        }
+    };
+    Some(LayerPlacement {
+        frame: Rect::from_xywh(margin, margin, width, height)?,
+        size: (
+            (width + margin * 2.0).ceil() as u32,
+            (height + margin * 2.0).ceil() as u32,
+        ),
+        transform: Transform::from_translate(x + width / 2.0, y + height / 2.0)
+            .pre_concat(Transform::from_rotate(
+                num_field(transform, "rotation").unwrap_or(0.0),
+            ))
+            .pre_scale(flip("flipX"), flip("flipY"))
+            .pre_translate(-width / 2.0 - margin, -height / 2.0 - margin),
+        opacity: num_field(transform, "opacity")
+            .unwrap_or(1.0)
+            .clamp(0.0, 1.0),
+        blend_mode: blend_mode(str_field(layer, "blendMode").unwrap_or("normal")),
+        effects,
+    })
+}
+
+fn draw_shape(surface: &mut Pixmap, frame: Rect, layer: &Value, scale: f32) {
+    let style = layer.get("style").cloned().unwrap_or(Value::Null);
+    let shape = str_field(layer, "shapeType").unwrap_or("rectangle");
+    let radius = num_field(&style, "cornerRadius").unwrap_or(0.0) * scale;
+    let path_for = |frame: Rect| match shape {
+        "ellipse" => PathBuilder::from_oval(frame),
+        "triangle" => {
+            let mut builder = PathBuilder::new();
+            builder.move_to(frame.left() + frame.width() / 2.0, frame.top());
+            builder.line_to(frame.right(), frame.bottom());
+            builder.line_to(frame.left(), frame.bottom());
+            builder.close();
+            builder.finish()
+        }
+        "line" => {
+            let mut builder = PathBuilder::new();
+            let y = frame.top() + frame.height() / 2.0;
+            builder.move_to(frame.left(), y);
+            builder.line_to(frame.right(), y);
+            builder.finish()
+        }
+        _ => rounded_rect(frame, radius),
+    };
+
+    if shape != "line" {
+        let fills: Vec<Value> = match layer_fills(layer) {
+            Some(fills) => fills.into_iter().cloned().collect(),
+            None => vec![json!({
+                "color": str_field(&style, "fill").unwrap_or("#3b82f6"),
+                "opacity": num_field(&style, "fillOpacity").unwrap_or(1.0),
+            })],
+        };
+        if let Some(path) = path_for(frame) {
+            // Fills are listed top first
+            for color in fills.iter().rev().filter_map(fill_color) {
+                let mut paint = Paint::default();
+                paint.set_color(color);
+                paint.anti_alias = true;
+                surface.fill_path(
+                    &path,
+                    &paint,
+                    FillRule::Winding,
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+    }
+
+    for stroke in layer_strokes(layer) {
+        let width = num_field(&stroke, "width").unwrap_or(0.0) * scale;
+        let Some(color) = fill_color(&stroke) else {
+            continue;
+        };
+        if width <= 0.0 {
+            continue;
+        }
+        let inset = match (shape, str_field(&stroke, "position")) {
+            ("line", _) | (_, Some("center")) => 0.0,
+            (_, Some("outside")) => -width / 2.0,
+            _ => width / 2.0,
+        };
+        let stroke_frame = Rect::from_ltrb(
+            frame.left() + inset,
+            frame.top() + inset,
+            frame.right() - inset,
+            frame.bottom() - inset,
+        );
+        let Some(path) = stroke_frame.and_then(path_for) else {
+            continue;
+        };
+        let mut paint = Paint::default();
+        paint.set_color(color);
+        paint.anti_alias = true;
+        let stroke_style = Stroke {
+            width,
+            ..Stroke::default()
+        };
+        surface.stroke_path(&path, &paint, &stroke_style, Transform::identity(), None);
+    }
+}
+
+fn rounded_rect(rect: Rect, radius: f32) -> Option<tiny_skia::Path> {
+    let r = radius.min(rect.width() / 2.0).min(rect.height() / 2.0);
+    if r <= 0.0 {
+        return Some(PathBuilder::from_rect(rect));
+    }
+    let (l, t, rt, b) = (rect.left(), rect.top(), rect.right(), rect.bottom());
+    let k = r * (1.0 - ARC_HANDLE);
+    let mut builder = PathBuilder::new();
+    builder.move_to(l + r, t);
+    builder.line_to(rt - r, t);
+    builder.cubic_to(rt - k, t, rt, t + k, rt, t + r);
+    builder.line_to(rt, b - r);
+    builder.cubic_to(rt, b - k, rt - k, b, rt - r, b);
+    builder.line_to(l + r, b);
+    builder.cubic_to(l + k, b, l, b - k, l, b - r);
+    builder.line_to(l, t + r);
+    builder.cubic_to(l, t + k, l + k, t, l + r, t);
+    builder.close();
+    builder.finish()
+}
+
+fn draw_backdrop(pixmap: &mut Pixmap, backdrop: &Backdrop) {
+    let (width, height) = (pixmap.width() as f32, pixmap.height() as f32);
+    let area = Rect::from_xywh(0.0, 0.0, width, height).expect("pixmap is never empty");
+    match backdrop {
+        Backdrop::Solid(color) => pixmap.fill(*color),
+        Backdrop::Gradient {
+            angle,
+            stops,
+            fallback,
+        } => {
+            let ((x0, y0), (x1, y1)) = gradient_line(*angle, width, height);
+            let stops = stops
+                .iter()
+                .map(|&(position, color)| GradientStop::new(position, color))
+                .collect();
+            match LinearGradient::new(
+                Point::from_xy(x0, y0),
+                Point::from_xy(x1, y1),
+                stops,
+                SpreadMode::Pad,
+                Transform::identity(),
+            ) {
+                Some(shader) => {
+                    let paint = Paint {
+                        shader,
+                        anti_alias: false,
+                        ..Paint::default()
+                    };
+                    pixmap.fill_rect(area, &paint, Transform::identity(), None);
+                }
+                // A single stop is a solid color
+                None => {
+                    if let Some(color) = fallback {
+                        pixmap.fill(*color);
+                    }
+                }
+            }
+        }
+        Backdrop::Image {
+            image,
+            fit,
+            position,
+            opacity,
+            ..
+        } => draw_image(pixmap, image, area, fit, *position, *opacity),
+    }
+}
+
+/// Start and end of a CSS linear gradient at `angle` degrees across a
+/// `width` by `height` box
+pub(crate) fn gradient_line(angle: f32, width: f32, height: f32) -> ((f32, f32), (f32, f32)) {
+    let angle = angle.to_radians();
+    let direction = (angle.sin(), -angle.cos());
+    let length = (width * direction.0).abs() + (height * direction.1).abs();
+    let (cx, cy) = (width / 2.0, height / 2.0);
+    let (dx, dy) = (direction.0 * length / 2.0, direction.1 * length / 2.0);
+    ((cx - dx, cy - dy), (cx + dx, cy + dy))
+}
+
+/// Where an image `size` pixels big lands in `frame` with CSS object-fit
+/// and a position in percent: its left, top, and its scale on each axis
+pub(crate) fn fit_image(
+    size: (f32, f32),
+    frame: Rect,
+    fit: &str,
+    position: (f32, f32),
+) -> (f32, f32, f32, f32) {
+    let (iw, ih) = size;
+    let (sx, sy) = match fit {
+        "fill" => (frame.width() / iw, frame.height() / ih),
+        "contain" => {
+            let s = (frame.width() / iw).min(frame.height() / ih);
+            (s, s)
+        }
+        "none" => (1.0, 1.0),
+        _ => {
+            let s = (frame.width() / iw).max(frame.height() / ih);
+            (s, s)
+        }
+    };
+    let tx = frame.left() + (frame.width() - iw * sx) * position.0 / 100.0;
+    let ty = frame.top() + (frame.height() - ih * sy) * position.1 / 100.0;
+    (tx, ty, sx, sy)
+}
+
+/// Draw an image into `frame` using CSS object-fit and a position in percent
+fn draw_image(
+    pixmap: &mut Pixmap,
+    image: &Pixmap,
+    frame: Rect,
+    fit: &str,
+    position: (f32, f32),
+    opacity: f32,
+) {
+    let (iw, ih) = (image.width() as f32, image.height() as f32);
+    let (tx, ty, sx, sy) = fit_image((iw, ih), frame, fit, position);
+    let paint = Paint {
+        shader: Pattern::new(
+            image.as_ref(),
+            SpreadMode::Pad,
+            FilterQuality::Bicubic,
+            opacity.clamp(0.0, 1.0),
+            Transform::from_row(sx, 0.0, 0.0, sy, tx, ty),
+        ),
+        ..Paint::default()
+    };
+    let visible = Rect::from_xywh(tx, ty, iw * sx, ih * sy).and_then(|r| r.intersect(&frame));
+    if let Some(visible) = visible {
+        pixmap.fill_rect(visible, &paint, Transform::identity(), None);
+    }
+}
+
+/// Premultiply decoded RGBA pixels into a pixmap
+fn to_pixmap(image: image::RgbaImage) -> Option<Pixmap> {
+    let (width, height) = image.dimensions();
+    let mut data = image.into_raw();
+    for pixel in data.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u16;
+        for channel in &mut pixel[..3] {
+            *channel = ((*channel as u16 * alpha + 127) / 255) as u8;
+        }
+    }
+    Pixmap::from_vec(data, tiny_skia::IntSize::from_wh(width, height)?)
+}
+
+fn system_font(family: &str, weight: u16, italic: bool) -> Option<FontVec> {
+    let mut properties = Properties::new();
+    properties.weight = Weight(weight as f32);
+    if italic {
+        properties.style = Style::Italic;
+    }
+    let handle = SystemSource::new()
+        .select_best_match(
+            &[FamilyName::Title(family.to_string()), FamilyName::SansSerif],
+            &properties,
+        )
+        .ok()?;
+    let (data, index) = match handle {
+        Handle::Path { path, font_index } => (std::fs::read(path).ok()?, font_index),
+        Handle::Memory { bytes, font_index } => (bytes.to_vec(), font_index),
+    };
+    FontVec::try_from_vec_and_index(data, index).ok()
+}
+
+/// ab_glyph scales by ascent-to-descent height; CSS sizes are per em
+pub(crate) fn px_scale(font: &FontVec, size: f32) -> PxScale {
+    let units_per_em = font.units_per_em().unwrap_or(1000.0);
+    PxScale::from(size * font.height_unscaled() / units_per_em)
+}
+
+/// Break text into lines no wider than `max_width`, at spaces and newlines
+fn layout(font: &FontVec, size: f32, spacing: f32, text: &str, max_width: f32) -> Vec<Line> {
+    let scaled = font.as_scaled(px_scale(font, size));
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = Line {
+            glyphs: Vec::new(),
+            width: 0.0,
+        };
+        let mut x = 0.0;
+        for word in paragraph.split_inclusive(' ') {
+            let mut glyphs = Vec::new();
+            let mut advance = 0.0;
+            let mut ink = 0.0;
+            let mut previous: Option<GlyphId> = None;
+            for c in word.chars() {
+                let id = scaled.glyph_id(c);
+                if let Some(previous) = previous {
+                    advance += scaled.kern(previous, id);
+                }
+                glyphs.push((id, advance));
+                advance += scaled.h_advance(id) + spacing;
+                if !c.is_whitespace() {
+                    ink = advance - spacing;
+                }
+                previous = Some(id);
+            }
+            // A word wider than the box overflows instead of breaking
+            if !line.glyphs.is_empty() && x + ink > max_width {
+                lines.push(line);
+                line = Line {
+                    glyphs: Vec::new(),
+                    width: 0.0,
+                };
+                x = 0.0;
+            }
+            line.glyphs
+                .extend(glyphs.into_iter().map(|(id, offset)| (id, x + offset)));
+            if ink > 0.0 {
+                line.width = x + ink;
+            }
+            x += advance;
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Composite `color` through a coverage mask, offset by (dx, dy)
+fn paint_mask(pixmap: &mut Pixmap, mask: &[f32], color: Color, dx: i32, dy: i32) {
+    let (w, h) = (pixmap.width() as i32, pixmap.height() as i32);
+    let color = color.premultiply();
+    let source = [color.red(), color.green(), color.blue(), color.alpha()];
+    let data = pixmap.data_mut();
+    for y in 0..h {
+        let sy = y - dy;
+        if sy < 0 || sy >= h {
+            continue;
+        }
+        for x in 0..w {
+            let sx = x - dx;
+            if sx < 0 || sx >= w {
+                continue;
+            }
+            let coverage = mask[(sy * w + sx) as usize];
+            if coverage <= 0.0 {
+                continue;
+            }
+            let index = ((y * w + x) * 4) as usize;
+            let inverse = 1.0 - source[3] * coverage;
+            for channel in 0..4 {
+                let value =
+                    source[channel] * coverage * 255.0 + data[index + channel] as f32 * inverse;
+                data[index + channel] = value.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+/// Grow a mask by `radius` pixels (square max filter, rows then columns)
+fn dilate(mask: &mut [f32], w: usize, h: usize, radius: f32) {
+    let r = radius.round() as usize;
+    if r == 0 {
+        return;
+    }
+    let mut row = vec![0.0f32; w.max(h)];
+    for y in 0..h {
+        row[..w].copy_from_slice(&mask[y * w..(y + 1) * w]);
+        for x in 0..w {
+            let (from, to) = (x.saturating_sub(r), (x + r).min(w - 1));
+            mask[y * w + x] = row[from..=to].iter().copied().fold(0.0, f32::max);
+        }
+    }
+    for x in 0..w {
+        for y in 0..h {
+            row[y] = mask[y * w + x];
+        }
+        for y in 0..h {
+            let (from, to) = (y.saturating_sub(r), (y + r).min(h - 1));
+            mask[y * w + x] = row[from..=to].iter().copied().fold(0.0, f32::max);
+        }
+    }
+}
+
+/// Approximate a gaussian blur with three box blurs
+fn blur(mask: &mut [f32], w: usize, h: usize, radius: f32) {
+    let r = radius.round() as usize;
+    if r == 0 {
+        return;
+    }
+    let mut line = vec![0.0f32; w.max(h)];
+    for _ in 0..3 {
+        for y in 0..h {
+            line[..w].copy_from_slice(&mask[y * w..(y + 1) * w]);
+            box_pass(&line[..w], |x, v| mask[y * w + x] = v, r);
+        }
+        for x in 0..w {
+            for y in 0..h {
+                line[y] = mask[y * w + x];
+            }
+            box_pass(&line[..h], |y, v| mask[y * w + x] = v, r);
+        }
+    }
+}
+
+/// Running-sum box filter over one row or column; edges count as empty
+fn box_pass(source: &[f32], mut write: impl FnMut(usize, f32), r: usize) {
+    let n = source.len();
+    let window = (2 * r + 1) as f32;
+    let mut sum: f32 = source[..r.min(n)].iter().sum();
+    for i in 0..n {
+        if i + r < n {
+            sum += source[i + r];
+        }
+        write(i, sum / window);
+        if i >= r {
+            sum -= source[i - r];
+        }
+    }
+}
+
+/// Blur every channel of a premultiplied pixmap
+fn blur_pixmap(pixmap: &mut Pixmap, radius: f32) {
+    let (w, h) = (pixmap.width() as usize, pixmap.height() as usize);
+    for channel in 0..4 {
+        let mut values: Vec<f32> = pixmap
+            .data()
+            .iter()
+            .skip(channel)
+            .step_by(4)
+            .map(|&v| v as f32)
+            .collect();
+        blur(&mut values, w, h, radius);
+        for (pixel, value) in pixmap.data_mut().chunks_exact_mut(4).zip(values) {
+            pixel[channel] = value.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    // Keep color channels within alpha after rounding
+    for pixel in pixmap.data_mut().chunks_exact_mut(4) {
+        let alpha = pixel[3];
+        for value in &mut pixel[..3] {
+            *value = (*value).min(alpha);
+        }
+    }
+}
+
+fn with_opacity(mut color: Color, opacity: f32) -> Color {
+    color.apply_opacity(opacity.clamp(0.0, 1.0));
+    color
+}
+
+fn blend_mode(name: &str) -> BlendMode {
+    match name {
+        "multiply" => BlendMode::Multiply,
+        "screen" => BlendMode::Screen,
+        "overlay" => BlendMode::Overlay,
+        "darken" => BlendMode::Darken,
+        "lighten" => BlendMode::Lighten,
+        "color-dodge" => BlendMode::ColorDodge,
+        "color-burn" => BlendMode::ColorBurn,
+        "hard-light" => BlendMode::HardLight,
+        "soft-light" => BlendMode::SoftLight,
+        "difference" => BlendMode::Difference,
+        "exclusion" => BlendMode::Exclusion,
+        _ => BlendMode::SourceOver,
+    }
+}
+
+/// Parse a CSS color: hex (#rgb, #rgba, #rrggbb, #rrggbbaa), rgb()/rgba() or a few names
+pub(crate) fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()?;
+        let channels: Vec<u8> = match digits.len() {
+            3 | 4 => digits.iter().map(|d| d * 17).collect(),
+            6 | 8 => digits
+                .chunks(2)
+                .map(|pair| pair[0] * 16 + pair[1])
+                .collect(),
+            _ => return None,
+        };
+        let alpha = channels.get(3).copied().unwrap_or(255);
+        return Some(Color::from_rgba8(
+            channels[0],
+            channels[1],
+            channels[2],
+            alpha,
+        ));
+    }
+    let lower = value.to_ascii_lowercase();
+    if let Some(args) = lower
+        .strip_prefix("rgba(")
+        .or_else(|| lower.strip_prefix("rgb("))
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let parts: Vec<&str> = args
+            .split([',', ' ', '/'])
+            .filter(|part| !part.is_empty())
+            .collect();
+        if parts.len() < 3 {
+            return None;
+        }
+        let channel = |part: &str| -> Option<u8> {
+            match part.strip_suffix('%') {
+                Some(percent) => Some(
+                    (percent.parse::<f32>().ok()? * 2.55)
+                        .round()
+                        .clamp(0.0, 255.0) as u8,
+                ),
+                None => Some(part.parse::<f32>().ok()?.round().clamp(0.0, 255.0) as u8),
+            }
+        };
+        let alpha = match parts.get(3) {
+            Some(part) => match part.strip_suffix('%') {
+                Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+                None => part.parse::<f32>().ok()?,
+            },
+            None => 1.0,
+        };
+        let mut color = Color::from_rgba8(
+            channel(parts[0])?,
+            channel(parts[1])?,
+            channel(parts[2])?,
+            255,
+        );
+        color.apply_opacity(alpha.clamp(0.0, 1.0));
+        return Some(color);
+    }
+    match lower.as_str() {
+        "transparent" => Some(Color::TRANSPARENT),
+        "black" => Some(Color::BLACK),
+        "white" => Some(Color::WHITE),
+        "red" => Some(Color::from_rgba8(255, 0, 0, 255)),
+        "green" => Some(Color::from_rgba8(0, 128, 0, 255)),
+        "blue" => Some(Color::from_rgba8(0, 0, 255, 255)),
+        "gray" | "grey" => Some(Color::from_rgba8(128, 128, 128, 255)),
+        _ => None,
+    }
+}
@@ -0,0 +1,336 @@
+//! Grabbing what an output window is showing
+//!
+//! Output windows are webviews composited by the OS, so what's on screen is
+//! read back from the screen itself rather than from the webview: GDI on
+//! Windows, Core Graphics on macOS (which needs the Screen Recording
+//! permission), and on Linux `grim` under wlroots compositors or ffmpeg's
+//! `x11grab` on X11. The window covers its monitor, so the grab is of the
+//! window's area on screen; anything kept above it would show too.
+
+use crate::cpres::CpresError;
+use image::{ImageFormat, RgbaImage};
+use serde::Serialize;
+use std::io::Cursor;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ScreenCaptureError {
+    #[cfg(not(any(windows, target_os = "macos")))]
+    #[error("Screen capture isn't supported on this system")]
+    Unsupported,
+
+    #[cfg(target_os = "macos")]
+    #[error("Screen capture needs permission: {0}")]
+    PermissionDenied(String),
+
+    #[error("Screen capture failed: {0}")]
+    Failed(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Ffmpeg(#[from] CpresError),
+}
+
+/// A grab of an output window, as PNG
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputCapture {
+    pub width: u32,
+    pub height: u32,
+    /// Where the PNG was saved, when a path was given
+    pub path: Option<String>,
+    /// The PNG itself, when no path was given
+    pub png: Option<Vec<u8>>,
+}
+
+/// An area of the screen, in physical pixels on the virtual desktop
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Physical pixels per logical pixel on the area's monitor
+    pub scale: f64,
+    /// Name of the monitor the area is on, as the OS gives it
+    pub monitor: Option<String>,
+}
+
+impl Region {
+    /// The area `window` covers on screen
+    pub fn of_window(window: &tauri::WebviewWindow) -> Result<Self, ScreenCaptureError> {
+        let failed = |e: tauri::Error| ScreenCaptureError::Failed(e.to_string());
+        let position = window.outer_position().map_err(failed)?;
+        let size = window.outer_size().map_err(failed)?;
+        let monitor = window.current_monitor().map_err(failed)?;
+        Ok(Self {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+            scale: window.scale_factor().map_err(failed)?,
+            monitor: monitor.and_then(|monitor| monitor.name().cloned()),
+        })
+    }
+}
+
+/// Grab `region` of the screen and save it as a PNG at `path`, or return
+/// the PNG with no path
+pub fn capture_png(
+    region: &Region,
+    path: Option<&Path>,
+) -> Result<OutputCapture, ScreenCaptureError> {
+    let image = capture(region)?;
+    let (width, height) = image.dimensions();
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| ScreenCaptureError::Failed(e.to_string()))?;
+    match path {
+        Some(path) => {
+            std::fs::write(path, &png)?;
+            Ok(OutputCapture {
+                width,
+                height,
+                path: Some(path.to_string_lossy().to_string()),
+                png: None,
+            })
+        }
+        None => Ok(OutputCapture {
+            width,
+            height,
+            path: None,
+            png: Some(png),
+        }),
+    }
+}
+
+/// Grab `region` of the screen
+pub fn capture(region: &Region) -> Result<RgbaImage, ScreenCaptureError> {
+    if region.width == 0 || region.height == 0 {
+        return Err(ScreenCaptureError::Failed(
+            "The window has no area on screen".to_string(),
+        ));
+    }
+    platform::capture(region)
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{Region, ScreenCaptureError};
+    use image::RgbaImage;
+    use windows::Win32::Graphics::Gdi::{
+        BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC,
+        GetDIBits, ReleaseDC, SelectObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, CAPTUREBLT,
+        DIB_RGB_COLORS, SRCCOPY,
+    };
+
+    pub fn capture(region: &Region) -> Result<RgbaImage, ScreenCaptureError> {
+        let (width, height) = (region.width as i32, region.height as i32);
+        let mut data = vec![0u8; region.width as usize * region.height as usize * 4];
+        // Copies the screen into a bitmap, then reads it top row first
+        let lines = unsafe {
+            let screen = GetDC(None);
+            if screen.is_invalid() {
+                return Err(ScreenCaptureError::Failed(
+                    "Could not open the screen".to_string(),
+                ));
+            }
+            let memory = CreateCompatibleDC(Some(screen));
+            let bitmap = CreateCompatibleBitmap(screen, width, height);
+            let previous = SelectObject(memory, bitmap.into());
+            let copied = BitBlt(
+                memory,
+                0,
+                0,
+                width,
+                height,
+                Some(screen),
+                region.x,
+                region.y,
+                SRCCOPY | CAPTUREBLT,
+            );
+            let mut info = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                    biWidth: width,
+                    biHeight: -height,
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            SelectObject(memory, previous);
+            let lines = match copied {
+                Ok(()) => GetDIBits(
+                    memory,
+                    bitmap,
+                    0,
+                    height as u32,
+                    Some(data.as_mut_ptr().cast()),
+                    &mut info,
+                    DIB_RGB_COLORS,
+                ),
+                Err(_) => 0,
+            };
+            let _ = DeleteObject(bitmap.into());
+            let _ = DeleteDC(memory);
+            ReleaseDC(None, screen);
+            lines
+        };
+        if lines != height {
+            return Err(ScreenCaptureError::Failed(
+                "Could not copy the screen".to_string(),
+            ));
+        }
+        // BGRX to RGBA
+        for pixel in data.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+            pixel[3] = 255;
+        }
+        RgbaImage::from_raw(region.width, region.height, data)
+            .ok_or_else(|| ScreenCaptureError::Failed("Short screen copy".to_string()))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{Region, ScreenCaptureError};
+    use core_graphics::display::CGDisplay;
+    use core_graphics::geometry::{CGPoint, CGRect, CGSize};
+    use core_graphics::window::{
+        kCGNullWindowID, kCGWindowImageDefault, kCGWindowListOptionOnScreenOnly,
+    };
+    use image::RgbaImage;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGPreflightScreenCaptureAccess() -> bool;
+        fn CGRequestScreenCaptureAccess() -> bool;
+    }
+
+    pub fn capture(region: &Region) -> Result<RgbaImage, ScreenCaptureError> {
+        // Without the permission macOS grabs only the desktop picture
+        if !unsafe { CGPreflightScreenCaptureAccess() } {
+            unsafe { CGRequestScreenCaptureAccess() };
+            return Err(ScreenCaptureError::PermissionDenied(
+                "allow Church Presenter under Screen Recording in System Settings".to_string(),
+            ));
+        }
+        // Core Graphics places windows in points
+        let bounds = CGRect::new(
+            &CGPoint::new(
+                region.x as f64 / region.scale,
+                region.y as f64 / region.scale,
+            ),
+            &CGSize::new(
+                region.width as f64 / region.scale,
+                region.height as f64 / region.scale,
+            ),
+        );
+        let image = CGDisplay::screenshot(
+            bounds,
+            kCGWindowListOptionOnScreenOnly,
+            kCGNullWindowID,
+            kCGWindowImageDefault,
+        )
+        .ok_or_else(|| ScreenCaptureError::Failed("Could not copy the screen".to_string()))?;
+        if image.bits_per_pixel() != 32 {
+            return Err(ScreenCaptureError::Failed(format!(
+                "Unexpected {}-bit screen image",
+                image.bits_per_pixel()
+            )));
+        }
+
+        let (width, height, stride) = (image.width(), image.height(), image.bytes_per_row());
+        let source = image.data();
+        let source = source.bytes();
+        let mut data = Vec::with_capacity(width * height * 4);
+        // Rows are padded, and pixels BGRA
+        for row in source.chunks(stride).take(height) {
+            for pixel in row[..width * 4].chunks_exact(4) {
+                data.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 255]);
+            }
+        }
+        RgbaImage::from_raw(width as u32, height as u32, data)
+            .ok_or_else(|| ScreenCaptureError::Failed("Short screen copy".to_string()))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{Region, ScreenCaptureError};
+    use crate::ffmpeg;
+    use image::RgbaImage;
+    use std::process::{Command, Output, Stdio};
+
+    pub fn capture(region: &Region) -> Result<RgbaImage, ScreenCaptureError> {
+        let output = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            grim(region)?
+        } else {
+            x11grab(region)?
+        };
+        if !output.status.success() || output.stdout.is_empty() {
+            let log = String::from_utf8_lossy(&output.stderr);
+            return Err(ScreenCaptureError::Failed(log.trim().to_string()));
+        }
+        image::load_from_memory(&output.stdout)
+            .map(|image| image.to_rgba8())
+            .map_err(|e| ScreenCaptureError::Failed(e.to_string()))
+    }
+
+    /// The monitor by name, or else the area in logical pixels, as PNG
+    fn grim(region: &Region) -> Result<Output, ScreenCaptureError> {
+        let mut command = Command::new("grim");
+        match &region.monitor {
+            Some(monitor) => command.args(["-o", monitor]),
+            None => command.args([
+                "-g",
+                &format!(
+                    "{},{} {}x{}",
+                    (region.x as f64 / region.scale).round(),
+                    (region.y as f64 / region.scale).round(),
+                    (region.width as f64 / region.scale).round(),
+                    (region.height as f64 / region.scale).round()
+                ),
+            ]),
+        };
+        command
+            .args(["-t", "png", "-"])
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => ScreenCaptureError::Failed(
+                    "grim isn't installed; it's needed to grab the screen on Wayland".to_string(),
+                ),
+                _ => e.into(),
+            })
+    }
+
+    /// One frame of the area from the X server, as PNG
+    fn x11grab(region: &Region) -> Result<Output, ScreenCaptureError> {
+        let display = std::env::var("DISPLAY").map_err(|_| ScreenCaptureError::Unsupported)?;
+        let mut command = ffmpeg::command()?;
+        command
+            .args(["-f", "x11grab", "-video_size"])
+            .arg(format!("{}x{}", region.width, region.height))
+            .arg("-i")
+            .arg(format!("{display}+{},{}", region.x, region.y))
+            .args(["-frames:v", "1", "-f", "image2pipe", "-c:v", "png", "-"]);
+        Ok(command.stdin(Stdio::null()).output()?)
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+mod platform {
+    use super::{Region, ScreenCaptureError};
+    use image::RgbaImage;
+
+    pub fn capture(_region: &Region) -> Result<RgbaImage, ScreenCaptureError> {
+        Err(ScreenCaptureError::Unsupported)
+    }
+}
@@ -0,0 +1,330 @@
+//! Find and replace text across slides
+//!
+//! Searches the `content` of text layers in slides.json, either in a bundle the
+//! editor has open (its unsaved `BundleState`) or in .cpres files on disk, so a
+//! misspelled name can be fixed in every presentation in the library at once.
+//! Plain queries match literally; with `regex` the query uses the `regex`
+//! crate's syntax and the replacement may refer to groups as `$1`.
+
+use crate::checksums::{Checksums, CHECKSUMS_FILE};
+use crate::cpres::{self, CpresError};
+use crate::{bundle_reader, history, song_import};
+use regex::{NoExpand, Regex};
+use serde::Serialize;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+const SLIDES_FILE: &str = "slides.json";
+
+/// What to look for and how to replace it
+pub struct Pattern {
+    regex: Regex,
+    /// Plain queries replace literally, so `$` in the replacement stays as typed
+    expand: bool,
+}
+
+impl Pattern {
+    pub fn new(query: &str, regex: bool) -> Result<Self, CpresError> {
+        if query.is_empty() {
+            return Err(CpresError::InvalidBundle(
+                "Search text is empty".to_string(),
+            ));
+        }
+        let source = if regex {
+            Cow::Borrowed(query)
+        } else {
+            Cow::Owned(regex::escape(query))
+        };
+        let compiled = Regex::new(&source)
+            .map_err(|e| CpresError::InvalidBundle(format!("Invalid pattern: {e}")))?;
+        Ok(Self {
+            regex: compiled,
+            expand: regex,
+        })
+    }
+
+    /// `text` with every match replaced, and how many there were
+    fn replace(&self, text: &str, replacement: &str) -> (String, usize) {
+        let count = self.regex.find_iter(text).count();
+        if count == 0 {
+            return (text.to_string(), 0);
+        }
+        let replaced = if self.expand {
+            self.regex.replace_all(text, replacement)
+        } else {
+            self.regex.replace_all(text, NoExpand(replacement))
+        };
+        (replaced.into_owned(), count)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TextMatch {
+    pub slide_id: String,
+    pub slide_index: usize,
+    pub layer_id: String,
+    /// Match position in the layer's content, in UTF-16 code units like JS strings
+    pub start: usize,
+    pub end: usize,
+    /// The line of text the match is on
+    pub context: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplaceResult {
+    pub replacements: usize,
+    /// Updated slides.json when replacing in an open bundle's state; None when
+    /// the bundle file was rewritten
+    pub slides: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BundleMatches {
+    pub path: String,
+    pub matches: Vec<TextMatch>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BundleReplacement {
+    pub path: String,
+    pub replacements: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedBundle {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LibrarySearchReport {
+    /// Bundles with at least one match
+    pub bundles: Vec<BundleMatches>,
+    pub skipped: Vec<SkippedBundle>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LibraryReplaceReport {
+    /// Bundles that were rewritten
+    pub bundles: Vec<BundleReplacement>,
+    pub skipped: Vec<SkippedBundle>,
+    pub replacements: usize,
+}
+
+/// Matches in the text layers of a slides.json document
+pub fn search_slides(slides: &str, pattern: &Pattern) -> Result<Vec<TextMatch>, CpresError> {
+    let slides: Vec<Value> = serde_json::from_str(slides)?;
+    let mut matches = Vec::new();
+    for (slide_index, slide) in slides.iter().enumerate() {
+        for layer in text_layers(slide) {
+            let Some(content) = layer.get("content").and_then(Value::as_str) else {
+                continue;
+            };
+            for found in pattern.regex.find_iter(content) {
+                let line_start = content[..found.start()].rfind('\n').map_or(0, |i| i + 1);
+                let line_end = content[found.end()..]
+                    .find('\n')
+                    .map_or(content.len(), |i| found.end() + i);
+                matches.push(TextMatch {
+                    slide_id: str_field(slide, "id"),
+                    slide_index,
+                    layer_id: str_field(layer, "id"),
+                    start: utf16_len(&content[..found.start()]),
+                    end: utf16_len(&content[..found.end()]),
+                    context: content[line_start..line_end].to_string(),
+                });
+            }
+        }
+    }
+    Ok(matches)
+}
+
+/// slides.json with every match replaced, or None when nothing matched
+pub fn replace_slides(
+    slides: &str,
+    pattern: &Pattern,
+    replacement: &str,
+) -> Result<Option<(String, usize)>, CpresError> {
+    let mut document: Vec<Value> = serde_json::from_str(slides)?;
+    let mut total = 0;
+    for slide in &mut document {
+        let Some(layers) = slide.get_mut("layers").and_then(Value::as_array_mut) else {
+            continue;
+        };
+        for layer in layers.iter_mut().filter(|layer| is_text_layer(layer)) {
+            let Some(content) = layer.get_mut("content") else {
+                continue;
+            };
+            let Some(text) = content.as_str() else {
+                continue;
+            };
+            let (replaced, count) = pattern.replace(text, replacement);
+            if count > 0 {
+                *content = Value::String(replaced);
+                total += count;
+            }
+        }
+    }
+    if total == 0 {
+        return Ok(None);
+    }
+    // Same layout the frontend saves with
+    Ok(Some((serde_json::to_string_pretty(&document)?, total)))
+}
+
+/// Matches in a bundle file
+pub fn search_bundle(path: &Path, pattern: &Pattern) -> Result<Vec<TextMatch>, CpresError> {
+    let bundle = cpres::open_bundle(path)?;
+    search_slides(&bundle.slides, pattern)
+}
+
+/// Replace in a bundle file and rewrite it; the previous bundle is kept as a
+/// history snapshot. Returns the number of replacements.
+pub fn replace_in_bundle(
+    path: &Path,
+    pattern: &Pattern,
+    replacement: &str,
+) -> Result<usize, CpresError> {
+    let bundle = cpres::open_bundle(path)?;
+    let Some((slides, count)) = replace_slides(&bundle.slides, pattern, replacement)? else {
+        return Ok(0);
+    };
+    history::snapshot(path, history::DEFAULT_KEEP_VERSIONS)?;
+    write_slides(path, &slides)?;
+    Ok(count)
+}
+
+/// Search every .cpres under `dir`; bundles that can't be read are skipped
+pub fn search_library(dir: &Path, pattern: &Pattern) -> Result<LibrarySearchReport, CpresError> {
+    let mut report = LibrarySearchReport {
+        bundles: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for path in library_bundles(dir)? {
+        match search_bundle(&path, pattern) {
+            Ok(matches) if matches.is_empty() => {}
+            Ok(matches) => report.bundles.push(BundleMatches {
+                path: path.to_string_lossy().to_string(),
+                matches,
+            }),
+            Err(e) => report.skipped.push(SkippedBundle {
+                path: path.to_string_lossy().to_string(),
+                reason: e.to_string(),
+            }),
+        }
+    }
+    Ok(report)
+}
+
+/// Replace in every .cpres under `dir`. `skip` names bundles that must not be
+/// rewritten (open in an editor, locked by another machine) and why.
+pub fn replace_in_library(
+    dir: &Path,
+    pattern: &Pattern,
+    replacement: &str,
+    skip: impl Fn(&Path) -> Option<String>,
+) -> Result<LibraryReplaceReport, CpresError> {
+    let mut report = LibraryReplaceReport {
+        bundles: Vec::new(),
+        skipped: Vec::new(),
+        replacements: 0,
+    };
+    for path in library_bundles(dir)? {
+        let display = path.to_string_lossy().to_string();
+        if let Some(reason) = skip(&path) {
+            // Only worth reporting when the bundle actually has something to replace
+            if search_bundle(&path, pattern).is_ok_and(|matches| !matches.is_empty()) {
+                report.skipped.push(SkippedBundle {
+                    path: display,
+                    reason,
+                });
+            }
+            continue;
+        }
+        match replace_in_bundle(&path, pattern, replacement) {
+            Ok(0) => {}
+            Ok(replacements) => {
+                report.replacements += replacements;
+                report.bundles.push(BundleReplacement {
+                    path: display,
+                    replacements,
+                });
+            }
+            Err(e) => report.skipped.push(SkippedBundle {
+                path: display,
+                reason: e.to_string(),
+            }),
+        }
+    }
+    Ok(report)
+}
+
+/// Every .cpres under `dir`, leaving out history snapshots
+pub(crate) fn library_bundles(dir: &Path) -> Result<Vec<PathBuf>, CpresError> {
+    let mut files = Vec::new();
+    song_import::collect_files(dir, 0, &mut files)?;
+    files.retain(|path| {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("cpres"))
+            && !path.parent().is_some_and(history::is_history_dir)
+    });
+    Ok(files)
+}
+
+/// Rewrite a bundle with a new slides.json, copying every other entry as-is
+fn write_slides(path: &Path, slides: &str) -> Result<(), CpresError> {
+    let mut archive = bundle_reader::open_archive(path)?;
+    let parent = path.parent().unwrap_or(Path::new("."));
+    let temp_file = NamedTempFile::new_in(parent)?;
+    let mut zip = ZipWriter::new(temp_file.reopen()?);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut checksums = Checksums::default();
+
+    checksums.write_entry(&mut zip, SLIDES_FILE, slides.as_bytes(), options)?;
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        let name = entry.name().to_string();
+        if entry.is_dir() || name == SLIDES_FILE || name == CHECKSUMS_FILE {
+            continue;
+        }
+        drop(entry);
+        checksums.add_from_archive(&mut archive, i, &name)?;
+        zip.raw_copy_file(archive.by_index_raw(i)?)?;
+    }
+    checksums.write(&mut zip, options)?;
+    zip.finish()?;
+
+    // Release the source handle before replacing it (required on Windows)
+    drop(archive);
+    cpres::persist_file(temp_file, path)
+}
+
+pub(crate) fn text_layers(slide: &Value) -> impl Iterator<Item = &Value> {
+    slide
+        .get("layers")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|layer| is_text_layer(layer))
+}
+
+fn is_text_layer(layer: &Value) -> bool {
+    layer.get("type").and_then(Value::as_str) == Some("text")
+}
+
+fn str_field(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
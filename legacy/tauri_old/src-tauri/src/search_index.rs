@@ -0,0 +1,336 @@
+//! Full-text index of the presentations in the content directory
+//!
+//! Slide text, section labels, and each presentation's title and author go
+//! into a SQLite FTS5 table in app data, so the library can be searched without
+//! opening every bundle. The index is brought up to date before each query and
+//! once in the background at startup: only bundles whose size or modification
+//! time changed are re-read, and bundles that are gone are dropped. The index
+//! only holds data derived from the bundles, so a database that can't be opened
+//! is deleted and rebuilt.
+
+use crate::commands;
+use crate::cpres::{self, CpresError};
+use crate::search;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Manager};
+
+const INDEX_FILENAME: &str = "search-index.sqlite";
+
+/// Bump when the schema or what gets indexed changes; older indexes are rebuilt
+const SCHEMA_VERSION: i64 = 1;
+
+pub const DEFAULT_LIMIT: usize = 50;
+
+/// Words of context around the best match in a snippet
+const SNIPPET_TOKENS: i64 = 16;
+
+/// Markers SQLite puts around matches; swapped for `<mark>` after escaping
+const MATCH_START: &str = "\u{E000}";
+const MATCH_END: &str = "\u{E001}";
+
+const SCHEMA: &str = "
+    CREATE TABLE bundles (
+        path TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        modified_ms INTEGER NOT NULL
+    );
+    CREATE VIRTUAL TABLE slides USING fts5(
+        path UNINDEXED,
+        slide_id UNINDEXED,
+        slide_index UNINDEXED,
+        title,
+        author,
+        section,
+        text,
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+";
+
+/// Column weights for ranking: a hit in the title counts most
+const RANK: &str = "bm25(slides, 0.0, 0.0, 0.0, 10.0, 4.0, 2.0, 1.0)";
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub path: String,
+    pub slide_id: String,
+    pub slide_index: usize,
+    /// HTML: escaped text with matches wrapped in `<mark>`
+    pub title: String,
+    pub section: String,
+    /// HTML: the best-matching stretch of the slide's text, marked like `title`
+    pub snippet: String,
+    /// Lower is better
+    pub score: f64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RefreshStats {
+    pub indexed: usize,
+    pub removed: usize,
+}
+
+/// Managed state: the open index database
+pub struct SearchIndex {
+    connection: Mutex<Connection>,
+}
+
+impl SearchIndex {
+    pub fn open(path: &Path) -> Result<Self, CpresError> {
+        let connection = match open_connection(path) {
+            Ok(connection) => connection,
+            Err(e) => {
+                log::warn!("Rebuilding search index {}: {e}", path.display());
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                open_connection(path)?
+            }
+        };
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Re-index bundles under `dir` that changed since the last refresh
+    pub fn refresh(&self, dir: &Path) -> Result<RefreshStats, CpresError> {
+        let mut connection = self.lock()?;
+        let known: HashMap<String, (i64, i64)> = {
+            let mut statement = connection
+                .prepare("SELECT path, size, modified_ms FROM bundles")
+                .map_err(database_error)?;
+            let rows = statement
+                .query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))
+                .map_err(database_error)?;
+            rows.collect::<Result<_, _>>().map_err(database_error)?
+        };
+
+        let mut stats = RefreshStats::default();
+        let mut present = HashSet::new();
+        let transaction = connection.transaction().map_err(database_error)?;
+        for path in search::library_bundles(dir)? {
+            let key = path.to_string_lossy().to_string();
+            let Some(stamp) = file_stamp(&path) else {
+                continue;
+            };
+            present.insert(key.clone());
+            if known.get(&key) == Some(&stamp) {
+                continue;
+            }
+            transaction
+                .execute("DELETE FROM slides WHERE path = ?1", [&key])
+                .map_err(database_error)?;
+            // Unreadable bundles are recorded too, so they aren't retried until they change
+            if let Err(e) = index_bundle(&transaction, &path, &key) {
+                log::warn!("Could not index {key}: {e}");
+            }
+            transaction
+                .execute(
+                    "INSERT OR REPLACE INTO bundles (path, size, modified_ms) VALUES (?1, ?2, ?3)",
+                    params![key, stamp.0, stamp.1],
+                )
+                .map_err(database_error)?;
+            stats.indexed += 1;
+        }
+        for key in known.keys().filter(|key| !present.contains(*key)) {
+            transaction
+                .execute("DELETE FROM slides WHERE path = ?1", [key])
+                .map_err(database_error)?;
+            transaction
+                .execute("DELETE FROM bundles WHERE path = ?1", [key])
+                .map_err(database_error)?;
+            stats.removed += 1;
+        }
+        transaction.commit().map_err(database_error)?;
+        Ok(stats)
+    }
+
+    /// Slides matching every word of `query`, best first. The last word also
+    /// matches as a prefix, so results show up while the user is typing.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, CpresError> {
+        let Some(expression) = match_expression(query) else {
+            return Ok(Vec::new());
+        };
+        let connection = self.lock()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT path, slide_id, slide_index,
+                    highlight(slides, 3, ?2, ?3),
+                    section,
+                    snippet(slides, 6, ?2, ?3, '…', ?4),
+                    {RANK} AS score
+                FROM slides WHERE slides MATCH ?1
+                ORDER BY score LIMIT ?5"
+            ))
+            .map_err(database_error)?;
+        let rows = statement
+            .query_map(
+                params![
+                    expression,
+                    MATCH_START,
+                    MATCH_END,
+                    SNIPPET_TOKENS,
+                    limit as i64
+                ],
+                |row| {
+                    Ok(SearchHit {
+                        path: row.get(0)?,
+                        slide_id: row.get(1)?,
+                        slide_index: row.get::<_, i64>(2)? as usize,
+                        title: to_html(&row.get::<_, String>(3)?),
+                        section: row.get(4)?,
+                        snippet: to_html(&row.get::<_, String>(5)?),
+                        score: row.get(6)?,
+                    })
+                },
+            )
+            .map_err(database_error)?;
+        rows.collect::<Result<_, _>>().map_err(database_error)
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Connection>, CpresError> {
+        self.connection
+            .lock()
+            .map_err(|e| CpresError::Database(e.to_string()))
+    }
+}
+
+fn open_connection(path: &Path) -> Result<Connection, CpresError> {
+    let connection = Connection::open(path).map_err(database_error)?;
+    let version: i64 = connection
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(database_error)?;
+    if version != SCHEMA_VERSION {
+        connection
+            .execute_batch(&format!(
+                "DROP TABLE IF EXISTS bundles;
+                DROP TABLE IF EXISTS slides;
+                {SCHEMA}
+                PRAGMA user_version = {SCHEMA_VERSION};"
+            ))
+            .map_err(database_error)?;
+    }
+    Ok(connection)
+}
+
+/// Add one row per slide of a bundle
+fn index_bundle(connection: &Connection, path: &Path, key: &str) -> Result<(), CpresError> {
+    let bundle = cpres::open_bundle(path)?;
+    let manifest: Value = serde_json::from_str(&bundle.manifest)?;
+    let slides: Vec<Value> = serde_json::from_str(&bundle.slides)?;
+    let title = str_field(&manifest, "title");
+    let author = str_field(&manifest, "author");
+
+    let mut insert = connection
+        .prepare(
+            "INSERT INTO slides (path, slide_id, slide_index, title, author, section, text)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .map_err(database_error)?;
+    for (index, slide) in slides.iter().enumerate() {
+        let text = search::text_layers(slide)
+            .filter_map(|layer| layer.get("content").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n");
+        insert
+            .execute(params![
+                key,
+                str_field(slide, "id"),
+                index as i64,
+                title,
+                author,
+                str_field(slide, "sectionLabel"),
+                text,
+            ])
+            .map_err(database_error)?;
+    }
+    Ok(())
+}
+
+/// FTS5 query for what the user typed: every word quoted so punctuation can't
+/// be read as query syntax, the last one as a prefix
+fn match_expression(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|word| word.replace('"', ""))
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{word}\""))
+        .collect();
+    let (last, rest) = words.split_last()?;
+    let mut terms = rest.to_vec();
+    terms.push(format!("{last}*"));
+    Some(terms.join(" "))
+}
+
+/// Size and modification time, to tell whether a bundle changed
+fn file_stamp(path: &Path) -> Option<(i64, i64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_millis();
+    Some((metadata.len() as i64, modified as i64))
+}
+
+fn to_html(marked: &str) -> String {
+    marked
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace(MATCH_START, "<mark>")
+        .replace(MATCH_END, "</mark>")
+}
+
+fn str_field(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn database_error(e: rusqlite::Error) -> CpresError {
+    CpresError::Database(e.to_string())
+}
+
+/// Open the index and bring it up to date in the background
+pub fn init(app: &AppHandle) -> Result<(), String> {
+    let path: PathBuf = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join(INDEX_FILENAME);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    app.manage(SearchIndex::open(&path).map_err(|e| e.to_string())?);
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = commands::resolve_content_dir(&handle)
+            .map_err(|e| e.to_string())
+            .and_then(|dir| {
+                handle
+                    .state::<SearchIndex>()
+                    .refresh(&dir)
+                    .map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(stats) => log::info!(
+                "Search index refreshed: {} indexed, {} removed",
+                stats.indexed,
+                stats.removed
+            ),
+            Err(e) => log::warn!("Could not refresh search index: {e}"),
+        }
+    });
+    Ok(())
+}
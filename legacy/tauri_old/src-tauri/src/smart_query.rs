@@ -0,0 +1,557 @@
+//! The search language of the media library and its smart collections
+//!
+//! A query is a list of terms, all of which must match: `christmas video
+//! duration<30s`. Terms are joined with `AND` and `OR` (upper case, `AND`
+//! binding tighter), negated with `NOT`, and grouped with parentheses. A bare
+//! word matches items tagged with it or with it in their filename, except
+//! the media types (`image`, `video`, `audio`, also in the plural), which
+//! match the type. Quotes keep spaces and keywords in a word: `"silent
+//! night"`, `tag:"christmas eve"`.
+//!
+//! Fields narrow a term:
+//!
+//! - `tag:` the exact tag, `name:` part of the filename, `type:` the media type
+//! - `rating` 0 to 5, with 0 for unrated
+//! - `duration` in seconds, or with a unit (`90s`, `1.5m`, `1h`) or as `1:30`;
+//!   items of unknown length never match
+//! - `size` in bytes, or with a unit (`500kb`, `20mb`, `1gb`)
+//! - `added` and `modified` as dates, `2024-12-01`, taken as UTC midnight
+//!
+//! and compare with `:` or `=`, `!=`, `<`, `<=`, `>`, and `>=`. A query is
+//! compiled to an SQL condition over `items i`, with its values bound as
+//! parameters.
+
+use crate::cpres::CpresError;
+use crate::media_library::escape_like;
+use rusqlite::types::Value as SqlValue;
+
+const MEDIA_TYPES: &[&str] = &["image", "video", "audio"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    /// A bare word: a tag or part of a filename
+    Word(String),
+    Tag(String),
+    Name(String),
+    Type(String),
+    Compare(Column, Op, f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Column {
+    Rating,
+    Duration,
+    Size,
+    Added,
+    Modified,
+}
+
+impl Column {
+    fn sql(self) -> &'static str {
+        match self {
+            Column::Rating => "i.rating",
+            Column::Duration => "i.duration",
+            Column::Size => "i.byte_size",
+            Column::Added => "i.added_ms",
+            Column::Modified => "i.modified_ms",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn sql(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    /// The word and whether any of it was quoted, which keeps it from being
+    /// read as a keyword or field
+    Word(String, bool),
+}
+
+/// A parsed query, ready to be compiled
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmartQuery {
+    /// None for a query without terms, which matches everything
+    expr: Option<Expr>,
+}
+
+impl SmartQuery {
+    pub fn parse(query: &str) -> Result<Self, CpresError> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser { tokens, next: 0 };
+        if parser.tokens.is_empty() {
+            return Ok(Self { expr: None });
+        }
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(invalid(&format!("unexpected {}", describe(token))));
+        }
+        Ok(Self { expr: Some(expr) })
+    }
+
+    /// An SQL condition over `items i` and the values for its `?` parameters,
+    /// in order
+    pub fn to_sql(&self) -> (String, Vec<SqlValue>) {
+        let mut values = Vec::new();
+        let sql = match &self.expr {
+            Some(expr) => compile(expr, &mut values),
+            None => "1".to_string(),
+        };
+        (sql, values)
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, CpresError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            _ => {
+                let mut word = String::new();
+                let mut quoted = false;
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    chars.next();
+                    if c != '"' {
+                        word.push(c);
+                        continue;
+                    }
+                    quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some(c) => word.push(c),
+                            None => return Err(invalid("a quote is not closed")),
+                        }
+                    }
+                }
+                tokens.push(Token::Word(word, quoted));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    /// Consume the next token when it's the unquoted keyword
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Word(word, false)) if word == keyword);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, CpresError> {
+        let mut expr = self.and()?;
+        while self.keyword("OR") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    /// Terms next to each other are joined with AND too
+    fn and(&mut self) -> Result<Expr, CpresError> {
+        let mut expr = self.not()?;
+        loop {
+            if self.keyword("AND") {
+                expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+                continue;
+            }
+            match self.peek() {
+                None | Some(Token::Close) => break,
+                Some(Token::Word(word, false)) if word == "OR" => break,
+                _ => expr = Expr::And(Box::new(expr), Box::new(self.not()?)),
+            }
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, CpresError> {
+        if self.keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, CpresError> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or_else(|| invalid("the query ends early"))?;
+        self.next += 1;
+        match token {
+            Token::Open => {
+                let expr = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(invalid("a parenthesis is not closed"));
+                }
+                self.next += 1;
+                Ok(expr)
+            }
+            Token::Close => Err(invalid("unexpected )")),
+            Token::Word(word, false) if ["AND", "OR", "NOT"].contains(&word.as_str()) => {
+                Err(invalid(&format!("{word} needs a term after it")))
+            }
+            Token::Word(word, quoted) => term(&word, quoted),
+        }
+    }
+}
+
+fn term(word: &str, quoted: bool) -> Result<Expr, CpresError> {
+    let Some((field, op, value)) = split_field(word) else {
+        let lower = word.to_lowercase();
+        let media_type = lower.strip_suffix('s').unwrap_or(&lower);
+        if !quoted && MEDIA_TYPES.contains(&media_type) {
+            return Ok(Expr::Term(Term::Type(media_type.to_string())));
+        }
+        return Ok(Expr::Term(Term::Word(word.to_string())));
+    };
+    let compare = |column: Column, number: Option<f64>| {
+        number
+            .map(|number| Expr::Term(Term::Compare(column, op, number)))
+            .ok_or_else(|| invalid(&format!("\"{value}\" is not a valid {field}")))
+    };
+    let text = |term: Term| match op {
+        Op::Eq => Ok(Expr::Term(term)),
+        Op::Ne => Ok(Expr::Not(Box::new(Expr::Term(term)))),
+        _ => Err(invalid(&format!(
+            "{field} can only be compared with : or !="
+        ))),
+    };
+    match field.as_str() {
+        "tag" => text(Term::Tag(value.to_string())),
+        "name" => text(Term::Name(value.to_string())),
+        "type" => {
+            let value = value.to_lowercase();
+            let media_type = value.strip_suffix('s').unwrap_or(&value);
+            if !MEDIA_TYPES.contains(&media_type) {
+                return Err(invalid(&format!("unknown media type \"{value}\"")));
+            }
+            text(Term::Type(media_type.to_string()))
+        }
+        "rating" => compare(
+            Column::Rating,
+            value
+                .parse::<u8>()
+                .ok()
+                .filter(|rating| *rating <= 5)
+                .map(f64::from),
+        ),
+        "duration" => compare(Column::Duration, parse_duration(value)),
+        "size" => compare(Column::Size, parse_size(value)),
+        "added" => compare(Column::Added, parse_date(value)),
+        "modified" => compare(Column::Modified, parse_date(value)),
+        _ => Err(invalid(&format!("unknown field \"{field}\""))),
+    }
+}
+
+/// `duration<30s` to ("duration", <, "30s"); None for words that don't start
+/// with a field name
+fn split_field(word: &str) -> Option<(String, Op, &str)> {
+    let name_end = word.find(|c: char| !c.is_ascii_alphabetic())?;
+    if name_end == 0 {
+        return None;
+    }
+    let rest = &word[name_end..];
+    let (op, length) = [
+        ("<=", Op::Le),
+        (">=", Op::Ge),
+        ("!=", Op::Ne),
+        ("<", Op::Lt),
+        (">", Op::Gt),
+        ("=", Op::Eq),
+        (":", Op::Eq),
+    ]
+    .into_iter()
+    .find(|(symbol, _)| rest.starts_with(symbol))
+    .map(|(symbol, op)| (op, symbol.len()))?;
+    Some((word[..name_end].to_lowercase(), op, &rest[length..]))
+}
+
+/// "90", "90s", "1.5m", "1h", or "1:30" to seconds
+fn parse_duration(value: &str) -> Option<f64> {
+    let value = value.to_lowercase();
+    if value.contains(':') {
+        let mut seconds = 0.0;
+        for part in value.split(':') {
+            seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+        }
+        return Some(seconds);
+    }
+    let (number, unit) = split_unit(&value);
+    let scale = match unit {
+        "" | "s" | "sec" => 1.0,
+        "m" | "min" => 60.0,
+        "h" => 3600.0,
+        _ => return None,
+    };
+    Some(number.parse::<f64>().ok()? * scale)
+}
+
+/// "1048576", "500kb", "20mb", or "1gb" to bytes; units are powers of 1024
+fn parse_size(value: &str) -> Option<f64> {
+    let value = value.to_lowercase();
+    let (number, unit) = split_unit(&value);
+    let scale = match unit {
+        "" | "b" => 1.0,
+        "k" | "kb" => 1024.0,
+        "m" | "mb" => 1024.0 * 1024.0,
+        "g" | "gb" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some(number.parse::<f64>().ok()? * scale)
+}
+
+fn split_unit(value: &str) -> (&str, &str) {
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    value.split_at(end)
+}
+
+/// "2024-12-01" to Unix milliseconds at UTC midnight
+fn parse_date(value: &str) -> Option<f64> {
+    let mut parts = value.split('-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days since 1970-01-01 in the proleptic Gregorian calendar, counting
+    // years from March so the leap day comes last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    Some((days * 86_400_000) as f64)
+}
+
+fn compile(expr: &Expr, values: &mut Vec<SqlValue>) -> String {
+    match expr {
+        Expr::And(left, right) => {
+            format!("({} AND {})", compile(left, values), compile(right, values))
+        }
+        Expr::Or(left, right) => {
+            format!("({} OR {})", compile(left, values), compile(right, values))
+        }
+        // An unknown duration is NULL either way round
+        Expr::Not(inner) => format!("COALESCE(NOT {}, 0)", compile(inner, values)),
+        Expr::Term(Term::Word(word)) => {
+            values.push(SqlValue::Text(word.clone()));
+            values.push(SqlValue::Text(format!("%{}%", escape_like(word))));
+            format!("({TAGGED} OR i.filename LIKE ? ESCAPE '\\')")
+        }
+        Expr::Term(Term::Tag(tag)) => {
+            values.push(SqlValue::Text(tag.clone()));
+            TAGGED.to_string()
+        }
+        Expr::Term(Term::Name(name)) => {
+            values.push(SqlValue::Text(format!("%{}%", escape_like(name))));
+            "i.filename LIKE ? ESCAPE '\\'".to_string()
+        }
+        Expr::Term(Term::Type(media_type)) => {
+            values.push(SqlValue::Text(media_type.clone()));
+            "i.media_type = ?".to_string()
+        }
+        Expr::Term(Term::Compare(column, op, number)) => {
+            values.push(SqlValue::Real(*number));
+            format!("{} {} ?", column.sql(), op.sql())
+        }
+    }
+}
+
+/// Tag names compare without case (the column is `COLLATE NOCASE`)
+const TAGGED: &str = "EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON t.id = it.tag_id
+    WHERE it.item_id = i.id AND t.name = ?)";
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Open => "(".to_string(),
+        Token::Close => ")".to_string(),
+        Token::Word(word, _) => format!("\"{word}\""),
+    }
+}
+
+fn invalid(reason: &str) -> CpresError {
+    CpresError::InvalidBundle(format!("Invalid search: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(word: &str) -> Expr {
+        Expr::Term(Term::Word(word.to_string()))
+    }
+
+    fn and(left: Expr, right: Expr) -> Expr {
+        Expr::And(Box::new(left), Box::new(right))
+    }
+
+    fn or(left: Expr, right: Expr) -> Expr {
+        Expr::Or(Box::new(left), Box::new(right))
+    }
+
+    fn not(inner: Expr) -> Expr {
+        Expr::Not(Box::new(inner))
+    }
+
+    fn leaf(term: Term) -> Expr {
+        Expr::Term(term)
+    }
+
+    #[test]
+    fn parses_terms_keywords_and_fields() {
+        let cases = [
+            ("christmas", word("christmas")),
+            (
+                "christmas video",
+                and(word("christmas"), leaf(Term::Type("video".into()))),
+            ),
+            ("Images", leaf(Term::Type("image".into()))),
+            ("\"video\"", word("video")),
+            ("\"silent night\"", word("silent night")),
+            ("a OR b c", or(word("a"), and(word("b"), word("c")))),
+            ("a AND b OR c", or(and(word("a"), word("b")), word("c"))),
+            ("NOT (a OR b)", not(or(word("a"), word("b")))),
+            ("NOT NOT a", not(not(word("a")))),
+            ("\"OR\"", word("OR")),
+            (
+                "tag:\"christmas eve\"",
+                leaf(Term::Tag("christmas eve".into())),
+            ),
+            ("tag!=draft", not(leaf(Term::Tag("draft".into())))),
+            ("name=intro", leaf(Term::Name("intro".into()))),
+            ("type:videos", leaf(Term::Type("video".into()))),
+            (
+                "rating>=4",
+                leaf(Term::Compare(Column::Rating, Op::Ge, 4.0)),
+            ),
+            (
+                "duration<1:30",
+                leaf(Term::Compare(Column::Duration, Op::Lt, 90.0)),
+            ),
+            (
+                "SIZE>20mb",
+                leaf(Term::Compare(Column::Size, Op::Gt, 20.0 * 1024.0 * 1024.0)),
+            ),
+            (
+                "added<=2024-12-01",
+                leaf(Term::Compare(Column::Added, Op::Le, 1_733_011_200_000.0)),
+            ),
+            (
+                "modified!=1970-01-01",
+                leaf(Term::Compare(Column::Modified, Op::Ne, 0.0)),
+            ),
+        ];
+        for (query, expected) in cases {
+            let parsed = SmartQuery::parse(query).unwrap_or_else(|e| panic!("{query}: {e}"));
+            assert_eq!(parsed.expr, Some(expected), "{query}");
+        }
+        assert_eq!(SmartQuery::parse("  ").unwrap().expr, None);
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        let cases = [
+            "\"open",
+            "(a",
+            "a)",
+            "()",
+            "AND",
+            "a AND",
+            "NOT",
+            "a OR",
+            "rating>6",
+            "rating:high",
+            "duration<soon",
+            "size>5tb",
+            "type:pdf",
+            "tag<x",
+            "name>=a",
+            "color:red",
+            "added:2024-13-01",
+            "added:2024-12",
+        ];
+        for query in cases {
+            assert!(SmartQuery::parse(query).is_err(), "{query}");
+        }
+    }
+
+    #[test]
+    fn compiles_to_bound_sql() {
+        let (sql, values) = SmartQuery::parse("").unwrap().to_sql();
+        assert_eq!((sql.as_str(), values.len()), ("1", 0));
+
+        let (sql, values) = SmartQuery::parse("NOT rating:0").unwrap().to_sql();
+        assert_eq!(sql, "COALESCE(NOT i.rating = ?, 0)");
+        assert_eq!(values, [SqlValue::Real(0.0)]);
+
+        let (sql, values) = SmartQuery::parse("50%_off").unwrap().to_sql();
+        assert!(sql.ends_with("OR i.filename LIKE ? ESCAPE '\\')"), "{sql}");
+        assert_eq!(
+            values,
+            [
+                SqlValue::Text("50%_off".into()),
+                SqlValue::Text("%50\\%\\_off%".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! OpenLP and OpenSong song import
+//!
+//! - OpenLP keeps songs in a SQLite database (`songs.sqlite`). Each row's
+//!   `lyrics` column is an XML document of `<verse type="v" label="1">`
+//!   elements, and `verse_order` lists tags such as `v1 c1 v2 c1`.
+//! - OpenSong stores one XML file per song, usually without an extension, in
+//!   a `Songs` folder. Lyrics are plain text with `[V1]`-style section headers
+//!   and the singing order is in `<presentation>`.
+//!
+//! Each song becomes its own .cpres file in the chosen library folder.
+
+use crate::cpres::CpresError;
+use crate::importer::{self, LibraryImport, LyricSection};
+use rusqlite::{Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+
+/// OpenLP's manual slide break inside a verse
+const OPENLP_SLIDE_BREAK: &str = "[---]";
+/// Folders deeper than this are not searched for OpenSong files
+const MAX_FOLDER_DEPTH: usize = 8;
+
+struct OpenLpSong {
+    title: String,
+    lyrics: String,
+    verse_order: String,
+    authors: Option<String>,
+}
+
+/// Import every song in an OpenLP `songs.sqlite` database into `output_dir`
+pub fn import_openlp(database: &Path, output_dir: &Path) -> Result<LibraryImport, CpresError> {
+    let connection = Connection::open_with_flags(database, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(database_error)?;
+    let mut statement = connection
+        .prepare(
+            "SELECT s.title, s.lyrics, s.verse_order,
+                (SELECT group_concat(a.display_name, ', ')
+                 FROM authors a JOIN authors_songs x ON x.author_id = a.id
+                 WHERE x.song_id = s.id)
+             FROM songs s ORDER BY s.title",
+        )
+        .map_err(database_error)?;
+    let songs = statement
+        .query_map([], |row| {
+            Ok(OpenLpSong {
+                title: row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                lyrics: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                verse_order: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                authors: row.get(3)?,
+            })
+        })
+        .map_err(database_error)?;
+
+    let mut report = LibraryImport::default();
+    for song in songs {
+        let song = song.map_err(database_error)?;
+        match openlp_sections(&song.lyrics) {
+            Ok((sections, tags)) if !sections.is_empty() => {
+                let order = section_order(&song.verse_order, &tags);
+                report.save(
+                    output_dir,
+                    importer::song_presentation(song.title, song.authors, sections, order),
+                );
+            }
+            Ok(_) => report.skip(song.title, "Song has no lyrics".to_string()),
+            Err(e) => report.skip(song.title, e),
+        }
+    }
+    Ok(report)
+}
+
+fn database_error(e: rusqlite::Error) -> CpresError {
+    CpresError::InvalidBundle(format!("Could not read OpenLP database: {e}"))
+}
+
+/// Sections of an OpenLP lyrics document, with the tag (e.g. "v1") of each
+fn openlp_sections(lyrics: &str) -> Result<(Vec<LyricSection>, Vec<String>), String> {
+    let doc = roxmltree::Document::parse(lyrics).map_err(|e| format!("Invalid lyrics: {e}"))?;
+    let mut sections = Vec::new();
+    let mut tags = Vec::new();
+    for verse in doc.descendants().filter(|n| n.has_tag_name("verse")) {
+        let tag = format!(
+            "{}{}",
+            verse.attribute("type").unwrap_or("v"),
+            verse.attribute("label").unwrap_or_default()
+        );
+        let text = strip_formatting_tags(&verse_text(verse));
+        let slides: Vec<String> = text
+            .split(OPENLP_SLIDE_BREAK)
+            .flat_map(importer::split_slides)
+            .collect();
+        if slides.is_empty() {
+            continue;
+        }
+        sections.push(LyricSection {
+            label: importer::label_for_tag(&tag),
+            slides,
+        });
+        tags.push(tag.to_lowercase());
+    }
+    Ok((sections, tags))
+}
+
+/// Text of a verse element; OpenLP 2 stores it as CDATA, older exports nest `<lines>`
+fn verse_text(verse: roxmltree::Node) -> String {
+    let lines: Vec<&str> = verse
+        .descendants()
+        .filter(|n| n.has_tag_name("lines"))
+        .filter_map(|n| n.text())
+        .collect();
+    if lines.is_empty() {
+        verse
+            .descendants()
+            .filter(|n| n.is_text())
+            .filter_map(|n| n.text())
+            .collect()
+    } else {
+        lines.join("\n\n")
+    }
+}
+
+/// Remove OpenLP formatting tags like `{st}`/`{/st}`; `{br}` becomes a line break
+fn strip_formatting_tags(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end)
+                if end <= 12
+                    && after[..end]
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '/') =>
+            {
+                if &after[..end] == "br" {
+                    out.push('\n');
+                }
+                rest = &after[end + 1..];
+            }
+            _ => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Map an order string like "v1 c1 v2" to section indices, given each section's tag
+pub(crate) fn section_order(order: &str, tags: &[String]) -> Option<Vec<usize>> {
+    let indices: Vec<usize> = order
+        .split_whitespace()
+        .filter_map(|tag| {
+            let tag = tag.to_lowercase();
+            // OpenSong writes "C" for a lone "C1"-style chorus and vice versa
+            tags.iter()
+                .position(|t| *t == tag)
+                .or_else(|| tags.iter().position(|t| *t == format!("{tag}1")))
+                .or_else(|| {
+                    let bare = tag.strip_suffix('1')?;
+                    tags.iter().position(|t| t == bare)
+                })
+        })
+        .collect();
+    (!indices.is_empty()).then_some(indices)
+}
+
+/// Import OpenSong songs from a song file or a folder of them into `output_dir`
+pub fn import_opensong(path: &Path, output_dir: &Path) -> Result<LibraryImport, CpresError> {
+    let mut files = Vec::new();
+    if path.is_dir() {
+        collect_files(path, 0, &mut files)?;
+    } else {
+        files.push(path.to_path_buf());
+    }
+
+    let mut report = LibraryImport::default();
+    for file in files {
+        let fallback_title = file
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+            .to_string();
+        match parse_opensong(&file) {
+            Ok(Some(mut song)) => {
+                if song.title.trim().is_empty() {
+                    song.title = fallback_title;
+                }
+                report.save(output_dir, song);
+            }
+            // Not an OpenSong song (settings, images, sets, ...)
+            Ok(None) => {}
+            Err(e) => report.skip(fallback_title, e),
+        }
+    }
+    Ok(report)
+}
+
+pub(crate) fn collect_files(
+    dir: &Path,
+    depth: usize,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), CpresError> {
+    if depth > MAX_FOLDER_DEPTH {
+        return Ok(());
+    }
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            !p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with('.'))
+        })
+        .collect();
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_files(&entry, depth + 1, files)?;
+        } else {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Parse one OpenSong file; None when the file isn't a song
+fn parse_opensong(path: &Path) -> Result<Option<importer::ImportedPresentation>, String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    if !data.trim_ascii_start().starts_with(b"<") {
+        return Ok(None);
+    }
+    let xml = String::from_utf8_lossy(&data);
+    let doc = roxmltree::Document::parse(&xml).map_err(|e| format!("Invalid XML: {e}"))?;
+    let root = doc.root_element();
+    if !root.has_tag_name("song") {
+        return Ok(None);
+    }
+
+    let field = |name: &str| {
+        root.children()
+            .find(|n| n.has_tag_name(name))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+    };
+    let (sections, tags) = opensong_sections(&field("lyrics").unwrap_or_default());
+    if sections.is_empty() {
+        return Err("Song has no lyrics".to_string());
+    }
+    let order = field("presentation").and_then(|order| section_order(&order, &tags));
+
+    Ok(Some(importer::song_presentation(
+        field("title").unwrap_or_default(),
+        field("author"),
+        sections,
+        order,
+    )))
+}
+
+/// Sections of OpenSong lyric text, with the tag of each.
+///
+/// Lines starting with `.` are chords and `;` comments. A digit at the start
+/// of a line assigns it to that verse number under the current header, so
+/// `[V]` followed by `1 ...` and `2 ...` lines yields V1 and V2. `||` forces a
+/// new slide and `|` a line break.
+fn opensong_sections(lyrics: &str) -> (Vec<LyricSection>, Vec<String>) {
+    let mut texts: Vec<(String, String)> = Vec::new();
+    let mut header = "V".to_string();
+
+    for line in lyrics.lines() {
+        if let Some(tag) = line
+            .trim()
+            .strip_prefix('[')
+            .and_then(|l| l.strip_suffix(']'))
+        {
+            header = tag.trim().to_string();
+            continue;
+        }
+        if line.starts_with('.') || line.starts_with(';') {
+            continue;
+        }
+
+        let (tag, text) = match line.chars().next() {
+            Some(digit) if digit.is_ascii_digit() => (format!("{header}{digit}"), &line[1..]),
+            _ => (header.clone(), line),
+        };
+        let text = text.trim().replace("||", "\n\n").replace('|', "\n");
+        let entry = match texts.iter_mut().find(|(t, _)| *t == tag) {
+            Some(entry) => entry,
+            None => {
+                texts.push((tag, String::new()));
+                texts.last_mut().expect("just pushed")
+            }
+        };
+        entry.1.push_str(&text);
+        entry.1.push('\n');
+    }
+
+    let mut sections = Vec::new();
+    let mut tags = Vec::new();
+    for (tag, text) in texts {
+        let slides = importer::split_slides(&text);
+        if slides.is_empty() {
+            continue;
+        }
+        sections.push(LyricSection {
+            label: importer::label_for_tag(&tag),
+            slides,
+        });
+        tags.push(tag.to_lowercase());
+    }
+    (sections, tags)
+}
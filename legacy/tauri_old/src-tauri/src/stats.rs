@@ -0,0 +1,179 @@
+//! Bundle statistics
+//!
+//! Summarizes where the bytes in a .cpres go so users can see why a
+//! presentation is too large to email.
+
+use crate::cpres::{self, CpresError};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::Path;
+use zip::ZipArchive;
+
+const LARGEST_ENTRY_COUNT: usize = 10;
+
+#[derive(Debug, Serialize, Default)]
+pub struct CategoryStats {
+    pub entries: u64,
+    pub compressed_bytes: u64,
+    pub uncompressed_bytes: u64,
+    pub compression_ratio: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntryStats {
+    pub name: String,
+    pub category: String,
+    pub compressed_bytes: u64,
+    pub uncompressed_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BundleStats {
+    pub file_bytes: u64,
+    pub entry_count: u64,
+    pub compressed_bytes: u64,
+    pub uncompressed_bytes: u64,
+    pub compression_ratio: f64,
+    pub slide_count: u64,
+    pub theme_count: u64,
+    pub media_count: u64,
+    pub font_count: u64,
+    /// Keyed by "image", "video", "audio", "font", "document", or "other"
+    pub categories: BTreeMap<String, CategoryStats>,
+    pub largest_entries: Vec<EntryStats>,
+}
+
+/// Compute size and content statistics for a bundle
+pub fn bundle_stats(path: &Path) -> Result<BundleStats, CpresError> {
+    let file_bytes = std::fs::metadata(path)?.len();
+    let file = File::open(path)?;
+    let mut archive = ZipArchive::new(file)?;
+
+    let media_types = manifest_media_types(&mut archive)?;
+
+    let mut entries = Vec::new();
+    let mut categories: BTreeMap<String, CategoryStats> = BTreeMap::new();
+    let mut theme_count = 0;
+    let mut media_count = 0;
+    let mut font_count = 0;
+
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+        let category = categorize(&name, &media_types);
+
+        if name.starts_with("themes/") && name.ends_with(".json") {
+            theme_count += 1;
+        } else if name.starts_with("media/") {
+            media_count += 1;
+        } else if name.starts_with("fonts/") {
+            font_count += 1;
+        }
+
+        let stats = categories.entry(category.clone()).or_default();
+        stats.entries += 1;
+        stats.compressed_bytes += entry.compressed_size();
+        stats.uncompressed_bytes += entry.size();
+
+        entries.push(EntryStats {
+            name,
+            category,
+            compressed_bytes: entry.compressed_size(),
+            uncompressed_bytes: entry.size(),
+        });
+    }
+
+    for stats in categories.values_mut() {
+        stats.compression_ratio = ratio(stats.compressed_bytes, stats.uncompressed_bytes);
+    }
+
+    let compressed_bytes = entries.iter().map(|e| e.compressed_bytes).sum();
+    let uncompressed_bytes = entries.iter().map(|e| e.uncompressed_bytes).sum();
+    let entry_count = entries.len() as u64;
+    let slide_count = count_slides(&mut archive)?;
+
+    entries.sort_by_key(|e| std::cmp::Reverse(e.compressed_bytes));
+    entries.truncate(LARGEST_ENTRY_COUNT);
+
+    Ok(BundleStats {
+        file_bytes,
+        entry_count,
+        compressed_bytes,
+        uncompressed_bytes,
+        compression_ratio: ratio(compressed_bytes, uncompressed_bytes),
+        slide_count,
+        theme_count,
+        media_count,
+        font_count,
+        categories,
+        largest_entries: entries,
+    })
+}
+
+/// Compressed size as a fraction of uncompressed size (1.0 = no savings)
+fn ratio(compressed: u64, uncompressed: u64) -> f64 {
+    if uncompressed == 0 {
+        1.0
+    } else {
+        compressed as f64 / uncompressed as f64
+    }
+}
+
+fn categorize(name: &str, media_types: &HashMap<String, String>) -> String {
+    if name.starts_with("media/") {
+        if let Some(media_type) = media_types.get(name) {
+            return media_type.clone();
+        }
+        return "other".to_string();
+    }
+    if name.starts_with("fonts/") {
+        return "font".to_string();
+    }
+    if name.ends_with(".json") {
+        return "document".to_string();
+    }
+    "other".to_string()
+}
+
+/// Map of bundle path -> media type ("image"/"video"/"audio") from the manifest
+fn manifest_media_types(
+    archive: &mut ZipArchive<File>,
+) -> Result<HashMap<String, String>, CpresError> {
+    let manifest = read_json(archive, "manifest.json")?;
+    let mut types = HashMap::new();
+
+    let media = manifest
+        .get("media")
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten();
+    for entry in media {
+        let (Some(path), Some(media_type)) = (
+            entry.get("path").and_then(|p| p.as_str()),
+            entry.get("type").and_then(|t| t.as_str()),
+        ) else {
+            continue;
+        };
+        types.insert(path.to_string(), media_type.to_string());
+    }
+
+    Ok(types)
+}
+
+fn count_slides(archive: &mut ZipArchive<File>) -> Result<u64, CpresError> {
+    let slides = read_json(archive, "slides.json")?;
+    let count = slides
+        .as_array()
+        .or_else(|| slides.get("slides").and_then(|s| s.as_array()))
+        .map(|s| s.len())
+        .unwrap_or(0);
+    Ok(count as u64)
+}
+
+fn read_json(archive: &mut ZipArchive<File>, name: &str) -> Result<serde_json::Value, CpresError> {
+    Ok(serde_json::from_str(&cpres::read_zip_file(archive, name)?)?)
+}
@@ -0,0 +1,701 @@
+//! Searching and downloading stock photos and videos
+//!
+//! Unsplash (photos) and Pexels (photos and videos) are a ready source of
+//! worship backgrounds. Each provider needs an API key of the church's own,
+//! kept in `<app data>/stock-media.json`; providers without a key are left
+//! out of searches. Downloads go through the download manager, so a file
+//! picked twice is fetched once, and are imported like a file from disk with
+//! the provider's credit stored as the entry's `attribution`.
+//!
+//! Stock ids are `<provider>:<media type>:<provider's id>`, such as
+//! `pexels:video:3045163`.
+
+use crate::cpres::{self, MediaEntry};
+use crate::download::{self, DownloadError};
+use crate::tasks::CancelToken;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tauri::AppHandle;
+
+const KEYS_FILENAME: &str = "stock-media.json";
+
+const UNSPLASH_API: &str = "https://api.unsplash.com";
+const PEXELS_API: &str = "https://api.pexels.com";
+/// Unsplash asks for these on every link back to it
+const UNSPLASH_REFERRAL: &str = "utm_source=church_presenter&utm_medium=referral";
+
+pub const DEFAULT_PER_PAGE: u32 = 30;
+/// The most either provider returns in one page
+const MAX_PER_PAGE: u32 = 30;
+/// Width a download is fetched at, at most: a 4K screen's
+const DOWNLOAD_WIDTH: u32 = 3840;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StockProvider {
+    Unsplash,
+    Pexels,
+}
+
+impl StockProvider {
+    const ALL: [StockProvider; 2] = [StockProvider::Unsplash, StockProvider::Pexels];
+
+    fn id(self) -> &'static str {
+        match self {
+            StockProvider::Unsplash => "unsplash",
+            StockProvider::Pexels => "pexels",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            StockProvider::Unsplash => "Unsplash",
+            StockProvider::Pexels => "Pexels",
+        }
+    }
+
+    fn license(self) -> &'static str {
+        match self {
+            StockProvider::Unsplash => "Unsplash License",
+            StockProvider::Pexels => "Pexels License",
+        }
+    }
+
+    fn supports(self, media_type: StockMediaType) -> bool {
+        self == StockProvider::Pexels || media_type == StockMediaType::Photo
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StockMediaType {
+    #[default]
+    Photo,
+    Video,
+}
+
+impl StockMediaType {
+    fn id(self) -> &'static str {
+        match self {
+            StockMediaType::Photo => "photo",
+            StockMediaType::Video => "video",
+        }
+    }
+}
+
+/// API keys by provider
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StockMediaKeys {
+    unsplash: Option<String>,
+    pexels: Option<String>,
+}
+
+impl StockMediaKeys {
+    fn get(&self, provider: StockProvider) -> Option<&str> {
+        match provider {
+            StockProvider::Unsplash => self.unsplash.as_deref(),
+            StockProvider::Pexels => self.pexels.as_deref(),
+        }
+    }
+
+    fn set(&mut self, provider: StockProvider, key: Option<String>) {
+        match provider {
+            StockProvider::Unsplash => self.unsplash = key,
+            StockProvider::Pexels => self.pexels = key,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProviderStatus {
+    pub provider: StockProvider,
+    pub name: &'static str,
+    /// Whether an API key is set
+    pub configured: bool,
+    pub videos: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StockSearchOptions {
+    /// Every provider with a key when None
+    pub provider: Option<StockProvider>,
+    pub media_type: StockMediaType,
+    /// Counting from 1
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+}
+
+/// Credit for a stock photo or video, as the provider asks for it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attribution {
+    /// "Unsplash" or "Pexels"
+    pub provider: String,
+    pub author: String,
+    pub author_url: Option<String>,
+    /// The photo or video's page on the provider's site
+    pub source_url: String,
+    pub license: String,
+    /// Ready to show, e.g. "Photo by Jane Doe on Unsplash"
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StockItem {
+    /// Pass to `download_stock_media`
+    pub id: String,
+    pub provider: StockProvider,
+    pub media_type: StockMediaType,
+    pub width: u32,
+    pub height: u32,
+    pub description: Option<String>,
+    /// A small image to show in results
+    pub preview_url: String,
+    /// Seconds, for videos
+    pub duration: Option<f64>,
+    pub attribution: Attribution,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StockSearchPage {
+    pub items: Vec<StockItem>,
+    pub page: u32,
+    /// Whether a provider has another page
+    pub has_more: bool,
+    /// Providers that couldn't be searched; the others still were
+    pub failed: Vec<ProviderFailure>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProviderFailure {
+    pub provider: StockProvider,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StockDownload {
+    /// The downloaded file: copy this into the bundle
+    pub source_path: String,
+    pub entry: MediaEntry,
+}
+
+/// Every provider, and whether it has a key
+pub fn providers(app_data_dir: &Path) -> Vec<ProviderStatus> {
+    let keys = read_keys(app_data_dir);
+    StockProvider::ALL
+        .into_iter()
+        .map(|provider| ProviderStatus {
+            provider,
+            name: provider.name(),
+            configured: keys.get(provider).is_some(),
+            videos: provider.supports(StockMediaType::Video),
+        })
+        .collect()
+}
+
+/// Store the API key for `provider`, or forget it with None
+pub fn set_key(
+    app_data_dir: &Path,
+    provider: StockProvider,
+    key: Option<&str>,
+) -> Result<(), DownloadError> {
+    let mut keys = read_keys(app_data_dir);
+    let key = key
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(String::from);
+    keys.set(provider, key);
+    std::fs::create_dir_all(app_data_dir)?;
+    std::fs::write(
+        app_data_dir.join(KEYS_FILENAME),
+        serde_json::to_vec_pretty(&keys)?,
+    )?;
+    Ok(())
+}
+
+/// Search one provider, or every provider with a key
+pub async fn search(
+    app_data_dir: &Path,
+    query: &str,
+    options: &StockSearchOptions,
+) -> Result<StockSearchPage, DownloadError> {
+    let keys = read_keys(app_data_dir);
+    let page = options.page.unwrap_or(1).max(1);
+    let per_page = options
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+    let providers: Vec<StockProvider> = match options.provider {
+        Some(provider) => {
+            if keys.get(provider).is_none() {
+                return Err(DownloadError::MissingApiKey(provider.name().to_string()));
+            }
+            vec![provider]
+        }
+        None => StockProvider::ALL
+            .into_iter()
+            .filter(|provider| keys.get(*provider).is_some())
+            .collect(),
+    };
+
+    let mut result = StockSearchPage {
+        items: Vec::new(),
+        page,
+        has_more: false,
+        failed: Vec::new(),
+    };
+    for provider in providers {
+        if !provider.supports(options.media_type) {
+            continue;
+        }
+        let key = keys.get(provider).unwrap_or_default();
+        let searched = match provider {
+            StockProvider::Unsplash => search_unsplash(key, query, page, per_page).await,
+            StockProvider::Pexels => {
+                search_pexels(key, query, options.media_type, page, per_page).await
+            }
+        };
+        match searched {
+            Ok((items, has_more)) => {
+                result.items.extend(items);
+                result.has_more |= has_more;
+            }
+            // One provider being down shouldn't hide the other's results
+            Err(e) if options.provider.is_none() => {
+                log::warn!("Could not search {}: {e}", provider.name());
+                result.failed.push(ProviderFailure {
+                    provider,
+                    reason: e.to_string(),
+                });
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(result)
+}
+
+/// Download the stock item `id` and describe it as a media entry credited
+/// to its author
+pub async fn download(
+    app: &AppHandle,
+    app_data_dir: &Path,
+    id: &str,
+) -> Result<StockDownload, DownloadError> {
+    let (provider, media_type, provider_id) =
+        parse_id(id).ok_or_else(|| DownloadError::UnsupportedUrl(id.to_string()))?;
+    let keys = read_keys(app_data_dir);
+    let key = keys
+        .get(provider)
+        .ok_or_else(|| DownloadError::MissingApiKey(provider.name().to_string()))?;
+
+    let (url, attribution) = match (provider, media_type) {
+        (StockProvider::Unsplash, _) => {
+            let photo: UnsplashPhoto = get_json(
+                unsplash_url(&format!("/photos/{provider_id}"), &[])?,
+                key,
+                provider,
+            )
+            .await?;
+            // Unsplash counts downloads through this endpoint and asks apps
+            // to call it; the file itself comes from the image CDN
+            if let Ok(url) = Url::parse(&photo.links.download_location) {
+                if let Err(e) = get_json::<serde_json::Value>(url, key, provider).await {
+                    log::info!("Could not report an Unsplash download: {e}");
+                }
+            }
+            let mut url = Url::parse(&photo.urls.raw)
+                .map_err(|_| DownloadError::UnsupportedUrl(photo.urls.raw.clone()))?;
+            url.query_pairs_mut()
+                .append_pair("w", &DOWNLOAD_WIDTH.to_string())
+                .append_pair("fm", "jpg")
+                .append_pair("q", "85");
+            (url, unsplash_attribution(&photo))
+        }
+        (StockProvider::Pexels, StockMediaType::Photo) => {
+            let photo: PexelsPhoto = get_json(
+                pexels_url(&format!("/v1/photos/{provider_id}"), &[])?,
+                key,
+                provider,
+            )
+            .await?;
+            let mut url = Url::parse(&photo.src.original)
+                .map_err(|_| DownloadError::UnsupportedUrl(photo.src.original.clone()))?;
+            if photo.width > DOWNLOAD_WIDTH {
+                url.query_pairs_mut()
+                    .append_pair("auto", "compress")
+                    .append_pair("w", &DOWNLOAD_WIDTH.to_string());
+            }
+            (
+                url,
+                pexels_attribution(
+                    media_type,
+                    &photo.photographer,
+                    &photo.photographer_url,
+                    &photo.url,
+                ),
+            )
+        }
+        (StockProvider::Pexels, StockMediaType::Video) => {
+            let video: PexelsVideo = get_json(
+                pexels_url(&format!("/videos/videos/{provider_id}"), &[])?,
+                key,
+                provider,
+            )
+            .await?;
+            let file = best_video_file(&video.video_files).ok_or_else(|| {
+                DownloadError::UnexpectedResponse("The video has no MP4 file".to_string())
+            })?;
+            let url = Url::parse(&file.link)
+                .map_err(|_| DownloadError::UnsupportedUrl(file.link.clone()))?;
+            (
+                url,
+                pexels_attribution(media_type, &video.user.name, &video.user.url, &video.url),
+            )
+        }
+    };
+
+    let file = download::fetch_cached(app, &download::cache_root(app_data_dir), url.as_str(), None)
+        .await?;
+    let source = Path::new(&file.path);
+    let mut entry = cpres::import_media_file(source, None, None, &CancelToken::default())?.entry;
+    let extension = Path::new(&entry.path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("bin")
+        .to_string();
+    entry.filename = format!(
+        "{}-{}-{provider_id}.{extension}",
+        provider.id(),
+        media_type.id()
+    );
+    entry.attribution = Some(attribution);
+    Ok(StockDownload {
+        source_path: file.path,
+        entry,
+    })
+}
+
+fn read_keys(app_data_dir: &Path) -> StockMediaKeys {
+    std::fs::read(app_data_dir.join(KEYS_FILENAME))
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default()
+}
+
+/// `<provider>:<media type>:<id>` split up
+fn parse_id(id: &str) -> Option<(StockProvider, StockMediaType, &str)> {
+    let mut parts = id.splitn(3, ':');
+    let provider = StockProvider::ALL
+        .into_iter()
+        .find(|provider| provider.id() == parts.next().unwrap_or_default())?;
+    let media_type = match parts.next()? {
+        "photo" => StockMediaType::Photo,
+        "video" => StockMediaType::Video,
+        _ => return None,
+    };
+    let provider_id = parts.next()?;
+    // The id goes into an API path
+    if provider_id.is_empty()
+        || !provider_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        || !provider.supports(media_type)
+    {
+        return None;
+    }
+    Some((provider, media_type, provider_id))
+}
+
+fn stock_id(provider: StockProvider, media_type: StockMediaType, id: &str) -> String {
+    format!("{}:{}:{id}", provider.id(), media_type.id())
+}
+
+fn unsplash_url(path: &str, params: &[(&str, String)]) -> Result<Url, DownloadError> {
+    Url::parse_with_params(&format!("{UNSPLASH_API}{path}"), params)
+        .map_err(|_| DownloadError::UnsupportedUrl(path.to_string()))
+}
+
+fn pexels_url(path: &str, params: &[(&str, String)]) -> Result<Url, DownloadError> {
+    Url::parse_with_params(&format!("{PEXELS_API}{path}"), params)
+        .map_err(|_| DownloadError::UnsupportedUrl(path.to_string()))
+}
+
+/// GET an API endpoint of `provider` and parse the JSON it returns
+async fn get_json<T: DeserializeOwned>(
+    url: Url,
+    key: &str,
+    provider: StockProvider,
+) -> Result<T, DownloadError> {
+    let request = reqwest::Client::new().get(url);
+    let request = match provider {
+        StockProvider::Unsplash => request
+            .header(reqwest::header::AUTHORIZATION, format!("Client-ID {key}"))
+            .header("Accept-Version", "v1"),
+        StockProvider::Pexels => request.header(reqwest::header::AUTHORIZATION, key),
+    };
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(DownloadError::Status(response.status().as_u16()));
+    }
+    Ok(serde_json::from_slice(&response.bytes().await?)?)
+}
+
+async fn search_unsplash(
+    key: &str,
+    query: &str,
+    page: u32,
+    per_page: u32,
+) -> Result<(Vec<StockItem>, bool), DownloadError> {
+    let url = unsplash_url(
+        "/search/photos",
+        &[
+            ("query", query.to_string()),
+            ("page", page.to_string()),
+            ("per_page", per_page.to_string()),
+            ("content_filter", "high".to_string()),
+        ],
+    )?;
+    let found: UnsplashSearch = get_json(url, key, StockProvider::Unsplash).await?;
+    let items = found
+        .results
+        .iter()
+        .map(|photo| StockItem {
+            id: stock_id(StockProvider::Unsplash, StockMediaType::Photo, &photo.id),
+            provider: StockProvider::Unsplash,
+            media_type: StockMediaType::Photo,
+            width: photo.width,
+            height: photo.height,
+            description: photo
+                .description
+                .clone()
+                .or_else(|| photo.alt_description.clone()),
+            preview_url: photo.urls.small.clone(),
+            duration: None,
+            attribution: unsplash_attribution(photo),
+        })
+        .collect();
+    Ok((items, page < found.total_pages))
+}
+
+async fn search_pexels(
+    key: &str,
+    query: &str,
+    media_type: StockMediaType,
+    page: u32,
+    per_page: u32,
+) -> Result<(Vec<StockItem>, bool), DownloadError> {
+    let params = [
+        ("query", query.to_string()),
+        ("page", page.to_string()),
+        ("per_page", per_page.to_string()),
+    ];
+    let items = match media_type {
+        StockMediaType::Photo => {
+            let found: PexelsPhotoSearch = get_json(
+                pexels_url("/v1/search", &params)?,
+                key,
+                StockProvider::Pexels,
+            )
+            .await?;
+            let items: Vec<StockItem> = found
+                .photos
+                .iter()
+                .map(|photo| StockItem {
+                    id: stock_id(StockProvider::Pexels, media_type, &photo.id.to_string()),
+                    provider: StockProvider::Pexels,
+                    media_type,
+                    width: photo.width,
+                    height: photo.height,
+                    description: photo.alt.clone().filter(|alt| !alt.is_empty()),
+                    preview_url: photo.src.medium.clone(),
+                    duration: None,
+                    attribution: pexels_attribution(
+                        media_type,
+                        &photo.photographer,
+                        &photo.photographer_url,
+                        &photo.url,
+                    ),
+                })
+                .collect();
+            (items, found.next_page.is_some())
+        }
+        StockMediaType::Video => {
+            let found: PexelsVideoSearch = get_json(
+                pexels_url("/videos/search", &params)?,
+                key,
+                StockProvider::Pexels,
+            )
+            .await?;
+            let items: Vec<StockItem> = found
+                .videos
+                .iter()
+                .map(|video| StockItem {
+                    id: stock_id(StockProvider::Pexels, media_type, &video.id.to_string()),
+                    provider: StockProvider::Pexels,
+                    media_type,
+                    width: video.width,
+                    height: video.height,
+                    description: None,
+                    preview_url: video.image.clone(),
+                    duration: video.duration,
+                    attribution: pexels_attribution(
+                        media_type,
+                        &video.user.name,
+                        &video.user.url,
+                        &video.url,
+                    ),
+                })
+                .collect();
+            (items, found.next_page.is_some())
+        }
+    };
+    Ok(items)
+}
+
+fn unsplash_attribution(photo: &UnsplashPhoto) -> Attribution {
+    let provider = StockProvider::Unsplash;
+    Attribution {
+        provider: provider.name().to_string(),
+        author: photo.user.name.clone(),
+        author_url: Some(format!("{}?{UNSPLASH_REFERRAL}", photo.user.links.html)),
+        source_url: format!("{}?{UNSPLASH_REFERRAL}", photo.links.html),
+        license: provider.license().to_string(),
+        text: format!("Photo by {} on Unsplash", photo.user.name),
+    }
+}
+
+fn pexels_attribution(
+    media_type: StockMediaType,
+    author: &str,
+    author_url: &str,
+    source_url: &str,
+) -> Attribution {
+    let provider = StockProvider::Pexels;
+    let kind = match media_type {
+        StockMediaType::Photo => "Photo",
+        StockMediaType::Video => "Video",
+    };
+    Attribution {
+        provider: provider.name().to_string(),
+        author: author.to_string(),
+        author_url: Some(author_url.to_string()).filter(|url| !url.is_empty()),
+        source_url: source_url.to_string(),
+        license: provider.license().to_string(),
+        text: format!("{kind} by {author} on Pexels"),
+    }
+}
+
+/// The largest MP4 rendition no wider than `DOWNLOAD_WIDTH`, or the
+/// smallest when every one is wider
+fn best_video_file(files: &[PexelsVideoFile]) -> Option<&PexelsVideoFile> {
+    let mp4 = || {
+        files
+            .iter()
+            .filter(|file| file.file_type.as_deref() == Some("video/mp4"))
+    };
+    mp4()
+        .filter(|file| file.width.is_some_and(|width| width <= DOWNLOAD_WIDTH))
+        .max_by_key(|file| file.width)
+        .or_else(|| mp4().min_by_key(|file| file.width))
+}
+
+#[derive(Deserialize)]
+struct UnsplashSearch {
+    total_pages: u32,
+    results: Vec<UnsplashPhoto>,
+}
+
+#[derive(Deserialize)]
+struct UnsplashPhoto {
+    id: String,
+    width: u32,
+    height: u32,
+    description: Option<String>,
+    alt_description: Option<String>,
+    urls: UnsplashUrls,
+    links: UnsplashLinks,
+    user: UnsplashUser,
+}
+
+#[derive(Deserialize)]
+struct UnsplashUrls {
+    raw: String,
+    small: String,
+}
+
+#[derive(Deserialize)]
+struct UnsplashLinks {
+    html: String,
+    #[serde(default)]
+    download_location: String,
+}
+
+#[derive(Deserialize)]
+struct UnsplashUser {
+    name: String,
+    links: UnsplashUserLinks,
+}
+
+#[derive(Deserialize)]
+struct UnsplashUserLinks {
+    html: String,
+}
+
+#[derive(Deserialize)]
+struct PexelsPhotoSearch {
+    photos: Vec<PexelsPhoto>,
+    next_page: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PexelsPhoto {
+    id: u64,
+    width: u32,
+    height: u32,
+    url: String,
+    photographer: String,
+    #[serde(default)]
+    photographer_url: String,
+    alt: Option<String>,
+    src: PexelsPhotoSources,
+}
+
+#[derive(Deserialize)]
+struct PexelsPhotoSources {
+    original: String,
+    medium: String,
+}
+
+#[derive(Deserialize)]
+struct PexelsVideoSearch {
+    videos: Vec<PexelsVideo>,
+    next_page: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PexelsVideo {
+    id: u64,
+    width: u32,
+    height: u32,
+    url: String,
+    image: String,
+    duration: Option<f64>,
+    user: PexelsUser,
+    video_files: Vec<PexelsVideoFile>,
+}
+
+#[derive(Deserialize)]
+struct PexelsUser {
+    name: String,
+    #[serde(default)]
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct PexelsVideoFile {
+    file_type: Option<String>,
+    width: Option<u32>,
+    link: String,
+}
@@ -0,0 +1,274 @@
+//! Storage checks for a bundle's location
+//!
+//! Saving writes a temp file next to the bundle and renames it into place, so
+//! a read-only SD card, a locked share, or a full disk only shows up at the very
+//! end of a save. These checks let the UI warn when the file is opened.
+
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+#[derive(Debug, Serialize)]
+pub struct StorageStatus {
+    pub exists: bool,
+    /// Whether a save to this path is expected to succeed
+    pub writable: bool,
+    /// Why the location is not writable
+    pub reason: Option<String>,
+    pub free_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+    /// Bytes a save needs at the destination (a temp copy of the current bundle)
+    pub required_bytes: u64,
+    /// e.g. "ntfs", "apfs", "exfat", "smbfs"
+    pub filesystem: Option<String>,
+    pub network: bool,
+}
+
+/// Inspect the volume and permissions at `path` (which need not exist yet)
+pub fn storage_status(path: &Path) -> StorageStatus {
+    let exists = path.is_file();
+    let required_bytes = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let dir = existing_ancestor(path);
+
+    let reason = dir
+        .as_deref()
+        .map_or(Some("Folder does not exist".to_string()), |dir| {
+            write_blocker(path, dir, exists)
+        });
+    let volume = dir.as_deref().map(volume_info).unwrap_or_default();
+
+    StorageStatus {
+        exists,
+        writable: reason.is_none(),
+        reason,
+        free_bytes: volume.free_bytes,
+        total_bytes: volume.total_bytes,
+        required_bytes,
+        filesystem: volume.filesystem,
+        network: volume.network,
+    }
+}
+
+/// Whether the file at `path` is on a local disk, outside the folders cloud
+/// sync clients manage: there only this app's saves change bundles, and those
+/// replace the file by renaming. Network shares and sync clients may rewrite
+/// a file in place, which a reader mapping it doesn't survive.
+pub(crate) fn is_local_unsynced(path: &Path) -> bool {
+    const LOCAL_FILESYSTEMS: &[&str] = &[
+        "ext4", "btrfs", "xfs", "zfs", "tmpfs", "overlay", "apfs", "hfs", "ntfs", "refs",
+    ];
+    const SYNC_FOLDERS: &[&str] = &[
+        "onedrive",
+        "dropbox",
+        "google drive",
+        "googledrive",
+        "icloud drive",
+        "icloud",
+        "mobile documents",
+        "cloudstorage",
+        "box",
+        "box sync",
+        "nextcloud",
+        "owncloud",
+        "pcloud drive",
+        "sync",
+    ];
+
+    let in_sync_folder = path.components().any(|component| {
+        let name = component.as_os_str().to_string_lossy().to_lowercase();
+        SYNC_FOLDERS.contains(&name.as_str()) || name.starts_with("onedrive - ")
+    });
+    if in_sync_folder {
+        return false;
+    }
+    let Some(dir) = existing_ancestor(path) else {
+        return false;
+    };
+    let volume = volume_info(&dir);
+    !volume.network
+        && volume
+            .filesystem
+            .is_some_and(|name| LOCAL_FILESYSTEMS.contains(&name.as_str()))
+}
+
+/// Reason a save to `path` would fail, or None when it should work
+fn write_blocker(path: &Path, dir: &Path, exists: bool) -> Option<String> {
+    if exists {
+        match std::fs::metadata(path) {
+            Ok(metadata) if metadata.permissions().readonly() => {
+                return Some("File is marked read-only".to_string());
+            }
+            Ok(_) => {}
+            Err(e) => return Some(e.to_string()),
+        }
+        // Opening for append doesn't modify the file but fails on locked or read-only media
+        if let Err(e) = OpenOptions::new().append(true).open(path) {
+            return Some(describe_io_error(&e));
+        }
+    }
+
+    // The save itself needs to create a temp file in the folder
+    NamedTempFile::new_in(dir)
+        .err()
+        .map(|e| describe_io_error(&e))
+}
+
+fn describe_io_error(error: &std::io::Error) -> String {
+    match error.kind() {
+        std::io::ErrorKind::ReadOnlyFilesystem => "The drive is read-only".to_string(),
+        std::io::ErrorKind::PermissionDenied => "Permission denied".to_string(),
+        _ => error.to_string(),
+    }
+}
+
+/// `path` itself if it is a directory, else its nearest existing parent folder
+fn existing_ancestor(path: &Path) -> Option<PathBuf> {
+    let mut current = if path.is_dir() {
+        Some(path)
+    } else {
+        path.parent()
+    };
+    while let Some(dir) = current {
+        if dir.as_os_str().is_empty() {
+            return std::env::current_dir().ok();
+        }
+        if dir.is_dir() {
+            return Some(dir.to_path_buf());
+        }
+        current = dir.parent();
+    }
+    None
+}
+
+#[derive(Default)]
+struct VolumeInfo {
+    free_bytes: Option<u64>,
+    total_bytes: Option<u64>,
+    filesystem: Option<String>,
+    network: bool,
+}
+
+#[cfg(unix)]
+fn volume_info(dir: &Path) -> VolumeInfo {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(c_path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return VolumeInfo::default();
+    };
+
+    let mut info = VolumeInfo::default();
+    let mut vfs: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut vfs) } == 0 {
+        let fragment = vfs.f_frsize as u64;
+        info.free_bytes = Some(vfs.f_bavail as u64 * fragment);
+        info.total_bytes = Some(vfs.f_blocks as u64 * fragment);
+    }
+
+    let mut fs: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut fs) } == 0 {
+        let (name, network) = filesystem_name(&fs);
+        info.filesystem = name;
+        info.network = network;
+    }
+
+    info
+}
+
+#[cfg(target_os = "linux")]
+fn filesystem_name(fs: &libc::statfs) -> (Option<String>, bool) {
+    // Magic numbers from linux/magic.h
+    let (name, network) = match fs.f_type as u32 {
+        0xEF53 => ("ext4", false),
+        0x9123_683E => ("btrfs", false),
+        0x5846_5342 => ("xfs", false),
+        0x2FC1_2FC1 => ("zfs", false),
+        0x4D44 => ("vfat", false),
+        0x2011_BAB0 => ("exfat", false),
+        0x5346_544E | 0x7366_746E => ("ntfs", false),
+        0x6573_5546 => ("fuseblk", false),
+        0x0102_1994 => ("tmpfs", false),
+        0x794C_7630 => ("overlay", false),
+        0x9660 => ("iso9660", false),
+        0x6969 => ("nfs", true),
+        0xFF53_4D42 => ("cifs", true),
+        0xFE53_4D42 => ("smb2", true),
+        0x517B => ("smb", true),
+        _ => return (None, false),
+    };
+    (Some(name.to_string()), network)
+}
+
+#[cfg(target_os = "macos")]
+fn filesystem_name(fs: &libc::statfs) -> (Option<String>, bool) {
+    let name: String = fs
+        .f_fstypename
+        .iter()
+        .take_while(|c| **c != 0)
+        .map(|c| *c as u8 as char)
+        .collect();
+    let network = matches!(name.as_str(), "smbfs" | "nfs" | "afpfs" | "webdav");
+    (Some(name).filter(|n| !n.is_empty()), network)
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+fn filesystem_name(_fs: &libc::statfs) -> (Option<String>, bool) {
+    (None, false)
+}
+
+#[cfg(target_os = "windows")]
+fn volume_info(dir: &Path) -> VolumeInfo {
+    use windows::core::PCWSTR;
+    use windows::Win32::Storage::FileSystem::{
+        GetDiskFreeSpaceExW, GetDriveTypeW, GetVolumeInformationW, GetVolumePathNameW,
+    };
+    use windows::Win32::System::WindowsProgramming::DRIVE_REMOTE;
+
+    let dir_w: Vec<u16> = dir
+        .to_string_lossy()
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut root_buf = [0u16; 260];
+    if unsafe { GetVolumePathNameW(PCWSTR(dir_w.as_ptr()), &mut root_buf) }.is_err() {
+        return VolumeInfo::default();
+    }
+    let root = PCWSTR(root_buf.as_ptr());
+
+    let mut info = VolumeInfo::default();
+    let mut free = 0u64;
+    let mut total = 0u64;
+    if unsafe {
+        GetDiskFreeSpaceExW(
+            root,
+            Some(&mut free as *mut u64),
+            Some(&mut total as *mut u64),
+            None,
+        )
+    }
+    .is_ok()
+    {
+        info.free_bytes = Some(free);
+        info.total_bytes = Some(total);
+    }
+
+    let mut fs_name = [0u16; 32];
+    if unsafe { GetVolumeInformationW(root, None, None, None, None, Some(&mut fs_name[..])) }
+        .is_ok()
+    {
+        let len = fs_name
+            .iter()
+            .position(|c| *c == 0)
+            .unwrap_or(fs_name.len());
+        info.filesystem = Some(String::from_utf16_lossy(&fs_name[..len]).to_lowercase());
+    }
+
+    info.network = unsafe { GetDriveTypeW(root) } == DRIVE_REMOTE;
+    info
+}
+
+#[cfg(not(any(unix, target_os = "windows")))]
+fn volume_info(_dir: &Path) -> VolumeInfo {
+    VolumeInfo::default()
+}
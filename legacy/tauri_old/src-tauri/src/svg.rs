@@ -0,0 +1,120 @@
+//! Rasterizing SVG logos at the size they're shown
+//!
+//! The webview scales an SVG cleanly where it draws it itself, but anywhere a
+//! bitmap is needed, such as exported slides or an output that shows a
+//! prepared image, an SVG comes out blurry from being scaled up as a small
+//! bitmap or isn't drawn at all. resvg draws it at the exact pixel size
+//! instead.
+//! `rasterize_svg` fits the drawing inside the size asked for, keeping its
+//! aspect ratio, and keeps the PNG in the thumbnail cache under the file's
+//! hash and that size, so a logo is drawn once per projector resolution.
+//!
+//! Text in an SVG uses the installed fonts, which are loaded the first time
+//! one is drawn.
+
+use crate::cpres::{self, CpresError};
+use crate::tasks::CancelToken;
+use crate::thumbnails::{self, ThumbnailCache};
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{self, fontdb};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+/// Longest edge drawn; an 8K output is 7680 wide
+const MAX_DIMENSION: u32 = 8192;
+
+#[derive(Debug, Serialize)]
+pub struct RasterizedSvg {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    /// False when it had to be drawn
+    pub cached: bool,
+}
+
+/// PNG of the SVG at `path`, as large as fits inside `width` × `height`
+pub fn rasterize_svg(
+    cache: &ThumbnailCache,
+    path: &Path,
+    width: u32,
+    height: u32,
+) -> Result<RasterizedSvg, CpresError> {
+    let (width, height) = (
+        width.clamp(1, MAX_DIMENSION),
+        height.clamp(1, MAX_DIMENSION),
+    );
+    let (digest, _) = cpres::hash_file(path, &CancelToken::default())?;
+    let key = format!("{digest}-svg-{width}x{height}");
+    if let Some(cached) = cache.get(&key) {
+        let (width, height) = thumbnails::dimensions(&cached)?;
+        return Ok(RasterizedSvg {
+            path: cached.to_string_lossy().to_string(),
+            width,
+            height,
+            cached: true,
+        });
+    }
+
+    let pixmap = rasterize(&std::fs::read(path)?, path.parent(), width, height, false)?;
+    let data = pixmap
+        .encode_png()
+        .map_err(|e| CpresError::InvalidBundle(format!("Could not encode the SVG: {e}")))?;
+    let output = cache.put(&key, "png", &data)?;
+    Ok(RasterizedSvg {
+        path: output.to_string_lossy().to_string(),
+        width: pixmap.width(),
+        height: pixmap.height(),
+        cached: false,
+    })
+}
+
+/// Draw an SVG scaled to fit inside `width` × `height`, or to cover it with
+/// `cover` set. Relative image references resolve against `resources_dir`.
+pub(crate) fn rasterize(
+    data: &[u8],
+    resources_dir: Option<&Path>,
+    width: u32,
+    height: u32,
+    cover: bool,
+) -> Result<Pixmap, CpresError> {
+    let options = usvg::Options {
+        resources_dir: resources_dir.map(Path::to_path_buf),
+        fontdb: system_fonts(),
+        ..usvg::Options::default()
+    };
+    let tree = usvg::Tree::from_data(data, &options)
+        .map_err(|e| CpresError::InvalidBundle(format!("Unsupported SVG: {e}")))?;
+    let size = tree.size();
+    let (scale_x, scale_y) = (width as f32 / size.width(), height as f32 / size.height());
+    let scale = if cover {
+        scale_x.max(scale_y)
+    } else {
+        scale_x.min(scale_y)
+    };
+    // Covering can overshoot the box a lot for a very long, thin drawing
+    let scale = scale.min(MAX_DIMENSION as f32 / size.width().max(size.height()));
+    let mut pixmap = Pixmap::new(
+        ((size.width() * scale).round() as u32).max(1),
+        ((size.height() * scale).round() as u32).max(1),
+    )
+    .ok_or_else(|| CpresError::InvalidBundle("The SVG has no size".to_string()))?;
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    Ok(pixmap)
+}
+
+/// Installed fonts, loaded once
+fn system_fonts() -> Arc<fontdb::Database> {
+    static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut fonts = fontdb::Database::new();
+            fonts.load_system_fonts();
+            Arc::new(fonts)
+        })
+        .clone()
+}
@@ -0,0 +1,331 @@
+//! The installed fonts, cached between runs
+//!
+//! Listing the installed fonts means loading every font file, which takes
+//! seconds on a Windows machine with thousands of them. The list is kept in
+//! `<app data>/system-fonts.json` together with the modification times of
+//! the font folders, which change whenever a font is installed or removed.
+//! While they match, the cached list is used as it is. When they don't, the
+//! stale list is still returned straight away and a new one is made in the
+//! background, announced with `fonts:changed` when it's ready; only the very
+//! first listing waits for the scan. `refresh` rescans on demand, for fonts
+//! installed somewhere the folders don't show.
+//!
+//! Fonts downloaded into `<app data>/fonts/` (from Google Fonts, say) are
+//! listed with the installed ones, so they can be picked and embedded like
+//! any other font without installing them for the whole machine, and so
+//! are a bundle's fonts registered for the session in
+//! `<app cache>/session-fonts/`.
+
+use crate::cpres::CpresError;
+use crate::font_details::{self, FontAxis};
+use crate::font_install;
+use font_kit::handle::Handle;
+use font_kit::properties::Style;
+use font_kit::source::SystemSource;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const FONTS_CHANGED_EVENT: &str = "fonts:changed";
+
+const CACHE_FILENAME: &str = "system-fonts.json";
+/// The app's own fonts folder, under app data
+pub const MANAGED_DIR_NAME: &str = "fonts";
+const FONT_EXTENSIONS: [&str; 4] = ["ttf", "otf", "ttc", "otc"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemFontInfo {
+    pub family: String,
+    pub full_name: String,
+    pub postscript_name: Option<String>,
+    pub path: String,
+    /// Position in a font collection (`.ttc`); 0 for single fonts
+    pub font_index: u32,
+    pub weight: u16,
+    pub style: String,
+    /// Variation axes of variable fonts, without their names; see
+    /// `get_font_details`
+    pub axes: Vec<FontAxis>,
+    /// In the app's own fonts folders (downloads and session fonts) rather
+    /// than installed
+    #[serde(default)]
+    pub downloaded: bool,
+}
+
+/// The font folders' modification times, in Unix milliseconds
+type FolderTimes = Vec<(String, u64)>;
+
+#[derive(Serialize, Deserialize)]
+struct CachedFonts {
+    folders: FolderTimes,
+    fonts: Vec<SystemFontInfo>,
+}
+
+/// Managed state: the last list read or made
+#[derive(Default)]
+pub struct SystemFonts {
+    cached: Mutex<Option<CachedFonts>>,
+    refreshing: AtomicBool,
+}
+
+impl SystemFonts {
+    /// The installed fonts, by family and name; stale while a refresh runs
+    pub fn list(&self, app: &AppHandle) -> Result<Vec<SystemFontInfo>, CpresError> {
+        let folders = folder_times(app);
+        let stale = {
+            let mut cached = self.lock()?;
+            if cached.is_none() {
+                *cached = read_cache(app);
+            }
+            match cached.as_ref() {
+                Some(cached) if cached.folders == folders => return Ok(cached.fonts.clone()),
+                Some(cached) => Some(cached.fonts.clone()),
+                None => None,
+            }
+        };
+        match stale {
+            Some(fonts) => {
+                self.refresh_in_background(app);
+                Ok(fonts)
+            }
+            None => self.refresh(app),
+        }
+    }
+
+    /// List the installed fonts again and cache the list
+    pub fn refresh(&self, app: &AppHandle) -> Result<Vec<SystemFontInfo>, CpresError> {
+        let folders = folder_times(app);
+        let fonts = scan(&managed_dirs(app))?;
+        let cached = CachedFonts {
+            folders,
+            fonts: fonts.clone(),
+        };
+        if let Err(e) = write_cache(app, &cached) {
+            log::warn!("Could not save the font list: {e}");
+        }
+        *self.lock()? = Some(cached);
+        let _ = app.emit(FONTS_CHANGED_EVENT, ());
+        Ok(fonts)
+    }
+
+    /// Start listing the fonts again without waiting for it; the new list
+    /// is announced with `fonts:changed`
+    pub fn refresh_in_background(&self, app: &AppHandle) {
+        if self.refreshing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let handle = app.clone();
+        std::thread::spawn(move || {
+            let fonts = handle.state::<SystemFonts>();
+            if let Err(e) = fonts.refresh(&handle) {
+                log::warn!("Could not list the installed fonts: {e}");
+            }
+            fonts.refreshing.store(false, Ordering::SeqCst);
+        });
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Option<CachedFonts>>, CpresError> {
+        self.cached
+            .lock()
+            .map_err(|e| CpresError::InvalidBundle(e.to_string()))
+    }
+}
+
+/// The app's own fonts folder
+pub fn managed_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(MANAGED_DIR_NAME)
+}
+
+/// The app's fonts folder and the session fonts folder
+fn managed_dirs(app: &AppHandle) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Ok(app_data_dir) = app.path().app_data_dir() {
+        dirs.push(managed_dir(&app_data_dir));
+    }
+    if let Ok(app_cache_dir) = app.path().app_cache_dir() {
+        dirs.push(font_install::session_dir(&app_cache_dir));
+    }
+    dirs
+}
+
+/// Load every installed font, and every font in the `managed` folders;
+/// files that won't load are left out
+fn scan(managed: &[PathBuf]) -> Result<Vec<SystemFontInfo>, CpresError> {
+    let handles = SystemSource::new()
+        .all_fonts()
+        .map_err(|e| CpresError::InvalidBundle(format!("Failed to list fonts: {e}")))?;
+
+    let mut fonts = Vec::new();
+    for handle in handles {
+        if let Handle::Path { path, font_index } = &handle {
+            fonts.extend(load_info(path, *font_index, false));
+        }
+    }
+    for dir in managed {
+        for path in managed_files(dir) {
+            // Session fonts on Windows are registered with the system too
+            let listed = path.to_string_lossy();
+            if fonts.iter().any(|font| font.path == listed) {
+                continue;
+            }
+            fonts.extend(load_info(&path, 0, true));
+        }
+    }
+
+    fonts.sort_by(|a, b| {
+        a.family
+            .cmp(&b.family)
+            .then_with(|| a.full_name.cmp(&b.full_name))
+    });
+    Ok(fonts)
+}
+
+/// One font of a file, described for the font list
+pub fn load_info(path: &Path, font_index: u32, downloaded: bool) -> Option<SystemFontInfo> {
+    let font = Handle::Path {
+        path: path.to_path_buf(),
+        font_index,
+    }
+    .load()
+    .ok()?;
+    let properties = font.properties();
+    let style = match properties.style {
+        Style::Italic | Style::Oblique => "italic",
+        _ => "normal",
+    };
+    Some(SystemFontInfo {
+        family: font.family_name(),
+        full_name: font.full_name(),
+        postscript_name: font.postscript_name(),
+        path: path.to_string_lossy().to_string(),
+        font_index,
+        weight: properties.weight.0 as u16,
+        style: style.to_string(),
+        axes: font
+            .load_font_table(u32::from_be_bytes(*b"fvar"))
+            .map(|fvar| font_details::axes(&fvar))
+            .unwrap_or_default(),
+        downloaded,
+    })
+}
+
+/// Font files in the app's fonts folder and the folders in it
+fn managed_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            files.extend(managed_files(&path));
+        } else if path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| FONT_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+        {
+            files.push(path);
+        }
+    }
+    files
+}
+
+/// Where this OS keeps installed fonts, system-wide and per user
+fn font_folders() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let mut folders = Vec::new();
+    if cfg!(target_os = "windows") {
+        let windows =
+            std::env::var_os("WINDIR").map_or(PathBuf::from(r"C:\Windows"), PathBuf::from);
+        folders.push(windows.join("Fonts"));
+        if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+            folders.push(PathBuf::from(local).join(r"Microsoft\Windows\Fonts"));
+        }
+    } else if cfg!(target_os = "macos") {
+        folders.push(PathBuf::from("/System/Library/Fonts"));
+        folders.push(PathBuf::from("/Library/Fonts"));
+        folders.extend(home.map(|home| home.join("Library/Fonts")));
+    } else {
+        folders.push(PathBuf::from("/usr/share/fonts"));
+        folders.push(PathBuf::from("/usr/local/share/fonts"));
+        if let Some(home) = home {
+            folders.push(home.join(".local/share/fonts"));
+            folders.push(home.join(".fonts"));
+        }
+    }
+    folders
+}
+
+/// Modification times of the font folders, the app's included, and the
+/// folders in them; adding or removing a file only changes the time of the
+/// folder it's in
+fn folder_times(app: &AppHandle) -> FolderTimes {
+    let mut times = Vec::new();
+    for folder in font_folders().into_iter().chain(managed_dirs(app)) {
+        collect_times(&folder, 0, &mut times);
+    }
+    times
+}
+
+fn collect_times(folder: &Path, depth: usize, times: &mut FolderTimes) {
+    let Ok(metadata) = std::fs::metadata(folder) else {
+        return;
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_millis() as u64);
+    times.push((folder.to_string_lossy().to_string(), modified));
+    if depth >= 4 {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return;
+    };
+    let mut folders: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .map(|entry| entry.path())
+        .collect();
+    folders.sort();
+    for child in folders {
+        collect_times(&child, depth + 1, times);
+    }
+}
+
+fn cache_path(app: &AppHandle) -> Option<PathBuf> {
+    Some(app.path().app_data_dir().ok()?.join(CACHE_FILENAME))
+}
+
+/// The list saved last run; a missing or unreadable file is no list
+fn read_cache(app: &AppHandle) -> Option<CachedFonts> {
+    let data = std::fs::read(cache_path(app)?).ok()?;
+    serde_json::from_slice(&data)
+        .inspect_err(|e| log::info!("Ignoring the saved font list: {e}"))
+        .ok()
+}
+
+fn write_cache(app: &AppHandle, cached: &CachedFonts) -> Result<(), CpresError> {
+    let path =
+        cache_path(app).ok_or_else(|| CpresError::MissingFile("app data directory".to_string()))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_vec(cached)?)?;
+    Ok(())
+}
+
+/// Start with the saved list, refreshing it in the background if the fonts
+/// changed since, so the font picker opens without waiting
+pub fn init(app: &AppHandle) {
+    app.manage(SystemFonts::default());
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        if let Err(e) = handle.state::<SystemFonts>().list(&handle) {
+            log::warn!("Could not list the installed fonts: {e}");
+        }
+    });
+}
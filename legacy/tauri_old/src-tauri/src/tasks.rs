@@ -0,0 +1,113 @@
+//! Cancellation for long-running commands
+//!
+//! The frontend picks a task id, passes it to a command such as `cpres_save`,
+//! and can call `cancel_task` with the same id while the command runs. The
+//! command polls its `CancelToken` between chunks of work and stops with
+//! `CpresError::Cancelled`, leaving any file it was replacing untouched.
+
+use crate::cpres::CpresError;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+
+/// Shared flag a running task polls; the default token is never cancelled
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Option<Arc<AtomicBool>>);
+
+impl CancelToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.0
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    /// `Err(Cancelled)` once the task has been cancelled
+    pub fn check(&self) -> Result<(), CpresError> {
+        if self.is_cancelled() {
+            Err(CpresError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Managed state: tokens of the tasks that are running
+#[derive(Default)]
+pub struct TaskRegistry {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl TaskRegistry {
+    /// Register a task under `task_id`; it stays cancellable until the guard is dropped.
+    /// Without an id the task can't be cancelled.
+    pub fn start(&self, task_id: Option<String>) -> TaskGuard<'_> {
+        let Some(task_id) = task_id else {
+            return TaskGuard {
+                registry: self,
+                task_id: None,
+                token: CancelToken::default(),
+            };
+        };
+        let flag = Arc::new(AtomicBool::new(false));
+        if let Ok(mut running) = self.running.lock() {
+            running.insert(task_id.clone(), flag.clone());
+        }
+        TaskGuard {
+            registry: self,
+            task_id: Some(task_id),
+            token: CancelToken(Some(flag)),
+        }
+    }
+
+    /// Ask a running task to stop; false when no task has that id
+    pub fn cancel(&self, task_id: &str) -> bool {
+        let running = match self.running.lock() {
+            Ok(running) => running,
+            Err(_) => return false,
+        };
+        match running.get(task_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A registered task; unregisters it when dropped
+pub struct TaskGuard<'a> {
+    registry: &'a TaskRegistry,
+    task_id: Option<String>,
+    token: CancelToken,
+}
+
+impl TaskGuard<'_> {
+    pub fn token(&self) -> &CancelToken {
+        &self.token
+    }
+}
+
+impl Drop for TaskGuard<'_> {
+    fn drop(&mut self) {
+        let Some(task_id) = &self.task_id else {
+            return;
+        };
+        if let Ok(mut running) = self.registry.running.lock() {
+            // A newer task may have reused the id
+            if running.get(task_id).is_some_and(|flag| {
+                self.token
+                    .0
+                    .as_ref()
+                    .is_some_and(|own| Arc::ptr_eq(flag, own))
+            }) {
+                running.remove(task_id);
+            }
+        }
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(TaskRegistry::default());
+}
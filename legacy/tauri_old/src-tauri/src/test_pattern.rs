@@ -0,0 +1,402 @@
+//! Test patterns and output identification, for checking the routing
+//!
+//! Before a service the tech needs to know which projector is fed by which
+//! output, and that each shows the whole picture at its native size. These
+//! images are drawn here at each output window's size in physical pixels, so
+//! they reach the screen one to one, and are sent to the windows as PNG data
+//! URLs over `output:overlay`; the windows show them above the slides until
+//! the overlay is cleared.
+
+use crate::cpres::CpresError;
+use crate::font_coverage;
+use crate::render;
+use ab_glyph::{point, Font, FontVec, GlyphId, ScaleFont};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tiny_skia::{Color, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+
+pub const OUTPUT_OVERLAY_EVENT: &str = "output:overlay";
+
+/// Family tried first for the labels; any sans-serif does otherwise
+const LABEL_FONT: &str = "Inter";
+
+/// 75% bars, left to right: white, yellow, cyan, green, magenta, red, blue
+const BARS: [[u8; 3]; 7] = [
+    [191, 191, 191],
+    [191, 191, 0],
+    [0, 191, 191],
+    [0, 191, 0],
+    [191, 0, 191],
+    [191, 0, 0],
+    [0, 0, 191],
+];
+/// The reverse blue bars under them, for setting hue and saturation with a
+/// monitor in blue-only mode
+const REVERSE_BARS: [[u8; 3]; 7] = [
+    [0, 0, 191],
+    [19, 19, 19],
+    [191, 0, 191],
+    [19, 19, 19],
+    [0, 191, 191],
+    [19, 19, 19],
+    [191, 191, 191],
+];
+/// Steps in the gray ramp under the bars
+const RAMP_STEPS: u32 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TestPattern {
+    /// A square grid with diagonals and a circle, to check scaling, geometry,
+    /// and that no edge is cropped
+    Grid,
+    /// 75% color bars over a gray ramp, to check color and black levels
+    ColorBars,
+    /// Action- and title-safe frames and the 4:3 area, to check overscan
+    SafeAreas,
+}
+
+/// Payload of `output:overlay`; without an image the overlay is cleared
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputOverlay {
+    /// A PNG data URL
+    pub image: Option<String>,
+}
+
+/// `pattern` at `width` × `height`, captioned with `label`
+pub fn draw_pattern(
+    pattern: TestPattern,
+    width: u32,
+    height: u32,
+    label: &str,
+) -> Result<Pixmap, CpresError> {
+    let mut pixmap = new_pixmap(width, height)?;
+    let (w, h) = (width as f32, height as f32);
+    let font = label_font();
+    match pattern {
+        TestPattern::Grid => {
+            pixmap.fill(Color::BLACK);
+            draw_grid(&mut pixmap, [110, 110, 110]);
+            line(&mut pixmap, (0.0, 0.0), (w, h), [70, 70, 70], 1.0);
+            line(&mut pixmap, (w, 0.0), (0.0, h), [70, 70, 70], 1.0);
+            if let Some(circle) = PathBuilder::from_circle(w / 2.0, h / 2.0, h * 0.45) {
+                stroke(&mut pixmap, &circle, [255, 255, 255], 2.0);
+            }
+            draw_center_cross(&mut pixmap);
+            frame(&mut pixmap, 0.0, [255, 255, 255], 1.0);
+            frame(&mut pixmap, 1.0, [255, 0, 0], 1.0);
+        }
+        TestPattern::ColorBars => {
+            let bars_bottom = (h * 2.0 / 3.0).round();
+            let reverse_bottom = (h * 0.75).round();
+            let bar_width = w / BARS.len() as f32;
+            for (i, (bar, reverse)) in BARS.iter().zip(REVERSE_BARS).enumerate() {
+                let left = (i as f32 * bar_width).round();
+                let right = ((i + 1) as f32 * bar_width).round();
+                fill(&mut pixmap, left, 0.0, right, bars_bottom, *bar);
+                fill(
+                    &mut pixmap,
+                    left,
+                    bars_bottom,
+                    right,
+                    reverse_bottom,
+                    reverse,
+                );
+            }
+            let step_width = w / RAMP_STEPS as f32;
+            for step in 0..RAMP_STEPS {
+                let level = (step * 255 / (RAMP_STEPS - 1)) as u8;
+                let left = (step as f32 * step_width).round();
+                let right = ((step + 1) as f32 * step_width).round();
+                fill(
+                    &mut pixmap,
+                    left,
+                    reverse_bottom,
+                    right,
+                    h,
+                    [level, level, level],
+                );
+            }
+        }
+        TestPattern::SafeAreas => {
+            pixmap.fill(Color::BLACK);
+            draw_grid(&mut pixmap, [45, 45, 45]);
+            // The middle 4:3 of a widescreen picture, as a 4:3 feed crops it
+            let side = (w - h * 4.0 / 3.0) / 2.0;
+            if side > 0.0 {
+                line(&mut pixmap, (side, 0.0), (side, h), [60, 110, 255], 2.0);
+                line(
+                    &mut pixmap,
+                    (w - side, 0.0),
+                    (w - side, h),
+                    [60, 110, 255],
+                    2.0,
+                );
+            }
+            // SMPTE ST 2046-1: action safe is 93% of the picture, title safe 90%
+            let action = frame_at(&mut pixmap, 0.93, [0, 220, 90]);
+            let title = frame_at(&mut pixmap, 0.90, [255, 210, 0]);
+            draw_center_cross(&mut pixmap);
+            frame(&mut pixmap, 0.0, [255, 255, 255], 1.0);
+            if let Some(font) = &font {
+                let size = h / 40.0;
+                draw_text(
+                    &mut pixmap,
+                    font,
+                    "Action safe",
+                    size,
+                    action.left() + size * 4.0,
+                    action.top() - size * 0.4,
+                    [0, 220, 90],
+                );
+                draw_text(
+                    &mut pixmap,
+                    font,
+                    "Title safe",
+                    size,
+                    title.left() + size * 4.0,
+                    title.top() + size * 1.3,
+                    [255, 210, 0],
+                );
+            }
+        }
+    }
+
+    if let Some(font) = &font {
+        let size = h / 18.0;
+        let label_width = text_width(font, label, size);
+        let baseline = match pattern {
+            TestPattern::ColorBars => h * 2.0 / 3.0 - size * 1.2,
+            _ => h / 2.0 + size * 2.4,
+        };
+        fill(
+            &mut pixmap,
+            w / 2.0 - label_width / 2.0 - size * 0.5,
+            baseline - size * 1.1,
+            w / 2.0 + label_width / 2.0 + size * 0.5,
+            baseline + size * 0.45,
+            [0, 0, 0],
+        );
+        draw_text(
+            &mut pixmap,
+            font,
+            label,
+            size,
+            w / 2.0,
+            baseline,
+            [255, 255, 255],
+        );
+    } else {
+        log::warn!("No font for the test pattern's label");
+    }
+    Ok(pixmap)
+}
+
+/// A big `title` over `subtitle`, to tell outputs apart across the room
+pub fn draw_identify(
+    width: u32,
+    height: u32,
+    title: &str,
+    subtitle: &str,
+) -> Result<Pixmap, CpresError> {
+    let mut pixmap = new_pixmap(width, height)?;
+    let (w, h) = (width as f32, height as f32);
+    pixmap.fill(Color::from_rgba8(16, 32, 64, 255));
+    let border = (h / 60.0).max(2.0);
+    frame(&mut pixmap, 0.0, [255, 255, 255], border);
+
+    let Some(font) = label_font() else {
+        log::warn!("No font to identify the output with");
+        return Ok(pixmap);
+    };
+    // As large as fits, up to a fifth of the height
+    let mut title_size = h / 5.0;
+    let title_width = text_width(&font, title, title_size);
+    if title_width > w * 0.9 {
+        title_size *= w * 0.9 / title_width;
+    }
+    let mut subtitle_size = h / 14.0;
+    let subtitle_width = text_width(&font, subtitle, subtitle_size);
+    if subtitle_width > w * 0.9 {
+        subtitle_size *= w * 0.9 / subtitle_width;
+    }
+    draw_text(
+        &mut pixmap,
+        &font,
+        title,
+        title_size,
+        w / 2.0,
+        h / 2.0,
+        [255, 255, 255],
+    );
+    draw_text(
+        &mut pixmap,
+        &font,
+        subtitle,
+        subtitle_size,
+        w / 2.0,
+        h / 2.0 + subtitle_size * 1.8,
+        [190, 205, 230],
+    );
+    Ok(pixmap)
+}
+
+/// `pixmap` as a PNG data URL, for `OutputOverlay`
+pub fn data_url(pixmap: &Pixmap) -> Result<String, CpresError> {
+    let png = pixmap
+        .encode_png()
+        .map_err(|e| CpresError::InvalidBundle(format!("Could not encode the pattern: {e}")))?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(png)
+    ))
+}
+
+fn new_pixmap(width: u32, height: u32) -> Result<Pixmap, CpresError> {
+    Pixmap::new(width, height)
+        .ok_or_else(|| CpresError::InvalidBundle(format!("Invalid size {width}x{height}")))
+}
+
+fn label_font() -> Option<FontVec> {
+    font_coverage::system_font(LABEL_FONT, 700, false)
+}
+
+/// Square cells a ninth of the height, centered on the picture
+fn draw_grid(pixmap: &mut Pixmap, color: [u8; 3]) {
+    let (w, h) = (pixmap.width() as f32, pixmap.height() as f32);
+    let cell = (h / 9.0).max(4.0);
+    let (cx, cy) = (w / 2.0, h / 2.0);
+    let columns = (cx / cell).ceil() as i32;
+    for i in -columns..=columns {
+        let x = (cx + i as f32 * cell).round() + 0.5;
+        line(pixmap, (x, 0.0), (x, h), color, 1.0);
+    }
+    let rows = (cy / cell).ceil() as i32;
+    for i in -rows..=rows {
+        let y = (cy + i as f32 * cell).round() + 0.5;
+        line(pixmap, (0.0, y), (w, y), color, 1.0);
+    }
+}
+
+fn draw_center_cross(pixmap: &mut Pixmap) {
+    let (w, h) = (pixmap.width() as f32, pixmap.height() as f32);
+    let arm = h / 30.0;
+    let (cx, cy) = (w / 2.0, h / 2.0);
+    line(pixmap, (cx - arm, cy), (cx + arm, cy), [255, 255, 255], 2.0);
+    line(pixmap, (cx, cy - arm), (cx, cy + arm), [255, 255, 255], 2.0);
+}
+
+/// A rectangle `inset` pixels in from the edges
+fn frame(pixmap: &mut Pixmap, inset: f32, color: [u8; 3], width: f32) {
+    let (w, h) = (pixmap.width() as f32, pixmap.height() as f32);
+    let half = width / 2.0;
+    if let Some(rect) = Rect::from_ltrb(
+        inset + half,
+        inset + half,
+        w - inset - half,
+        h - inset - half,
+    ) {
+        stroke(pixmap, &PathBuilder::from_rect(rect), color, width);
+    }
+}
+
+/// A rectangle of `share` of the picture's width and height, centered;
+/// returns where it is
+fn frame_at(pixmap: &mut Pixmap, share: f32, color: [u8; 3]) -> Rect {
+    let (w, h) = (pixmap.width() as f32, pixmap.height() as f32);
+    let (dx, dy) = (w * (1.0 - share) / 2.0, h * (1.0 - share) / 2.0);
+    let rect = Rect::from_ltrb(dx, dy, w - dx, h - dy).expect("share is under 1");
+    stroke(pixmap, &PathBuilder::from_rect(rect), color, 2.0);
+    rect
+}
+
+fn fill(pixmap: &mut Pixmap, left: f32, top: f32, right: f32, bottom: f32, color: [u8; 3]) {
+    if let Some(rect) = Rect::from_ltrb(left, top, right, bottom) {
+        pixmap.fill_rect(rect, &paint(color), Transform::identity(), None);
+    }
+}
+
+fn line(pixmap: &mut Pixmap, from: (f32, f32), to: (f32, f32), color: [u8; 3], width: f32) {
+    let mut path = PathBuilder::new();
+    path.move_to(from.0, from.1);
+    path.line_to(to.0, to.1);
+    if let Some(path) = path.finish() {
+        stroke(pixmap, &path, color, width);
+    }
+}
+
+fn stroke(pixmap: &mut Pixmap, path: &tiny_skia::Path, color: [u8; 3], width: f32) {
+    let stroke = Stroke {
+        width,
+        ..Stroke::default()
+    };
+    pixmap.stroke_path(path, &paint(color), &stroke, Transform::identity(), None);
+}
+
+fn paint(color: [u8; 3]) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(color[0], color[1], color[2], 255);
+    paint.anti_alias = true;
+    paint
+}
+
+/// Glyphs of `text` on one line from x = 0, and the line's width
+fn lay_out(font: &FontVec, text: &str, size: f32) -> (Vec<(GlyphId, f32)>, f32) {
+    let scaled = font.as_scaled(render::px_scale(font, size));
+    let mut glyphs = Vec::new();
+    let mut x = 0.0;
+    let mut previous: Option<GlyphId> = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            x += scaled.kern(previous, id);
+        }
+        glyphs.push((id, x));
+        x += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    (glyphs, x)
+}
+
+fn text_width(font: &FontVec, text: &str, size: f32) -> f32 {
+    lay_out(font, text, size).1
+}
+
+/// `text` at `size` pixels per em, centered on `center_x` with its baseline
+/// at `baseline`
+fn draw_text(
+    pixmap: &mut Pixmap,
+    font: &FontVec,
+    text: &str,
+    size: f32,
+    center_x: f32,
+    baseline: f32,
+    color: [u8; 3],
+) {
+    let scale = render::px_scale(font, size);
+    let (glyphs, width) = lay_out(font, text, size);
+    let left = center_x - width / 2.0;
+    let (w, h) = (pixmap.width() as i64, pixmap.height() as i64);
+    let data = pixmap.data_mut();
+    for (id, offset) in glyphs {
+        let glyph = id.with_scale_and_position(scale, point(left + offset, baseline));
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + gx as i64;
+            let py = bounds.min.y as i64 + gy as i64;
+            if px < 0 || py < 0 || px >= w || py >= h {
+                return;
+            }
+            // Opaque color over what's there, premultiplied as tiny-skia keeps it
+            let at = ((py * w + px) * 4) as usize;
+            let coverage = coverage.clamp(0.0, 1.0);
+            for (channel, value) in data[at..at + 3].iter_mut().zip(color) {
+                *channel = (value as f32 * coverage + *channel as f32 * (1.0 - coverage)) as u8;
+            }
+            data[at + 3] = (255.0 * coverage + data[at + 3] as f32 * (1.0 - coverage)) as u8;
+        });
+    }
+}
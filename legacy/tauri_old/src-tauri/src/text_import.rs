@@ -0,0 +1,513 @@
+//! Plain text and Markdown lyrics import
+//!
+//! Lyrics are split into stanzas at blank lines. A stanza becomes one slide,
+//! or several when `lines_per_slide` is set. Sections start at header lines:
+//! - "Verse 1", "Chorus:" and similar lines (see `importer::section_header`)
+//! - `[Bridge]`, or `[V1]`-style tags
+//! - Markdown headings below the title (`## Chorus`)
+//!
+//! A header with no lyrics under it repeats the earlier section of that name.
+//! Without headers, automatic labels name stanzas "Verse 1", "Verse 2", ...
+//! and a stanza that comes back word for word is labeled "Chorus" and
+//! repeated in the arrangement.
+//!
+//! A Markdown `# Heading` before the lyrics is the title; `Title:` and
+//! `Author:` lines (or YAML front matter) before the lyrics set metadata.
+
+use crate::cpres::{BundleState, CpresError};
+use crate::importer::{self, LyricSection};
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TextImportOptions {
+    /// Split stanzas into slides of at most this many lines
+    pub lines_per_slide: Option<usize>,
+    /// Label stanzas without a header as verses and repeated stanzas as the chorus
+    pub auto_labels: bool,
+}
+
+impl Default for TextImportOptions {
+    fn default() -> Self {
+        Self {
+            lines_per_slide: None,
+            auto_labels: true,
+        }
+    }
+}
+
+/// A section as written in the file, before labels are settled
+struct Section {
+    label: Option<String>,
+    stanzas: Vec<Vec<String>>,
+}
+
+/// Convert a lyrics text or Markdown file into an unsaved bundle
+pub fn import_text(path: &Path, options: &TextImportOptions) -> Result<BundleState, CpresError> {
+    let data = std::fs::read(path)?;
+    let text = String::from_utf8_lossy(&data);
+    let markdown = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| matches!(e.to_lowercase().as_str(), "md" | "markdown"));
+    let fallback_title = path
+        .file_stem()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let mut title = None;
+    let mut author = None;
+    let mut sections: Vec<Section> = Vec::new();
+    // Section index of every occurrence, in singing order
+    let mut order: Vec<usize> = Vec::new();
+    let mut pending_header: Option<String> = None;
+    let mut stanza: Vec<String> = Vec::new();
+    let mut started = false;
+    let mut in_front_matter = false;
+
+    let lines: Vec<&str> = text.trim_start_matches('\u{feff}').lines().collect();
+    for (i, raw) in lines.iter().enumerate() {
+        let line = raw.trim();
+
+        if markdown && line == "---" && (i == 0 || in_front_matter) {
+            in_front_matter = i == 0;
+            continue;
+        }
+        if in_front_matter || !started {
+            if let Some((key, value)) = metadata(line) {
+                match key.as_str() {
+                    "title" => title = Some(value),
+                    "author" | "authors" | "by" => author = Some(value),
+                    _ => {}
+                }
+                continue;
+            }
+            if in_front_matter {
+                continue;
+            }
+        }
+
+        if line.is_empty() || (markdown && is_rule(line)) {
+            finish_stanza(&mut stanza, &mut sections, &mut order, &mut pending_header);
+            continue;
+        }
+        if markdown && line.starts_with("<!--") {
+            continue;
+        }
+
+        let heading = markdown.then(|| markdown_heading(line)).flatten();
+        if let Some((1, text)) = &heading {
+            if !started {
+                title.get_or_insert_with(|| text.clone());
+                continue;
+            }
+        }
+        let header = heading
+            .map(|(_, text)| text)
+            .or_else(|| bracketed_header(line))
+            .or_else(|| importer::section_header(&clean_line(line, markdown)));
+        if let Some(header) = header {
+            finish_stanza(&mut stanza, &mut sections, &mut order, &mut pending_header);
+            if let Some(previous) = pending_header.take() {
+                repeat_section(&previous, &sections, &mut order);
+            }
+            pending_header = Some(header);
+            started = true;
+            continue;
+        }
+
+        let cleaned = clean_line(line, markdown);
+        if !cleaned.is_empty() {
+            stanza.push(cleaned);
+            started = true;
+        }
+    }
+    finish_stanza(&mut stanza, &mut sections, &mut order, &mut pending_header);
+    if let Some(previous) = pending_header.take() {
+        repeat_section(&previous, &sections, &mut order);
+    }
+
+    if sections.is_empty() {
+        return Err(CpresError::InvalidBundle(
+            "No lyrics found in file".to_string(),
+        ));
+    }
+
+    if options.auto_labels {
+        merge_repeats(&mut sections, &mut order);
+    }
+    let labels = settle_labels(&sections, &order, options.auto_labels);
+    let lyric_sections = sections
+        .into_iter()
+        .zip(labels)
+        .map(|(section, label)| LyricSection {
+            label,
+            slides: section
+                .stanzas
+                .iter()
+                .flat_map(|stanza| split_lines(stanza, options.lines_per_slide))
+                .collect(),
+        })
+        .collect();
+
+    let presentation = importer::song_presentation(
+        title.filter(|t| !t.is_empty()).unwrap_or(fallback_title),
+        author.filter(|a| !a.is_empty()),
+        lyric_sections,
+        Some(order),
+    );
+    importer::to_bundle_state(presentation)
+}
+
+/// Close the current stanza into the open section, or into a new one
+fn finish_stanza(
+    stanza: &mut Vec<String>,
+    sections: &mut Vec<Section>,
+    order: &mut Vec<usize>,
+    pending_header: &mut Option<String>,
+) {
+    if stanza.is_empty() {
+        return;
+    }
+    let lines = std::mem::take(stanza);
+
+    match pending_header.take() {
+        Some(label) => {
+            // The same section written out again in full is a repeat
+            let existing = sections.iter().position(|section| {
+                section
+                    .label
+                    .as_deref()
+                    .is_some_and(|l| l.eq_ignore_ascii_case(&label))
+                    && section.stanzas.first() == Some(&lines)
+            });
+            match existing {
+                Some(index) => order.push(index),
+                None => {
+                    sections.push(Section {
+                        label: Some(label),
+                        stanzas: vec![lines],
+                    });
+                    order.push(sections.len() - 1);
+                }
+            }
+        }
+        None => {
+            // Further stanzas under a header belong to its section
+            let continues = order
+                .last()
+                .filter(|&&last| last == sections.len().wrapping_sub(1))
+                .is_some_and(|&last| sections[last].label.is_some());
+            if continues {
+                if let Some(section) = sections.last_mut() {
+                    section.stanzas.push(lines);
+                }
+                return;
+            }
+            sections.push(Section {
+                label: None,
+                stanzas: vec![lines],
+            });
+            order.push(sections.len() - 1);
+        }
+    }
+}
+
+/// A header with nothing under it sings an earlier section again
+fn repeat_section(label: &str, sections: &[Section], order: &mut Vec<usize>) {
+    let index = sections.iter().rposition(|section| {
+        section
+            .label
+            .as_deref()
+            .is_some_and(|l| l.eq_ignore_ascii_case(label))
+    });
+    match index {
+        Some(index) => order.push(index),
+        None => log::warn!("Section \"{label}\" has no lyrics and no earlier section to repeat"),
+    }
+}
+
+/// Fold unlabeled stanzas that recur word for word into their first copy
+fn merge_repeats(sections: &mut Vec<Section>, order: &mut [usize]) {
+    // New index of every section, after repeats are dropped
+    let mut remap = Vec::with_capacity(sections.len());
+    let mut kept = 0;
+    for index in 0..sections.len() {
+        let earlier = (sections[index].label.is_none())
+            .then(|| {
+                sections[..index].iter().position(|other| {
+                    other.label.is_none() && other.stanzas == sections[index].stanzas
+                })
+            })
+            .flatten();
+        match earlier {
+            Some(earlier) => remap.push(remap[earlier]),
+            None => {
+                remap.push(kept);
+                kept += 1;
+            }
+        }
+    }
+
+    let mut next = 0;
+    sections.retain(|_| {
+        let keep = remap[..next].iter().all(|&i| i != remap[next]);
+        next += 1;
+        keep
+    });
+    for index in order.iter_mut() {
+        *index = remap[*index];
+    }
+}
+
+/// Final label of each section. With automatic labels, unlabeled sections
+/// sung more than once become choruses and the rest are numbered verses.
+fn settle_labels(sections: &[Section], order: &[usize], auto_labels: bool) -> Vec<String> {
+    let mut verses = 0;
+    let mut choruses = 0;
+    sections
+        .iter()
+        .enumerate()
+        .map(|(index, section)| {
+            if let Some(label) = &section.label {
+                return importer::label_for_tag(label);
+            }
+            if !auto_labels {
+                return String::new();
+            }
+            if order.iter().filter(|&&i| i == index).count() > 1 {
+                choruses += 1;
+                if choruses == 1 {
+                    "Chorus".to_string()
+                } else {
+                    format!("Chorus {choruses}")
+                }
+            } else {
+                verses += 1;
+                format!("Verse {verses}")
+            }
+        })
+        .collect()
+}
+
+/// Slides for one stanza, at most `lines_per_slide` lines each
+fn split_lines(stanza: &[String], lines_per_slide: Option<usize>) -> Vec<String> {
+    match lines_per_slide.filter(|&n| n > 0) {
+        Some(n) => stanza.chunks(n).map(|chunk| chunk.join("\n")).collect(),
+        None => vec![stanza.join("\n")],
+    }
+}
+
+/// `Key: value` metadata line
+fn metadata(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim().trim_matches('*').trim().to_lowercase();
+    matches!(
+        key.as_str(),
+        "title" | "author" | "authors" | "by" | "ccli" | "key" | "tempo"
+    )
+    .then(|| {
+        // `**Author:** Name` leaves the closing emphasis on the value
+        let value = value.trim().trim_start_matches('*').trim();
+        (key, value.trim_matches('"').to_string())
+    })
+}
+
+/// Level and text of a Markdown ATX heading
+fn markdown_heading(line: &str) -> Option<(usize, String)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    let text = text.trim().trim_end_matches('#').trim().to_string();
+    ((1..=6).contains(&level) && !text.is_empty()).then_some((level, text))
+}
+
+/// `[Chorus]`, `[V1]`, or a section name in parentheses, like `(Chorus)`
+fn bracketed_header(line: &str) -> Option<String> {
+    if let Some(inner) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+        return Some(inner.trim().to_string()).filter(|inner| !inner.is_empty());
+    }
+    let inner = line
+        .strip_prefix('(')
+        .and_then(|l| l.strip_suffix(')'))
+        .or_else(|| line.strip_prefix("**").and_then(|l| l.strip_suffix("**")))?;
+    importer::section_header(inner)
+}
+
+/// Horizontal rule: three or more `-`, `*` or `_`
+fn is_rule(line: &str) -> bool {
+    let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    compact.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&mark| compact.chars().all(|c| c == mark))
+}
+
+/// Lyric text without Markdown quote, list and emphasis markup
+fn clean_line(line: &str, markdown: bool) -> String {
+    if !markdown {
+        return line.to_string();
+    }
+    let mut line = line.trim_start_matches('>').trim_start();
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(bullet) {
+            line = rest;
+            break;
+        }
+    }
+    line.replace("**", "")
+        .replace("__", "")
+        .replace('`', "")
+        .trim_end_matches('\\')
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stanza(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    fn section(label: Option<&str>, lines: &[&str]) -> Section {
+        Section {
+            label: label.map(str::to_string),
+            stanzas: vec![stanza(lines)],
+        }
+    }
+
+    #[test]
+    fn metadata_reads_known_keys() {
+        let cases = [
+            ("Title: Amazing Grace", Some(("title", "Amazing Grace"))),
+            ("**Author:** John Newton", Some(("author", "John Newton"))),
+            ("title: \"Quoted\"", Some(("title", "Quoted"))),
+            ("CCLI: 22025", Some(("ccli", "22025"))),
+            ("Verse 1:", None),
+            ("Lord: you are good", None),
+            ("No colon here", None),
+        ];
+        for (line, expected) in cases {
+            let expected = expected.map(|(k, v)| (k.to_string(), v.to_string()));
+            assert_eq!(metadata(line), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn markdown_heading_levels() {
+        let cases = [
+            ("# Title", Some((1, "Title"))),
+            ("## Chorus ##", Some((2, "Chorus"))),
+            ("###### Deep", Some((6, "Deep"))),
+            ("####### Too deep", None),
+            ("#NoSpace", None),
+            ("# ", None),
+            ("Plain line", None),
+        ];
+        for (line, expected) in cases {
+            let expected = expected.map(|(level, text)| (level, text.to_string()));
+            assert_eq!(markdown_heading(line), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn bracketed_header_forms() {
+        let cases = [
+            ("[Chorus]", Some("Chorus")),
+            ("[ V1 ]", Some("V1")),
+            ("[]", None),
+            ("(Chorus)", Some("Chorus")),
+            ("**Verse 2**", Some("Verse 2")),
+            ("(softly now)", None),
+            ("Chorus", None),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(
+                bracketed_header(line),
+                expected.map(str::to_string),
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rules_and_markup() {
+        let rules = [
+            ("---", true),
+            ("* * *", true),
+            ("___", true),
+            ("--", false),
+            ("-*-", false),
+            ("- item", false),
+        ];
+        for (line, expected) in rules {
+            assert_eq!(is_rule(line), expected, "{line:?}");
+        }
+
+        let cleaned = [
+            ("> Amazing grace", true, "Amazing grace"),
+            ("- **How sweet** the `sound`", true, "How sweet the sound"),
+            ("__That saved__ a wretch\\", true, "That saved a wretch"),
+            ("- **kept** as written", false, "- **kept** as written"),
+        ];
+        for (line, markdown, expected) in cleaned {
+            assert_eq!(clean_line(line, markdown), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn split_lines_into_slides() {
+        let lines = stanza(&["a", "b", "c"]);
+        let cases: [(Option<usize>, &[&str]); 4] = [
+            (None, &["a\nb\nc"]),
+            (Some(0), &["a\nb\nc"]),
+            (Some(2), &["a\nb", "c"]),
+            (Some(5), &["a\nb\nc"]),
+        ];
+        for (per_slide, expected) in cases {
+            assert_eq!(split_lines(&lines, per_slide), expected, "{per_slide:?}");
+        }
+    }
+
+    #[test]
+    fn repeats_become_choruses() {
+        let mut sections = vec![
+            section(None, &["verse one"]),
+            section(None, &["chorus"]),
+            section(None, &["verse two"]),
+            section(None, &["chorus"]),
+            section(Some("Bridge"), &["bridge"]),
+        ];
+        let mut order = vec![0, 1, 2, 3, 4];
+        merge_repeats(&mut sections, &mut order);
+        assert_eq!(sections.len(), 4);
+        assert_eq!(order, [0, 1, 2, 1, 3]);
+        assert_eq!(
+            settle_labels(&sections, &order, true),
+            ["Verse 1", "Chorus", "Verse 2", "Bridge"]
+        );
+        assert_eq!(
+            settle_labels(&sections, &order, false),
+            ["", "", "", "Bridge"]
+        );
+    }
+
+    #[test]
+    fn imports_headers_and_repeats() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.md");
+        std::fs::write(
+            &path,
+            "---\ntitle: Front Matter\n---\n# Ignored\n\n## Verse 1\nline one\n\n[Chorus]\nsing\n\n## Chorus\n",
+        )
+        .unwrap();
+        assert!(import_text(&path, &TextImportOptions::default()).is_ok());
+
+        let empty = dir.path().join("empty.txt");
+        std::fs::write(&empty, "Title: Nothing\n\n").unwrap();
+        assert!(import_text(&empty, &TextImportOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,635 @@
+//! Sharing the output as a GPU texture: Spout on Windows, Syphon on macOS
+//!
+//! VJ and projection-mapping software on the same computer (Resolume,
+//! MadMapper, TouchDesigner) can take the slides straight from graphics
+//! memory, without a capture card or the network. Each output can be shared
+//! under its own sender name, at its monitor's resolution unless another is
+//! given. The slides are drawn offscreen (see `output_frames`) and copied
+//! into the shared texture when the slide changes; receivers keep showing
+//! the last frame in between.
+//!
+//! Spout senders are made the way the Spout 2 SDK makes them: a shared
+//! Direct3D 11 texture, announced in the `SpoutSenderNames` shared memory.
+//! Syphon comes as a framework that isn't part of macOS; it's loaded from
+//! the app bundle, `/Library/Frameworks`, or `CHURCH_PRESENTER_SYPHON`.
+
+use crate::cpres::CpresError;
+use crate::output_frames::{self, OutputFrames};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use tauri::{AppHandle, Manager};
+use thiserror::Error;
+
+#[cfg(target_os = "macos")]
+const ENV_OVERRIDE: &str = "CHURCH_PRESENTER_SYPHON";
+
+#[derive(Error, Debug)]
+pub enum TextureShareError {
+    #[error("Texture sharing needs Spout (Windows) or Syphon (macOS)")]
+    Unsupported,
+
+    #[cfg(target_os = "macos")]
+    #[error("Syphon is not installed; put Syphon.framework in /Library/Frameworks")]
+    SyphonNotFound,
+
+    #[error("Texture sharing error: {0}")]
+    Failed(String),
+
+    #[error(transparent)]
+    Render(#[from] CpresError),
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TextureShareOptions {
+    /// Sender name shown in the receiving program
+    pub name: Option<String>,
+    /// The output monitor's size when not given
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedOutput {
+    /// The output's monitor id
+    pub output_id: String,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// "spout" or "syphon"
+    pub technology: &'static str,
+}
+
+/// Managed state: the shared outputs, by output id
+#[derive(Default)]
+pub struct TextureSharing {
+    senders: Mutex<BTreeMap<String, (SharedOutput, platform::Sender)>>,
+    /// The bundle and slide being shown, drawn for outputs shared later;
+    /// None shows black
+    showing: Mutex<Option<(PathBuf, String)>>,
+    frames: Mutex<OutputFrames>,
+}
+
+impl TextureSharing {
+    /// Share the output `output_id` as `name`, replacing an earlier sender
+    /// for it
+    pub fn enable(
+        &self,
+        output_id: &str,
+        name: &str,
+        width: u32,
+        height: u32,
+    ) -> Result<SharedOutput, TextureShareError> {
+        let (width, height) = (width.clamp(16, 7680), height.clamp(16, 4320));
+        let mut senders = self.lock_senders();
+        senders.remove(output_id);
+        if let Some((other, _)) = senders.values().find(|(shared, _)| shared.name == name) {
+            return Err(TextureShareError::Failed(format!(
+                "{name} is already the name of output {}",
+                other.output_id
+            )));
+        }
+        let mut sender = platform::Sender::new(name, width, height)?;
+        let shared = SharedOutput {
+            output_id: output_id.to_string(),
+            name: name.to_string(),
+            width,
+            height,
+            technology: platform::TECHNOLOGY,
+        };
+        let showing = self.showing.lock().ok().and_then(|showing| showing.clone());
+        sender.publish(&self.frame(showing.as_ref(), width, height))?;
+        log::info!(
+            "Sharing output {output_id} as {} sender {name} at {width}x{height}",
+            platform::TECHNOLOGY
+        );
+        senders.insert(output_id.to_string(), (shared.clone(), sender));
+        Ok(shared)
+    }
+
+    /// Stop sharing the output; receivers lose the sender
+    pub fn disable(&self, output_id: &str) {
+        if let Some((shared, _)) = self.lock_senders().remove(output_id) {
+            log::info!("Stopped sharing output {output_id} as {}", shared.name);
+        }
+    }
+
+    pub fn disable_all(&self) {
+        self.lock_senders().clear();
+    }
+
+    pub fn list(&self) -> Vec<SharedOutput> {
+        self.lock_senders()
+            .values()
+            .map(|(shared, _)| shared.clone())
+            .collect()
+    }
+
+    /// Show the slide with `slide_id` from the bundle at `path` on every
+    /// shared output, or black with None
+    pub fn show(&self, slide: Option<(&Path, &str)>) -> Result<(), TextureShareError> {
+        let showing = slide.map(|(path, slide_id)| (path.to_path_buf(), slide_id.to_string()));
+        if let Ok(mut current) = self.showing.lock() {
+            current.clone_from(&showing);
+        }
+        let mut senders = self.lock_senders();
+        // Outputs of the same size share a frame
+        let mut drawn: BTreeMap<(u32, u32), Vec<u8>> = BTreeMap::new();
+        for (shared, sender) in senders.values_mut() {
+            let size = (shared.width, shared.height);
+            let frame = drawn
+                .entry(size)
+                .or_insert_with(|| self.frame(showing.as_ref(), shared.width, shared.height));
+            sender.publish(frame)?;
+        }
+        Ok(())
+    }
+
+    /// The slide at the size, or black when there's none or it can't be drawn
+    fn frame(&self, showing: Option<&(PathBuf, String)>, width: u32, height: u32) -> Vec<u8> {
+        let Some((path, slide_id)) = showing else {
+            return output_frames::black(width, height);
+        };
+        let frame = self
+            .frames
+            .lock()
+            .map_err(|e| CpresError::InvalidBundle(e.to_string()))
+            .and_then(|mut frames| frames.render(path, slide_id, width, height));
+        frame.unwrap_or_else(|e| {
+            log::warn!("Could not draw the slide for texture sharing: {e}");
+            output_frames::black(width, height)
+        })
+    }
+
+    fn lock_senders(&self) -> MutexGuard<'_, BTreeMap<String, (SharedOutput, platform::Sender)>> {
+        self.senders
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(TextureSharing::default());
+}
+
+pub fn shutdown(app: &AppHandle) {
+    if let Some(sharing) = app.try_state::<TextureSharing>() {
+        sharing.disable_all();
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::TextureShareError;
+    use std::ffi::c_void;
+    use windows::core::{Interface, HSTRING};
+    use windows::Win32::Foundation::{CloseHandle, HANDLE, HMODULE, INVALID_HANDLE_VALUE};
+    use windows::Win32::Graphics::Direct3D::D3D_DRIVER_TYPE_HARDWARE;
+    use windows::Win32::Graphics::Direct3D11::{
+        D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D,
+        D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE, D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+        D3D11_RESOURCE_MISC_SHARED, D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT,
+    };
+    use windows::Win32::Graphics::Dxgi::Common::{DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_SAMPLE_DESC};
+    use windows::Win32::Graphics::Dxgi::IDXGIResource;
+    use windows::Win32::System::Memory::{
+        CreateFileMappingW, MapViewOfFile, UnmapViewOfFile, FILE_MAP_ALL_ACCESS,
+        MEMORY_MAPPED_VIEW_ADDRESS, PAGE_READWRITE,
+    };
+    use windows::Win32::System::Threading::{CreateMutexW, ReleaseMutex, WaitForSingleObject};
+
+    pub const TECHNOLOGY: &str = "spout";
+
+    /// Spout's limits on sender names and on how many are listed
+    const NAME_LENGTH: usize = 256;
+    const MAX_SENDERS: usize = 64;
+    const SENDER_NAMES: &str = "SpoutSenderNames";
+    const ACTIVE_SENDER: &str = "ActiveSenderName";
+    /// `SharedTextureInfo`: handle, width, height, format, usage, a
+    /// 256-byte description (the sending program), and a partner id
+    const INFO_SIZE: usize = 4 * 5 + 256 + 4;
+    /// How long to wait for a receiver to let go of the texture
+    const ACCESS_TIMEOUT_MS: u32 = 67;
+
+    /// A Spout sender, used only under the state's lock
+    pub struct Sender {
+        name: String,
+        width: u32,
+        height: u32,
+        context: ID3D11DeviceContext,
+        texture: ID3D11Texture2D,
+        /// Kept open while the sender is, so receivers can find it
+        info: SharedMemory,
+        names: SharedMemory,
+        active: SharedMemory,
+        access: HANDLE,
+        _device: ID3D11Device,
+    }
+
+    unsafe impl Send for Sender {}
+
+    impl Sender {
+        pub fn new(name: &str, width: u32, height: u32) -> Result<Self, TextureShareError> {
+            if name.len() >= NAME_LENGTH || name.contains('\0') {
+                return Err(TextureShareError::Failed(format!(
+                    "The sender name {name} is too long"
+                )));
+            }
+            let mut device = None;
+            let mut context = None;
+            unsafe {
+                D3D11CreateDevice(
+                    None,
+                    D3D_DRIVER_TYPE_HARDWARE,
+                    HMODULE::default(),
+                    D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+                    None,
+                    D3D11_SDK_VERSION,
+                    Some(&mut device),
+                    None,
+                    Some(&mut context),
+                )
+            }
+            .map_err(failed)?;
+            let (Some(device), Some(context)) = (device, context) else {
+                return Err(failed("No Direct3D 11 device"));
+            };
+
+            let description = D3D11_TEXTURE2D_DESC {
+                Width: width,
+                Height: height,
+                MipLevels: 1,
+                ArraySize: 1,
+                Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Usage: D3D11_USAGE_DEFAULT,
+                BindFlags: (D3D11_BIND_SHADER_RESOURCE.0 | D3D11_BIND_RENDER_TARGET.0) as u32,
+                CPUAccessFlags: 0,
+                MiscFlags: D3D11_RESOURCE_MISC_SHARED.0 as u32,
+            };
+            let mut texture = None;
+            unsafe { device.CreateTexture2D(&description, None, Some(&mut texture)) }
+                .map_err(failed)?;
+            let texture = texture.ok_or_else(|| failed("No shared texture"))?;
+            let resource: IDXGIResource = texture.cast().map_err(failed)?;
+            let handle = unsafe { resource.GetSharedHandle() }.map_err(failed)?;
+
+            // Receivers look the texture up by sender name
+            let info = SharedMemory::create(name, INFO_SIZE)?;
+            let mut bytes = Vec::with_capacity(INFO_SIZE);
+            for value in [
+                handle.0 as usize as u32,
+                width,
+                height,
+                DXGI_FORMAT_B8G8R8A8_UNORM.0 as u32,
+                0,
+            ] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            let program = std::env::current_exe()
+                .map(|exe| exe.to_string_lossy().to_string())
+                .unwrap_or_default();
+            bytes.extend(
+                program
+                    .bytes()
+                    .take(255)
+                    .chain(std::iter::repeat(0))
+                    .take(256),
+            );
+            bytes.extend_from_slice(&0u32.to_le_bytes());
+            info.write(&bytes);
+
+            let access = unsafe {
+                CreateMutexW(
+                    None,
+                    false,
+                    &HSTRING::from(format!("{name}_SpoutAccessMutex")),
+                )
+            }
+            .map_err(failed)?;
+            let sender = Self {
+                name: name.to_string(),
+                width,
+                height,
+                context,
+                texture,
+                info,
+                names: SharedMemory::create(SENDER_NAMES, NAME_LENGTH * MAX_SENDERS)?,
+                active: SharedMemory::create(ACTIVE_SENDER, NAME_LENGTH)?,
+                access,
+                _device: device,
+            };
+            sender.register(true)?;
+            Ok(sender)
+        }
+
+        /// Copy an RGBA frame into the shared texture
+        pub fn publish(&mut self, rgba: &[u8]) -> Result<(), TextureShareError> {
+            let mut bgra = rgba.to_vec();
+            for pixel in bgra.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+            let locked = unsafe { WaitForSingleObject(self.access, ACCESS_TIMEOUT_MS) }.0 == 0;
+            unsafe {
+                self.context.UpdateSubresource(
+                    &self.texture,
+                    0,
+                    None,
+                    bgra.as_ptr() as *const c_void,
+                    self.width * 4,
+                    self.width * self.height * 4,
+                );
+                self.context.Flush();
+            }
+            if locked {
+                let _ = unsafe { ReleaseMutex(self.access) };
+            }
+            Ok(())
+        }
+
+        /// Add the name to the senders Spout receivers list, or remove it; the
+        /// list is sorted, as the Spout SDK keeps it
+        fn register(&self, add: bool) -> Result<(), TextureShareError> {
+            let name = self.name.as_str();
+            let lock = unsafe {
+                CreateMutexW(None, false, &HSTRING::from(format!("{SENDER_NAMES}_mutex")))
+            }
+            .map_err(failed)?;
+            let locked = unsafe { WaitForSingleObject(lock, ACCESS_TIMEOUT_MS) }.0 == 0;
+
+            let mut listed: Vec<String> = self
+                .names
+                .read()
+                .chunks(NAME_LENGTH)
+                .map(|slot| {
+                    let end = slot.iter().position(|b| *b == 0).unwrap_or(slot.len());
+                    String::from_utf8_lossy(&slot[..end]).to_string()
+                })
+                .take_while(|listed| !listed.is_empty())
+                .filter(|listed| listed != name)
+                .collect();
+            if add {
+                listed.push(name.to_string());
+            }
+            listed.sort();
+            listed.truncate(MAX_SENDERS);
+            let mut bytes = vec![0; NAME_LENGTH * MAX_SENDERS];
+            for (slot, listed) in bytes.chunks_mut(NAME_LENGTH).zip(&listed) {
+                slot[..listed.len()].copy_from_slice(listed.as_bytes());
+            }
+            self.names.write(&bytes);
+
+            // Receivers with no sender chosen take the active one
+            let mut bytes = vec![0; NAME_LENGTH];
+            if let Some(first) = listed.iter().find(|listed| add || *listed != name) {
+                bytes[..first.len()].copy_from_slice(first.as_bytes());
+            }
+            self.active.write(&bytes);
+
+            if locked {
+                let _ = unsafe { ReleaseMutex(lock) };
+            }
+            let _ = unsafe { CloseHandle(lock) };
+            Ok(())
+        }
+    }
+
+    impl Drop for Sender {
+        fn drop(&mut self) {
+            if let Err(e) = self.register(false) {
+                log::warn!("Could not remove the Spout sender {}: {e}", self.name);
+            }
+            let _ = unsafe { CloseHandle(self.access) };
+        }
+    }
+
+    /// A named shared memory block, mapped for reading and writing
+    struct SharedMemory {
+        handle: HANDLE,
+        view: MEMORY_MAPPED_VIEW_ADDRESS,
+        size: usize,
+    }
+
+    impl SharedMemory {
+        fn create(name: &str, size: usize) -> Result<Self, TextureShareError> {
+            let handle = unsafe {
+                CreateFileMappingW(
+                    INVALID_HANDLE_VALUE,
+                    None,
+                    PAGE_READWRITE,
+                    0,
+                    size as u32,
+                    &HSTRING::from(name),
+                )
+            }
+            .map_err(failed)?;
+            let view = unsafe { MapViewOfFile(handle, FILE_MAP_ALL_ACCESS, 0, 0, size) };
+            if view.Value.is_null() {
+                let _ = unsafe { CloseHandle(handle) };
+                return Err(failed(format!("Could not map {name}")));
+            }
+            Ok(Self { handle, view, size })
+        }
+
+        fn read(&self) -> Vec<u8> {
+            let mut bytes = vec![0; self.size];
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    self.view.Value as *const u8,
+                    bytes.as_mut_ptr(),
+                    self.size,
+                )
+            };
+            bytes
+        }
+
+        fn write(&self, bytes: &[u8]) {
+            let len = bytes.len().min(self.size);
+            unsafe {
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), self.view.Value as *mut u8, len)
+            };
+        }
+    }
+
+    impl Drop for SharedMemory {
+        fn drop(&mut self) {
+            let _ = unsafe { UnmapViewOfFile(self.view) };
+            let _ = unsafe { CloseHandle(self.handle) };
+        }
+    }
+
+    fn failed(e: impl std::fmt::Display) -> TextureShareError {
+        TextureShareError::Failed(e.to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{TextureShareError, ENV_OVERRIDE};
+    use libloading::Library;
+    use objc2::msg_send;
+    use objc2::rc::{Allocated, Retained};
+    use objc2::runtime::{AnyClass, AnyObject, ProtocolObject};
+    use objc2_foundation::{NSPoint, NSRect, NSSize, NSString};
+    use objc2_metal::{
+        MTLCommandBuffer, MTLCommandQueue, MTLCreateSystemDefaultDevice, MTLDevice, MTLOrigin,
+        MTLPixelFormat, MTLRegion, MTLSize, MTLTexture, MTLTextureDescriptor, MTLTextureUsage,
+    };
+    use std::path::PathBuf;
+    use std::ptr::NonNull;
+    use std::sync::OnceLock;
+
+    pub const TECHNOLOGY: &str = "syphon";
+
+    static SYPHON: OnceLock<Library> = OnceLock::new();
+
+    /// A Syphon Metal server, used only under the state's lock
+    pub struct Sender {
+        server: Retained<AnyObject>,
+        queue: Retained<ProtocolObject<dyn MTLCommandQueue>>,
+        texture: Retained<ProtocolObject<dyn MTLTexture>>,
+        width: u32,
+        height: u32,
+    }
+
+    unsafe impl Send for Sender {}
+
+    impl Sender {
+        pub fn new(name: &str, width: u32, height: u32) -> Result<Self, TextureShareError> {
+            load()?;
+            let class =
+                AnyClass::get(c"SyphonMetalServer").ok_or(TextureShareError::SyphonNotFound)?;
+            let device = MTLCreateSystemDefaultDevice().ok_or_else(|| failed("No Metal device"))?;
+            let queue = device
+                .newCommandQueue()
+                .ok_or_else(|| failed("No Metal command queue"))?;
+
+            let descriptor = unsafe {
+                MTLTextureDescriptor::texture2DDescriptorWithPixelFormat_width_height_mipmapped(
+                    MTLPixelFormat::RGBA8Unorm,
+                    width as usize,
+                    height as usize,
+                    false,
+                )
+            };
+            descriptor.setUsage(MTLTextureUsage::ShaderRead);
+            let texture = device
+                .newTextureWithDescriptor(&descriptor)
+                .ok_or_else(|| failed("No Metal texture"))?;
+
+            let server: Option<Retained<AnyObject>> = unsafe {
+                let allocated: Allocated<AnyObject> = msg_send![class, alloc];
+                msg_send![
+                    allocated,
+                    initWithName: &*NSString::from_str(name),
+                    device: &*device,
+                    options: None::<&AnyObject>
+                ]
+            };
+            let server = server.ok_or_else(|| failed(format!("Could not start {name}")))?;
+            Ok(Self {
+                server,
+                queue,
+                texture,
+                width,
+                height,
+            })
+        }
+
+        /// Copy an RGBA frame into the texture and publish it
+        pub fn publish(&mut self, rgba: &[u8]) -> Result<(), TextureShareError> {
+            let region = MTLRegion {
+                origin: MTLOrigin { x: 0, y: 0, z: 0 },
+                size: MTLSize {
+                    width: self.width as usize,
+                    height: self.height as usize,
+                    depth: 1,
+                },
+            };
+            let bytes = NonNull::new(rgba.as_ptr() as *mut _).ok_or_else(|| failed("No frame"))?;
+            unsafe {
+                self.texture
+                    .replaceRegion_mipmapLevel_withBytes_bytesPerRow(
+                        region,
+                        0,
+                        bytes,
+                        self.width as usize * 4,
+                    )
+            };
+            let buffer = self
+                .queue
+                .commandBuffer()
+                .ok_or_else(|| failed("No Metal command buffer"))?;
+            let image = NSRect::new(
+                NSPoint::new(0.0, 0.0),
+                NSSize::new(self.width as f64, self.height as f64),
+            );
+            unsafe {
+                let _: () = msg_send![
+                    &*self.server,
+                    publishFrameTexture: &*self.texture,
+                    onCommandBuffer: &*buffer,
+                    imageRegion: image,
+                    flipped: false
+                ];
+            }
+            buffer.commit();
+            Ok(())
+        }
+    }
+
+    impl Drop for Sender {
+        fn drop(&mut self) {
+            unsafe {
+                let _: () = msg_send![&*self.server, stop];
+            }
+        }
+    }
+
+    /// Load Syphon.framework once; its classes then register themselves
+    fn load() -> Result<(), TextureShareError> {
+        if SYPHON.get().is_some() {
+            return Ok(());
+        }
+        let beside_app = std::env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.parent()?.join("../Frameworks/Syphon.framework/Syphon")));
+        let library = std::env::var_os(ENV_OVERRIDE)
+            .map(PathBuf::from)
+            .into_iter()
+            .chain(beside_app)
+            .chain([PathBuf::from("/Library/Frameworks/Syphon.framework/Syphon")])
+            .find_map(|path| unsafe { Library::new(path) }.ok())
+            .ok_or(TextureShareError::SyphonNotFound)?;
+        let _ = SYPHON.set(library);
+        Ok(())
+    }
+
+    fn failed(e: impl std::fmt::Display) -> TextureShareError {
+        TextureShareError::Failed(e.to_string())
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod platform {
+    use super::TextureShareError;
+
+    pub const TECHNOLOGY: &str = "none";
+
+    pub struct Sender;
+
+    impl Sender {
+        pub fn new(_name: &str, _width: u32, _height: u32) -> Result<Self, TextureShareError> {
+            Err(TextureShareError::Unsupported)
+        }
+
+        pub fn publish(&mut self, _rgba: &[u8]) -> Result<(), TextureShareError> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,485 @@
+//! Theme packs (.cptheme)
+//!
+//! A .cptheme file is a ZIP archive containing:
+//! - pack.json: Pack metadata plus the manifest entries of the bundled media and fonts
+//! - themes/*.json: Theme documents, unchanged from the source bundle
+//! - media/*: Background media referenced by the themes
+//! - fonts/*: Embedded fonts used by the themes
+//!
+//! Packs are imported either into an existing .cpres bundle (ids that collide
+//! are remapped, identical media/fonts are stored once) or extracted into the
+//! theme library under the content directory.
+
+use crate::bundle_reader::{self, BundleReader};
+use crate::checksums::{Checksums, CHECKSUMS_FILE};
+use crate::cpres::{self, CpresError};
+use crate::history;
+use crate::merge::{unique_bundle_path, unique_id};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Component, Path};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tempfile::NamedTempFile;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const PACK_MANIFEST: &str = "pack.json";
+const PACK_FORMAT_VERSION: u32 = 1;
+
+/// Keys in theme documents that hold a media id
+const MEDIA_ID_KEYS: &[&str] = &["mediaId"];
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PackManifest {
+    format_version: u32,
+    pack_id: String,
+    name: String,
+    created_at_ms: u64,
+    themes: Vec<PackTheme>,
+    /// Entries copied from the source bundle's manifest `media` list
+    #[serde(default)]
+    media: Vec<Value>,
+    /// Entries copied from the source bundle's manifest `fonts` list
+    #[serde(default)]
+    fonts: Vec<Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PackTheme {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThemePackSummary {
+    pub pack_id: String,
+    pub name: String,
+    pub themes: Vec<PackTheme>,
+    pub media_count: usize,
+    pub font_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedTheme {
+    /// Id inside the pack
+    pub source_id: String,
+    /// Id the theme was stored under (differs when it collided with an existing theme)
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThemePackImport {
+    pub pack_id: String,
+    /// Bundle file or library folder the pack was imported into
+    pub destination: String,
+    pub themes: Vec<ImportedTheme>,
+    pub media_added: usize,
+    pub fonts_added: usize,
+}
+
+/// Export the themes with `theme_ids` from a bundle, with the media and fonts they use
+pub fn export_pack(
+    bundle_path: &Path,
+    theme_ids: &[String],
+    name: Option<String>,
+    output: &Path,
+) -> Result<ThemePackSummary, CpresError> {
+    if theme_ids.is_empty() {
+        return Err(CpresError::InvalidBundle(
+            "No themes selected for export".to_string(),
+        ));
+    }
+
+    let bundle = cpres::open_bundle(bundle_path)?;
+    let manifest: Value = serde_json::from_str(&bundle.manifest)?;
+
+    let mut available = HashMap::new();
+    for theme_file in &bundle.themes {
+        let theme: Value = serde_json::from_str(&theme_file.content)?;
+        available.insert(str_field(&theme, "id"), theme);
+    }
+
+    let mut themes = Vec::new();
+    let mut media_ids = BTreeSet::new();
+    let mut families = BTreeSet::new();
+    for id in theme_ids {
+        let theme = available
+            .remove(id)
+            .ok_or_else(|| CpresError::MissingFile(format!("themes/{id}.json")))?;
+        for key in MEDIA_ID_KEYS {
+            cpres::collect_references(&theme, key, &mut media_ids);
+        }
+        cpres::collect_font_families(&theme, &mut families);
+        themes.push(theme);
+    }
+
+    let families: HashSet<String> = families.iter().map(|f| f.to_lowercase()).collect();
+    let media: Vec<Value> = manifest_list(&manifest, "media")
+        .filter(|entry| media_ids.contains(&str_field(entry, "id")))
+        .cloned()
+        .collect();
+    let fonts: Vec<Value> = manifest_list(&manifest, "fonts")
+        .filter(|entry| families.contains(&str_field(entry, "family").to_lowercase()))
+        .cloned()
+        .collect();
+
+    let name = name
+        .filter(|n| !n.trim().is_empty())
+        .or_else(|| themes.first().map(|t| str_field(t, "name")))
+        .unwrap_or_else(|| "Theme pack".to_string());
+    let pack = PackManifest {
+        format_version: PACK_FORMAT_VERSION,
+        pack_id: uuid::Uuid::new_v4().to_string(),
+        name,
+        created_at_ms: unix_millis(),
+        themes: themes
+            .iter()
+            .map(|t| PackTheme {
+                id: str_field(t, "id"),
+                name: str_field(t, "name"),
+            })
+            .collect(),
+        media,
+        fonts,
+    };
+
+    let parent = output.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)?;
+    let temp_file = NamedTempFile::new_in(parent)?;
+    let mut zip = ZipWriter::new(temp_file.reopen()?);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    zip.start_file(PACK_MANIFEST, options)?;
+    zip.write_all(serde_json::to_string_pretty(&pack)?.as_bytes())?;
+    for theme in &themes {
+        zip.start_file(format!("themes/{}.json", str_field(theme, "id")), options)?;
+        zip.write_all(serde_json::to_string_pretty(theme)?.as_bytes())?;
+    }
+
+    // Media and fonts keep their bundle paths and are raw-copied without recompressing
+    let mut archive = bundle_reader::open_archive(bundle_path)?;
+    for entry in pack.media.iter().chain(&pack.fonts) {
+        let path = str_field(entry, "path");
+        if path.is_empty() {
+            continue;
+        }
+        let index = archive
+            .index_for_name(&path)
+            .ok_or_else(|| CpresError::MissingFile(path.clone()))?;
+        zip.raw_copy_file(archive.by_index_raw(index)?)?;
+    }
+
+    zip.finish()?;
+    cpres::persist_file(temp_file, output)?;
+
+    Ok(ThemePackSummary {
+        media_count: pack.media.len(),
+        font_count: pack.fonts.len(),
+        pack_id: pack.pack_id,
+        name: pack.name,
+        themes: pack.themes,
+    })
+}
+
+/// Add a pack's themes, media, and fonts to an existing bundle on disk.
+/// The previous bundle is kept as a history snapshot.
+pub fn import_into_bundle(
+    pack_path: &Path,
+    bundle_path: &Path,
+) -> Result<ThemePackImport, CpresError> {
+    let (pack, themes, mut pack_archive) = read_pack(pack_path)?;
+    let mut bundle_archive = bundle_reader::open_archive(bundle_path)?;
+    let mut manifest: Value =
+        serde_json::from_str(&cpres::read_zip_file(&mut bundle_archive, "manifest.json")?)?;
+
+    let mut taken_paths: HashSet<String> = bundle_archive.file_names().map(String::from).collect();
+    let mut existing_themes = HashMap::new();
+    for name in taken_paths
+        .iter()
+        .filter(|n| n.starts_with("themes/") && n.ends_with(".json"))
+    {
+        let theme: Value = serde_json::from_str(&cpres::read_zip_file(&mut bundle_archive, name)?)?;
+        existing_themes.insert(str_field(&theme, "id"), theme);
+    }
+
+    // (path in pack, path in bundle)
+    let mut copies = Vec::new();
+
+    let mut media_map = HashMap::new();
+    let mut media_ids: HashSet<String> = manifest_list(&manifest, "media")
+        .map(|m| str_field(m, "id"))
+        .collect();
+    let mut media_by_hash: HashMap<String, String> = manifest_list(&manifest, "media")
+        .map(|m| (str_field(m, "sha256"), str_field(m, "id")))
+        .filter(|(sha, _)| !sha.is_empty())
+        .collect();
+    let mut new_media = Vec::new();
+    for mut entry in pack.media {
+        let id = str_field(&entry, "id");
+        let sha = str_field(&entry, "sha256");
+        if let Some(existing) = media_by_hash.get(&sha).filter(|_| !sha.is_empty()) {
+            media_map.insert(id, existing.clone());
+            continue;
+        }
+
+        let new_id = unique_id(&id, &media_ids);
+        let source_path = str_field(&entry, "path");
+        if !source_path.is_empty() {
+            let new_path = unique_bundle_path(&source_path, &new_id, &taken_paths);
+            entry["path"] = json!(new_path);
+            taken_paths.insert(new_path.clone());
+            copies.push((source_path, new_path));
+        }
+        entry["id"] = json!(new_id);
+
+        media_ids.insert(new_id.clone());
+        if !sha.is_empty() {
+            media_by_hash.insert(sha, new_id.clone());
+        }
+        if new_id != id {
+            media_map.insert(id, new_id);
+        }
+        new_media.push(entry);
+    }
+
+    let mut font_ids: HashSet<String> = manifest_list(&manifest, "fonts")
+        .map(|f| str_field(f, "id"))
+        .collect();
+    let mut font_hashes: HashSet<String> = manifest_list(&manifest, "fonts")
+        .map(|f| str_field(f, "sha256"))
+        .filter(|sha| !sha.is_empty())
+        .collect();
+    let mut new_fonts = Vec::new();
+    for mut entry in pack.fonts {
+        let sha = str_field(&entry, "sha256");
+        if !sha.is_empty() && !font_hashes.insert(sha) {
+            continue;
+        }
+
+        let new_id = unique_id(&str_field(&entry, "id"), &font_ids);
+        let source_path = str_field(&entry, "path");
+        if !source_path.is_empty() {
+            let new_path = unique_bundle_path(&source_path, &new_id, &taken_paths);
+            entry["path"] = json!(new_path);
+            taken_paths.insert(new_path.clone());
+            copies.push((source_path, new_path));
+        }
+        entry["id"] = json!(new_id);
+        font_ids.insert(new_id);
+        new_fonts.push(entry);
+    }
+
+    // New themes are written as themes/<id>.json, so an id is taken when a
+    // theme has it or when a file of that name is there under another id
+    let mut theme_ids: HashSet<String> = existing_themes.keys().cloned().collect();
+    theme_ids.extend(taken_paths.iter().filter_map(|name| {
+        name.strip_prefix("themes/")?
+            .strip_suffix(".json")
+            .map(String::from)
+    }));
+    let mut imported = Vec::new();
+    let mut theme_writes = Vec::new();
+    for (source_id, mut theme) in themes {
+        cpres::rewrite_references(&mut theme, MEDIA_ID_KEYS, &media_map);
+        let id = match existing_themes.get(&source_id) {
+            Some(existing) if *existing == theme => source_id.clone(),
+            _ => {
+                let new_id = unique_id(&source_id, &theme_ids);
+                theme["id"] = json!(new_id);
+                theme_writes.push((new_id.clone(), theme.clone()));
+                new_id
+            }
+        };
+        theme_ids.insert(id.clone());
+        imported.push(ImportedTheme {
+            source_id,
+            id: id.clone(),
+            name: str_field(&theme, "name"),
+        });
+        existing_themes.insert(id, theme);
+    }
+
+    let media_added = new_media.len();
+    let fonts_added = new_fonts.len();
+    append_list(&mut manifest, "media", new_media);
+    append_list(&mut manifest, "fonts", new_fonts);
+
+    history::snapshot(bundle_path, history::DEFAULT_KEEP_VERSIONS)?;
+
+    let parent = bundle_path.parent().unwrap_or(Path::new("."));
+    let temp_file = NamedTempFile::new_in(parent)?;
+    let mut zip = ZipWriter::new(temp_file.reopen()?);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let mut checksums = Checksums::default();
+
+    checksums.write_entry(
+        &mut zip,
+        "manifest.json",
+        serde_json::to_string_pretty(&manifest)?.as_bytes(),
+        options,
+    )?;
+    for i in 0..bundle_archive.len() {
+        let entry = bundle_archive.by_index_raw(i)?;
+        let name = entry.name().to_string();
+        if entry.is_dir() || name == "manifest.json" || name == CHECKSUMS_FILE {
+            continue;
+        }
+        drop(entry);
+        checksums.add_from_archive(&mut bundle_archive, i, &name)?;
+        zip.raw_copy_file(bundle_archive.by_index_raw(i)?)?;
+    }
+    for (id, theme) in &theme_writes {
+        checksums.write_entry(
+            &mut zip,
+            &format!("themes/{id}.json"),
+            serde_json::to_string_pretty(theme)?.as_bytes(),
+            options,
+        )?;
+    }
+    for (source_path, target_path) in &copies {
+        let index = pack_archive
+            .index_for_name(source_path)
+            .ok_or_else(|| CpresError::MissingFile(source_path.clone()))?;
+        checksums.add_from_archive(&mut pack_archive, index, target_path)?;
+        zip.raw_copy_file_rename(pack_archive.by_index_raw(index)?, target_path)?;
+    }
+    checksums.write(&mut zip, options)?;
+    zip.finish()?;
+
+    // Release the source handle before replacing it (required on Windows)
+    drop(bundle_archive);
+    cpres::persist_file(temp_file, bundle_path)?;
+
+    Ok(ThemePackImport {
+        pack_id: pack.pack_id,
+        destination: bundle_path.to_string_lossy().to_string(),
+        themes: imported,
+        media_added,
+        fonts_added,
+    })
+}
+
+/// Extract a pack into `<library_dir>/<pack id>/`, replacing an earlier import of the same pack
+pub fn import_into_library(
+    pack_path: &Path,
+    library_dir: &Path,
+) -> Result<ThemePackImport, CpresError> {
+    let (pack, themes, mut archive) = read_pack(pack_path)?;
+    if !is_plain_file_name(&pack.pack_id) {
+        return Err(CpresError::InvalidBundle(format!(
+            "Invalid pack id: {}",
+            pack.pack_id
+        )));
+    }
+
+    fs::create_dir_all(library_dir)?;
+    let staging = tempfile::Builder::new()
+        .prefix(".import-")
+        .tempdir_in(library_dir)?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        // Skip entries that would escape the destination folder
+        let Some(relative) = entry.enclosed_name() else {
+            continue;
+        };
+        let target = staging.path().join(relative);
+        if entry.is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = File::create(&target)?;
+        std::io::copy(&mut entry, &mut file)?;
+    }
+
+    let destination = library_dir.join(&pack.pack_id);
+    if destination.exists() {
+        fs::remove_dir_all(&destination)?;
+    }
+    let staged = staging.keep();
+    if let Err(e) = fs::rename(&staged, &destination) {
+        let _ = fs::remove_dir_all(&staged);
+        return Err(e.into());
+    }
+
+    Ok(ThemePackImport {
+        destination: destination.to_string_lossy().to_string(),
+        themes: themes
+            .iter()
+            .map(|(id, theme)| ImportedTheme {
+                source_id: id.clone(),
+                id: id.clone(),
+                name: str_field(theme, "name"),
+            })
+            .collect(),
+        media_added: pack.media.len(),
+        fonts_added: pack.fonts.len(),
+        pack_id: pack.pack_id,
+    })
+}
+
+type OpenedPack = (PackManifest, Vec<(String, Value)>, ZipArchive<BundleReader>);
+
+fn read_pack(pack_path: &Path) -> Result<OpenedPack, CpresError> {
+    let mut archive = bundle_reader::open_archive(pack_path)?;
+    let pack: PackManifest =
+        serde_json::from_str(&cpres::read_zip_file(&mut archive, PACK_MANIFEST)?)?;
+    if pack.format_version > PACK_FORMAT_VERSION {
+        return Err(CpresError::InvalidBundle(format!(
+            "Theme pack format {} is newer than this version supports",
+            pack.format_version
+        )));
+    }
+
+    let mut themes = Vec::new();
+    for theme in &pack.themes {
+        let content = cpres::read_zip_file(&mut archive, &format!("themes/{}.json", theme.id))?;
+        themes.push((theme.id.clone(), serde_json::from_str(&content)?));
+    }
+
+    Ok((pack, themes, archive))
+}
+
+fn manifest_list<'a>(manifest: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    manifest
+        .get(key)
+        .and_then(|l| l.as_array())
+        .into_iter()
+        .flatten()
+}
+
+fn append_list(manifest: &mut Value, key: &str, items: Vec<Value>) {
+    match manifest.get_mut(key).and_then(|l| l.as_array_mut()) {
+        Some(list) => list.extend(items),
+        None => manifest[key] = Value::Array(items),
+    }
+}
+
+fn is_plain_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(components.next(), Some(Component::Normal(_))) && components.next().is_none()
+}
+
+fn str_field(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
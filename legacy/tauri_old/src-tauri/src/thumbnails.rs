@@ -0,0 +1,271 @@
+//! Thumbnails for media browsers and slide sorters
+//!
+//! Thumbnails are written to `thumbnails/` in app data, named after the sha256
+//! of the source content and the requested size, so the same background used in
+//! twenty bundles is decoded once. Bundle media use the digest recorded in
+//! `checksums.json` when there is one and are only read on a cache miss. The
+//! cache is trimmed back under `MAX_CACHE_BYTES`, least recently used first,
+//! whenever a thumbnail is added.
+
+use crate::bundle_reader::{self, BundleReader};
+use crate::checksums;
+use crate::cpres::{self, CpresError};
+use crate::export;
+use crate::image_convert;
+use crate::tasks::CancelToken;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::{DynamicImage, ImageDecoder, ImageEncoder, ImageReader};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, FileTimes};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use zip::ZipArchive;
+
+pub(crate) const CACHE_DIR_NAME: &str = "thumbnails";
+
+/// Longest edge when the caller doesn't ask for a size
+pub const DEFAULT_SIZE: u32 = 256;
+const MIN_SIZE: u32 = 16;
+const MAX_SIZE: u32 = 2048;
+
+const MAX_CACHE_BYTES: u64 = 512 * 1024 * 1024;
+/// Trim to this much below the limit so the next few thumbnails don't trim again
+const TRIM_TO_BYTES: u64 = MAX_CACHE_BYTES / 10 * 8;
+
+const JPEG_QUALITY: u8 = 85;
+
+/// Formats thumbnails are stored in: JPEG, or PNG when the source has transparency
+const EXTENSIONS: [&str; 2] = ["jpg", "png"];
+
+/// A media file inside a bundle
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleMedia {
+    pub bundle: String,
+    /// Entry path, e.g. "media/abc123.jpg"
+    pub media_path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Thumbnail {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    /// False when it had to be generated
+    pub cached: bool,
+}
+
+/// The thumbnail folder and its size limit
+pub struct ThumbnailCache {
+    dir: PathBuf,
+}
+
+impl ThumbnailCache {
+    /// The cache under the app data directory
+    pub fn in_app_data(app_data_dir: &Path) -> Self {
+        Self {
+            dir: app_data_dir.join(CACHE_DIR_NAME),
+        }
+    }
+
+    /// A cached file for `key`, marked as recently used
+    pub fn get(&self, key: &str) -> Option<PathBuf> {
+        let path = EXTENSIONS
+            .iter()
+            .map(|ext| self.dir.join(format!("{key}.{ext}")))
+            .find(|path| path.is_file())?;
+        // The modification time doubles as the last-used time for eviction
+        let touched = File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_times(FileTimes::new().set_modified(SystemTime::now())));
+        if let Err(e) = touched {
+            log::debug!("Could not mark {} as used: {e}", path.display());
+        }
+        Some(path)
+    }
+
+    /// Store `data` under `key`, then trim the cache
+    pub fn put(&self, key: &str, extension: &str, data: &[u8]) -> Result<PathBuf, CpresError> {
+        let path = self.dir.join(format!("{key}.{extension}"));
+        export::write_atomic(&path, data)?;
+        if let Err(e) = self.trim() {
+            log::warn!("Could not trim the thumbnail cache: {e}");
+        }
+        Ok(path)
+    }
+
+    /// Delete least recently used thumbnails while the cache is over its limit
+    fn trim(&self) -> Result<(), CpresError> {
+        let mut files = Vec::new();
+        let mut total = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let is_thumbnail = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| EXTENSIONS.contains(&ext));
+            if !is_thumbnail {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            total += metadata.len();
+            files.push((used, metadata.len(), path));
+        }
+        if total <= MAX_CACHE_BYTES {
+            return Ok(());
+        }
+        files.sort();
+        for (_, bytes, path) in files {
+            if total <= TRIM_TO_BYTES {
+                break;
+            }
+            fs::remove_file(&path)?;
+            total -= bytes;
+        }
+        Ok(())
+    }
+}
+
+/// Thumbnail of an image file, at most `size` pixels on its longest edge
+pub fn file_thumbnail(
+    cache: &ThumbnailCache,
+    path: &Path,
+    size: u32,
+) -> Result<Thumbnail, CpresError> {
+    let (digest, _) = cpres::hash_file(path, &CancelToken::default())?;
+    thumbnail(cache, &digest, size, || Ok(fs::read(path)?))
+}
+
+/// Thumbnail of an image stored in a bundle
+pub fn bundle_thumbnail(
+    cache: &ThumbnailCache,
+    media: &BundleMedia,
+    size: u32,
+) -> Result<Thumbnail, CpresError> {
+    let mut archive = bundle_reader::open_archive(Path::new(&media.bundle))?;
+    match checksums::recorded_digest(&mut archive, &media.media_path)? {
+        Some(digest) => thumbnail(cache, &digest, size, || {
+            read_entry(&mut archive, &media.media_path)
+        }),
+        // Unlisted entries have to be read to be hashed
+        None => {
+            let data = read_entry(&mut archive, &media.media_path)?;
+            let digest = hex::encode(Sha256::digest(&data));
+            thumbnail(cache, &digest, size, || Ok(data))
+        }
+    }
+}
+
+fn read_entry(archive: &mut ZipArchive<BundleReader>, name: &str) -> Result<Vec<u8>, CpresError> {
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|_| CpresError::MissingFile(name.to_string()))?;
+    let mut data = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// The cached thumbnail for `digest`, or one made from the image bytes `load`
+/// returns
+pub(crate) fn thumbnail(
+    cache: &ThumbnailCache,
+    digest: &str,
+    size: u32,
+    load: impl FnOnce() -> Result<Vec<u8>, CpresError>,
+) -> Result<Thumbnail, CpresError> {
+    if let Some(thumbnail) = cached_thumbnail(cache, digest, size)? {
+        return Ok(thumbnail);
+    }
+
+    let size = size.clamp(MIN_SIZE, MAX_SIZE);
+    let image = decode(&load()?)?;
+    let scaled = if image.width().max(image.height()) > size {
+        image.thumbnail(size, size)
+    } else {
+        image
+    };
+    let (extension, data) = encode(&scaled)?;
+    let path = cache.put(&format!("{digest}-{size}"), extension, &data)?;
+    Ok(Thumbnail {
+        path: path.to_string_lossy().to_string(),
+        width: scaled.width(),
+        height: scaled.height(),
+        cached: false,
+    })
+}
+
+/// The thumbnail for `digest` if it has been made already
+pub(crate) fn cached_thumbnail(
+    cache: &ThumbnailCache,
+    digest: &str,
+    size: u32,
+) -> Result<Option<Thumbnail>, CpresError> {
+    let size = size.clamp(MIN_SIZE, MAX_SIZE);
+    let Some(path) = cache.get(&format!("{digest}-{size}")) else {
+        return Ok(None);
+    };
+    let (width, height) = dimensions(&path)?;
+    Ok(Some(Thumbnail {
+        path: path.to_string_lossy().to_string(),
+        width,
+        height,
+        cached: true,
+    }))
+}
+
+/// Size of a cached image; reading the header is enough
+pub(crate) fn dimensions(path: &Path) -> Result<(u32, u32), CpresError> {
+    image::image_dimensions(path)
+        .map_err(|e| CpresError::InvalidBundle(format!("Unreadable thumbnail: {e}")))
+}
+
+/// Decode an image, turned upright according to its EXIF orientation. HEIC
+/// and AVIF are handed to ffmpeg.
+pub(crate) fn decode(data: &[u8]) -> Result<DynamicImage, CpresError> {
+    if image_convert::is_convertible(data) {
+        return image_convert::decode_bytes(data);
+    }
+    let unsupported =
+        |e: image::ImageError| CpresError::InvalidBundle(format!("Unsupported image: {e}"));
+    let mut decoder = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .into_decoder()
+        .map_err(unsupported)?;
+    let orientation = decoder.orientation().map_err(unsupported)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(unsupported)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+fn encode(image: &DynamicImage) -> Result<(&'static str, Vec<u8>), CpresError> {
+    let encode_error = |e: image::ImageError| CpresError::InvalidBundle(e.to_string());
+    let mut data = Vec::new();
+    if image.color().has_alpha() {
+        let rgba = image.to_rgba8();
+        PngEncoder::new(&mut data)
+            .write_image(
+                &rgba,
+                rgba.width(),
+                rgba.height(),
+                image::ExtendedColorType::Rgba8,
+            )
+            .map_err(encode_error)?;
+        Ok(("png", data))
+    } else {
+        let rgb = image.to_rgb8();
+        JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY)
+            .write_image(
+                &rgb,
+                rgb.width(),
+                rgb.height(),
+                image::ExtendedColorType::Rgb8,
+            )
+            .map_err(encode_error)?;
+        Ok(("jpg", data))
+    }
+}
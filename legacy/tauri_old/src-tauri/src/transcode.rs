@@ -0,0 +1,412 @@
+//! Converting library videos to H.264 the output window can play smoothly
+//!
+//! Videographers hand over ProRes `.mov` files and phones record HEVC; the
+//! webview may not decode those at all, or not fast enough for a 4K
+//! background. A transcode writes an H.264/AAC MP4 next to the source, named
+//! after it, and the library indexes it like any other file. Jobs wait in a
+//! queue and run one at a time, since each keeps every core (or the GPU's
+//! encoder) busy. Every change to a job is emitted as
+//! `media:transcode-progress`.
+//!
+//! A hardware H.264 encoder (NVENC, Quick Sync, AMF, VideoToolbox) is used
+//! when ffmpeg was built with one that works on this machine, otherwise
+//! libx264. The check runs once, before the first job.
+
+use crate::cpres::{self, CpresError};
+use crate::ffmpeg;
+use crate::media_library::MediaItem;
+use crate::media_watch;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const TRANSCODE_PROGRESS_EVENT: &str = "media:transcode-progress";
+
+/// Finished jobs still listed by `transcode_jobs`
+const MAX_FINISHED_JOBS: usize = 50;
+
+/// Hardware encoders to try, best first
+#[cfg(target_os = "macos")]
+const HARDWARE_ENCODERS: [&str; 1] = ["h264_videotoolbox"];
+#[cfg(not(target_os = "macos"))]
+const HARDWARE_ENCODERS: [&str; 3] = ["h264_nvenc", "h264_qsv", "h264_amf"];
+const SOFTWARE_ENCODER: &str = "libx264";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TranscodePreset {
+    /// The source's size, up to 4K
+    #[default]
+    Compatible,
+    /// Scaled down to fit 1920×1080
+    #[serde(rename = "1080p")]
+    Hd1080,
+    /// Scaled down to fit 1280×720, for older machines
+    #[serde(rename = "720p")]
+    Hd720,
+}
+
+impl TranscodePreset {
+    /// Largest frame, width by height; portrait videos are fitted the same way
+    fn bounds(self) -> (u32, u32) {
+        match self {
+            Self::Compatible => (3840, 2160),
+            Self::Hd1080 => (1920, 1080),
+            Self::Hd720 => (1280, 720),
+        }
+    }
+
+    /// Target bitrate for encoders without a constant-quality mode
+    fn bitrate(self) -> &'static str {
+        match self {
+            Self::Compatible => "40M",
+            Self::Hd1080 => "12M",
+            Self::Hd720 => "6M",
+        }
+    }
+
+    /// Added to the output's filename
+    fn label(self) -> &'static str {
+        match self {
+            Self::Compatible => "H.264",
+            Self::Hd1080 => "1080p",
+            Self::Hd720 => "720p",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscodeState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscodeJob {
+    pub id: String,
+    pub item_id: String,
+    pub source: String,
+    pub preset: TranscodePreset,
+    pub state: TranscodeState,
+    /// Share done, 0 to 1; stays None while running when the source's length
+    /// is unknown
+    pub progress: Option<f64>,
+    /// ffmpeg's name for the encoder, e.g. "h264_nvenc"
+    pub encoder: Option<String>,
+    /// The new file, once done
+    pub output: Option<String>,
+    pub error: Option<String>,
+    #[serde(skip)]
+    folder: (i64, PathBuf),
+}
+
+/// Managed state: queued, running, and recently finished transcodes
+#[derive(Default)]
+pub struct TranscodeQueue {
+    state: Mutex<QueueState>,
+    encoder: OnceLock<&'static str>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Oldest first
+    jobs: Vec<TranscodeJob>,
+    /// Id and stop flag of the running job
+    running: Option<(String, Arc<AtomicBool>)>,
+    worker: bool,
+}
+
+impl TranscodeQueue {
+    pub fn jobs(&self) -> Result<Vec<TranscodeJob>, CpresError> {
+        Ok(self.lock()?.jobs.clone())
+    }
+
+    /// Queue a transcode of the library video `item`, which is in the folder
+    /// `folder_root`; starts the worker when it's idle
+    pub fn enqueue(
+        &self,
+        app: &AppHandle,
+        item: MediaItem,
+        folder_root: PathBuf,
+        preset: TranscodePreset,
+    ) -> Result<TranscodeJob, CpresError> {
+        if item.media_type != "video" {
+            return Err(CpresError::InvalidBundle(format!(
+                "{} is not a video",
+                item.filename
+            )));
+        }
+        let job = TranscodeJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            item_id: item.id,
+            source: item.path,
+            preset,
+            state: TranscodeState::Queued,
+            progress: None,
+            encoder: None,
+            output: None,
+            error: None,
+            folder: (item.folder_id, folder_root),
+        };
+        let mut state = self.lock()?;
+        state.jobs.push(job.clone());
+        if !state.worker {
+            state.worker = true;
+            let handle = app.clone();
+            std::thread::spawn(move || run_queue(&handle));
+        }
+        drop(state);
+        let _ = app.emit(TRANSCODE_PROGRESS_EVENT, &job);
+        Ok(job)
+    }
+
+    /// Take a queued job off the queue or stop the running one; false when no
+    /// unfinished job has that id
+    pub fn cancel(&self, app: &AppHandle, job_id: &str) -> Result<bool, CpresError> {
+        let mut state = self.lock()?;
+        if let Some((id, stop)) = &state.running {
+            if id == job_id {
+                stop.store(true, Ordering::Relaxed);
+                return Ok(true);
+            }
+        }
+        let Some(job) = state
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == job_id && job.state == TranscodeState::Queued)
+        else {
+            return Ok(false);
+        };
+        job.state = TranscodeState::Cancelled;
+        let _ = app.emit(TRANSCODE_PROGRESS_EVENT, &*job);
+        Ok(true)
+    }
+
+    /// Change a job and tell the frontend
+    fn update(&self, app: &AppHandle, job_id: &str, change: impl FnOnce(&mut TranscodeJob)) {
+        let Ok(mut state) = self.lock() else {
+            return;
+        };
+        if let Some(job) = state.jobs.iter_mut().find(|job| job.id == job_id) {
+            change(job);
+            let _ = app.emit(TRANSCODE_PROGRESS_EVENT, &*job);
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, QueueState>, CpresError> {
+        self.state
+            .lock()
+            .map_err(|e| CpresError::InvalidBundle(e.to_string()))
+    }
+}
+
+/// Run queued jobs until there are none left
+fn run_queue(app: &AppHandle) {
+    let queue = app.state::<TranscodeQueue>();
+    loop {
+        let (job, stop) = {
+            let Ok(mut state) = queue.lock() else {
+                return;
+            };
+            let Some(job) = state
+                .jobs
+                .iter_mut()
+                .find(|job| job.state == TranscodeState::Queued)
+            else {
+                state.worker = false;
+                state.running = None;
+                forget_finished(&mut state.jobs);
+                return;
+            };
+            job.state = TranscodeState::Running;
+            job.encoder = None;
+            let job = job.clone();
+            let stop = Arc::new(AtomicBool::new(false));
+            state.running = Some((job.id.clone(), stop.clone()));
+            (job, stop)
+        };
+
+        let encoder = *queue.encoder.get_or_init(detect_encoder);
+        queue.update(app, &job.id, |job| job.encoder = Some(encoder.to_string()));
+        let result = transcode(&job, encoder, &stop, |progress| {
+            queue.update(app, &job.id, |job| job.progress = Some(progress));
+        });
+        if let Ok(mut state) = queue.lock() {
+            state.running = None;
+        }
+        match result {
+            Ok(output) => {
+                log::info!("Transcoded {} to {}", job.source, output.display());
+                queue.update(app, &job.id, |job| {
+                    job.state = TranscodeState::Done;
+                    job.progress = Some(1.0);
+                    job.output = Some(output.to_string_lossy().to_string());
+                });
+                // Index the new file now rather than waiting for the watcher
+                media_watch::rescan(app, std::iter::once(job.folder.clone()));
+            }
+            Err(CpresError::Cancelled) => {
+                queue.update(app, &job.id, |job| job.state = TranscodeState::Cancelled);
+            }
+            Err(e) => {
+                log::warn!("Could not transcode {}: {e}", job.source);
+                queue.update(app, &job.id, |job| {
+                    job.state = TranscodeState::Failed;
+                    job.error = Some(e.to_string());
+                });
+            }
+        }
+    }
+}
+
+/// Drop the oldest finished jobs beyond `MAX_FINISHED_JOBS`
+fn forget_finished(jobs: &mut Vec<TranscodeJob>) {
+    let finished =
+        |job: &TranscodeJob| !matches!(job.state, TranscodeState::Queued | TranscodeState::Running);
+    let mut excess = jobs
+        .iter()
+        .filter(|job| finished(job))
+        .count()
+        .saturating_sub(MAX_FINISHED_JOBS);
+    jobs.retain(|job| {
+        if excess > 0 && finished(job) {
+            excess -= 1;
+            return false;
+        }
+        true
+    });
+}
+
+/// Encode `job`'s source into a new file beside it and return its path.
+/// `on_progress` gets the share done, at most once per percent.
+fn transcode(
+    job: &TranscodeJob,
+    encoder: &str,
+    stop: &AtomicBool,
+    on_progress: impl Fn(f64),
+) -> Result<PathBuf, CpresError> {
+    let source = Path::new(&job.source);
+    let dir = source
+        .parent()
+        .ok_or_else(|| CpresError::MissingFile(job.source.clone()))?;
+    let duration = ffmpeg::duration(source)?;
+    // A dot name keeps the library scan away from the unfinished file
+    let temp_file = tempfile::Builder::new()
+        .prefix(".transcode-")
+        .suffix(".mp4")
+        .tempfile_in(dir)?;
+
+    let (max_width, max_height) = job.preset.bounds();
+    let filter = format!(
+        "scale=w='min(iw,{max_width})':h='min(ih,{max_height})'\
+         :force_original_aspect_ratio=decrease:force_divisible_by=2,format={}",
+        pixel_format(encoder)
+    );
+    let mut command = ffmpeg::command()?;
+    command
+        .arg("-i")
+        .arg(source)
+        .args(["-map", "0:v:0", "-map", "0:a:0?", "-sn", "-dn"])
+        .args(["-vf", &filter])
+        .args(encoder_args(encoder, job.preset))
+        .args(["-c:a", "aac", "-b:a", "192k", "-ac", "2"])
+        .args(["-movflags", "+faststart", "-f", "mp4"])
+        .args(ffmpeg::PROGRESS_ARGS)
+        .arg(temp_file.path());
+    ffmpeg::run_with_progress(
+        command,
+        duration,
+        "transcoding",
+        || stop.load(Ordering::Relaxed),
+        on_progress,
+    )?;
+
+    let output = output_path(source, job.preset);
+    cpres::persist_file(temp_file, &output)?;
+    Ok(output)
+}
+
+/// `<name> (<label>).mp4` beside the source, numbered when that's taken
+fn output_path(source: &Path, preset: TranscodePreset) -> PathBuf {
+    let dir = source.parent().unwrap_or(Path::new(""));
+    let stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let label = preset.label();
+    let mut output = dir.join(format!("{stem} ({label}).mp4"));
+    for attempt in 2.. {
+        if !output.exists() {
+            break;
+        }
+        output = dir.join(format!("{stem} ({label}) ({attempt}).mp4"));
+    }
+    output
+}
+
+/// The first hardware encoder that works here, or libx264
+fn detect_encoder() -> &'static str {
+    let listed = ffmpeg::command()
+        .and_then(|mut command| Ok(command.arg("-encoders").stdin(Stdio::null()).output()?))
+        .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+        .unwrap_or_default();
+    // Being listed only means ffmpeg was built with it; a tiny test encode
+    // tells whether the GPU and its driver are actually there
+    let encoder = HARDWARE_ENCODERS
+        .into_iter()
+        .filter(|encoder| listed.contains(encoder))
+        .find(|encoder| encoder_works(encoder))
+        .unwrap_or(SOFTWARE_ENCODER);
+    log::info!("Transcoding with {encoder}");
+    encoder
+}
+
+fn encoder_works(encoder: &str) -> bool {
+    let Ok(mut command) = ffmpeg::command() else {
+        return false;
+    };
+    command
+        .args(["-f", "lavfi", "-i", "color=c=black:s=256x256:d=0.1"])
+        .args(["-frames:v", "1", "-pix_fmt", pixel_format(encoder)])
+        .args(["-c:v", encoder, "-f", "null", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// 8-bit 4:2:0, which every H.264 decoder handles, in the layout the encoder takes
+fn pixel_format(encoder: &str) -> &'static str {
+    if encoder == SOFTWARE_ENCODER {
+        "yuv420p"
+    } else {
+        "nv12"
+    }
+}
+
+fn encoder_args(encoder: &str, preset: TranscodePreset) -> Vec<String> {
+    let args: Vec<&str> = match encoder {
+        "h264_nvenc" => vec!["-preset", "p5", "-rc", "vbr", "-cq", "21", "-b:v", "0"],
+        "h264_qsv" => vec!["-preset", "medium", "-global_quality", "21"],
+        "h264_amf" => vec![
+            "-quality", "balanced", "-rc", "cqp", "-qp_i", "20", "-qp_p", "22",
+        ],
+        "h264_videotoolbox" => vec!["-b:v", preset.bitrate()],
+        _ => vec!["-preset", "medium", "-crf", "20"],
+    };
+    ["-c:v", encoder, "-profile:v", "high"]
+        .into_iter()
+        .chain(args)
+        .map(String::from)
+        .collect()
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(TranscodeQueue::default());
+}
@@ -0,0 +1,740 @@
+//! Playing videos for the output windows
+//!
+//! Large background videos stutter in the webview: its decoder shares the
+//! page's thread with the slide animations, often runs in software, and
+//! drops frames whenever the page is busy. The player decodes with ffmpeg
+//! instead, on the GPU where there is one (`-hwaccel auto`), scaled to the
+//! size it's shown at, and keeps the frame that's due by the video's own
+//! frame rate. Output windows draw it onto a canvas from
+//! `cpvideo://localhost/<player id>`; a request with `?after=<sequence>`
+//! waits for the frame after that one, so each frame is drawn once, as it
+//! comes. Each player has one thread answering those requests, and while
+//! it plays, the frame decoded is handed to the request as it is: each
+//! output window loads its own player, so nobody else needs it.
+//!
+//! Players are made by id with `load`, then played, paused, seeked and
+//! looped. `video:state` follows every change with the frame it happened
+//! on, `video:looped` each time a looping video starts over, and
+//! `video:ended` when one that doesn't loop finishes. Sound isn't played
+//! (`-an`): only muted backgrounds are played here, and videos with sound
+//! stay in the webview.
+
+use crate::bundle_reader;
+use crate::cpres::CpresError;
+use crate::ffmpeg;
+use crate::media_probe;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tauri::http::{Request, Response};
+use tauri::{AppHandle, Emitter, Manager, UriSchemeResponder};
+use thiserror::Error;
+
+pub const VIDEO_PROTOCOL: &str = "cpvideo";
+pub const VIDEO_STATE_EVENT: &str = "video:state";
+pub const VIDEO_LOOPED_EVENT: &str = "video:looped";
+pub const VIDEO_ENDED_EVENT: &str = "video:ended";
+
+/// How long a frame request waits for a new frame
+const FRAME_WAIT: Duration = Duration::from_secs(1);
+/// How often waiting frame requests are checked for having waited too long
+const WAITING_INTERVAL: Duration = Duration::from_millis(100);
+/// For videos whose frame rate can't be read
+const DEFAULT_FRAME_RATE: f64 = 30.0;
+/// Largest frame decoded, on either side
+const MAX_SIZE: u32 = 7680;
+/// How often a paused player checks whether it's playing again
+const PAUSED_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Error, Debug)]
+pub enum VideoError {
+    #[error("No video is loaded as {0}")]
+    NotLoaded(String),
+
+    #[error("Video playback failed: {0}")]
+    Failed(String),
+
+    #[error(transparent)]
+    Ffmpeg(#[from] CpresError),
+}
+
+impl From<std::io::Error> for VideoError {
+    fn from(error: std::io::Error) -> Self {
+        CpresError::from(error).into()
+    }
+}
+
+/// A video file, or a video stored in a bundle
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoSource {
+    /// The file, or the entry path inside `bundle`
+    pub path: String,
+    pub bundle: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PlaybackOptions {
+    /// Size the frames are decoded at: the video's own when neither is
+    /// given, and its shape when one is
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    #[serde(rename = "loop")]
+    pub looping: bool,
+    pub autoplay: bool,
+    /// Where to start, in seconds
+    pub start: f64,
+}
+
+impl Default for PlaybackOptions {
+    fn default() -> Self {
+        Self {
+            width: None,
+            height: None,
+            looping: true,
+            autoplay: true,
+            start: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoStatus {
+    pub player_id: String,
+    pub playing: bool,
+    pub looping: bool,
+    /// The frame shown, counted from the start of the video
+    pub frame: u64,
+    pub seconds: f64,
+    pub frame_rate: f64,
+    pub duration: Option<f64>,
+    /// Size of the decoded frames
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Payload of `video:looped` and `video:ended`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoMark {
+    pub player_id: String,
+    /// The last frame shown before it
+    pub frame: u64,
+}
+
+/// A decoded frame, as RGBA
+#[derive(Clone)]
+struct Frame {
+    /// Counts every frame the player shows, across seeks and loops
+    sequence: u64,
+    /// Where it is in the video
+    position: u64,
+    data: Vec<u8>,
+}
+
+/// A frame request to answer, with the frame or with nothing
+type Answer = (UriSchemeResponder, Option<Frame>);
+
+/// A frame request waiting for the frame after `after`
+struct Waiting {
+    after: u64,
+    since: Instant,
+    responder: UriSchemeResponder,
+}
+
+#[derive(Default)]
+struct Frames {
+    sequence: u64,
+    /// The latest frame, until it's handed to a request
+    latest: Option<Frame>,
+    waiting: Vec<Waiting>,
+    closed: bool,
+}
+
+/// What the decoding thread shares with the player and frame requests
+struct Shared {
+    frames: Mutex<Frames>,
+    changed: Condvar,
+    playing: AtomicBool,
+    /// The frame shown, counted from the start of the video
+    position: AtomicU64,
+}
+
+impl Shared {
+    fn lock_frames(&self) -> MutexGuard<'_, Frames> {
+        self.frames
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn publish(&self, position: u64, data: Vec<u8>) {
+        let mut frames = self.lock_frames();
+        frames.sequence += 1;
+        frames.latest = Some(Frame {
+            sequence: frames.sequence,
+            position,
+            data,
+        });
+        self.changed.notify_all();
+    }
+
+    fn wait_for_frame(&self, after: u64, responder: UriSchemeResponder) {
+        self.lock_frames().waiting.push(Waiting {
+            after,
+            since: Instant::now(),
+            responder,
+        });
+        self.changed.notify_all();
+    }
+
+    fn close(&self) {
+        self.lock_frames().closed = true;
+        self.changed.notify_all();
+    }
+
+    /// Answer frame requests until the player is unloaded
+    fn serve_frames(&self, width: u32, height: u32) {
+        let mut frames = self.lock_frames();
+        loop {
+            let answers = self.ready_answers(&mut frames);
+            if !answers.is_empty() {
+                drop(frames);
+                for (responder, frame) in answers {
+                    match frame {
+                        Some(frame) => respond(responder, 200, Some((frame, width, height))),
+                        None => respond(responder, 204, None),
+                    }
+                }
+                frames = self.lock_frames();
+                continue;
+            }
+            if frames.closed {
+                return;
+            }
+            frames = self
+                .changed
+                .wait_timeout(frames, WAITING_INTERVAL)
+                .map(|(frames, _)| frames)
+                .unwrap_or_else(|poisoned| poisoned.into_inner().0);
+        }
+    }
+
+    /// Take the requests that can be answered: with the latest frame, or
+    /// with nothing when they've waited too long or the player's gone
+    fn ready_answers(&self, frames: &mut Frames) -> Vec<Answer> {
+        let closed = frames.closed;
+        let sequence = frames.latest.as_ref().map(|frame| frame.sequence);
+        let (mut due, rest): (Vec<_>, Vec<_>) = std::mem::take(&mut frames.waiting)
+            .into_iter()
+            .partition(|waiting| {
+                !closed && sequence.is_some_and(|sequence| waiting.after < sequence)
+            });
+        let (expired, waiting): (Vec<_>, Vec<_>) = rest
+            .into_iter()
+            .partition(|waiting| closed || waiting.since.elapsed() >= FRAME_WAIT);
+        frames.waiting = waiting;
+
+        let mut answers: Vec<Answer> = expired
+            .into_iter()
+            .map(|waiting| (waiting.responder, None))
+            .collect();
+        // The last request takes the frame itself while playing; paused, the
+        // frame stays on for requests to come
+        let last = if self.playing.load(Ordering::SeqCst) {
+            due.pop()
+        } else {
+            None
+        };
+        for waiting in due {
+            answers.push((waiting.responder, frames.latest.clone()));
+        }
+        if let Some(waiting) = last {
+            answers.push((waiting.responder, frames.latest.take()));
+        }
+        answers
+    }
+}
+
+/// ffmpeg decoding from a frame onward, and the thread showing its frames
+struct Decoder {
+    child: Child,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Decoder {
+    /// Whether the video ended
+    fn finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct Player {
+    /// The file decoded; a copy out of the bundle is deleted with the player
+    file: PathBuf,
+    _copy: Option<tempfile::TempPath>,
+    width: u32,
+    height: u32,
+    frame_rate: f64,
+    /// Frames in the video, when its length is known
+    frames: Option<u64>,
+    duration: Option<f64>,
+    looping: bool,
+    shared: Arc<Shared>,
+    decoder: Option<Decoder>,
+    /// The thread answering frame requests
+    server: Option<JoinHandle<()>>,
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        self.decoder = None;
+        self.shared.close();
+        if let Some(server) = self.server.take() {
+            let _ = server.join();
+        }
+    }
+}
+
+/// Managed state: the players, by id
+pub struct VideoPlayers {
+    app: AppHandle,
+    players: Mutex<HashMap<String, Player>>,
+}
+
+impl VideoPlayers {
+    pub fn new(app: AppHandle) -> Self {
+        Self {
+            app,
+            players: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Load `source` as `player_id`, replacing what was loaded there
+    pub fn load(
+        &self,
+        player_id: &str,
+        source: &VideoSource,
+        options: PlaybackOptions,
+    ) -> Result<VideoStatus, VideoError> {
+        self.unload(player_id);
+        let (file, copy) = match &source.bundle {
+            Some(bundle) => {
+                let copy = copy_out(Path::new(bundle), &source.path)?;
+                (copy.to_path_buf(), Some(copy))
+            }
+            None => (PathBuf::from(&source.path), None),
+        };
+        let metadata = media_probe::probe(&file, "video");
+        let (Some(video_width), Some(video_height)) = (metadata.width, metadata.height) else {
+            return Err(VideoError::Failed(format!(
+                "{} has no video to play",
+                source.path
+            )));
+        };
+        let frame_rate = metadata
+            .frame_rate
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+            .unwrap_or(DEFAULT_FRAME_RATE);
+        let (width, height) = match (options.width, options.height) {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => (width, scale(width, video_height, video_width)),
+            (None, Some(height)) => (scale(height, video_width, video_height), height),
+            (None, None) => (video_width, video_height),
+        };
+        // Even sizes, as the scaler rounds odd ones
+        let width = width.clamp(2, MAX_SIZE) & !1;
+        let height = height.clamp(2, MAX_SIZE) & !1;
+
+        let mut player = Player {
+            file,
+            _copy: copy,
+            width,
+            height,
+            frame_rate,
+            frames: metadata
+                .duration
+                .map(|seconds| (seconds * frame_rate).round() as u64)
+                .filter(|frames| *frames > 0),
+            duration: metadata.duration,
+            looping: options.looping,
+            shared: Arc::new(Shared {
+                frames: Mutex::new(Frames::default()),
+                changed: Condvar::new(),
+                playing: AtomicBool::new(options.autoplay),
+                position: AtomicU64::new(0),
+            }),
+            decoder: None,
+            server: None,
+        };
+        let shared = player.shared.clone();
+        player.server = Some(std::thread::spawn(move || {
+            shared.serve_frames(width, height)
+        }));
+        let first = (options.start.max(0.0) * frame_rate).round() as u64;
+        player.decoder = Some(self.start_decoder(player_id, &player, first)?);
+        log::info!(
+            "Playing {} as {player_id} at {width}x{height}, {frame_rate:.2} fps",
+            source.path
+        );
+
+        let status = status_of(player_id, &player);
+        self.lock_players().insert(player_id.to_string(), player);
+        let _ = self.app.emit(VIDEO_STATE_EVENT, &status);
+        Ok(status)
+    }
+
+    pub fn play(&self, player_id: &str) -> Result<VideoStatus, VideoError> {
+        let mut players = self.lock_players();
+        let player = players
+            .get_mut(player_id)
+            .ok_or_else(|| VideoError::NotLoaded(player_id.to_string()))?;
+        player.shared.playing.store(true, Ordering::SeqCst);
+        // A video that ended plays again from the start
+        if player.decoder.as_ref().is_none_or(Decoder::finished) {
+            player.decoder = Some(self.start_decoder(player_id, player, 0)?);
+        }
+        Ok(self.changed(player_id, player))
+    }
+
+    pub fn pause(&self, player_id: &str) -> Result<VideoStatus, VideoError> {
+        let players = self.lock_players();
+        let player = players
+            .get(player_id)
+            .ok_or_else(|| VideoError::NotLoaded(player_id.to_string()))?;
+        player.shared.playing.store(false, Ordering::SeqCst);
+        Ok(self.changed(player_id, player))
+    }
+
+    /// Show the frame at `seconds`, and carry on from there if playing
+    pub fn seek(&self, player_id: &str, seconds: f64) -> Result<VideoStatus, VideoError> {
+        let mut players = self.lock_players();
+        let player = players
+            .get_mut(player_id)
+            .ok_or_else(|| VideoError::NotLoaded(player_id.to_string()))?;
+        let mut first = (seconds.max(0.0) * player.frame_rate).round() as u64;
+        if let Some(frames) = player.frames {
+            first = first.min(frames.saturating_sub(1));
+        }
+        player.decoder = None;
+        player.decoder = Some(self.start_decoder(player_id, player, first)?);
+        Ok(self.changed(player_id, player))
+    }
+
+    pub fn set_loop(&self, player_id: &str, looping: bool) -> Result<VideoStatus, VideoError> {
+        let mut players = self.lock_players();
+        let player = players
+            .get_mut(player_id)
+            .ok_or_else(|| VideoError::NotLoaded(player_id.to_string()))?;
+        if player.looping != looping {
+            player.looping = looping;
+            // ffmpeg loops the input itself, so it starts again where it was
+            if player.decoder.is_some() {
+                let position = player.shared.position.load(Ordering::SeqCst);
+                player.decoder = None;
+                player.decoder = Some(self.start_decoder(player_id, player, position + 1)?);
+            }
+        }
+        Ok(self.changed(player_id, player))
+    }
+
+    pub fn unload(&self, player_id: &str) {
+        if self.lock_players().remove(player_id).is_some() {
+            log::info!("Stopped playing {player_id}");
+        }
+    }
+
+    pub fn status(&self, player_id: &str) -> Result<VideoStatus, VideoError> {
+        let players = self.lock_players();
+        let player = players
+            .get(player_id)
+            .ok_or_else(|| VideoError::NotLoaded(player_id.to_string()))?;
+        Ok(status_of(player_id, player))
+    }
+
+    /// Answer a `cpvideo` request for a player's next frame
+    pub fn serve(&self, request: &Request<Vec<u8>>, responder: UriSchemeResponder) {
+        let uri = request.uri();
+        let player_id = percent_encoding::percent_decode_str(uri.path().trim_matches('/'))
+            .decode_utf8_lossy()
+            .to_string();
+        let after = uri
+            .query()
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .find_map(|pair| pair.strip_prefix("after="))
+            .and_then(|after| after.parse().ok())
+            .unwrap_or(0);
+        let shared = self
+            .lock_players()
+            .get(&player_id)
+            .map(|player| player.shared.clone());
+        match shared {
+            // Answered by the player's own thread, so the webview's other
+            // requests aren't held up waiting for the frame
+            Some(shared) => shared.wait_for_frame(after, responder),
+            None => respond(responder, 404, None),
+        }
+    }
+
+    fn start_decoder(
+        &self,
+        player_id: &str,
+        player: &Player,
+        first: u64,
+    ) -> Result<Decoder, VideoError> {
+        let mut command = ffmpeg::command()?;
+        command.args(["-hwaccel", "auto"]);
+        if player.looping {
+            command.args(["-stream_loop", "-1"]);
+        }
+        command
+            .arg("-ss")
+            .arg(format!("{:.6}", first as f64 / player.frame_rate))
+            .arg("-i")
+            .arg(&player.file)
+            .args(["-an", "-sn", "-vf"])
+            .arg(format!(
+                "scale={}:{}:flags=bicubic,format=rgba",
+                player.width, player.height
+            ))
+            .arg("-r")
+            .arg(format!("{:.6}", player.frame_rate))
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba", "pipe:1"]);
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(stderr) = child.stderr.take() {
+            let player_id = player_id.to_string();
+            std::thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    log::warn!("ffmpeg (video {player_id}): {line}");
+                }
+            });
+        }
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| VideoError::Failed("ffmpeg has no output".to_string()))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        player.shared.position.store(first, Ordering::SeqCst);
+        let playback = Playback {
+            app: self.app.clone(),
+            player_id: player_id.to_string(),
+            shared: player.shared.clone(),
+            stop: stop.clone(),
+            frame_size: player.width as usize * player.height as usize * 4,
+            interval: Duration::from_secs_f64(1.0 / player.frame_rate),
+            frames: player.frames.filter(|_| player.looping),
+            status: status_of(player_id, player),
+        };
+        let thread = std::thread::spawn(move || playback.run(stdout, first));
+        Ok(Decoder {
+            child,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Report a change made from a command
+    fn changed(&self, player_id: &str, player: &Player) -> VideoStatus {
+        let status = status_of(player_id, player);
+        let _ = self.app.emit(VIDEO_STATE_EVENT, &status);
+        status
+    }
+
+    fn lock_players(&self) -> MutexGuard<'_, HashMap<String, Player>> {
+        self.players
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The decoding thread: reads ffmpeg's frames and shows each when it's due
+struct Playback {
+    app: AppHandle,
+    player_id: String,
+    shared: Arc<Shared>,
+    stop: Arc<AtomicBool>,
+    frame_size: usize,
+    interval: Duration,
+    /// Frames in the video while it loops, to tell where it starts over
+    frames: Option<u64>,
+    /// The player's status when it started, reported again when it ends;
+    /// the players can't be locked from here, as they wait for this thread
+    status: VideoStatus,
+}
+
+impl Playback {
+    fn run(self, mut stdout: ChildStdout, first: u64) {
+        let mut number = first;
+        // When frame `number` is due
+        let mut due = Instant::now();
+        let mut shown_any = false;
+        loop {
+            if self.stop.load(Ordering::SeqCst) {
+                return;
+            }
+            // Paused after the first frame, so a seek shows where it landed
+            if shown_any && !self.shared.playing.load(Ordering::SeqCst) {
+                std::thread::sleep(PAUSED_INTERVAL);
+                due = Instant::now();
+                continue;
+            }
+
+            let mut data = vec![0; self.frame_size];
+            if stdout.read_exact(&mut data).is_err() {
+                if !self.stop.load(Ordering::SeqCst) {
+                    self.ended(number.saturating_sub(1));
+                }
+                return;
+            }
+            let now = Instant::now();
+            if let Some(wait) = due.checked_duration_since(now) {
+                std::thread::sleep(wait);
+            }
+            // Frames too late to show are skipped to catch up
+            let late = now.checked_duration_since(due + self.interval).is_some();
+            let frame = match self.frames {
+                Some(frames) => number % frames,
+                None => number,
+            };
+            if frame == 0 && number > first {
+                let _ = self.app.emit(
+                    VIDEO_LOOPED_EVENT,
+                    VideoMark {
+                        player_id: self.player_id.clone(),
+                        frame: self.frames.map_or(number, |frames| frames - 1),
+                    },
+                );
+            }
+            if !late || !shown_any {
+                self.shared.position.store(frame, Ordering::SeqCst);
+                self.shared.publish(frame, data);
+                shown_any = true;
+            }
+            number += 1;
+            due += self.interval;
+            if late {
+                due = due.max(now);
+            }
+        }
+    }
+
+    /// A video that doesn't loop reached its end
+    fn ended(&self, frame: u64) {
+        self.shared.playing.store(false, Ordering::SeqCst);
+        log::info!("Video {} ended at frame {frame}", self.player_id);
+        let _ = self.app.emit(
+            VIDEO_ENDED_EVENT,
+            VideoMark {
+                player_id: self.player_id.clone(),
+                frame,
+            },
+        );
+        let status = VideoStatus {
+            playing: false,
+            frame,
+            seconds: frame as f64 / self.status.frame_rate,
+            ..self.status.clone()
+        };
+        let _ = self.app.emit(VIDEO_STATE_EVENT, status);
+    }
+}
+
+/// `size` times `numerator` over `denominator`, rounded
+fn scale(size: u32, numerator: u32, denominator: u32) -> u32 {
+    (f64::from(size) * f64::from(numerator) / f64::from(denominator.max(1))).round() as u32
+}
+
+fn status_of(player_id: &str, player: &Player) -> VideoStatus {
+    let frame = player.shared.position.load(Ordering::SeqCst);
+    VideoStatus {
+        player_id: player_id.to_string(),
+        playing: player.shared.playing.load(Ordering::SeqCst),
+        looping: player.looping,
+        frame,
+        seconds: frame as f64 / player.frame_rate,
+        frame_rate: player.frame_rate,
+        duration: player.duration,
+        width: player.width,
+        height: player.height,
+    }
+}
+
+/// Copy the video at `entry` out of the bundle at `bundle`; ffmpeg needs a
+/// file to seek in
+fn copy_out(bundle: &Path, entry: &str) -> Result<tempfile::TempPath, VideoError> {
+    let mut archive = bundle_reader::open_archive(bundle)?;
+    let extension = Path::new(entry)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("bin");
+    let mut copy = tempfile::Builder::new()
+        .suffix(&format!(".{extension}"))
+        .tempfile()?;
+    let mut file = archive
+        .by_name(entry)
+        .map_err(|_| CpresError::MissingFile(entry.to_string()))?;
+    std::io::copy(&mut file, copy.as_file_mut())?;
+    Ok(copy.into_temp_path())
+}
+
+/// Answer a frame request; the frame's numbers and size go in headers
+fn respond(responder: UriSchemeResponder, status: u16, frame: Option<(Frame, u32, u32)>) {
+    let mut response = Response::builder()
+        .status(status)
+        .header("Access-Control-Allow-Origin", "*")
+        .header(
+            "Access-Control-Expose-Headers",
+            "X-Sequence, X-Frame, X-Width, X-Height",
+        )
+        .header("Cache-Control", "no-store");
+    let body = match frame {
+        Some((frame, width, height)) => {
+            response = response
+                .header("Content-Type", "application/octet-stream")
+                .header("X-Sequence", frame.sequence.to_string())
+                .header("X-Frame", frame.position.to_string())
+                .header("X-Width", width.to_string())
+                .header("X-Height", height.to_string());
+            frame.data
+        }
+        None => Vec::new(),
+    };
+    if let Ok(response) = response.body(body) {
+        responder.respond(response);
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(VideoPlayers::new(app.clone()));
+}
+
+pub fn shutdown(app: &AppHandle) {
+    if let Some(players) = app.try_state::<VideoPlayers>() {
+        players.lock_players().clear();
+    }
+}
@@ -0,0 +1,244 @@
+//! Poster frames and scrub thumbnails for videos
+//!
+//! ffmpeg grabs a poster frame a little way into the video (the first frame is
+//! often black) and, when asked, a sprite sheet: evenly spaced frames tiled
+//! left to right, top to bottom, so the media browser can preview a video
+//! while the pointer moves across it. Both go in the thumbnail cache under the
+//! video's content hash, so ffmpeg only runs the first time; the poster shares
+//! its key with image thumbnails, so `generate_thumbnail` serves it too.
+
+use crate::bundle_reader;
+use crate::checksums;
+use crate::cpres::{self, CpresError};
+use crate::ffmpeg;
+use crate::tasks::CancelToken;
+use crate::thumbnails::{self, BundleMedia, Thumbnail, ThumbnailCache};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// How far into the video the poster frame is taken, as a share of its length
+const POSTER_POSITION: f64 = 0.1;
+/// ...but never later than this many seconds
+const POSTER_MAX_SECONDS: f64 = 5.0;
+
+const DEFAULT_FRAMES: u32 = 20;
+const MAX_FRAMES: u32 = 100;
+const DEFAULT_TILE_WIDTH: u32 = 160;
+const MAX_TILE_WIDTH: u32 = 480;
+const MAX_COLUMNS: u32 = 10;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VideoThumbnailOptions {
+    /// Longest edge of the poster, like `generate_thumbnail`'s size
+    pub size: Option<u32>,
+    /// Also make a sprite sheet of seek thumbnails
+    pub sprite: bool,
+    /// Frames in the sprite sheet
+    pub frames: Option<u32>,
+    /// Width of each frame in the sprite sheet
+    pub tile_width: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpriteSheet {
+    pub path: String,
+    pub columns: u32,
+    pub rows: u32,
+    /// Frame `i` shows the video `i / frames` of the way through
+    pub frames: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub cached: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VideoThumbnails {
+    pub poster: Thumbnail,
+    /// None unless asked for, or when the video's length is unknown
+    pub sprite: Option<SpriteSheet>,
+}
+
+/// Thumbnails of a video file
+pub fn file_video_thumbnails(
+    cache: &ThumbnailCache,
+    path: &Path,
+    options: &VideoThumbnailOptions,
+) -> Result<VideoThumbnails, CpresError> {
+    let (digest, _) = cpres::hash_file(path, &CancelToken::default())?;
+    video_thumbnails(cache, &digest, path, options)
+}
+
+/// Thumbnails of a video stored in a bundle. ffmpeg needs a file, so on a
+/// cache miss the video is copied out to a temporary one.
+pub fn bundle_video_thumbnails(
+    cache: &ThumbnailCache,
+    media: &BundleMedia,
+    options: &VideoThumbnailOptions,
+) -> Result<VideoThumbnails, CpresError> {
+    let mut archive = bundle_reader::open_archive(Path::new(&media.bundle))?;
+    let recorded = checksums::recorded_digest(&mut archive, &media.media_path)?;
+    if let Some(digest) = &recorded {
+        if let Some(thumbnails) = cached(cache, digest, options)? {
+            return Ok(thumbnails);
+        }
+    }
+
+    let extension = Path::new(&media.media_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("bin");
+    let mut temp_file = tempfile::Builder::new()
+        .suffix(&format!(".{extension}"))
+        .tempfile()?;
+    let mut entry = archive
+        .by_name(&media.media_path)
+        .map_err(|_| CpresError::MissingFile(media.media_path.clone()))?;
+    std::io::copy(&mut entry, temp_file.as_file_mut())?;
+    drop(entry);
+
+    let digest = match recorded {
+        Some(digest) => digest,
+        None => cpres::hash_file(temp_file.path(), &CancelToken::default())?.0,
+    };
+    video_thumbnails(cache, &digest, temp_file.path(), options)
+}
+
+fn video_thumbnails(
+    cache: &ThumbnailCache,
+    digest: &str,
+    video: &Path,
+    options: &VideoThumbnailOptions,
+) -> Result<VideoThumbnails, CpresError> {
+    if let Some(thumbnails) = cached(cache, digest, options)? {
+        return Ok(thumbnails);
+    }
+    let duration = ffmpeg::duration(video)?;
+
+    let size = options.size.unwrap_or(thumbnails::DEFAULT_SIZE);
+    let poster = thumbnails::thumbnail(cache, digest, size, || poster_frame(video, duration))?;
+
+    let sprite = match (options.sprite, duration) {
+        (false, _) => None,
+        (true, Some(seconds)) if seconds > 0.0 => {
+            Some(sprite_sheet(cache, digest, video, seconds, options)?)
+        }
+        (true, _) => {
+            log::info!(
+                "No sprite sheet for {}: its length is unknown",
+                video.display()
+            );
+            None
+        }
+    };
+    Ok(VideoThumbnails { poster, sprite })
+}
+
+/// Everything asked for, if it's all in the cache already
+fn cached(
+    cache: &ThumbnailCache,
+    digest: &str,
+    options: &VideoThumbnailOptions,
+) -> Result<Option<VideoThumbnails>, CpresError> {
+    let sprite = if options.sprite {
+        let layout = SpriteLayout::new(options);
+        match cache.get(&layout.key(digest)) {
+            Some(path) => Some(layout.sheet(&path, true)?),
+            None => return Ok(None),
+        }
+    } else {
+        None
+    };
+    let size = options.size.unwrap_or(thumbnails::DEFAULT_SIZE);
+    let poster = match thumbnails::cached_thumbnail(cache, digest, size)? {
+        Some(poster) => poster,
+        None => return Ok(None),
+    };
+    Ok(Some(VideoThumbnails { poster, sprite }))
+}
+
+/// The poster frame of a video `duration` seconds long, full size, as PNG;
+/// scaling and encoding are left to the caller
+pub(crate) fn poster_frame(video: &Path, duration: Option<f64>) -> Result<Vec<u8>, CpresError> {
+    let seconds = duration.map_or(0.0, |seconds| {
+        (seconds * POSTER_POSITION).min(POSTER_MAX_SECONDS)
+    });
+    let mut command = ffmpeg::command()?;
+    // -ss before -i seeks by keyframe, which is much faster on long videos
+    command
+        .args(["-ss", &format!("{seconds:.3}"), "-i"])
+        .arg(video)
+        .args(["-an", "-sn", "-frames:v", "1"])
+        .args(["-c:v", "png", "-f", "image2pipe", "-"]);
+    ffmpeg::capture(command, "thumbnail extraction")
+}
+
+fn sprite_sheet(
+    cache: &ThumbnailCache,
+    digest: &str,
+    video: &Path,
+    seconds: f64,
+    options: &VideoThumbnailOptions,
+) -> Result<SpriteSheet, CpresError> {
+    let layout = SpriteLayout::new(options);
+    let filter = format!(
+        "fps={}/{seconds:.3},scale={}:-2,tile={}x{}",
+        layout.frames, layout.tile_width, layout.columns, layout.rows
+    );
+    let mut command = ffmpeg::command()?;
+    command
+        .arg("-i")
+        .arg(video)
+        .args(["-an", "-sn", "-vf", &filter, "-frames:v", "1"])
+        .args(["-c:v", "mjpeg", "-q:v", "4", "-f", "image2pipe", "-"]);
+    let data = ffmpeg::capture(command, "thumbnail extraction")?;
+    let path = cache.put(&layout.key(digest), "jpg", &data)?;
+    layout.sheet(&path, false)
+}
+
+/// Grid of a sprite sheet, from the options with defaults and limits applied
+struct SpriteLayout {
+    frames: u32,
+    tile_width: u32,
+    columns: u32,
+    rows: u32,
+}
+
+impl SpriteLayout {
+    fn new(options: &VideoThumbnailOptions) -> Self {
+        let frames = options
+            .frames
+            .unwrap_or(DEFAULT_FRAMES)
+            .clamp(1, MAX_FRAMES);
+        // Even widths keep ffmpeg's -2 height rounding exact
+        let tile_width = options
+            .tile_width
+            .unwrap_or(DEFAULT_TILE_WIDTH)
+            .clamp(16, MAX_TILE_WIDTH)
+            & !1;
+        let columns = frames.min(MAX_COLUMNS);
+        Self {
+            frames,
+            tile_width,
+            columns,
+            rows: frames.div_ceil(columns),
+        }
+    }
+
+    fn key(&self, digest: &str) -> String {
+        format!("{digest}-sprite-{}x{}", self.frames, self.tile_width)
+    }
+
+    fn sheet(&self, path: &Path, cached: bool) -> Result<SpriteSheet, CpresError> {
+        let (_, height) = thumbnails::dimensions(path)?;
+        Ok(SpriteSheet {
+            path: path.to_string_lossy().to_string(),
+            columns: self.columns,
+            rows: self.rows,
+            frames: self.frames,
+            tile_width: self.tile_width,
+            tile_height: height / self.rows,
+            cached,
+        })
+    }
+}
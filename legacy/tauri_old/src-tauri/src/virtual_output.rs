@@ -0,0 +1,159 @@
+//! An output with no window, for services that are only streamed
+//!
+//! NDI, DeckLink and shared textures draw the slides offscreen already, but
+//! recording grabs an output window from the screen, so an online-only
+//! service would need a display, or a dummy HDMI plug, to put one on. The
+//! virtual output draws the program offscreen at its own size instead (see
+//! `output_frames`) and keeps the latest frame, which recordings started on
+//! `VIRTUAL_OUTPUT_LABEL` are fed from. Like NDI, video and web layers are
+//! missing from it.
+//!
+//! The frame is kept in one place for as long as the app runs, so a
+//! recording carries on through restarts; it's empty while stopped, and
+//! recordings show black while it's stopped or at another size.
+
+use crate::cpres::CpresError;
+use crate::output_frames::{self, OutputFrames};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+
+/// What recordings are started on to record the virtual output
+pub const VIRTUAL_OUTPUT_LABEL: &str = "virtual";
+
+/// The latest frame as RGBA, swapped whole when the slide changes; empty
+/// while stopped
+pub type VirtualFrame = Arc<Mutex<Arc<Vec<u8>>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VirtualOutputOptions {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for VirtualOutputOptions {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VirtualOutputStatus {
+    pub running: bool,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Managed state: the frame being drawn, and the bundle it's drawn from
+#[derive(Default)]
+pub struct VirtualOutput {
+    options: Mutex<Option<VirtualOutputOptions>>,
+    frame: VirtualFrame,
+    /// The bundle and slide being shown, kept so a restart at another size
+    /// can draw it again; None shows black
+    showing: Mutex<Option<(PathBuf, String)>>,
+    frames: Mutex<OutputFrames>,
+}
+
+impl VirtualOutput {
+    /// Start drawing, or start again at a new size
+    pub fn start(&self, options: VirtualOutputOptions) -> VirtualOutputStatus {
+        // Even sizes, which H.264 needs
+        let options = VirtualOutputOptions {
+            width: options.width.clamp(16, 7680) & !1,
+            height: options.height.clamp(16, 4320) & !1,
+        };
+        if let Ok(mut running) = self.options.lock() {
+            *running = Some(options);
+        }
+        self.set_frame(output_frames::black(options.width, options.height));
+        log::info!(
+            "Drawing the virtual output at {}x{}",
+            options.width,
+            options.height
+        );
+
+        let showing = self.showing.lock().ok().and_then(|showing| showing.clone());
+        if let Some((path, slide_id)) = showing {
+            if let Err(e) = self.show(&path, &slide_id) {
+                log::warn!("Could not draw the slide for the virtual output: {e}");
+            }
+        }
+        self.status()
+    }
+
+    /// Stop drawing
+    pub fn stop(&self) {
+        if let Ok(mut running) = self.options.lock() {
+            if running.take().is_some() {
+                log::info!("Stopped the virtual output");
+            }
+        }
+        self.set_frame(Vec::new());
+    }
+
+    /// Draw the slide with `slide_id` from the bundle at `path`; the bundle
+    /// is kept open for the next slide until it's saved again
+    pub fn show(&self, path: &Path, slide_id: &str) -> Result<(), CpresError> {
+        if let Ok(mut showing) = self.showing.lock() {
+            *showing = Some((path.to_path_buf(), slide_id.to_string()));
+        }
+        let Some(options) = self.options() else {
+            return Ok(());
+        };
+
+        let data = self
+            .frames
+            .lock()
+            .map_err(|e| CpresError::Io(std::io::Error::other(e.to_string())))?
+            .render(path, slide_id, options.width, options.height)?;
+        self.set_frame(data);
+        Ok(())
+    }
+
+    /// Draw black, as when the presentation is cleared
+    pub fn clear(&self) {
+        if let Ok(mut showing) = self.showing.lock() {
+            *showing = None;
+        }
+        if let Some(options) = self.options() {
+            self.set_frame(output_frames::black(options.width, options.height));
+        }
+    }
+
+    /// The size it's drawn at, while it's running
+    pub fn options(&self) -> Option<VirtualOutputOptions> {
+        self.options.lock().ok().and_then(|options| *options)
+    }
+
+    /// The latest frame, for recordings to read from
+    pub fn frame(&self) -> VirtualFrame {
+        self.frame.clone()
+    }
+
+    pub fn status(&self) -> VirtualOutputStatus {
+        let options = self.options();
+        let size = options.unwrap_or_default();
+        VirtualOutputStatus {
+            running: options.is_some(),
+            width: size.width,
+            height: size.height,
+        }
+    }
+
+    fn set_frame(&self, data: Vec<u8>) {
+        if let Ok(mut frame) = self.frame.lock() {
+            *frame = Arc::new(data);
+        }
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    app.manage(VirtualOutput::default());
+}
@@ -0,0 +1,165 @@
+//! Waveform peaks for scrub bars and in/out points
+//!
+//! ffmpeg decodes the audio of an audio or video file to mono samples, which
+//! are split into `resolution` equal buckets; each bucket keeps its lowest and
+//! highest sample. Peaks are cached as JSON in `waveforms/` in the app cache,
+//! named after the file's sha256 and the resolution, so a countdown track is
+//! only decoded the first time it's opened.
+
+use crate::cpres::{self, CpresError};
+use crate::export;
+use crate::ffmpeg;
+use crate::tasks::CancelToken;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+pub(crate) const CACHE_DIR_NAME: &str = "waveforms";
+
+/// Buckets when the caller doesn't ask for a number
+pub const DEFAULT_RESOLUTION: u32 = 1000;
+const MIN_RESOLUTION: u32 = 16;
+const MAX_RESOLUTION: u32 = 20_000;
+
+/// Low enough to decode quickly, high enough that short transients still show
+const SAMPLE_RATE: u32 = 8000;
+/// Samples read from ffmpeg at a time
+const CHUNK_SAMPLES: usize = 16 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Waveform {
+    /// Seconds
+    pub duration: f64,
+    /// Seconds covered by each bucket
+    pub bucket_seconds: f64,
+    /// `[min, max]` per bucket, between -1 and 1
+    pub peaks: Vec<[f32; 2]>,
+    /// False when the file had to be decoded
+    #[serde(skip_deserializing)]
+    pub cached: bool,
+}
+
+/// Peaks of the audio in `path` in `resolution` buckets, from the cache in
+/// `app_cache_dir` when they've been made before
+pub fn generate_waveform(
+    app_cache_dir: &Path,
+    path: &Path,
+    resolution: u32,
+    cancel: &CancelToken,
+) -> Result<Waveform, CpresError> {
+    let resolution = resolution.clamp(MIN_RESOLUTION, MAX_RESOLUTION);
+    let (digest, _) = cpres::hash_file(path, cancel)?;
+    let cache_path = cache_path(app_cache_dir, &digest, resolution);
+    if let Some(mut waveform) = read_cached(&cache_path) {
+        waveform.cached = true;
+        return Ok(waveform);
+    }
+
+    let duration = ffmpeg::duration(path)?
+        .filter(|seconds| *seconds > 0.0)
+        .ok_or_else(|| CpresError::Ffmpeg(format!("{} has no known length", path.display())))?;
+    let waveform = decode_peaks(path, duration, resolution, cancel)?;
+    export::write_atomic(&cache_path, &serde_json::to_vec(&waveform)?)?;
+    Ok(waveform)
+}
+
+fn cache_path(app_cache_dir: &Path, digest: &str, resolution: u32) -> PathBuf {
+    app_cache_dir
+        .join(CACHE_DIR_NAME)
+        .join(format!("{digest}-{resolution}.json"))
+}
+
+/// A cached waveform; a missing or unreadable file is just a cache miss
+fn read_cached(path: &Path) -> Option<Waveform> {
+    let data = std::fs::read(path).ok()?;
+    serde_json::from_slice(&data)
+        .inspect_err(|e| log::debug!("Ignoring cached waveform {}: {e}", path.display()))
+        .ok()
+}
+
+fn decode_peaks(
+    path: &Path,
+    duration: f64,
+    resolution: u32,
+    cancel: &CancelToken,
+) -> Result<Waveform, CpresError> {
+    let mut child = ffmpeg::command()?
+        .arg("-i")
+        .arg(path)
+        .args(["-vn", "-sn", "-dn", "-map", "0:a:0", "-ac", "1"])
+        .args(["-ar", &SAMPLE_RATE.to_string(), "-f", "f32le", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Drain stderr on its own thread so a chatty ffmpeg can't block the pipe
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let errors = std::thread::spawn(move || {
+        let mut errors = String::new();
+        let _ = stderr.read_to_string(&mut errors);
+        errors
+    });
+
+    // The reported length is only an estimate, so the last bucket may end up
+    // a little short or samples past it land in it
+    let expected_samples = (duration * SAMPLE_RATE as f64).ceil() as usize;
+    let bucket_samples = expected_samples.div_ceil(resolution as usize).max(1);
+    let mut peaks = Vec::with_capacity(resolution as usize);
+    let mut bucket = [f32::MAX, f32::MIN];
+    let mut in_bucket = 0;
+
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut buffer = vec![0u8; CHUNK_SAMPLES * 4];
+    let mut filled = 0;
+    let read = loop {
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            break Err(CpresError::Cancelled);
+        }
+        let count = match stdout.read(&mut buffer[filled..]) {
+            Ok(0) => break Ok(()),
+            Ok(count) => count,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => break Err(e.into()),
+        };
+        filled += count;
+        // Keep a partial sample for the next read
+        let whole = filled / 4 * 4;
+        for bytes in buffer[..whole].chunks_exact(4) {
+            let sample = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            bucket = [bucket[0].min(sample), bucket[1].max(sample)];
+            in_bucket += 1;
+            if in_bucket == bucket_samples && peaks.len() + 1 < resolution as usize {
+                peaks.push(bucket);
+                bucket = [f32::MAX, f32::MIN];
+                in_bucket = 0;
+            }
+        }
+        buffer.copy_within(whole..filled, 0);
+        filled -= whole;
+    };
+
+    let status = child.wait()?;
+    let errors = errors.join().unwrap_or_default();
+    read?;
+    if !status.success() {
+        return Err(CpresError::Ffmpeg(format!(
+            "waveform decoding failed ({status}): {}",
+            errors.trim()
+        )));
+    }
+    if in_bucket > 0 {
+        peaks.push(bucket);
+    }
+    let peaks: Vec<[f32; 2]> = peaks
+        .into_iter()
+        .map(|[min, max]| [min.clamp(-1.0, 1.0), max.clamp(-1.0, 1.0)])
+        .collect();
+    Ok(Waveform {
+        duration,
+        bucket_seconds: bucket_samples as f64 / SAMPLE_RATE as f64,
+        peaks,
+        cached: false,
+    })
+}
@@ -36,7 +36,14 @@ import {
   useWorkspaceStore,
 } from '@/lib/stores';
 import { createSongPresentation } from '@/lib/models';
-import type { Library, Playlist, SlideType, PresentationRef, Presentation } from '@/lib/models';
+import type {
+  Library,
+  Playlist,
+  SlideType,
+  PresentationRef,
+  Presentation,
+  OutputSignal,
+} from '@/lib/models';
 import type { Song, SetWithSongs } from '@/lib/supabase';
 import { fetchSongArrangementSlides, fetchSong } from '@/lib/musicManager';
 import {
@@ -51,6 +58,25 @@ import {
   closeOutputWindows,
   getMonitors,
   openOutputWindows,
+  setOutputAutoReopen,
+  setOutputGuard,
+  setOutputPointerConfined,
+  setOutputPrewarm,
+  setOutputWindowed,
+  setOutputTransforms,
+  setOutputSpan,
+  startNdiOutput,
+  stopNdiOutput,
+  enableTextureShare,
+  disableTextureShare,
+  startDeckLinkOutput,
+  stopDeckLinkOutput,
+  startVirtualOutput,
+  stopVirtualOutput,
+  setAudioRoute,
+  startRemoteServer,
+  stopRemoteServer,
+  setProgramLogo,
   openBundle,
   saveBundle,
   isContentDirUnderRepo,
@@ -221,6 +247,8 @@ export default function App() {
   const hasAppliedStartupSelectionRef = useRef(false);
   const hasCheckedUpdatesRef = useRef(false);
   const [monitors, setMonitors] = useState<MonitorInfo[]>([]);
+  const sharedTextureIdsRef = useRef(new Set<string>());
+  const deckLinkDevicesRef = useRef(new Set<string>());
   const outputWindowStateRef = useRef<{ enabled: boolean; configuredKey: string }>({
     enabled: false,
     configuredKey: '',
@@ -385,15 +413,204 @@ export default function App() {
     };
 
     fetchMonitors();
-    const interval = window.setInterval(fetchMonitors, 3000);
+    const unlisten = listen<MonitorInfo[]>('monitors:changed', (event) => {
+      if (active) setMonitors(event.payload);
+    });
     window.addEventListener('focus', fetchMonitors);
     return () => {
       active = false;
-      window.clearInterval(interval);
+      void unlisten.then((stop) => stop());
       window.removeEventListener('focus', fetchMonitors);
     };
   }, []);
 
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    void setOutputAutoReopen(settings.output.autoReopen).catch((error) => {
+      console.warn('Failed to set output auto-reopen:', error);
+    });
+  }, [settings.output.autoReopen]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    void setOutputPrewarm(settings.output.prewarm).catch((error) => {
+      console.warn('Failed to set output pre-warming:', error);
+    });
+  }, [settings.output.prewarm]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    void setOutputGuard(settings.output.keepInFront, settings.output.blockPopups).catch(
+      (error) => {
+        console.warn('Failed to set the output guard:', error);
+      }
+    );
+  }, [settings.output.keepInFront, settings.output.blockPopups]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    void setOutputPointerConfined(settings.output.confinePointer).catch((error) => {
+      console.warn('Failed to set output pointer confinement:', error);
+    });
+  }, [settings.output.confinePointer]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    void setOutputWindowed(settings.output.windowed).catch((error) => {
+      console.warn('Failed to set windowed outputs:', error);
+    });
+  }, [settings.output.windowed]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    void setOutputTransforms(settings.output.transforms).catch((error) => {
+      console.warn('Failed to set output transforms:', error);
+    });
+  }, [settings.output.transforms]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    void setOutputSpan(settings.output.span).catch((error) => {
+      console.warn('Failed to set the output span:', error);
+    });
+  }, [settings.output.span]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    const { enabled, ...options } = settings.output.ndi;
+    if (!enabled) {
+      void stopNdiOutput().catch((error) => {
+        console.warn('Failed to stop NDI output:', error);
+      });
+      return;
+    }
+    void startNdiOutput(options).catch((error) => {
+      console.warn('Failed to start NDI output:', error);
+    });
+  }, [settings.output.ndi]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    const shared = sharedTextureIdsRef.current;
+    const wanted = settings.output.textureShareIds;
+    for (const id of shared) {
+      if (!wanted.includes(id)) {
+        shared.delete(id);
+        void disableTextureShare(id).catch((error) => {
+          console.warn('Failed to stop sharing output:', error);
+        });
+      }
+    }
+    for (const id of wanted) {
+      if (!shared.has(id)) {
+        shared.add(id);
+        void enableTextureShare(id).catch((error) => {
+          shared.delete(id);
+          console.warn('Failed to share output:', error);
+        });
+      }
+    }
+  }, [settings.output.textureShareIds]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    const { enabled, device, formatCode, keyDevice } = settings.output.decklink;
+    // With a key output, the main output plays the fill for the keyer
+    const wanted = new Map<string, OutputSignal>();
+    if (enabled && device && formatCode) {
+      wanted.set(device, keyDevice && keyDevice !== device ? 'fill' : 'program');
+      if (keyDevice && keyDevice !== device) wanted.set(keyDevice, 'key');
+    }
+    const playing = deckLinkDevicesRef.current;
+    for (const playingDevice of playing) {
+      if (!wanted.has(playingDevice)) {
+        playing.delete(playingDevice);
+        void stopDeckLinkOutput(playingDevice).catch((error) => {
+          console.warn('Failed to stop DeckLink output:', error);
+        });
+      }
+    }
+    if (!formatCode) return;
+    for (const [wantedDevice, signal] of wanted) {
+      playing.add(wantedDevice);
+      void startDeckLinkOutput(wantedDevice, formatCode, signal).catch((error) => {
+        playing.delete(wantedDevice);
+        console.warn('Failed to start DeckLink output:', error);
+      });
+    }
+  }, [settings.output.decklink]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    const { enabled, ...options } = settings.output.virtualOutput;
+    if (!enabled) {
+      void stopVirtualOutput().catch((error) => {
+        console.warn('Failed to stop the virtual output:', error);
+      });
+      return;
+    }
+    void startVirtualOutput(options).catch((error) => {
+      console.warn('Failed to start the virtual output:', error);
+    });
+  }, [settings.output.virtualOutput]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    const { output, preview } = settings.output.audioRoutes;
+    void setAudioRoute('output', output).catch((error) => {
+      console.warn('Failed to route the output sound:', error);
+    });
+    void setAudioRoute('preview', preview).catch((error) => {
+      console.warn('Failed to route the preview sound:', error);
+    });
+  }, [settings.output.audioRoutes]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    const { enabled, ...options } = settings.integrations.remote;
+    if (!enabled) {
+      void stopRemoteServer().catch((error) => {
+        console.warn('Failed to stop the remote control server:', error);
+      });
+      return;
+    }
+    // Keep the same token across restarts, so paired remotes stay paired
+    if (!options.token) {
+      const { integrations } = useSettingsStore.getState().settings;
+      updateSettings({
+        integrations: {
+          ...integrations,
+          remote: { ...options, enabled, token: crypto.randomUUID().replace(/-/g, '') },
+        },
+      });
+      return;
+    }
+    void startRemoteServer(options).catch((error) => {
+      console.warn('Failed to start the remote control server:', error);
+    });
+  }, [settings.integrations.remote, updateSettings]);
+
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    void setProgramLogo(settings.output.logoPath).catch((error) => {
+      console.warn('Failed to set the logo:', error);
+    });
+  }, [settings.output.logoPath]);
+
   useEffect(() => {
     const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
     if (!isTauriApp) return;
@@ -417,9 +634,12 @@ export default function App() {
       return;
     }
 
-    const configured = (settings.output.monitorIds || [])
-      .map((id) => Number(id))
-      .filter((id) => monitors.some((monitor) => monitor.index === id));
+    // Saved ids are stable monitor ids, or positions from older settings
+    const configured = monitors.filter((monitor) =>
+      (settings.output.monitorIds || []).some(
+        (id) => id === monitor.id || id === String(monitor.index)
+      )
+    );
 
     if (configured.length === 0) {
       if (outputState.enabled) {
@@ -430,16 +650,37 @@ export default function App() {
       return;
     }
 
-    const normalizedConfigured = [...configured].sort((a, b) => a - b);
-    const configuredKey = normalizedConfigured.join(',');
+    // Mirrors of outputs that are connected, on monitors that are too
+    const configuredIds = configured.map((monitor) => monitor.id);
+    const mirrors = Object.fromEntries(
+      Object.entries(settings.output.mirrors).filter(
+        ([mirrorId, sourceId]) =>
+          configuredIds.includes(sourceId) &&
+          !configuredIds.includes(mirrorId) &&
+          monitors.some((monitor) => monitor.id === mirrorId)
+      )
+    );
+
+    // Includes positions so windows are moved when the monitors are reordered
+    const configuredKey = [
+      configured.map((monitor) => `${monitor.id}@${monitor.index}`).join(','),
+      Object.entries(mirrors)
+        .map(([mirrorId, sourceId]) => `${mirrorId}>${sourceId}`)
+        .join(','),
+    ].join('|');
     if (outputState.enabled && outputState.configuredKey === configuredKey) {
       return;
     }
 
     outputState.enabled = true;
     outputState.configuredKey = configuredKey;
-    void openOutputWindows(normalizedConfigured);
-  }, [monitors, settings.output.audienceEnabled, settings.output.monitorIds]);
+    void openOutputWindows(configuredIds, mirrors);
+  }, [
+    monitors,
+    settings.output.audienceEnabled,
+    settings.output.monitorIds,
+    settings.output.mirrors,
+  ]);
 
   useEffect(() => {
     const shouldBeLive = settings.output.audienceEnabled && !!presentation;
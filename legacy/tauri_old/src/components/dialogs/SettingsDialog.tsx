@@ -277,14 +277,18 @@ export function SettingsDialog({ open, onOpenChange }: SettingsDialogProps) {
                           preserveAspectRatio="xMidYMid meet"
                         >
                           {monitors.map((monitor) => {
-                            const isSelected = selectedMonitors.includes(String(monitor.index));
-                            const monitorId = String(monitor.index);
+                            const monitorId = monitor.id;
+                            // Older settings saved positions instead of ids
+                            const legacyId = String(monitor.index);
+                            const isSelected =
+                              selectedMonitors.includes(monitorId) ||
+                              selectedMonitors.includes(legacyId);
                             const fontSize = Math.max(
                               36,
                               Math.min(monitor.width, monitor.height) * 0.28
                             );
                             return (
-                              <g key={monitor.index}>
+                              <g key={monitor.id}>
                                 <rect
                                   x={monitor.x}
                                   y={monitor.y}
@@ -295,7 +299,7 @@ export function SettingsDialog({ open, onOpenChange }: SettingsDialogProps) {
                                   tabIndex={0}
                                   onClick={() => {
                                     const next = isSelected
-                                      ? selectedMonitors.filter((id) => id !== monitorId)
+                                      ? selectedMonitors.filter((id) => id !== monitorId && id !== legacyId)
                                       : [...selectedMonitors, monitorId];
                                     handleMonitorSelection(next);
                                   }}
@@ -303,7 +307,7 @@ export function SettingsDialog({ open, onOpenChange }: SettingsDialogProps) {
                                     if (event.key === 'Enter' || event.key === ' ') {
                                       event.preventDefault();
                                       const next = isSelected
-                                        ? selectedMonitors.filter((id) => id !== monitorId)
+                                        ? selectedMonitors.filter((id) => id !== monitorId && id !== legacyId)
                                         : [...selectedMonitors, monitorId];
                                       handleMonitorSelection(next);
                                     }
@@ -2,12 +2,13 @@
  * RightPanel - Preview panel and tools tabs
  */
 
-import { ChevronLeft, ChevronRight, Presentation, Film, Undo2 } from 'lucide-react';
+import { AlertTriangle, ChevronLeft, ChevronRight, Presentation, Film, Undo2 } from 'lucide-react';
 import { Button } from '@/components/ui/button';
 import { ButtonGroup, ButtonGroupSeparator } from '@/components/ui/button-group';
 import { OutputStage, type OutputMediaLayer } from '@/components/output/OutputStage';
 import { Switch } from '@/components/ui/switch';
-import { useCallback, useMemo } from 'react';
+import { useCallback, useEffect, useMemo, useState } from 'react';
+import { listen } from '@tauri-apps/api/event';
 import { useLiveStore, useEditorStore, useSettingsStore, useShowStore } from '@/lib/stores';
 import { Tooltip, TooltipContent, TooltipTrigger } from '@/components/ui/tooltip';
 import {
@@ -20,8 +21,12 @@ import {
 import { useResolvedMediaUrl } from '@/lib/media/resolveMediaUrl';
 import { cn } from '@/lib/utils';
 import type { MediaLayersState, MediaLayerId } from '@/lib/models';
+import type { OutputUnhealthy } from '@/lib/tauri-api';
 import { getBackgroundMediaId, resolveSlideBackground } from '@/lib/models';
 
+// How long a recreated output stays flagged
+const UNHEALTHY_NOTICE_MS = 10000;
+
 interface RightPanelProps {
   onOpenOutputSettings?: () => void;
   collapsed: boolean;
@@ -52,6 +57,23 @@ export function RightPanel({ onOpenOutputSettings: _onOpenOutputSettings, collap
     canUndoClearMedia,
   } = useLiveStore();
   
+  // The last output the watchdog found dead, while it's worth pointing out
+  const [unhealthyOutput, setUnhealthyOutput] = useState<OutputUnhealthy | null>(null);
+  useEffect(() => {
+    const isTauriApp = '__TAURI_INTERNALS__' in window || '__TAURI__' in window;
+    if (!isTauriApp) return;
+    let timer: number | undefined;
+    const unlisten = listen<OutputUnhealthy>('output:unhealthy', (event) => {
+      setUnhealthyOutput(event.payload);
+      window.clearTimeout(timer);
+      timer = window.setTimeout(() => setUnhealthyOutput(null), UNHEALTHY_NOTICE_MS);
+    });
+    return () => {
+      void unlisten.then((stop) => stop());
+      window.clearTimeout(timer);
+    };
+  }, []);
+
   // Provide defaults in case state is not yet initialized
   const mediaLayers = rawMediaLayers ?? { mediaUnderlay: null, mediaOverlay: null, audio: null };
   const suppress = rawSuppress ?? { presentation: false, media: false };
@@ -215,6 +237,21 @@ export function RightPanel({ onOpenOutputSettings: _onOpenOutputSettings, collap
             className="h-6 w-10 p-1 data-[state=checked]:bg-green-500 data-[state=unchecked]:bg-red-500"
           />
           <span className="text-sm font-semibold">Audience</span>
+          {unhealthyOutput && (
+            <Tooltip>
+              <TooltipTrigger asChild>
+                <AlertTriangle className="h-4 w-4 text-amber-500" aria-label="Output restarted" />
+              </TooltipTrigger>
+              <TooltipContent side="bottom">
+                <p>
+                  {unhealthyOutput.reason === 'closed'
+                    ? 'An output window closed'
+                    : 'An output stopped responding'}
+                  {unhealthyOutput.recreated ? ' and was reopened' : " and couldn't be reopened"}
+                </p>
+              </TooltipContent>
+            </Tooltip>
+          )}
         </div>
         <Tooltip>
           <TooltipTrigger asChild>
@@ -258,6 +295,7 @@ export function RightPanel({ onOpenOutputSettings: _onOpenOutputSettings, collap
                 onClearPresentationComplete={handleClearPresentationComplete}
                 onClearMediaComplete={handleClearMediaComplete}
                 resolvedBackgroundSrc={resolvedBackgroundSrc}
+                audioContext="preview"
                 className="w-full h-full pointer-events-none select-none"
               />
             </div>
@@ -0,0 +1,78 @@
+/**
+ * NativeVideo - a video decoded by the app rather than the webview, drawn
+ * onto a canvas frame by frame as the player in Rust shows them
+ *
+ * The video is decoded at the canvas's size in device pixels; each request
+ * waits for the frame after the last one drawn. `onError` is called when it
+ * can't be played, so the caller can fall back to a <video>.
+ */
+
+import { useEffect, useRef } from 'react';
+import { loadVideo, unloadVideo, videoFrameUrl, type VideoSource } from '@/lib/tauri-api';
+import { cn } from '@/lib/utils';
+
+interface NativeVideoProps {
+  source: VideoSource;
+  loop?: boolean;
+  className?: string;
+  style?: React.CSSProperties;
+  onError?: (error: unknown) => void;
+}
+
+export function NativeVideo({ source, loop = true, className, style, onError }: NativeVideoProps) {
+  const { bundle, path } = source;
+  const canvasRef = useRef<HTMLCanvasElement>(null);
+  const onErrorRef = useRef(onError);
+  onErrorRef.current = onError;
+
+  useEffect(() => {
+    const canvas = canvasRef.current;
+    const context = canvas?.getContext('2d');
+    if (!canvas || !context) return;
+
+    const playerId = crypto.randomUUID();
+    const url = videoFrameUrl(playerId);
+    let cancelled = false;
+
+    const draw = async () => {
+      const rect = canvas.getBoundingClientRect();
+      const width = Math.round(Math.max(rect.width, 1) * window.devicePixelRatio);
+      await loadVideo(playerId, { bundle, path }, { width, loop, autoplay: true });
+      // Unmounted while it loaded, after the unload was sent
+      if (cancelled) {
+        await unloadVideo(playerId);
+        return;
+      }
+
+      let after = 0;
+      while (!cancelled) {
+        const response = await fetch(`${url}?after=${after}`, { cache: 'no-store' });
+        if (response.status === 204) continue;
+        if (!response.ok) throw new Error(`No frame for video ${playerId}: ${response.status}`);
+        const frameWidth = Number(response.headers.get('X-Width'));
+        const frameHeight = Number(response.headers.get('X-Height'));
+        after = Number(response.headers.get('X-Sequence')) || after;
+        const data = new Uint8ClampedArray(await response.arrayBuffer());
+        if (cancelled || data.length !== frameWidth * frameHeight * 4) continue;
+        if (canvas.width !== frameWidth || canvas.height !== frameHeight) {
+          canvas.width = frameWidth;
+          canvas.height = frameHeight;
+        }
+        context.putImageData(new ImageData(data, frameWidth, frameHeight), 0, 0);
+      }
+    };
+
+    void draw().catch((error) => {
+      if (cancelled) return;
+      console.warn('Failed to play video natively:', error);
+      onErrorRef.current?.(error);
+    });
+
+    return () => {
+      cancelled = true;
+      void unloadVideo(playerId).catch(() => {});
+    };
+  }, [bundle, path, loop]);
+
+  return <canvas ref={canvasRef} className={cn(className)} style={style} />;
+}
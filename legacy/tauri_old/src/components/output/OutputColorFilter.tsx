@@ -0,0 +1,47 @@
+/**
+ * OutputColorFilter - an SVG filter with an output's color correction, for
+ * `filter: url(#id)` on the output; CSS filters have no gamma
+ */
+
+import type { OutputColor } from '@/lib/models';
+
+interface OutputColorFilterProps {
+  id: string;
+  color: OutputColor;
+}
+
+export function isNeutralColor(color: OutputColor | null): boolean {
+  return (
+    !color ||
+    (color.brightness === 0 &&
+      color.contrast === 1 &&
+      color.gamma === 1 &&
+      color.red === 1 &&
+      color.green === 1 &&
+      color.blue === 1)
+  );
+}
+
+export function OutputColorFilter({ id, color }: OutputColorFilterProps) {
+  // amplitude * x^exponent + offset, with contrast around mid gray
+  const exponent = 1 / color.gamma;
+  const offset = (1 - color.contrast) / 2 + color.brightness;
+  const channel = (gain: number) => ({
+    type: 'gamma' as const,
+    amplitude: gain * color.contrast,
+    exponent,
+    offset,
+  });
+
+  return (
+    <svg aria-hidden="true" className="absolute h-0 w-0">
+      <filter id={id} colorInterpolationFilters="sRGB">
+        <feComponentTransfer>
+          <feFuncR {...channel(color.red)} />
+          <feFuncG {...channel(color.green)} />
+          <feFuncB {...channel(color.blue)} />
+        </feComponentTransfer>
+      </filter>
+    </svg>
+  );
+}
@@ -0,0 +1,124 @@
+/**
+ * OutputSpanFrame - shows this output's slice of a picture spanning several
+ * projectors, faded where it overlaps its neighbours
+ */
+
+import { useEffect, useMemo, useState, type ReactNode } from 'react';
+import type { BlendEdge, SpanSlice } from '@/lib/models';
+import { computeOutputLayout } from '@/components/output/OutputTransformFrame';
+
+interface OutputSpanFrameProps {
+  slice: SpanSlice;
+  /** Width over height of the program */
+  programAspect: number;
+  children: ReactNode;
+}
+
+// Enough stops that the gradient's straight segments don't show as bands
+const BLEND_STOPS = 24;
+
+/**
+ * How much of this side's light is left at `t` across the overlap, from 0
+ * at the outer edge to 1 where the overlap ends
+ */
+function blendWeight(edge: BlendEdge, t: number): number {
+  return edge.curve === 'linear' ? t : 0.5 - 0.5 * Math.cos(Math.PI * t);
+}
+
+/**
+ * Black over the overlap, thinned out so the light this side gives follows
+ * the blend curve once the projector's gamma is applied
+ */
+function blendGradient(edge: BlendEdge, direction: 'to right' | 'to left'): string {
+  const stops = Array.from({ length: BLEND_STOPS + 1 }, (_, i) => {
+    const t = i / BLEND_STOPS;
+    const alpha = 1 - Math.pow(blendWeight(edge, t), 1 / edge.gamma);
+    return `rgba(0, 0, 0, ${alpha.toFixed(4)}) ${(t * 100).toFixed(2)}%`;
+  });
+  return `linear-gradient(${direction}, ${stops.join(', ')})`;
+}
+
+export function OutputSpanFrame({ slice, programAspect, children }: OutputSpanFrameProps) {
+  const [viewport, setViewport] = useState(() => ({
+    width: window.innerWidth,
+    height: window.innerHeight,
+  }));
+
+  useEffect(() => {
+    const handleResize = () => {
+      setViewport({ width: window.innerWidth, height: window.innerHeight });
+    };
+    window.addEventListener('resize', handleResize);
+    return () => window.removeEventListener('resize', handleResize);
+  }, []);
+
+  // CSS pixels per physical pixel of the slice
+  const ratio = viewport.width / Math.max(1, slice.width);
+  const canvas = {
+    width: slice.canvasWidth * ratio,
+    height: slice.canvasHeight * ratio,
+  };
+  const layout = useMemo(
+    () =>
+      computeOutputLayout(
+        {
+          aspect: null,
+          crop: null,
+          scale: slice.scale,
+          margins: { top: 0, right: 0, bottom: 0, left: 0 },
+          rotation: 0,
+        },
+        canvas,
+        programAspect,
+        1
+      ),
+    [slice.scale, canvas.width, canvas.height, programAspect]
+  );
+  const { program, scaleX } = layout;
+
+  return (
+    <div className="absolute inset-0 overflow-hidden bg-black">
+      <div
+        className="absolute"
+        style={{
+          left: -slice.x * ratio,
+          top: -slice.y * ratio,
+          width: canvas.width,
+          height: canvas.height,
+        }}
+      >
+        <div
+          className="absolute"
+          style={{
+            left: program.left,
+            top: program.top,
+            width: program.width,
+            height: program.height,
+            transform: scaleX === 1 ? undefined : `scaleX(${scaleX})`,
+            transformOrigin: 'top left',
+          }}
+        >
+          {children}
+        </div>
+      </div>
+      {slice.left && slice.left.overlap > 0 && (
+        <div
+          className="pointer-events-none absolute inset-y-0 left-0 z-30"
+          style={{
+            width: slice.left.overlap * ratio,
+            backgroundImage: blendGradient(slice.left, 'to right'),
+          }}
+        />
+      )}
+      {slice.right && slice.right.overlap > 0 && (
+        <div
+          className="pointer-events-none absolute inset-y-0 right-0 z-30"
+          style={{
+            width: slice.right.overlap * ratio,
+            backgroundImage: blendGradient(slice.right, 'to left'),
+          }}
+        />
+      )}
+    </div>
+  );
+}
@@ -7,6 +7,10 @@
  * 3. Media Overlay (between background and slide elements)
  * 4. Slide Elements (text, shapes, etc.)
  * 5. Blackout/Clear overlays
+ *
+ * For a switcher's keyer, the fill and key signals leave out the underlay
+ * and background so the lyrics sit on black; the key then draws everything
+ * left in white, keeping its opacity.
  */
 
 import { useEffect, useMemo, useRef } from 'react';
@@ -17,11 +21,14 @@ import type {
   Background,
   OutputLayerMedia,
   MediaLayersState,
+  OutputSignal,
   SuppressState,
   Slide,
   SlideTransitionType,
 } from '@/lib/models';
 import { getBackgroundStyle, resolveSlideBackground } from '@/lib/models';
+import { AudioSinkContext, applyAudioSink, useAudioSink, useAudioSinkContext } from '@/lib/media/audioSink';
+import type { AudioContext, VideoSource } from '@/lib/tauri-api';
 import { cn } from '@/lib/utils';
 
 export interface OutputMediaLayer extends OutputLayerMedia {
@@ -35,6 +42,9 @@ interface OutputStageProps {
   slideSize?: { width: number; height: number };
   isBlackout?: boolean;
   isClear?: boolean;
+  // Logo screen, with the image shown for it
+  isLogo?: boolean;
+  logoSrc?: string | null;
   visibleLayerIds?: string[];
   className?: string;
   // New layer model
@@ -47,8 +57,17 @@ interface OutputStageProps {
   onClearMediaComplete?: () => void;
   // Resolved background media source (for image/video backgrounds)
   resolvedBackgroundSrc?: string | null;
+  // Bundle entry of a video background to decode natively, when it is
+  nativeBackgroundSource?: VideoSource | null;
+  // Whole slide, or the fill or key for a keyer
+  signal?: OutputSignal;
+  // Where its sound is routed: the output windows, or the operator's preview
+  audioContext?: AudioContext;
 }
 
+// Color gone, opacity kept: white over the black stage where it's opaque
+const KEY_STYLE = { filter: 'brightness(0) invert(1)' };
+
 export function OutputStage({
   slide,
   aspectRatio,
@@ -56,6 +75,8 @@ export function OutputStage({
   slideSize,
   isBlackout = false,
   isClear = false,
+  isLogo = false,
+  logoSrc,
   visibleLayerIds,
   className,
   mediaLayers,
@@ -64,6 +85,9 @@ export function OutputStage({
   onClearPresentationComplete,
   onClearMediaComplete,
   resolvedBackgroundSrc,
+  nativeBackgroundSource,
+  signal = 'program',
+  audioContext = 'output',
 }: OutputStageProps) {
   const outputAspectClass = getAspectClass(outputAspectRatio ?? aspectRatio);
   const suppressPresentation = suppress.presentation;
@@ -150,15 +174,18 @@ export function OutputStage({
   const showPresentation = !suppressPresentation;
   // Show media content if not suppressed (even while clearing, to show exit animation)
   const showMedia = !suppressMedia;
+  const showBackdrop = signal === 'program';
+  const layerStyle = signal === 'key' ? KEY_STYLE : undefined;
+  const sink = useAudioSink(audioContext);
 
-  return (
+  const stage = (
     <div className={cn('relative w-full h-full bg-black overflow-hidden', className)}>
       <div className="absolute inset-0 flex items-center justify-center">
         <div className={cn('relative w-full max-h-full', outputAspectClass)}>
           {/* 1. Media Underlay - behind everything, only visible through transparent backgrounds */}
           <div className="absolute inset-0">
             <AnimatePresence mode="wait">
-              {showMedia && mediaUnderlay && !clearingMedia && (
+              {showBackdrop && showMedia && mediaUnderlay && !clearingMedia && (
                 <motion.div
                   key="media-underlay"
                   className="w-full h-full"
@@ -176,7 +203,7 @@ export function OutputStage({
 
           {/* 2. Presentation Background - solid/gradient/image/video */}
           <AnimatePresence mode="wait">
-            {showPresentation && !clearingPresentation && slide && (
+            {showBackdrop && showPresentation && !clearingPresentation && slide && (
               <motion.div
                 key={`presentation-bg-${slide.id}`}
                 className="absolute inset-0"
@@ -190,6 +217,7 @@ export function OutputStage({
                 <BackgroundMedia
                   background={effectiveBackground}
                   src={backgroundSrc}
+                  nativeSource={suppressPresentation ? null : nativeBackgroundSource}
                   className="absolute inset-0"
                 />
               </motion.div>
@@ -197,7 +225,7 @@ export function OutputStage({
           </AnimatePresence>
 
           {/* 3. Media Overlay - between background and slide elements */}
-          <div className="absolute inset-0">
+          <div className="absolute inset-0" style={layerStyle}>
             <AnimatePresence mode="wait">
               {showMedia && mediaOverlay && !clearingMedia && (
                 <motion.div
@@ -216,9 +244,9 @@ export function OutputStage({
           </div>
 
           {/* 4. Slide Elements - text, shapes, media layers, etc. */}
-          <div className="absolute inset-0">
+          <div className="absolute inset-0" style={layerStyle}>
             <AnimatePresence mode="wait">
-              {showPresentation && !clearingPresentation && slide && (
+              {showPresentation && !clearingPresentation && !isClear && slide && (
                 <motion.div
                   key={slide.id}
                   className="w-full h-full"
@@ -243,17 +271,32 @@ export function OutputStage({
             </AnimatePresence>
           </div>
 
-          {/* 5. Blackout / Clear overlays */}
+          {/* 5. Blackout / Logo overlays (clear just drops the slide elements) */}
           {isBlackout && <div className="absolute inset-0 bg-black" />}
-          {isClear && (
+          {isLogo && (
             <div className="absolute inset-0 bg-black flex items-center justify-center">
-              <div className="text-white/20 text-2xl font-light">Church Presenter</div>
+              {logoSrc ? (
+                <img
+                  src={logoSrc}
+                  alt=""
+                  className="h-full w-full object-contain"
+                  style={layerStyle}
+                  draggable={false}
+                />
+              ) : (
+                showBackdrop && (
+                  <div className="text-white/20 text-2xl font-light">Church Presenter</div>
+                )
+              )}
             </div>
           )}
         </div>
       </div>
     </div>
   );
+
+  // Videos in the stage play their sound on the device chosen for it
+  return <AudioSinkContext.Provider value={sink}>{stage}</AudioSinkContext.Provider>;
 }
 
 // Get variants for different transition types (for clearing animation)
@@ -338,6 +381,7 @@ function getAspectClass(aspectRatio?: '16:9' | '4:3' | '16:10'): string {
 }
 
 function MediaLayer({ media }: { media: OutputMediaLayer | null }) {
+  const sink = useAudioSinkContext();
   if (!media || media.mediaType === 'audio') return null;
   const src = media.src ?? media.mediaId;
   if (!src) return null;
@@ -357,10 +401,11 @@ function MediaLayer({ media }: { media: OutputMediaLayer | null }) {
   if (media.mediaType === 'video') {
     return (
       <video
+        ref={(element) => applyAudioSink(element, sink)}
         src={src}
         className={mediaClass}
         loop={media.loop}
-        muted={media.muted}
+        muted={media.muted || sink === null}
         autoPlay={media.autoplay}
         playsInline
       />
@@ -0,0 +1,150 @@
+/**
+ * OutputTransformFrame - letterboxes, crops, scales and turns the program for
+ * one output's display, such as a 4:3 projector, an LED wall processor or a
+ * side screen hung in portrait
+ */
+
+import { useEffect, useMemo, useState, type ReactNode } from 'react';
+import type { OutputTransform } from '@/lib/models';
+
+interface OutputTransformFrameProps {
+  transform: OutputTransform | null;
+  /** Width over height of the program */
+  programAspect: number;
+  children: ReactNode;
+}
+
+interface Rect {
+  left: number;
+  top: number;
+  width: number;
+  height: number;
+}
+
+export interface OutputLayout {
+  /** The picture, inside the margins; the program is clipped to it */
+  area: Rect;
+  /** The whole program, uncropped, relative to the area */
+  program: Rect;
+  /** Horizontal stretch applied to the program on top of its size */
+  scaleX: number;
+}
+
+/**
+ * Where the program goes in a viewport of the given CSS pixel size. The
+ * picture is laid out at the transform's aspect and then squeezed into the
+ * area, the way the display stretches it back out.
+ */
+export function computeOutputLayout(
+  transform: OutputTransform | null,
+  viewport: { width: number; height: number },
+  programAspect: number,
+  pixelRatio: number
+): OutputLayout {
+  const margins = transform?.margins ?? { top: 0, right: 0, bottom: 0, left: 0 };
+  const area: Rect = {
+    left: margins.left / pixelRatio,
+    top: margins.top / pixelRatio,
+    width: Math.max(1, viewport.width - (margins.left + margins.right) / pixelRatio),
+    height: Math.max(1, viewport.height - (margins.top + margins.bottom) / pixelRatio),
+  };
+
+  // The picture as the audience sees it, then its pixels as the window has them
+  const pictureHeight = area.height;
+  const pictureWidth = transform?.aspect ? pictureHeight * transform.aspect : area.width;
+  const squeeze = area.width / pictureWidth;
+
+  const crop = transform?.crop ?? { x: 0, y: 0, width: 1, height: 1 };
+  const shownWidth = programAspect * crop.width;
+  const shownHeight = crop.height;
+  let sx = pictureWidth / shownWidth;
+  let sy = pictureHeight / shownHeight;
+  if (transform?.scale !== 'stretch') {
+    const uniform = transform?.scale === 'fill' ? Math.max(sx, sy) : Math.min(sx, sy);
+    sx = uniform;
+    sy = uniform;
+  }
+
+  const left = (pictureWidth - shownWidth * sx) / 2 - crop.x * programAspect * sx;
+  const top = (pictureHeight - shownHeight * sy) / 2 - crop.y * sy;
+  return {
+    area,
+    program: {
+      left: left * squeeze,
+      top,
+      width: programAspect * sy,
+      height: sy,
+    },
+    scaleX: (sx * squeeze) / sy,
+  };
+}
+
+export function OutputTransformFrame({
+  transform,
+  programAspect,
+  children,
+}: OutputTransformFrameProps) {
+  const [viewport, setViewport] = useState(() => ({
+    width: window.innerWidth,
+    height: window.innerHeight,
+  }));
+
+  useEffect(() => {
+    const handleResize = () => {
+      setViewport({ width: window.innerWidth, height: window.innerHeight });
+    };
+    window.addEventListener('resize', handleResize);
+    return () => window.removeEventListener('resize', handleResize);
+  }, []);
+
+  // The picture is laid out upright, then turned to fit the window
+  const rotation = transform?.rotation ?? 0;
+  const sideways = rotation === 90 || rotation === 270;
+  const upright = useMemo(
+    () => (sideways ? { width: viewport.height, height: viewport.width } : viewport),
+    [sideways, viewport]
+  );
+  const layout = useMemo(
+    () => computeOutputLayout(transform, upright, programAspect, window.devicePixelRatio || 1),
+    [transform, upright, programAspect]
+  );
+
+  if (!transform) {
+    return <>{children}</>;
+  }
+
+  const { area, program, scaleX } = layout;
+  return (
+    <div className="absolute inset-0 overflow-hidden bg-black">
+      <div
+        className="absolute"
+        style={{
+          left: (viewport.width - upright.width) / 2,
+          top: (viewport.height - upright.height) / 2,
+          width: upright.width,
+          height: upright.height,
+          transform: rotation === 0 ? undefined : `rotate(${rotation}deg)`,
+        }}
+      >
+        <div
+          className="absolute overflow-hidden"
+          style={{ left: area.left, top: area.top, width: area.width, height: area.height }}
+        >
+          <div
+            className="absolute"
+            style={{
+              left: program.left,
+              top: program.top,
+              width: program.width,
+              height: program.height,
+              transform: scaleX === 1 ? undefined : `scaleX(${scaleX})`,
+              transformOrigin: 'top left',
+            }}
+          >
+            {children}
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+}
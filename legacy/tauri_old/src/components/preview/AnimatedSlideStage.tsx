@@ -12,6 +12,7 @@ import {
   getLayerContentStyle,
   getLayerTransformStyle,
 } from '@/components/preview/slide-layer-content';
+import { applyAudioSink, useAudioSinkContext } from '@/lib/media/audioSink';
 import { cn } from '@/lib/utils';
 import { getBackgroundStyle, resolveSlideBackground } from '@/lib/models';
 
@@ -586,6 +587,7 @@ function ShapeLayerRenderer({ layer }: { layer: ShapeLayer }) {
 }
 
 function MediaLayerRenderer({ layer }: { layer: MediaLayer }) {
+  const sink = useAudioSinkContext();
   const style: React.CSSProperties = {
     width: '100%',
     height: '100%',
@@ -595,10 +597,11 @@ function MediaLayerRenderer({ layer }: { layer: MediaLayer }) {
   if (layer.mediaType === 'video') {
     return (
       <video
+        ref={(element) => applyAudioSink(element, sink)}
         src={layer.mediaId}
         style={style}
         loop={layer.loop}
-        muted={layer.muted}
+        muted={layer.muted || sink === null}
         autoPlay={layer.autoplay}
         playsInline
       />
@@ -1,16 +1,26 @@
+import { useState } from 'react';
+import { NativeVideo } from '@/components/output/NativeVideo';
 import type { Background } from '@/lib/models';
+import { applyAudioSink, useAudioSinkContext } from '@/lib/media/audioSink';
+import type { VideoSource } from '@/lib/tauri-api';
 import { cn } from '@/lib/utils';
 
 interface BackgroundMediaProps {
   background?: Background | null;
   src?: string | null;
+  // Plays a video background natively rather than in the webview, falling
+  // back to it when that fails
+  nativeSource?: VideoSource | null;
   className?: string;
 }
 
 const clamp = (value: number, min = 0, max = 1) =>
   Number.isFinite(value) ? Math.min(max, Math.max(min, value)) : min;
 
-export function BackgroundMedia({ background, src, className }: BackgroundMediaProps) {
+export function BackgroundMedia({ background, src, nativeSource, className }: BackgroundMediaProps) {
+  const [nativeFailed, setNativeFailed] = useState(false);
+  const sink = useAudioSinkContext();
+
   if (!background) return null;
 
   if (background.type === 'image') {
@@ -36,14 +46,31 @@ export function BackgroundMedia({ background, src, className }: BackgroundMediaP
   }
 
   if (background.type === 'video') {
+    const opacity = clamp(background.opacity ?? 1);
+    if (nativeSource && !nativeFailed && (background.muted ?? true)) {
+      return (
+        <NativeVideo
+          source={nativeSource}
+          loop={background.loop ?? true}
+          className={cn(className)}
+          style={{
+            width: '100%',
+            height: '100%',
+            objectFit: background.fit ?? 'cover',
+            opacity,
+          }}
+          onError={() => setNativeFailed(true)}
+        />
+      );
+    }
     const mediaSrc = src ?? background.mediaId;
     if (!mediaSrc) return null;
-    const opacity = clamp(background.opacity ?? 1);
     return (
       <video
+        ref={(element) => applyAudioSink(element, sink)}
         src={mediaSrc}
         loop={background.loop ?? true}
-        muted={background.muted ?? true}
+        muted={(background.muted ?? true) || sink === null}
         autoPlay
         playsInline
         className={cn(className)}
@@ -2,6 +2,7 @@ import { useEffect, useMemo, useRef, useState } from 'react';
 import type { Layer, MediaLayer, ShapeLayer, TextLayer, VectorLayer, WebLayer } from '@/lib/models';
 import { ScaledWebLayer } from '@/components/preview/ScaledWebLayer';
 import { toRgba } from '@/lib/color-utils';
+import { applyAudioSink, useAudioSinkContext } from '@/lib/media/audioSink';
 import {
   getStrokeInset,
   getPrimaryStroke,
@@ -292,6 +293,7 @@ function ShapeLayerContent({ layer, textScale }: { layer: ShapeLayer; textScale:
 }
 
 function MediaLayerContent({ layer }: { layer: MediaLayer }) {
+  const sink = useAudioSinkContext();
   const style: React.CSSProperties = {
     width: '100%',
     height: '100%',
@@ -301,10 +303,11 @@ function MediaLayerContent({ layer }: { layer: MediaLayer }) {
   if (layer.mediaType === 'video') {
     return (
       <video
+        ref={(element) => applyAudioSink(element, sink)}
         src={layer.mediaId}
         style={style}
         loop={layer.loop}
-        muted={layer.muted}
+        muted={layer.muted || sink === null}
         autoPlay={layer.autoplay}
         playsInline
       />
@@ -0,0 +1,93 @@
+/**
+ * Sending media sound to the device chosen for where it plays
+ *
+ * The routes are kept in Rust by device name (see `setAudioRoute`), while the
+ * webview knows its devices by ids of its own, so they're matched by label.
+ * When the chosen device can't be found here, the media is muted rather than
+ * played on the default device, which is usually the PA.
+ */
+
+import { createContext, useContext, useEffect, useState } from 'react';
+import { listen } from '@tauri-apps/api/event';
+import { getAudioRoutes, type AudioContext, type AudioRoutes } from '@/lib/tauri-api';
+
+/** Sink id to play on: '' for the default device, null when the chosen one is missing */
+export type AudioSink = string | null;
+
+/** The sink for media inside it; the default device outside any provider */
+export const AudioSinkContext = createContext<AudioSink>('');
+
+export function useAudioSinkContext(): AudioSink {
+  return useContext(AudioSinkContext);
+}
+
+function isTauriEnvironment() {
+  return (
+    typeof window !== 'undefined' &&
+    ('__TAURI_INTERNALS__' in window || '__TAURI__' in window)
+  );
+}
+
+async function findSink(device: string | null): Promise<AudioSink> {
+  if (!device) return '';
+  const devices = (await navigator.mediaDevices?.enumerateDevices?.()) ?? [];
+  const outputs = devices.filter((found) => found.kind === 'audiooutput' && found.label);
+  const match =
+    outputs.find((found) => found.label === device) ??
+    outputs.find((found) => found.label.includes(device) || device.includes(found.label));
+  return match?.deviceId ?? null;
+}
+
+/** The sink chosen for `context`, following changes to the routes and devices */
+export function useAudioSink(context: AudioContext): AudioSink {
+  const [sink, setSink] = useState<AudioSink>('');
+
+  useEffect(() => {
+    if (!isTauriEnvironment()) return;
+    let cancelled = false;
+    let device: string | null = null;
+
+    const match = () => {
+      void findSink(device).then((found) => {
+        if (cancelled) return;
+        if (found === null) {
+          console.warn(`Audio device "${device}" not found; muting ${context} media`);
+        }
+        setSink(found);
+      });
+    };
+    const apply = (routes: AudioRoutes) => {
+      device = routes[context];
+      match();
+    };
+
+    void getAudioRoutes()
+      .then(apply)
+      .catch((error) => {
+        console.warn('Failed to get the audio routes:', error);
+      });
+    const unlisten = listen<AudioRoutes>('audio:routes', (event) => apply(event.payload));
+    navigator.mediaDevices?.addEventListener?.('devicechange', match);
+
+    return () => {
+      cancelled = true;
+      void unlisten.then((stop) => stop());
+      navigator.mediaDevices?.removeEventListener?.('devicechange', match);
+    };
+  }, [context]);
+
+  return sink;
+}
+
+/** Send the sound of `element` to `sink`; a missing device is left to muting */
+export function applyAudioSink(element: HTMLMediaElement | null, sink: AudioSink) {
+  if (!element || sink === null) return;
+  const media = element as HTMLMediaElement & {
+    sinkId?: string;
+    setSinkId?: (sinkId: string) => Promise<void>;
+  };
+  if (!media.setSinkId || media.sinkId === sink) return;
+  void media.setSinkId(sink).catch((error) => {
+    console.warn('Failed to route media sound:', error);
+  });
+}
@@ -741,6 +741,41 @@ export const defaultAppSettings: AppSettings = {
   output: {
     monitorIds: [],
     audienceEnabled: false,
+    autoReopen: true,
+    prewarm: false,
+    keepInFront: true,
+    blockPopups: false,
+    confinePointer: false,
+    windowed: {},
+    transforms: {},
+    span: null,
+    mirrors: {},
+    ndi: {
+      enabled: false,
+      name: 'Church Presenter',
+      width: 1920,
+      height: 1080,
+      frameRate: 30,
+    },
+    textureShareIds: [],
+    monitorSignals: {},
+    decklink: {
+      enabled: false,
+      device: null,
+      formatCode: null,
+      keyDevice: null,
+    },
+    virtualOutput: {
+      enabled: false,
+      width: 1920,
+      height: 1080,
+    },
+    nativeVideo: false,
+    audioRoutes: {
+      output: null,
+      preview: null,
+    },
+    logoPath: null,
     scaling: 'fit',
     aspectRatio: '16:9',
     clearGroups: [],
@@ -769,6 +804,12 @@ export const defaultAppSettings: AppSettings = {
       defaultSongAction: 'import',
       preferSetImportView: false,
     },
+    remote: {
+      enabled: false,
+      port: 8765,
+      token: null,
+      lan: false,
+    },
   },
   theme: 'system',
   recentFiles: [],
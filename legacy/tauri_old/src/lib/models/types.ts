@@ -17,9 +17,33 @@ export interface MediaEntry {
   sha256: string;
   byteSize: number;
   type: MediaType;
-  width?: number; // For images/videos
+  width?: number; // For images/videos, as displayed
   height?: number;
+  orientation?: number; // EXIF orientation (2-8) of a photo stored rotated or mirrored
   duration?: number; // For videos/audio in seconds
+  frameRate?: number; // For videos
+  videoCodec?: string; // ffmpeg codec name, e.g. "h264"
+  audioCodec?: string;
+  audioChannels?: number;
+  loudness?: number; // For audio: EBU R128 integrated loudness in LUFS
+  truePeak?: number; // dBTP
+  loudnessRange?: number; // LU
+  gain?: number; // Playback gain in dB from loudness normalization
+  blurhash?: string; // Placeholder shown while the thumbnail loads
+  trimStart?: number; // In point in seconds, for audio/video
+  trimEnd?: number; // Out point in seconds
+  trimmedFrom?: { sha256: string; start: number; end: number | null }; // Set once a trim was baked in on save
+  attribution?: MediaAttribution; // For stock media downloaded from a provider
+}
+
+// Credit for stock media, as the provider asks for it
+export interface MediaAttribution {
+  provider: string; // "Unsplash" or "Pexels"
+  author: string;
+  authorUrl?: string;
+  sourceUrl: string; // The photo or video's page on the provider's site
+  license: string;
+  text: string; // Ready to show, e.g. "Photo by Jane Doe on Unsplash"
 }
 
 export type FontStyleType = 'normal' | 'italic';
@@ -35,6 +59,18 @@ export interface FontEntry {
   byteSize: number;
   weight: number;
   style: FontStyleType;
+  /** License and embedding permissions read from the font itself */
+  license?: FontLicense | null;
+  /** Set when the license doesn't allow sharing the font inside a bundle */
+  embeddingWarning?: string | null;
+}
+
+export interface FontLicense {
+  copyright?: string | null;
+  license?: string | null;
+  licenseUrl?: string | null;
+  embedding?: 'installable' | 'editable' | 'preview-and-print' | 'restricted' | null;
+  subsettingAllowed: boolean;
 }
 
 // ============================================================================
@@ -610,11 +646,158 @@ export interface Playlist {
 export interface OutputSettings {
   monitorIds: string[];
   audienceEnabled: boolean;
+  /** Put output windows back when their monitor is reconnected or moved */
+  autoReopen: boolean;
+  /** Keep output windows loaded and hidden while closed, so they show at once */
+  prewarm: boolean;
+  /** Put output windows back in front when something covers or minimizes them */
+  keepInFront: boolean;
+  /** Keep system pop-ups such as notifications off the outputs where the system allows it */
+  blockPopups: boolean;
+  /** Keep the mouse cursor from crossing onto the outputs */
+  confinePointer: boolean;
+  /** Outputs shown in a window instead of fullscreen, by monitor id */
+  windowed: Record<string, OutputWindowGeometry>;
+  /** How outputs fit the program to their display, by monitor id */
+  transforms: Record<string, OutputTransform>;
+  /** Outputs making one wide picture, blended where they overlap */
+  span: OutputSpan | null;
+  /** Monitors showing exactly what an output does, mapped to that output's monitor id */
+  mirrors: Record<string, string>;
+  /** Send the slides as an NDI source for livestream computers */
+  ndi: NdiOutputSettings;
+  /** Outputs (monitor ids) shared as Spout or Syphon textures */
+  textureShareIds: string[];
+  /** Monitors showing the fill or key for a switcher's keyer, by monitor id */
+  monitorSignals: Record<string, OutputSignal>;
+  /** Play out of a Blackmagic DeckLink card over SDI */
+  decklink: DeckLinkOutputSettings;
+  /** Draw the program with no window, for recording an online-only service */
+  virtualOutput: VirtualOutputSettings;
+  /** Device the output windows and the operator's preview play sound on, by name; null for the system's default */
+  audioRoutes: AudioRouteSettings;
+  /** Decode muted background videos in the app rather than the webview, which stutters on large ones */
+  nativeVideo: boolean;
+  /** Image the outputs show when switched to the logo */
+  logoPath: string | null;
   scaling: 'fit' | 'fill';
   aspectRatio: '16:9' | '4:3' | '16:10';
   clearGroups: OutputClearGroup[];
 }
 
+/** Physical pixels from the monitor's top-left corner */
+export interface OutputWindowGeometry {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+/** How the program is fitted into an output's picture */
+export type OutputScale = 'fit' | 'fill' | 'stretch';
+
+/** Letterboxing, cropping and scaling for one output */
+export interface OutputTransform {
+  /** Width over height of the picture the display shows; null for the window's shape */
+  aspect: number | null;
+  /** Part of the program shown, as fractions of its size; null for all of it */
+  crop: { x: number; y: number; width: number; height: number } | null;
+  scale: OutputScale;
+  /** Physical pixels kept black at each edge of the picture as it's seen */
+  margins: { top: number; right: number; bottom: number; left: number };
+  /** Degrees the picture is turned clockwise, for displays mounted on their side */
+  rotation: OutputRotation;
+}
+
+export type OutputRotation = 0 | 90 | 180 | 270;
+
+/** How a side's brightness falls off across a projector overlap */
+export type BlendCurve = 'linear' | 'smooth';
+
+/** Where two neighbouring outputs of a span overlap */
+export interface BlendEdge {
+  /** Physical pixels the two outputs share */
+  overlap: number;
+  curve: BlendCurve;
+  /** The projectors' gamma, 1 to 3, which the fade is corrected for */
+  gamma: number;
+}
+
+/** Outputs making one picture across several projectors */
+export interface OutputSpan {
+  /** Monitor ids, left to right */
+  outputs: string[];
+  /** Between each output and the next */
+  edges: BlendEdge[];
+  /** How the program is fitted into the whole picture */
+  scale: OutputScale;
+}
+
+/** An output's part of a span, in physical pixels */
+export interface SpanSlice {
+  canvasWidth: number;
+  canvasHeight: number;
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+  scale: OutputScale;
+  left: BlendEdge | null;
+  right: BlendEdge | null;
+}
+
+/**
+ * Color correction for one output, kept on the Rust side with the computer
+ * rather than in the settings; each channel goes through
+ * `gain * contrast * x^(1 / gamma) + (1 - contrast) / 2 + brightness`
+ */
+export interface OutputColor {
+  /** -1 to 1 */
+  brightness: number;
+  /** 0 to 3 */
+  contrast: number;
+  /** 0.1 to 4; above 1 lifts the midtones */
+  gamma: number;
+  /** Channel gains, 0 to 2 */
+  red: number;
+  green: number;
+  blue: number;
+}
+
+export interface NdiOutputSettings {
+  enabled: boolean;
+  name: string;
+  width: number;
+  height: number;
+  frameRate: number;
+}
+
+export interface DeckLinkOutputSettings {
+  enabled: boolean;
+  device: string | null;
+  /** ffmpeg's display mode code, such as 'Hp30' */
+  formatCode: string | null;
+  /** A second output playing the key, making `device` play the fill */
+  keyDevice: string | null;
+}
+
+export interface VirtualOutputSettings {
+  enabled: boolean;
+  width: number;
+  height: number;
+}
+
+export interface AudioRouteSettings {
+  output: string | null;
+  preview: string | null;
+}
+
+/**
+ * What an output shows: the whole slide, or for keying lyrics over cameras,
+ * the foreground on black (fill) or its opacity in grayscale (key)
+ */
+export type OutputSignal = 'program' | 'fill' | 'key';
+
 export interface EditorSettings {
   autosaveInterval: number; // seconds, 0 = disabled
   autoSaveEnabled: boolean; // master toggle for auto-save
@@ -642,6 +825,15 @@ export interface IntegrationsSettings {
     defaultSongAction: 'import' | 'link';
     preferSetImportView: boolean;
   };
+  /** Control from phones and tablets on the local network */
+  remote: {
+    enabled: boolean;
+    port: number;
+    /** Remotes have to give it to connect; made up when it's first enabled */
+    token: string | null;
+    /** Reachable from other devices; only from this computer otherwise */
+    lan: boolean;
+  };
 }
 
 export interface AppSettings {
@@ -730,6 +922,42 @@ export interface OutputClearGroup {
   layers: OutputControlGroup[];
 }
 
+// ============================================================================
+// Program State Types (kept on the Rust side)
+// ============================================================================
+
+/** What covers the slides on every output */
+export type ProgramScreen = 'live' | 'blackout' | 'logo' | 'clear';
+
+/** Where the live presentation is at, as the main window works it out */
+export interface ProgramLiveState {
+  isLive: boolean;
+  presentationId: string | null;
+  presentationPath: string | null;
+  currentSlideId: string | null;
+  currentSlideIndex: number;
+  currentBuildIndex: number;
+  totalBuildSteps: number;
+  visibleLayerIds: string[];
+  mediaLayers: MediaLayersState;
+  suppress: SuppressState;
+  isClearing: { presentation: boolean; media: boolean };
+}
+
+export interface ProgramState extends ProgramLiveState {
+  /** Goes up by one with every change */
+  revision: number;
+  screen: ProgramScreen;
+  /** Image shown for the logo screen */
+  logoPath: string | null;
+}
+
+export interface ProgramPresentation {
+  revision: number;
+  presentation: Presentation | null;
+  presentationPath: string | null;
+}
+
 // ============================================================================
 // Editor State Types
 // ============================================================================
@@ -52,6 +52,7 @@ import {
 import {
   importFontFiles,
   openBundle,
+  releaseBundleLock,
   saveBundle,
   type FontFileRef,
   type MediaFileRef,
@@ -59,6 +60,14 @@ import {
 } from '../tauri-api';
 import { getDocumentsDataDirPath } from '../services/appDataService';
 
+/** Let other machines edit a bundle again once the editor has moved off it */
+function releaseLockFor(previousPath: string | null, nextPath: string | null) {
+  if (!previousPath || previousPath === nextPath) return;
+  void releaseBundleLock(previousPath).catch((error) => {
+    console.warn('Failed to release the bundle lock:', error);
+  });
+}
+
 // ============================================================================
 // Auto-Save Status Types
 // ============================================================================
@@ -603,6 +612,7 @@ export const useEditorStore = create<EditorState>()(
 
     newPresentation: (title: string) => {
       const presentation = createPresentation(title);
+      releaseLockFor(get().filePath, null);
       set((state) => {
         state.presentation = presentation;
         state.filePath = null;
@@ -634,6 +644,7 @@ export const useEditorStore = create<EditorState>()(
 
       presentation = ensureValidPresentation(presentation);
 
+      releaseLockFor(get().filePath, resolvedPath);
       set((state) => {
         state.presentation = presentation;
         state.filePath = resolvedPath;
@@ -717,6 +728,8 @@ export const useEditorStore = create<EditorState>()(
         }
 
         await saveBundle(savePath, updatedPresentation, mediaRefs, fontRefs);
+        // Saved as another file: the lock moves with it
+        releaseLockFor(filePath, savePath);
 
         set((state) => {
           state.presentation = updatedPresentation;
@@ -741,6 +754,7 @@ export const useEditorStore = create<EditorState>()(
     },
 
     closePresentation: () => {
+      releaseLockFor(get().filePath, null);
       set((state) => {
         state.presentation = null;
         state.filePath = null;
@@ -1856,6 +1870,7 @@ export const useEditorStore = create<EditorState>()(
     },
 
     markSaved: (filePath: string) => {
+      releaseLockFor(get().filePath, filePath);
       set((state) => {
         state.filePath = filePath;
         state.isDirty = false;
@@ -4,7 +4,7 @@
 
 import { create } from 'zustand';
 import { immer } from 'zustand/middleware/immer';
-import { emit, listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import type {
   Presentation,
   Slide,
@@ -14,7 +14,18 @@ import type {
   OutputLayerMedia,
   SuppressState,
   OutputControlGroup,
+  ProgramLiveState,
+  ProgramScreen,
+  ProgramState,
 } from '../models';
+import {
+  getProgramPresentation,
+  getProgramState,
+  setLiveState,
+  setProgramPresentation,
+  setProgramScreen,
+  type RemoteCommand,
+} from '../tauri-api';
 
 // State saved when clearing for undo
 export interface ClearedPresentationState {
@@ -27,28 +38,6 @@ export interface ClearedMediaState {
   mediaLayers: MediaLayersState;
 }
 
-// Event types for IPC
-export interface LiveStateEvent {
-  presentationId: string | null;
-  currentSlideId: string | null;
-  currentSlideIndex: number;
-  currentBuildIndex: number;
-  totalBuildSteps: number;
-  visibleLayerIds: string[];
-  isBlackout: boolean;
-  isClear: boolean;
-  mediaLayers: MediaLayersState;
-  suppress: SuppressState;
-  // Clearing animation state
-  isClearing: { presentation: boolean; media: boolean };
-}
-
-export interface LivePresentationEvent {
-  presentation: Presentation | null;
-  slideId: string | null;
-  presentationPath: string | null;
-}
-
 interface LiveState {
   // State
   isLive: boolean;
@@ -59,6 +48,7 @@ interface LiveState {
   currentBuildIndex: number; // -1 means no builds triggered yet, 0+ means that build step is active
   isBlackout: boolean;
   isClear: boolean;
+  isLogo: boolean;
   mediaLayers: MediaLayersState;
   suppress: SuppressState;
   
@@ -106,8 +96,10 @@ interface LiveState {
   // Display controls
   setBlackout: (enabled: boolean) => void;
   setClear: (enabled: boolean) => void;
+  setLogo: (enabled: boolean) => void;
   toggleBlackout: () => void;
   toggleClear: () => void;
+  toggleLogo: () => void;
   clearPresentation: () => void;
   clearMedia: () => void;
   undoClearPresentation: () => string | null;  // Returns restored slideId or null
@@ -118,10 +110,11 @@ interface LiveState {
   _finishClearPresentation: () => void;
   _finishClearMedia: () => void;
 
-  // Event handling
+  // Program state, kept on the Rust side for every window
   emitState: () => void;
   emitPresentation: () => void;
-  emitSlide: () => void;
+  emitScreen: () => void;
+  _applyScreen: (screen: ProgramScreen) => void;
   setupListeners: () => Promise<UnlistenFn>;
   remapPresentationPath: (oldBase: string, newBase: string) => void;
 }
@@ -215,6 +208,7 @@ export const useLiveStore = create<LiveState>()(
     currentBuildIndex: -1,
     isBlackout: false,
     isClear: false,
+    isLogo: false,
     mediaLayers: { ...DEFAULT_MEDIA_LAYERS },
     suppress: { ...DEFAULT_SUPPRESS },
     
@@ -290,6 +284,7 @@ export const useLiveStore = create<LiveState>()(
         state.currentBuildIndex = -1; // Reset build index
         state.isBlackout = false;
         state.isClear = false;
+        state.isLogo = false;
         state.mediaLayers = { ...DEFAULT_MEDIA_LAYERS };
         state.suppress = { ...DEFAULT_SUPPRESS };
         state.isClearing = { presentation: false, media: false };
@@ -303,7 +298,6 @@ export const useLiveStore = create<LiveState>()(
 
       get().emitState();
       get().emitPresentation();
-      get().emitSlide();
     },
 
     endLive: async () => {
@@ -316,6 +310,7 @@ export const useLiveStore = create<LiveState>()(
         state.currentBuildIndex = -1;
         state.isBlackout = false;
         state.isClear = false;
+        state.isLogo = false;
         state._visibleLayerIds = [];
         state.mediaLayers = { ...DEFAULT_MEDIA_LAYERS };
         state.suppress = { ...DEFAULT_SUPPRESS };
@@ -326,7 +321,6 @@ export const useLiveStore = create<LiveState>()(
 
       get().emitState();
       get().emitPresentation();
-      get().emitSlide();
     },
 
     goToSlide: (slideId: string) => {
@@ -342,6 +336,7 @@ export const useLiveStore = create<LiveState>()(
         state.currentBuildIndex = -1; // Reset build index when changing slides
         state.isBlackout = false;
         state.isClear = false;
+        state.isLogo = false;
         state.suppress = { ...DEFAULT_SUPPRESS };
         state.isClearing = { presentation: false, media: false };
         // Clear undo state when navigating to a new slide
@@ -352,7 +347,7 @@ export const useLiveStore = create<LiveState>()(
       });
 
       get().emitState();
-      get().emitSlide();
+      get().emitScreen();
     },
 
     goToSlideIndex: (index: number) => {
@@ -368,6 +363,7 @@ export const useLiveStore = create<LiveState>()(
         state.currentBuildIndex = -1; // Reset build index when changing slides
         state.isBlackout = false;
         state.isClear = false;
+        state.isLogo = false;
         state.suppress = { ...DEFAULT_SUPPRESS };
         state.isClearing = { presentation: false, media: false };
         // Clear undo state when navigating to a new slide
@@ -377,7 +373,7 @@ export const useLiveStore = create<LiveState>()(
       });
 
       get().emitState();
-      get().emitSlide();
+      get().emitScreen();
     },
 
     nextSlideAction: () => {
@@ -450,19 +446,18 @@ export const useLiveStore = create<LiveState>()(
     },
 
     setBlackout: (enabled: boolean) => {
-      set((state) => {
-        state.isBlackout = enabled;
-        if (enabled) state.isClear = false;
-      });
-      get().emitState();
+      get()._applyScreen(enabled ? 'blackout' : 'live');
+      get().emitScreen();
     },
 
     setClear: (enabled: boolean) => {
-      set((state) => {
-        state.isClear = enabled;
-        if (enabled) state.isBlackout = false;
-      });
-      get().emitState();
+      get()._applyScreen(enabled ? 'clear' : 'live');
+      get().emitScreen();
+    },
+
+    setLogo: (enabled: boolean) => {
+      get()._applyScreen(enabled ? 'logo' : 'live');
+      get().emitScreen();
     },
 
     toggleBlackout: () => {
@@ -473,6 +468,10 @@ export const useLiveStore = create<LiveState>()(
       get().setClear(!get().isClear);
     },
 
+    toggleLogo: () => {
+      get().setLogo(!get().isLogo);
+    },
+
     clearPresentation: () => {
       const { currentSlideId, currentSlideIndex, currentBuildIndex, suppress, isClearing } = get();
       
@@ -505,7 +504,6 @@ export const useLiveStore = create<LiveState>()(
         state._visibleLayerIds = [];
       });
       get().emitState();
-      get().emitSlide();
     },
 
     clearMedia: () => {
@@ -570,7 +568,6 @@ export const useLiveStore = create<LiveState>()(
       }
       
       get().emitState();
-      get().emitSlide();
       
       // Return the slideId so the caller can restore selectedSlideId
       return slideId;
@@ -602,64 +599,106 @@ export const useLiveStore = create<LiveState>()(
 
     emitState: () => {
       const {
+        isLive,
         presentation,
+        presentationPath,
         currentSlideId,
         currentSlideIndex,
         currentBuildIndex,
         totalBuildSteps,
         visibleLayerIds,
-        isBlackout,
-        isClear,
         mediaLayers,
         suppress,
         isClearing,
       } = get();
 
-      const event: LiveStateEvent = {
+      const state: ProgramLiveState = {
+        isLive,
         presentationId: presentation?.manifest.presentationId || null,
+        presentationPath,
         currentSlideId,
         currentSlideIndex,
         currentBuildIndex,
         totalBuildSteps,
         visibleLayerIds,
-        isBlackout,
-        isClear,
         mediaLayers,
         suppress,
         isClearing,
       };
 
-      emit('live:state', event);
+      void setLiveState(state).catch((error) => {
+        console.warn('Failed to update the program state:', error);
+      });
     },
 
     emitPresentation: () => {
-      const { presentation, currentSlideId, presentationPath } = get();
-      const event: LivePresentationEvent = {
-        presentation: presentation ? JSON.parse(JSON.stringify(presentation)) : null,
-        slideId: currentSlideId,
-        presentationPath,
-      };
+      const { presentation, presentationPath } = get();
+      void setProgramPresentation(presentation, presentationPath).catch((error) => {
+        console.warn('Failed to update the program presentation:', error);
+      });
+    },
 
-      emit('live:presentation', event);
+    emitScreen: () => {
+      const { isBlackout, isClear, isLogo } = get();
+      const screen: ProgramScreen = isBlackout
+        ? 'blackout'
+        : isLogo
+          ? 'logo'
+          : isClear
+            ? 'clear'
+            : 'live';
+      void setProgramScreen(screen).catch((error) => {
+        console.warn('Failed to update the program screen:', error);
+      });
     },
 
-    emitSlide: () => {
-      const { currentSlideId } = get();
-      emit('live:slide', currentSlideId);
+    _applyScreen: (screen: ProgramScreen) => {
+      set((state) => {
+        state.isBlackout = screen === 'blackout';
+        state.isClear = screen === 'clear';
+        state.isLogo = screen === 'logo';
+      });
     },
 
     setupListeners: async () => {
-      const unlistenState = await listen('live:request-state', () => {
-        get().emitState();
-        get().emitPresentation();
-        get().emitSlide();
+      // Blackout, logo and clear can also be switched from remotes
+      const unlistenState = await listen<ProgramState>('program:state', (event) => {
+        get()._applyScreen(event.payload.screen);
       });
+      // After a reload, carry on with what the outputs are still showing
+      try {
+        const [program, live] = await Promise.all([getProgramState(), getProgramPresentation()]);
+        if (program.isLive && live.presentation && !get().isLive) {
+          set((state) => {
+            state.isLive = true;
+            state.presentation = live.presentation;
+            state.presentationPath = live.presentationPath;
+            state.currentSlideId = program.currentSlideId;
+            state.currentSlideIndex = program.currentSlideIndex;
+            state.currentBuildIndex = program.currentBuildIndex;
+            state.mediaLayers = program.mediaLayers;
+            state.suppress = program.suppress;
+            state.isClearing = program.isClearing;
+            updateVisibleLayerIds(state);
+          });
+        }
+        get()._applyScreen(program.screen);
+      } catch (error) {
+        console.warn('Failed to restore the program state:', error);
+      }
       const unlistenNext = await listen('live:next', () => {
         get().nextSlideAction();
       });
       const unlistenPrevious = await listen('live:previous', () => {
         get().previousSlideAction();
       });
+      // Slide changes from remotes are carried out like the operator's own
+      const unlistenRemote = await listen<RemoteCommand>('remote:command', (event) => {
+        const command = event.payload;
+        if (command.type === 'next') get().nextSlideAction();
+        else if (command.type === 'previous') get().previousSlideAction();
+        else if (command.type === 'goTo') get().goToSlideIndex(command.index);
+      });
       // Listen for clearing animation completion from output window
       const unlistenFinishClearPresentation = await listen('live:finish-clear-presentation', () => {
         get()._finishClearPresentation();
@@ -672,6 +711,7 @@ export const useLiveStore = create<LiveState>()(
         unlistenState();
         unlistenNext();
         unlistenPrevious();
+        unlistenRemote();
         unlistenFinishClearPresentation();
         unlistenFinishClearMedia();
       };
@@ -25,6 +25,16 @@ const getSettingsStore = () => {
 
 const mergeSettings = (stored?: AppSettings | null): AppSettings => {
   const output = { ...defaultAppSettings.output, ...stored?.output };
+  output.ndi = { ...defaultAppSettings.output.ndi, ...stored?.output?.ndi };
+  output.decklink = { ...defaultAppSettings.output.decklink, ...stored?.output?.decklink };
+  output.virtualOutput = {
+    ...defaultAppSettings.output.virtualOutput,
+    ...stored?.output?.virtualOutput,
+  };
+  output.audioRoutes = {
+    ...defaultAppSettings.output.audioRoutes,
+    ...stored?.output?.audioRoutes,
+  };
   const legacyMonitorId = (stored?.output as { monitorId?: string } | undefined)?.monitorId;
   if ((!output.monitorIds || output.monitorIds.length === 0) && legacyMonitorId) {
     output.monitorIds = [legacyMonitorId];
@@ -44,6 +54,10 @@ const mergeSettings = (stored?: AppSettings | null): AppSettings => {
         ...defaultAppSettings.integrations.musicManager,
         ...stored?.integrations?.musicManager,
       },
+      remote: {
+        ...defaultAppSettings.integrations.remote,
+        ...stored?.integrations?.remote,
+      },
     },
     updates: { ...defaultAppSettings.updates, ...stored?.updates },
   };
@@ -2,8 +2,22 @@
  * Tauri API wrappers for Church Presenter
  */
 
-import { invoke } from '@tauri-apps/api/core';
-import type { Presentation, MediaEntry, FontEntry } from './models';
+import { convertFileSrc, invoke as tauriInvoke } from '@tauri-apps/api/core';
+import type {
+  Presentation,
+  MediaEntry,
+  FontEntry,
+  OutputSignal,
+  OutputWindowGeometry,
+  OutputTransform,
+  OutputColor,
+  OutputSpan,
+  SpanSlice,
+  ProgramLiveState,
+  ProgramPresentation,
+  ProgramScreen,
+  ProgramState,
+} from './models';
 
 // ============================================================================
 // Types from Rust
@@ -34,6 +48,15 @@ export interface MediaFileRef {
   id: string;
   source_path: string;
   bundle_path: string;
+  trim?: MediaTrim;
+}
+
+/** In and out points of audio or video, in seconds */
+export interface MediaTrim {
+  start?: number;
+  end?: number;
+  /** Store only the trimmed part in the bundle on save */
+  render?: boolean;
 }
 
 export interface FontFileRef {
@@ -42,16 +65,155 @@ export interface FontFileRef {
   bundle_path: string;
 }
 
+export interface SkippedFile {
+  path: string;
+  reason: string;
+}
+
+/** Options for scaling down oversized images on import */
+export interface ImageOptimizeOptions {
+  /** Longest edge to keep; defaults to 3840 */
+  maxDimension?: number;
+  /** 'auto' picks JPEG for opaque images and WebP for transparent ones */
+  format?: 'auto' | 'jpeg' | 'webp';
+  /** Also store the original under `media/originals/` */
+  keepOriginal?: boolean;
+  /** Animated GIFs become looping clips; 'auto' picks WebM for transparent ones and MP4 otherwise */
+  animatedGifs?: 'auto' | 'mp4' | 'webm' | 'off';
+}
+
+export interface LoudnessOptions {
+  /** Integrated loudness to aim for in LUFS; defaults to -16 */
+  target?: number;
+  /** Highest true peak after the gain in dBTP; defaults to -1 */
+  maxTruePeak?: number;
+  /** 'gain' stores a playback gain; 'render' imports a normalized AAC copy */
+  mode?: 'gain' | 'render';
+}
+
+/** An audio track that was replaced by a copy at the target loudness */
+export interface NormalizedImport {
+  /** The file to copy into the bundle instead of the one picked */
+  source_path: string;
+  original_mime: string;
+  original_byte_size: number;
+  original_loudness: number;
+  /** Gain applied in dB */
+  gain: number;
+}
+
+/** An image that was replaced by a converted (HEIC, AVIF, animated GIF) or scaled-down copy */
+export interface OptimizedImport {
+  /** The file to copy into the bundle instead of the one picked */
+  source_path: string;
+  original_mime: string;
+  original_width: number;
+  original_height: number;
+  original_byte_size: number;
+  /** Bundle path for the original when it's kept */
+  original_path: string | null;
+}
+
+export interface MediaImportResult {
+  entries: MediaEntry[];
+  /** Keyed by entry id */
+  optimized: Record<string, OptimizedImport>;
+  /** Keyed by entry id */
+  normalized: Record<string, NormalizedImport>;
+  /** Keyed by entry id: paths of library files with the same contents */
+  inLibrary: Record<string, string[]>;
+  /** Files that couldn't be imported; the rest of the batch still was */
+  skipped: SkippedFile[];
+}
+
+/** Payload of the `media:import-progress` event */
+export interface MediaImportProgress {
+  path: string;
+  finished: number;
+  total: number;
+  error: string | null;
+}
+
+export interface FontAxis {
+  /** 'wght', 'wdth', 'ital', 'slnt', 'opsz', or a custom axis */
+  tag: string;
+  name?: string | null;
+  min: number;
+  default: number;
+  max: number;
+  hidden: boolean;
+}
+
 export interface SystemFontInfo {
   family: string;
   full_name: string;
   postscript_name?: string | null;
   path: string;
+  /** Position in a font collection (.ttc) */
+  font_index: number;
   weight: number;
   style: 'normal' | 'italic';
+  /** Variation axes of variable fonts, without names */
+  axes: FontAxis[];
+  /** In the app's own fonts folder (Google Fonts downloads) */
+  downloaded?: boolean;
+}
+
+export interface FontDetails {
+  family: string;
+  full_name: string;
+  postscript_name?: string | null;
+  weight: number;
+  style: 'normal' | 'italic';
+  axes: FontAxis[];
+  instances: {
+    name?: string | null;
+    postscript_name?: string | null;
+    coordinates: Record<string, number>;
+  }[];
+}
+
+export interface FontCoverage {
+  /** False when the font is neither embedded in the bundle nor installed */
+  found: boolean;
+  embedded: boolean;
+  /** Characters the font has no glyph for, in order of first appearance */
+  missing: { character: string; code_point: string; count: number }[];
+}
+
+export interface MissingFont {
+  family: string;
+  /** Names of the themes that use it */
+  themes: string[];
+  /** Slides that set it directly */
+  slide_count: number;
+  /** Installed fonts to use instead, best first */
+  suggestions: {
+    family: string;
+    reason: 'same-family' | 'metric-compatible' | 'similar-name' | 'generic';
+  }[];
+}
+
+export interface GoogleFont {
+  family: string;
+  category: string;
+  /** 'regular', 'italic', '700', '700italic', ... */
+  variants: string[];
+  subsets: string[];
+  installed: boolean;
+}
+
+export interface FontPreview {
+  /** Cached PNG of the sample text */
+  path: string;
+  width: number;
+  height: number;
+  cached: boolean;
 }
 
 export interface MonitorInfo {
+  /** Stays the same when displays are unplugged or reordered */
+  id: string;
   index: number;
   name: string;
   width: number;
@@ -62,6 +224,294 @@ export interface MonitorInfo {
   refresh_rate?: number | null;
 }
 
+export interface NdiOptions {
+  /** Source name shown in OBS and vMix, after the computer's name */
+  name?: string;
+  width?: number;
+  height?: number;
+  frameRate?: number;
+}
+
+export interface NdiStatus {
+  running: boolean;
+  /** Whether the NDI runtime is installed */
+  runtime_available: boolean;
+  name: string;
+  width: number;
+  height: number;
+  frame_rate: number;
+  /** Receivers showing the source */
+  connections: number;
+}
+
+export interface TextureShareOptions {
+  /** Sender name shown in Resolume, MadMapper, ... */
+  name?: string;
+  /** The output monitor's size when not given */
+  width?: number;
+  height?: number;
+}
+
+export interface SharedOutput {
+  output_id: string;
+  name: string;
+  width: number;
+  height: number;
+  technology: 'spout' | 'syphon';
+}
+
+export interface OutputCapture {
+  width: number;
+  height: number;
+  /** Where the PNG was saved, when a path was given */
+  path: string | null;
+  /** The PNG itself, when no path was given */
+  png: number[] | null;
+}
+
+export interface RecordingOptions {
+  frameRate?: number;
+  /** H.264 quality (CRF); lower is better and larger, 23 by default */
+  quality?: number;
+  /** Record the computer's audio too */
+  systemAudio?: boolean;
+  /**
+   * Loopback device to record it from: needed on Windows (such as 'Stereo
+   * Mix') and macOS (such as 'BlackHole 2ch'); a PulseAudio source on Linux,
+   * where the default output is used without one
+   */
+  audioDevice?: string | null;
+}
+
+export interface RecordingStatus {
+  state: 'idle' | 'recording' | 'paused';
+  path: string | null;
+  /** Time recorded, not counting pauses */
+  seconds: number;
+  bytes: number;
+  free_bytes: number | null;
+}
+
+/** Payload of `recording:stopped`, when a recording ends by itself */
+export interface RecordingStopped {
+  path: string | null;
+  reason: string;
+}
+
+/** Payload of `output:unhealthy`, when an output window died and was reopened */
+export interface OutputUnhealthy {
+  monitorId: string;
+  reason: 'closed' | 'unresponsive';
+  /** Whether a new window could be opened for it */
+  recreated: boolean;
+}
+
+export interface DeckLinkFormat {
+  /** ffmpeg's code for the display mode, such as 'Hp30' */
+  code: string;
+  width: number;
+  height: number;
+  /** Frames per second as a fraction, such as '30000/1001' */
+  frame_rate: string;
+  interlaced: boolean;
+  description: string;
+}
+
+export interface DeckLinkPlayout {
+  device: string;
+  format: DeckLinkFormat;
+  signal: OutputSignal;
+  /** False once playout stopped on its own, as when the card is unplugged */
+  running: boolean;
+}
+
+export interface VirtualOutputOptions {
+  width?: number;
+  height?: number;
+}
+
+export interface VirtualOutputStatus {
+  running: boolean;
+  width: number;
+  height: number;
+}
+
+/** A video file, or a video stored in a bundle when `bundle` is given */
+export interface VideoSource {
+  path: string;
+  bundle?: string | null;
+}
+
+export interface PlaybackOptions {
+  /** Size the frames are decoded at; the video's own shape when only one is given */
+  width?: number;
+  height?: number;
+  loop?: boolean;
+  autoplay?: boolean;
+  /** Where to start, in seconds */
+  start?: number;
+}
+
+/** Sent with `video:state` whenever a player changes */
+export interface VideoStatus {
+  playerId: string;
+  playing: boolean;
+  looping: boolean;
+  /** The frame shown, counted from the start of the video */
+  frame: number;
+  seconds: number;
+  frameRate: number;
+  duration: number | null;
+  width: number;
+  height: number;
+}
+
+/** An audio file, or audio stored in a bundle when `bundle` is given */
+export interface AudioTrack {
+  path: string;
+  bundle?: string | null;
+  /** Playback gain in dB, as recorded when the audio was imported */
+  gain?: number;
+}
+
+export interface PlaylistOptions {
+  /** Seconds each track fades into the next; none plays them gaplessly */
+  crossfade?: number;
+  loop?: boolean;
+  autoplay?: boolean;
+}
+
+/** Sent with `audio:position`, `audio:track` and `audio:ended` */
+export interface AudioStatus {
+  playing: boolean;
+  /** The track playing in the playlist, the incoming one during a crossfade */
+  index: number | null;
+  /** Seconds into it */
+  position: number;
+  duration: number | null;
+  tracks: number;
+  volume: number;
+  /** The device chosen; null for the system's default */
+  device: string | null;
+}
+
+export interface AudioDevice {
+  name: string;
+  default: boolean;
+}
+
+/** Where sound from the webviews comes from: the output windows, or the operator's preview */
+export type AudioContext = 'output' | 'preview';
+
+/** The device each context plays on, by name; null for the system's default */
+export type AudioRoutes = Record<AudioContext, string | null>;
+
+export interface RemoteOptions {
+  /** 0 to have one picked */
+  port?: number;
+  /** What remotes have to give as `?token=`; null to have one made up */
+  token?: string | null;
+  /** Listen on the local network; only this computer otherwise */
+  lan?: boolean;
+}
+
+export interface RemoteStatus {
+  running: boolean;
+  /** The port it's listening on, while running */
+  port: number | null;
+  /** What remotes have to give, while running */
+  token: string | null;
+}
+
+/** Sent with `remote:command` for the main window to carry out */
+export type RemoteCommand =
+  | { type: 'next' }
+  | { type: 'previous' }
+  | { type: 'goTo'; index: number };
+
+/** Sent with `video:looped` and `video:ended` */
+export interface VideoMark {
+  playerId: string;
+  /** The last frame shown before it */
+  frame: number;
+}
+
+export type ErrorCode =
+  | 'io'
+  | 'not-found'
+  | 'permission-denied'
+  | 'disk-full'
+  | 'invalid-bundle'
+  | 'missing-file'
+  | 'file-locked'
+  | 'bundle-locked'
+  | 'corrupted'
+  | 'ffmpeg'
+  | 'network'
+  | 'cancelled'
+  | 'database'
+  | 'import'
+  | 'missing-api-key'
+  | 'ndi'
+  | 'texture-share'
+  | 'screen-capture'
+  | 'recording'
+  | 'video'
+  | 'audio'
+  | 'remote-control'
+  | 'unknown';
+
+export interface AppError {
+  code: ErrorCode;
+  message: string;
+  details?: string | null;
+}
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/**
+ * A rejected command. `code` tells the UI which recovery to offer; `message`
+ * is safe to show as-is.
+ */
+export class CommandError extends Error {
+  readonly code: ErrorCode;
+  readonly details: string | null;
+
+  constructor(error: AppError) {
+    super(error.message);
+    this.name = 'CommandError';
+    this.code = error.code;
+    this.details = error.details ?? null;
+  }
+
+  // Callers that stringify errors show the message, as they did for plain-string rejections
+  toString(): string {
+    return this.message;
+  }
+}
+
+function isAppError(value: unknown): value is AppError {
+  return (
+    typeof value === 'object' &&
+    value !== null &&
+    typeof (value as AppError).code === 'string' &&
+    typeof (value as AppError).message === 'string'
+  );
+}
+
+/**
+ * `invoke` that rejects with a `CommandError` when the command returns an `AppError`
+ */
+async function invoke<T>(command: string, args?: Record<string, unknown>): Promise<T> {
+  try {
+    return await tauriInvoke<T>(command, args);
+  } catch (error) {
+    throw isAppError(error) ? new CommandError(error) : error;
+  }
+}
+
 // ============================================================================
 // Bundle I/O
 // ============================================================================
@@ -85,6 +535,33 @@ export async function openBundle(path: string): Promise<Presentation> {
   };
 }
 
+/**
+ * Fonts a bundle uses that are neither embedded in it nor installed, with
+ * suggested replacements; check after opening a bundle
+ */
+export async function findMissingFonts(
+  path: string
+): Promise<{ used: string[]; missing: MissingFont[] }> {
+  return invoke('cpres_missing_fonts', { path });
+}
+
+/**
+ * Install a bundle's embedded fonts (all of them unless ids are given) for
+ * the current user, or only until the app exits; web fonts are skipped
+ */
+export async function installBundleFonts(
+  path: string,
+  scope: 'user' | 'session',
+  fontIds?: string[]
+): Promise<Array<{
+  id: string;
+  family: string;
+  path?: string | null;
+  status: 'installed' | 'already-installed' | 'unsupported';
+}>> {
+  return invoke('cpres_install_fonts', { path, fontIds: fontIds ?? null, scope });
+}
+
 /**
  * Save a presentation bundle atomically
  */
@@ -109,6 +586,16 @@ export async function saveBundle(
   await invoke('cpres_save', { path, state });
 }
 
+/**
+ * Give up this app's lock on a bundle, which saving takes; call when the
+ * editor closes the file or moves on to another. 'bundle-lock:lost' carries
+ * the path of a bundle whose lock another computer took over while this one
+ * was asleep or offline
+ */
+export async function releaseBundleLock(path: string): Promise<void> {
+  await invoke('cpres_release_lock', { path });
+}
+
 /**
  * Read media from a bundle
  */
@@ -118,10 +605,15 @@ export async function readBundleMedia(bundlePath: string, mediaPath: string): Pr
 }
 
 /**
- * Import media files and compute their metadata
+ * Import media files and compute their metadata. Progress is reported through
+ * the `media:import-progress` event.
  */
-export async function importMediaFiles(paths: string[]): Promise<MediaEntry[]> {
-  const entries = await invoke<Array<{
+export async function importMediaFiles(
+  paths: string[],
+  optimize?: ImageOptimizeOptions,
+  normalize?: LoudnessOptions
+): Promise<MediaImportResult> {
+  const result = await invoke<{ entries: Array<{
     id: string;
     filename: string;
     path: string;
@@ -129,9 +621,26 @@ export async function importMediaFiles(paths: string[]): Promise<MediaEntry[]> {
     sha256: string;
     byte_size: number;
     media_type: string;
-  }>>('cpres_import_media', { paths });
+    duration: number | null;
+    width: number | null;
+    height: number | null;
+    orientation: number | null;
+    frame_rate: number | null;
+    video_codec: string | null;
+    audio_codec: string | null;
+    audio_channels: number | null;
+    loudness: number | null;
+    true_peak: number | null;
+    loudness_range: number | null;
+    gain?: number;
+    blurhash?: string;
+  }>; optimized: Record<string, OptimizedImport>; normalized: Record<string, NormalizedImport>;
+    in_library: Record<string, string[]>; skipped: SkippedFile[] }>(
+    'cpres_import_media',
+    { paths, optimize: optimize ?? null, normalize: normalize ?? null }
+  );
 
-  return entries.map(e => ({
+  const entries = result.entries.map(e => ({
     id: e.id,
     filename: e.filename,
     path: e.path,
@@ -139,7 +648,27 @@ export async function importMediaFiles(paths: string[]): Promise<MediaEntry[]> {
     sha256: e.sha256,
     byteSize: e.byte_size,
     type: e.media_type as MediaEntry['type'],
+    width: e.width ?? undefined,
+    height: e.height ?? undefined,
+    orientation: e.orientation ?? undefined,
+    duration: e.duration ?? undefined,
+    frameRate: e.frame_rate ?? undefined,
+    videoCodec: e.video_codec ?? undefined,
+    audioCodec: e.audio_codec ?? undefined,
+    audioChannels: e.audio_channels ?? undefined,
+    loudness: e.loudness ?? undefined,
+    truePeak: e.true_peak ?? undefined,
+    loudnessRange: e.loudness_range ?? undefined,
+    gain: e.gain,
+    blurhash: e.blurhash,
   }));
+  return {
+    entries,
+    optimized: result.optimized,
+    normalized: result.normalized,
+    inLibrary: result.in_library,
+    skipped: result.skipped,
+  };
 }
 
 /**
@@ -157,6 +686,14 @@ export async function importFontFiles(paths: string[]): Promise<FontEntry[]> {
     byte_size: number;
     weight: number;
     style: 'normal' | 'italic';
+    license?: {
+      copyright?: string | null;
+      license?: string | null;
+      license_url?: string | null;
+      embedding?: 'installable' | 'editable' | 'preview-and-print' | 'restricted' | null;
+      subsetting_allowed: boolean;
+    } | null;
+    embedding_warning?: string | null;
   }>>('cpres_import_fonts', { paths });
 
   return entries.map(entry => ({
@@ -170,16 +707,120 @@ export async function importFontFiles(paths: string[]): Promise<FontEntry[]> {
     byteSize: entry.byte_size,
     weight: entry.weight,
     style: entry.style,
+    license: entry.license
+      ? {
+          copyright: entry.license.copyright ?? null,
+          license: entry.license.license ?? null,
+          licenseUrl: entry.license.license_url ?? null,
+          embedding: entry.license.embedding ?? null,
+          subsettingAllowed: entry.license.subsetting_allowed,
+        }
+      : null,
+    embeddingWarning: entry.embedding_warning ?? null,
   }));
 }
 
 /**
- * List system-installed fonts
+ * List system-installed fonts; cached between runs, so the list may be stale
+ * until a 'fonts:changed' event follows
  */
 export async function listSystemFonts(): Promise<SystemFontInfo[]> {
   return invoke<SystemFontInfo[]>('cpres_list_system_fonts');
 }
 
+/**
+ * List system-installed fonts again, bypassing the cache
+ */
+export async function refreshFonts(): Promise<SystemFontInfo[]> {
+  return invoke<SystemFontInfo[]>('refresh_fonts');
+}
+
+/**
+ * Variation axes and named instances of a font file
+ */
+export async function getFontDetails(path: string, fontIndex?: number): Promise<FontDetails> {
+  return invoke<FontDetails>('get_font_details', { path, fontIndex: fontIndex ?? null });
+}
+
+/**
+ * Find the characters of some slide text that a font can't draw; the
+ * bundle's embedded font of that family is checked first
+ */
+export async function checkFontCoverage(
+  font: string,
+  text: string,
+  options?: { weight?: number; italic?: boolean; bundle?: string }
+): Promise<FontCoverage> {
+  return invoke<FontCoverage>('check_font_coverage', { font, text, options });
+}
+
+/**
+ * Draw sample text (the font's name by default) in a font, given by family
+ * or file path, as a PNG for the font picker
+ */
+export async function renderFontPreview(
+  font: string,
+  sampleText?: string,
+  size?: number,
+  options?: { weight?: number; italic?: boolean; color?: string }
+): Promise<FontPreview> {
+  return invoke<FontPreview>('render_font_preview', {
+    font,
+    sampleText: sampleText ?? null,
+    size: size ?? null,
+    options,
+  });
+}
+
+/**
+ * Whether a Google Fonts API key is set
+ */
+export async function googleFontsHasKey(): Promise<boolean> {
+  return invoke<boolean>('google_fonts_has_key');
+}
+
+/**
+ * Store the Google Fonts API key; null removes it
+ */
+export async function setGoogleFontsKey(key: string | null): Promise<void> {
+  return invoke('set_google_fonts_key', { key });
+}
+
+/**
+ * Search the Google Fonts catalog by family name, most popular first
+ */
+export async function searchGoogleFonts(
+  query: string,
+  options?: { category?: string; subset?: string; refresh?: boolean }
+): Promise<GoogleFont[]> {
+  return invoke<GoogleFont[]>('search_google_fonts', { query, options });
+}
+
+/**
+ * Google Fonts families downloaded into the app's fonts folder
+ */
+export async function listGoogleFonts(): Promise<{ family: string; paths: string[] }[]> {
+  return invoke('list_google_fonts');
+}
+
+/**
+ * Download a Google Fonts family (all styles unless given) without
+ * installing it; the fonts returned can be embedded with importFontFiles
+ */
+export async function downloadGoogleFont(
+  family: string,
+  variants?: string[]
+): Promise<SystemFontInfo[]> {
+  return invoke<SystemFontInfo[]>('download_google_font', { family, variants: variants ?? null });
+}
+
+/**
+ * Delete a downloaded Google Fonts family
+ */
+export async function removeGoogleFont(family: string): Promise<void> {
+  return invoke('remove_google_font', { family });
+}
+
 // ============================================================================
 // App Data
 // ============================================================================
@@ -297,10 +938,23 @@ export async function allowMediaLibraryDir(path: string): Promise<void> {
 // ============================================================================
 
 /**
- * Open output windows on the specified monitors
+ * Open output windows on the specified monitors, by their stable ids;
+ * monitors that aren't connected are skipped. `mirrors` maps more monitors
+ * to the output (one of `monitorIds`) each shows exactly.
  */
-export async function openOutputWindows(monitorIndices: number[]): Promise<void> {
-  await invoke('open_output_windows', { monitorIndices });
+export async function openOutputWindows(
+  monitorIds: string[],
+  mirrors: Record<string, string> = {}
+): Promise<void> {
+  await invoke('open_output_windows', { monitorIds, mirrors });
+}
+
+/**
+ * Monitor id of the output the output window calling mirrors, or null;
+ * changes arrive as `output:mirror`
+ */
+export async function getOutputMirror(): Promise<string | null> {
+  return invoke<string | null>('get_output_mirror');
 }
 
 /**
@@ -311,7 +965,491 @@ export async function closeOutputWindows(): Promise<void> {
 }
 
 /**
- * Get list of available monitors
+ * Grab what the output window `label` shows on screen and save it as a PNG
+ * at `path`. On macOS this rejects with code 'permission-denied' until the
+ * app is allowed to record the screen.
+ */
+export async function captureOutputToFile(label: string, path: string): Promise<OutputCapture> {
+  return invoke<OutputCapture>('capture_output', { label, path });
+}
+
+/**
+ * Grab what the output window `label` shows on screen as PNG data
+ */
+export async function captureOutputPng(label: string): Promise<Uint8Array> {
+  const capture = await invoke<OutputCapture>('capture_output', { label });
+  return new Uint8Array(capture.png ?? []);
+}
+
+/**
+ * Record what the output window `label`, or 'virtual' for the virtual
+ * output, shows to an MP4 at `path`. Rejects with code 'disk-full' when the
+ * disk is low; `recording:stopped` fires if it later fills, and
+ * `recording:status` every few seconds.
+ */
+export async function startRecording(
+  label: string,
+  path: string,
+  options?: RecordingOptions
+): Promise<RecordingStatus> {
+  return invoke<RecordingStatus>('start_recording', { label, path, options });
+}
+
+export async function pauseRecording(): Promise<RecordingStatus> {
+  return invoke<RecordingStatus>('pause_recording');
+}
+
+export async function resumeRecording(): Promise<RecordingStatus> {
+  return invoke<RecordingStatus>('resume_recording');
+}
+
+/**
+ * Finish the recording, joining the parts recorded between pauses
+ */
+export async function stopRecording(): Promise<RecordingStatus> {
+  return invoke<RecordingStatus>('stop_recording');
+}
+
+export async function getRecordingStatus(): Promise<RecordingStatus> {
+  return invoke<RecordingStatus>('get_recording_status');
+}
+
+/**
+ * Keep output windows loaded and hidden while the outputs are closed, so
+ * they show without the first-load delay; kept for the next launch too
+ */
+export async function setOutputPrewarm(enabled: boolean): Promise<void> {
+  await invoke('set_output_prewarm', { enabled });
+}
+
+/**
+ * Put output windows back on their monitors when those are reconnected or
+ * rearranged
+ */
+export async function setOutputAutoReopen(enabled: boolean): Promise<void> {
+  await invoke('set_output_auto_reopen', { enabled });
+}
+
+/**
+ * Put output windows back in front when they're covered, minimized or drop
+ * out of fullscreen, and optionally keep system pop-ups off them
+ */
+export async function setOutputGuard(keepInFront: boolean, blockPopups: boolean): Promise<void> {
+  await invoke('set_output_guard', { keepInFront, blockPopups });
+}
+
+/**
+ * Tell the watchdog this output window is still running; outputs that stop
+ * are reopened
+ */
+export async function reportOutputHeartbeat(): Promise<void> {
+  await invoke('report_output_heartbeat');
+}
+
+/**
+ * Keep the mouse cursor from crossing onto the output windows
+ */
+export async function setOutputPointerConfined(enabled: boolean): Promise<void> {
+  await invoke('set_output_pointer_confined', { enabled });
+}
+
+export type TestPattern = 'grid' | 'color-bars' | 'safe-areas';
+
+/**
+ * Show a test pattern on every output window, drawn at its native size,
+ * until `clearOutputOverlay`
+ */
+export async function showOutputTestPattern(pattern: TestPattern): Promise<void> {
+  await invoke('show_output_test_pattern', { pattern });
+}
+
+/**
+ * Show each output's number and monitor on it in large type, until
+ * `clearOutputOverlay`
+ */
+export async function identifyOutputs(): Promise<void> {
+  await invoke('identify_outputs');
+}
+
+export async function clearOutputOverlay(): Promise<void> {
+  await invoke('clear_output_overlay');
+}
+
+/**
+ * What the outputs are showing, kept on the Rust side so a window that
+ * reloads can pick it back up; changes arrive as `program:state`
+ */
+export async function getProgramState(): Promise<ProgramState> {
+  return invoke<ProgramState>('get_program_state');
+}
+
+/**
+ * The live presentation; changes arrive as `program:presentation`
+ */
+export async function getProgramPresentation(): Promise<ProgramPresentation> {
+  return invoke<ProgramPresentation>('get_program_presentation');
+}
+
+/**
+ * Push where the live presentation is at. Going live or to another slide
+ * also takes the outputs back to the live screen.
+ */
+export async function setLiveState(state: ProgramLiveState): Promise<ProgramState> {
+  return invoke<ProgramState>('set_live_state', { state });
+}
+
+export async function setProgramPresentation(
+  presentation: Presentation | null,
+  path: string | null
+): Promise<ProgramPresentation> {
+  return invoke<ProgramPresentation>('set_program_presentation', { presentation, path });
+}
+
+/**
+ * Black out the outputs, show the logo, clear the text, or go back to the
+ * live slide
+ */
+export async function setProgramScreen(screen: ProgramScreen): Promise<ProgramState> {
+  return invoke<ProgramState>('set_program_screen', { screen });
+}
+
+/**
+ * Set the image shown for the logo screen; rejects when the file is missing
+ */
+export async function setProgramLogo(path: string | null): Promise<ProgramState> {
+  return invoke<ProgramState>('set_program_logo', { path });
+}
+
+export async function readProgramLogo(): Promise<Uint8Array> {
+  const data = await invoke<number[]>('read_program_logo');
+  return new Uint8Array(data);
+}
+
+/**
+ * Make the outputs on the monitors in `windows` (by monitor id) windows of
+ * that size and place instead of fullscreen; the others go fullscreen
+ */
+export async function setOutputWindowed(
+  windows: Record<string, OutputWindowGeometry>
+): Promise<void> {
+  await invoke('set_output_windowed', { windows });
+}
+
+/**
+ * Letterbox, crop or scale the program on the outputs of the monitors in
+ * `transforms` (by monitor id); the others show it as it is
+ */
+export async function setOutputTransforms(
+  transforms: Record<string, OutputTransform>
+): Promise<void> {
+  await invoke('set_output_transforms', { transforms });
+}
+
+/**
+ * The transform of the output window calling; changes arrive as
+ * `output:transform`
+ */
+export async function getOutputTransform(): Promise<OutputTransform> {
+  return invoke<OutputTransform>('get_output_transform');
+}
+
+/**
+ * Make one picture across the outputs in `span`, left to right, blended
+ * where they overlap; null gives each output the whole program again
+ */
+export async function setOutputSpan(span: OutputSpan | null): Promise<void> {
+  await invoke('set_output_span', { span });
+}
+
+/**
+ * The slice of the span the output window calling shows, or null; changes
+ * arrive as `output:span`
+ */
+export async function getOutputSpan(): Promise<SpanSlice | null> {
+  return invoke<SpanSlice | null>('get_output_span');
+}
+
+/**
+ * The color correction of each output, by monitor id; outputs not listed
+ * are left as they are
+ */
+export async function getOutputColors(): Promise<Record<string, OutputColor>> {
+  return invoke<Record<string, OutputColor>>('get_output_colors');
+}
+
+/**
+ * Correct the colors of the output on a monitor, or take the correction off
+ * with null. Saved, and shown live on the output for calibration.
+ */
+export async function setOutputColor(
+  monitorId: string,
+  color: OutputColor | null
+): Promise<OutputColor> {
+  return invoke<OutputColor>('set_output_color', { monitorId, color });
+}
+
+/**
+ * The color correction of the output window calling; changes arrive as
+ * `output:color`
+ */
+export async function getOutputColor(): Promise<OutputColor> {
+  return invoke<OutputColor>('get_output_color');
+}
+
+/**
+ * Send the presentation as an NDI source, or restart it with new options.
+ * Rejects with code 'ndi' when the NDI runtime isn't installed.
+ */
+export async function startNdiOutput(options?: NdiOptions): Promise<NdiStatus> {
+  return invoke<NdiStatus>('start_ndi_output', { options });
+}
+
+export async function stopNdiOutput(): Promise<void> {
+  await invoke('stop_ndi_output');
+}
+
+export async function getNdiStatus(): Promise<NdiStatus> {
+  return invoke<NdiStatus>('get_ndi_status');
+}
+
+/**
+ * Show a slide of a saved bundle on the NDI source, drawn offscreen; black
+ * when either is null
+ */
+export async function setNdiSlide(path: string | null, slideId: string | null): Promise<void> {
+  await invoke('set_ndi_slide', { path, slideId });
+}
+
+/**
+ * Share an output as a Spout (Windows) or Syphon (macOS) texture for VJ
+ * software on this computer. Rejects with code 'texture-share' elsewhere,
+ * or when Syphon isn't installed.
+ */
+export async function enableTextureShare(
+  outputId: string,
+  options?: TextureShareOptions
+): Promise<SharedOutput> {
+  return invoke<SharedOutput>('enable_texture_share', { outputId, options });
+}
+
+export async function disableTextureShare(outputId: string): Promise<void> {
+  await invoke('disable_texture_share', { outputId });
+}
+
+export async function listTextureShares(): Promise<SharedOutput[]> {
+  return invoke<SharedOutput[]>('list_texture_shares');
+}
+
+/**
+ * Show a slide of a saved bundle on the shared textures; black when either
+ * is null
+ */
+/**
+ * DeckLink outputs ffmpeg can play to. Rejects with code 'ffmpeg' when
+ * ffmpeg was built without DeckLink support.
+ */
+export async function listDeckLinkDevices(): Promise<string[]> {
+  return invoke<string[]>('list_decklink_devices');
+}
+
+export async function listDeckLinkFormats(device: string): Promise<DeckLinkFormat[]> {
+  return invoke<DeckLinkFormat[]>('list_decklink_formats', { device });
+}
+
+/**
+ * Play the presentation out of a DeckLink card over SDI or HDMI, replacing a
+ * playout already on it. `signal` sends the fill or the key for a switcher's
+ * keyer instead of the whole slide.
+ */
+export async function startDeckLinkOutput(
+  device: string,
+  formatCode: string,
+  signal: OutputSignal = 'program'
+): Promise<DeckLinkPlayout> {
+  return invoke<DeckLinkPlayout>('start_decklink_output', { device, formatCode, signal });
+}
+
+export async function stopDeckLinkOutput(device: string): Promise<void> {
+  await invoke('stop_decklink_output', { device });
+}
+
+export async function listDeckLinkPlayouts(): Promise<DeckLinkPlayout[]> {
+  return invoke<DeckLinkPlayout[]>('list_decklink_playouts');
+}
+
+/**
+ * Play a slide of a saved bundle out of the DeckLink cards; black when
+ * either is null
+ */
+export async function setDeckLinkSlide(path: string | null, slideId: string | null): Promise<void> {
+  await invoke('set_decklink_slide', { path, slideId });
+}
+
+/**
+ * Draw the program offscreen with no window, so a service that's only
+ * streamed can be recorded from `startRecording('virtual', ...)` without a
+ * display; or restart it at a new size
+ */
+export async function startVirtualOutput(
+  options?: VirtualOutputOptions
+): Promise<VirtualOutputStatus> {
+  return invoke<VirtualOutputStatus>('start_virtual_output', { options });
+}
+
+export async function stopVirtualOutput(): Promise<void> {
+  await invoke('stop_virtual_output');
+}
+
+export async function getVirtualOutputStatus(): Promise<VirtualOutputStatus> {
+  return invoke<VirtualOutputStatus>('get_virtual_output_status');
+}
+
+/**
+ * Load a video to play natively as `playerId`, decoded by ffmpeg rather than
+ * the webview; draw its frames from `videoFrameUrl(playerId)`
+ */
+export async function loadVideo(
+  playerId: string,
+  source: VideoSource,
+  options?: PlaybackOptions
+): Promise<VideoStatus> {
+  return invoke<VideoStatus>('load_video', { playerId, source, options });
+}
+
+export async function playVideo(playerId: string): Promise<VideoStatus> {
+  return invoke<VideoStatus>('play_video', { playerId });
+}
+
+export async function pauseVideo(playerId: string): Promise<VideoStatus> {
+  return invoke<VideoStatus>('pause_video', { playerId });
+}
+
+export async function seekVideo(playerId: string, seconds: number): Promise<VideoStatus> {
+  return invoke<VideoStatus>('seek_video', { playerId, seconds });
+}
+
+export async function setVideoLoop(playerId: string, looping: boolean): Promise<VideoStatus> {
+  return invoke<VideoStatus>('set_video_loop', { playerId, looping });
+}
+
+export async function unloadVideo(playerId: string): Promise<void> {
+  await invoke('unload_video', { playerId });
+}
+
+export async function getVideoStatus(playerId: string): Promise<VideoStatus> {
+  return invoke<VideoStatus>('get_video_status', { playerId });
+}
+
+/**
+ * Where a player's frames are fetched from, as RGBA with their size in the
+ * `X-Width` and `X-Height` headers; add `?after=<X-Sequence>` to wait for
+ * the next one. No content means no new frame yet
+ */
+export function videoFrameUrl(playerId: string): string {
+  return convertFileSrc(playerId, 'cpvideo');
+}
+
+/**
+ * Load a playlist into the audio engine, which plays outside the webview on
+ * the chosen device; it starts from the first track unless `autoplay` is off
+ */
+export async function loadAudioPlaylist(
+  tracks: AudioTrack[],
+  options?: PlaylistOptions
+): Promise<AudioStatus> {
+  return invoke<AudioStatus>('load_audio_playlist', { tracks, options });
+}
+
+export async function playAudio(): Promise<AudioStatus> {
+  return invoke<AudioStatus>('play_audio');
+}
+
+export async function pauseAudio(): Promise<AudioStatus> {
+  return invoke<AudioStatus>('pause_audio');
+}
+
+export async function stopAudio(): Promise<AudioStatus> {
+  return invoke<AudioStatus>('stop_audio');
+}
+
+/** Play the track at `index` in the playlist */
+export async function skipAudio(index: number): Promise<AudioStatus> {
+  return invoke<AudioStatus>('skip_audio', { index });
+}
+
+export async function seekAudio(seconds: number): Promise<AudioStatus> {
+  return invoke<AudioStatus>('seek_audio', { seconds });
+}
+
+/** Volume of the playlist and sound cues, from 0 to 2 */
+export async function setAudioVolume(volume: number): Promise<AudioStatus> {
+  return invoke<AudioStatus>('set_audio_volume', { volume });
+}
+
+/** List the devices sound can be played on, for the audio engine and the routes */
+export async function getAudioDevices(): Promise<AudioDevice[]> {
+  return invoke<AudioDevice[]>('get_audio_devices');
+}
+
+export async function getAudioRoutes(): Promise<AudioRoutes> {
+  return invoke<AudioRoutes>('get_audio_routes');
+}
+
+/**
+ * Play the sound of `context` on the named device, or the system's default
+ * when null; windows get `audio:routes` when it changes
+ */
+export async function setAudioRoute(
+  context: AudioContext,
+  device: string | null
+): Promise<AudioRoutes> {
+  return invoke<AudioRoutes>('set_audio_route', { context, device });
+}
+
+/** Play audio on the named device, or the system's default when null */
+export async function setAudioDevice(device: string | null): Promise<AudioStatus> {
+  return invoke<AudioStatus>('set_audio_device', { device });
+}
+
+/** Play a sound once, over the playlist */
+export async function playSoundCue(track: AudioTrack): Promise<AudioStatus> {
+  return invoke<AudioStatus>('play_sound_cue', { track });
+}
+
+export async function stopSoundCues(): Promise<AudioStatus> {
+  return invoke<AudioStatus>('stop_sound_cues');
+}
+
+export async function getAudioStatus(): Promise<AudioStatus> {
+  return invoke<AudioStatus>('get_audio_status');
+}
+
+/**
+ * Serve remote control on the local network: `GET /state`, and `GET /ws` for
+ * live updates and commands; or serve it again with new options
+ */
+export async function startRemoteServer(options?: RemoteOptions): Promise<RemoteStatus> {
+  return invoke<RemoteStatus>('start_remote_server', { options });
+}
+
+export async function stopRemoteServer(): Promise<void> {
+  await invoke('stop_remote_server');
+}
+
+export async function getRemoteServerStatus(): Promise<RemoteStatus> {
+  return invoke<RemoteStatus>('get_remote_server_status');
+}
+
+export async function setTextureShareSlide(
+  path: string | null,
+  slideId: string | null
+): Promise<void> {
+  await invoke('set_texture_share_slide', { path, slideId });
+}
+
+/**
+ * Get list of available monitors; 'monitors:changed' carries the new list
+ * whenever it changes
  */
 export async function getMonitors(): Promise<MonitorInfo[]> {
   return invoke<MonitorInfo[]>('get_monitors');
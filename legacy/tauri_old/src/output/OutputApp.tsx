@@ -3,27 +3,65 @@
  * This is rendered in a separate Tauri window
  */
 
-import { useCallback, useEffect, useMemo, useState } from 'react';
+import { useCallback, useEffect, useMemo, useRef, useState } from 'react';
 import { listen } from '@tauri-apps/api/event';
 import { emit } from '@tauri-apps/api/event';
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
 import { X } from 'lucide-react';
 import { OutputStage, type OutputMediaLayer } from '@/components/output/OutputStage';
-import type { MediaLayersState, SuppressState, Presentation, Slide } from '@/lib/models';
+import { OutputTransformFrame } from '@/components/output/OutputTransformFrame';
+import { OutputSpanFrame } from '@/components/output/OutputSpanFrame';
+import { OutputColorFilter, isNeutralColor } from '@/components/output/OutputColorFilter';
+import type {
+  MediaLayersState,
+  OutputColor,
+  OutputTransform,
+  SpanSlice,
+  SuppressState,
+  Presentation,
+  ProgramPresentation,
+  ProgramState,
+} from '@/lib/models';
 import { getBackgroundMediaId, resolveSlideBackground } from '@/lib/models';
-import type { LivePresentationEvent, LiveStateEvent } from '@/lib/stores/liveStore';
+import {
+  getOutputColor,
+  getOutputMirror,
+  getOutputSpan,
+  getOutputTransform,
+  getProgramPresentation,
+  getProgramState,
+  readProgramLogo,
+  reportOutputHeartbeat,
+  type VideoSource,
+} from '@/lib/tauri-api';
 import { loadBundledFonts } from '@/lib/services/fontService';
 import { useResolvedMediaUrl } from '@/lib/media/resolveMediaUrl';
 import { useSettingsStore } from '@/lib/stores';
+import { cn } from '@/lib/utils';
+
+// How long the mouse has to be still before the cursor is hidden
+const CURSOR_IDLE_MS = 2000;
+// How often the window tells the watchdog it's still running
+const HEARTBEAT_MS = 1000;
 
 export function OutputApp() {
   const { settings } = useSettingsStore();
   const [presentation, setPresentation] = useState<Presentation | null>(null);
   const [presentationPath, setPresentationPath] = useState<string | null>(null);
-  const [currentSlide, setCurrentSlide] = useState<Slide | null>(null);
+  const [currentSlideId, setCurrentSlideId] = useState<string | null>(null);
+  const currentSlide = useMemo(
+    () => presentation?.slides.find((slide) => slide.id === currentSlideId) ?? null,
+    [presentation, currentSlideId]
+  );
   const aspectRatio = presentation?.manifest.aspectRatio;
   const outputAspectRatio = settings.output.aspectRatio;
+  const programAspect =
+    outputAspectRatio === '4:3' ? 4 / 3 : outputAspectRatio === '16:10' ? 16 / 10 : 16 / 9;
   const [isBlackout, setIsBlackout] = useState(false);
   const [isClear, setIsClear] = useState(false);
+  const [isLogo, setIsLogo] = useState(false);
+  const [logoPath, setLogoPath] = useState<string | null>(null);
+  const [logoSrc, setLogoSrc] = useState<string | null>(null);
   const [visibleLayerIds, setVisibleLayerIds] = useState<string[] | null>(null);
   const [suppress, setSuppress] = useState<SuppressState>({
     presentation: false,
@@ -33,6 +71,16 @@ export function OutputApp() {
     presentation: false,
     media: false,
   });
+  // Letterboxing, cropping and scaling for this output's display
+  const [transform, setTransform] = useState<OutputTransform | null>(null);
+  // This output's part of a picture across several projectors
+  const [spanSlice, setSpanSlice] = useState<SpanSlice | null>(null);
+  // Color correction for this output's projector
+  const [color, setColor] = useState<OutputColor | null>(null);
+  // Test pattern or identification from the Rust side, over everything
+  const [overlayImage, setOverlayImage] = useState<string | null>(null);
+  // Hidden from the start, and shown only while the mouse moves
+  const [isCursorHidden, setIsCursorHidden] = useState(true);
   const [mediaLayers, setMediaLayers] = useState<MediaLayersState>({
     mediaUnderlay: null,
     mediaOverlay: null,
@@ -41,33 +89,175 @@ export function OutputApp() {
   const isTauriApp =
     typeof window !== 'undefined' &&
     ('__TAURI_INTERNALS__' in window || '__TAURI__' in window);
+  // Output windows are labelled "output-<monitor id>"
+  const monitorId = useMemo(() => {
+    if (!isTauriApp) return null;
+    const label = getCurrentWebviewWindow().label;
+    return label.startsWith('output-') ? label.slice('output-'.length) : null;
+  }, [isTauriApp]);
+  // A mirror shows what its output does, signal included
+  const [mirrorOf, setMirrorOf] = useState<string | null>(null);
+  const feedId = mirrorOf ?? monitorId;
+  const signal = (feedId && settings.output.monitorSignals[feedId]) || 'program';
+
+  useEffect(() => {
+    if (!isTauriApp) return;
+    const unlisten = listen<string | null>('output:mirror', (event) => {
+      setMirrorOf(event.payload);
+    });
+    void getOutputMirror()
+      .then(setMirrorOf)
+      .catch((error) => {
+        console.warn('Failed to load the output mirror:', error);
+      });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [isTauriApp]);
+
+  // The program is kept on the Rust side, so a reloaded window catches up
+  // without the main window; revisions keep a slow fetch from undoing an event
+  const stateRevisionRef = useRef(0);
+  const presentationRevisionRef = useRef(0);
 
   useEffect(() => {
     if (!isTauriApp) return;
-    // Request initial state
-    emit('live:request-state');
-
-    // Listen for live state updates
-    const unlisten = listen<LiveStateEvent>('live:state', (event) => {
-      const state = event.payload;
-      
-      setIsBlackout(state.isBlackout);
-      setIsClear(state.isClear);
+    const applyState = (state: ProgramState) => {
+      if (state.revision < stateRevisionRef.current) return;
+      stateRevisionRef.current = state.revision;
+
+      setIsBlackout(state.screen === 'blackout');
+      setIsClear(state.screen === 'clear');
+      setIsLogo(state.screen === 'logo');
+      setLogoPath(state.logoPath);
+      setCurrentSlideId(state.isLive ? state.currentSlideId : null);
       setVisibleLayerIds(state.visibleLayerIds ?? null);
       setMediaLayers(state.mediaLayers);
       setSuppress(state.suppress);
       setIsClearing(state.isClearing);
+    };
+
+    const unlisten = listen<ProgramState>('program:state', (event) => {
+      applyState(event.payload);
+    });
+    void getProgramState()
+      .then(applyState)
+      .catch((error) => {
+        console.warn('Failed to load the program state:', error);
+      });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [isTauriApp]);
+
+  useEffect(() => {
+    if (!isTauriApp) return;
+    const applyPresentation = (live: ProgramPresentation) => {
+      if (live.revision < presentationRevisionRef.current) return;
+      presentationRevisionRef.current = live.revision;
+      setPresentation(live.presentation);
+      setPresentationPath(live.presentationPath);
+    };
 
-      // For now, we just track the state
-      // In a full implementation, we'd also receive the presentation data
-      // or fetch it from a shared store
+    const unlisten = listen<ProgramPresentation>('program:presentation', (event) => {
+      applyPresentation(event.payload);
     });
+    void getProgramPresentation()
+      .then(applyPresentation)
+      .catch((error) => {
+        console.warn('Failed to load the program presentation:', error);
+      });
 
     return () => {
       unlisten.then((fn) => fn());
     };
   }, [isTauriApp]);
+
+  useEffect(() => {
+    if (!isTauriApp || !logoPath) {
+      setLogoSrc(null);
+      return;
+    }
+    let cancelled = false;
+    let url: string | null = null;
+    readProgramLogo()
+      .then((data) => {
+        if (cancelled) return;
+        url = URL.createObjectURL(new Blob([data]));
+        setLogoSrc(url);
+      })
+      .catch((error) => {
+        console.warn('Failed to load the logo:', error);
+        if (!cancelled) setLogoSrc(null);
+      });
+
+    return () => {
+      cancelled = true;
+      if (url) URL.revokeObjectURL(url);
+    };
+  }, [isTauriApp, logoPath]);
   
+  useEffect(() => {
+    if (!isTauriApp) return;
+    const unlisten = listen<OutputTransform>('output:transform', (event) => {
+      setTransform(event.payload);
+    });
+    void getOutputTransform()
+      .then(setTransform)
+      .catch((error) => {
+        console.warn('Failed to load the output transform:', error);
+      });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [isTauriApp]);
+
+  useEffect(() => {
+    if (!isTauriApp) return;
+    const unlisten = listen<SpanSlice | null>('output:span', (event) => {
+      setSpanSlice(event.payload);
+    });
+    void getOutputSpan()
+      .then(setSpanSlice)
+      .catch((error) => {
+        console.warn('Failed to load the output span:', error);
+      });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [isTauriApp]);
+
+  useEffect(() => {
+    if (!isTauriApp) return;
+    const unlisten = listen<OutputColor>('output:color', (event) => {
+      setColor(event.payload);
+    });
+    void getOutputColor()
+      .then(setColor)
+      .catch((error) => {
+        console.warn('Failed to load the output color correction:', error);
+      });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [isTauriApp]);
+
+  useEffect(() => {
+    if (!isTauriApp) return;
+    const unlisten = listen<{ image: string | null }>('output:overlay', (event) => {
+      setOverlayImage(event.payload.image);
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [isTauriApp]);
+
   // Callbacks for when clearing animations complete
   // These emit events back to the main window to finish the clear
   const handleClearPresentationComplete = useCallback(() => {
@@ -83,6 +273,30 @@ export function OutputApp() {
     emit('output:disable-audience');
   }, [isTauriApp]);
 
+  useEffect(() => {
+    if (!isTauriApp) return;
+    const beat = () => {
+      void reportOutputHeartbeat().catch(() => undefined);
+    };
+    beat();
+    const interval = window.setInterval(beat, HEARTBEAT_MS);
+    return () => window.clearInterval(interval);
+  }, [isTauriApp]);
+
+  useEffect(() => {
+    let timer: number | undefined;
+    const handleMouseMove = () => {
+      setIsCursorHidden(false);
+      window.clearTimeout(timer);
+      timer = window.setTimeout(() => setIsCursorHidden(true), CURSOR_IDLE_MS);
+    };
+    window.addEventListener('mousemove', handleMouseMove);
+    return () => {
+      window.removeEventListener('mousemove', handleMouseMove);
+      window.clearTimeout(timer);
+    };
+  }, []);
+
   useEffect(() => {
     if (!isTauriApp) return;
 
@@ -136,6 +350,22 @@ export function OutputApp() {
     presentation,
     presentationPath,
   });
+  // Muted video backgrounds in a saved bundle can be decoded natively
+  // instead; the native player has no sound, so the rest stay in the webview
+  const nativeBackgroundMuted =
+    effectiveBackground?.type === 'video' && effectiveBackground.muted;
+  const nativeBackgroundSource = useMemo<VideoSource | null>(() => {
+    if (!settings.output.nativeVideo || !nativeBackgroundMuted) return null;
+    if (!presentationPath || !backgroundMediaId) return null;
+    const entry = presentation?.manifest.media?.find((media) => media.id === backgroundMediaId);
+    return entry ? { bundle: presentationPath, path: entry.path } : null;
+  }, [
+    settings.output.nativeVideo,
+    nativeBackgroundMuted,
+    presentation,
+    presentationPath,
+    backgroundMediaId,
+  ]);
 
   // Build resolved media layers with URLs
   const resolvedMediaLayers = useMemo(() => ({
@@ -148,74 +378,61 @@ export function OutputApp() {
       : null,
   }), [mediaLayers, resolvedMediaUnderlaySrc, resolvedMediaOverlaySrc]);
 
-  // Listen for presentation data updates
-  useEffect(() => {
-    if (!isTauriApp) return;
-    const unlisten = listen<LivePresentationEvent>(
-      'live:presentation',
-      (event) => {
-        const { presentation, slideId } = event.payload;
-        setPresentation(presentation);
-        setPresentationPath(event.payload.presentationPath ?? null);
-
-        if (!presentation || !slideId) {
-          setCurrentSlide(null);
-          return;
-        }
-
-        const slide = presentation.slides.find((s) => s.id === slideId);
-        setCurrentSlide(slide || null);
-
-      }
-    );
-
-    return () => {
-      unlisten.then((fn) => fn());
-    };
-  }, [isTauriApp]);
-
   useEffect(() => {
     void loadBundledFonts(presentation, presentationPath);
   }, [presentation, presentationPath]);
 
-  // Listen for slide changes
-  useEffect(() => {
-    if (!isTauriApp) return;
-    const unlisten = listen<string | null>('live:slide', (event) => {
-      if (presentation && event.payload) {
-        const slide = presentation.slides.find((s) => s.id === event.payload);
-        setCurrentSlide(slide || null);
-      } else if (!event.payload) {
-        setCurrentSlide(null);
-      }
-    });
-
-    return () => {
-      unlisten.then((fn) => fn());
-    };
-  }, [isTauriApp, presentation]);
+  const colorCorrected = !isNeutralColor(color);
+  const stage = (
+    <OutputStage
+      slide={currentSlide}
+      aspectRatio={aspectRatio}
+      outputAspectRatio={outputAspectRatio}
+      slideSize={presentation?.manifest.slideSize}
+      isBlackout={isBlackout}
+      isClear={isClear}
+      isLogo={isLogo}
+      logoSrc={logoSrc}
+      visibleLayerIds={effectiveVisibleLayerIds}
+      mediaLayers={resolvedMediaLayers}
+      suppress={suppress}
+      isClearing={isClearing}
+      onClearPresentationComplete={handleClearPresentationComplete}
+      onClearMediaComplete={handleClearMediaComplete}
+      resolvedBackgroundSrc={resolvedBackgroundSrc}
+      nativeBackgroundSource={nativeBackgroundSource}
+      signal={signal}
+      className="h-full w-full"
+    />
+  );
 
   return (
     <div
-      className="group relative h-screen w-screen bg-black overflow-hidden select-none"
+      className={cn(
+        'group relative h-screen w-screen bg-black overflow-hidden select-none',
+        isCursorHidden && 'cursor-none'
+      )}
       onContextMenu={(event) => event.preventDefault()}
+      style={colorCorrected ? { filter: 'url(#output-color)' } : undefined}
     >
-      <OutputStage
-        slide={currentSlide}
-        aspectRatio={aspectRatio}
-        outputAspectRatio={outputAspectRatio}
-        slideSize={presentation?.manifest.slideSize}
-        isBlackout={isBlackout}
-        isClear={isClear}
-        visibleLayerIds={effectiveVisibleLayerIds}
-        mediaLayers={resolvedMediaLayers}
-        suppress={suppress}
-        isClearing={isClearing}
-        onClearPresentationComplete={handleClearPresentationComplete}
-        onClearMediaComplete={handleClearMediaComplete}
-        resolvedBackgroundSrc={resolvedBackgroundSrc}
-        className="h-full w-full"
-      />
+      {color && colorCorrected && <OutputColorFilter id="output-color" color={color} />}
+      {spanSlice ? (
+        <OutputSpanFrame slice={spanSlice} programAspect={programAspect}>
+          {stage}
+        </OutputSpanFrame>
+      ) : (
+        <OutputTransformFrame transform={transform} programAspect={programAspect}>
+          {stage}
+        </OutputTransformFrame>
+      )}
+      {overlayImage && (
+        <img
+          src={overlayImage}
+          alt=""
+          className="absolute inset-0 z-40 h-full w-full object-fill"
+          draggable={false}
+        />
+      )}
       <button
         type="button"
         aria-label="Disable audience output"